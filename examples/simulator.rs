@@ -0,0 +1,445 @@
+//! Simulate a network of miners to see how the difficulty control and the
+//! fork choice rule behave under varying hash rates, propagation latencies and
+//! adversarial mining.
+//!
+//! Blocks are not actually mined. Instead, the time until a miner finds a
+//! block is sampled from an exponential distribution with mean
+//! `difficulty / hash_rate`, and new difficulties and fork choices are
+//! computed by the node's own code. The canonical chain is written to stdout
+//! as CSV, and a summary is written to stderr.
+//!
+//! Example:
+//!
+//! ```text
+//! cargo run --release --example simulator -- \
+//!     --hash-rates 10,10,10,15 --adversaries 3 --latency-ms 5000 --blocks 2000 > chain.csv
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use clap::Parser;
+use neptune_core::config_models::network::Network;
+use neptune_core::models::blockchain::block::block_body::BlockBody;
+use neptune_core::models::blockchain::block::block_header::{BlockHeader, TARGET_BLOCK_INTERVAL};
+use neptune_core::models::blockchain::block::block_height::BlockHeight;
+use neptune_core::models::blockchain::block::Block;
+use neptune_core::models::consensus::timestamp::Timestamp;
+use neptune_core::prelude::twenty_first::amount::u32s::U32s;
+use num_bigint::BigUint;
+use num_traits::ToPrimitive;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+#[derive(Debug, Parser)]
+#[clap(name = "simulator", about = "Simulate difficulty control and forks")]
+struct Args {
+    /// Hash rate of each miner, in hashes per second
+    #[clap(long, value_delimiter = ',', default_value = "10,10,10")]
+    hash_rates: Vec<f64>,
+
+    /// Indices of miners that mine selfishly, withholding blocks to orphan
+    /// those of honest miners
+    #[clap(long, value_delimiter = ',')]
+    adversaries: Vec<usize>,
+
+    /// Time for a block to reach other miners
+    #[clap(long, default_value = "2000")]
+    latency_ms: u64,
+
+    /// Max additional, uniformly random, propagation delay
+    #[clap(long, default_value = "1000")]
+    latency_jitter_ms: u64,
+
+    /// Stop once the heaviest chain is this long
+    #[clap(long, default_value = "1000")]
+    blocks: u64,
+
+    /// Difficulty of the genesis block. Defaults to the difficulty at which
+    /// the total hash rate hits the target block interval.
+    #[clap(long)]
+    initial_difficulty: Option<u64>,
+
+    /// Seed for the random number generator, for reproducible runs
+    #[clap(long, default_value = "0")]
+    seed: u64,
+}
+
+type Difficulty = U32s<5>;
+
+struct SimulatedBlock {
+    parent: Option<usize>,
+    height: u64,
+    timestamp: Timestamp,
+    difficulty: Difficulty,
+    proof_of_work_family: Difficulty,
+    miner: usize,
+}
+
+struct Miner {
+    hash_rate: f64,
+    adversarial: bool,
+
+    /// The block this miner mines on. For adversaries, the private tip.
+    tip: usize,
+
+    /// Adversaries only: the highest block of their own they have published
+    published: usize,
+
+    /// Bumped whenever the miner starts on a new block, so that block finds
+    /// sampled for the previous tip can be discarded
+    epoch: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    BlockFound { miner: usize, epoch: u64 },
+    BlockArrives { miner: usize, block: usize },
+}
+
+struct Simulation {
+    args: Args,
+    rng: StdRng,
+    now_millis: u64,
+    events: BinaryHeap<Reverse<(u64, u64, Event)>>,
+    event_count: u64,
+    blocks: Vec<SimulatedBlock>,
+    miners: Vec<Miner>,
+
+    /// Body for the blocks passed to the difficulty control, which operates
+    /// on full blocks
+    scratch_body: BlockBody,
+    scratch_header: BlockHeader,
+
+    reorg_count: u64,
+    max_reorg_depth: u64,
+}
+
+impl Simulation {
+    fn new(args: Args) -> Self {
+        let genesis_block = Block::genesis_block(Network::RegTest);
+        let total_hash_rate: f64 = args.hash_rates.iter().sum();
+        let initial_difficulty = args.initial_difficulty.unwrap_or_else(|| {
+            (total_hash_rate * TARGET_BLOCK_INTERVAL as f64 / 1000.0).max(2.0) as u64
+        });
+
+        let genesis = SimulatedBlock {
+            parent: None,
+            height: 0,
+            timestamp: genesis_block.header().timestamp,
+            difficulty: difficulty_from_u64(initial_difficulty),
+            proof_of_work_family: genesis_block.header().proof_of_work_family,
+            miner: usize::MAX,
+        };
+        let miners = args
+            .hash_rates
+            .iter()
+            .enumerate()
+            .map(|(i, hash_rate)| Miner {
+                hash_rate: *hash_rate,
+                adversarial: args.adversaries.contains(&i),
+                tip: 0,
+                published: 0,
+                epoch: 0,
+            })
+            .collect();
+
+        Self {
+            rng: StdRng::seed_from_u64(args.seed),
+            args,
+            now_millis: 0,
+            events: BinaryHeap::new(),
+            event_count: 0,
+            blocks: vec![genesis],
+            miners,
+            scratch_body: genesis_block.body().clone(),
+            scratch_header: genesis_block.header().clone(),
+            reorg_count: 0,
+            max_reorg_depth: 0,
+        }
+    }
+
+    fn schedule(&mut self, delay_millis: u64, event: Event) {
+        self.event_count += 1;
+        self.events.push(Reverse((
+            self.now_millis + delay_millis,
+            self.event_count,
+            event,
+        )));
+    }
+
+    /// Start mining on the miner's tip, discarding any block find sampled
+    /// for a previous tip
+    fn start_mining(&mut self, miner: usize) {
+        self.miners[miner].epoch += 1;
+        let hash_rate = self.miners[miner].hash_rate;
+        if hash_rate <= 0.0 {
+            return;
+        }
+
+        let difficulty = to_f64(self.blocks[self.miners[miner].tip].difficulty);
+        let mean_millis = difficulty / hash_rate * 1000.0;
+        let uniform: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        let delay_millis = (-uniform.ln() * mean_millis) as u64;
+        let epoch = self.miners[miner].epoch;
+        self.schedule(delay_millis, Event::BlockFound { miner, epoch });
+    }
+
+    fn broadcast(&mut self, sender: usize, block: usize) {
+        for miner in (0..self.miners.len()).filter(|m| *m != sender) {
+            let jitter = self.rng.gen_range(0..=self.args.latency_jitter_ms);
+            self.schedule(
+                self.args.latency_ms + jitter,
+                Event::BlockArrives { miner, block },
+            );
+        }
+    }
+
+    /// Build a block on top of `parent`, the way the miner does
+    fn new_block(&mut self, parent: usize, miner: usize) -> usize {
+        let parent_block = &self.blocks[parent];
+        let timestamp = self.blocks[0].timestamp + Timestamp::millis(self.now_millis);
+        let mut parent_header = self.scratch_header.clone();
+        parent_header.height = BlockHeight::from(parent_block.height);
+        parent_header.timestamp = parent_block.timestamp;
+        parent_header.difficulty = parent_block.difficulty;
+        let scratch = Block::new(
+            parent_header,
+            self.scratch_body.clone(),
+            Block::mk_std_block_type(None),
+        );
+
+        let block = SimulatedBlock {
+            parent: Some(parent),
+            height: parent_block.height + 1,
+            timestamp,
+            difficulty: Block::difficulty_control(&scratch, timestamp),
+            proof_of_work_family: parent_block.proof_of_work_family + parent_block.difficulty,
+            miner,
+        };
+        self.blocks.push(block);
+        self.blocks.len() - 1
+    }
+
+    fn is_heavier(&self, candidate: usize, tip: usize) -> bool {
+        self.blocks[candidate].proof_of_work_family > self.blocks[tip].proof_of_work_family
+    }
+
+    fn common_ancestor(&self, mut a: usize, mut b: usize) -> usize {
+        while a != b {
+            if self.blocks[a].height >= self.blocks[b].height {
+                a = self.blocks[a].parent.unwrap();
+            } else {
+                b = self.blocks[b].parent.unwrap();
+            }
+        }
+        a
+    }
+
+    /// Switch an honest miner to a heavier tip, recording any reorganization
+    fn switch_tip(&mut self, miner: usize, new_tip: usize) {
+        let old_tip = self.miners[miner].tip;
+        let ancestor = self.common_ancestor(old_tip, new_tip);
+        if ancestor != old_tip {
+            let depth = self.blocks[old_tip].height - self.blocks[ancestor].height;
+            self.reorg_count += 1;
+            self.max_reorg_depth = self.max_reorg_depth.max(depth);
+        }
+
+        self.miners[miner].tip = new_tip;
+        self.start_mining(miner);
+    }
+
+    /// Adversaries publish the withheld blocks of their private chain up to
+    /// the given height
+    fn publish_up_to(&mut self, miner: usize, height: u64) {
+        let mut to_publish = vec![];
+        let mut block = self.miners[miner].tip;
+        while block != self.miners[miner].published && self.blocks[block].height > 0 {
+            if self.blocks[block].height <= height && self.blocks[block].miner == miner {
+                to_publish.push(block);
+            }
+            block = self.blocks[block].parent.unwrap();
+        }
+
+        if let Some(highest) = to_publish.first() {
+            self.miners[miner].published = *highest;
+        }
+        for block in to_publish.into_iter().rev() {
+            self.broadcast(miner, block);
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::BlockFound { miner, epoch } => {
+                if epoch != self.miners[miner].epoch {
+                    return;
+                }
+
+                let block = self.new_block(self.miners[miner].tip, miner);
+                self.miners[miner].tip = block;
+                if !self.miners[miner].adversarial {
+                    self.broadcast(miner, block);
+                }
+                self.start_mining(miner);
+            }
+            Event::BlockArrives { miner, block } => {
+                let tip = self.miners[miner].tip;
+                if !self.miners[miner].adversarial {
+                    if self.is_heavier(block, tip) {
+                        self.switch_tip(miner, block);
+                    }
+                    return;
+                }
+
+                // Selfish mining, after Eyal and Sirer: give up the private
+                // chain once the public one is heavier, publish all of it
+                // when the lead shrinks to one block, and otherwise publish
+                // just enough to compete with the public chain.
+                if self.blocks[block].miner == miner {
+                    return;
+                }
+                if self.is_heavier(block, tip) {
+                    self.miners[miner].tip = block;
+                    self.miners[miner].published = block;
+                    self.start_mining(miner);
+                    return;
+                }
+                let height = self.blocks[block].height;
+                let lead = self.blocks[tip].height.saturating_sub(height);
+                if lead <= 1 {
+                    self.publish_up_to(miner, u64::MAX);
+                } else {
+                    self.publish_up_to(miner, height);
+                }
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        for miner in 0..self.miners.len() {
+            self.start_mining(miner);
+        }
+
+        while self.heaviest_tip().map(|tip| self.blocks[tip].height) < Some(self.args.blocks) {
+            let Some(Reverse((time, _, event))) = self.events.pop() else {
+                break;
+            };
+            self.now_millis = time;
+            self.handle(event);
+        }
+    }
+
+    /// The heaviest tip among honest miners, or of all miners if all are
+    /// adversarial
+    fn heaviest_tip(&self) -> Option<usize> {
+        let honest_tips = self
+            .miners
+            .iter()
+            .filter(|miner| !miner.adversarial)
+            .map(|miner| miner.tip)
+            .collect::<Vec<_>>();
+        let tips = if honest_tips.is_empty() {
+            self.miners.iter().map(|miner| miner.tip).collect()
+        } else {
+            honest_tips
+        };
+
+        tips.into_iter()
+            .max_by_key(|tip| self.blocks[*tip].proof_of_work_family)
+    }
+
+    fn canonical_chain(&self) -> Vec<usize> {
+        let mut chain = vec![];
+        let mut block = self.heaviest_tip();
+        while let Some(b) = block {
+            chain.push(b);
+            block = self.blocks[b].parent;
+        }
+        chain.reverse();
+        chain
+    }
+
+    fn write_csv(&self, chain: &[usize]) {
+        println!("height,timestamp_ms,interval_ms,difficulty,miner,adversarial");
+        let genesis_time = self.blocks[0].timestamp;
+        for pair in chain.windows(2) {
+            let (parent, block) = (&self.blocks[pair[0]], &self.blocks[pair[1]]);
+            println!(
+                "{},{},{},{},{},{}",
+                block.height,
+                millis(block.timestamp - genesis_time),
+                millis(block.timestamp - parent.timestamp),
+                BigUint::from(block.difficulty),
+                block.miner,
+                self.miners[block.miner].adversarial,
+            );
+        }
+    }
+
+    fn write_summary(&self, chain: &[usize]) {
+        let mined = self.blocks.len() as u64 - 1;
+        let canonical = chain.len() as u64 - 1;
+        let stale = mined - canonical;
+        let duration =
+            millis(self.blocks[*chain.last().unwrap()].timestamp - self.blocks[0].timestamp);
+        let total_hash_rate: f64 = self.miners.iter().map(|miner| miner.hash_rate).sum();
+
+        eprintln!("blocks mined:       {mined}");
+        eprintln!("canonical blocks:   {canonical}");
+        eprintln!(
+            "stale blocks:       {stale} ({:.2}%)",
+            100.0 * stale as f64 / mined.max(1) as f64
+        );
+        eprintln!("reorganizations:    {}", self.reorg_count);
+        eprintln!("max reorg depth:    {}", self.max_reorg_depth);
+        eprintln!(
+            "mean interval:      {:.0} ms (target {TARGET_BLOCK_INTERVAL} ms)",
+            duration as f64 / canonical.max(1) as f64
+        );
+        for (i, miner) in self.miners.iter().enumerate() {
+            let share = chain[1..]
+                .iter()
+                .filter(|block| self.blocks[**block].miner == i)
+                .count();
+            eprintln!(
+                "miner {i}{}: {:.1}% of hash rate, {:.1}% of canonical blocks",
+                if miner.adversarial {
+                    " (adversarial)"
+                } else {
+                    ""
+                },
+                100.0 * miner.hash_rate / total_hash_rate,
+                100.0 * share as f64 / canonical.max(1) as f64
+            );
+        }
+    }
+}
+
+fn difficulty_from_u64(value: u64) -> Difficulty {
+    U32s::new([value as u32, (value >> 32) as u32, 0, 0, 0])
+}
+
+fn to_f64(difficulty: Difficulty) -> f64 {
+    BigUint::from(difficulty).to_f64().unwrap()
+}
+
+fn millis(timestamp: Timestamp) -> u64 {
+    timestamp.0.value()
+}
+
+fn main() {
+    let args = Args::parse();
+    assert!(
+        args.adversaries.iter().all(|i| *i < args.hash_rates.len()),
+        "adversary index out of range"
+    );
+
+    let mut simulation = Simulation::new(args);
+    simulation.run();
+
+    let chain = simulation.canonical_chain();
+    simulation.write_csv(&chain);
+    simulation.write_summary(&chain);
+}
@@ -147,7 +147,7 @@ impl HistoryScreen {
         loop {
             select! {
                 _ = &mut balance_history => {
-                    let bh = rpc_client.history(context::current()).await.unwrap();
+                    let bh = rpc_client.history(context::current()).await.unwrap().unwrap_or_default();
                     let mut history_builder = Vec::with_capacity(bh.len());
                     let mut balance = NeptuneCoins::zero();
                     for (_, block_height, timestamp, amount) in bh.iter() {
@@ -1,4 +1,5 @@
 use neptune_core::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use neptune_core::models::consensus::timestamp::Timestamp;
 use neptune_core::prelude::twenty_first;
 
 use std::net::SocketAddr;
@@ -35,6 +36,7 @@ pub struct OverviewData {
     timelocked_balance: Option<NeptuneCoins>,
     confirmations: Option<BlockHeight>,
     synchronization_percentage: Option<f64>,
+    last_wallet_backup: Option<Timestamp>,
 
     network: Network,
     syncing: bool,
@@ -72,6 +74,7 @@ impl OverviewData {
             timelocked_balance: Default::default(),
             confirmations: Default::default(),
             synchronization_percentage: Default::default(),
+            last_wallet_backup: Default::default(),
             network,
             syncing: Default::default(),
             is_mining: Default::default(),
@@ -101,6 +104,7 @@ impl OverviewData {
             timelocked_balance: Some(NeptuneCoins::zero()),
             confirmations: Some(17.into()),
             synchronization_percentage: Some(99.5),
+            last_wallet_backup: None,
 
             listen_address: None,
             network: Network::Testnet,
@@ -218,6 +222,7 @@ impl OverviewScreen {
                                 own_overview_data.is_mining = resp.is_mining;
                                 own_overview_data.confirmations = resp.confirmations;
                                 own_overview_data.cpu_temperature = resp.cpu_temp;
+                                own_overview_data.last_wallet_backup = resp.last_wallet_backup;
                             }
 
                             *escalatable_event.lock().unwrap() = Some(DashboardEvent::RefreshScreen);
@@ -389,6 +394,12 @@ impl Widget for OverviewScreen {
                 None => "-".to_string(),
             }
         ));
+        lines.push(format!(
+            "last backup: {}",
+            dashifnotset!(data
+                .last_wallet_backup
+                .map(|timestamp| timestamp.standard_format())),
+        ));
         Self::report(&lines, "Wallet")
             .style(style)
             .render(vrecter.next(2 + lines.len() as u16), buf);
@@ -68,7 +68,10 @@ impl ReceiveScreen {
                     .own_receiving_address(context::current())
                     .await
                     .unwrap();
-                *data.lock().unwrap() = Some(receiving_address.to_bech32m(network).unwrap());
+                *data.lock().unwrap() = Some(match receiving_address {
+                    Ok(address) => address.to_bech32m(network).unwrap(),
+                    Err(err) => err.to_string(),
+                });
                 *escalatable_event.lock().unwrap() = Some(DashboardEvent::RefreshScreen);
             });
         }
@@ -88,7 +91,10 @@ impl ReceiveScreen {
                 .own_receiving_address(context::current())
                 .await
                 .unwrap();
-            *data.lock().unwrap() = Some(receiving_address.to_bech32m(network).unwrap());
+            *data.lock().unwrap() = Some(match receiving_address {
+                Ok(address) => address.to_bech32m(network).unwrap(),
+                Err(err) => err.to_string(),
+            });
             *generating.lock().unwrap() = false;
             *escalatable_event.lock().unwrap() = Some(DashboardEvent::RefreshScreen);
         });
@@ -113,10 +113,17 @@ impl SendScreen {
 
         *notice_arc.lock().await = "Validated amount; checking against balance ...".to_string();
 
-        let enough_balance = rpc_client
+        let enough_balance = match rpc_client
             .amount_leq_synced_balance(context::current(), valid_amount)
             .await
-            .unwrap();
+            .unwrap()
+        {
+            Ok(enough_balance) => enough_balance,
+            Err(err) => {
+                *notice_arc.lock().await = err.to_string();
+                return;
+            }
+        };
         if !enough_balance {
             *notice_arc.lock().await = "Insufficient balance.".to_string();
             *focus_arc.lock().await = SendScreenWidget::Amount;
@@ -133,12 +140,12 @@ impl SendScreen {
         const SEND_DEADLINE_IN_SECONDS: u64 = 40;
         send_ctx.deadline = SystemTime::now() + Duration::from_secs(SEND_DEADLINE_IN_SECONDS);
         let send_result = rpc_client
-            .send(send_ctx, valid_amount, valid_address, fee)
+            .send(send_ctx, valid_amount, valid_address, fee, None, None)
             .await
             .unwrap();
 
-        if send_result.is_none() {
-            *notice_arc.lock().await = "Could not send due to error.".to_string();
+        if let Err(send_failure) = send_result {
+            *notice_arc.lock().await = format!("Could not send: {send_failure}");
             *focus_arc.lock().await = SendScreenWidget::Address;
             return;
         }
@@ -1,5 +1,8 @@
+use neptune_core::models::blockchain::consensus_encoding::{decode_hex, encode_hex};
 use neptune_core::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
 use neptune_core::models::state::wallet::coin_with_possible_timelock::CoinWithPossibleTimeLock;
+use neptune_core::models::state::wallet::monitored_utxo::UtxoOrigin;
+use neptune_core::models::state::wallet::shared_control::SharedSpend;
 
 use anyhow::{bail, Result};
 use clap::{CommandFactory, Parser};
@@ -7,16 +10,21 @@ use clap_complete::{generate, Shell};
 
 use neptune_core::config_models::data_directory::DataDirectory;
 use neptune_core::config_models::network::Network;
+use neptune_core::models::consensus::timestamp::Timestamp;
+use neptune_core::models::state::alerts::{Alert, AlertSecretKey, AlertSeverity, SignedAlert};
+use neptune_core::models::state::proving_queue::{ProvingJobId, ProvingJobStatus};
 use neptune_core::models::state::wallet::address::generation_address;
 use neptune_core::models::state::wallet::WalletSecret;
 use std::io;
 use std::io::Write;
 use std::net::IpAddr;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use tarpc::{client, context, tokio_serde::formats::Json};
 
 use neptune_core::models::blockchain::block::block_selector::BlockSelector;
 use neptune_core::models::state::wallet::wallet_status::WalletStatus;
+use neptune_core::prelude::twenty_first::math::digest::Digest;
 use neptune_core::rpc_server::RPCClient;
 use std::io::stdout;
 
@@ -42,16 +50,147 @@ enum Command {
         n: usize,
     },
     TipHeader,
+    /// List the digests of the blocks mined in a time range
+    BlocksInTimeRange {
+        /// start of the range, in milliseconds since the Unix epoch
+        from: u64,
+        /// end of the range, inclusive, in milliseconds since the Unix epoch
+        to: u64,
+    },
     Header {
         /// one of: genesis, tip, height/<n>, digest/<hex>
         block_selector: BlockSelector,
     },
     SyncedBalance,
+    /// Show the sum of unspent UTXOs that are time-locked, and cannot be spent yet
+    TimelockedBalance,
+    /// Show the confirmed balance, and the balance with the mempool's
+    /// transactions applied
+    Balance,
+    BalanceAt {
+        /// block height; requires the node to run with --historical-index
+        height: u64,
+    },
+    /// Export the wallet's changes since a sequence number, encrypted for a
+    /// companion wallet with the same seed
+    WalletDelta {
+        /// sequence number of the last delta the companion wallet applied; 0 for all
+        #[clap(long, default_value = "0")]
+        since: u64,
+
+        /// file to write the encrypted delta to
+        out: PathBuf,
+    },
     WalletStatus,
     OwnReceivingAddress,
     ListCoins,
+    ListUtxos,
+    /// List the changes to the balance, with the origin of each UTXO
+    TransactionHistory {
+        /// only show UTXOs of this origin
+        #[clap(long)]
+        kind: Option<UtxoOrigin>,
+    },
+    /// List transactions sent since startup, and whether they were double spent
+    SentTransactions,
+    AddressBook,
     MempoolTxCount,
     MempoolSize,
+    /// Show the fee, size and age of a transaction in the mempool
+    MempoolEntry {
+        /// transaction digest, as hex
+        digest: String,
+    },
+    /// Print the canonical encoding of a stored block, as hex
+    BlockHex {
+        /// block digest, as hex
+        digest: String,
+    },
+    /// Print the canonical encoding of a mempool transaction, as hex
+    TransactionHex {
+        /// transaction digest, as hex
+        digest: String,
+    },
+    /// Decode a block from a file holding its canonical encoding as hex
+    DecodeBlock {
+        hex_file: PathBuf,
+    },
+    /// Decode a transaction from a file holding its canonical encoding as hex
+    DecodeTransaction {
+        hex_file: PathBuf,
+    },
+
+    /// watch a transaction in the mempool until it has the given number of
+    /// confirmations; see `--on-watched-transaction`
+    WatchTransaction {
+        /// transaction digest, as hex
+        digest: String,
+        confirmations: u64,
+    },
+    JournalEvents {
+        /// first sequence number to return
+        #[clap(default_value_t = 0)]
+        since: u64,
+    },
+    BlockTimelines {
+        /// number of recent blocks to show
+        #[clap(default_value_t = 10)]
+        n: usize,
+    },
+    BlockPropagation {
+        /// number of recently announced blocks to show
+        #[clap(default_value_t = 10)]
+        n: usize,
+    },
+    /// Export the recent blocks, forks included, as JSON, or as DOT for graphviz
+    BlockTree {
+        /// number of heights below the tip to include
+        #[clap(default_value_t = 20)]
+        depth: u64,
+
+        /// print in the DOT language, e.g. for `dot -Tsvg`
+        #[clap(long)]
+        dot: bool,
+    },
+    /// List the tips of recent forks, the heaviest first
+    ListForks,
+    Health,
+    /// Show handshake failures, connection durations and traffic by message
+    /// type, per direction and per connected peer
+    ConnectionMetrics,
+    ActiveWindowStats,
+    MemoryStatus,
+    /// Show the tip, sync progress, peers, mempool, recent blocks and balance
+    DashboardSnapshot,
+    /// Show the disk used by the data directory
+    DiskUsage,
+    /// Show the tip height, difficulty, peers, mempool and disk usage over time
+    MetricsHistory {
+        /// number of hours back to show
+        #[clap(default_value_t = 24)]
+        hours: u64,
+    },
+    AuditSupply,
+    /// Show network alerts that have not expired
+    Alerts,
+    /// Show queued, running and recently finished proving jobs
+    ProvingJobs,
+    /// Show the last block marked final
+    FinalizedBlock,
+    /// Show the cosigner key with which the wallet takes part in a
+    /// shared-control lock, to hand to the coordinator of the lock
+    SharedControlKey {
+        /// use a new index for every lock
+        #[clap(default_value_t = 0)]
+        index: u16,
+    },
+    /// Show the lock that `threshold` of the given cosigners must unlock
+    /// together, bech32m-encoded
+    SharedControlLock {
+        threshold: usize,
+        /// cosigner keys, as hex
+        cosigner_keys: Vec<String>,
+    },
 
     /******** CHANGE STATE ********/
     Shutdown,
@@ -59,15 +198,132 @@ enum Command {
     ClearStandingByIp {
         ip: IpAddr,
     },
+    /// Mark a block, and thereby its ancestors, final so that no reorg can roll
+    /// it back
+    MarkBlockFinal {
+        /// block digest, as hex
+        digest: String,
+    },
     Send {
         amount: NeptuneCoins,
+        /// bech32m-encoded address, or the name of an address book entry
         address: String,
         fee: NeptuneCoins,
+
+        /// Time-lock the coins: the recipient cannot spend them before this
+        /// date, in milliseconds since the Unix epoch
+        #[clap(long)]
+        release_date: Option<u64>,
+
+        /// Resubmitting a request with the same key does not send again
+        #[clap(long)]
+        idempotency_key: Option<String>,
+    },
+    /// Select inputs for a send and show what it will do, without sending
+    PrepareSend {
+        amount: NeptuneCoins,
+        /// bech32m-encoded address, or the name of an address book entry
+        address: String,
+        fee: NeptuneCoins,
+
+        /// Time-lock the coins: the recipient cannot spend them before this
+        /// date, in milliseconds since the Unix epoch
+        #[clap(long)]
+        release_date: Option<u64>,
+    },
+    /// Send a prepared send
+    ConfirmSend {
+        /// token returned by prepare-send, as hex
+        token: String,
+
+        /// Resubmitting a request with the same key does not send again
+        #[clap(long)]
+        idempotency_key: Option<String>,
+    },
+    /// Save a named address in the address book
+    SaveAddress {
+        name: String,
+        address: String,
+        #[clap(long, default_value = "")]
+        note: String,
+    },
+    RemoveAddress {
+        name: String,
+    },
+    /// Relay a signed alert, as written by sign-alert, to all peers
+    BroadcastAlert {
+        alert_file: PathBuf,
+    },
+    /// Cancel a queued or running proving job
+    CancelProvingJob {
+        id: u64,
+    },
+    /// Pin the node to a competing tip with as much proof-of-work as the tip
+    PreferTip {
+        /// digest of the block, in hex
+        digest: String,
     },
     PauseMiner,
     RestartMiner,
     PruneAbandonedMonitoredUtxos,
 
+    /// rescan the chain from the given height for UTXOs announced to the
+    /// wallet, e.g. after restoring it from its seed phrase
+    RescanWallet {
+        #[clap(default_value_t = 0)]
+        from_height: u64,
+    },
+
+    /// show the progress of the running, or the last, wallet rescan
+    RescanStatus,
+
+    /// stop the running wallet rescan, keeping the UTXOs found so far
+    CancelRescan,
+
+    /// send all funds of another wallet, e.g. a paper wallet, to this wallet.
+    /// The seed phrase of the other wallet is read from stdin.
+    SweepKey {
+        fee: NeptuneCoins,
+    },
+
+    /// embed a short text in the chain, in a transaction that pays only the fee
+    SendData {
+        text: String,
+        fee: NeptuneCoins,
+    },
+
+    /// sign a shared spend, in a file holding its encoding as hex, with the
+    /// wallet's shared-control unlock key, and write it back for the next
+    /// cosigner
+    SignSharedSpend {
+        spend_file: PathBuf,
+        #[clap(long, default_value_t = 0)]
+        index: u16,
+    },
+
+    /// broadcast the transaction of a shared spend that enough cosigners signed
+    BroadcastSharedSpend {
+        spend_file: PathBuf,
+    },
+
+    /******** ALERT SIGNING ********/
+    /// Generate a key for signing network alerts, and print its public key
+    GenerateAlertKey {
+        secret_key_file: PathBuf,
+    },
+    /// Sign a network alert for the network given by --network, and print it
+    /// as JSON
+    SignAlert {
+        secret_key_file: PathBuf,
+        /// distinguishes alerts signed with the same key
+        id: u64,
+        severity: AlertSeverity,
+        message: String,
+        /// days until the alert expires
+        #[clap(long, default_value_t = 7)]
+        days: usize,
+    },
+
     /******** WALLET ********/
     GenerateWallet {
         #[clap(long, default_value_t=Network::default())]
@@ -175,31 +431,7 @@ async fn main() -> Result<()> {
 
             // read seed phrase from user input
             println!("Importing seed phrase. Please enter words:");
-            let mut phrase = vec![];
-            let mut i = 1;
-            loop {
-                print!("{}. ", i);
-                io::stdout().flush()?;
-                let mut buffer = "".to_string();
-                std::io::stdin()
-                    .read_line(&mut buffer)
-                    .expect("Cannot accept user input.");
-                let word = buffer.trim();
-                if bip39::Language::English
-                    .wordlist()
-                    .get_words_by_prefix("")
-                    .iter()
-                    .any(|s| *s == word)
-                {
-                    phrase.push(word.to_string());
-                    i += 1;
-                    if i > 18 {
-                        break;
-                    }
-                } else {
-                    println!("Did not recognize word \"{}\"; please try again.", word);
-                }
-            }
+            let phrase = read_seed_phrase()?;
             let wallet_secret = match WalletSecret::from_phrase(&phrase) {
                 Err(_) => {
                     println!("Invalid seed phrase. Please try again.");
@@ -224,6 +456,39 @@ async fn main() -> Result<()> {
 
             return Ok(());
         }
+        Command::GenerateAlertKey { secret_key_file } => {
+            if secret_key_file.exists() {
+                bail!("{} already exists", secret_key_file.display());
+            }
+            let secret_key = AlertSecretKey::generate();
+            std::fs::write(&secret_key_file, secret_key.to_hex())?;
+            println!("Secret key stored in: {}", secret_key_file.display());
+            println!("Public key: {}", secret_key.public_key());
+            return Ok(());
+        }
+        Command::SignAlert {
+            secret_key_file,
+            id,
+            severity,
+            message,
+            days,
+        } => {
+            let secret_key: AlertSecretKey = std::fs::read_to_string(&secret_key_file)?.parse()?;
+            let created = Timestamp::now();
+            let alert = Alert {
+                id,
+                network: args.network,
+                severity,
+                message,
+                created,
+                expires: created + Timestamp::days(days),
+            };
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&alert.sign(&secret_key))?
+            );
+            return Ok(());
+        }
         Command::ExportSeedPhrase { network } => {
             // The root path is where both the wallet and all databases are stored
             let data_dir = DataDirectory::get(None, network)?;
@@ -265,13 +530,46 @@ async fn main() -> Result<()> {
         | Command::GenerateWallet { .. }
         | Command::WhichWallet { .. }
         | Command::ExportSeedPhrase { .. }
-        | Command::ImportSeedPhrase { .. } => unreachable!("Case should be handled earlier."),
+        | Command::ImportSeedPhrase { .. }
+        | Command::GenerateAlertKey { .. }
+        | Command::SignAlert { .. } => unreachable!("Case should be handled earlier."),
 
         /******** READ STATE ********/
         Command::ListCoins => {
-            let list = client.list_own_coins(ctx).await?;
+            let list = client.list_own_coins(ctx).await??;
             println!("{}", CoinWithPossibleTimeLock::report(&list));
         }
+        Command::TransactionHistory { kind } => {
+            for entry in client.get_transaction_history(ctx, kind).await?? {
+                println!(
+                    "{} {}: {} ({})",
+                    entry.height,
+                    entry.timestamp.standard_format(),
+                    entry.amount,
+                    entry.origin
+                );
+            }
+        }
+        Command::SentTransactions => {
+            for sent in client.sent_transactions(ctx).await?? {
+                println!("{}", serde_json::to_string(&sent)?);
+            }
+        }
+        Command::ListUtxos => {
+            let utxos = client.list_utxos(ctx).await??;
+            for utxo in utxos {
+                let leaf_index = match utxo.aocl_leaf_index {
+                    Some(leaf_index) => leaf_index.to_string(),
+                    None => "-".to_string(),
+                };
+                let spent = if utxo.spent { ", spent" } else { "" };
+                let abandoned = if utxo.abandoned { ", abandoned" } else { "" };
+                println!(
+                    "{leaf_index}: {} ({}{spent}{abandoned})",
+                    utxo.amount, utxo.state
+                );
+            }
+        }
         Command::Network => {
             let network = client.network(ctx).await?;
             println!("{network}")
@@ -309,6 +607,11 @@ async fn main() -> Result<()> {
             let peers = client.peer_info(ctx).await?;
             println!("{} connected peers", peers.len());
             println!("{}", serde_json::to_string(&peers)?);
+            let configured_peers = client.configured_peers(ctx).await?;
+            if !configured_peers.is_empty() {
+                println!("{} configured peers", configured_peers.len());
+                println!("{}", serde_json::to_string(&configured_peers)?);
+            }
         }
         Command::AllSanctionedPeers => {
             let peer_sanctions = client.all_sanctioned_peers(ctx).await?;
@@ -337,6 +640,14 @@ async fn main() -> Result<()> {
                 println!("{hash}");
             }
         }
+        Command::BlocksInTimeRange { from, to } => {
+            let digests = client
+                .blocks_in_time_range(ctx, Timestamp::millis(from), Timestamp::millis(to))
+                .await?;
+            for digest in digests {
+                println!("{digest}");
+            }
+        }
         Command::TipHeader => {
             let val = client
                 .header(ctx, BlockSelector::Tip)
@@ -353,18 +664,47 @@ async fn main() -> Result<()> {
             }
         }
         Command::SyncedBalance => {
-            let val = client.synced_balance(ctx).await?;
+            let val = client.synced_balance(ctx).await??;
             println!("{val}");
         }
+        Command::TimelockedBalance => {
+            let val = client.timelocked_balance(ctx).await??;
+            println!("{val}");
+        }
+        Command::Balance => {
+            let balance = client.balance(ctx).await??;
+            println!("confirmed: {}", balance.confirmed);
+            println!("unconfirmed: {}", balance.unconfirmed);
+        }
+        Command::BalanceAt { height } => {
+            let val = client.balance_at(ctx, height.into()).await??;
+            println!("{val}");
+        }
+        Command::WalletDelta { since, out } => {
+            let encrypted_delta = client.wallet_delta(ctx, since).await??;
+            std::fs::write(&out, encrypted_delta)?;
+            println!("Wallet delta stored in: {}", out.display());
+        }
         Command::WalletStatus => {
-            let wallet_status: WalletStatus = client.wallet_status(ctx).await?;
+            let wallet_status: WalletStatus = client.wallet_status(ctx).await??;
             println!("{}", serde_json::to_string_pretty(&wallet_status)?);
         }
         Command::OwnReceivingAddress => {
             let rec_addr: generation_address::ReceivingAddress =
-                client.own_receiving_address(ctx).await?;
+                client.own_receiving_address(ctx).await??;
             println!("{}", rec_addr.to_bech32m(args.network).unwrap())
         }
+        Command::AddressBook => {
+            let entries = client.address_book(ctx).await?;
+            for entry in entries {
+                let address = entry.address.to_bech32m(args.network)?;
+                if entry.note.is_empty() {
+                    println!("{}: {address}", entry.name);
+                } else {
+                    println!("{}: {address} ({})", entry.name, entry.note);
+                }
+            }
+        }
         Command::MempoolTxCount => {
             let count: usize = client.mempool_tx_count(ctx).await?;
             println!("{}", count);
@@ -373,6 +713,169 @@ async fn main() -> Result<()> {
             let size_in_bytes: usize = client.mempool_size(ctx).await?;
             println!("{} bytes", size_in_bytes);
         }
+        Command::BlockHex { digest } => {
+            let digest = Digest::try_from_hex(&digest)?;
+            match client.get_block_hex(ctx, digest).await? {
+                Some(hex) => println!("{hex}"),
+                None => println!("Block not found."),
+            }
+        }
+        Command::TransactionHex { digest } => {
+            let digest = Digest::try_from_hex(&digest)?;
+            match client.get_transaction_hex(ctx, digest).await? {
+                Some(hex) => println!("{hex}"),
+                None => println!("Transaction is not in the mempool."),
+            }
+        }
+        Command::DecodeBlock { hex_file } => {
+            let hex = std::fs::read_to_string(hex_file)?;
+            let block = client.decode_block(ctx, hex).await??;
+            println!("{}", serde_json::to_string_pretty(&block)?);
+        }
+        Command::DecodeTransaction { hex_file } => {
+            let hex = std::fs::read_to_string(hex_file)?;
+            let transaction = client.decode_transaction(ctx, hex).await??;
+            println!("{}", serde_json::to_string_pretty(&transaction)?);
+        }
+        Command::MempoolEntry { digest } => {
+            let digest = Digest::try_from_hex(&digest)?;
+            match client.get_mempool_entry(ctx, digest).await? {
+                Some(entry) => println!("{}", serde_json::to_string_pretty(&entry)?),
+                None => println!("Transaction is not in the mempool."),
+            }
+        }
+        Command::WatchTransaction {
+            digest,
+            confirmations,
+        } => {
+            let digest = Digest::try_from_hex(&digest)?;
+            client
+                .watch_transaction(ctx, digest, confirmations)
+                .await??;
+            println!("Watching transaction {digest} for {confirmations} confirmations.");
+        }
+        Command::JournalEvents { since } => {
+            let entries = client.journal_events_since(ctx, since).await?;
+            for entry in entries {
+                println!("{}", serde_json::to_string(&entry)?);
+            }
+        }
+        Command::Health => {
+            let report = client.health(ctx).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::ConnectionMetrics => {
+            let report = client.connection_metrics(ctx).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::ActiveWindowStats => {
+            let stats = client.active_window_stats(ctx).await?;
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+        }
+        Command::MemoryStatus => {
+            let status = client.memory_status(ctx).await?;
+            println!("{status}");
+        }
+        Command::DashboardSnapshot => {
+            let snapshot = client.dashboard_snapshot(ctx).await?;
+            println!("{}", serde_json::to_string_pretty(&snapshot)?);
+        }
+        Command::DiskUsage => match client.get_disk_usage(ctx).await? {
+            Some(usage) => println!("{usage}"),
+            None => println!("Could not measure disk usage. See the node's log."),
+        },
+        Command::MetricsHistory { hours } => {
+            for snapshot in client.get_metrics_history(ctx, hours).await? {
+                println!("{}", serde_json::to_string(&snapshot)?);
+            }
+        }
+        Command::Alerts => {
+            for signed_alert in client.alerts(ctx).await? {
+                let alert = signed_alert.alert;
+                println!(
+                    "[{}] {} (expires {}, signed by {})",
+                    alert.severity,
+                    alert.message,
+                    alert.expires.standard_format(),
+                    signed_alert.public_key
+                );
+            }
+        }
+        Command::ProvingJobs => {
+            for job in client.list_proving_jobs(ctx).await? {
+                let status = match &job.status {
+                    ProvingJobStatus::Failed(err) => format!("failed: {err}"),
+                    ProvingJobStatus::TimedOut => "timed out".to_string(),
+                    status => format!("{status:?}").to_lowercase(),
+                };
+                println!(
+                    "{}: {} ({status}, {}/{} proofs, queued {})",
+                    job.id,
+                    job.description,
+                    job.proofs_done,
+                    job.proofs_total,
+                    job.queued_at.standard_format()
+                );
+            }
+        }
+        Command::FinalizedBlock => match client.finalized_block(ctx).await? {
+            Some(finalized) => println!(
+                "Block {} at height {} is final.",
+                finalized.digest, finalized.height
+            ),
+            None => println!("No block is marked final."),
+        },
+        Command::SharedControlKey { index } => {
+            let cosigner_key = client.shared_control_key(ctx, index).await??;
+            println!("{}", cosigner_key.to_hex());
+        }
+        Command::SharedControlLock {
+            threshold,
+            cosigner_keys,
+        } => {
+            let cosigner_keys = cosigner_keys
+                .iter()
+                .map(|key| Digest::try_from_hex(key))
+                .collect::<Result<Vec<_>, _>>()?;
+            let lock = client
+                .shared_control_lock(ctx, threshold, cosigner_keys)
+                .await??;
+            println!("{lock}");
+        }
+        Command::AuditSupply => match client.audit_supply(ctx).await? {
+            Some(audit) => println!("{}", serde_json::to_string_pretty(&audit)?),
+            None => println!("Supply audit is only available on archival nodes."),
+        },
+        Command::BlockTimelines { n } => {
+            let timelines = client.block_processing_timelines(ctx, n).await?;
+            for timeline in timelines {
+                println!(
+                    "height {} ({}): {:.3} secs",
+                    timeline.height,
+                    timeline.digest.to_hex(),
+                    timeline.total_duration_secs()
+                );
+                for stage_timing in timeline.stages {
+                    println!(
+                        "  {:<20} {:.3} secs",
+                        stage_timing.stage, stage_timing.duration_secs
+                    );
+                }
+            }
+        }
+        Command::BlockPropagation { n } => {
+            let report = client.block_propagation(ctx, n).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::BlockTree { depth, dot } => match client.export_block_tree(ctx, depth).await? {
+            Some(tree) if dot => print!("{}", tree.to_dot()),
+            Some(tree) => println!("{}", serde_json::to_string_pretty(&tree)?),
+            None => println!("Block trees are only available on archival nodes."),
+        },
+        Command::ListForks => {
+            let tips = client.list_forks(ctx).await?;
+            println!("{}", serde_json::to_string_pretty(&tips)?);
+        }
 
         /******** CHANGE STATE ********/
         Command::Shutdown => {
@@ -388,17 +891,140 @@ async fn main() -> Result<()> {
             client.clear_standing_by_ip(ctx, ip).await?;
             println!("Cleared standing of {}", ip);
         }
+        Command::MarkBlockFinal { digest } => {
+            let digest = Digest::try_from_hex(&digest)?;
+            let finalized = client.mark_block_final(ctx, digest).await??;
+            println!(
+                "Block {} at height {} is final.",
+                finalized.digest, finalized.height
+            );
+        }
         Command::Send {
             amount,
             address,
             fee,
+            release_date,
+            idempotency_key,
         } => {
-            // Parse on client
             let receiving_address =
-                generation_address::ReceivingAddress::from_bech32m(address.clone(), args.network)?;
+                resolve_address(&client, ctx, address.clone(), args.network).await?;
+            let release_date = release_date.map(Timestamp::millis);
+
+            match client
+                .send(
+                    ctx,
+                    amount,
+                    receiving_address,
+                    fee,
+                    release_date,
+                    idempotency_key,
+                )
+                .await?
+            {
+                Ok(transaction_digest) => println!(
+                    "Send-command issues. Recipient: {address}; amount: {amount}; transaction: {}",
+                    transaction_digest.to_hex()
+                ),
+                Err(send_failure) => println!("{send_failure}"),
+            }
+        }
+        Command::PrepareSend {
+            amount,
+            address,
+            fee,
+            release_date,
+        } => {
+            let receiving_address = resolve_address(&client, ctx, address, args.network).await?;
+            let release_date = release_date.map(Timestamp::millis);
+
+            match client
+                .prepare_send(ctx, amount, receiving_address, fee, release_date)
+                .await?
+            {
+                Ok(preview) => println!("{}", serde_json::to_string_pretty(&preview)?),
+                Err(send_failure) => println!("{send_failure}"),
+            }
+        }
+        Command::ConfirmSend {
+            token,
+            idempotency_key,
+        } => {
+            let token = Digest::try_from_hex(&token)?;
 
-            client.send(ctx, amount, receiving_address, fee).await?;
-            println!("Send-command issues. Recipient: {address}; amount: {amount}");
+            match client.confirm_send(ctx, token, idempotency_key).await? {
+                Ok(transaction_digest) => {
+                    println!("Sent transaction {}", transaction_digest.to_hex())
+                }
+                Err(send_failure) => println!("{send_failure}"),
+            }
+        }
+        Command::SweepKey { fee } => {
+            println!("Sweeping wallet. Please enter the words of its seed phrase:");
+            let phrase = read_seed_phrase()?;
+            match client.sweep_key(ctx, phrase, fee).await? {
+                Ok(transaction_digest) => {
+                    println!("Sent transaction {}", transaction_digest.to_hex())
+                }
+                Err(err) => println!("{err}"),
+            }
+        }
+        Command::SendData { text, fee } => {
+            match client.send_data(ctx, text.into_bytes(), fee).await? {
+                Ok(transaction_digest) => {
+                    println!("Sent transaction {}", transaction_digest.to_hex())
+                }
+                Err(err) => println!("{err}"),
+            }
+        }
+        Command::SignSharedSpend { spend_file, index } => {
+            let shared_spend: SharedSpend = decode_hex(&std::fs::read_to_string(&spend_file)?)?;
+            let shared_spend = client.sign_shared_spend(ctx, shared_spend, index).await??;
+            std::fs::write(&spend_file, encode_hex(&shared_spend))?;
+            println!(
+                "Signed as cosigner; {} of {} required signatures.",
+                shared_spend.unlock_keys.len(),
+                shared_spend.lock.threshold
+            );
+        }
+        Command::BroadcastSharedSpend { spend_file } => {
+            let shared_spend: SharedSpend = decode_hex(&std::fs::read_to_string(spend_file)?)?;
+            match client.broadcast_shared_spend(ctx, shared_spend).await? {
+                Ok(transaction_digest) => {
+                    println!("Sent transaction {}", transaction_digest.to_hex())
+                }
+                Err(err) => println!("{err}"),
+            }
+        }
+        Command::SaveAddress {
+            name,
+            address,
+            note,
+        } => match client
+            .save_address_book_entry(ctx, name.clone(), address, note)
+            .await?
+        {
+            Ok(true) => println!("Replaced address of {name}"),
+            Ok(false) => println!("Saved address of {name}"),
+            Err(err) => println!("{err}"),
+        },
+        Command::RemoveAddress { name } => {
+            if client.remove_address_book_entry(ctx, name.clone()).await? {
+                println!("Removed {name} from address book");
+            } else {
+                println!("No address book entry named {name}");
+            }
+        }
+        Command::BroadcastAlert { alert_file } => {
+            let alert: SignedAlert = serde_json::from_str(&std::fs::read_to_string(alert_file)?)?;
+            match client.broadcast_alert(ctx, alert).await? {
+                Ok(()) => println!("Broadcast alert"),
+                Err(err) => println!("{err}"),
+            }
+        }
+        Command::PreferTip { digest } => {
+            let digest = Digest::try_from_hex(&digest)?;
+            client.prefer_tip(ctx, digest).await??;
+            println!("Preferring tip {digest}.");
         }
         Command::PauseMiner => {
             println!("Sending command to pause miner.");
@@ -411,11 +1037,82 @@ async fn main() -> Result<()> {
             println!("Command completed successfully");
         }
 
+        Command::CancelProvingJob { id } => {
+            if client.cancel_proving_job(ctx, ProvingJobId(id)).await? {
+                println!("Cancelled proving job {id}");
+            } else {
+                println!("No queued or running proving job {id}");
+            }
+        }
+
         Command::PruneAbandonedMonitoredUtxos => {
-            let prunt_res_count = client.prune_abandoned_monitored_utxos(ctx).await?;
+            let prunt_res_count = client.prune_abandoned_monitored_utxos(ctx).await??;
             println!("{prunt_res_count} monitored UTXOs marked as abandoned");
         }
+
+        Command::RescanWallet { from_height } => {
+            client.rescan_wallet(ctx, from_height.into()).await??;
+            println!("Rescanning from height {from_height}. See `rescan-status` for progress.");
+        }
+
+        Command::RescanStatus => match client.dashboard_overview_data(ctx).await?.wallet_rescan {
+            Some(progress) => println!("{}", serde_json::to_string_pretty(&progress)?),
+            None => println!("No rescan since startup."),
+        },
+
+        Command::CancelRescan => {
+            client.cancel_rescan(ctx).await??;
+            println!("Rescan cancelled");
+        }
     }
 
     Ok(())
 }
+
+/// Read the 18 words of a seed phrase from stdin, one at a time
+fn read_seed_phrase() -> Result<Vec<String>> {
+    let mut phrase = vec![];
+    let mut i = 1;
+    loop {
+        print!("{}. ", i);
+        io::stdout().flush()?;
+        let mut buffer = "".to_string();
+        std::io::stdin()
+            .read_line(&mut buffer)
+            .expect("Cannot accept user input.");
+        let word = buffer.trim();
+        if bip39::Language::English
+            .wordlist()
+            .get_words_by_prefix("")
+            .iter()
+            .any(|s| *s == word)
+        {
+            phrase.push(word.to_string());
+            i += 1;
+            if i > 18 {
+                break;
+            }
+        } else {
+            println!("Did not recognize word \"{}\"; please try again.", word);
+        }
+    }
+
+    Ok(phrase)
+}
+
+/// Parse a bech32m-encoded address, or else look it up by name in the node's
+/// address book.
+async fn resolve_address(
+    client: &RPCClient,
+    ctx: context::Context,
+    address: String,
+    network: Network,
+) -> Result<generation_address::ReceivingAddress> {
+    match generation_address::ReceivingAddress::from_bech32m(address.clone(), network) {
+        Ok(receiving_address) => Ok(receiving_address),
+        Err(err) => match client.address_book_entry(ctx, address).await? {
+            Some(entry) => Ok(entry.address),
+            None => bail!("Not a valid address nor an address book entry: {err}"),
+        },
+    }
+}
@@ -0,0 +1,92 @@
+//! Pretty-print captured peer protocol frames.
+//!
+//! Reads the bytes one side of a peer connection sent, e.g. as exported from
+//! a packet capture, and prints the messages they hold.
+
+use std::io::Read;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use bytes::BytesMut;
+use clap::Parser;
+use neptune_core::models::peer::wire_format::{
+    conformance_vectors, decode_frame, encode_frame, from_hex, to_hex, variant_name,
+};
+
+#[derive(Debug, Parser)]
+#[clap(
+    name = "neptune-dissect",
+    about = "Decode captured peer protocol frames"
+)]
+struct Args {
+    /// File holding the captured frames. Read from stdin if absent.
+    input: Option<PathBuf>,
+
+    /// Read the input as raw bytes rather than as hex
+    #[clap(long)]
+    raw: bool,
+
+    /// Print the conformance vectors, as hex, instead of decoding input
+    #[clap(long)]
+    vectors: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    if args.vectors {
+        for message in conformance_vectors() {
+            println!(
+                "{}: {}",
+                variant_name(&message),
+                to_hex(&encode_frame(&message)?)
+            );
+        }
+        return Ok(());
+    }
+
+    let mut input = match &args.input {
+        Some(path) => std::fs::read(path)?,
+        None => {
+            let mut input = vec![];
+            std::io::stdin().read_to_end(&mut input)?;
+            input
+        }
+    };
+    if !args.raw {
+        input = from_hex(&String::from_utf8(input)?)?;
+    }
+
+    let total_length = input.len();
+    let mut buffer = BytesMut::from(&input[..]);
+    let mut index = 0;
+    loop {
+        let offset = total_length - buffer.len();
+        let message = match decode_frame(&mut buffer) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(err) => {
+                eprintln!("Frame {index} at offset {offset}: {err:#}");
+                std::process::exit(1);
+            }
+        };
+        let frame_length = total_length - buffer.len() - offset;
+        println!(
+            "Frame {index} at offset {offset}, {frame_length} bytes: {}",
+            variant_name(&message)
+        );
+        println!("{message:#?}");
+        index += 1;
+    }
+
+    if !buffer.is_empty() {
+        eprintln!(
+            "{} bytes of an incomplete frame at offset {}",
+            buffer.len(),
+            total_length - buffer.len()
+        );
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Produce proofs for nodes that delegate proving with `--prover-endpoint`.
+//!
+//! A fixed number of workers accept connections, each reading one proof job
+//! and answering with the proof. Connections beyond that wait in the listen
+//! backlog, so a flood of connections cannot exhaust threads or memory. See
+//! `models::consensus::prover` for the protocol.
+//!
+//! Proof jobs include secret witnesses and are not encrypted, so only listen
+//! on addresses that trusted nodes alone can reach, e.g. loopback behind an
+//! SSH tunnel.
+
+use std::net::{SocketAddr, TcpListener};
+use std::time::Duration;
+
+use anyhow::Result;
+use clap::Parser;
+use neptune_core::models::consensus::prover::serve_proof_job;
+
+/// Time to wait for a node to send the next bytes of its proof job, so that
+/// idle connections do not hold on to a worker
+const JOB_READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Parser)]
+#[clap(
+    name = "neptune-prover",
+    about = "Produce proofs for neptune-core nodes"
+)]
+struct Args {
+    /// Address to accept proof jobs on. Only listen on addresses that
+    /// trusted nodes alone can reach, as proof jobs include secret witnesses.
+    #[clap(long, default_value = "127.0.0.1:9800")]
+    listen: SocketAddr,
+
+    /// Max number of proof jobs to serve at a time. Proving uses all cores,
+    /// so more than one job at a time rarely pays off.
+    #[clap(long, default_value = "1", value_parser = clap::value_parser!(u16).range(1..))]
+    max_jobs: u16,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let listener = TcpListener::bind(args.listen)?;
+    println!(
+        "Accepting proof jobs on {}, {} at a time",
+        listener.local_addr()?,
+        args.max_jobs
+    );
+
+    let workers = (0..args.max_jobs)
+        .map(|_| {
+            let listener = listener.try_clone()?;
+            Ok(std::thread::spawn(move || serve(listener)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    for worker in workers {
+        let _ = worker.join();
+    }
+
+    Ok(())
+}
+
+/// Serve proof jobs one at a time, for as long as connections can be accepted
+fn serve(listener: TcpListener) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+        let peer = stream
+            .peer_addr()
+            .map(|address| address.to_string())
+            .unwrap_or_default();
+        if let Err(err) = stream.set_read_timeout(Some(JOB_READ_TIMEOUT)) {
+            eprintln!("Failed to set read timeout for {peer}: {err}");
+            continue;
+        }
+        match serve_proof_job(&mut stream) {
+            Ok(()) => println!("Served proof job from {peer}"),
+            Err(err) => eprintln!("Failed to serve proof job from {peer}: {err:#}"),
+        }
+    }
+}
@@ -1,9 +1,17 @@
+use super::execution_profile::ExecutionProfile;
 use super::network::Network;
+use crate::models::blockchain::block::checkpoints::{Checkpoint, Checkpoints};
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::prover::Prover;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::alerts::AlertPublicKey;
+use crate::models::state::event_journal::JournalRetention;
 use bytesize::ByteSize;
 use clap::builder::RangedI64ValueParser;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use std::str::FromStr;
 
 /// The `neptune-core` command-line program starts a Neptune node.
 #[derive(Parser, Debug, Clone)]
@@ -31,6 +39,17 @@ pub struct Args {
     #[clap(long, value_name = "IP")]
     pub ban: Vec<IpAddr>,
 
+    /// Trust peers at IP address, e.g. your own infrastructure nodes.
+    ///
+    /// Transactions from whitelisted peers bypass the mempool policy, see
+    /// `--mempool-policy`, and whitelisted peers are never banned for bad
+    /// standing. `--ban` still applies. Peers are identified by IP, as they
+    /// have no persistent identity that could be verified.
+    ///
+    /// E.g.: --whitelist 10.0.0.2 --whitelist 10.0.0.3
+    #[clap(long, value_name = "IP")]
+    pub whitelist: Vec<IpAddr>,
+
     /// Refuse connection if peer is in bad standing.
     ///
     /// This sets the threshold for when a peer should be automatically refused.
@@ -45,16 +64,144 @@ pub struct Args {
     #[clap(long, default_value = "10", value_name = "COUNT")]
     pub max_peers: u16,
 
+    /// Max time, in seconds, to wait for an outgoing connection to a peer's
+    /// address to be established. The addresses of a peer are dialed
+    /// concurrently, and the first connection made is used.
+    #[clap(long, default_value = "10", value_name = "SECONDS")]
+    pub peer_connect_timeout: u64,
+
+    /// Max time, in seconds, to wait for each phase of the handshake with a
+    /// peer, i.e. for each handshake message to be sent or received.
+    #[clap(long, default_value = "10", value_name = "SECONDS")]
+    pub handshake_timeout: u64,
+
+    /// Max time, in seconds, from a TCP connection to a peer being established
+    /// until the handshake is complete.
+    #[clap(long, default_value = "30", value_name = "SECONDS")]
+    pub connection_setup_timeout: u64,
+
+    /// The role of this node: `archival`, `full`, `light`, or `miner`.
+    ///
+    /// A profile implies the settings of other arguments, e.g. `miner` implies
+    /// `--mine`, and arguments that conflict with the profile are rejected.
+    #[clap(long, default_value = "archival", value_name = "PROFILE")]
+    pub profile: ExecutionProfile,
+
+    /// Run without a wallet.
+    ///
+    /// No wallet files are read or created, and wallet RPCs fail. Mining
+    /// requires --mine-to-address, as there is no wallet to receive the
+    /// coinbase.
+    #[clap(long)]
+    pub no_wallet: bool,
+
+    /// Record how each block changes the wallet's UTXOs.
+    ///
+    /// Enables queries for the wallet's balance and UTXOs at past block
+    /// heights, back to the first block recorded.
+    #[clap(long)]
+    pub historical_index: bool,
+
+    /// Back up the wallet database after this many changes to it, such as
+    /// received and spent UTXOs, and generated keys. 0 disables backups.
+    ///
+    /// Backups are encrypted under a key derived from the wallet secret, so
+    /// restoring one requires the wallet secret or its seed phrase.
+    #[clap(long, default_value = "10", value_name = "CHANGES")]
+    pub wallet_backup_interval: u64,
+
+    /// Directory to write wallet backups to. Defaults to the `backups`
+    /// directory in the wallet directory.
+    #[clap(long, value_name = "DIR")]
+    pub wallet_backup_dir: Option<PathBuf>,
+
+    /// Number of most recent wallet backups to keep
+    #[clap(long, default_value = "10", value_name = "COUNT")]
+    pub wallet_backup_count: usize,
+
     /// Should this node participate in competitive mining?
     ///
     /// Mining is disabled by default.
     #[clap(long)]
     pub mine: bool,
 
+    /// Pay mining rewards to this address instead of to the node's wallet.
+    ///
+    /// Must be an address for the network the node runs on. The coinbase is
+    /// announced publicly so that the wallet owning the address finds it; the
+    /// node's own wallet does not track it.
+    #[clap(long, value_name = "ADDRESS")]
+    pub mine_to_address: Option<String>,
+
+    /// Embed this tag, e.g. the name of a mining pool, in the coinbase
+    /// transactions of mined blocks. At most 64 bytes.
+    #[clap(long, value_name = "TAG")]
+    pub coinbase_message: Option<String>,
+
+    /// Extra nonce to embed in coinbase transactions along with the coinbase
+    /// message, so that pool workers search disjoint spaces.
+    ///
+    /// If a coinbase message is set without an extra nonce, a random extra
+    /// nonce is used for every block template.
+    #[clap(long, value_name = "NONCE")]
+    pub extra_nonce: Option<u64>,
+
     /// If mining, use all available CPU power. Ignored if mine flag not set.
     #[clap(long)]
     pub unrestricted_mining: bool,
 
+    /// Max number of proofs to produce at a time, e.g. for concurrent sends.
+    /// Further proving jobs are queued.
+    ///
+    /// Defaults to one per core, as far as available RAM allows.
+    #[clap(long, value_name = "COUNT")]
+    pub max_proving_jobs: Option<usize>,
+
+    /// Max time, in seconds, for a send to create its transaction, from
+    /// waiting for the wallet to producing the proofs. Sends that take longer
+    /// fail, and their change is no longer expected. A send can also be
+    /// cancelled as a proving job.
+    #[clap(long, default_value = "1800", value_name = "SECONDS")]
+    pub send_timeout: u64,
+
+    /// Max number of blocks and transactions whose proofs are verified at a
+    /// time. Lower it on small machines.
+    ///
+    /// Defaults to one per core.
+    #[clap(long, value_name = "COUNT")]
+    pub max_proof_verifications: Option<usize>,
+
+    /// Max number of blocks received from peers that are validated at a time.
+    ///
+    /// Defaults to one per core.
+    #[clap(long, value_name = "COUNT")]
+    pub max_block_validations: Option<usize>,
+
+    /// Max number of transactions received from peers that are checked for
+    /// admission to the mempool at a time.
+    ///
+    /// Defaults to one per core.
+    #[clap(long, value_name = "COUNT")]
+    pub mempool_admission_workers: Option<usize>,
+
+    /// Delegate proving to an external prover, e.g. `neptune-prover`, at this
+    /// address. Proofs are produced locally if it cannot be reached.
+    ///
+    /// Proof jobs include the secret witnesses of the transaction, e.g.
+    /// spending keys, and are sent unencrypted. So endpoints that are not on
+    /// this machine are refused unless `--allow-remote-prover` is set.
+    ///
+    /// E.g. --prover-endpoint 127.0.0.1:9800
+    #[clap(long, value_name = "HOST:PORT")]
+    pub prover_endpoint: Option<String>,
+
+    /// Allow a prover endpoint that is not on this machine. Only set this if
+    /// the network path to the prover is trusted, e.g. a VPN or SSH tunnel,
+    /// as anyone who can observe it learns the secrets needed to spend the
+    /// wallet's coins.
+    #[clap(long)]
+    pub allow_remote_prover: bool,
+
     /// Prune the mempool when it exceeds this size in RAM.
     ///
     /// Units: B (bytes), K (kilobytes), M (megabytes), G (gigabytes)
@@ -63,6 +210,17 @@ pub struct Args {
     #[clap(long, default_value = "1G", value_name = "SIZE")]
     pub max_mempool_size: ByteSize,
 
+    /// Override the mempool policy of the network from a JSON file.
+    ///
+    /// Recognized fields: `min_fee` (amount), `max_transaction_size` (size),
+    /// `max_age_secs`, `max_future_drift_secs`,
+    /// `replacement_fee_density_increase_percent` and `max_data_carrier_size`
+    /// (bytes of data carriers per transaction; 0 rejects them).
+    ///
+    /// E.g. --mempool-policy mempool-policy.json
+    #[clap(long, value_name = "FILE")]
+    pub mempool_policy: Option<PathBuf>,
+
     /// Prune the pool of UTXO notification when it exceeds this size in RAM.
     ///
     /// Units: B (bytes), K (kilobytes), M (megabytes), G (gigabytes)
@@ -71,6 +229,30 @@ pub struct Args {
     #[clap(long, default_value = "50M", value_name = "SIZE")]
     pub max_utxo_notification_size: ByteSize,
 
+    /// Diffuse transactions held in the Dandelion stem phase early when they
+    /// exceed this size in RAM.
+    ///
+    /// Units: B (bytes), K (kilobytes), M (megabytes), G (gigabytes)
+    ///
+    /// E.g. --max-stem-pool-size 20M
+    #[clap(long, default_value = "50M", value_name = "SIZE")]
+    pub max_stem_pool_size: ByteSize,
+
+    /// Start a new event journal file once the current one exceeds this size.
+    ///
+    /// Units: B (bytes), K (kilobytes), M (megabytes), G (gigabytes)
+    #[clap(long, default_value = "16M", value_name = "SIZE")]
+    pub journal_max_file_size: ByteSize,
+
+    /// Number of event journal files to keep. The oldest are deleted first.
+    #[clap(long, default_value = "8", value_name = "COUNT")]
+    pub journal_max_files: usize,
+
+    /// Delete event journal files last written to more than this many days
+    /// ago. Kept regardless of age if not set.
+    #[clap(long, value_name = "DAYS")]
+    pub journal_max_age: Option<usize>,
+
     /// Maximum number of unconfirmed expected UTXOs that can be stored for each peer.
     ///
     /// You may want to increase this number from its default value if
@@ -88,9 +270,46 @@ pub struct Args {
     #[clap(long, default_value = "9799", value_name = "PORT")]
     pub rpc_port: u16,
 
+    /// IP on which to listen for RPC connections, e.g. `::1` for IPv6
+    /// localhost, or `::` for all interfaces, IPv4 and IPv6.
+    ///
+    /// The RPC server gives full control of the node and its wallet, so only
+    /// listen beyond localhost on trusted networks.
+    #[clap(long, default_value = "127.0.0.1", value_name = "IP")]
+    pub rpc_listen_addr: IpAddr,
+
+    /// Serve a public, read-only subset of the RPC on this port, e.g. for a
+    /// block explorer. It cannot reach the wallet. Disabled by default.
+    #[clap(long, value_name = "PORT")]
+    pub public_rpc_port: Option<u16>,
+
+    /// IP on which to listen for public RPC connections. Will default to all
+    /// network interfaces, IPv4 and IPv6.
+    #[clap(long, default_value = "::", value_name = "IP")]
+    pub public_rpc_listen_addr: IpAddr,
+
+    /// Max number of public RPC requests per minute from one IP.
+    #[clap(long, default_value = "60", value_name = "COUNT")]
+    pub public_rpc_requests_per_minute: usize,
+
     /// IP on which to listen for peer connections. Will default to all network interfaces, IPv4 and IPv6.
-    #[clap(short, long, default_value = "::")]
-    pub listen_addr: IpAddr,
+    ///
+    /// Can be given multiple times to listen on several interfaces, each with
+    /// an optional port that overrides `--peer-port`.
+    ///
+    /// E.g. --listen-addr 192.168.1.5 --listen-addr [2001:db8::5]:19798
+    #[clap(short, long, default_value = "::", value_name = "IP[:PORT]")]
+    pub listen_addr: Vec<ListenAddress>,
+
+    /// Address on which peers can reach this node, if it differs from the
+    /// ones it listens on, e.g. behind NAT with port forwarding. Advertised to
+    /// peers instead of the listen addresses.
+    ///
+    /// Can be given multiple times, e.g. for an IPv4 and an IPv6 address.
+    ///
+    /// E.g. --announce-address 203.0.113.7:19798
+    #[clap(long, value_name = "IP:PORT")]
+    pub announce_address: Vec<SocketAddr>,
 
     /// Max number of blocks that the client can catch up to before going into syncing mode.
     ///
@@ -104,6 +323,14 @@ pub struct Args {
     #[structopt(long)]
     pub peers: Vec<SocketAddr>,
 
+    /// Max number of attempts to connect to a peer given with --peers before
+    /// giving up on it. Retried forever if not set.
+    ///
+    /// Attempts back off exponentially, and start over when a connection is
+    /// lost.
+    #[clap(long, value_name = "COUNT")]
+    pub max_reconnect_attempts: Option<u32>,
+
     /// Specify network, `alpha`, `testnet`, or `regtest`
     #[structopt(long, short, default_value = "alpha")]
     pub network: Network,
@@ -124,10 +351,193 @@ pub struct Args {
     #[clap(long, default_value = "false")]
     pub privacy: bool,
 
+    /// Announce own transactions to all peers right away.
+    ///
+    /// By default, own transactions are first passed along a random path of
+    /// peers, each forwarding it to a single peer, before being announced to
+    /// all (Dandelion relay). This hides which node created the transaction.
+    #[clap(long)]
+    pub no_dandelion: bool,
+
     /// Enable tokio tracing for consumption by the tokio-console application
     /// note: this will attempt to connect to localhost:6669
     #[structopt(long, name = "tokio-console", default_value = "false")]
     pub tokio_console: bool,
+
+    /// Command to run whenever a new block becomes the tip.
+    ///
+    /// The command is executed through the system shell. Event details are
+    /// passed in `NEPTUNE_*` environment variables and as a JSON object on
+    /// stdin, e.g.: --on-new-block 'echo $NEPTUNE_BLOCK_HEIGHT >> tips.log'
+    #[clap(long, value_name = "CMD")]
+    pub on_new_block: Option<String>,
+
+    /// Command to run whenever a block adds UTXOs to this node's wallet.
+    ///
+    /// Event details are passed the same way as for `--on-new-block`.
+    #[clap(long, value_name = "CMD")]
+    pub on_wallet_receive: Option<String>,
+
+    /// Command to run whenever the new tip does not extend the previous tip.
+    ///
+    /// Event details are passed the same way as for `--on-new-block`.
+    #[clap(long, value_name = "CMD")]
+    pub on_reorg: Option<String>,
+
+    /// Command to run whenever a transaction, in the mempool or in a block,
+    /// spends an input of an unconfirmed transaction sent by this wallet.
+    ///
+    /// Event details are passed the same way as for `--on-new-block`.
+    #[clap(long, value_name = "CMD")]
+    pub on_double_spend: Option<String>,
+
+    /// Command to run whenever a transaction watched with `watch-transaction`
+    /// reaches the requested number of confirmations, or the block that
+    /// included it leaves the canonical chain.
+    ///
+    /// Event details are passed the same way as for `--on-new-block`.
+    #[clap(long, value_name = "CMD")]
+    pub on_watched_transaction: Option<String>,
+
+    /// Command to run when the free disk space falls below
+    /// `--min-free-disk-space`, and when it recovers.
+    ///
+    /// Event details are passed the same way as for `--on-new-block`.
+    #[clap(long, value_name = "CMD")]
+    pub on_disk_space: Option<String>,
+
+    /// Serve HTTP health checks (`/health` and `/ready`) on this port.
+    ///
+    /// Binds to the IP of the first `--listen-addr`. Disabled by default.
+    #[clap(long, value_name = "PORT")]
+    pub health_port: Option<u16>,
+
+    /// Max age of the tip, in seconds, for the node to be reported as ready.
+    #[clap(long, default_value = "3600", value_name = "SECONDS")]
+    pub health_max_tip_age: u64,
+
+    /// Min number of connected peers for the node to be reported as ready.
+    #[clap(long, default_value = "1", value_name = "COUNT")]
+    pub health_min_peers: usize,
+
+    /// Max deviation, in seconds, of this node's clock from its peers' clocks
+    /// and from the tip's timestamp before a clock-skew warning is raised.
+    #[clap(long, default_value = "120", value_name = "SECONDS")]
+    pub max_clock_skew: u64,
+
+    /// Stop mining while this node's clock is skewed. See `--max-clock-skew`.
+    #[clap(long)]
+    pub refuse_mining_on_clock_skew: bool,
+
+    /// Seed all randomness of the node, including wallet secrets, from this
+    /// value, so that a test run can be reproduced exactly. Only available in
+    /// builds with the `test-determinism` feature.
+    #[cfg(feature = "test-determinism")]
+    #[clap(long, value_name = "SEED")]
+    pub deterministic_seed: Option<u64>,
+
+    /// Disconnect from all peers when the tip has not changed for several
+    /// block intervals while peers report higher tips, so that fresh peers are
+    /// found. Peers given with `--peers` are reconnected.
+    #[clap(long)]
+    pub reconnect_on_stale_tip: bool,
+
+    /// Stop storing blocks, syncing and mining while the disk holding the data
+    /// directory has less free space than this. They resume once the free
+    /// space is 10% above it. 0 disables the check.
+    ///
+    /// Units: B (bytes), K (kilobytes), M (megabytes), G (gigabytes)
+    ///
+    /// E.g. --min-free-disk-space 500M
+    #[clap(long, default_value = "1G", value_name = "SIZE")]
+    pub min_free_disk_space: ByteSize,
+
+    /// Max size of the blocks served to peers per minute. The limit is shared
+    /// evenly between the peers requesting blocks, and requests beyond a
+    /// peer's share are not answered. Whitelisted peers are exempt. 0 disables
+    /// the limit.
+    ///
+    /// Units: B (bytes), K (kilobytes), M (megabytes), G (gigabytes)
+    ///
+    /// E.g. --block-serving-limit 200M
+    #[clap(long, default_value = "1G", value_name = "SIZE")]
+    pub block_serving_limit: ByteSize,
+
+    /// Refuse to send more than this amount, including the fee, in one transaction.
+    #[clap(long, value_name = "AMOUNT")]
+    pub max_send_amount: Option<NeptuneCoins>,
+
+    /// Refuse to send more than this amount, including fees, in any 24 hours.
+    #[clap(long, value_name = "AMOUNT")]
+    pub max_daily_send_amount: Option<NeptuneCoins>,
+
+    /// Only send to these addresses. If not set, any address is allowed.
+    ///
+    /// E.g. --send-allowlist nolgam1... --send-allowlist nolgam1...
+    #[clap(long, value_name = "ADDRESS")]
+    pub send_allowlist: Vec<String>,
+
+    /// Also accept network alerts signed by this Ed25519 public key, given
+    /// as hex. Alerts signed by the built-in developer keys are always
+    /// accepted.
+    ///
+    /// E.g. --alert-key 3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c
+    #[clap(long, value_name = "KEY")]
+    pub alert_key: Vec<AlertPublicKey>,
+
+    /// Ask peers to leave proofs out of the blocks they send, to save
+    /// bandwidth on constrained links. Peers of versions before 0.0.6 send
+    /// blocks with proofs regardless.
+    ///
+    /// The transactions of blocks received without proofs are not verified;
+    /// such blocks are trusted on the strength of their proof-of-work.
+    /// Ignored when mining, as a miner must fully verify the blocks it builds
+    /// on.
+    #[clap(long)]
+    pub omit_block_proofs: bool,
+
+    /// Neither request nor relay transactions of other nodes, to save
+    /// bandwidth, e.g. on archival or backup nodes.
+    ///
+    /// The mempool only holds this node's own transactions, which are still
+    /// sent to peers. Peers of version 0.0.6 or later learn of the mode right
+    /// after the handshake and stop announcing transactions to this node.
+    #[clap(long)]
+    pub blocks_only: bool,
+
+    /// Mark blocks this many blocks below the tip final, so that no reorg
+    /// can roll them back. Archival nodes only.
+    ///
+    /// Blocks can also be marked final by an external finality provider
+    /// through the `mark_block_final` RPC.
+    #[clap(long, value_name = "DEPTH")]
+    pub finality_depth: Option<u64>,
+
+    /// Only follow chains with this block at this height, given as
+    /// HEIGHT:DIGEST. Peers offering blocks of other chains are sanctioned.
+    ///
+    /// Can be given multiple times, and overrides the built-in checkpoint of
+    /// the network at the same height, if any.
+    ///
+    /// E.g. --checkpoint 1000:5a8b...
+    #[clap(long, value_name = "HEIGHT:DIGEST")]
+    pub checkpoint: Vec<Checkpoint>,
+
+    /// Run a command instead of the node
+    #[clap(subcommand)]
+    pub command: Option<CoreCommand>,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum CoreCommand {
+    /// Check that the node can start: data directory permissions, disk space,
+    /// clock, ports, databases and wallet file. Exits with an error if any
+    /// check fails.
+    Doctor {
+        /// Print the report as JSON
+        #[clap(long)]
+        json: bool,
+    },
 }
 
 impl Default for Args {
@@ -137,9 +547,76 @@ impl Default for Args {
     }
 }
 
+impl Args {
+    pub fn is_whitelisted(&self, ip: IpAddr) -> bool {
+        self.whitelist.contains(&ip)
+    }
+
+    /// Whether a peer with this standing is banned. Whitelisted peers never are.
+    pub fn is_banned_by_standing(&self, ip: IpAddr, standing: i32) -> bool {
+        !self.is_whitelisted(ip) && standing < -(self.peer_tolerance as i32)
+    }
+
+    /// The addresses on which to listen for peer connections
+    pub fn peer_listen_addresses(&self) -> Vec<SocketAddr> {
+        self.listen_addr
+            .iter()
+            .map(|address| SocketAddr::new(address.ip, address.port.unwrap_or(self.peer_port)))
+            .collect()
+    }
+
+    /// How much of the event journal is kept on disk
+    pub fn journal_retention(&self) -> JournalRetention {
+        JournalRetention {
+            max_file_size: self.journal_max_file_size.as_u64(),
+            max_file_count: self.journal_max_files,
+            max_age: self.journal_max_age.map(Timestamp::days),
+        }
+    }
+
+    /// The checkpoints of the network and those given on the command line
+    pub fn checkpoints(&self) -> Checkpoints {
+        Checkpoints::new(self.network, &self.checkpoint)
+    }
+
+    /// Whether blocks without proofs are asked for and accepted
+    pub fn prover(&self) -> Prover {
+        Prover::new(self.prover_endpoint.clone(), self.allow_remote_prover)
+    }
+
+    pub fn accepts_blocks_without_proofs(&self) -> bool {
+        self.omit_block_proofs && !self.mine
+    }
+}
+
+/// An IP to listen for peer connections on, with an optional port
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListenAddress {
+    pub ip: IpAddr,
+    pub port: Option<u16>,
+}
+
+impl FromStr for ListenAddress {
+    type Err = std::net::AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(address) = s.parse::<SocketAddr>() {
+            return Ok(Self {
+                ip: address.ip(),
+                port: Some(address.port()),
+            });
+        }
+
+        Ok(Self {
+            ip: s.parse()?,
+            port: None,
+        })
+    }
+}
+
 #[cfg(test)]
 mod cli_args_tests {
-    use std::net::Ipv6Addr;
+    use std::net::{Ipv4Addr, Ipv6Addr};
 
     use super::*;
 
@@ -148,12 +625,86 @@ mod cli_args_tests {
         let default_args = Args::default();
 
         assert_eq!(100, default_args.peer_tolerance);
+        assert!(default_args.whitelist.is_empty());
         assert_eq!(10, default_args.max_peers);
         assert_eq!(9798, default_args.peer_port);
         assert_eq!(9799, default_args.rpc_port);
         assert_eq!(
-            IpAddr::from(Ipv6Addr::UNSPECIFIED),
-            default_args.listen_addr
+            vec![SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 9798)],
+            default_args.peer_listen_addresses()
+        );
+        assert!(default_args.announce_address.is_empty());
+        assert_eq!(
+            IpAddr::from(Ipv4Addr::LOCALHOST),
+            default_args.rpc_listen_addr
         );
     }
+
+    #[test]
+    fn ipv6_addresses_test() {
+        let args = Args::parse_from([
+            "neptune-core",
+            "--listen-addr",
+            "2001:db8::2",
+            "--rpc-listen-addr",
+            "::1",
+            "--peers",
+            "[2001:db8::1]:9798",
+            "--peers",
+            "192.0.2.1:9798",
+        ]);
+
+        assert_eq!(
+            vec![SocketAddr::new("2001:db8::2".parse().unwrap(), 9798)],
+            args.peer_listen_addresses()
+        );
+        assert_eq!(IpAddr::from(Ipv6Addr::LOCALHOST), args.rpc_listen_addr);
+        assert_eq!(
+            vec![
+                SocketAddr::new("2001:db8::1".parse().unwrap(), 9798),
+                SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 9798),
+            ],
+            args.peers
+        );
+    }
+
+    #[test]
+    fn multiple_listen_addresses_and_announce_address_test() {
+        let args = Args::parse_from([
+            "neptune-core",
+            "--peer-port",
+            "9800",
+            "--listen-addr",
+            "192.168.1.5",
+            "--listen-addr",
+            "[2001:db8::5]:19798",
+            "--announce-address",
+            "203.0.113.7:19798",
+        ]);
+
+        assert_eq!(
+            vec![
+                SocketAddr::new(Ipv4Addr::new(192, 168, 1, 5).into(), 9800),
+                "[2001:db8::5]:19798".parse().unwrap(),
+            ],
+            args.peer_listen_addresses()
+        );
+        assert_eq!(
+            vec!["203.0.113.7:19798".parse::<SocketAddr>().unwrap()],
+            args.announce_address
+        );
+        assert!("192.168.1.5:".parse::<ListenAddress>().is_err());
+    }
+
+    #[test]
+    fn whitelisted_peers_are_never_banned_by_standing_test() {
+        let args = Args::parse_from(["neptune-core", "--whitelist", "10.0.0.2"]);
+        let own_node: IpAddr = Ipv4Addr::new(10, 0, 0, 2).into();
+        let stranger: IpAddr = Ipv4Addr::new(10, 0, 0, 3).into();
+
+        assert!(args.is_whitelisted(own_node));
+        assert!(!args.is_banned_by_standing(own_node, -1000));
+        assert!(args.is_banned_by_standing(stranger, -1000));
+        assert!(!args.is_banned_by_standing(stranger, -50));
+    }
 }
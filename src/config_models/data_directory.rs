@@ -5,11 +5,16 @@ use std::path::{Path, PathBuf};
 use crate::config_models::network::Network;
 use crate::models::database::DATABASE_DIRECTORY_ROOT_NAME;
 use crate::models::state::archival_state::{BLOCK_INDEX_DB_NAME, MUTATOR_SET_DIRECTORY_NAME};
-use crate::models::state::networking_state::BANNED_IPS_DB_NAME;
+use crate::models::state::event_journal::EVENT_JOURNAL_DIRECTORY_NAME;
+use crate::models::state::light_state::LIGHT_STATE_FILE_NAME;
+use crate::models::state::metrics_history::METRICS_HISTORY_FILE_NAME;
+use crate::models::state::networking_state::{BANNED_IPS_DB_NAME, PEER_HISTORIES_DB_NAME};
 use crate::models::state::shared::{
     BLOCK_FILENAME_EXTENSION, BLOCK_FILENAME_PREFIX, DIR_NAME_FOR_BLOCKS,
 };
+use crate::models::state::wallet::wallet_backup::WALLET_BACKUP_DIRECTORY;
 use crate::models::state::wallet::{WALLET_DB_NAME, WALLET_DIRECTORY, WALLET_OUTPUT_COUNT_DB_NAME};
+use crate::util_types::entropy::ENTROPY_SEED_FILE_NAME;
 
 // TODO: Add `rusty_leveldb::Options` and `fs::OpenOptions` here too, since they keep being repeated.
 #[derive(Debug, Clone)]
@@ -85,6 +90,16 @@ impl DataDirectory {
         self.database_dir_path().join(Path::new(BANNED_IPS_DB_NAME))
     }
 
+    ///////////////////////////////////////////////////////////////////////////
+    ///
+    /// The peer histories database directory path.
+    ///
+    /// This directory lives within `DataDirectory::database_dir_path()`.
+    pub fn peer_histories_database_dir_path(&self) -> PathBuf {
+        self.database_dir_path()
+            .join(Path::new(PEER_HISTORIES_DB_NAME))
+    }
+
     ///////////////////////////////////////////////////////////////////////////
     ///
     /// The wallet file path
@@ -92,6 +107,14 @@ impl DataDirectory {
         self.data_dir.join(Path::new(WALLET_DIRECTORY))
     }
 
+    /// The default directory of wallet backups.
+    ///
+    /// This directory lives within `DataDirectory::wallet_directory_path()`.
+    pub fn wallet_backup_dir_path(&self) -> PathBuf {
+        self.wallet_directory_path()
+            .join(Path::new(WALLET_BACKUP_DIRECTORY))
+    }
+
     /// The wallet database directory path.
     ///
     /// This directory lives within `DataDirectory::database_dir_path()`.
@@ -134,6 +157,36 @@ impl DataDirectory {
             .join(Path::new(BLOCK_INDEX_DB_NAME))
     }
 
+    ///////////////////////////////////////////////////////////////////////////
+    ///
+    /// The event journal directory.
+    ///
+    /// This directory lives within `DataDirectory::root_dir_path()`.
+    pub fn event_journal_dir_path(&self) -> PathBuf {
+        self.data_dir.join(Path::new(EVENT_JOURNAL_DIRECTORY_NAME))
+    }
+
+    /// The file holding the tip of a light node.
+    ///
+    /// This file lives within `DataDirectory::root_dir_path()`.
+    pub fn light_state_file_path(&self) -> PathBuf {
+        self.data_dir.join(Path::new(LIGHT_STATE_FILE_NAME))
+    }
+
+    /// The ring buffer of metrics snapshots.
+    ///
+    /// This file lives within `DataDirectory::root_dir_path()`.
+    pub fn metrics_history_file_path(&self) -> PathBuf {
+        self.data_dir.join(Path::new(METRICS_HISTORY_FILE_NAME))
+    }
+
+    /// The file holding the seed mixed into the node's randomness.
+    ///
+    /// This file lives within `DataDirectory::root_dir_path()`.
+    pub fn entropy_seed_file_path(&self) -> PathBuf {
+        self.data_dir.join(Path::new(ENTROPY_SEED_FILE_NAME))
+    }
+
     /// The file path that contains block(s) with `file_index`.
     ///
     /// Note that multiple blocks can be stored in one block file.
@@ -0,0 +1,88 @@
+use std::fmt;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::cli_args::Args;
+
+/// The role of a node, which implies the settings of other CLI arguments.
+/// Only mining depends on the profile so far: the node has no block pruning or
+/// snapshot serving for a profile to switch, and the historical index is a
+/// wallet setting that is independent of the role.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum ExecutionProfile {
+    /// Store all blocks and serve them to syncing peers. Mining is optional.
+    #[default]
+    Archival,
+
+    /// Validate and relay blocks and transactions, without mining. All blocks
+    /// are stored, as block pruning is not implemented yet.
+    Full,
+
+    /// Keep only the tip. Not supported yet.
+    Light,
+
+    /// Store all blocks and mine.
+    Miner,
+}
+
+impl ExecutionProfile {
+    /// Set the CLI arguments implied by this profile, and fail if any
+    /// argument conflicts with it.
+    pub fn apply(self, args: &mut Args) -> Result<()> {
+        match self {
+            ExecutionProfile::Archival => (),
+            ExecutionProfile::Full => {
+                if args.mine {
+                    bail!("--mine conflicts with --profile full. Use --profile miner to mine.");
+                }
+            }
+            ExecutionProfile::Light => {
+                if args.mine {
+                    bail!("--mine conflicts with --profile light, as mining requires all blocks.");
+                }
+                bail!("--profile light is not supported yet.");
+            }
+            ExecutionProfile::Miner => args.mine = true,
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExecutionProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let string = match self {
+            ExecutionProfile::Archival => "archival",
+            ExecutionProfile::Full => "full",
+            ExecutionProfile::Light => "light",
+            ExecutionProfile::Miner => "miner",
+        };
+        write!(f, "{string}")
+    }
+}
+
+#[cfg(test)]
+mod execution_profile_tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn applied(cli: &[&str]) -> Result<Args> {
+        let mut args = Args::parse_from([&["neptune-core"][..], cli].concat());
+        args.profile.apply(&mut args)?;
+        Ok(args)
+    }
+
+    #[test]
+    fn profiles_set_and_check_mining() {
+        assert!(!applied(&[]).unwrap().mine);
+        assert!(applied(&["--mine"]).unwrap().mine);
+        assert!(applied(&["--profile", "miner"]).unwrap().mine);
+        assert!(!applied(&["--profile", "full"]).unwrap().mine);
+
+        assert!(applied(&["--profile", "full", "--mine"]).is_err());
+        assert!(applied(&["--profile", "light", "--mine"]).is_err());
+        assert!(applied(&["--profile", "light"]).is_err());
+    }
+}
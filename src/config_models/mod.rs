@@ -1,3 +1,4 @@
 pub mod cli_args;
 pub mod data_directory;
+pub mod execution_profile;
 pub mod network;
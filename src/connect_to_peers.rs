@@ -1,8 +1,15 @@
-use anyhow::{bail, Result};
-use futures::{FutureExt, SinkExt, TryStreamExt};
-use std::{fmt::Debug, net::SocketAddr};
+use anyhow::{bail, Context, Result};
+use futures::stream::FuturesUnordered;
+use futures::{Future, FutureExt, SinkExt, StreamExt, TryStreamExt};
+use std::{
+    fmt::Debug,
+    io::ErrorKind,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant, SystemTime},
+};
 use tokio::{
     io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
     sync::{broadcast, mpsc},
 };
 use tokio_serde::{
@@ -16,8 +23,11 @@ use crate::{
     models::{
         channel::{MainToPeerThread, PeerThreadToMain},
         peer::{
-            ConnectionRefusedReason, ConnectionStatus, HandshakeData, PeerMessage, PeerStanding,
+            ConnectionRefusedReason, ConnectionStatus, HandshakeData, InstanceId, PeerInfo,
+            PeerMessage, PeerStanding,
         },
+        state::connection_metrics::{ConnectionDirection, HandshakeFailure},
+        state::slow_peers::HandshakeOutcome,
         state::GlobalStateLock,
     },
     peer_loop::PeerLoopHandler,
@@ -27,15 +37,158 @@ use crate::{
 // Max peer message size is 2000MB
 pub const MAX_PEER_FRAME_LENGTH_IN_BYTES: usize = 2000 * 1024 * 1024;
 
+/// Delay before dialing the next address of a peer while the previous attempt
+/// is still pending, as recommended by RFC 8305
+pub const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
 /// Use this function to ensure that the same rules apply for both
 /// ingoing and outgoing connections. This limits the size of messages
 /// peers can send.
-fn get_codec_rules() -> LengthDelimitedCodec {
+pub(crate) fn get_codec_rules() -> LengthDelimitedCodec {
     let mut codec_rules = LengthDelimitedCodec::new();
     codec_rules.set_max_frame_length(MAX_PEER_FRAME_LENGTH_IN_BYTES);
     codec_rules
 }
 
+fn bind_error_context(address: SocketAddr) -> String {
+    format!("Failed to bind to local TCP port {address}. Is an instance of this program already running?")
+}
+
+/// Bind the listeners for incoming peer connections on all of
+/// `listen_addresses`.
+///
+/// The unspecified IPv6 address `::` listens on all interfaces, for both IPv4
+/// and IPv6. Whether an IPv6 socket also accepts IPv4 connections depends on
+/// the OS, so an IPv4 socket is bound as well where it does not. On hosts
+/// without IPv6, only the IPv4 socket is bound.
+///
+/// Returns the listeners and the addresses on which they accept connections.
+pub async fn bind_peer_listeners(
+    listen_addresses: &[SocketAddr],
+) -> Result<(Vec<TcpListener>, Vec<SocketAddr>)> {
+    let mut listeners = vec![];
+    let mut bound_addresses = vec![];
+    for &address in listen_addresses {
+        let (new_listeners, new_addresses) = bind_peer_listeners_on(address).await?;
+        listeners.extend(new_listeners);
+        bound_addresses.extend(new_addresses);
+    }
+
+    Ok((listeners, bound_addresses))
+}
+
+async fn bind_peer_listeners_on(
+    address: SocketAddr,
+) -> Result<(Vec<TcpListener>, Vec<SocketAddr>)> {
+    let port = address.port();
+    if address.ip() != IpAddr::V6(Ipv6Addr::UNSPECIFIED) {
+        let listener = TcpListener::bind(address)
+            .await
+            .with_context(|| bind_error_context(address))?;
+        let address = listener.local_addr()?;
+        return Ok((vec![listener], vec![address]));
+    }
+
+    let ipv6_listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(err) if err.kind() != ErrorKind::AddrInUse => {
+            info!("Cannot listen for peers on IPv6, listening on IPv4 only: {err}");
+            let ipv4_address = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port);
+            let listener = TcpListener::bind(ipv4_address)
+                .await
+                .with_context(|| bind_error_context(ipv4_address))?;
+            let ipv4_address = listener.local_addr()?;
+            return Ok((vec![listener], vec![ipv4_address]));
+        }
+        Err(err) => return Err(err).with_context(|| bind_error_context(address)),
+    };
+
+    // Use the port of the IPv6 socket, in case it was picked by the OS
+    let ipv6_address = ipv6_listener.local_addr()?;
+    let ipv4_address = SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), ipv6_address.port());
+    let mut listeners = vec![ipv6_listener];
+    match TcpListener::bind(ipv4_address).await {
+        Ok(listener) => listeners.push(listener),
+
+        // The IPv6 socket already accepts IPv4 connections, as is the default
+        // on Linux
+        Err(err) if err.kind() == ErrorKind::AddrInUse => {
+            debug!("Listening for IPv4 peers on the dual-stack IPv6 socket");
+        }
+        Err(err) => return Err(err).with_context(|| bind_error_context(ipv4_address)),
+    }
+
+    Ok((listeners, vec![ipv4_address, ipv6_address]))
+}
+
+/// Return the address of a connected peer in its own family. IPv4 peers that
+/// connect to a dual-stack socket have IPv4-mapped IPv6 addresses, and would
+/// otherwise escape the standings and bans of their IPv4 address.
+pub fn canonical_peer_address(address: SocketAddr) -> SocketAddr {
+    SocketAddr::new(address.ip().to_canonical(), address.port())
+}
+
+/// Of two connections between the same two nodes, one initiated by each, as
+/// happens when they connect to each other at the same time, the one initiated
+/// by the node with the lower instance ID is kept. Both nodes reach the same
+/// decision without coordinating.
+pub(crate) fn keeps_cross_connection(
+    own_instance_id: InstanceId,
+    peer_instance_id: InstanceId,
+    inbound: bool,
+) -> bool {
+    let initiator = if inbound {
+        peer_instance_id
+    } else {
+        own_instance_id
+    };
+
+    initiator == own_instance_id.min(peer_instance_id)
+}
+
+/// How a new connection relates to the established ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DuplicateConnection {
+    /// The peer is not connected yet
+    None,
+
+    /// The peer is already connected, and the new connection must be refused
+    Refuse,
+
+    /// The peer is connected in the other direction, and the new connection
+    /// replaces that connection, which has this address
+    Supersede(SocketAddr),
+}
+
+/// Check whether a new connection duplicates an established one, by the
+/// peer's address or instance ID, in either direction
+pub(crate) fn find_duplicate_connection<'a>(
+    connected_peers: impl IntoIterator<Item = &'a PeerInfo>,
+    own_instance_id: InstanceId,
+    peer_instance_id: InstanceId,
+    peer_address: SocketAddr,
+    inbound: bool,
+) -> DuplicateConnection {
+    for peer in connected_peers {
+        if peer.connected_address == peer_address {
+            return DuplicateConnection::Refuse;
+        }
+        if peer.instance_id != peer_instance_id {
+            continue;
+        }
+
+        return if peer.inbound != inbound
+            && keeps_cross_connection(own_instance_id, peer_instance_id, inbound)
+        {
+            DuplicateConnection::Supersede(peer.connected_address)
+        } else {
+            DuplicateConnection::Refuse
+        };
+    }
+
+    DuplicateConnection::None
+}
+
 /// Check if connection is allowed. Used for both ingoing and outgoing connections.
 ///
 /// Locking:
@@ -45,6 +198,7 @@ async fn check_if_connection_is_allowed(
     own_handshake: &HandshakeData,
     other_handshake: &HandshakeData,
     peer_address: &SocketAddr,
+    inbound: bool,
 ) -> ConnectionStatus {
     let global_state = global_state_lock.lock_guard().await;
     fn versions_are_compatible(own_version: &str, other_version: &str) -> bool {
@@ -69,6 +223,12 @@ async fn check_if_connection_is_allowed(
         true
     }
 
+    // Disallow connection to self. The instance ID is a random nonce drawn at
+    // startup, so it identifies this node whichever address it was reached on.
+    if own_handshake.instance_id == other_handshake.instance_id {
+        return ConnectionStatus::Refused(ConnectionRefusedReason::SelfConnect);
+    }
+
     // Disallow connection if peer is banned via CLI arguments
     if global_state.cli().ban.contains(&peer_address.ip()) {
         warn!(
@@ -84,9 +244,11 @@ async fn check_if_connection_is_allowed(
         .get_peer_standing_from_database(peer_address.ip())
         .await;
 
-    if standing.is_some()
-        && standing.unwrap().standing < -(global_state.cli().peer_tolerance as i32)
-    {
+    if standing.is_some_and(|standing| {
+        global_state
+            .cli()
+            .is_banned_by_standing(peer_address.ip(), standing.standing)
+    }) {
         return ConnectionStatus::Refused(ConnectionRefusedReason::BadStanding);
     }
 
@@ -97,11 +259,16 @@ async fn check_if_connection_is_allowed(
                 ConnectionRefusedReason::MaxPeerNumberExceeded,
             ))
         }
-        // Disallow connection to already connected peer.
-        else if global_state.net.peer_map.values().any(|peer| {
-            peer.instance_id == other_handshake.instance_id
-                || *peer_address == peer.connected_address
-        }) {
+        // Disallow connection to already connected peer, unless this
+        // connection wins over one in the other direction
+        else if find_duplicate_connection(
+            global_state.net.peer_map.values(),
+            own_handshake.instance_id,
+            other_handshake.instance_id,
+            *peer_address,
+            inbound,
+        ) == DuplicateConnection::Refuse
+        {
             Some(ConnectionStatus::Refused(
                 ConnectionRefusedReason::AlreadyConnected,
             ))
@@ -112,11 +279,6 @@ async fn check_if_connection_is_allowed(
         return status;
     }
 
-    // Disallow connection to self
-    if own_handshake.instance_id == other_handshake.instance_id {
-        return ConnectionStatus::Refused(ConnectionRefusedReason::SelfConnect);
-    }
-
     // Disallow connection if versions are incompatible
     if !versions_are_compatible(&own_handshake.version, &other_handshake.version) {
         warn!(
@@ -130,6 +292,98 @@ async fn check_if_connection_is_allowed(
     ConnectionStatus::Accepted
 }
 
+/// Count a failed handshake in the connection metrics
+async fn record_handshake_failure(
+    state: &GlobalStateLock,
+    direction: ConnectionDirection,
+    failure: HandshakeFailure,
+) {
+    state
+        .lock_guard_mut()
+        .await
+        .net
+        .connection_metrics
+        .record_handshake_failure(direction, failure);
+}
+
+/// Bounds the phases of a handshake in time, and records how it went
+struct HandshakeTimer<'a> {
+    state: &'a GlobalStateLock,
+    peer_address: SocketAddr,
+    direction: ConnectionDirection,
+    started: Instant,
+    phase_timeout: Duration,
+    deadline: Instant,
+}
+
+impl<'a> HandshakeTimer<'a> {
+    fn start(
+        state: &'a GlobalStateLock,
+        peer_address: SocketAddr,
+        direction: ConnectionDirection,
+    ) -> Self {
+        let started = Instant::now();
+        Self {
+            state,
+            peer_address,
+            direction,
+            started,
+            phase_timeout: Duration::from_secs(state.cli().handshake_timeout),
+            deadline: started + Duration::from_secs(state.cli().connection_setup_timeout),
+        }
+    }
+
+    async fn fail(&self, failure: HandshakeFailure) {
+        record_handshake_failure(self.state, self.direction, failure).await;
+    }
+
+    /// Run a phase of the handshake, such as sending or receiving a message,
+    /// bounded by the handshake timeout and the connection setup deadline
+    async fn phase<T, E>(&self, phase: impl Future<Output = Result<T, E>>) -> Result<T>
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let timeout = self
+            .phase_timeout
+            .min(self.deadline.saturating_duration_since(Instant::now()));
+        match tokio::time::timeout(timeout, phase).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(err)) => {
+                self.fail(HandshakeFailure::Transport).await;
+                Err(err.into())
+            }
+            Err(_) => {
+                self.fail(HandshakeFailure::TimedOut).await;
+                self.state
+                    .lock_guard_mut()
+                    .await
+                    .net
+                    .slow_peers
+                    .record(self.peer_address.ip(), HandshakeOutcome::TimedOut);
+                bail!(
+                    "Handshake with {} timed out after {} ms",
+                    self.peer_address,
+                    timeout.as_millis()
+                );
+            }
+        }
+    }
+
+    /// Record the duration of the completed handshake
+    async fn complete(&self) {
+        let outcome = HandshakeOutcome::of_completed(self.started.elapsed(), self.phase_timeout);
+        if outcome == HandshakeOutcome::Slow {
+            debug!("Slow handshake with {}", self.peer_address);
+        }
+        self.state
+            .lock_guard_mut()
+            .await
+            .net
+            .slow_peers
+            .record(self.peer_address.ip(), outcome);
+    }
+}
+
 pub async fn answer_peer_wrapper<S>(
     stream: S,
     state_lock: GlobalStateLock,
@@ -198,21 +452,26 @@ where
     > = SymmetricallyFramed::new(length_delimited, SymmetricalBincode::default());
 
     // Complete Neptune handshake
-    let peer_handshake_data: HandshakeData = match peer.try_next().await? {
+    let handshake = HandshakeTimer::start(&state, peer_address, ConnectionDirection::Inbound);
+    let first_message = handshake.phase(peer.try_next()).await?;
+    let peer_handshake_data: HandshakeData = match first_message {
         Some(PeerMessage::Handshake(payload)) => {
             let (v, hsd) = *payload;
             if v != crate::MAGIC_STRING_REQUEST {
+                handshake.fail(HandshakeFailure::BadMagicValue).await;
                 bail!("Expected magic value, got {:?}", v);
             }
 
-            peer.send(PeerMessage::Handshake(Box::new((
-                crate::MAGIC_STRING_RESPONSE.to_vec(),
-                own_handshake_data.clone(),
-            ))))
-            .await?;
+            handshake
+                .phase(peer.send(PeerMessage::Handshake(Box::new((
+                    crate::MAGIC_STRING_RESPONSE.to_vec(),
+                    own_handshake_data.clone(),
+                )))))
+                .await?;
 
             // Verify peer network before moving on
             if hsd.network != own_handshake_data.network {
+                handshake.fail(HandshakeFailure::NetworkMismatch).await;
                 bail!(
                     "Cannot connect with {}: Peer runs {}, this client runs {}.",
                     peer_address,
@@ -227,13 +486,18 @@ where
                 &own_handshake_data,
                 &hsd,
                 &peer_address,
+                true,
             )
             .await;
 
-            peer.send(PeerMessage::ConnectionStatus(connection_status))
+            handshake
+                .phase(peer.send(PeerMessage::ConnectionStatus(connection_status)))
                 .await?;
             if let ConnectionStatus::Refused(refused_reason) = connection_status {
                 warn!("Incoming connection refused: {:?}", refused_reason);
+                handshake
+                    .fail(HandshakeFailure::RefusedByUs(refused_reason))
+                    .await;
                 bail!("Refusing incoming connection. Reason: {:?}", refused_reason);
             }
 
@@ -241,9 +505,11 @@ where
             hsd
         }
         _ => {
+            handshake.fail(HandshakeFailure::UnexpectedMessage).await;
             bail!("Didn't get handshake on connection attempt");
         }
     };
+    handshake.complete().await;
 
     // Whether the incoming connection comes from a peer in bad standing is checked in `get_connection_status`
     info!("Connection accepted from {}", peer_address);
@@ -264,10 +530,74 @@ where
     Ok(())
 }
 
+/// Order a peer's addresses for dialing, alternating between IPv6 and IPv4,
+/// starting with IPv6, as recommended by RFC 8305
+fn interleave_address_families(addresses: &[SocketAddr]) -> Vec<SocketAddr> {
+    let (ipv6, ipv4): (Vec<_>, Vec<_>) = addresses.iter().copied().partition(|a| a.is_ipv6());
+    let mut ipv6 = ipv6.into_iter();
+    let mut ipv4 = ipv4.into_iter();
+
+    let mut ordered = vec![];
+    loop {
+        match (ipv6.next(), ipv4.next()) {
+            (None, None) => break,
+            (first, second) => ordered.extend(first.into_iter().chain(second)),
+        }
+    }
+    ordered.dedup();
+
+    ordered
+}
+
+async fn connect_with_timeout(address: SocketAddr, timeout: Duration) -> Result<TcpStream> {
+    match tokio::time::timeout(timeout, TcpStream::connect(address)).await {
+        Ok(result) => Ok(result?),
+        Err(_) => bail!("timed out after {} ms", timeout.as_millis()),
+    }
+}
+
+/// Connect to the first reachable of a peer's addresses, "Happy Eyeballs"
+/// style. Attempts are started [`CONNECTION_ATTEMPT_DELAY`] apart, or as soon
+/// as the previous one fails, and run concurrently. Each attempt is abandoned
+/// after `timeout`. The first connection made wins, and the other attempts are
+/// cancelled.
+///
+/// Returns the connection and the address it was made to.
+pub(crate) async fn dial(
+    addresses: &[SocketAddr],
+    timeout: Duration,
+) -> Result<(TcpStream, SocketAddr)> {
+    let mut remaining = interleave_address_families(addresses).into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut errors = vec![];
+    loop {
+        if let Some(address) = remaining.next() {
+            debug!("Dialing {address}");
+            attempts.push(async move { (address, connect_with_timeout(address, timeout).await) });
+        } else if attempts.is_empty() {
+            break;
+        }
+
+        tokio::select! {
+            Some((address, result)) = attempts.next() => match result {
+                // Dropping the other attempts cancels them
+                Ok(stream) => return Ok((stream, address)),
+                Err(err) => errors.push(format!("{address}: {err}")),
+            },
+            _ = tokio::time::sleep(CONNECTION_ATTEMPT_DELAY), if !remaining.as_slice().is_empty() => {}
+        }
+    }
+
+    bail!("Failed to connect to peer. {}", errors.join("; "))
+}
+
 /// Perform handshake and establish connection to a new peer while handling any panics in the peer
 /// thread gracefully.
+///
+/// The peer is dialed on all the given addresses of it, of which there must be
+/// at least one, see [`dial`].
 pub async fn call_peer_wrapper(
-    peer_address: std::net::SocketAddr,
+    peer_addresses: Vec<SocketAddr>,
     state: GlobalStateLock,
     main_to_peer_thread_rx: broadcast::Receiver<MainToPeerThread>,
     peer_thread_to_main_tx: mpsc::Sender<PeerThreadToMain>,
@@ -276,13 +606,22 @@ pub async fn call_peer_wrapper(
 ) {
     let state_clone = state.clone();
     let peer_thread_to_main_tx_clone = peer_thread_to_main_tx.clone();
+    let connect_timeout = Duration::from_secs(state.cli().peer_connect_timeout);
+    let mut peer_address = peer_addresses[0];
     let panic_result = std::panic::AssertUnwindSafe(async {
         debug!("Attempting to initiate connection");
-        match tokio::net::TcpStream::connect(peer_address).await {
+        match dial(&peer_addresses, connect_timeout).await {
             Err(e) => {
                 warn!("Failed to establish connection: {}", e);
+                record_handshake_failure(
+                    &state,
+                    ConnectionDirection::Outbound,
+                    HandshakeFailure::Unreachable,
+                )
+                .await;
             }
-            Ok(stream) => {
+            Ok((stream, connected_address)) => {
+                peer_address = connected_address;
                 match call_peer(
                     stream,
                     state,
@@ -343,20 +682,25 @@ where
     > = SymmetricallyFramed::new(length_delimited, SymmetricalBincode::default());
 
     // Make Neptune handshake
-    peer.send(PeerMessage::Handshake(Box::new((
-        Vec::from(MAGIC_STRING_REQUEST),
-        own_handshake.to_owned(),
-    ))))
-    .await?;
+    let handshake = HandshakeTimer::start(&state, peer_address, ConnectionDirection::Outbound);
+    handshake
+        .phase(peer.send(PeerMessage::Handshake(Box::new((
+            Vec::from(MAGIC_STRING_REQUEST),
+            own_handshake.to_owned(),
+        )))))
+        .await?;
     debug!("Awaiting connection status response from {}", peer_address);
 
-    let other_handshake: HandshakeData = match peer.try_next().await? {
+    let handshake_response = handshake.phase(peer.try_next()).await?;
+    let other_handshake: HandshakeData = match handshake_response {
         Some(PeerMessage::Handshake(payload)) => {
             let (v, hsd) = *payload;
             if v != MAGIC_STRING_RESPONSE {
+                handshake.fail(HandshakeFailure::BadMagicValue).await;
                 bail!("Didn't get expected magic value for handshake");
             }
             if hsd.network != own_handshake.network {
+                handshake.fail(HandshakeFailure::NetworkMismatch).await;
                 bail!(
                     "Cannot connect with {}: Peer runs {}, this client runs {}.",
                     peer_address,
@@ -368,18 +712,23 @@ where
             hsd
         }
         _ => {
+            handshake.fail(HandshakeFailure::UnexpectedMessage).await;
             bail!("Didn't get handshake response");
         }
     };
 
-    match peer.try_next().await? {
+    match handshake.phase(peer.try_next()).await? {
         Some(PeerMessage::ConnectionStatus(ConnectionStatus::Accepted)) => {
             info!("Outgoing connection accepted by {peer_address}");
         }
         Some(PeerMessage::ConnectionStatus(ConnectionStatus::Refused(reason))) => {
+            handshake
+                .fail(HandshakeFailure::RefusedByPeer(reason))
+                .await;
             bail!("Outgoing connection attempt refused. Reason: {:?}", reason);
         }
         _ => {
+            handshake.fail(HandshakeFailure::UnexpectedMessage).await;
             bail!("Got invalid connection status response on outgoing connection");
         }
     }
@@ -392,6 +741,7 @@ where
         own_handshake,
         &other_handshake,
         &peer_address,
+        false,
     )
     .await;
     if let ConnectionStatus::Refused(refused_reason) = connection_status {
@@ -399,9 +749,13 @@ where
             "Outgoing connection refused. Reason: {:?}\nNow hanging up.",
             refused_reason
         );
+        handshake
+            .fail(HandshakeFailure::RefusedByUs(refused_reason))
+            .await;
         peer.send(PeerMessage::Bye).await?;
         bail!("Attempted to connect to peer that was not allowed. This connection attempt should not have been made.");
     }
+    handshake.complete().await;
 
     let peer_loop_handler = PeerLoopHandler::new(
         peer_thread_to_main_tx,
@@ -432,6 +786,11 @@ pub async fn close_peer_connected_callback(
     let mut global_state_mut = global_state_lock.lock_guard_mut().await;
     // Store any new peer-standing to database
     let peer_info_writeback = global_state_mut.net.peer_map.remove(&peer_address);
+    global_state_mut.net.buffer_sizes.remove_peer(peer_address);
+    global_state_mut
+        .net
+        .connection_metrics
+        .connection_closed(peer_address, SystemTime::now());
 
     let new_standing = match peer_info_writeback {
         Some(new) => new.standing,
@@ -473,11 +832,52 @@ mod connect_tests {
         ConnectionStatus, PeerInfo, PeerMessage, PeerSanctionReason, PeerStanding,
     };
     use crate::tests::shared::{
-        get_dummy_handshake_data_for_genesis, get_dummy_peer_connection_data_genesis,
-        get_dummy_socket_address, get_test_genesis_setup, to_bytes,
+        get_dummy_handshake_data_for_genesis, get_dummy_peer,
+        get_dummy_peer_connection_data_genesis, get_dummy_socket_address, get_test_genesis_setup,
+        to_bytes,
     };
     use crate::{MAGIC_STRING_REQUEST, MAGIC_STRING_RESPONSE};
 
+    #[test]
+    fn addresses_are_dialed_alternating_families() {
+        let v4 = |port| SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port);
+        let v6 = |port| SocketAddr::new(Ipv6Addr::LOCALHOST.into(), port);
+        assert_eq!(
+            vec![v6(1), v4(1), v6(2), v4(2), v4(3)],
+            interleave_address_families(&[v4(1), v4(2), v4(3), v6(1), v6(2)])
+        );
+        assert_eq!(vec![v4(1)], interleave_address_families(&[v4(1)]));
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn dial_uses_first_reachable_address() -> Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let reachable = listener.local_addr()?;
+
+        // A port that was just freed refuses connections
+        let refusing = TcpListener::bind("127.0.0.1:0").await?.local_addr()?;
+
+        let (_stream, connected) = dial(&[refusing, reachable], Duration::from_secs(5)).await?;
+        assert_eq!(reachable, connected);
+
+        assert!(dial(&[refusing], Duration::from_secs(5)).await.is_err());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn dial_gives_up_after_timeout() {
+        // A non-routable address, on which connecting hangs or fails
+        let unreachable = SocketAddr::new(Ipv4Addr::new(10, 255, 255, 1).into(), 9798);
+        let started = std::time::Instant::now();
+        assert!(dial(&[unreachable], Duration::from_millis(100))
+            .await
+            .is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_outgoing_connection_succeed() -> Result<()> {
@@ -543,6 +943,7 @@ mod connect_tests {
             &own_handshake,
             &other_handshake,
             &peer_sa,
+            false,
         )
         .await;
         if status != ConnectionStatus::Accepted {
@@ -554,6 +955,7 @@ mod connect_tests {
             &own_handshake,
             &own_handshake,
             &peer_sa,
+            false,
         )
         .await;
         if status != ConnectionStatus::Refused(ConnectionRefusedReason::SelfConnect) {
@@ -570,6 +972,7 @@ mod connect_tests {
             &own_handshake,
             &other_handshake,
             &peer_sa,
+            false,
         )
         .await;
         if status != ConnectionStatus::Refused(ConnectionRefusedReason::MaxPeerNumberExceeded) {
@@ -593,6 +996,7 @@ mod connect_tests {
             &own_handshake,
             &mutated_other_handshake,
             &peer_sa,
+            false,
         )
         .await;
         if status != ConnectionStatus::Refused(ConnectionRefusedReason::AlreadyConnected) {
@@ -612,6 +1016,7 @@ mod connect_tests {
             &own_handshake,
             &other_handshake,
             &peer_sa,
+            false,
         )
         .await;
         if status != ConnectionStatus::Refused(ConnectionRefusedReason::BadStanding) {
@@ -627,6 +1032,7 @@ mod connect_tests {
             &own_handshake,
             &other_handshake,
             &peer_sa,
+            false,
         )
         .await;
         if status != ConnectionStatus::Accepted {
@@ -655,6 +1061,7 @@ mod connect_tests {
             &own_handshake,
             &other_handshake,
             &peer_sa,
+            false,
         )
         .await;
         if status != ConnectionStatus::Refused(ConnectionRefusedReason::BadStanding) {
@@ -664,6 +1071,96 @@ mod connect_tests {
         Ok(())
     }
 
+    #[test]
+    fn simultaneous_cross_connections_keep_exactly_one() {
+        let connected = |address: SocketAddr, instance_id: InstanceId, inbound: bool| PeerInfo {
+            connected_address: address,
+            instance_id,
+            inbound,
+            ..get_dummy_peer(address)
+        };
+
+        // Nodes A and B connect to each other at the same time. The
+        // connection initiated by A, with the lower instance ID, is kept,
+        // whichever connection each node sees first.
+        let (id_a, id_b) = (1, 2);
+        let from_a_at_b = get_dummy_socket_address(1);
+        let from_b_at_a = get_dummy_socket_address(2);
+        let to_b_at_a = get_dummy_socket_address(3);
+        let to_a_at_b = get_dummy_socket_address(4);
+
+        assert!(keeps_cross_connection(id_a, id_b, false));
+        assert!(!keeps_cross_connection(id_a, id_b, true));
+        assert!(keeps_cross_connection(id_b, id_a, true));
+        assert!(!keeps_cross_connection(id_b, id_a, false));
+
+        // At A, the outbound connection is kept
+        assert_eq!(
+            DuplicateConnection::Refuse,
+            find_duplicate_connection(
+                &[connected(to_b_at_a, id_b, false)],
+                id_a,
+                id_b,
+                from_b_at_a,
+                true
+            )
+        );
+        assert_eq!(
+            DuplicateConnection::Supersede(from_b_at_a),
+            find_duplicate_connection(
+                &[connected(from_b_at_a, id_b, true)],
+                id_a,
+                id_b,
+                to_b_at_a,
+                false
+            )
+        );
+
+        // At B, the inbound connection is kept
+        assert_eq!(
+            DuplicateConnection::Refuse,
+            find_duplicate_connection(
+                &[connected(from_a_at_b, id_a, true)],
+                id_b,
+                id_a,
+                to_a_at_b,
+                false
+            )
+        );
+        assert_eq!(
+            DuplicateConnection::Supersede(to_a_at_b),
+            find_duplicate_connection(
+                &[connected(to_a_at_b, id_a, false)],
+                id_b,
+                id_a,
+                from_a_at_b,
+                true
+            )
+        );
+
+        // Duplicates in the same direction are always refused
+        assert_eq!(
+            DuplicateConnection::Refuse,
+            find_duplicate_connection(
+                &[connected(to_b_at_a, id_b, false)],
+                id_a,
+                id_b,
+                get_dummy_socket_address(5),
+                false
+            )
+        );
+        assert_eq!(
+            DuplicateConnection::None,
+            find_duplicate_connection(
+                &[connected(to_b_at_a, id_b, false)],
+                id_a,
+                3,
+                get_dummy_socket_address(5),
+                false
+            )
+        );
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_incoming_connection_succeed() -> Result<()> {
@@ -795,6 +1292,7 @@ mod connect_tests {
             &own_handshake,
             &other_handshake,
             &peer_address,
+            false,
         )
         .await;
         assert_eq!(
@@ -954,4 +1452,57 @@ mod connect_tests {
 
         Ok(())
     }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn dual_stack_listeners_accept_ipv4_peers() -> Result<()> {
+        let (listeners, addresses) =
+            bind_peer_listeners(&[SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0)]).await?;
+        let port = addresses[0].port();
+        assert!(port != 0);
+        assert!(addresses.iter().all(|address| address.port() == port));
+        assert!(addresses.contains(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), port)));
+
+        let _stream = tokio::net::TcpStream::connect((Ipv4Addr::LOCALHOST, port)).await?;
+        let (accepted, _, _) = futures::future::select_all(
+            listeners.iter().map(|listener| Box::pin(listener.accept())),
+        )
+        .await;
+        let (_, peer_address) = accepted?;
+        assert_eq!(
+            IpAddr::from(Ipv4Addr::LOCALHOST),
+            canonical_peer_address(peer_address).ip()
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn listeners_are_bound_on_every_address() -> Result<()> {
+        let (listeners, addresses) = bind_peer_listeners(&[
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0),
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0),
+        ])
+        .await?;
+        assert_eq!(2, listeners.len());
+        assert_eq!(2, addresses.len());
+        assert!(addresses[0].port() != addresses[1].port());
+        for address in addresses {
+            tokio::net::TcpStream::connect(address).await?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn ipv4_mapped_peer_addresses_are_canonicalized() {
+        let mapped: SocketAddr = "[::ffff:192.0.2.1]:9798".parse().unwrap();
+        let ipv6: SocketAddr = "[2001:db8::1]:9798".parse().unwrap();
+        assert_eq!(
+            "192.0.2.1:9798".parse::<SocketAddr>().unwrap(),
+            canonical_peer_address(mapped)
+        );
+        assert_eq!(ipv6, canonical_peer_address(ipv6));
+    }
 }
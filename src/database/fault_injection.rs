@@ -0,0 +1,66 @@
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+
+/// The storage writes of the archival state, in the order a new tip makes them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WritePoint {
+    /// The block is written to its block file
+    BlockFile,
+
+    /// The block index is updated, marking the block as tip
+    BlockIndex,
+
+    /// The mutator set is updated with the block
+    MutatorSet,
+}
+
+impl Display for WritePoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let string = match self {
+            WritePoint::BlockFile => "block file",
+            WritePoint::BlockIndex => "block index",
+            WritePoint::MutatorSet => "mutator set",
+        };
+        write!(f, "{string}")
+    }
+}
+
+/// Fails storage writes on demand, so that tests can interrupt the archival
+/// state at any write and check that it recovers on restart.
+///
+/// Once a fault is injected, every later write fails too, as after a power
+/// cut nothing more is written.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    fail_at_write: Option<usize>,
+    fail_at_point: Option<WritePoint>,
+    write_count: usize,
+    powered_off: bool,
+}
+
+impl FaultInjector {
+    /// Fail the `n`th write from now on, counting from one
+    pub fn fail_nth_write(&mut self, n: usize) {
+        self.fail_at_write = Some(self.write_count + n);
+    }
+
+    /// Fail the next write to `point`, and all writes after it
+    pub fn cut_power_before(&mut self, point: WritePoint) {
+        self.fail_at_point = Some(point);
+    }
+
+    /// Called before each write. Fails if the write must not happen.
+    pub fn before_write(&mut self, point: WritePoint) -> Result<()> {
+        self.write_count += 1;
+        if self.fail_at_write == Some(self.write_count) || self.fail_at_point == Some(point) {
+            self.powered_off = true;
+        }
+
+        if self.powered_off {
+            bail!("Injected fault: {point} write #{} failed", self.write_count);
+        }
+
+        Ok(())
+    }
+}
@@ -251,6 +251,54 @@ impl DbIntMut {
     }
 }
 
+impl DbIntMut {
+    /// Take a [`DbSnapshot`] of the database as of now
+    pub fn owned_snapshot(&self) -> DbSnapshot {
+        let db = self.db.clone().unwrap();
+
+        // SAFETY: the snapshot borrows the database, which `db` keeps alive,
+        // and in place, for as long as the snapshot exists
+        let snapshot =
+            unsafe { std::mem::transmute::<Snapshot<'_>, Snapshot<'static>>(db.snapshot()) };
+
+        DbSnapshot {
+            snapshot,
+            _db: db,
+            read_options: self.read_options.clone(),
+        }
+    }
+}
+
+/// A read-only view of a database as of the moment it was taken, unaffected
+/// by later writes. Unlike a [`Snapshot`], it holds on to the database rather
+/// than borrowing it, so it can be kept across tasks.
+pub struct DbSnapshot {
+    // Declared before `_db`, so that the snapshot is released before the
+    // database may be closed
+    snapshot: Snapshot<'static>,
+    _db: Arc<Database>,
+    read_options: ReadOptions,
+}
+
+// SAFETY: the LevelDB C++ API allows reading a snapshot from any thread
+unsafe impl Send for DbSnapshot {}
+unsafe impl Sync for DbSnapshot {}
+
+impl DbSnapshot {
+    /// Get the value of a key as of the snapshot
+    #[inline]
+    pub fn get_u8(&self, key: &[u8]) -> Result<Option<Vec<u8>>, DbError> {
+        let snapshot: &Snapshot<'_> = &self.snapshot;
+        snapshot.get_u8(&self.read_options, key)
+    }
+}
+
+impl std::fmt::Debug for DbSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DbSnapshot").finish()
+    }
+}
+
 impl Drop for DbIntMut {
     #[inline]
     fn drop(&mut self) {
@@ -562,6 +610,14 @@ impl<'a> Iterable<'a> for DB {
     }
 }
 
+impl DB {
+    /// Take a [`DbSnapshot`] of the database as of now
+    #[inline]
+    pub fn owned_snapshot(&self) -> DbSnapshot {
+        self.0.owned_snapshot()
+    }
+}
+
 impl Snapshots for DB {
     fn snapshot(&self) -> Snapshot {
         self.0.snapshot()
@@ -1,3 +1,5 @@
+#[cfg(test)]
+pub mod fault_injection;
 pub mod leveldb;
 mod neptune_leveldb;
 pub mod storage;
@@ -1,4 +1,4 @@
-use super::leveldb::DB;
+use super::leveldb::{DbSnapshot, DB};
 use anyhow::Result;
 use leveldb::{
     batch::WriteBatch,
@@ -10,6 +10,7 @@ use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::Arc;
 use tokio::task;
 
 struct NeptuneLevelDbInternal<Key, Value>
@@ -18,6 +19,11 @@ where
     Value: Serialize + DeserializeOwned,
 {
     database: DB,
+
+    // Set if this is a read-only view of the database, see
+    // [`NeptuneLevelDb::snapshot`]
+    snapshot: Option<Arc<DbSnapshot>>,
+
     _key: PhantomData<Key>,
     _value: PhantomData<Value>,
 }
@@ -30,6 +36,7 @@ where
     fn from(database: DB) -> Self {
         Self {
             database,
+            snapshot: None,
             _key: Default::default(),
             _value: Default::default(),
         }
@@ -44,6 +51,7 @@ where
     fn clone(&self) -> Self {
         Self {
             database: self.database.clone(),
+            snapshot: self.snapshot.clone(),
             _key: Default::default(),
             _value: Default::default(),
         }
@@ -89,6 +97,7 @@ where
         let database = DB::open_with_options(db_path, options, read_options, write_options)?;
         let database = Self {
             database,
+            snapshot: None,
             _key: PhantomData,
             _value: PhantomData,
         };
@@ -97,25 +106,38 @@ where
 
     fn get(&self, key: Key) -> Option<Value> {
         let key_bytes: Vec<u8> = bincode::serialize(&key).unwrap();
-        let value_bytes: Option<Vec<u8>> = self.database.get(&key_bytes).unwrap();
+        let value_bytes: Option<Vec<u8>> = self.get_u8(&key_bytes);
         value_bytes.map(|bytes| bincode::deserialize(&bytes).unwrap())
     }
 
-    fn get_u8(&mut self, key: &[u8]) -> Option<Vec<u8>> {
-        self.database.get_u8(key).unwrap()
+    fn get_u8(&self, key: &[u8]) -> Option<Vec<u8>> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.get_u8(key).unwrap(),
+            None => self.database.get_u8(key).unwrap(),
+        }
+    }
+
+    fn check_writable(&self) {
+        assert!(
+            self.snapshot.is_none(),
+            "A database snapshot cannot be written to"
+        );
     }
 
     fn put(&mut self, key: Key, value: Value) {
+        self.check_writable();
         let key_bytes: Vec<u8> = bincode::serialize(&key).unwrap();
         let value_bytes: Vec<u8> = bincode::serialize(&value).unwrap();
         self.database.put(&key_bytes, &value_bytes).unwrap();
     }
 
     fn put_u8(&mut self, key: &[u8], value: &[u8]) {
+        self.check_writable();
         self.database.put_u8(key, value).unwrap()
     }
 
     fn batch_write(&mut self, entries: WriteBatchAsync<Key, Value>) {
+        self.check_writable();
         let batch = WriteBatch::new();
         for op in entries.0.into_iter() {
             match op {
@@ -135,6 +157,7 @@ where
     }
 
     fn delete(&mut self, key: Key) -> Option<Value> {
+        self.check_writable();
         let key_bytes: Vec<u8> = bincode::serialize(&key).unwrap(); // add safety
         let value_bytes: Option<Vec<u8>> = self.database.get(&key_bytes).unwrap();
         let value_object = value_bytes.map(|bytes| bincode::deserialize(&bytes).unwrap());
@@ -147,6 +170,7 @@ where
     }
 
     fn flush(&mut self) {
+        self.check_writable();
         self.database
             .write(&WriteBatch::new(), true)
             .expect("Database flushing to disk must succeed");
@@ -238,7 +262,7 @@ where
     }
 
     pub async fn get_u8(&self, key: Vec<u8>) -> Option<Vec<u8>> {
-        let mut inner = self.0.clone();
+        let inner = self.0.clone();
         task::spawn_blocking(move || inner.get_u8(&key))
             .await
             .unwrap()
@@ -281,6 +305,20 @@ where
         task::spawn_blocking(move || inner.flush()).await.unwrap()
     }
 
+    /// A read-only view of the database as of now, which later writes to the
+    /// database do not change. Reads go through a LevelDB snapshot, so taking
+    /// one copies nothing. Writing to the view panics, and [`Self::iter`]
+    /// does not see through it.
+    pub fn snapshot(&self) -> Self {
+        if self.0.snapshot.is_some() {
+            return self.clone();
+        }
+
+        let mut inner = self.0.clone();
+        inner.snapshot = Some(Arc::new(inner.database.owned_snapshot()));
+        Self(inner)
+    }
+
     /// returns the directory path of the database files on disk.
     #[inline]
     pub fn path(&self) -> &std::path::PathBuf {
@@ -59,4 +59,10 @@ impl SimpleRustyStorage {
         );
         Self { schema, db }
     }
+
+    /// The database that the tables are stored in
+    #[inline]
+    pub fn db(&self) -> &NeptuneLevelDb<RustyKey, RustyValue> {
+        &self.db
+    }
 }
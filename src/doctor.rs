@@ -0,0 +1,428 @@
+//! `neptune-core doctor`: checks that the node can start, without starting it.
+//!
+//! Each check reports what it found and, if something is wrong, what to do
+//! about it. The report is printed for humans, or as JSON with `--json` for
+//! provisioning scripts, and the command fails if any check failed. Warnings
+//! point out problems that do not prevent the node from starting.
+//!
+//! The checks touch the data directory as the node would: missing directories
+//! and databases are created, but nothing is written to existing ones.
+
+use std::fmt::Display;
+use std::net::{SocketAddr, TcpListener};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Result};
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use systemstat::{Platform, System};
+
+use crate::config_models::cli_args::Args;
+use crate::config_models::data_directory::DataDirectory;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::archival_state::ArchivalState;
+use crate::models::state::networking_state::NetworkingState;
+use crate::models::state::wallet::WalletSecret;
+
+/// Less free disk space than this fails the disk space check
+pub const MIN_FREE_DISK_SPACE: ByteSize = ByteSize::gb(1);
+
+/// Less free disk space than this is warned about
+pub const RECOMMENDED_FREE_DISK_SPACE: ByteSize = ByteSize::gb(10);
+
+const PROBE_FILE_NAME: &str = ".doctor_probe";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Failed,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: CheckStatus,
+
+    /// What the check found
+    pub detail: String,
+
+    /// What to do about it, if anything is wrong
+    pub advice: Option<String>,
+}
+
+impl DoctorCheck {
+    fn ok(name: &str, detail: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Ok,
+            detail,
+            advice: None,
+        }
+    }
+
+    fn warning(name: &str, detail: String, advice: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Warning,
+            detail,
+            advice: Some(advice),
+        }
+    }
+
+    fn failed(name: &str, detail: String, advice: String) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Failed,
+            detail,
+            advice: Some(advice),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoctorReport {
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    /// Run all checks against the configuration in `cli`
+    pub async fn collect(cli: &Args) -> Self {
+        let mut checks = vec![];
+
+        let data_dir = match DataDirectory::get(cli.data_dir.clone(), cli.network) {
+            Ok(data_dir) => data_dir,
+            Err(err) => {
+                checks.push(DoctorCheck::failed(
+                    "data directory",
+                    format!("{err:#}"),
+                    "Choose a data directory with --data-dir.".to_string(),
+                ));
+                return Self { checks };
+            }
+        };
+
+        let data_dir_check = check_data_directory(&data_dir.root_dir_path()).await;
+        let data_dir_usable = data_dir_check.status != CheckStatus::Failed;
+        checks.push(data_dir_check);
+        checks.push(check_disk_space(&data_dir.root_dir_path()));
+        checks.push(check_clock(cli, &data_dir, SystemTime::now()));
+        checks.extend(check_ports(cli));
+
+        // The remaining checks need a usable data directory
+        if data_dir_usable {
+            checks.push(check_databases(&data_dir).await);
+            checks.push(check_wallet(cli, &data_dir));
+        }
+
+        Self { checks }
+    }
+
+    /// True if no check failed
+    pub fn passed(&self) -> bool {
+        self.checks
+            .iter()
+            .all(|check| check.status != CheckStatus::Failed)
+    }
+}
+
+impl Display for DoctorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                CheckStatus::Ok => "  OK  ",
+                CheckStatus::Warning => " WARN ",
+                CheckStatus::Failed => " FAIL ",
+            };
+            writeln!(f, "[{status}] {}: {}", check.name, check.detail)?;
+            if let Some(advice) = &check.advice {
+                writeln!(f, "         {advice}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Run the checks, print the report, and fail if any check failed
+pub async fn run(mut cli: Args, json: bool) -> Result<()> {
+    let profile = cli.profile;
+    profile.apply(&mut cli)?;
+
+    let report = DoctorReport::collect(&cli).await;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{report}");
+    }
+
+    if !report.passed() {
+        let failed_count = report
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+            .count();
+        bail!("{failed_count} of {} checks failed", report.checks.len());
+    }
+
+    Ok(())
+}
+
+async fn check_data_directory(root_dir: &Path) -> DoctorCheck {
+    const NAME: &str = "data directory";
+    let advice = format!(
+        "Check the ownership and permissions of {}, or choose another directory with --data-dir.",
+        root_dir.display()
+    );
+    if let Err(err) = DataDirectory::create_dir_if_not_exists(root_dir).await {
+        return DoctorCheck::failed(NAME, format!("{err:#}"), advice);
+    }
+
+    let probe_path = root_dir.join(PROBE_FILE_NAME);
+    let probe =
+        std::fs::write(&probe_path, b"probe").and_then(|_| std::fs::remove_file(&probe_path));
+    match probe {
+        Ok(()) => DoctorCheck::ok(NAME, format!("{} is writable", root_dir.display())),
+        Err(err) => DoctorCheck::failed(
+            NAME,
+            format!("{} is not writable: {err}", root_dir.display()),
+            advice,
+        ),
+    }
+}
+
+fn check_disk_space(root_dir: &Path) -> DoctorCheck {
+    const NAME: &str = "disk space";
+    let available = match System::new().mount_at(root_dir) {
+        Ok(filesystem) => ByteSize(filesystem.avail.as_u64()),
+        Err(err) => {
+            return DoctorCheck::warning(
+                NAME,
+                format!("Could not determine the free space: {err}"),
+                format!(
+                    "Make sure that the disk holding {} has at least {RECOMMENDED_FREE_DISK_SPACE} free.",
+                    root_dir.display()
+                ),
+            )
+        }
+    };
+
+    assess_disk_space(available, root_dir)
+}
+
+fn assess_disk_space(available: ByteSize, root_dir: &Path) -> DoctorCheck {
+    const NAME: &str = "disk space";
+    let detail = format!("{available} free on the disk holding the data directory");
+    let advice = format!(
+        "Free up space, or move the data directory to a larger disk with --data-dir. At least {RECOMMENDED_FREE_DISK_SPACE} is recommended for {}.",
+        root_dir.display()
+    );
+    if available < MIN_FREE_DISK_SPACE {
+        DoctorCheck::failed(NAME, detail, advice)
+    } else if available < RECOMMENDED_FREE_DISK_SPACE {
+        DoctorCheck::warning(NAME, detail, advice)
+    } else {
+        DoctorCheck::ok(NAME, detail)
+    }
+}
+
+/// The clock must not be before the launch of the network, nor before the
+/// last start of the node, which is when the entropy seed file was written.
+fn check_clock(cli: &Args, data_dir: &DataDirectory, now: SystemTime) -> DoctorCheck {
+    const NAME: &str = "clock";
+    let advice = "Set the system clock, e.g. by enabling NTP synchronization.".to_string();
+    let now_timestamp = Timestamp::millis(
+        now.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+    );
+    let launch_date = cli.network.launch_date();
+    if now_timestamp < launch_date {
+        return DoctorCheck::failed(
+            NAME,
+            format!(
+                "The system clock, {}, is before the launch of {}, {}",
+                now_timestamp.standard_format(),
+                cli.network,
+                launch_date.standard_format()
+            ),
+            advice,
+        );
+    }
+
+    let last_start = std::fs::metadata(data_dir.entropy_seed_file_path())
+        .and_then(|metadata| metadata.modified())
+        .ok();
+    if let Some(last_start) = last_start {
+        let tolerance = Duration::from_secs(cli.max_clock_skew);
+        if let Ok(behind) = last_start.duration_since(now) {
+            if behind > tolerance {
+                return DoctorCheck::warning(
+                    NAME,
+                    format!(
+                        "The system clock is {}s behind the last start of the node",
+                        behind.as_secs()
+                    ),
+                    advice,
+                );
+            }
+        }
+    }
+
+    DoctorCheck::ok(NAME, now_timestamp.standard_format())
+}
+
+/// The ports the node listens on must be free
+fn check_ports(cli: &Args) -> Vec<DoctorCheck> {
+    let mut addresses = cli
+        .peer_listen_addresses()
+        .into_iter()
+        .map(|address| ("peer port", address, "--peer-port or --listen-addr"))
+        .collect::<Vec<_>>();
+    #[cfg(feature = "rpc")]
+    addresses.push((
+        "RPC port",
+        SocketAddr::new(cli.rpc_listen_addr, cli.rpc_port),
+        "--rpc-port",
+    ));
+    if let Some(health_port) = cli.health_port {
+        addresses.push((
+            "health port",
+            SocketAddr::new(cli.listen_addr[0].ip, health_port),
+            "--health-port",
+        ));
+    }
+
+    addresses
+        .into_iter()
+        .map(|(name, address, option)| match TcpListener::bind(address) {
+            Ok(_) => DoctorCheck::ok(name, format!("{address} is available")),
+            Err(err) => DoctorCheck::failed(
+                name,
+                format!("Cannot listen on {address}: {err}"),
+                format!("Stop the program using the port, e.g. another node, or choose another port with {option}."),
+            ),
+        })
+        .collect()
+}
+
+async fn check_databases(data_dir: &DataDirectory) -> DoctorCheck {
+    const NAME: &str = "databases";
+    let opened = async {
+        ArchivalState::initialize_block_index_database(data_dir).await?;
+        NetworkingState::initialize_peer_databases(data_dir).await?;
+        ArchivalState::initialize_mutator_set(data_dir).await?;
+        anyhow::Ok(())
+    }
+    .await;
+
+    match opened {
+        Ok(()) => DoctorCheck::ok(NAME, "All databases open".to_string()),
+        Err(err) => DoctorCheck::failed(
+            NAME,
+            format!("{err:#}"),
+            format!(
+                "Stop any node running on {}. If none is, the database may be corrupt; restore it from a backup or remove it to resync.",
+                data_dir.root_dir_path().display()
+            ),
+        ),
+    }
+}
+
+fn check_wallet(cli: &Args, data_dir: &DataDirectory) -> DoctorCheck {
+    const NAME: &str = "wallet";
+    if cli.no_wallet {
+        return DoctorCheck::ok(NAME, "Skipped, running without a wallet".to_string());
+    }
+
+    let wallet_file = WalletSecret::wallet_secret_path(&data_dir.wallet_directory_path());
+    if !wallet_file.exists() {
+        return DoctorCheck::ok(
+            NAME,
+            format!(
+                "No wallet at {}. A new one is created on start.",
+                wallet_file.display()
+            ),
+        );
+    }
+
+    if let Err(err) = WalletSecret::read_from_file(&wallet_file) {
+        return DoctorCheck::failed(
+            NAME,
+            format!("{err:#}"),
+            "Restore the wallet file from a backup, or recover the wallet from its seed phrase."
+                .to_string(),
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if let Ok(metadata) = std::fs::metadata(&wallet_file) {
+            if metadata.permissions().mode() & 0o077 != 0 {
+                return DoctorCheck::warning(
+                    NAME,
+                    format!("{} is readable by other users", wallet_file.display()),
+                    format!("Run `chmod 600 {}`.", wallet_file.display()),
+                );
+            }
+        }
+    }
+
+    DoctorCheck::ok(NAME, format!("{} is intact", wallet_file.display()))
+}
+
+#[cfg(test)]
+mod doctor_tests {
+    use crate::config_models::network::Network;
+    use crate::tests::shared::unit_test_data_directory;
+
+    use super::*;
+
+    #[test]
+    fn disk_space_thresholds() {
+        let root_dir = Path::new("/data");
+        assert_eq!(
+            CheckStatus::Failed,
+            assess_disk_space(ByteSize::mb(500), root_dir).status
+        );
+        assert_eq!(
+            CheckStatus::Warning,
+            assess_disk_space(ByteSize::gb(5), root_dir).status
+        );
+        assert_eq!(
+            CheckStatus::Ok,
+            assess_disk_space(ByteSize::gb(50), root_dir).status
+        );
+    }
+
+    #[tokio::test]
+    async fn corrupt_wallet_file_fails_the_wallet_check() {
+        let network = Network::RegTest;
+        let data_dir = unit_test_data_directory(network).unwrap();
+        let cli = Args {
+            network,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            CheckStatus::Ok,
+            check_data_directory(&data_dir.root_dir_path()).await.status
+        );
+        assert_eq!(CheckStatus::Ok, check_databases(&data_dir).await.status);
+        assert_eq!(CheckStatus::Ok, check_wallet(&cli, &data_dir).status);
+
+        let wallet_dir = data_dir.wallet_directory_path();
+        std::fs::create_dir_all(&wallet_dir).unwrap();
+        std::fs::write(
+            WalletSecret::wallet_secret_path(&wallet_dir),
+            "not a wallet",
+        )
+        .unwrap();
+        let check = check_wallet(&cli, &data_dir);
+        assert_eq!(CheckStatus::Failed, check.status);
+        assert!(check.advice.is_some());
+    }
+}
@@ -0,0 +1,287 @@
+//! Runs user-configured external commands when chain events occur, in the
+//! spirit of bitcoind's `-blocknotify`.
+//!
+//! Each command is executed through the system shell. The details of the
+//! event are passed to it both as `NEPTUNE_*` environment variables and as a
+//! single JSON object written to its stdin.
+
+use std::process::Stdio;
+
+use serde_json::{Map, Value};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use crate::config_models::cli_args::Args;
+use crate::models::channel::ChainEvent;
+
+/// Prefix of the environment variables that carry event details
+const ENV_VAR_PREFIX: &str = "NEPTUNE_";
+
+/// Return true if at least one event hook was set on the command line.
+pub fn any_hook_configured(cli: &Args) -> bool {
+    cli.on_new_block.is_some()
+        || cli.on_wallet_receive.is_some()
+        || cli.on_reorg.is_some()
+        || cli.on_double_spend.is_some()
+        || cli.on_watched_transaction.is_some()
+        || cli.on_disk_space.is_some()
+}
+
+/// Listen for chain events and run the configured command for each of them.
+///
+/// Commands are not awaited before the next event is handled, so a slow
+/// command does not hold back later notifications.
+pub async fn run(mut chain_event_rx: broadcast::Receiver<ChainEvent>, cli: Args) {
+    loop {
+        let event = match chain_event_rx.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped_count)) => {
+                warn!("Event hooks fell behind. Skipped {skipped_count} chain events.");
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        let Some(command) = hook_command(&cli, &event) else {
+            continue;
+        };
+
+        if let Err(err) = spawn_hook(command, &event) {
+            warn!("Failed to run {} hook `{command}`: {err}", event.get_type());
+        }
+    }
+}
+
+/// Return the command configured for this type of event, if any.
+fn hook_command<'a>(cli: &'a Args, event: &ChainEvent) -> Option<&'a str> {
+    match event {
+        ChainEvent::NewBlock { .. } => cli.on_new_block.as_deref(),
+        ChainEvent::Reorg { .. } => cli.on_reorg.as_deref(),
+        ChainEvent::WalletReceive { .. } => cli.on_wallet_receive.as_deref(),
+        ChainEvent::DoubleSpend { .. } => cli.on_double_spend.as_deref(),
+        ChainEvent::TransactionConfirmed { .. } | ChainEvent::TransactionReorgedOut { .. } => {
+            cli.on_watched_transaction.as_deref()
+        }
+        ChainEvent::DiskSpaceLow { .. } | ChainEvent::DiskSpaceRecovered { .. } => {
+            cli.on_disk_space.as_deref()
+        }
+    }
+}
+
+/// Return the details of an event as a list of named values. The type of the
+/// event is always the first entry.
+fn event_fields(event: &ChainEvent) -> Vec<(&'static str, Value)> {
+    let mut fields = vec![("event", Value::from(event.get_type()))];
+    match event {
+        ChainEvent::NewBlock {
+            digest,
+            height,
+            timestamp,
+        } => {
+            fields.push(("block_digest", Value::from(digest.to_hex())));
+            fields.push(("block_height", Value::from(u64::from(*height))));
+            fields.push(("block_timestamp", Value::from(timestamp.0.value())));
+        }
+        ChainEvent::Reorg {
+            old_tip_digest,
+            old_tip_height,
+            new_tip_digest,
+            new_tip_height,
+        } => {
+            fields.push(("old_tip_digest", Value::from(old_tip_digest.to_hex())));
+            fields.push(("old_tip_height", Value::from(u64::from(*old_tip_height))));
+            fields.push(("new_tip_digest", Value::from(new_tip_digest.to_hex())));
+            fields.push(("new_tip_height", Value::from(u64::from(*new_tip_height))));
+        }
+        ChainEvent::WalletReceive {
+            block_digest,
+            height,
+            amount,
+            utxo_count,
+        } => {
+            fields.push(("block_digest", Value::from(block_digest.to_hex())));
+            fields.push(("block_height", Value::from(u64::from(*height))));
+            fields.push(("amount", Value::from(amount.to_string())));
+            fields.push(("utxo_count", Value::from(*utxo_count)));
+        }
+        ChainEvent::DoubleSpend {
+            own_transaction_digest,
+            conflicting_digest,
+            in_block,
+        } => {
+            fields.push((
+                "own_transaction_digest",
+                Value::from(own_transaction_digest.to_hex()),
+            ));
+            fields.push((
+                "conflicting_digest",
+                Value::from(conflicting_digest.to_hex()),
+            ));
+            fields.push(("in_block", Value::from(*in_block)));
+        }
+        ChainEvent::TransactionConfirmed {
+            transaction_digest,
+            block_digest,
+            confirmations,
+        } => {
+            fields.push((
+                "transaction_digest",
+                Value::from(transaction_digest.to_hex()),
+            ));
+            fields.push(("block_digest", Value::from(block_digest.to_hex())));
+            fields.push(("confirmations", Value::from(*confirmations)));
+        }
+        ChainEvent::TransactionReorgedOut {
+            transaction_digest,
+            block_digest,
+        } => {
+            fields.push((
+                "transaction_digest",
+                Value::from(transaction_digest.to_hex()),
+            ));
+            fields.push(("block_digest", Value::from(block_digest.to_hex())));
+        }
+        ChainEvent::DiskSpaceLow {
+            available,
+            min_free,
+        } => {
+            fields.push(("available_bytes", Value::from(*available)));
+            fields.push(("min_free_bytes", Value::from(*min_free)));
+        }
+        ChainEvent::DiskSpaceRecovered { available } => {
+            fields.push(("available_bytes", Value::from(*available)));
+        }
+    }
+    fields
+}
+
+/// Build the JSON object that is written to the stdin of a hook command.
+fn event_json(event: &ChainEvent) -> Value {
+    Value::Object(
+        event_fields(event)
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect::<Map<String, Value>>(),
+    )
+}
+
+/// Build the environment variables that are passed to a hook command.
+fn event_env_vars(event: &ChainEvent) -> Vec<(String, String)> {
+    event_fields(event)
+        .into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::String(string) => string,
+                other => other.to_string(),
+            };
+            (format!("{ENV_VAR_PREFIX}{}", name.to_uppercase()), value)
+        })
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// Start the hook command and hand it the event. The spawned task writes the
+/// event to the command's stdin and logs its exit status.
+fn spawn_hook(command: &str, event: &ChainEvent) -> std::io::Result<()> {
+    let mut child = shell_command(command)
+        .envs(event_env_vars(event))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    let stdin_payload = format!("{}\n", event_json(event));
+    let command = command.to_owned();
+    let event_type = event.get_type();
+    tokio::spawn(async move {
+        if let Some(mut stdin) = child.stdin.take() {
+            // A command that ignores its stdin may exit before we are done writing.
+            if let Err(err) = stdin.write_all(stdin_payload.as_bytes()).await {
+                debug!("Could not write event to stdin of {event_type} hook: {err}");
+            }
+        }
+
+        match child.wait().await {
+            Ok(status) if status.success() => {
+                debug!("{event_type} hook `{command}` finished");
+            }
+            Ok(status) => warn!("{event_type} hook `{command}` exited with {status}"),
+            Err(err) => warn!("Failed to wait for {event_type} hook `{command}`: {err}"),
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod event_hooks_tests {
+    use crate::models::blockchain::block::block_height::BlockHeight;
+    use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+    use crate::prelude::twenty_first::math::digest::Digest;
+
+    use super::*;
+
+    #[test]
+    fn hook_is_selected_by_event_type() {
+        let cli = Args {
+            on_new_block: Some("new-block-cmd".to_string()),
+            on_reorg: Some("reorg-cmd".to_string()),
+            ..Default::default()
+        };
+        assert!(any_hook_configured(&cli));
+        assert!(!any_hook_configured(&Args::default()));
+
+        let wallet_receive = ChainEvent::WalletReceive {
+            block_digest: Digest::default(),
+            height: BlockHeight::from(2u64),
+            amount: NeptuneCoins::new(5),
+            utxo_count: 1,
+        };
+        let reorg = ChainEvent::Reorg {
+            old_tip_digest: Digest::default(),
+            old_tip_height: BlockHeight::from(4u64),
+            new_tip_digest: Digest::default(),
+            new_tip_height: BlockHeight::from(4u64),
+        };
+        assert_eq!(Some("reorg-cmd"), hook_command(&cli, &reorg));
+        assert_eq!(None, hook_command(&cli, &wallet_receive));
+    }
+
+    #[test]
+    fn env_vars_and_json_carry_same_fields() {
+        let event = ChainEvent::WalletReceive {
+            block_digest: Digest::default(),
+            height: BlockHeight::from(7u64),
+            amount: NeptuneCoins::new(3),
+            utxo_count: 2,
+        };
+
+        let env_vars = event_env_vars(&event);
+        assert!(env_vars.contains(&("NEPTUNE_EVENT".to_string(), "wallet_receive".to_string())));
+        assert!(env_vars.contains(&("NEPTUNE_BLOCK_HEIGHT".to_string(), "7".to_string())));
+        assert!(env_vars.contains(&("NEPTUNE_UTXO_COUNT".to_string(), "2".to_string())));
+
+        let json = event_json(&event);
+        assert_eq!(env_vars.len(), json.as_object().unwrap().len());
+        assert_eq!(Value::from(7u64), json["block_height"]);
+        assert_eq!(
+            Value::from(Digest::default().to_hex()),
+            json["block_digest"]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! A minimal HTTP server answering `GET /health` and `GET /ready`, meant for
+//! orchestration tools such as Kubernetes probes or a systemd watchdog.
+//!
+//! `/health` answers `200 OK` as long as the node is running, i.e. its
+//! databases were opened and the main loop has started. `/ready` answers
+//! `200 OK` only if the tip is recent and enough peers are connected, and
+//! `503 Service Unavailable` otherwise. Both return a JSON [`HealthReport`].
+//!
+//! `GET /metrics` returns the JSON
+//! [`ConnectionMetricsReport`](crate::models::state::connection_metrics::ConnectionMetricsReport)
+//! of the connections to peers.
+
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::alerts::Alert;
+use crate::models::state::{GlobalState, GlobalStateLock};
+
+/// Requests longer than this are rejected
+const MAX_REQUEST_SIZE_IN_BYTES: usize = 8 * 1024;
+
+/// Connections that have not sent a whole request within this time are closed
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause after failing to accept a connection
+const ACCEPT_ERROR_PAUSE: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub databases_open: bool,
+    pub tip_height: BlockHeight,
+    pub tip_age_secs: u64,
+    pub tip_is_recent: bool,
+    pub peer_count: usize,
+    pub has_enough_peers: bool,
+    pub syncing: bool,
+
+    /// True if our clock deviates from peers or the tip by more than
+    /// `--max-clock-skew`. Does not affect readiness.
+    pub clock_skewed: bool,
+
+    /// True if the tip has not changed for several block intervals while
+    /// peers report higher tips. Does not affect readiness, which already
+    /// requires a recent tip.
+    pub tip_stale: bool,
+    pub median_peer_clock_offset_millis: Option<i64>,
+
+    /// True while the free disk space is below `--min-free-disk-space`, so
+    /// that blocks are not stored. Does not affect readiness, which already
+    /// requires a recent tip.
+    pub disk_space_low: bool,
+
+    /// Network alerts from the developers that have not expired, most severe
+    /// first. Do not affect readiness.
+    pub alerts: Vec<Alert>,
+
+    /// True if the node is fit to serve requests: its tip is recent and it
+    /// has enough peers.
+    pub ready: bool,
+}
+
+impl HealthReport {
+    /// Assess the state of the node against the thresholds from the CLI arguments.
+    pub fn from_state(global_state: &GlobalState, now: Timestamp) -> Self {
+        let cli = global_state.cli();
+        let tip_header = global_state.chain.light_state().header();
+        let tip_age_secs = if now > tip_header.timestamp {
+            (now - tip_header.timestamp).0.value() / 1000
+        } else {
+            0
+        };
+        let tip_is_recent = tip_age_secs <= cli.health_max_tip_age;
+        let peer_count = global_state.net.peer_map.len();
+        let has_enough_peers = peer_count >= cli.health_min_peers;
+
+        Self {
+            // The state can only exist after all databases were opened.
+            databases_open: true,
+            tip_height: tip_header.height,
+            tip_age_secs,
+            tip_is_recent,
+            peer_count,
+            has_enough_peers,
+            syncing: global_state.net.syncing,
+            clock_skewed: global_state.net.clock_skew.skewed,
+            tip_stale: global_state.net.tip_staleness.stale,
+            median_peer_clock_offset_millis: global_state.net.clock_skew.median_peer_offset_millis,
+            disk_space_low: global_state.net.disk_space.low,
+            alerts: global_state
+                .net
+                .alerts
+                .active(now)
+                .into_iter()
+                .map(|signed_alert| signed_alert.alert)
+                .collect(),
+            ready: tip_is_recent && has_enough_peers,
+        }
+    }
+}
+
+/// Serve health checks on the given address until the task is aborted.
+pub async fn run(listen_address: SocketAddr, global_state_lock: GlobalStateLock) -> Result<()> {
+    let listener = TcpListener::bind(listen_address).await?;
+    info!("Health-check endpoint listening on {listen_address}");
+
+    loop {
+        // Accepting fails e.g. when the process runs out of file descriptors,
+        // which must not end the health checks for good. Pausing keeps the
+        // loop from spinning until descriptors are freed.
+        let (stream, peer_address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("Failed to accept health-check connection: {err}");
+                tokio::time::sleep(ACCEPT_ERROR_PAUSE).await;
+                continue;
+            }
+        };
+        let state = global_state_lock.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, state).await {
+                debug!("Health-check request from {peer_address} failed: {err}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    global_state_lock: GlobalStateLock,
+) -> Result<()> {
+    let mut request = vec![];
+    let read_request = async {
+        let mut buffer = [0u8; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+            let read_count = stream.read(&mut buffer).await?;
+            if read_count == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..read_count]);
+            if request.len() > MAX_REQUEST_SIZE_IN_BYTES {
+                return anyhow::Ok(false);
+            }
+        }
+
+        Ok(true)
+    };
+    match tokio::time::timeout(REQUEST_READ_TIMEOUT, read_request).await {
+        Ok(Ok(true)) => (),
+        Ok(Ok(false)) => {
+            warn!("Rejecting oversized health-check request");
+            return write_response(&mut stream, "413 Payload Too Large", "").await;
+        }
+        Ok(Err(err)) => return Err(err),
+        Err(_) => {
+            debug!("Closing health-check connection that sent no request in time");
+            return Ok(());
+        }
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (method, path) = (request_line.next(), request_line.next());
+
+    if method != Some("GET") {
+        return write_response(&mut stream, "405 Method Not Allowed", "").await;
+    }
+
+    if path == Some("/metrics") {
+        let report = global_state_lock
+            .lock_guard()
+            .await
+            .net
+            .connection_metrics
+            .report(SystemTime::now());
+        return write_response(&mut stream, "200 OK", &serde_json::to_string(&report)?).await;
+    }
+
+    let report = HealthReport::from_state(&*global_state_lock.lock_guard().await, Timestamp::now());
+    let body = serde_json::to_string(&report)?;
+    let status = match path {
+        Some("/health") => "200 OK",
+        Some("/ready") if report.ready => "200 OK",
+        Some("/ready") => "503 Service Unavailable",
+        _ => return write_response(&mut stream, "404 Not Found", "").await,
+    };
+
+    write_response(&mut stream, status, &body).await
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, body: &str) -> Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod health_server_tests {
+    use crate::config_models::cli_args;
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::mock_genesis_global_state;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn readiness_depends_on_tip_age_and_peers() {
+        let network = Network::RegTest;
+        let mut global_state_lock =
+            mock_genesis_global_state(network, 2, WalletSecret::devnet_wallet()).await;
+        let genesis_timestamp = global_state_lock
+            .lock_guard()
+            .await
+            .chain
+            .light_state()
+            .header()
+            .timestamp;
+
+        let fresh = genesis_timestamp + Timestamp::seconds(10);
+        let report = HealthReport::from_state(&*global_state_lock.lock_guard().await, fresh);
+        assert!(report.databases_open);
+        assert_eq!(10, report.tip_age_secs);
+        assert_eq!(2, report.peer_count);
+        assert!(report.ready);
+
+        let stale = genesis_timestamp + Timestamp::hours(2);
+        let report = HealthReport::from_state(&*global_state_lock.lock_guard().await, stale);
+        assert!(!report.tip_is_recent);
+        assert!(!report.ready);
+
+        global_state_lock
+            .set_cli(cli_args::Args {
+                network,
+                health_min_peers: 3,
+                ..Default::default()
+            })
+            .await;
+        let report = HealthReport::from_state(&*global_state_lock.lock_guard().await, fresh);
+        assert!(!report.has_enough_peers);
+        assert!(!report.ready);
+    }
+}
@@ -7,14 +7,23 @@
 pub mod config_models;
 pub mod connect_to_peers;
 pub mod database;
+pub mod doctor;
+pub mod event_hooks;
+pub mod health_server;
 pub mod locks;
 pub mod macros;
 pub mod main_loop;
+#[cfg(feature = "miner")]
 pub mod mine_loop;
 pub mod models;
 pub mod peer_loop;
 pub mod prelude;
+#[cfg(feature = "rpc")]
+pub mod public_rpc;
+#[cfg(feature = "rpc")]
 pub mod rpc_server;
+#[cfg(feature = "miner")]
+pub mod template_manager;
 pub mod util_types;
 
 // needed by TasmObject derive macro
@@ -25,28 +34,29 @@ use triton_vm::prelude::BFieldElement;
 pub mod tests;
 
 use crate::config_models::data_directory::DataDirectory;
-use crate::connect_to_peers::call_peer_wrapper;
+use crate::connect_to_peers::{bind_peer_listeners, call_peer_wrapper};
 use crate::main_loop::MainLoopHandler;
 use crate::models::channel::RPCServerToMain;
 
 use crate::models::state::archival_state::ArchivalState;
 use crate::models::state::blockchain_state::{BlockchainArchivalState, BlockchainState};
+use crate::models::state::event_journal::EventJournal;
 use crate::models::state::light_state::LightState;
 use crate::models::state::mempool::Mempool;
+use crate::models::state::mempool_policy::MempoolPolicy;
 use crate::models::state::networking_state::NetworkingState;
+use crate::models::state::peer_reconnect::PeerReconnector;
+use crate::models::state::wallet::spend_policy::SpendPolicy;
 use crate::models::state::wallet::wallet_state::WalletState;
 use crate::models::state::wallet::WalletSecret;
 use crate::models::state::GlobalStateLock;
-use crate::rpc_server::RPC;
-use anyhow::{Context, Result};
+use crate::util_types::entropy;
+use anyhow::{bail, Result};
 use config_models::cli_args;
 
 use crate::locks::tokio as sync_tokio;
 use crate::locks::tokio::{LockCallbackFn, LockEvent};
 use chrono::{DateTime, Local, NaiveDateTime, Utc};
-use futures::future;
-use futures::Future;
-use futures::StreamExt;
 
 use models::blockchain::block::Block;
 use models::blockchain::shared::Hash;
@@ -54,16 +64,14 @@ use models::peer::PeerInfo;
 use std::collections::HashMap;
 use std::env;
 use std::net::SocketAddr;
-use tarpc::server;
-use tarpc::server::incoming::Incoming;
-use tarpc::server::Channel;
-use tarpc::tokio_serde::formats::*;
-use tokio::net::TcpListener;
+use std::time::SystemTime;
 use tokio::sync::{broadcast, mpsc, watch};
 use tokio::time::Instant;
-use tracing::{info, trace};
+use tracing::{error, info, trace, warn};
 
-use crate::models::channel::{MainToMiner, MainToPeerThread, MinerToMain, PeerThreadToMain};
+use crate::models::channel::{
+    ChainEvent, MainToMiner, MainToPeerThread, MinerToMain, PeerThreadToMain,
+};
 use crate::models::peer::HandshakeData;
 
 /// Magic string to ensure other program is Neptune Core
@@ -72,23 +80,64 @@ pub const MAGIC_STRING_RESPONSE: &[u8] = b"Hello Neptune!\n";
 const PEER_CHANNEL_CAPACITY: usize = 1000;
 const MINER_CHANNEL_CAPACITY: usize = 3;
 const RPC_CHANNEL_CAPACITY: usize = 1000;
+const CHAIN_EVENT_CHANNEL_CAPACITY: usize = 1000;
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
+pub async fn initialize(mut cli_args: cli_args::Args) -> Result<()> {
+    let profile = cli_args.profile;
+    profile.apply(&mut cli_args)?;
+    info!("Running with profile {profile}");
+    if cfg!(not(feature = "miner")) && cli_args.mine {
+        bail!("This build does not include the miner. Build with the `miner` feature to mine.");
+    }
+    if cli_args.mine_to_address.is_some() && !cli_args.mine {
+        bail!("--mine-to-address requires --mine.");
+    }
+    if cli_args.no_wallet && cli_args.mine && cli_args.mine_to_address.is_none() {
+        bail!("--no-wallet requires --mine-to-address when mining, as there is no wallet to receive the coinbase.");
+    }
+    #[cfg(feature = "miner")]
+    {
+        mine_loop::coinbase_address(&cli_args)?;
+        mine_loop::coinbase_message(&cli_args)?;
+    }
+
     // Get data directory (wallet, block database), create one if none exists
     let data_dir = DataDirectory::get(cli_args.data_dir.clone(), cli_args.network)?;
     DataDirectory::create_dir_if_not_exists(&data_dir.root_dir_path()).await?;
     info!("Data directory is {}", data_dir);
 
+    // Seed the randomness for secrets and nonces before any is drawn
+    #[cfg(feature = "test-determinism")]
+    let deterministic_seed = cli_args.deterministic_seed;
+    #[cfg(not(feature = "test-determinism"))]
+    let deterministic_seed: Option<u64> = None;
+    match deterministic_seed {
+        Some(seed) => {
+            warn!("Running with deterministic seed {seed}. Secrets are predictable!");
+            entropy::install(entropy::EntropySource::deterministic(seed))?;
+        }
+        None => entropy::initialize(&data_dir.entropy_seed_file_path())?,
+    }
+
+    // Fail early on a malformed spend policy, rather than on the first send
+    SpendPolicy::from_cli_args(&cli_args)?;
+
     // Get wallet object, create various wallet secret files
-    let wallet_dir = data_dir.wallet_directory_path();
-    DataDirectory::create_dir_if_not_exists(&wallet_dir).await?;
-    let (wallet_secret, _) =
-        WalletSecret::read_from_file_or_create(&data_dir.wallet_directory_path())?;
-    info!("Now getting wallet state. This may take a while if the database needs pruning.");
-    let wallet_state =
-        WalletState::new_from_wallet_secret(&data_dir, wallet_secret, &cli_args).await;
-    info!("Got wallet state.");
+    let wallet_state = if cli_args.no_wallet {
+        info!("Running without a wallet.");
+        WalletState::new_ephemeral(&cli_args).await
+    } else {
+        let wallet_dir = data_dir.wallet_directory_path();
+        DataDirectory::create_dir_if_not_exists(&wallet_dir).await?;
+        let (wallet_secret, _) =
+            WalletSecret::read_from_file_or_create(&data_dir.wallet_directory_path())?;
+        info!("Now getting wallet state. This may take a while if the database needs pruning.");
+        let wallet_state =
+            WalletState::new_from_wallet_secret(&data_dir, wallet_secret, &cli_args).await;
+        info!("Got wallet state.");
+        wallet_state
+    };
 
     // Connect to or create databases for block index, peers, mutator set, block sync
     let block_index_db = ArchivalState::initialize_block_index_database(&data_dir).await?;
@@ -100,22 +149,29 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
     let archival_mutator_set = ArchivalState::initialize_mutator_set(&data_dir).await?;
     info!("Got archival mutator set");
 
-    let archival_state = ArchivalState::new(
+    let journal = EventJournal::open(
+        data_dir.event_journal_dir_path(),
+        cli_args.journal_retention(),
+    )
+    .await?;
+    info!("Got event journal");
+
+    let mut archival_state = ArchivalState::new(
         data_dir,
         block_index_db,
         archival_mutator_set,
         cli_args.network,
     )
     .await;
+    archival_state.recover_interrupted_tip_update().await?;
 
     // Get latest block. Use hardcoded genesis block if nothing is in database.
     let latest_block: Block = archival_state.get_tip().await;
 
     // Bind socket to port on this machine, to handle incoming connections from peers
-    let incoming_peer_listener = TcpListener::bind((cli_args.listen_addr, cli_args.peer_port))
-    .await
-    .with_context(|| format!("Failed to bind to local TCP port {}:{}. Is an instance of this program already running?", cli_args.listen_addr, cli_args.peer_port))?;
-    info!("Now listening for incoming transactions");
+    let (incoming_peer_listeners, listen_addresses) =
+        bind_peer_listeners(&cli_args.peer_listen_addresses()).await?;
+    info!("Now listening for incoming transactions on {listen_addresses:?}");
 
     let peer_map: HashMap<SocketAddr, PeerInfo> = HashMap::new();
 
@@ -130,7 +186,18 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
     // Create handshake data which is used when connecting to outgoing peers specified in the
     // CLI arguments
     let syncing = false;
-    let networking_state = NetworkingState::new(peer_map, peer_databases, syncing);
+    let mut networking_state = NetworkingState::new(
+        peer_map,
+        peer_databases,
+        syncing,
+        cli_args.max_stem_pool_size,
+    );
+    networking_state.peer_reconnector = PeerReconnector::new(
+        &cli_args.peers,
+        cli_args.max_reconnect_attempts,
+        SystemTime::now(),
+    );
+    networking_state.listen_addresses = listen_addresses;
 
     let light_state: LightState = LightState::from(latest_block.clone());
     let blockchain_archival_state = BlockchainArchivalState {
@@ -138,13 +205,14 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
         archival_state,
     };
     let blockchain_state = BlockchainState::Archival(blockchain_archival_state);
-    let mempool = Mempool::new(cli_args.max_mempool_size);
+    let mempool = Mempool::new(MempoolPolicy::from_cli(&cli_args)?);
     let global_state_lock = GlobalStateLock::new(
         wallet_state,
         blockchain_state,
         networking_state,
         cli_args,
         mempool,
+        journal,
         false,
     );
     let own_handshake_data: HandshakeData = global_state_lock
@@ -158,13 +226,15 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
     );
 
     // Check if we need to restore the wallet database, and if so, do it.
-    info!("Checking if we need to restore UTXOs");
-    global_state_lock
-        .lock_guard_mut()
-        .await
-        .restore_monitored_utxos_from_recovery_data()
-        .await?;
-    info!("UTXO restoration check complete");
+    if !global_state_lock.cli().no_wallet {
+        info!("Checking if we need to restore UTXOs");
+        global_state_lock
+            .lock_guard_mut()
+            .await
+            .restore_monitored_utxos_from_recovery_data()
+            .await?;
+        info!("UTXO restoration check complete");
+    }
 
     // Connect to peers, and provide each peer thread with a thread-safe copy of the state
     let mut thread_join_handles = vec![];
@@ -179,7 +249,7 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
             .name("call_peer_wrapper_3")
             .spawn(async move {
                 call_peer_wrapper(
-                    peer_address,
+                    vec![peer_address],
                     peer_state_var.clone(),
                     main_to_peer_broadcast_rx_clone,
                     peer_thread_to_main_tx_clone,
@@ -195,8 +265,30 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
     // Start mining threads if requested
     let (miner_to_main_tx, miner_to_main_rx) = mpsc::channel::<MinerToMain>(MINER_CHANNEL_CAPACITY);
     let (main_to_miner_tx, main_to_miner_rx) = watch::channel::<MainToMiner>(MainToMiner::Empty);
-    let miner_state_lock = global_state_lock.clone(); // bump arc refcount.
+    let (mempool_changed_tx, mempool_changed_rx) = watch::channel(());
+    #[cfg(feature = "miner")]
     if global_state_lock.cli().mine {
+        // The template manager tells the miner when to rebuild its block
+        // template for better fees
+        let (template_fees_tx, template_fees_rx) = watch::channel(None);
+        let (template_refresh_tx, template_refresh_rx) = watch::channel(());
+        let template_manager = template_manager::TemplateManager::new(
+            global_state_lock.clone(),
+            mempool_changed_rx,
+            template_fees_rx,
+            template_refresh_tx,
+        );
+        let template_manager_join_handle = tokio::task::Builder::new()
+            .name("template_manager")
+            .spawn(async move {
+                template_manager
+                    .run()
+                    .await
+                    .expect("Error in template manager thread");
+            })?;
+        thread_join_handles.push(template_manager_join_handle);
+
+        let miner_state_lock = global_state_lock.clone(); // bump arc refcount.
         let miner_join_handle = tokio::task::Builder::new()
             .name("miner")
             .spawn(async move {
@@ -205,6 +297,8 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
                     miner_to_main_tx,
                     latest_block,
                     miner_state_lock,
+                    template_fees_tx,
+                    template_refresh_rx,
                 )
                 .await
                 .expect("Error in mining thread");
@@ -212,58 +306,79 @@ pub async fn initialize(cli_args: cli_args::Args) -> Result<()> {
         thread_join_handles.push(miner_join_handle);
         info!("Started mining thread");
     }
+    // The main loop messages the miner whether or not it runs, so the
+    // channel must stay open.
+    #[cfg(not(feature = "miner"))]
+    let _miner_channel_ends = (miner_to_main_tx, main_to_miner_rx, mempool_changed_rx);
+
+    // Publish chain events to the external command hooks, if any are configured
+    let (chain_event_tx, chain_event_rx) =
+        broadcast::channel::<ChainEvent>(CHAIN_EVENT_CHANNEL_CAPACITY);
+    if event_hooks::any_hook_configured(global_state_lock.cli()) {
+        let hooks_cli = global_state_lock.cli().clone();
+        let event_hooks_join_handle =
+            tokio::task::Builder::new()
+                .name("event_hooks")
+                .spawn(async move {
+                    event_hooks::run(chain_event_rx, hooks_cli).await;
+                })?;
+        thread_join_handles.push(event_hooks_join_handle);
+        info!("Started event hooks thread");
+    } else {
+        drop(chain_event_rx);
+    }
+
+    // Serve health checks for orchestration tools if requested
+    if let Some(health_port) = global_state_lock.cli().health_port {
+        let health_address =
+            SocketAddr::new(global_state_lock.cli().listen_addr[0].ip, health_port);
+        let health_state_lock = global_state_lock.clone();
+        let health_join_handle =
+            tokio::task::Builder::new()
+                .name("health_server")
+                .spawn(async move {
+                    if let Err(err) = health_server::run(health_address, health_state_lock).await {
+                        error!("Health-check endpoint stopped: {err}");
+                    }
+                })?;
+        thread_join_handles.push(health_join_handle);
+    }
 
     // Start RPC server for CLI request and more. It's important that this is done as late
     // as possible, so requests do not hang while initialization code runs.
     let (rpc_server_to_main_tx, rpc_server_to_main_rx) =
         mpsc::channel::<RPCServerToMain>(RPC_CHANNEL_CAPACITY);
-    let mut rpc_listener = tarpc::serde_transport::tcp::listen(
-        format!("127.0.0.1:{}", global_state_lock.cli().rpc_port),
-        Json::default,
-    )
-    .await?;
-    rpc_listener.config_mut().max_frame_length(usize::MAX);
-
-    let rpc_state_lock = global_state_lock.clone();
-
-    async fn spawn(fut: impl Future<Output = ()> + Send + 'static) {
-        tokio::spawn(fut);
+    #[cfg(feature = "rpc")]
+    {
+        let rpc_join_handle =
+            rpc_server::serve(global_state_lock.clone(), rpc_server_to_main_tx).await?;
+        thread_join_handles.push(rpc_join_handle);
+        info!("Started RPC server");
+
+        if let Some(public_rpc_port) = global_state_lock.cli().public_rpc_port {
+            let public_rpc_join_handle = public_rpc::serve(
+                public_rpc_port,
+                global_state_lock.clone(),
+                rpc_server_to_main_tx.clone(),
+            )
+            .await?;
+            thread_join_handles.push(public_rpc_join_handle);
+            info!("Started public RPC server");
+        }
     }
-
-    let rpc_join_handle = tokio::spawn(async move {
-        rpc_listener
-            // Ignore accept errors.
-            .filter_map(|r| future::ready(r.ok()))
-            .map(server::BaseChannel::with_defaults)
-            // Limit channels to 5 per IP. 1 for dashboard and a few more for CLI interactions
-            .max_channels_per_key(5, |t| t.transport().peer_addr().unwrap().ip())
-            // serve is generated by the service attribute. It takes as input any type implementing
-            // the generated RPC trait.
-            .map(move |channel| {
-                let server = rpc_server::NeptuneRPCServer {
-                    socket_address: channel.transport().peer_addr().unwrap(),
-                    state: rpc_state_lock.clone(),
-                    rpc_server_to_main_tx: rpc_server_to_main_tx.clone(),
-                };
-
-                channel.execute(server.serve()).for_each(spawn)
-            })
-            // Max 10 channels.
-            .buffer_unordered(10)
-            .for_each(|_| async {})
-            .await;
-    });
-    thread_join_handles.push(rpc_join_handle);
-    info!("Started RPC server");
+    #[cfg(not(feature = "rpc"))]
+    drop(rpc_server_to_main_tx);
 
     // Handle incoming connections, messages from peer threads, and messages from the mining thread
     info!("Starting main loop");
     let main_loop_handler = MainLoopHandler::new(
-        incoming_peer_listener,
+        incoming_peer_listeners,
         global_state_lock,
         main_to_peer_broadcast_tx,
         peer_thread_to_main_tx,
         main_to_miner_tx,
+        chain_event_tx,
+        mempool_changed_tx,
     );
     main_loop_handler
         .run(
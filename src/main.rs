@@ -8,6 +8,11 @@ pub async fn main() -> Result<()> {
     // Fetch the CLI arguments
     let args: cli_args::Args = cli_args::Args::parse();
 
+    // The doctor prints its own report, which log lines would clutter
+    if let Some(cli_args::CoreCommand::Doctor { json }) = args.command {
+        return neptune_core::doctor::run(args, json).await;
+    }
+
     if args.tokio_console {
         console_subscriber::init();
     } else {
@@ -1,6 +1,7 @@
 use crate::prelude::twenty_first;
 
-use crate::connect_to_peers::{answer_peer_wrapper, call_peer_wrapper};
+use crate::config_models::data_directory::DataDirectory;
+use crate::connect_to_peers::{answer_peer_wrapper, call_peer_wrapper, canonical_peer_address};
 
 use crate::models::blockchain::block::block_header::{BlockHeader, PROOF_OF_WORK_COUNT_U32_SIZE};
 use crate::models::blockchain::block::block_height::BlockHeight;
@@ -9,59 +10,103 @@ use crate::models::peer::{
     HandshakeData, PeerInfo, PeerSynchronizationState, TransactionNotification,
 };
 
-use crate::models::state::GlobalStateLock;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::data_carrier::DataCarrier;
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::clock_skew::ClockSkewStatus;
+use crate::models::state::dandelion::choose_stem_peer;
+use crate::models::state::disk_space::DiskSpaceStatus;
+use crate::models::state::disk_usage::{DiskUsage, JournalDiskUsage};
+use crate::models::state::event_journal::JournalEvent;
+use crate::models::state::fork_choice::check_preferable_tip;
+use crate::models::state::future_blocks::FutureBlocks;
+use crate::models::state::metrics_history::{MetricsHistory, MetricsSnapshot};
+use crate::models::state::peer_quality::{
+    choose_sync_peer, prefer_archival_peers, sync_quality_score, SyncPeerRecord,
+};
+use crate::models::state::slow_peers::SlowPeers;
+use crate::models::state::transaction_watch::WatchEvent;
+use crate::models::state::wallet::sent_transactions::DoubleSpend;
+use crate::models::state::{GlobalState, GlobalStateLock};
+use crate::util_types::entropy;
 use anyhow::Result;
+use bytesize::ByteSize;
+use futures::future;
+use get_size::GetSize;
 use itertools::Itertools;
-use rand::prelude::{IteratorRandom, SliceRandom};
-use rand::thread_rng;
-use std::collections::HashMap;
+use rand::prelude::IteratorRandom;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::thread::sleep;
 use std::time::{Duration, SystemTime};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::{broadcast, mpsc, watch};
 use tokio::task::JoinHandle;
 use tokio::{select, signal, time};
 use tracing::{debug, error, info, warn};
 use twenty_first::amount::u32s::U32s;
+use twenty_first::math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
 use crate::models::channel::{
-    MainToMiner, MainToPeerThread, MinerToMain, PeerThreadToMain, RPCServerToMain,
+    ChainEvent, MainToMiner, MainToPeerThread, MinerToMain, PeerThreadToMain,
+    PeerThreadToMainTransaction, RPCServerToMain,
 };
 
 const PEER_DISCOVERY_INTERVAL_IN_SECONDS: u64 = 120;
+const PEER_RECONNECT_INTERVAL_IN_SECONDS: u64 = 1;
 const SYNC_REQUEST_INTERVAL_IN_SECONDS: u64 = 3;
 const MEMPOOL_PRUNE_INTERVAL_IN_SECS: u64 = 30 * 60; // 30mins
 const MP_RESYNC_INTERVAL_IN_SECS: u64 = 59;
 const UTXO_NOTIFICATION_POOL_PRUNE_INTERVAL_IN_SECS: u64 = 19 * 60; // 19 mins
+const CLOCK_SKEW_CHECK_INTERVAL_IN_SECS: u64 = 60;
+const STEM_EMBARGO_CHECK_INTERVAL_IN_SECS: u64 = 5;
+const FUTURE_BLOCK_CHECK_INTERVAL_IN_SECS: u64 = 10;
+const TIP_STALENESS_CHECK_INTERVAL_IN_SECS: u64 = 60;
+const METRICS_SNAPSHOT_INTERVAL_IN_SECS: u64 = 10 * 60; // 10 mins
+const DISK_SPACE_CHECK_INTERVAL_IN_SECS: u64 = 30;
 
 const SANCTION_PEER_TIMEOUT_FACTOR: u64 = 40;
 const POTENTIAL_PEER_MAX_COUNT_AS_A_FACTOR_OF_MAX_PEERS: usize = 20;
 const STANDARD_BATCH_BLOCK_LOOKBEHIND_SIZE: usize = 100;
 
+/// After starting, the mempool is filled from the mempools of this many of
+/// the first peers to connect
+const MEMPOOL_REQUEST_PEER_COUNT: usize = 3;
+
 /// MainLoop is the immutable part of the input for the main loop function
 pub struct MainLoopHandler {
-    incoming_peer_listener: TcpListener,
+    incoming_peer_listeners: Vec<TcpListener>,
     global_state_lock: GlobalStateLock,
     main_to_peer_broadcast_tx: broadcast::Sender<MainToPeerThread>,
     peer_thread_to_main_tx: mpsc::Sender<PeerThreadToMain>,
     main_to_miner_tx: watch::Sender<MainToMiner>,
+    chain_event_tx: broadcast::Sender<ChainEvent>,
+
+    /// Tells the template manager that transactions entered the mempool
+    mempool_changed_tx: watch::Sender<()>,
 }
 
 impl MainLoopHandler {
     pub fn new(
-        incoming_peer_listener: TcpListener,
+        incoming_peer_listeners: Vec<TcpListener>,
         global_state_lock: GlobalStateLock,
         main_to_peer_broadcast_tx: broadcast::Sender<MainToPeerThread>,
         peer_thread_to_main_tx: mpsc::Sender<PeerThreadToMain>,
         main_to_miner_tx: watch::Sender<MainToMiner>,
+        chain_event_tx: broadcast::Sender<ChainEvent>,
+        mempool_changed_tx: watch::Sender<()>,
     ) -> Self {
         Self {
-            incoming_peer_listener,
+            incoming_peer_listeners,
             global_state_lock,
             main_to_miner_tx,
             main_to_peer_broadcast_tx,
             peer_thread_to_main_tx,
+            chain_event_tx,
+            mempool_changed_tx,
         }
     }
 }
@@ -70,7 +115,11 @@ impl MainLoopHandler {
 struct MutableMainLoopState {
     sync_state: SyncState,
     potential_peers: PotentialPeersState,
+    future_blocks: FutureBlocks,
     thread_handles: Vec<JoinHandle<()>>,
+
+    /// Number of peers asked for their mempool since starting
+    mempool_requests_sent: usize,
 }
 
 impl MutableMainLoopState {
@@ -78,7 +127,9 @@ impl MutableMainLoopState {
         Self {
             sync_state: SyncState::default(),
             potential_peers: PotentialPeersState::default(),
+            future_blocks: FutureBlocks::default(),
             thread_handles,
+            mempool_requests_sent: 0,
         }
     }
 }
@@ -87,6 +138,9 @@ impl MutableMainLoopState {
 struct SyncState {
     peer_sync_states: HashMap<SocketAddr, PeerSynchronizationState>,
     last_sync_request: Option<(SystemTime, BlockHeight, SocketAddr)>,
+
+    // How connected peers served past sync requests, used to rank them
+    peer_records: HashMap<SocketAddr, SyncPeerRecord>,
 }
 
 impl SyncState {
@@ -94,6 +148,7 @@ impl SyncState {
         Self {
             peer_sync_states: HashMap::new(),
             last_sync_request: None,
+            peer_records: HashMap::new(),
         }
     }
 
@@ -101,6 +156,27 @@ impl SyncState {
         self.last_sync_request = Some((SystemTime::now(), requested_block_height, peer));
     }
 
+    /// Credit the peer the last request was sent to with the blocks it
+    /// delivered, or with a timeout. Must be called once per request, when the
+    /// next request is about to be made.
+    fn record_outcome_of_last_request(&mut self, current_block_height: BlockHeight) {
+        let Some((req_time, requested_height, peer)) = self.last_sync_request else {
+            return;
+        };
+
+        let elapsed = req_time.elapsed().unwrap_or_default();
+        let record = self.peer_records.entry(peer).or_default();
+        if requested_height > current_block_height {
+            record.record_timeout();
+        } else if elapsed
+            < Duration::from_secs(SANCTION_PEER_TIMEOUT_FACTOR * SYNC_REQUEST_INTERVAL_IN_SECONDS)
+        {
+            // An answer arriving later than that belongs to an earlier sync session
+            let block_count = (current_block_height - requested_height + 1) as u64;
+            record.record_answer(block_count, elapsed.as_millis() as u64);
+        }
+    }
+
     /// Return a list of peers that have reported to be in possession of blocks with a PoW family
     /// above a threshold.
     fn get_potential_peers_for_sync_request(
@@ -151,6 +227,21 @@ impl SyncState {
     }
 }
 
+/// Accept the next incoming connection on any of the listeners
+async fn accept_incoming_peer(
+    listeners: &[TcpListener],
+) -> std::io::Result<(TcpStream, SocketAddr)> {
+    if listeners.is_empty() {
+        return future::pending().await;
+    }
+
+    let (accepted, _, _) =
+        future::select_all(listeners.iter().map(|listener| Box::pin(listener.accept()))).await;
+    let (stream, peer_address) = accepted?;
+
+    Ok((stream, canonical_peer_address(peer_address)))
+}
+
 /// holds information about a potential peer in the process of peer discovery
 struct PotentialPeerInfo {
     _reported: SystemTime,
@@ -205,7 +296,7 @@ impl PotentialPeersState {
         if self.potential_peers.len()
             > max_peers * POTENTIAL_PEER_MAX_COUNT_AS_A_FACTOR_OF_MAX_PEERS
         {
-            let mut rng = rand::thread_rng();
+            let mut rng = entropy::seeded_rng();
             let random_potential_peer = self
                 .potential_peers
                 .keys()
@@ -223,18 +314,22 @@ impl PotentialPeersState {
 
     /// Return a random peer from the potential peer list that we aren't connected to
     /// and that isn't our own address. Returns (socket address, peer distance)
+    /// Return the addresses of a potential peer to connect to, and its
+    /// distance. The addresses are all those reported for the peer's instance.
+    /// Chronically slow peers are only returned if no other peer is left.
     fn get_distant_candidate(
         &self,
         connected_clients: &[PeerInfo],
         own_instance_id: u128,
-    ) -> Option<(SocketAddr, u8)> {
+        slow_peers: &SlowPeers,
+    ) -> Option<(Vec<SocketAddr>, u8)> {
         let peers_instance_ids: Vec<u128> =
             connected_clients.iter().map(|x| x.instance_id).collect();
 
         // Only pick those peers that report a listening port
         let peers_listen_addresses: Vec<SocketAddr> = connected_clients
             .iter()
-            .filter_map(|x| x.listen_address())
+            .flat_map(|x| x.known_listen_addresses())
             .collect();
 
         // Find the appropriate candidates
@@ -249,16 +344,30 @@ impl PotentialPeersState {
             .filter(|potential_peer| !peers_instance_ids.contains(&potential_peer.1.instance_id))
             .filter(|potential_peer| !peers_listen_addresses.contains(potential_peer.0))
             .collect::<Vec<_>>();
+        let (slow, not_slow): (Vec<_>, Vec<_>) = not_connected_peers
+            .into_iter()
+            .partition(|potential_peer| slow_peers.is_slow(potential_peer.0.ip()));
+        let not_connected_peers = if not_slow.is_empty() { slow } else { not_slow };
 
         // Get the candidate list with the highest distance
         let max_distance_candidates = not_connected_peers.iter().max_by_key(|pp| pp.1.distance);
 
         // Pick a random candidate from the appropriate candidates
-        let mut rng = rand::thread_rng();
-        max_distance_candidates
-            .iter()
-            .choose(&mut rng)
-            .map(|x| (x.0.to_owned(), x.1.distance))
+        let mut rng = entropy::seeded_rng();
+        let candidate = max_distance_candidates.iter().choose(&mut rng)?;
+        let (candidate_address, candidate) = (*candidate.0, candidate.1);
+
+        let mut addresses = vec![candidate_address];
+        addresses.extend(
+            not_connected_peers
+                .iter()
+                .filter(|(address, peer)| {
+                    peer.instance_id == candidate.instance_id && **address != candidate_address
+                })
+                .map(|(address, _)| **address),
+        );
+
+        Some((addresses, candidate.distance))
     }
 }
 
@@ -297,6 +406,241 @@ fn stay_in_sync_mode(
 }
 
 impl MainLoopHandler {
+    /// Publish the chain events caused by the current tip replacing the block
+    /// `old_tip_digest` at height `old_tip_height` as tip, and record them in
+    /// the event journal.
+    async fn publish_new_tip_events(
+        &self,
+        global_state_mut: &mut GlobalState,
+        old_tip_digest: Digest,
+        old_tip_height: BlockHeight,
+    ) {
+        let new_tip_digest = global_state_mut.chain.light_state().hash();
+        let new_tip_header = global_state_mut.chain.light_state().header().clone();
+
+        // Any new tip differentiates the work of the tips the operator chose
+        // between
+        if global_state_mut.net.preferred_tip != Some(new_tip_digest) {
+            global_state_mut.net.preferred_tip = None;
+        }
+
+        let mut events = vec![];
+        let mut journal_events = vec![];
+        if new_tip_header.prev_block_digest != old_tip_digest {
+            events.push(ChainEvent::Reorg {
+                old_tip_digest,
+                old_tip_height,
+                new_tip_digest,
+                new_tip_height: new_tip_header.height,
+            });
+
+            if global_state_mut.chain.is_archival_node() {
+                let (disconnected, _luca, connected) = global_state_mut
+                    .chain
+                    .archival_state()
+                    .find_path(old_tip_digest, new_tip_digest)
+                    .await;
+
+                // `disconnected` walks down from the old tip, `connected` walks
+                // up to the new tip.
+                let mut height = old_tip_height;
+                for digest in disconnected {
+                    journal_events.push(JournalEvent::BlockDisconnected { digest, height });
+                    height = height.previous();
+                }
+                for digest in connected {
+                    height = height.next();
+                    journal_events.push(JournalEvent::BlockConnected { digest, height });
+                }
+            } else {
+                // Light nodes only know the new tip, not the blocks the reorg
+                // disconnected or connected below it
+                journal_events.push(JournalEvent::BlockConnected {
+                    digest: new_tip_digest,
+                    height: new_tip_header.height,
+                });
+            }
+        } else {
+            journal_events.push(JournalEvent::BlockConnected {
+                digest: new_tip_digest,
+                height: new_tip_header.height,
+            });
+        }
+
+        events.push(ChainEvent::NewBlock {
+            digest: new_tip_digest,
+            height: new_tip_header.height,
+            timestamp: new_tip_header.timestamp,
+        });
+
+        let received_utxos = global_state_mut
+            .wallet_state
+            .get_utxos_confirmed_in_block(new_tip_digest)
+            .await;
+        if !received_utxos.is_empty() {
+            let amount: NeptuneCoins = received_utxos
+                .iter()
+                .map(|utxo| utxo.get_native_currency_amount())
+                .sum();
+            events.push(ChainEvent::WalletReceive {
+                block_digest: new_tip_digest,
+                height: new_tip_header.height,
+                amount,
+                utxo_count: received_utxos.len(),
+            });
+            journal_events.push(JournalEvent::TransactionReceived {
+                block_digest: new_tip_digest,
+                height: new_tip_header.height,
+                amount,
+            });
+        }
+
+        // Index the data carriers of the new tip for applications following
+        // the journal
+        let data_carriers =
+            DataCarrier::all_in(&global_state_mut.chain.light_state().kernel.body.transaction);
+        for data_carrier in data_carriers {
+            journal_events.push(JournalEvent::DataCarrier {
+                block_digest: new_tip_digest,
+                height: new_tip_header.height,
+                data: data_carrier.data,
+            });
+        }
+
+        for journal_event in journal_events {
+            global_state_mut.journal.record(journal_event).await;
+        }
+
+        // Skip publishing when nobody is subscribed
+        if self.chain_event_tx.receiver_count() > 0 {
+            for event in events {
+                // Sending only fails when the last subscriber just left, which
+                // is fine.
+                let _ = self.chain_event_tx.send(event);
+            }
+        }
+
+        let double_spends = global_state_mut
+            .wallet_state
+            .sent_transactions
+            .check_block(global_state_mut.chain.light_state());
+        self.publish_double_spends(global_state_mut, double_spends)
+            .await;
+
+        self.publish_watch_events(global_state_mut).await;
+    }
+
+    /// Update the transaction watches for the new tip, and publish the ones
+    /// that fire.
+    async fn publish_watch_events(&self, global_state_mut: &mut GlobalState) {
+        if global_state_mut.transaction_watches.is_empty() {
+            return;
+        }
+
+        // Light nodes only know the tip, so a block is taken to have left the
+        // canonical chain only if the tip replaced it at its height or above.
+        let tip = global_state_mut.chain.light_state();
+        let tip_digest = tip.hash();
+        let tip_height = tip.kernel.header.height;
+        let mut reorged_out = HashSet::new();
+        for (block_digest, height) in global_state_mut.transaction_watches.including_blocks() {
+            let is_canonical = if global_state_mut.chain.is_archival_node() {
+                global_state_mut
+                    .chain
+                    .archival_state()
+                    .block_belongs_to_canonical_chain(block_digest, tip_digest)
+                    .await
+            } else {
+                block_digest == tip_digest || height < tip_height
+            };
+            if !is_canonical {
+                reorged_out.insert(block_digest);
+            }
+        }
+
+        let watch_events = global_state_mut
+            .transaction_watches
+            .update_with_tip(global_state_mut.chain.light_state(), |block_digest| {
+                !reorged_out.contains(&block_digest)
+            });
+        for watch_event in watch_events {
+            let (journal_event, event) = match watch_event {
+                WatchEvent::Confirmed {
+                    transaction_digest,
+                    block_digest,
+                    confirmations,
+                } => {
+                    info!("Watched transaction {transaction_digest} has {confirmations} confirmations");
+                    (
+                        JournalEvent::TransactionConfirmed {
+                            transaction_digest,
+                            block_digest,
+                            confirmations,
+                        },
+                        ChainEvent::TransactionConfirmed {
+                            transaction_digest,
+                            block_digest,
+                            confirmations,
+                        },
+                    )
+                }
+                WatchEvent::ReorgedOut {
+                    transaction_digest,
+                    block_digest,
+                } => {
+                    warn!("Block {block_digest} with watched transaction {transaction_digest} left the canonical chain");
+                    (
+                        JournalEvent::TransactionReorgedOut {
+                            transaction_digest,
+                            block_digest,
+                        },
+                        ChainEvent::TransactionReorgedOut {
+                            transaction_digest,
+                            block_digest,
+                        },
+                    )
+                }
+            };
+
+            global_state_mut.journal.record(journal_event).await;
+            let _ = self.chain_event_tx.send(event);
+        }
+    }
+
+    /// Publish the double spends of transactions sent by this wallet, and
+    /// record them in the event journal.
+    async fn publish_double_spends(
+        &self,
+        global_state_mut: &mut GlobalState,
+        double_spends: Vec<DoubleSpend>,
+    ) {
+        for double_spend in double_spends {
+            let DoubleSpend {
+                own_digest,
+                conflicting_digest,
+                in_block,
+            } = double_spend;
+            warn!(
+                "Transaction {own_digest} sent by this wallet is double spent by {} {conflicting_digest}",
+                if in_block { "block" } else { "transaction" }
+            );
+
+            global_state_mut
+                .journal
+                .record(JournalEvent::DoubleSpend {
+                    own_transaction_digest: own_digest,
+                    conflicting_digest,
+                    in_block,
+                })
+                .await;
+            let _ = self.chain_event_tx.send(ChainEvent::DoubleSpend {
+                own_transaction_digest: own_digest,
+                conflicting_digest,
+                in_block,
+            });
+        }
+    }
+
     /// Locking:
     ///   * acquires `global_state_lock` for write
     async fn handle_miner_thread_message(&self, msg: MinerToMain) -> Result<()> {
@@ -332,13 +676,26 @@ impl MainLoopHandler {
                     warn!("Got new block from miner thread that was not child of tip. Discarding.");
                     return Ok(());
                 }
+                if global_state_mut.net.disk_space.low {
+                    warn!("Free disk space is low. Discarding block from miner thread.");
+                    return Ok(());
+                }
 
-                global_state_mut
-                    .set_new_self_mined_tip(
-                        new_block.as_ref().clone(),
-                        new_block_info.coinbase_utxo_info.as_ref().clone(),
-                    )
-                    .await?;
+                let tip_height = global_state_mut.chain.light_state().header().height;
+                match new_block_info.coinbase_utxo_info {
+                    Some(coinbase_utxo_info) => {
+                        global_state_mut
+                            .set_new_self_mined_tip(new_block.as_ref().clone(), *coinbase_utxo_info)
+                            .await?
+                    }
+                    None => {
+                        global_state_mut
+                            .set_new_tip(new_block.as_ref().clone())
+                            .await?
+                    }
+                }
+                self.publish_new_tip_events(&mut global_state_mut, tip_hash, tip_height)
+                    .await;
                 drop(global_state_mut);
 
                 // Inform miner that mempool has been updated and that it is safe
@@ -385,8 +742,32 @@ impl MainLoopHandler {
                         .header
                         .proof_of_work_family;
 
+                    if global_state_mut.net.disk_space.low {
+                        warn!("Free disk space is low. Not storing blocks.");
+                        return Ok(());
+                    }
+
                     let block_is_new =
                         tip_proof_of_work_family < last_block.kernel.header.proof_of_work_family;
+                    let ties_with_tip =
+                        tip_proof_of_work_family == last_block.kernel.header.proof_of_work_family;
+                    if ties_with_tip
+                        && blocks.len() == 1
+                        && global_state_mut.chain.is_archival_node()
+                    {
+                        // Kept for the operator to choose between, see `fork_choice`
+                        info!(
+                            "Storing competing tip {} of height {} with as much proof-of-work as the tip",
+                            last_block.hash(),
+                            last_block.kernel.header.height
+                        );
+                        global_state_mut
+                            .chain
+                            .archival_state_mut()
+                            .write_block_as_competing_tip(&last_block)
+                            .await?;
+                        return Ok(());
+                    }
                     if !block_is_new {
                         warn!("Blocks were not new. Not storing blocks.");
 
@@ -396,6 +777,20 @@ impl MainLoopHandler {
                         return Ok(());
                     }
 
+                    if global_state_mut.chain.is_archival_node() {
+                        let new_parent = blocks[0].kernel.header.prev_block_digest;
+                        let tip_digest = global_state_mut.chain.light_state().hash();
+                        if global_state_mut
+                            .chain
+                            .archival_state()
+                            .reverts_finalized_block(new_parent, tip_digest)
+                            .await
+                        {
+                            warn!("Blocks would roll back a finalized block. Not storing blocks.");
+                            return Ok(());
+                        }
+                    }
+
                     // Get out of sync mode if needed
                     if global_state_mut.net.syncing {
                         let stay_in_sync_mode = stay_in_sync_mode(
@@ -418,7 +813,15 @@ impl MainLoopHandler {
                             new_block.kernel.header.timestamp.standard_format()
                         );
 
+                        let old_tip_digest = global_state_mut.chain.light_state().hash();
+                        let old_tip_height = global_state_mut.chain.light_state().header().height;
                         global_state_mut.set_new_tip(new_block).await?;
+                        self.publish_new_tip_events(
+                            &mut global_state_mut,
+                            old_tip_digest,
+                            old_tip_height,
+                        )
+                        .await;
                     }
                 }
 
@@ -445,6 +848,26 @@ impl MainLoopHandler {
                     .peer_sync_states
                     .insert(socket_addr, claimed_state);
 
+                // Seed the sync record of a reconnecting peer with its record
+                // from past connections
+                if !main_loop_state
+                    .sync_state
+                    .peer_records
+                    .contains_key(&socket_addr)
+                {
+                    let history = self
+                        .global_state_lock
+                        .lock_guard()
+                        .await
+                        .net
+                        .get_peer_history_from_database(socket_addr.ip())
+                        .await;
+                    main_loop_state
+                        .sync_state
+                        .peer_records
+                        .insert(socket_addr, history.sync_record.decayed());
+                }
+
                 // Check if synchronization mode should be activated. Synchronization mode is entered if
                 // PoW family exceeds our tip and if the height difference is beyond a threshold value.
                 // TODO: If we are not checking the PoW claims of the tip this can be abused by forcing
@@ -462,6 +885,23 @@ impl MainLoopHandler {
                     global_state_mut.net.syncing = true;
                     self.main_to_miner_tx.send(MainToMiner::StartSyncing)?;
                 }
+
+                // Transactions received while syncing could not be confirmed.
+                // Older peers cannot decode mempool requests.
+                let peer_supports_mempool_request = global_state_mut
+                    .net
+                    .peer_map
+                    .get(&socket_addr)
+                    .is_some_and(|peer| peer.supports_message_extensions());
+                if !global_state_mut.net.syncing
+                    && !global_state_mut.cli().blocks_only
+                    && peer_supports_mempool_request
+                    && main_loop_state.mempool_requests_sent < MEMPOOL_REQUEST_PEER_COUNT
+                {
+                    main_loop_state.mempool_requests_sent += 1;
+                    self.main_to_peer_broadcast_tx
+                        .send(MainToPeerThread::MempoolRequest(socket_addr))?;
+                }
             }
             PeerThreadToMain::RemovePeerMaxBlockHeight(socket_addr) => {
                 debug!(
@@ -472,10 +912,24 @@ impl MainLoopHandler {
                     .sync_state
                     .peer_sync_states
                     .remove(&socket_addr);
+                let sync_record = main_loop_state.sync_state.peer_records.remove(&socket_addr);
 
                 // Get out of sync mode if needed.
                 let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
 
+                let mut history = global_state_mut
+                    .net
+                    .get_peer_history_from_database(socket_addr.ip())
+                    .await;
+                history.connections += 1;
+                if let Some(sync_record) = sync_record {
+                    history.sync_record = sync_record;
+                }
+                global_state_mut
+                    .net
+                    .write_peer_history(socket_addr.ip(), history)
+                    .await;
+
                 if global_state_mut.net.syncing {
                     let stay_in_sync_mode = stay_in_sync_mode(
                         global_state_mut.chain.light_state().header(),
@@ -515,95 +969,162 @@ impl MainLoopHandler {
                     return Ok(());
                 }
 
-                // Insert into mempool
-                global_state_mut
-                    .mempool
-                    .insert(&pt2m_transaction.transaction);
+                let double_spends = global_state_mut
+                    .wallet_state
+                    .sent_transactions
+                    .check_transaction(&pt2m_transaction.transaction);
+                self.publish_double_spends(&mut global_state_mut, double_spends)
+                    .await;
+
+                let PeerThreadToMainTransaction {
+                    transaction,
+                    stem_from,
+                    ..
+                } = *pt2m_transaction;
+                match stem_from {
+                    Some(stem_from) => {
+                        let transaction_digest = Hash::hash(&transaction);
+                        if global_state_mut.mempool.contains(transaction_digest)
+                            || global_state_mut.net.stem_pool.contains(transaction_digest)
+                        {
+                            debug!("stem-phase transaction was already known");
+                            return Ok(());
+                        }
 
-                // send notification to peers
-                let transaction_notification: TransactionNotification =
-                    pt2m_transaction.transaction.into();
+                        self.relay_transaction(
+                            &mut global_state_mut,
+                            transaction,
+                            Some(stem_from),
+                        )?;
+                    }
+                    None => self.diffuse_transaction(&mut global_state_mut, transaction)?,
+                }
+            }
+            PeerThreadToMain::FutureBlocks(blocks) => {
+                let now = Timestamp::now();
+                for block in blocks {
+                    let height = block.kernel.header.height;
+                    if !main_loop_state.future_blocks.insert(block, now) {
+                        warn!("Not holding future block of height {height}");
+                    }
+                }
+                self.global_state_lock
+                    .lock_guard_mut()
+                    .await
+                    .net
+                    .buffer_sizes
+                    .future_blocks = main_loop_state.future_blocks.get_size();
+            }
+            PeerThreadToMain::Alert(alert) => {
+                // Verified and stored by the peer thread
+                self.main_to_peer_broadcast_tx
+                    .send(MainToPeerThread::Alert(alert))
+                    .expect(
+                        "Peer handler broadcast channel prematurely closed. This should never happen.",
+                    );
+            }
+            PeerThreadToMain::SupersededConnection(peer_address) => {
                 self.main_to_peer_broadcast_tx
-                    .send(MainToPeerThread::TransactionNotification(
-                        transaction_notification,
-                    ))?;
+                    .send(MainToPeerThread::Disconnect(peer_address))
+                    .expect(
+                        "Peer handler broadcast channel prematurely closed. This should never happen.",
+                    );
             }
         }
 
         Ok(())
     }
 
-    /// Function to perform peer discovery: Finds potential peers from connected peers and attempts
-    /// to establish connections with one of those potential peers.
+    /// Validate held blocks whose timestamps are no longer too far in the future
+    /// and store the valid ones, as if just received from a peer.
     ///
     /// Locking:
-    ///   * acquires `global_state_lock` for read
-    async fn peer_discovery_and_reconnector(
+    ///   * acquires `global_state_lock` for read, and for write to store blocks
+    async fn release_future_blocks(
         &self,
         main_loop_state: &mut MutableMainLoopState,
     ) -> Result<()> {
-        let global_state = self.global_state_lock.lock_guard().await;
-
-        let connected_peers: Vec<PeerInfo> = global_state.net.peer_map.values().cloned().collect();
+        let released = main_loop_state
+            .future_blocks
+            .take_released(Timestamp::now());
+        self.global_state_lock
+            .lock_guard_mut()
+            .await
+            .net
+            .buffer_sizes
+            .future_blocks = main_loop_state.future_blocks.get_size();
+        for block in released {
+            let parent = self
+                .global_state_lock
+                .lock_guard()
+                .await
+                .chain
+                .archival_state()
+                .get_block(block.kernel.header.prev_block_digest)
+                .await?;
+            let verify_proofs = !(block.proofs_omitted()
+                && self.global_state_lock.cli().accepts_blocks_without_proofs());
+            let is_valid = parent.is_some_and(|parent| {
+                block.has_proof_of_work(&parent)
+                    && block.is_valid_with(&parent, Timestamp::now(), verify_proofs)
+            });
+            if !is_valid {
+                warn!(
+                    "Dropping held block of height {}: not valid after all",
+                    block.kernel.header.height
+                );
+                continue;
+            }
 
-        // Check if we are connected to too many peers
-        if connected_peers.len() > global_state.cli().max_peers as usize {
-            // This would indicate a race-condition on the peer map field in the state which
-            // we unfortunately cannot exclude. So we just disconnect from a peer that the user
-            // didn't request a connection to.
-            warn!(
-                "Max peer parameter is exceeded. max is {} but we are connected to {}. Attempting to fix.",
-                connected_peers.len(),
-                global_state.cli().max_peers
+            info!(
+                "Held block of height {} is no longer too far in the future",
+                block.kernel.header.height
             );
-            let mut rng = thread_rng();
+            self.handle_peer_thread_message(
+                PeerThreadToMain::NewBlocks(vec![block]),
+                main_loop_state,
+            )
+            .await?;
+        }
 
-            // pick a peer that was not specified in the CLI arguments to disconnect from
-            let peer_to_disconnect = connected_peers
-                .iter()
-                .filter(|peer| !global_state.cli().peers.contains(&peer.connected_address))
-                .choose(&mut rng);
-            match peer_to_disconnect {
-                Some(peer) => {
-                    self.main_to_peer_broadcast_tx
-                        .send(MainToPeerThread::Disconnect(peer.connected_address))?;
-                }
-                None => warn!("Unable to resolve max peer constraint due to manual override."),
-            };
+        Ok(())
+    }
 
+    /// Attempt to connect to the peers specified in the CLI arguments that are
+    /// not connected and whose backoff delay has passed.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn reconnect_to_configured_peers(
+        &self,
+        main_loop_state: &mut MutableMainLoopState,
+    ) -> Result<()> {
+        if self.global_state_lock.cli().peers.is_empty() {
             return Ok(());
         }
-
-        // Check if we lost connection to any of the peers specified in the peers CLI list.
-        // If we did, attempt to reconnect.
-        let connected_peer_addresses = connected_peers
-            .iter()
-            .map(|x| x.connected_address)
-            .collect_vec();
-        let peers_with_lost_connection = global_state
-            .cli()
-            .peers
-            .iter()
-            .filter(|peer| !connected_peer_addresses.contains(peer))
-            .cloned()
-            .collect_vec();
-        for peer_with_lost_connection in peers_with_lost_connection {
+        let mut global_state = self.global_state_lock.lock_guard_mut().await;
+
+        let connected_peer_addresses = global_state.net.peer_map.keys().copied().collect_vec();
+        let due_peers = global_state
+            .net
+            .peer_reconnector
+            .due_peers(&connected_peer_addresses, SystemTime::now());
+        for peer_address in due_peers {
             // Disallow reconnection if peer is in bad standing
             let standing = global_state
                 .net
-                .get_peer_standing_from_database(peer_with_lost_connection.ip())
+                .get_peer_standing_from_database(peer_address.ip())
                 .await;
-
-            if standing.is_some()
-                && standing.unwrap().standing < -(global_state.cli().peer_tolerance as i32)
-            {
-                info!("Not reconnecting to peer with lost connection because it was banned: {peer_with_lost_connection}");
-            } else {
-                info!(
-                    "Attempting to reconnect to peer with lost connection: {peer_with_lost_connection}"
-                );
+            if standing.is_some_and(|standing| {
+                global_state
+                    .cli()
+                    .is_banned_by_standing(peer_address.ip(), standing.standing)
+            }) {
+                info!("Not reconnecting to peer because it was banned: {peer_address}");
+                continue;
             }
 
+            info!("Attempting to connect to peer specified in CLI arguments: {peer_address}");
             let own_handshake_data: HandshakeData = global_state.get_own_handshakedata().await;
             let main_to_peer_broadcast_rx = self.main_to_peer_broadcast_tx.subscribe();
             let global_state_lock_clone = self.global_state_lock.clone();
@@ -613,7 +1134,7 @@ impl MainLoopHandler {
                 .name("call_peer_wrapper_1")
                 .spawn(async move {
                     call_peer_wrapper(
-                        peer_with_lost_connection,
+                        vec![peer_address],
                         global_state_lock_clone,
                         main_to_peer_broadcast_rx,
                         peer_thread_to_main_tx_clone,
@@ -630,6 +1151,47 @@ impl MainLoopHandler {
                 .retain(|th| !th.is_finished());
         }
 
+        Ok(())
+    }
+
+    /// Function to perform peer discovery: Finds potential peers from connected peers and attempts
+    /// to establish connections with one of those potential peers.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for read
+    async fn peer_discovery(&self, main_loop_state: &mut MutableMainLoopState) -> Result<()> {
+        let global_state = self.global_state_lock.lock_guard().await;
+
+        let connected_peers: Vec<PeerInfo> = global_state.net.peer_map.values().cloned().collect();
+
+        // Check if we are connected to too many peers
+        if connected_peers.len() > global_state.cli().max_peers as usize {
+            // This would indicate a race-condition on the peer map field in the state which
+            // we unfortunately cannot exclude. So we just disconnect from a peer that the user
+            // didn't request a connection to.
+            warn!(
+                "Max peer parameter is exceeded. max is {} but we are connected to {}. Attempting to fix.",
+                connected_peers.len(),
+                global_state.cli().max_peers
+            );
+            let mut rng = entropy::seeded_rng();
+
+            // pick a peer that was not specified in the CLI arguments to disconnect from
+            let peer_to_disconnect = connected_peers
+                .iter()
+                .filter(|peer| !global_state.cli().peers.contains(&peer.connected_address))
+                .choose(&mut rng);
+            match peer_to_disconnect {
+                Some(peer) => {
+                    self.main_to_peer_broadcast_tx
+                        .send(MainToPeerThread::Disconnect(peer.connected_address))?;
+                }
+                None => warn!("Unable to resolve max peer constraint due to manual override."),
+            };
+
+            return Ok(());
+        }
+
         // We don't make an outgoing connection if we've reached the peer limit, *or* if we are
         // one below the peer limit as we reserve this last slot for an ingoing connection.
         if connected_peers.len() == global_state.cli().max_peers as usize
@@ -653,28 +1215,32 @@ impl MainLoopHandler {
             .send(MainToPeerThread::MakePeerDiscoveryRequest)?;
 
         // 1)
-        let (peer_candidate, candidate_distance) = match main_loop_state
-            .potential_peers
-            .get_distant_candidate(&connected_peers, global_state.net.instance_id)
-        {
-            Some(candidate) => candidate,
-            None => return Ok(()),
-        };
+        let (peer_candidate, candidate_distance) =
+            match main_loop_state.potential_peers.get_distant_candidate(
+                &connected_peers,
+                global_state.net.instance_id,
+                &global_state.net.slow_peers,
+            ) {
+                Some(candidate) => candidate,
+                None => return Ok(()),
+            };
 
         // 2)
         info!(
             "Connecting to peer {} with distance {}",
-            peer_candidate, candidate_distance
+            peer_candidate.iter().join(", "),
+            candidate_distance
         );
         let own_handshake_data: HandshakeData = global_state.get_own_handshakedata().await;
         let main_to_peer_broadcast_rx = self.main_to_peer_broadcast_tx.subscribe();
         let global_state_lock_clone = self.global_state_lock.clone();
         let peer_thread_to_main_tx_clone = self.peer_thread_to_main_tx.to_owned();
+        let candidate_addresses = peer_candidate.clone();
         let outgoing_connection_thread = tokio::task::Builder::new()
             .name("call_peer_wrapper_2")
             .spawn(async move {
                 call_peer_wrapper(
-                    peer_candidate,
+                    candidate_addresses,
                     global_state_lock_clone,
                     main_to_peer_broadcast_rx,
                     peer_thread_to_main_tx_clone,
@@ -691,10 +1257,11 @@ impl MainLoopHandler {
             .retain(|th| !th.is_finished());
 
         // 3
-        self.main_to_peer_broadcast_tx
-            .send(MainToPeerThread::MakeSpecificPeerDiscoveryRequest(
-                peer_candidate,
-            ))?;
+        // Only the peer thread of the address that was connected to acts on this
+        for address in peer_candidate {
+            self.main_to_peer_broadcast_tx
+                .send(MainToPeerThread::MakeSpecificPeerDiscoveryRequest(address))?;
+        }
 
         // 4 is completed in the next call to this function provided that the in (3) connected
         // peer responded to the peer list request.
@@ -714,6 +1281,11 @@ impl MainLoopHandler {
             return Ok(());
         }
 
+        if global_state.net.disk_space.low {
+            debug!("Not syncing while free disk space is low");
+            return Ok(());
+        }
+
         info!("Running sync");
 
         // Check when latest batch of blocks was requested
@@ -746,12 +1318,46 @@ impl MainLoopHandler {
         // Create the next request from the reported
         info!("Creating new sync request");
 
-        // Pick a random peer that has reported to have relevant blocks
-        let candidate_peers = main_loop_state
+        main_loop_state
             .sync_state
-            .get_potential_peers_for_sync_request(current_block_proof_of_work_family);
-        let mut rng = thread_rng();
-        let chosen_peer = candidate_peers.choose(&mut rng);
+            .record_outcome_of_last_request(current_block_height);
+
+        // Pick the best ranked peer that has reported to have relevant blocks, or
+        // occasionally a random one. Archival peers are asked first, as light
+        // peers only store the blocks they received while running.
+        let candidate_peers = prefer_archival_peers(
+            main_loop_state
+                .sync_state
+                .get_potential_peers_for_sync_request(current_block_proof_of_work_family),
+            |peer| {
+                global_state
+                    .net
+                    .peer_map
+                    .get(&peer)
+                    .is_some_and(|peer_info| peer_info.is_archival_node)
+            },
+        );
+        let propagation = global_state.block_propagation.report(0).peers;
+        let scored_candidates = candidate_peers
+            .into_iter()
+            .map(|peer| {
+                let standing = global_state
+                    .net
+                    .peer_map
+                    .get(&peer)
+                    .map(|peer_info| peer_info.standing.standing)
+                    .unwrap_or_default();
+                let score = sync_quality_score(
+                    standing,
+                    main_loop_state.sync_state.peer_records.get(&peer),
+                    propagation.iter().find(|stats| stats.peer == peer),
+                );
+                (peer, score)
+            })
+            .collect_vec();
+        debug!("Sync candidates and their scores: {scored_candidates:?}");
+        let mut rng = entropy::seeded_rng();
+        let chosen_peer = choose_sync_peer(&scored_candidates, &mut rng);
         assert!(
             chosen_peer.is_some(),
             "A synchronization candidate must be available for a request. Otherwise the data structure is in an invalid state and syncing should not be active"
@@ -778,7 +1384,7 @@ impl MainLoopHandler {
         self.main_to_peer_broadcast_tx
             .send(MainToPeerThread::RequestBlockBatch(
                 most_canonical_digests,
-                *chosen_peer,
+                chosen_peer,
             ))
             .expect("Sending message to peers must succeed");
 
@@ -786,7 +1392,290 @@ impl MainLoopHandler {
         let requested_block_height = current_block_height.next();
         main_loop_state
             .sync_state
-            .record_request(requested_block_height, *chosen_peer);
+            .record_request(requested_block_height, chosen_peer);
+
+        Ok(())
+    }
+
+    /// Append a snapshot of the node's vital metrics to the metrics history.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for read
+    async fn record_metrics_snapshot(&self) -> Result<()> {
+        let cli = self.global_state_lock.cli();
+        let data_dir = DataDirectory::get(cli.data_dir.clone(), cli.network)?;
+
+        let (mut snapshot, journal_usage) = {
+            let global_state = self.global_state_lock.lock_guard().await;
+            let tip_header = global_state.chain.light_state().header();
+            let snapshot = MetricsSnapshot {
+                taken_at: Timestamp::now(),
+                tip_height: tip_header.height,
+                difficulty: tip_header.difficulty,
+                peer_count: global_state.net.peer_map.len() as u64,
+                mempool_transaction_count: global_state.mempool.len() as u64,
+                mempool_size: global_state.mempool.get_size() as u64,
+                disk_usage: 0,
+            };
+            let journal_usage = JournalDiskUsage::from_journal(&global_state.journal).await;
+            (snapshot, journal_usage)
+        };
+
+        // Walking the data directory is slow, so it is done without the lock
+        match journal_usage {
+            Ok(journal_usage) => match DiskUsage::measure(&data_dir, journal_usage).await {
+                Ok(disk_usage) => snapshot.disk_usage = disk_usage.total,
+                Err(err) => warn!("Failed to measure disk usage for metrics: {err:#}"),
+            },
+            Err(err) => warn!("Failed to measure journal disk usage for metrics: {err:#}"),
+        }
+
+        MetricsHistory::new(&data_dir.metrics_history_file_path())
+            .append(&snapshot)
+            .await
+    }
+
+    /// Measure the free space on the disk holding the data directory, and stop
+    /// or resume storing blocks when it crosses `--min-free-disk-space`.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn check_disk_space(&self) -> Result<()> {
+        let cli = self.global_state_lock.cli();
+        let min_free = cli.min_free_disk_space;
+        let data_dir = DataDirectory::get(cli.data_dir.clone(), cli.network)?;
+        let available = DiskSpaceStatus::measure(&data_dir.root_dir_path());
+        if available.is_none() {
+            debug!("Could not determine the free disk space");
+        }
+
+        let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+        let was_low = global_state_mut.net.disk_space.low;
+        let status = global_state_mut.net.disk_space.assess(available, min_free);
+        global_state_mut.net.disk_space = status;
+        let mine = global_state_mut.cli().mine;
+        drop(global_state_mut);
+
+        let event = match (was_low, status.low, status.available) {
+            (false, true, Some(available)) => {
+                warn!(
+                    "Only {} free on the disk holding the data directory. \
+                    Not storing blocks until more than {min_free} is free.",
+                    ByteSize(available)
+                );
+                if mine {
+                    self.main_to_miner_tx.send(MainToMiner::DiskSpaceLow)?;
+                }
+                ChainEvent::DiskSpaceLow {
+                    available,
+                    min_free: min_free.as_u64(),
+                }
+            }
+            (true, false, Some(available)) => {
+                info!(
+                    "{} free on the disk holding the data directory. Storing blocks again.",
+                    ByteSize(available)
+                );
+                if mine {
+                    self.main_to_miner_tx
+                        .send(MainToMiner::DiskSpaceRecovered)?;
+                }
+                ChainEvent::DiskSpaceRecovered { available }
+            }
+            _ => return Ok(()),
+        };
+
+        // Sending only fails when nobody is subscribed, which is fine.
+        let _ = self.chain_event_tx.send(event);
+
+        Ok(())
+    }
+
+    /// Check whether the tip stopped advancing while peers report higher tips,
+    /// and warn if it did. With `--reconnect-on-stale-tip`, disconnect from all
+    /// peers so that fresh ones are found.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn check_tip_staleness(&self, main_loop_state: &MutableMainLoopState) -> Result<()> {
+        let peer_heights = main_loop_state
+            .sync_state
+            .peer_sync_states
+            .values()
+            .map(|sync_state| sync_state.claimed_max_height)
+            .collect_vec();
+
+        let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+        let was_stale = global_state_mut.net.tip_staleness.stale;
+        let staleness = global_state_mut.net.tip_staleness.assess(
+            global_state_mut.chain.light_state().hash(),
+            global_state_mut.chain.light_state().kernel.header.height,
+            &peer_heights,
+            Timestamp::now(),
+        );
+        global_state_mut.net.tip_staleness = staleness;
+
+        if staleness.stale && !was_stale {
+            warn!(
+                "Tip has not changed since {}, while peers report tips up to height {}. Syncing may have stalled.",
+                staleness.tip_seen_at.standard_format(),
+                staleness
+                    .highest_peer_height
+                    .map(|height| height.to_string())
+                    .unwrap_or_default(),
+            );
+            if global_state_mut.cli().reconnect_on_stale_tip {
+                info!("Disconnecting from all peers to find fresh ones");
+                self.main_to_peer_broadcast_tx
+                    .send(MainToPeerThread::DisconnectAll())?;
+            }
+        } else if !staleness.stale && was_stale {
+            info!("Tip is no longer stale");
+        }
+
+        Ok(())
+    }
+
+    /// Compare our clock against the clocks reported by peers and against the
+    /// tip's timestamp, and warn if it deviates too much. Also asks the peers
+    /// for their clocks again, for the next check.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn check_clock_skew(&self) -> Result<()> {
+        let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+        let peer_offsets = global_state_mut
+            .net
+            .peer_map
+            .values()
+            .filter_map(|peer| peer.clock_offset_millis)
+            .collect_vec();
+        let status = ClockSkewStatus::assess(
+            &peer_offsets,
+            global_state_mut.chain.light_state().header().timestamp,
+            Timestamp::now(),
+            global_state_mut.cli().max_clock_skew,
+        );
+
+        let was_skewed = global_state_mut.net.clock_skew.skewed;
+        global_state_mut.net.clock_skew = status;
+        if !global_state_mut.net.peer_map.is_empty() {
+            self.main_to_peer_broadcast_tx
+                .send(MainToPeerThread::ClockRequest)?;
+        }
+
+        if status.skewed && !was_skewed {
+            warn!(
+                "Local clock appears to be skewed: median peer offset {:?} ms, tip is {} ms in the future. \
+                Check the system clock.",
+                status.median_peer_offset_millis, status.tip_ahead_millis
+            );
+            if global_state_mut.cli().mine && global_state_mut.cli().refuse_mining_on_clock_skew {
+                self.main_to_miner_tx.send(MainToMiner::ClockSkewDetected)?;
+            }
+        } else if !status.skewed && was_skewed {
+            info!("Local clock no longer appears to be skewed");
+            if global_state_mut.cli().mine && global_state_mut.cli().refuse_mining_on_clock_skew {
+                self.main_to_miner_tx.send(MainToMiner::ClockSkewResolved)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Insert a transaction into the mempool and announce it to all peers,
+    /// ending its Dandelion stem phase if it was in one.
+    fn diffuse_transaction(
+        &self,
+        global_state: &mut GlobalState,
+        transaction: Transaction,
+    ) -> Result<()> {
+        global_state.net.stem_pool.remove(Hash::hash(&transaction));
+        global_state.mempool.insert(&transaction);
+        self.mempool_changed_tx.send_replace(());
+
+        // send notification to peers
+        let transaction_notification: TransactionNotification = transaction.into();
+        self.main_to_peer_broadcast_tx
+            .send(MainToPeerThread::TransactionNotification(
+                transaction_notification,
+            ))?;
+
+        Ok(())
+    }
+
+    /// Pass a transaction in the Dandelion stem phase on to a random peer, or
+    /// diffuse it if the stem ends here. `stem_from` is the peer that passed the
+    /// transaction to us, or `None` if the transaction is our own.
+    fn relay_transaction(
+        &self,
+        global_state: &mut GlobalState,
+        transaction: Transaction,
+        stem_from: Option<SocketAddr>,
+    ) -> Result<()> {
+        let mut rng = entropy::seeded_rng();
+        // Blocks-only peers would drop the transaction, and older peers cannot
+        // decode stem-phase transactions
+        let peers = global_state
+            .net
+            .peer_map
+            .iter()
+            .filter(|(_, peer)| !peer.blocks_only && peer.supports_message_extensions())
+            .map(|(address, _)| *address)
+            .collect_vec();
+        let Some(next_peer) = choose_stem_peer(peers, stem_from, &mut rng) else {
+            return self.diffuse_transaction(global_state, transaction);
+        };
+
+        // Hold the transaction, so that it can be diffused if the stem is cut short
+        let evicted =
+            global_state
+                .net
+                .stem_pool
+                .insert(transaction.clone(), Timestamp::now(), &mut rng);
+        for evicted in evicted {
+            self.diffuse_transaction(global_state, evicted)?;
+        }
+
+        debug!("Passing stem-phase transaction to {next_peer}");
+        self.main_to_peer_broadcast_tx
+            .send(MainToPeerThread::StemTransaction(
+                Box::new(transaction),
+                next_peer,
+            ))?;
+
+        Ok(())
+    }
+
+    /// Diffuse stem-phase transactions whose embargo has ended without them
+    /// being diffused by another node. Transactions that can no longer be
+    /// confirmed are dropped.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn diffuse_embargoed_transactions(&self) -> Result<()> {
+        let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+        let expired = global_state_mut
+            .net
+            .stem_pool
+            .take_expired(Timestamp::now());
+        for transaction in expired {
+            let confirmable = transaction.is_confirmable_relative_to(
+                &global_state_mut
+                    .chain
+                    .light_state()
+                    .kernel
+                    .body
+                    .mutator_set_accumulator,
+            );
+            if !confirmable {
+                debug!("Dropping stem-phase transaction that can no longer be confirmed");
+                continue;
+            }
+
+            info!("Embargo of stem-phase transaction ended; diffusing it");
+            self.diffuse_transaction(&mut global_state_mut, transaction)?;
+        }
 
         Ok(())
     }
@@ -806,6 +1695,12 @@ impl MainLoopHandler {
         let peer_discovery_timer = time::sleep(peer_discovery_timer_interval);
         tokio::pin!(peer_discovery_timer);
 
+        // Set reconnection to configured peers to be checked every second. The
+        // backoff delays are kept by the peer reconnector.
+        let peer_reconnect_timer_interval = Duration::from_secs(PEER_RECONNECT_INTERVAL_IN_SECONDS);
+        let peer_reconnect_timer = time::sleep(peer_reconnect_timer_interval);
+        tokio::pin!(peer_reconnect_timer);
+
         // Set synchronization to run every M seconds. The timer must be reset every time it has run.
         let sync_timer_interval = Duration::from_secs(SYNC_REQUEST_INTERVAL_IN_SECONDS);
         let synchronization_timer = time::sleep(sync_timer_interval);
@@ -827,6 +1722,38 @@ impl MainLoopHandler {
         let mp_resync_timer = time::sleep(mp_resync_timer_interval);
         tokio::pin!(mp_resync_timer);
 
+        // Set comparison of our clock against peers and the tip to run every R seconds
+        let clock_skew_timer_interval = Duration::from_secs(CLOCK_SKEW_CHECK_INTERVAL_IN_SECS);
+        let clock_skew_timer = time::sleep(clock_skew_timer_interval);
+        tokio::pin!(clock_skew_timer);
+
+        // Set up timer for diffusing stem-phase transactions whose embargo ended
+        let stem_embargo_timer_interval = Duration::from_secs(STEM_EMBARGO_CHECK_INTERVAL_IN_SECS);
+        let stem_embargo_timer = time::sleep(stem_embargo_timer_interval);
+        tokio::pin!(stem_embargo_timer);
+
+        // Set up timer for validating held blocks once they are no longer too far in the future
+        let future_block_timer_interval = Duration::from_secs(FUTURE_BLOCK_CHECK_INTERVAL_IN_SECS);
+        let future_block_timer = time::sleep(future_block_timer_interval);
+        tokio::pin!(future_block_timer);
+
+        // Set up timer for detecting a tip that stopped advancing
+        let tip_staleness_timer_interval =
+            Duration::from_secs(TIP_STALENESS_CHECK_INTERVAL_IN_SECS);
+        let tip_staleness_timer = time::sleep(tip_staleness_timer_interval);
+        tokio::pin!(tip_staleness_timer);
+
+        // Set up timer for recording the metrics history
+        let metrics_snapshot_timer_interval =
+            Duration::from_secs(METRICS_SNAPSHOT_INTERVAL_IN_SECS);
+        let metrics_snapshot_timer = time::sleep(metrics_snapshot_timer_interval);
+        tokio::pin!(metrics_snapshot_timer);
+
+        // Set up timer for checking the free disk space
+        let disk_space_timer_interval = Duration::from_secs(DISK_SPACE_CHECK_INTERVAL_IN_SECS);
+        let disk_space_timer = time::sleep(disk_space_timer_interval);
+        tokio::pin!(disk_space_timer);
+
         // Spawn threads to monitor for SIGTERM, SIGINT, and SIGQUIT. These
         // signals are only used on Unix systems.
         let (_tx_term, mut rx_term): (mpsc::Sender<()>, mpsc::Receiver<()>) =
@@ -895,7 +1822,7 @@ impl MainLoopHandler {
                 }
 
                 // Handle incoming connections from peer
-                Ok((stream, peer_address)) = self.incoming_peer_listener.accept() => {
+                Ok((stream, peer_address)) = accept_incoming_peer(&self.incoming_peer_listeners) => {
                     let state = self.global_state_lock.lock_guard().await;
                     let main_to_peer_broadcast_rx_clone: broadcast::Receiver<MainToPeerThread> = self.main_to_peer_broadcast_tx.subscribe();
                     let peer_thread_to_main_tx_clone: mpsc::Sender<PeerThreadToMain> = self.peer_thread_to_main_tx.clone();
@@ -948,12 +1875,19 @@ impl MainLoopHandler {
                     // Check number of peers we are connected to and connect to more peers
                     // if needed.
                     debug!("Timer: peer discovery job");
-                    self.peer_discovery_and_reconnector(&mut main_loop_state).await?;
+                    self.peer_discovery(&mut main_loop_state).await?;
 
                     // Reset the timer to run this branch again in N seconds
                     peer_discovery_timer.as_mut().reset(tokio::time::Instant::now() + peer_discovery_timer_interval);
                 }
 
+                // Handle reconnection to peers specified in the CLI arguments
+                _ = &mut peer_reconnect_timer => {
+                    self.reconnect_to_configured_peers(&mut main_loop_state).await?;
+
+                    peer_reconnect_timer.as_mut().reset(tokio::time::Instant::now() + peer_reconnect_timer_interval);
+                }
+
                 // Handle synchronization (i.e. batch-downloading of blocks)
                 _ = &mut synchronization_timer => {
                     debug!("Timer: block-synchronization job");
@@ -987,6 +1921,58 @@ impl MainLoopHandler {
 
                     mp_resync_timer.as_mut().reset(tokio::time::Instant::now() + mp_resync_timer_interval);
                 }
+
+                // Handle clock-skew detection
+                _ = &mut clock_skew_timer => {
+                    debug!("Timer: clock-skew check");
+                    self.check_clock_skew().await?;
+
+                    clock_skew_timer.as_mut().reset(tokio::time::Instant::now() + clock_skew_timer_interval);
+                }
+
+                // Handle Dandelion stem-phase embargoes
+                _ = &mut stem_embargo_timer => {
+                    debug!("Timer: stem-phase embargo check");
+                    self.diffuse_embargoed_transactions().await?;
+
+                    stem_embargo_timer.as_mut().reset(tokio::time::Instant::now() + stem_embargo_timer_interval);
+                }
+
+                // Handle held blocks that were too far in the future
+                _ = &mut future_block_timer => {
+                    if !main_loop_state.future_blocks.is_empty() {
+                        debug!("Timer: future block check");
+                        self.release_future_blocks(&mut main_loop_state).await?;
+                    }
+
+                    future_block_timer.as_mut().reset(tokio::time::Instant::now() + future_block_timer_interval);
+                }
+
+                // Handle tips that stopped advancing
+                _ = &mut tip_staleness_timer => {
+                    debug!("Timer: tip staleness check");
+                    self.check_tip_staleness(&main_loop_state).await?;
+
+                    tip_staleness_timer.as_mut().reset(tokio::time::Instant::now() + tip_staleness_timer_interval);
+                }
+
+                // Record the metrics history
+                _ = &mut metrics_snapshot_timer => {
+                    debug!("Timer: metrics snapshot");
+                    if let Err(err) = self.record_metrics_snapshot().await {
+                        warn!("Failed to record metrics snapshot: {err:#}");
+                    }
+
+                    metrics_snapshot_timer.as_mut().reset(tokio::time::Instant::now() + metrics_snapshot_timer_interval);
+                }
+
+                // Stop or resume storing blocks as the free disk space changes
+                _ = &mut disk_space_timer => {
+                    debug!("Timer: disk space check");
+                    self.check_disk_space().await?;
+
+                    disk_space_timer.as_mut().reset(tokio::time::Instant::now() + disk_space_timer_interval);
+                }
             }
         }
 
@@ -1008,15 +1994,28 @@ impl MainLoopHandler {
                     transaction.kernel.mutator_set_hash
                 );
 
-                // send notification to peers
-                let notification: TransactionNotification = transaction.as_ref().clone().into();
-                self.main_to_peer_broadcast_tx
-                    .send(MainToPeerThread::TransactionNotification(notification))?;
-
-                // insert transaction into mempool
-                self.global_state_lock
-                    .lock_mut(|s| s.mempool.insert(&transaction))
+                // Start relaying the transaction to peers. In the Dandelion stem
+                // phase it is kept out of the mempool, which peers can query, so as
+                // not to reveal that it originated here. It enters the mempool when
+                // diffused.
+                let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+                global_state_mut
+                    .wallet_state
+                    .sent_transactions
+                    .record(&transaction, Timestamp::now());
+                global_state_mut
+                    .journal
+                    .record(JournalEvent::TransactionSent {
+                        transaction_digest: Hash::hash(transaction.as_ref()),
+                        fee: transaction.kernel.fee,
+                    })
                     .await;
+                if global_state_mut.cli().no_dandelion {
+                    self.diffuse_transaction(&mut global_state_mut, *transaction)?;
+                } else {
+                    self.relay_transaction(&mut global_state_mut, *transaction, None)?;
+                }
+                drop(global_state_mut);
 
                 // do not shut down
                 Ok(false)
@@ -1032,6 +2031,83 @@ impl MainLoopHandler {
                 self.main_to_miner_tx.send(MainToMiner::StartMining)?;
                 Ok(false)
             }
+            RPCServerToMain::BroadcastAlert(alert) => {
+                // Verified and stored by the RPC server
+                info!("Broadcasting alert {} to peers", alert.alert.id);
+                self.main_to_peer_broadcast_tx
+                    .send(MainToPeerThread::Alert(alert))
+                    .expect(
+                        "Peer handler broadcast channel prematurely closed. This should never happen.",
+                    );
+                Ok(false)
+            }
+            RPCServerToMain::PreferTip(digest) => {
+                let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+                if global_state_mut.net.disk_space.low {
+                    warn!("Free disk space is low. Not switching to preferred tip {digest}.");
+                    return Ok(false);
+                }
+
+                // The tip may have changed since the RPC server checked the block
+                let old_tip_digest = global_state_mut.chain.light_state().hash();
+                let old_tip_height = global_state_mut.chain.light_state().header().height;
+                if let Err(err) = check_preferable_tip(
+                    global_state_mut.chain.archival_state(),
+                    old_tip_digest,
+                    digest,
+                )
+                .await
+                {
+                    warn!("Not switching to preferred tip: {err}");
+                    return Ok(false);
+                }
+
+                let mut new_tip = None;
+                if digest != old_tip_digest {
+                    let Some(block) = global_state_mut
+                        .chain
+                        .archival_state()
+                        .get_block(digest)
+                        .await?
+                    else {
+                        warn!("Preferred tip {digest} is not stored");
+                        return Ok(false);
+                    };
+                    info!("Switching to tip {digest} as preferred by the operator");
+                    global_state_mut.set_new_tip(block.clone()).await?;
+                    self.publish_new_tip_events(
+                        &mut global_state_mut,
+                        old_tip_digest,
+                        old_tip_height,
+                    )
+                    .await;
+                    new_tip = Some(block);
+                }
+
+                global_state_mut.net.preferred_tip = Some(digest);
+                let height = global_state_mut.chain.light_state().header().height;
+                global_state_mut
+                    .journal
+                    .record(JournalEvent::TipPreferred {
+                        digest,
+                        height,
+                        previous_tip_digest: old_tip_digest,
+                    })
+                    .await;
+                drop(global_state_mut);
+
+                if let Some(block) = new_tip {
+                    if self.global_state_lock.cli().mine {
+                        self.main_to_miner_tx
+                            .send(MainToMiner::NewBlock(Box::new(block.clone())))?;
+                    }
+                    self.main_to_peer_broadcast_tx
+                        .send(MainToPeerThread::Block(Box::new(block)))
+                        .expect("Peer handler broadcast was closed. This should never happen");
+                }
+
+                Ok(false)
+            }
             RPCServerToMain::Shutdown => {
                 info!("Recived RPC shutdown request.");
 
@@ -1,3 +1,4 @@
+use crate::config_models::cli_args;
 use crate::models::blockchain::block::block_body::BlockBody;
 use crate::models::blockchain::block::block_header::BlockHeader;
 use crate::models::blockchain::block::block_height::BlockHeight;
@@ -5,6 +6,7 @@ use crate::models::blockchain::block::mutator_set_update::*;
 use crate::models::blockchain::block::*;
 use crate::models::blockchain::shared::*;
 use crate::models::blockchain::transaction;
+use crate::models::blockchain::transaction::coinbase_message::CoinbaseMessage;
 use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
 use crate::models::blockchain::transaction::utxo::*;
 use crate::models::blockchain::transaction::validity::TransactionValidationLogic;
@@ -14,19 +16,19 @@ use crate::models::blockchain::type_scripts::TypeScript;
 use crate::models::channel::*;
 use crate::models::consensus::timestamp::Timestamp;
 use crate::models::shared::SIZE_20MB_IN_BYTES;
+use crate::models::state::wallet::address::generation_address::ReceivingAddress;
 use crate::models::state::wallet::utxo_notification_pool::{ExpectedUtxo, UtxoNotifier};
 use crate::models::state::wallet::WalletSecret;
 use crate::models::state::{GlobalState, GlobalStateLock};
 use crate::prelude::twenty_first;
+use crate::util_types::entropy;
 use crate::util_types::mutator_set::commit;
 use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 use anyhow::{Context, Result};
 use futures::channel::oneshot;
 use num_traits::identities::Zero;
 use rand::rngs::StdRng;
-use rand::thread_rng;
 use rand::Rng;
-use rand::SeedableRng;
 use std::ops::Deref;
 use std::time::Duration;
 use tasm_lib::twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
@@ -104,7 +106,7 @@ async fn mine_block(
     block_header: BlockHeader,
     block_body: BlockBody,
     sender: oneshot::Sender<NewBlockFound>,
-    coinbase_utxo_info: ExpectedUtxo,
+    coinbase_utxo_info: Option<ExpectedUtxo>,
     difficulty: U32s<5>,
     unrestricted_mining: bool,
 ) {
@@ -138,7 +140,7 @@ fn mine_block_worker(
     block_header: BlockHeader,
     block_body: BlockBody,
     sender: oneshot::Sender<NewBlockFound>,
-    coinbase_utxo_info: ExpectedUtxo,
+    coinbase_utxo_info: Option<ExpectedUtxo>,
     difficulty: U32s<5>,
     unrestricted_mining: bool,
 ) {
@@ -150,10 +152,9 @@ fn mine_block_worker(
         threshold
     );
 
-    // The RNG used to sample nonces must be thread-safe, which `thread_rng()` is not.
-    // Solution: use `thread_rng()` to generate a seed, and generate a thread-safe RNG
-    // seeded with that seed. The `thread_rng()` object is dropped immediately.
-    let mut rng: StdRng = SeedableRng::from_seed(thread_rng().gen());
+    // The RNG used to sample nonces must be thread-safe and must not contend
+    // for the lock of the entropy source, so it gets a generator of its own.
+    let mut rng: StdRng = entropy::seeded_rng();
 
     let block_type = Block::mk_std_block_type(None);
     let mut block = Block::new(block_header, block_body, block_type);
@@ -195,7 +196,7 @@ fn mine_block_worker(
 
     let new_block_found = NewBlockFound {
         block: Box::new(block),
-        coinbase_utxo_info: Box::new(coinbase_utxo_info),
+        coinbase_utxo_info: coinbase_utxo_info.map(Box::new),
     };
 
     let timestamp = new_block_found.block.kernel.header.timestamp;
@@ -223,6 +224,7 @@ Difficulty threshold: {threshold}
 fn make_coinbase_transaction(
     coinbase_utxo: &Utxo,
     receiver_digest: Digest,
+    public_announcements: Vec<PublicAnnouncement>,
     wallet_secret: &WalletSecret,
     block_height: BlockHeight,
     mutator_set_accumulator: MutatorSetAccumulator,
@@ -249,7 +251,7 @@ fn make_coinbase_transaction(
     let kernel = TransactionKernel {
         inputs: vec![],
         outputs: vec![coinbase_addition_record],
-        public_announcements: vec![],
+        public_announcements,
         fee: NeptuneCoins::zero(),
         coinbase: Some(coinbase_amount),
         timestamp,
@@ -276,20 +278,46 @@ fn make_coinbase_transaction(
     )
 }
 
+/// Return the address given with `--mine-to-address`, if any. Fails if the
+/// address is not valid on the node's network.
+pub fn coinbase_address(cli_args: &cli_args::Args) -> Result<Option<ReceivingAddress>> {
+    cli_args
+        .mine_to_address
+        .as_ref()
+        .map(|encoded| {
+            ReceivingAddress::from_bech32m(encoded.to_owned(), cli_args.network)
+                .with_context(|| format!("Invalid address to mine to: {encoded}"))
+        })
+        .transpose()
+}
+
+/// Return the coinbase message set with `--coinbase-message` and
+/// `--extra-nonce`, if any. Fails if the tag is too long.
+pub fn coinbase_message(cli_args: &cli_args::Args) -> Result<Option<CoinbaseMessage>> {
+    if cli_args.coinbase_message.is_none() && cli_args.extra_nonce.is_none() {
+        return Ok(None);
+    }
+
+    let tag = cli_args.coinbase_message.clone().unwrap_or_default();
+    let extra_nonce = cli_args.extra_nonce.unwrap_or_else(entropy::random);
+    CoinbaseMessage::new(tag, extra_nonce).map(Some)
+}
+
 /// Create the transaction that goes into the block template. The transaction is
 /// built from the mempool and from the coinbase transaction. Also returns the
-/// "sender randomness" used in the coinbase transaction.
+/// coinbase UTXO for the wallet to expect, unless the coinbase pays to an
+/// address given with `--mine-to-address`.
 fn create_block_transaction(
     latest_block: &Block,
     global_state: &GlobalState,
     timestamp: Timestamp,
-) -> (Transaction, ExpectedUtxo) {
+) -> (Transaction, Option<ExpectedUtxo>) {
     let block_capacity_for_transactions = SIZE_20MB_IN_BYTES;
 
     // Get most valuable transactions from mempool
     let transactions_to_include = global_state
         .mempool
-        .get_transactions_for_block(block_capacity_for_transactions);
+        .get_transactions_for_block(block_capacity_for_transactions, timestamp);
 
     // Build coinbase UTXO
     let transaction_fees = transactions_to_include
@@ -300,16 +328,38 @@ fn create_block_transaction(
         .wallet_state
         .wallet_secret
         .nth_generation_spending_key(0);
-    let receiving_address = coinbase_recipient_spending_key.to_address();
+    let external_address = coinbase_address(global_state.cli())
+        .expect("address to mine to must have been validated on startup");
+    let receiving_address = external_address
+        .clone()
+        .unwrap_or_else(|| coinbase_recipient_spending_key.to_address());
     let next_block_height: BlockHeight = latest_block.kernel.header.height.next();
 
     let lock_script = receiving_address.lock_script();
     let coinbase_amount = Block::get_mining_reward(next_block_height) + transaction_fees;
     let coinbase_utxo = Utxo::new_native_coin(lock_script, coinbase_amount);
 
+    // An external recipient learns of the coinbase only through an announcement
+    let sender_randomness = global_state
+        .wallet_state
+        .wallet_secret
+        .generate_sender_randomness(next_block_height, receiving_address.privacy_digest);
+    let mut public_announcements = match external_address {
+        Some(address) => vec![address
+            .generate_public_announcement(&coinbase_utxo, sender_randomness)
+            .expect("coinbase UTXO must be encryptable")],
+        None => vec![],
+    };
+    if let Some(message) = coinbase_message(global_state.cli())
+        .expect("coinbase message must have been validated on startup")
+    {
+        public_announcements.push(message.to_public_announcement());
+    }
+
     let (coinbase_transaction, coinbase_sender_randomness) = make_coinbase_transaction(
         &coinbase_utxo,
         receiving_address.privacy_digest,
+        public_announcements,
         &global_state.wallet_state.wallet_secret,
         next_block_height,
         latest_block.kernel.body.mutator_set_accumulator.clone(),
@@ -328,16 +378,22 @@ fn create_block_transaction(
             Transaction::merge_with(acc, transaction)
         });
 
-    let utxo_info_for_coinbase = ExpectedUtxo::new(
-        coinbase_utxo,
-        coinbase_sender_randomness,
-        coinbase_recipient_spending_key.privacy_preimage,
-        UtxoNotifier::OwnMiner,
-    );
+    let utxo_info_for_coinbase = global_state.cli().mine_to_address.is_none().then(|| {
+        ExpectedUtxo::new(
+            coinbase_utxo,
+            coinbase_sender_randomness,
+            coinbase_recipient_spending_key.privacy_preimage.expose(),
+            UtxoNotifier::OwnMiner,
+        )
+    });
 
     (merged_transaction, utxo_info_for_coinbase)
 }
 
+/// Mine on the latest block. The fees of each block template are reported on
+/// `template_fees_tx`, and the template is rebuilt whenever the template
+/// manager signals on `template_refresh`.
+///
 /// Locking:
 ///   * acquires `global_state_lock` for write
 pub async fn mine(
@@ -345,6 +401,8 @@ pub async fn mine(
     to_main: mpsc::Sender<MinerToMain>,
     mut latest_block: Block,
     global_state_lock: GlobalStateLock,
+    template_fees_tx: watch::Sender<Option<NeptuneCoins>>,
+    mut template_refresh: watch::Receiver<()>,
 ) -> Result<()> {
     // Wait before starting mining thread to ensure that peers have sent us information about
     // their latest blocks. This should prevent the client from finding blocks that will later
@@ -360,18 +418,31 @@ pub async fn mine(
                 info!("Not mining because we are syncing");
                 global_state_lock.set_mining(false).await;
                 None
+            } else if global_state_lock
+                .lock(|s| s.net.clock_skew.skewed && s.cli().refuse_mining_on_clock_skew)
+                .await
+            {
+                info!("Not mining because our clock is skewed");
+                global_state_lock.set_mining(false).await;
+                None
+            } else if global_state_lock.lock(|s| s.net.disk_space.low).await {
+                info!("Not mining because free disk space is low");
+                global_state_lock.set_mining(false).await;
+                None
             } else if pause_mine {
                 info!("Not mining because mining was paused");
                 global_state_lock.set_mining(false).await;
                 None
             } else {
                 // Build the block template and spawn the worker thread to mine on it
+                template_refresh.borrow_and_update();
                 let now = Timestamp::now();
                 let (transaction, coinbase_utxo_info) = create_block_transaction(
                     &latest_block,
                     global_state_lock.lock_guard().await.deref(),
                     now,
                 );
+                template_fees_tx.send_replace(Some(transaction.kernel.fee));
                 let (block_header, block_body) =
                     make_block_template(&latest_block, transaction, now);
                 let miner_task = mine_block(
@@ -390,8 +461,19 @@ pub async fn mine(
                 )
             };
 
-        // Await a message from either the worker thread or from the main loop
+        if miner_thread.is_none() {
+            template_fees_tx.send_replace(None);
+        }
+
+        // Await a message from either the worker thread, the main loop, or the
+        // template manager
         select! {
+            Ok(()) = template_refresh.changed(), if miner_thread.is_some() => {
+                debug!("Miner rebuilds its block template for better fees");
+                if let Some(mt) = miner_thread {
+                    mt.abort();
+                }
+            }
             changed = from_main.changed() => {
                 info!("Mining thread got message from main");
                 if let e@Err(_) = changed {
@@ -445,6 +527,25 @@ pub async fn mine(
                             mt.abort();
                         }
                     }
+                    MainToMiner::ClockSkewDetected => {
+                        // Blocks mined with a bad clock could be rejected by
+                        // peers, so halt the mining thread like for syncing.
+                        if let Some(mt) = miner_thread {
+                            mt.abort();
+                        }
+                    }
+                    MainToMiner::ClockSkewResolved => {
+                        // Mining resumes at the top of the loop.
+                    }
+                    MainToMiner::DiskSpaceLow => {
+                        // Found blocks could not be stored.
+                        if let Some(mt) = miner_thread {
+                            mt.abort();
+                        }
+                    }
+                    MainToMiner::DiskSpaceRecovered => {
+                        // Mining resumes at the top of the loop.
+                    }
                 }
             }
             new_block_res = worker_thread_rx => {
@@ -614,6 +715,93 @@ mod mine_loop_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn coinbase_can_pay_to_external_address() -> Result<()> {
+        let network = Network::RegTest;
+        let mut global_state_lock =
+            mock_genesis_global_state(network, 2, WalletSecret::devnet_wallet()).await;
+        let cold_key = WalletSecret::new_random().nth_generation_spending_key(0);
+
+        // pretend --mine --mine-to-address <cold address>
+        let mut cli = global_state_lock.cli().clone();
+        cli.mine = true;
+        cli.mine_to_address = Some(cold_key.to_address().to_bech32m(network)?);
+        global_state_lock.set_cli(cli).await;
+
+        let global_state = global_state_lock.lock_guard().await;
+        let genesis_block = Block::genesis_block(network);
+        let now = genesis_block.kernel.header.timestamp;
+        let (transaction, coinbase_utxo_info) =
+            create_block_transaction(&genesis_block, &global_state, now);
+        assert!(
+            coinbase_utxo_info.is_none(),
+            "Node wallet must not expect a coinbase paid to an external address"
+        );
+
+        // The owner of the external address finds the coinbase
+        let announced = cold_key.scan_for_announced_utxos(&transaction);
+        assert_eq!(1, announced.len());
+        assert_eq!(transaction.kernel.outputs, vec![announced[0].0]);
+
+        let (block_header, block_body) = make_block_template(&genesis_block, transaction, now);
+        let block = Block::new(block_header, block_body, Block::mk_std_block_type(None));
+        assert!(block.is_valid(&genesis_block, now));
+
+        // Addresses for other networks are rejected
+        let mut cli = global_state.cli().clone();
+        cli.mine_to_address = Some(cold_key.to_address().to_bech32m(Network::Alpha)?);
+        assert!(coinbase_address(&cli).is_err());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn coinbase_message_partitions_search_space() -> Result<()> {
+        let network = Network::RegTest;
+        let mut global_state_lock =
+            mock_genesis_global_state(network, 2, WalletSecret::devnet_wallet()).await;
+        let genesis_block = Block::genesis_block(network);
+        let now = genesis_block.kernel.header.timestamp;
+
+        let mut templates = vec![];
+        for extra_nonce in [1, 2] {
+            // pretend --coinbase-message <tag> --extra-nonce <extra_nonce>
+            let mut cli = global_state_lock.cli().clone();
+            cli.coinbase_message = Some("test pool".to_owned());
+            cli.extra_nonce = Some(extra_nonce);
+            global_state_lock.set_cli(cli).await;
+
+            let global_state = global_state_lock.lock_guard().await;
+            let (transaction, _) = create_block_transaction(&genesis_block, &global_state, now);
+            let messages = transaction
+                .kernel
+                .public_announcements
+                .iter()
+                .filter_map(CoinbaseMessage::from_public_announcement)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                vec![CoinbaseMessage::new("test pool".to_owned(), extra_nonce)?],
+                messages
+            );
+
+            let (block_header, block_body) = make_block_template(&genesis_block, transaction, now);
+            let block = Block::new(block_header, block_body, Block::mk_std_block_type(None));
+            assert!(block.is_valid(&genesis_block, now));
+            templates.push(block);
+        }
+
+        // same header nonce, different extra nonce: different blocks
+        assert_eq!(
+            templates[0].kernel.header.nonce,
+            templates[1].kernel.header.nonce
+        );
+        assert_ne!(templates[0].hash(), templates[1].hash());
+
+        Ok(())
+    }
+
     /// This test mines a single block at height 1 on the regtest network
     /// and then validates it with `Block::is_valid()` and
     /// `Block::has_proof_of_work()`.
@@ -1,4 +1,5 @@
 pub mod block;
+pub mod consensus_encoding;
 pub mod shared;
 pub mod transaction;
 pub mod type_scripts;
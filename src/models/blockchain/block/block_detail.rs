@@ -0,0 +1,37 @@
+//! BlockDetail is the full content of a block, but for its proofs, intended
+//! for block explorers.
+
+use serde::{Deserialize, Serialize};
+
+use super::block_header::BlockHeader;
+use super::Block;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlockDetail {
+    #[serde(with = "digest_serde")]
+    pub digest: Digest,
+    pub header: BlockHeader,
+    pub transaction_kernel: TransactionKernel,
+    pub num_addition_records: usize,
+    pub num_removal_records: usize,
+
+    /// Whether the block is on the chain of the tip, rather than on a fork
+    pub is_canonical: bool,
+}
+
+impl BlockDetail {
+    pub fn from_block(block: &Block, is_canonical: bool) -> Self {
+        let transaction_kernel = block.body().transaction.kernel.clone();
+        Self {
+            digest: block.hash(),
+            header: block.header().clone(),
+            num_addition_records: transaction_kernel.outputs.len(),
+            num_removal_records: transaction_kernel.inputs.len(),
+            transaction_kernel,
+            is_canonical,
+        }
+    }
+}
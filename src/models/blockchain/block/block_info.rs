@@ -8,6 +8,7 @@ use crate::models::blockchain::block::Block;
 use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
 use crate::models::consensus::timestamp::Timestamp;
 use crate::prelude::twenty_first;
+use crate::util_types::digest_serde;
 use serde::{Deserialize, Serialize};
 use twenty_first::math::digest::Digest;
 use twenty_first::prelude::U32s;
@@ -16,7 +17,9 @@ use twenty_first::prelude::U32s;
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct BlockInfo {
     pub height: BlockHeight,
+    #[serde(with = "digest_serde")]
     pub digest: Digest,
+    #[serde(with = "digest_serde")]
     pub prev_block_digest: Digest,
     pub timestamp: Timestamp,
     pub proof_of_work_line: U32s<PROOF_OF_WORK_COUNT_U32_SIZE>,
@@ -29,6 +32,10 @@ pub struct BlockInfo {
     pub fee: NeptuneCoins,
     pub is_genesis: bool,
     pub is_tip: bool,
+
+    /// Whether the block can no longer be reorganized away. See
+    /// [`finality`](crate::models::state::finality).
+    pub is_final: bool,
 }
 
 // note: this is used by neptune-cli block-info command.
@@ -48,13 +55,16 @@ impl std::fmt::Display for BlockInfo {
             + &format!("mining_reward: {}\n", self.mining_reward)
             + &format!("fee: {}\n", self.fee)
             + &format!("is_genesis: {}\n", self.is_genesis)
-            + &format!("is_tip: {}\n", self.is_tip);
+            + &format!("is_tip: {}\n", self.is_tip)
+            + &format!("is_final: {}\n", self.is_final);
 
         write!(f, "{}", buf)
     }
 }
 
 impl BlockInfo {
+    /// Only the genesis block is considered final; callers with access to
+    /// the archival state set `is_final` from it.
     pub fn from_block_and_digests(
         block: &Block,
         genesis_digest: Digest,
@@ -78,6 +88,7 @@ impl BlockInfo {
             mining_reward: crate::Block::get_mining_reward(header.height),
             is_genesis: digest == genesis_digest,
             is_tip: digest == tip_digest,
+            is_final: digest == genesis_digest,
         }
     }
 }
@@ -0,0 +1,116 @@
+//! Known digests of blocks at given heights.
+//!
+//! Blocks received from peers are cross-checked against the checkpoints of
+//! the network and those given with `--checkpoint`. A peer whose chain has
+//! another block at a checkpoint height is sanctioned before the rest of its
+//! chain is downloaded and validated.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+
+use super::block_height::BlockHeight;
+use crate::config_models::network::Network;
+use crate::prelude::twenty_first::math::digest::Digest;
+
+/// The digest of the block at a height of the canonical chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub height: BlockHeight,
+    pub digest: Digest,
+}
+
+impl FromStr for Checkpoint {
+    type Err = anyhow::Error;
+
+    /// Parse `HEIGHT:DIGEST`, with the digest given as hex
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((height, digest)) = s.split_once(':') else {
+            bail!("Checkpoint must be given as HEIGHT:DIGEST");
+        };
+        let height = height.parse::<u64>().context("Invalid checkpoint height")?;
+        let digest = Digest::try_from_hex(digest).context("Invalid checkpoint digest")?;
+
+        Ok(Self {
+            height: height.into(),
+            digest,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checkpoints(BTreeMap<BlockHeight, Digest>);
+
+impl Checkpoints {
+    /// The checkpoints of the network, extended or overridden by `extra`
+    pub fn new(network: Network, extra: &[Checkpoint]) -> Self {
+        let mut checkpoints = BTreeMap::new();
+        for checkpoint in Self::network_checkpoints(network).iter().chain(extra) {
+            checkpoints.insert(checkpoint.height, checkpoint.digest);
+        }
+
+        Self(checkpoints)
+    }
+
+    /// Checkpoints shipped with the client. None yet, as no network has a
+    /// settled history.
+    fn network_checkpoints(_network: Network) -> Vec<Checkpoint> {
+        vec![]
+    }
+
+    /// Whether a block with this height and digest is on another chain than
+    /// the checkpoints
+    pub fn contradicts(&self, height: BlockHeight, digest: Digest) -> bool {
+        self.0
+            .get(&height)
+            .is_some_and(|checkpoint_digest| *checkpoint_digest != digest)
+    }
+
+    /// The height of the first block that contradicts a checkpoint, if any
+    pub fn first_contradiction(
+        &self,
+        blocks: impl IntoIterator<Item = (BlockHeight, Digest)>,
+    ) -> Option<BlockHeight> {
+        blocks
+            .into_iter()
+            .find(|(height, digest)| self.contradicts(*height, *digest))
+            .map(|(height, _)| height)
+    }
+}
+
+#[cfg(test)]
+mod checkpoints_tests {
+    use rand::random;
+
+    use super::*;
+
+    #[test]
+    fn blocks_are_checked_at_checkpoint_heights_only() {
+        let checkpoint = Checkpoint {
+            height: 5u64.into(),
+            digest: random(),
+        };
+        let parsed: Checkpoint = format!("5:{}", checkpoint.digest.to_hex()).parse().unwrap();
+        assert_eq!(checkpoint, parsed);
+        assert!("5".parse::<Checkpoint>().is_err());
+        assert!("five:00".parse::<Checkpoint>().is_err());
+
+        let checkpoints = Checkpoints::new(Network::RegTest, &[checkpoint]);
+        assert!(!checkpoints.contradicts(5u64.into(), checkpoint.digest));
+        assert!(checkpoints.contradicts(5u64.into(), random()));
+        assert!(!checkpoints.contradicts(6u64.into(), random()));
+
+        let chain = (4u64..=6)
+            .map(|height| (height.into(), random()))
+            .collect::<Vec<(BlockHeight, Digest)>>();
+        assert_eq!(
+            Some(5u64.into()),
+            checkpoints.first_contradiction(chain.clone())
+        );
+        assert_eq!(
+            None,
+            Checkpoints::new(Network::RegTest, &[]).first_contradiction(chain)
+        );
+    }
+}
@@ -0,0 +1,414 @@
+//! Compact, fixed-size encoding of block headers, in which headers are
+//! exchanged with peers and light clients.
+//!
+//! Unlike bincode's, the encoding does not depend on serializer settings, and
+//! every header has the same size, so that headers can be indexed in a buffer
+//! without decoding them. All integers are little-endian, and field elements
+//! are encoded by their canonical value:
+//!
+//! | offset | size | field                  | encoding                |
+//! |-------:|-----:|------------------------|-------------------------|
+//! |      0 |    8 | `version`              | field element           |
+//! |      8 |    8 | `height`               | field element           |
+//! |     16 |   40 | `prev_block_digest`    | 5 field elements        |
+//! |     56 |    8 | `timestamp`            | field element, millis   |
+//! |     64 |   24 | `nonce`                | 3 field elements        |
+//! |     88 |    4 | `max_block_size`       | u32                     |
+//! |     92 |   20 | `proof_of_work_line`   | 5 u32 limbs, low first  |
+//! |    112 |   20 | `proof_of_work_family` | 5 u32 limbs, low first  |
+//! |    132 |   20 | `difficulty`           | 5 u32 limbs, low first  |
+//!
+//! for [`COMPACT_HEADER_SIZE`] bytes. A [`ProvenHeader`] is its header
+//! followed by the MAST hash of the block body, 5 field elements, for
+//! [`COMPACT_PROVEN_HEADER_SIZE`] bytes.
+//!
+//! Decoding rejects field elements that are not canonical, i.e. not smaller
+//! than the field modulus, so that every header has exactly one encoding.
+//! The tests freeze the layout with golden vectors.
+
+use std::fmt;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use super::block_header::{BlockHeader, PROOF_OF_WORK_COUNT_U32_SIZE, TARGET_DIFFICULTY_U32_SIZE};
+use super::header_chain_proof::ProvenHeader;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::peer::wire_format::{from_hex, to_hex};
+use crate::prelude::twenty_first::amount::u32s::U32s;
+use crate::prelude::twenty_first::math::b_field_element::BFieldElement;
+use crate::prelude::twenty_first::math::bfield_codec::BFieldCodec;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde::{self, DIGEST_SIZE_IN_BYTES};
+
+pub const COMPACT_HEADER_SIZE: usize = 152;
+pub const COMPACT_PROVEN_HEADER_SIZE: usize = COMPACT_HEADER_SIZE + DIGEST_SIZE_IN_BYTES;
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum CompactHeaderError {
+    #[error("non-canonical field element {value} at offset {offset}")]
+    NonCanonicalElement { offset: usize, value: u64 },
+}
+
+struct Writer<'a> {
+    bytes: &'a mut [u8],
+    offset: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) {
+        self.bytes[self.offset..self.offset + bytes.len()].copy_from_slice(bytes);
+        self.offset += bytes.len();
+    }
+
+    fn put_element(&mut self, element: BFieldElement) {
+        self.put(&element.value().to_le_bytes());
+    }
+
+    fn put_u32s<const N: usize>(&mut self, value: &U32s<N>) {
+        // A `U32s` encodes as one field element per limb, low limb first
+        for limb in value.encode() {
+            self.put(&(limb.value() as u32).to_le_bytes());
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let bytes = self.bytes[self.offset..self.offset + N].try_into().unwrap();
+        self.offset += N;
+        bytes
+    }
+
+    fn element(&mut self) -> Result<BFieldElement, CompactHeaderError> {
+        let offset = self.offset;
+        let value = u64::from_le_bytes(self.take());
+        if value >= BFieldElement::P {
+            return Err(CompactHeaderError::NonCanonicalElement { offset, value });
+        }
+
+        Ok(BFieldElement::new(value))
+    }
+
+    fn elements<const N: usize>(&mut self) -> Result<[BFieldElement; N], CompactHeaderError> {
+        let mut elements = [BFieldElement::new(0); N];
+        for element in elements.iter_mut() {
+            *element = self.element()?;
+        }
+
+        Ok(elements)
+    }
+
+    fn u32s<const N: usize>(&mut self) -> U32s<N> {
+        let mut limbs = [0u32; N];
+        for limb in limbs.iter_mut() {
+            *limb = u32::from_le_bytes(self.take());
+        }
+
+        U32s::new(limbs)
+    }
+}
+
+fn write_header(writer: &mut Writer, header: &BlockHeader) {
+    writer.put_element(header.version);
+    writer.put_element(header.height.into());
+    writer.put(&digest_serde::to_bytes(&header.prev_block_digest));
+    writer.put_element(header.timestamp.0);
+    for element in header.nonce {
+        writer.put_element(element);
+    }
+    writer.put(&header.max_block_size.to_le_bytes());
+    writer.put_u32s(&header.proof_of_work_line);
+    writer.put_u32s(&header.proof_of_work_family);
+    writer.put_u32s(&header.difficulty);
+}
+
+fn read_header(reader: &mut Reader) -> Result<BlockHeader, CompactHeaderError> {
+    Ok(BlockHeader {
+        version: reader.element()?,
+        height: reader.element()?.into(),
+        prev_block_digest: Digest::new(reader.elements()?),
+        timestamp: Timestamp(reader.element()?),
+        nonce: reader.elements()?,
+        max_block_size: u32::from_le_bytes(reader.take()),
+        proof_of_work_line: reader.u32s::<PROOF_OF_WORK_COUNT_U32_SIZE>(),
+        proof_of_work_family: reader.u32s::<PROOF_OF_WORK_COUNT_U32_SIZE>(),
+        difficulty: reader.u32s::<TARGET_DIFFICULTY_U32_SIZE>(),
+    })
+}
+
+pub fn encode_header(header: &BlockHeader) -> [u8; COMPACT_HEADER_SIZE] {
+    let mut bytes = [0u8; COMPACT_HEADER_SIZE];
+    write_header(
+        &mut Writer {
+            bytes: &mut bytes,
+            offset: 0,
+        },
+        header,
+    );
+
+    bytes
+}
+
+pub fn decode_header(bytes: &[u8; COMPACT_HEADER_SIZE]) -> Result<BlockHeader, CompactHeaderError> {
+    read_header(&mut Reader { bytes, offset: 0 })
+}
+
+pub fn encode_proven_header(proven_header: &ProvenHeader) -> [u8; COMPACT_PROVEN_HEADER_SIZE] {
+    let mut bytes = [0u8; COMPACT_PROVEN_HEADER_SIZE];
+    let mut writer = Writer {
+        bytes: &mut bytes,
+        offset: 0,
+    };
+    write_header(&mut writer, &proven_header.header);
+    writer.put(&digest_serde::to_bytes(&proven_header.body_mast_hash));
+
+    bytes
+}
+
+pub fn decode_proven_header(
+    bytes: &[u8; COMPACT_PROVEN_HEADER_SIZE],
+) -> Result<ProvenHeader, CompactHeaderError> {
+    let mut reader = Reader { bytes, offset: 0 };
+    let header = read_header(&mut reader)?;
+    let body_mast_hash = Digest::new(reader.elements()?);
+
+    Ok(ProvenHeader {
+        header,
+        body_mast_hash,
+    })
+}
+
+/// Proven headers serialize to their compact encoding: hex in human-readable
+/// formats such as JSON, and a fixed-size array of bytes otherwise
+impl Serialize for ProvenHeader {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = encode_proven_header(self);
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&to_hex(&bytes))
+        } else {
+            // A tuple rather than a byte slice, so no length prefix is written
+            let mut tuple = serializer.serialize_tuple(COMPACT_PROVEN_HEADER_SIZE)?;
+            for byte in bytes {
+                tuple.serialize_element(&byte)?;
+            }
+            tuple.end()
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ProvenHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(ProvenHeaderVisitor)
+        } else {
+            deserializer.deserialize_tuple(COMPACT_PROVEN_HEADER_SIZE, ProvenHeaderVisitor)
+        }
+    }
+}
+
+struct ProvenHeaderVisitor;
+
+impl<'de> Visitor<'de> for ProvenHeaderVisitor {
+    type Value = ProvenHeader;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "a compact proven header of {COMPACT_PROVEN_HEADER_SIZE} bytes"
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, hex: &str) -> Result<ProvenHeader, E> {
+        let bytes = from_hex(hex).map_err(|err| E::custom(format!("{err:#}")))?;
+        let bytes: [u8; COMPACT_PROVEN_HEADER_SIZE] = bytes
+            .try_into()
+            .map_err(|bytes: Vec<u8>| E::invalid_length(bytes.len(), &self))?;
+        decode_proven_header(&bytes).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ProvenHeader, A::Error> {
+        let mut bytes = [0u8; COMPACT_PROVEN_HEADER_SIZE];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        decode_proven_header(&bytes).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod compact_header_tests {
+    use rand::{thread_rng, Rng};
+
+    use super::*;
+    use crate::config_models::network::Network;
+    use crate::models::blockchain::block::Block;
+
+    const P_MINUS_ONE: u64 = BFieldElement::P - 1;
+
+    fn element(value: u64) -> BFieldElement {
+        BFieldElement::new(value)
+    }
+
+    fn golden_header() -> BlockHeader {
+        BlockHeader {
+            version: element(1),
+            height: 0x0102_0304_0506_0708u64.into(),
+            prev_block_digest: Digest::new([1, 2, 3, 4, 5].map(element)),
+            timestamp: Timestamp::millis(1_700_000_000_000),
+            nonce: [7, 8, 9].map(element),
+            max_block_size: 10_000,
+            proof_of_work_line: U32s::new([0x11, 0x12, 0, 0, 0]),
+            proof_of_work_family: U32s::new([0x21, 0, 0, 0, 0x22]),
+            difficulty: U32s::new([2, 0, 0, 0, 0]),
+        }
+    }
+
+    const GOLDEN_HEADER: &str = "\
+        0100000000000000\
+        0807060504030201\
+        0100000000000000020000000000000003000000000000000400000000000000\
+        0500000000000000\
+        0068e5cf8b010000\
+        070000000000000008000000000000000900000000000000\
+        10270000\
+        1100000012000000000000000000000000000000\
+        2100000000000000000000000000000022000000\
+        0200000000000000000000000000000000000000";
+
+    /// Every field at its maximum
+    fn golden_proven_header() -> ProvenHeader {
+        let max_element = element(P_MINUS_ONE);
+        ProvenHeader {
+            header: BlockHeader {
+                version: max_element,
+                height: max_element.into(),
+                prev_block_digest: Digest::new([max_element; 5]),
+                timestamp: Timestamp(max_element),
+                nonce: [max_element; 3],
+                max_block_size: u32::MAX,
+                proof_of_work_line: U32s::new([u32::MAX; PROOF_OF_WORK_COUNT_U32_SIZE]),
+                proof_of_work_family: U32s::new([u32::MAX; PROOF_OF_WORK_COUNT_U32_SIZE]),
+                difficulty: U32s::new([u32::MAX; TARGET_DIFFICULTY_U32_SIZE]),
+            },
+            body_mast_hash: Digest::new([6, 7, 8, 9, 10].map(element)),
+        }
+    }
+
+    const GOLDEN_PROVEN_HEADER: &str = "\
+        00000000ffffffff\
+        00000000ffffffff\
+        00000000ffffffff00000000ffffffff00000000ffffffff00000000ffffffff\
+        00000000ffffffff\
+        00000000ffffffff\
+        00000000ffffffff00000000ffffffff00000000ffffffff\
+        ffffffff\
+        ffffffffffffffffffffffffffffffffffffffff\
+        ffffffffffffffffffffffffffffffffffffffff\
+        ffffffffffffffffffffffffffffffffffffffff\
+        0600000000000000070000000000000008000000000000000900000000000000\
+        0a00000000000000";
+
+    fn random_proven_header() -> ProvenHeader {
+        let mut rng = thread_rng();
+        ProvenHeader {
+            header: BlockHeader {
+                version: rng.gen(),
+                height: rng.gen::<BFieldElement>().into(),
+                prev_block_digest: rng.gen(),
+                timestamp: rng.gen(),
+                nonce: rng.gen(),
+                max_block_size: rng.gen(),
+                proof_of_work_line: rng.gen(),
+                proof_of_work_family: rng.gen(),
+                difficulty: rng.gen(),
+            },
+            body_mast_hash: rng.gen(),
+        }
+    }
+
+    #[test]
+    fn golden_vectors_are_stable() {
+        let header = golden_header();
+        assert_eq!(GOLDEN_HEADER, to_hex(&encode_header(&header)));
+        let bytes: [u8; COMPACT_HEADER_SIZE] = from_hex(GOLDEN_HEADER).unwrap().try_into().unwrap();
+        assert_eq!(Ok(header), decode_header(&bytes));
+
+        let proven_header = golden_proven_header();
+        assert_eq!(
+            GOLDEN_PROVEN_HEADER,
+            to_hex(&encode_proven_header(&proven_header))
+        );
+        let bytes: [u8; COMPACT_PROVEN_HEADER_SIZE] =
+            from_hex(GOLDEN_PROVEN_HEADER).unwrap().try_into().unwrap();
+        assert_eq!(Ok(proven_header), decode_proven_header(&bytes));
+    }
+
+    #[test]
+    fn headers_round_trip() {
+        let genesis = ProvenHeader::from_block(&Block::genesis_block(Network::Main));
+        let proven_headers = [genesis, golden_proven_header()]
+            .into_iter()
+            .chain((0..100).map(|_| random_proven_header()));
+        for proven_header in proven_headers {
+            let bytes = encode_proven_header(&proven_header);
+            assert_eq!(Ok(proven_header.clone()), decode_proven_header(&bytes));
+            assert_eq!(
+                Ok(proven_header.header.clone()),
+                decode_header(&encode_header(&proven_header.header))
+            );
+            assert_eq!(
+                bytes[..COMPACT_HEADER_SIZE],
+                encode_header(&proven_header.header)
+            );
+
+            let bincode = bincode::serialize(&proven_header).unwrap();
+            assert_eq!(bytes.to_vec(), bincode);
+            assert_eq!(
+                proven_header,
+                bincode::deserialize::<ProvenHeader>(&bincode).unwrap()
+            );
+
+            let json = serde_json::to_string(&proven_header).unwrap();
+            assert_eq!(format!("\"{}\"", to_hex(&bytes)), json);
+            assert_eq!(
+                proven_header,
+                serde_json::from_str::<ProvenHeader>(&json).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn non_canonical_elements_are_rejected() {
+        let bytes = encode_proven_header(&golden_proven_header());
+
+        // The offsets of all field elements, per the layout
+        let element_offsets = (0..64)
+            .step_by(8)
+            .chain((64..88).step_by(8))
+            .chain((COMPACT_HEADER_SIZE..COMPACT_PROVEN_HEADER_SIZE).step_by(8));
+        for offset in element_offsets {
+            let mut non_canonical = bytes;
+            non_canonical[offset..offset + 8].copy_from_slice(&BFieldElement::P.to_le_bytes());
+            assert_eq!(
+                Err(CompactHeaderError::NonCanonicalElement {
+                    offset,
+                    value: BFieldElement::P
+                }),
+                decode_proven_header(&non_canonical)
+            );
+            assert!(bincode::deserialize::<ProvenHeader>(&non_canonical).is_err());
+        }
+
+        assert!(bincode::deserialize::<ProvenHeader>(&bytes[1..]).is_err());
+    }
+}
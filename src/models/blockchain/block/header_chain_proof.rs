@@ -0,0 +1,404 @@
+//! Compact proofs of the proof-of-work of a chain, for light clients.
+//!
+//! Every block body holds an MMR of the digests of all preceding blocks. A
+//! [`HeaderChainProof`] authenticates that MMR against the tip, and opens it
+//! at heights of the client's choosing. Each opened block comes with its
+//! parent, so that its proof-of-work can be checked. As the client picks the
+//! heights at random, a chain that lacks the claimed work in a fraction of
+//! its blocks is caught with a probability that grows with the number of
+//! samples, without downloading every header.
+
+use std::future::Future;
+
+use itertools::Itertools;
+use rand::seq::index::sample;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tasm_lib::twenty_first::util_types::mmr::mmr_accumulator::MmrAccumulator;
+use tasm_lib::twenty_first::util_types::mmr::mmr_membership_proof::MmrMembershipProof;
+use tasm_lib::twenty_first::util_types::mmr::mmr_trait::Mmr;
+use thiserror::Error;
+
+use crate::models::blockchain::shared::Hash;
+use crate::models::consensus::mast_hash::{HasDiscriminant, MastHash};
+use crate::prelude::twenty_first;
+use twenty_first::math::bfield_codec::BFieldCodec;
+use twenty_first::math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use super::block_body::BlockBodyField;
+use super::block_header::BlockHeader;
+use super::block_height::BlockHeight;
+use super::header_validation::{check_proof_of_work, next_difficulty, ConsensusParams};
+use super::Block;
+
+/// Max number of blocks a peer may ask to have opened in one proof
+pub const MAX_HEADER_CHAIN_PROOF_SAMPLES: usize = 200;
+
+/// Recompute a Merkle root from a leaf and its authentication path, bottom up
+fn merkle_root_from_path(leaf: Digest, leaf_index: usize, path: &[Digest]) -> Digest {
+    let mut node_index = (1 << path.len()) + leaf_index;
+    let mut node = leaf;
+    for &sibling in path {
+        node = if node_index & 1 == 1 {
+            Hash::hash_pair(sibling, node)
+        } else {
+            Hash::hash_pair(node, sibling)
+        };
+        node_index >>= 1;
+    }
+
+    node
+}
+
+/// A block header with the MAST hash of its body, from which the block's
+/// digest can be recomputed. Serializes to its
+/// [compact encoding](super::compact_header).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProvenHeader {
+    pub header: BlockHeader,
+    pub body_mast_hash: Digest,
+}
+
+impl ProvenHeader {
+    pub fn from_block(block: &Block) -> Self {
+        Self {
+            header: block.kernel.header.clone(),
+            body_mast_hash: block.kernel.body.mast_hash(),
+        }
+    }
+
+    /// The digest of the block, as [`Block::hash`] computes it
+    pub fn digest(&self) -> Digest {
+        let header_leaf = Hash::hash_varlen(&self.header.mast_hash().encode());
+        let body_leaf = Hash::hash_varlen(&self.body_mast_hash.encode());
+        merkle_root_from_path(header_leaf, 0, &[body_leaf])
+    }
+
+    /// Check that this block is the child of `parent`, that its difficulty
+    /// follows the difficulty control, that its accumulated proof-of-work
+    /// adds the parent's difficulty to the parent's, and that it has the
+    /// proof-of-work that the parent's difficulty demands
+    fn check_child_of(&self, parent: &ProvenHeader) -> Result<(), HeaderChainProofError> {
+        let height = self.header.height;
+        if self.header.prev_block_digest != parent.digest()
+            || self.header.height != parent.header.height.next()
+        {
+            return Err(HeaderChainProofError::ParentMismatch(height));
+        }
+        if self.header.timestamp < parent.header.timestamp {
+            return Err(HeaderChainProofError::TimestampBeforeParent(height));
+        }
+        let expected_difficulty = next_difficulty(
+            &parent.header,
+            self.header.timestamp,
+            &ConsensusParams::default(),
+        );
+        if self.header.difficulty != expected_difficulty {
+            return Err(HeaderChainProofError::DifficultyMismatch(height));
+        }
+        if self.header.proof_of_work_family
+            != parent.header.proof_of_work_family + parent.header.difficulty
+        {
+            return Err(HeaderChainProofError::ProofOfWorkNotAccumulated(height));
+        }
+        if check_proof_of_work(&parent.header, self.digest()).is_err() {
+            return Err(HeaderChainProofError::InsufficientProofOfWork(height));
+        }
+
+        Ok(())
+    }
+}
+
+/// A block opened from the block MMR of the tip
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SampledBlock {
+    pub parent: ProvenHeader,
+    pub block: ProvenHeader,
+
+    /// Membership of the block in the block MMR of the tip, at its height
+    pub membership_proof: MmrMembershipProof<Hash>,
+}
+
+/// A proof that the tip commits to a chain in which the sampled blocks carry
+/// the required proof-of-work
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderChainProof {
+    pub tip: ProvenHeader,
+
+    /// Absent iff the tip is the genesis block
+    pub tip_parent: Option<ProvenHeader>,
+
+    /// The MMR of the digests of all blocks before the tip, from the tip's
+    /// body
+    pub block_mmr_accumulator: MmrAccumulator<Hash>,
+
+    /// Authenticates `block_mmr_accumulator` against the MAST hash of the
+    /// tip's body
+    pub block_mmr_accumulator_path: Vec<Digest>,
+
+    /// Ordered by height
+    pub samples: Vec<SampledBlock>,
+}
+
+/// A [`HeaderChainProof`] of which the headers of the tip's parent and of the
+/// sampled blocks are yet to be read. Blocks never change once stored, so
+/// the headers can be read one at a time, without holding on to the state
+/// that the outline was taken from.
+#[derive(Clone, Debug)]
+pub struct HeaderChainProofOutline {
+    pub tip: ProvenHeader,
+    pub tip_parent_digest: Option<Digest>,
+    pub block_mmr_accumulator: MmrAccumulator<Hash>,
+    pub block_mmr_accumulator_path: Vec<Digest>,
+
+    /// Digests of each sampled block's parent and of the block, with the
+    /// block's membership proof. Ordered by height.
+    pub samples: Vec<(Digest, Digest, MmrMembershipProof<Hash>)>,
+}
+
+impl HeaderChainProofOutline {
+    /// Complete the proof with the headers that `proven_header` reads
+    pub async fn complete<F, Fut>(self, proven_header: F) -> anyhow::Result<HeaderChainProof>
+    where
+        F: Fn(Digest) -> Fut,
+        Fut: Future<Output = anyhow::Result<ProvenHeader>>,
+    {
+        let tip_parent = match self.tip_parent_digest {
+            Some(digest) => Some(proven_header(digest).await?),
+            None => None,
+        };
+
+        let mut samples = Vec::with_capacity(self.samples.len());
+        for (parent_digest, block_digest, membership_proof) in self.samples {
+            samples.push(SampledBlock {
+                parent: proven_header(parent_digest).await?,
+                block: proven_header(block_digest).await?,
+                membership_proof,
+            });
+        }
+
+        Ok(HeaderChainProof {
+            tip: self.tip,
+            tip_parent,
+            block_mmr_accumulator: self.block_mmr_accumulator,
+            block_mmr_accumulator_path: self.block_mmr_accumulator_path,
+            samples,
+        })
+    }
+}
+
+/// The reason a [`HeaderChainProof`] was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum HeaderChainProofError {
+    #[error("the block MMR does not match the tip")]
+    InvalidBlockMmr,
+
+    #[error("block at height {0} is not the child of the parent given with it")]
+    ParentMismatch(BlockHeight),
+
+    #[error("block at height {0} is timestamped before its parent")]
+    TimestampBeforeParent(BlockHeight),
+
+    #[error("block at height {0} does not have the difficulty that its parent sets")]
+    DifficultyMismatch(BlockHeight),
+
+    #[error("block at height {0} does not have enough proof-of-work")]
+    InsufficientProofOfWork(BlockHeight),
+
+    #[error("block at height {0} does not add its parent's difficulty to the proof-of-work of its parent")]
+    ProofOfWorkNotAccumulated(BlockHeight),
+
+    #[error("block at height {0} is not in the block MMR of the tip")]
+    NotInBlockMmr(BlockHeight),
+
+    #[error("proof-of-work of block at height {0} exceeds that of the tip")]
+    ExceedsTip(BlockHeight),
+
+    #[error("block at height {0} was requested but not sampled")]
+    MissingSample(BlockHeight),
+}
+
+impl HeaderChainProof {
+    /// Check the proof, and that it opens every requested height below the
+    /// tip. The tip's proof-of-work can then be trusted to the degree that
+    /// the number of samples allows.
+    pub fn verify(&self, requested_heights: &[BlockHeight]) -> Result<(), HeaderChainProofError> {
+        let tip_height = self.tip.header.height;
+
+        let block_mmr_leaf = Hash::hash_varlen(&self.block_mmr_accumulator.encode());
+        let block_mmr_index = BlockBodyField::BlockMmrAccumulator.discriminant();
+        if merkle_root_from_path(
+            block_mmr_leaf,
+            block_mmr_index,
+            &self.block_mmr_accumulator_path,
+        ) != self.tip.body_mast_hash
+            || self.block_mmr_accumulator.count_leaves() != u64::from(tip_height)
+        {
+            return Err(HeaderChainProofError::InvalidBlockMmr);
+        }
+
+        match &self.tip_parent {
+            Some(tip_parent) => self.tip.check_child_of(tip_parent)?,
+            None if tip_height.is_genesis() => (),
+            None => return Err(HeaderChainProofError::ParentMismatch(tip_height)),
+        }
+
+        let peaks = self.block_mmr_accumulator.get_peaks();
+        let leaf_count = self.block_mmr_accumulator.count_leaves();
+        for sample in self.samples.iter() {
+            let height = sample.block.header.height;
+            sample.block.check_child_of(&sample.parent)?;
+            if sample.membership_proof.leaf_index != u64::from(height)
+                || !sample
+                    .membership_proof
+                    .verify(&peaks, sample.block.digest(), leaf_count)
+            {
+                return Err(HeaderChainProofError::NotInBlockMmr(height));
+            }
+            if sample.block.header.proof_of_work_family > self.tip.header.proof_of_work_family {
+                return Err(HeaderChainProofError::ExceedsTip(height));
+            }
+        }
+
+        let sampled_heights = self
+            .samples
+            .iter()
+            .map(|sample| sample.block.header.height)
+            .collect_vec();
+        if let Some(&missing) = requested_heights.iter().find(|&&height| {
+            !height.is_genesis() && height < tip_height && !sampled_heights.contains(&height)
+        }) {
+            return Err(HeaderChainProofError::MissingSample(missing));
+        }
+
+        Ok(())
+    }
+}
+
+/// Pick up to `count` distinct heights to sample from a chain with the
+/// given tip height, uniformly at random. The genesis block has no
+/// proof-of-work, so it is never picked.
+pub fn sample_heights<R: Rng>(
+    tip_height: BlockHeight,
+    count: usize,
+    rng: &mut R,
+) -> Vec<BlockHeight> {
+    let candidates = u64::from(tip_height).saturating_sub(1) as usize;
+    sample(rng, candidates, count.min(candidates))
+        .into_iter()
+        .map(|index| BlockHeight::from(index as u64 + 1))
+        .sorted()
+        .collect()
+}
+
+#[cfg(test)]
+mod header_chain_proof_tests {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{make_mock_block_with_valid_pow, mock_genesis_archival_state};
+
+    #[test]
+    fn proven_header_digest_matches_block_digest() {
+        let genesis = Block::genesis_block(Network::RegTest);
+        assert_eq!(genesis.hash(), ProvenHeader::from_block(&genesis).digest());
+    }
+
+    #[tokio::test]
+    async fn archival_node_proofs_verify_and_tampering_is_caught() {
+        let network = Network::RegTest;
+        let (mut archival_state, _peer_db_lock, _data_dir) =
+            mock_genesis_archival_state(network).await;
+        let address = WalletSecret::devnet_wallet()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let mut rng = thread_rng();
+        let mut tip = Block::genesis_block(network);
+        for _ in 0..6 {
+            let (block, _, _) = make_mock_block_with_valid_pow(&tip, None, address, rng.gen());
+            archival_state.write_block_as_tip(&block).await.unwrap();
+            tip = block;
+        }
+
+        let heights = sample_heights(tip.kernel.header.height, 3, &mut rng);
+        assert_eq!(3, heights.len());
+        let proof = archival_state.header_chain_proof(&heights).await.unwrap();
+        assert_eq!(tip.hash(), proof.tip.digest());
+        assert_eq!(3, proof.samples.len());
+        proof.verify(&heights).unwrap();
+
+        let mut missing_sample = proof.clone();
+        missing_sample.samples.pop();
+        assert_eq!(
+            Err(HeaderChainProofError::MissingSample(heights[2])),
+            missing_sample.verify(&heights)
+        );
+
+        let mut inflated_work = proof.clone();
+        inflated_work.samples[0].block.header.proof_of_work_family =
+            inflated_work.tip.header.proof_of_work_family;
+        assert_eq!(
+            Err(HeaderChainProofError::ProofOfWorkNotAccumulated(heights[0])),
+            inflated_work.verify(&heights)
+        );
+
+        let mut inflated_difficulty = proof.clone();
+        inflated_difficulty.samples[0].block.header.difficulty =
+            inflated_difficulty.samples[0].block.header.difficulty + 1u32.into();
+        assert_eq!(
+            Err(HeaderChainProofError::DifficultyMismatch(heights[0])),
+            inflated_difficulty.verify(&heights)
+        );
+
+        let mut foreign_mmr = proof.clone();
+        foreign_mmr.block_mmr_accumulator = MmrAccumulator::new(vec![tip.hash()]);
+        assert_eq!(
+            Err(HeaderChainProofError::InvalidBlockMmr),
+            foreign_mmr.verify(&heights)
+        );
+    }
+
+    #[tokio::test]
+    async fn proofs_follow_the_tip_through_reorganizations() {
+        let network = Network::RegTest;
+        let (mut archival_state, _peer_db_lock, _data_dir) =
+            mock_genesis_archival_state(network).await;
+        let address = WalletSecret::devnet_wallet()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let mut rng = thread_rng();
+        let mut chain = vec![Block::genesis_block(network)];
+        for _ in 0..4 {
+            let (block, _, _) =
+                make_mock_block_with_valid_pow(chain.last().unwrap(), None, address, rng.gen());
+            archival_state.write_block_as_tip(&block).await.unwrap();
+            chain.push(block);
+        }
+
+        let heights = sample_heights(chain.last().unwrap().kernel.header.height, 2, &mut rng);
+        archival_state
+            .header_chain_proof(&heights)
+            .await
+            .unwrap()
+            .verify(&heights)
+            .unwrap();
+
+        // A fork from height 2 that overtakes the chain that was proven
+        let mut fork = chain[..=2].to_vec();
+        for _ in 0..3 {
+            let (block, _, _) =
+                make_mock_block_with_valid_pow(fork.last().unwrap(), None, address, rng.gen());
+            archival_state.write_block_as_tip(&block).await.unwrap();
+            fork.push(block);
+        }
+
+        let tip = fork.last().unwrap();
+        let heights = sample_heights(tip.kernel.header.height, 4, &mut rng);
+        let proof = archival_state.header_chain_proof(&heights).await.unwrap();
+        assert_eq!(tip.hash(), proof.tip.digest());
+        proof.verify(&heights).unwrap();
+    }
+}
@@ -0,0 +1,265 @@
+//! Validation of a block header against the header of its parent.
+//!
+//! These are the checks that need nothing but the two headers and the MAST
+//! hashes of their bodies: height, parent digest, timestamp, difficulty and
+//! proof-of-work. They are shared by full block validation, the miner, and
+//! light clients that follow headers only.
+
+use num_traits::abs;
+use thiserror::Error;
+
+use super::block_header::{
+    BlockHeader, MINIMUM_DIFFICULTY, TARGET_BLOCK_INTERVAL, TARGET_DIFFICULTY_U32_SIZE,
+};
+use super::block_height::BlockHeight;
+use super::header_chain_proof::ProvenHeader;
+use super::{Block, FUTUREDATING_LIMIT_IN_HOURS};
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::amount::u32s::U32s;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_ext::DigestExt;
+
+/// The consensus parameters that headers are validated against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConsensusParams {
+    /// Block interval in milliseconds that the difficulty is adjusted towards
+    pub target_block_interval: u64,
+
+    pub minimum_difficulty: u32,
+
+    /// Headers timestamped this far or further ahead of the local clock are
+    /// invalid
+    pub futuredating_limit: Timestamp,
+}
+
+impl Default for ConsensusParams {
+    fn default() -> Self {
+        Self {
+            target_block_interval: TARGET_BLOCK_INTERVAL,
+            minimum_difficulty: MINIMUM_DIFFICULTY,
+            futuredating_limit: Timestamp::hours(FUTUREDATING_LIMIT_IN_HOURS),
+        }
+    }
+}
+
+/// Why a header is invalid as the child of a parent header
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum HeaderError {
+    #[error("height {height} is not one above the parent's height {parent_height}")]
+    HeightMismatch {
+        height: BlockHeight,
+        parent_height: BlockHeight,
+    },
+
+    #[error("header does not point to the parent")]
+    ParentMismatch,
+
+    #[error("parent digest is zero, which only the genesis block may have")]
+    ZeroParentDigest,
+
+    #[error("timestamp {timestamp} is before the parent's timestamp {parent_timestamp}")]
+    TimestampBeforeParent {
+        timestamp: Timestamp,
+        parent_timestamp: Timestamp,
+    },
+
+    #[error("timestamp {0} is too far in the future")]
+    TooFarInFuture(Timestamp),
+
+    #[error("difficulty {actual} does not match the expected difficulty {expected}")]
+    DifficultyMismatch {
+        expected: U32s<TARGET_DIFFICULTY_U32_SIZE>,
+        actual: U32s<TARGET_DIFFICULTY_U32_SIZE>,
+    },
+
+    #[error("digest {digest} is above the proof-of-work threshold {threshold}")]
+    InsufficientProofOfWork { digest: Digest, threshold: Digest },
+}
+
+/// Validate `header` as the child of `parent_header` at local time `now`,
+/// proof-of-work included. Checks nothing of the block body beyond its MAST
+/// hash.
+pub fn validate_header(
+    parent_header: &ProvenHeader,
+    header: &ProvenHeader,
+    consensus_params: &ConsensusParams,
+    now: Timestamp,
+) -> Result<(), HeaderError> {
+    check_header_against_parent(
+        &parent_header.header,
+        parent_header.digest(),
+        &header.header,
+        consensus_params,
+        now,
+    )?;
+
+    check_proof_of_work(&parent_header.header, header.digest())
+}
+
+/// All checks of [`validate_header`] except the proof-of-work, for callers
+/// that know the digests and check the proof-of-work separately
+pub(crate) fn check_header_against_parent(
+    parent_header: &BlockHeader,
+    parent_digest: Digest,
+    header: &BlockHeader,
+    consensus_params: &ConsensusParams,
+    now: Timestamp,
+) -> Result<(), HeaderError> {
+    if parent_header.height.next() != header.height {
+        return Err(HeaderError::HeightMismatch {
+            height: header.height,
+            parent_height: parent_header.height,
+        });
+    }
+
+    if header.prev_block_digest.is_zero() {
+        return Err(HeaderError::ZeroParentDigest);
+    }
+
+    if parent_digest != header.prev_block_digest {
+        return Err(HeaderError::ParentMismatch);
+    }
+
+    // Equal timestamps are allowed
+    if parent_header.timestamp > header.timestamp {
+        return Err(HeaderError::TimestampBeforeParent {
+            timestamp: header.timestamp,
+            parent_timestamp: parent_header.timestamp,
+        });
+    }
+
+    if header.timestamp >= now + consensus_params.futuredating_limit {
+        return Err(HeaderError::TooFarInFuture(header.timestamp));
+    }
+
+    let expected = next_difficulty(parent_header, header.timestamp, consensus_params);
+    if header.difficulty != expected {
+        return Err(HeaderError::DifficultyMismatch {
+            expected,
+            actual: header.difficulty,
+        });
+    }
+
+    Ok(())
+}
+
+/// Check that `digest`, the digest of a child of `parent_header`, meets the
+/// proof-of-work threshold that the parent's difficulty sets
+pub(crate) fn check_proof_of_work(
+    parent_header: &BlockHeader,
+    digest: Digest,
+) -> Result<(), HeaderError> {
+    let threshold = Block::difficulty_to_digest_threshold(parent_header.difficulty);
+    if digest > threshold {
+        return Err(HeaderError::InsufficientProofOfWork { digest, threshold });
+    }
+
+    Ok(())
+}
+
+/// Control system for block difficulty. This function computes the difficulty
+/// of a child of `parent_header` from the child's timestamp. It is a PID
+/// controller (with i=d=0) regulating the block interval by tuning the
+/// difficulty. We assume that the timestamp is valid.
+pub fn next_difficulty(
+    parent_header: &BlockHeader,
+    timestamp: Timestamp,
+    consensus_params: &ConsensusParams,
+) -> U32s<TARGET_DIFFICULTY_U32_SIZE> {
+    // no adjustment if the parent is the genesis block
+    if parent_header.height.is_genesis() {
+        return parent_header.difficulty;
+    }
+
+    // otherwise, compute PID control signal
+    let t = timestamp - parent_header.timestamp;
+
+    let new_error = t.0.value() as i64 - consensus_params.target_block_interval as i64;
+
+    let adjustment = -new_error / 100;
+    let absolute_adjustment = abs(adjustment) as u64;
+    let adjustment_is_positive = adjustment >= 0;
+    let adj_hi = (absolute_adjustment >> 32) as u32;
+    let adj_lo = absolute_adjustment as u32;
+    let adjustment_u32s =
+        U32s::<TARGET_DIFFICULTY_U32_SIZE>::new([adj_lo, adj_hi, 0u32, 0u32, 0u32]);
+    let minimum_difficulty = consensus_params.minimum_difficulty.into();
+    if adjustment_is_positive {
+        parent_header.difficulty + adjustment_u32s
+    } else if adjustment_u32s > parent_header.difficulty - minimum_difficulty {
+        minimum_difficulty
+    } else {
+        parent_header.difficulty - adjustment_u32s
+    }
+}
+
+#[cfg(test)]
+mod header_validation_tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{make_mock_block, make_mock_block_with_valid_pow};
+
+    use super::*;
+
+    #[test]
+    fn headers_are_checked_against_their_parent() {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let genesis = Block::genesis_block(network);
+        let (block_1, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, rng.gen());
+        let (block_2, _, _) = make_mock_block_with_valid_pow(&block_1, None, address, rng.gen());
+
+        let params = ConsensusParams::default();
+        let now = block_2.kernel.header.timestamp;
+        let parent = ProvenHeader::from_block(&block_1);
+        let child = ProvenHeader::from_block(&block_2);
+        assert_eq!(Ok(()), validate_header(&parent, &child, &params, now));
+
+        // Not the child of its grandparent
+        let grandparent = ProvenHeader::from_block(&genesis);
+        assert!(matches!(
+            validate_header(&grandparent, &child, &params, now),
+            Err(HeaderError::HeightMismatch { .. })
+        ));
+
+        // A sibling has the right height but another parent
+        let (block_1b, _, _) = make_mock_block(&genesis, None, address, rng.gen());
+        assert_eq!(
+            Err(HeaderError::ParentMismatch),
+            validate_header(&ProvenHeader::from_block(&block_1b), &child, &params, now)
+        );
+
+        // A zero parent digest is rejected even if the parent's digest is zero
+        let mut orphan = child.clone();
+        orphan.header.prev_block_digest = Digest::ZERO;
+        assert_eq!(
+            Err(HeaderError::ZeroParentDigest),
+            check_header_against_parent(&parent.header, Digest::ZERO, &orphan.header, &params, now)
+        );
+
+        // Seen from a clock far behind the header
+        let long_ago = now - params.futuredating_limit;
+        assert_eq!(
+            Err(HeaderError::TooFarInFuture(child.header.timestamp)),
+            validate_header(&parent, &child, &params, long_ago)
+        );
+
+        let mut wrong_difficulty = child.clone();
+        wrong_difficulty.header.difficulty = wrong_difficulty.header.difficulty + 1u32.into();
+        assert!(matches!(
+            check_header_against_parent(
+                &parent.header,
+                parent.digest(),
+                &wrong_difficulty.header,
+                &params,
+                now
+            ),
+            Err(HeaderError::DifficultyMismatch { .. })
+        ));
+    }
+}
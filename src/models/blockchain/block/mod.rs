@@ -3,11 +3,12 @@ use crate::models::consensus::mast_hash::MastHash;
 use crate::models::consensus::timestamp::Timestamp;
 use crate::models::consensus::{ValidityAstType, ValidityTree, WitnessType};
 use crate::prelude::twenty_first;
+use crate::util_types::digest_ext::DigestExt;
 
 use get_size::GetSize;
 use itertools::Itertools;
 use num_bigint::BigUint;
-use num_traits::{abs, Zero};
+use num_traits::Zero;
 
 use serde::{Deserialize, Serialize};
 use std::cmp::max;
@@ -26,26 +27,34 @@ use twenty_first::math::tip5::DIGEST_LENGTH;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
 pub mod block_body;
+pub mod block_detail;
 pub mod block_header;
 pub mod block_height;
 pub mod block_info;
 pub mod block_kernel;
 pub mod block_selector;
+pub mod checkpoints;
+pub mod compact_header;
+pub mod header_chain_proof;
+pub mod header_validation;
 pub mod mutator_set_update;
 pub mod transfer_block;
 pub mod validity;
 
+/// Blocks with a timestamp this far or further ahead of the local clock are invalid
+pub const FUTUREDATING_LIMIT_IN_HOURS: usize = 2;
+
 use self::block_body::BlockBody;
-use self::block_header::{
-    BlockHeader, MINIMUM_DIFFICULTY, TARGET_BLOCK_INTERVAL, TARGET_DIFFICULTY_U32_SIZE,
-};
+use self::block_header::{BlockHeader, MINIMUM_DIFFICULTY, TARGET_DIFFICULTY_U32_SIZE};
 use self::block_height::BlockHeight;
 use self::block_kernel::BlockKernel;
+use self::header_validation::{check_header_against_parent, check_proof_of_work, ConsensusParams};
 use self::mutator_set_update::MutatorSetUpdate;
 use self::transfer_block::{ProofType, TransferBlock};
 use super::transaction::transaction_kernel::TransactionKernel;
 use super::transaction::utxo::Utxo;
 use super::transaction::validity::TransactionValidationLogic;
+use super::transaction::verification_cache::all_in_parallel;
 use super::transaction::Transaction;
 use super::type_scripts::neptune_coins::NeptuneCoins;
 use super::type_scripts::time_lock::TimeLock;
@@ -108,7 +117,7 @@ pub enum BlockType {
 // initialized digest field and the other has not.
 //
 // The field should not be serialized, so it has the `#[serde(skip)]` attribute.
-// Upon deserialization, the field will have Digest::default() which is desired
+// Upon deserialization, the field will be empty, which is desired
 // so that the digest will be recomputed if/when hash() is called.
 //
 // We likewise skip the field for `BFieldCodec`, and `GetSize` because there
@@ -275,7 +284,7 @@ impl Block {
             let utxo_digest = Hash::hash(&utxo);
             // generate randomness for mutator set commitment
             // Sender randomness cannot be random because there is no sender.
-            let bad_randomness = Digest::default();
+            let bad_randomness = Digest::ZERO;
             let receiver_digest = receiving_address.privacy_digest;
 
             // Add pre-mine UTXO to MutatorSet
@@ -298,7 +307,7 @@ impl Block {
         let header: BlockHeader = BlockHeader {
             version: BFieldElement::zero(),
             height: BFieldElement::zero().into(),
-            prev_block_digest: Default::default(),
+            prev_block_digest: Digest::ZERO,
             timestamp: network.launch_date(),
             // to be set to something difficult to predict ahead of time
             nonce: [
@@ -368,6 +377,25 @@ impl Block {
         BlockType::Standard(proof_type)
     }
 
+    /// Whether the block was received without its proofs, see
+    /// [`TransferBlock::without_proofs`]
+    pub fn proofs_omitted(&self) -> bool {
+        self.block_type == BlockType::Standard(ProofType::Omitted)
+    }
+
+    /// Digest of the block together with its proofs, which the block's
+    /// digest does not cover. Identifies the exact block whose proofs
+    /// verified.
+    pub fn proofs_digest(&self) -> Digest {
+        Hash::hash_pair(
+            self.hash(),
+            Hash::hash_pair(
+                Hash::hash(&self.block_type),
+                Hash::hash(&self.kernel.body.transaction.witness),
+            ),
+        )
+    }
+
     /// Merge a transaction into this block's transaction.
     /// The mutator set data must be valid in all inputs.
     ///
@@ -430,10 +458,32 @@ impl Block {
         self.unset_digest();
     }
 
+    /// The earliest local time at which the block's timestamp is not too far in the future
+    pub fn earliest_valid_time(&self) -> Timestamp {
+        self.kernel.header.timestamp - Timestamp::hours(FUTUREDATING_LIMIT_IN_HOURS)
+            + Timestamp::millis(1)
+    }
+
     /// Verify a block. It is assumed that `previous_block` is valid.
     /// Note that this function does **not** check that the PoW digest is below the threshold.
     /// That must be done separately by the caller.
     pub(crate) fn is_valid(&self, previous_block: &Block, now: Timestamp) -> bool {
+        self.is_valid_with(previous_block, now, true)
+    }
+
+    /// Like [`Self::is_valid`], but the proofs are only verified if
+    /// `verify_proofs` is set. The caller decides, as the block's own
+    /// [`proofs_omitted`](Self::proofs_omitted) flag is set by whoever sent it.
+    /// Proofs need not be verified again for a block whose proofs verified
+    /// before, and cannot be for a block without proofs, which only nodes
+    /// that asked for them accept, see
+    /// [`accepts_blocks_without_proofs`](crate::config_models::cli_args::Args::accepts_blocks_without_proofs).
+    pub(crate) fn is_valid_with(
+        &self,
+        previous_block: &Block,
+        now: Timestamp,
+        verify_proofs: bool,
+    ) -> bool {
         // The block value doesn't actually change. Some function calls just require
         // mutable references because that's how the interface was defined for them.
         let block_copy = self.to_owned();
@@ -457,19 +507,15 @@ impl Block {
         //   f) transaction coinbase <= miner reward
         //   g) transaction is valid (internally consistent)
 
-        // 0.a) Block height is previous plus one
-        if previous_block.kernel.header.height.next() != block_copy.kernel.header.height {
-            warn!(
-                "Block height ({}) does not match previous height plus one ({})",
-                block_copy.kernel.header.height,
-                previous_block.kernel.header.height.next()
-            );
-            return false;
-        }
-
-        // 0.b) Block header points to previous block
-        if previous_block.hash() != block_copy.kernel.header.prev_block_digest {
-            warn!("Hash digest does not match previous digest");
+        // 0.a), 0.b), 0.d), 0.e) and 0.f) are checks of the header alone
+        if let Err(err) = check_header_against_parent(
+            &previous_block.kernel.header,
+            previous_block.hash(),
+            &block_copy.kernel.header,
+            &ConsensusParams::default(),
+            now,
+        ) {
+            warn!("Invalid block header: {err}");
             return false;
         }
 
@@ -481,44 +527,15 @@ impl Block {
             return false;
         }
 
-        // 0.d) Block timestamp is greater than (or equal to) that of previous block
-        if previous_block.kernel.header.timestamp > block_copy.kernel.header.timestamp {
-            warn!(
-                "Block's timestamp ({}) should be greater than or equal to that of previous block ({})\nprevious <= current ?? {}",
-                block_copy.kernel.header.timestamp,
-                previous_block.kernel.header.timestamp,
-                previous_block.kernel.header.timestamp <= block_copy.kernel.header.timestamp
-            );
-            return false;
-        }
-
-        // 0.e) Target difficulty, and other control parameters, were updated correctly
-        if block_copy.kernel.header.difficulty
-            != Self::difficulty_control(previous_block, block_copy.kernel.header.timestamp)
-        {
-            warn!("Value for new difficulty is incorrect.");
-            return false;
-        }
-
-        // 0.f) Block timestamp is less than host-time (utc) + 2 hours.
-        let future_limit = now + Timestamp::hours(2);
-        if block_copy.kernel.header.timestamp >= future_limit {
-            warn!("block time is too far in the future");
-            return false;
-        }
-
         // 1.b) Verify validity of removal records: That their MMR MPs match the SWBF, and
         // that at least one of their listed indices is absent.
-        for removal_record in block_copy.kernel.body.transaction.kernel.inputs.iter() {
-            if !previous_block
-                .kernel
-                .body
-                .mutator_set_accumulator
-                .can_remove(removal_record)
-            {
-                warn!("Removal record cannot be removed from mutator set");
-                return false;
-            }
+        let previous_mutator_set = &previous_block.kernel.body.mutator_set_accumulator;
+        if !all_in_parallel(
+            &block_copy.kernel.body.transaction.kernel.inputs,
+            |removal_record| previous_mutator_set.can_remove(removal_record),
+        ) {
+            warn!("Removal record cannot be removed from mutator set");
+            return false;
         }
 
         // 1.c) Verify that the removal records do not contain duplicate `AbsoluteIndexSet`s
@@ -568,6 +585,8 @@ impl Block {
         }
 
         // 1.e) verify that the transaction timestamp is less than or equal to the block's timestamp.
+        // Time-locked inputs are released relative to the transaction timestamp, so this
+        // check is what keeps them locked until the chain has reached their release date.
         if block_copy.kernel.body.transaction.kernel.timestamp > block_copy.kernel.header.timestamp
         {
             warn!(
@@ -590,7 +609,10 @@ impl Block {
         }
 
         // 1.g) Verify transaction, but without relating it to the blockchain tip (that was done above).
-        if !block_copy.kernel.body.transaction.is_valid() {
+        // The placeholder witness of a block without proofs would pass as valid.
+        if verify_proofs
+            && (block_copy.proofs_omitted() || !block_copy.kernel.body.transaction.is_valid())
+        {
             warn!("Invalid transaction found in block");
             return false;
         }
@@ -618,7 +640,7 @@ impl Block {
     /// compare the hash of the current block against the difficulty determined by
     /// the previous.
     pub fn has_proof_of_work(&self, previous_block: &Block) -> bool {
-        self.hash() <= Self::difficulty_to_digest_threshold(previous_block.kernel.header.difficulty)
+        check_proof_of_work(&previous_block.kernel.header, self.hash()).is_ok()
     }
 
     /// Converts `difficulty` to type `Digest` so that the hash of a block can be
@@ -636,37 +658,17 @@ impl Block {
     }
 
     /// Control system for block difficulty. This function computes the new block's
-    /// difficulty from its timestamp and the previous block. It is a PID controller
-    /// (with i=d=0) regulating the block interval by tuning the difficulty.
-    /// We assume that the block timestamp is valid.
+    /// difficulty from its timestamp and the previous block. See
+    /// [`header_validation::next_difficulty`].
     pub fn difficulty_control(
         old_block: &Block,
         new_timestamp: Timestamp,
     ) -> U32s<TARGET_DIFFICULTY_U32_SIZE> {
-        // no adjustment if the previous block is the genesis block
-        if old_block.kernel.header.height.is_genesis() {
-            return old_block.kernel.header.difficulty;
-        }
-
-        // otherwise, compute PID control signal
-        let t = new_timestamp - old_block.kernel.header.timestamp;
-
-        let new_error = t.0.value() as i64 - TARGET_BLOCK_INTERVAL as i64;
-
-        let adjustment = -new_error / 100;
-        let absolute_adjustment = abs(adjustment) as u64;
-        let adjustment_is_positive = adjustment >= 0;
-        let adj_hi = (absolute_adjustment >> 32) as u32;
-        let adj_lo = absolute_adjustment as u32;
-        let adjustment_u32s =
-            U32s::<TARGET_DIFFICULTY_U32_SIZE>::new([adj_lo, adj_hi, 0u32, 0u32, 0u32]);
-        if adjustment_is_positive {
-            old_block.kernel.header.difficulty + adjustment_u32s
-        } else if adjustment_u32s > old_block.kernel.header.difficulty - MINIMUM_DIFFICULTY.into() {
-            MINIMUM_DIFFICULTY.into()
-        } else {
-            old_block.kernel.header.difficulty - adjustment_u32s
-        }
+        header_validation::next_difficulty(
+            &old_block.kernel.header,
+            new_timestamp,
+            &ConsensusParams::default(),
+        )
     }
 }
 
@@ -866,6 +868,24 @@ mod block_tests {
         assert!(!block_1.is_valid(&genesis_block, now));
     }
 
+    #[test]
+    fn block_without_proofs_is_only_valid_when_proofs_are_not_verified() {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let genesis_block = Block::genesis_block(network);
+        let now = genesis_block.kernel.header.timestamp + Timestamp::hours(1);
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) =
+            make_mock_block_with_valid_pow(&genesis_block, None, address, rng.gen());
+        let stripped_block = Block::from(TransferBlock::from(block_1.clone()).without_proofs());
+
+        assert!(block_1.is_valid(&genesis_block, now));
+        assert!(!stripped_block.is_valid(&genesis_block, now));
+        assert!(stripped_block.is_valid_with(&genesis_block, now, false));
+    }
+
     #[tokio::test]
     async fn can_prove_block_ancestry() {
         let mut rng = thread_rng();
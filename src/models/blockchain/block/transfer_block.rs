@@ -4,11 +4,16 @@ use tasm_lib::triton_vm::proof::Proof;
 
 use super::{block_body::BlockBody, block_header::BlockHeader};
 use crate::models::blockchain::block::BFieldCodec;
+use crate::models::blockchain::transaction::validity::TransactionValidationLogic;
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Eq, BFieldCodec, GetSize)]
 pub enum ProofType {
     Unimplemented, // temporary, can should be removed once all Proof's are implemented.
     Proof(Proof),
+
+    /// Left out by the sender, along with the witness of the block's
+    /// transaction, because the receiver asked for blocks without proofs
+    Omitted,
 }
 
 /// Data structure for communicating blocks with peers. The hash digest is not
@@ -19,3 +24,16 @@ pub struct TransferBlock {
     pub body: BlockBody,
     pub proof_type: ProofType,
 }
+
+impl TransferBlock {
+    /// Leave out the proofs, which do not contribute to the block's digest
+    pub fn without_proofs(mut self) -> Self {
+        self.proof_type = ProofType::Omitted;
+        self.body.transaction.witness = TransactionValidationLogic::default();
+        self
+    }
+
+    pub fn proofs_omitted(&self) -> bool {
+        self.proof_type == ProofType::Omitted
+    }
+}
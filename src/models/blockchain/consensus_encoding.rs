@@ -0,0 +1,72 @@
+//! Canonical encoding of blocks and transactions, as hex, for external
+//! verification tools that do not link this crate.
+//!
+//! The bytes are the bincode encoding with which blocks are stored on disk
+//! and transactions are relayed between peers. Decoding rejects trailing
+//! bytes, so that every object has exactly one encoding that round-trips.
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::peer::wire_format::{from_hex, to_hex};
+
+#[derive(Clone, Debug, Error, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DecodeError {
+    #[error("invalid hex: {0}")]
+    InvalidHex(String),
+
+    #[error("invalid encoding: {0}")]
+    InvalidEncoding(String),
+}
+
+fn bincode_options() -> impl Options {
+    // The options of `bincode::serialize`, but strict about trailing bytes
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+}
+
+/// Encode a block or a transaction as hex
+pub fn encode_hex<T: Serialize>(value: &T) -> String {
+    to_hex(&bincode::serialize(value).expect("blocks and transactions can be encoded"))
+}
+
+/// Decode a block or a transaction from hex, ignoring whitespace
+pub fn decode_hex<T: DeserializeOwned>(hex: &str) -> Result<T, DecodeError> {
+    let bytes = from_hex(hex).map_err(|err| DecodeError::InvalidHex(format!("{err:#}")))?;
+
+    bincode_options()
+        .deserialize(&bytes)
+        .map_err(|err| DecodeError::InvalidEncoding(err.to_string()))
+}
+
+#[cfg(test)]
+mod consensus_encoding_tests {
+    use crate::config_models::network::Network;
+    use crate::models::blockchain::block::Block;
+    use crate::models::blockchain::transaction::Transaction;
+
+    use super::*;
+
+    #[test]
+    fn blocks_and_transactions_round_trip() {
+        let block = Block::genesis_block(Network::RegTest);
+        let hex = encode_hex(&block);
+        assert_eq!(block, decode_hex::<Block>(&hex).unwrap());
+        assert_eq!(
+            block.kernel.body.transaction,
+            decode_hex::<Transaction>(&encode_hex(&block.kernel.body.transaction)).unwrap()
+        );
+
+        assert!(matches!(
+            decode_hex::<Block>(&format!("{hex}00")),
+            Err(DecodeError::InvalidEncoding(_))
+        ));
+        assert!(matches!(
+            decode_hex::<Block>(&hex[1..]),
+            Err(DecodeError::InvalidHex(_))
+        ));
+    }
+}
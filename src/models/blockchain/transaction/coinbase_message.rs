@@ -0,0 +1,112 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::PublicAnnouncement;
+use crate::models::state::wallet::address::generation_address::{bfes_to_bytes, bytes_to_bfes};
+use crate::prelude::twenty_first::math::b_field_element::BFieldElement;
+use crate::prelude::twenty_first::math::bfield_codec::BFieldCodec;
+
+/// Marks the public announcement of a coinbase transaction that carries a
+/// [`CoinbaseMessage`]. Distinct from the flag of generation addresses, so
+/// wallets scanning for UTXOs skip it.
+pub const COINBASE_MESSAGE_FLAG: BFieldElement = BFieldElement::new(80);
+
+/// Max length in bytes of the tag in a coinbase message
+pub const MAX_COINBASE_TAG_LENGTH: usize = 64;
+
+/// A miner-chosen message in the coinbase transaction: a short tag, typically
+/// identifying a mining pool, and an extra nonce.
+///
+/// The extra nonce changes the block body, and thus the block hash, so pools
+/// can give each worker its own extra nonce to split the search space.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoinbaseMessage {
+    pub tag: String,
+    pub extra_nonce: u64,
+}
+
+impl CoinbaseMessage {
+    pub fn new(tag: String, extra_nonce: u64) -> Result<Self> {
+        if tag.len() > MAX_COINBASE_TAG_LENGTH {
+            bail!(
+                "Coinbase tag is {} bytes long, max is {MAX_COINBASE_TAG_LENGTH}",
+                tag.len()
+            );
+        }
+
+        Ok(Self { tag, extra_nonce })
+    }
+
+    pub fn to_public_announcement(&self) -> PublicAnnouncement {
+        let mut message = vec![COINBASE_MESSAGE_FLAG];
+        message.extend(self.extra_nonce.encode());
+        message.extend(bytes_to_bfes(self.tag.as_bytes()));
+
+        PublicAnnouncement::new(message)
+    }
+
+    /// Read the coinbase message from a public announcement. Returns `None`
+    /// if the announcement does not hold one.
+    pub fn from_public_announcement(announcement: &PublicAnnouncement) -> Option<Self> {
+        let (flag, rest) = announcement.message.split_first()?;
+        if *flag != COINBASE_MESSAGE_FLAG || rest.len() < 3 {
+            return None;
+        }
+
+        let (extra_nonce, encoded_tag) = rest.split_at(2);
+        let extra_nonce = *u64::decode(extra_nonce).ok()?;
+
+        // `bfes_to_bytes` panics on a length prefix exceeding the data, so
+        // check the prefix is consistent before decoding
+        let tag_length = encoded_tag[0].value() as usize;
+        if tag_length > MAX_COINBASE_TAG_LENGTH || tag_length > decoded_length(&encoded_tag[1..]) {
+            return None;
+        }
+        let tag = String::from_utf8(bfes_to_bytes(encoded_tag).ok()?).ok()?;
+
+        Self::new(tag, extra_nonce).ok()
+    }
+}
+
+/// Number of bytes `bfes_to_bytes` decodes from the elements following the
+/// length prefix. An element whose top half is all ones is followed by one
+/// holding the low half only.
+pub(super) fn decoded_length(elements: &[BFieldElement]) -> usize {
+    let mut length = 0;
+    let mut skip_top = false;
+    for element in elements {
+        if skip_top || element.value() >> 32 == 0xffff_ffff {
+            length += 4;
+            skip_top = !skip_top;
+        } else {
+            length += 8;
+        }
+    }
+    length
+}
+
+#[cfg(test)]
+mod coinbase_message_tests {
+    use super::*;
+
+    #[test]
+    fn coinbase_message_round_trips_through_public_announcement() {
+        let message = CoinbaseMessage::new("/pool.example/worker 7/".to_owned(), u64::MAX).unwrap();
+        let announcement = message.to_public_announcement();
+        assert_eq!(
+            Some(message),
+            CoinbaseMessage::from_public_announcement(&announcement)
+        );
+
+        assert!(
+            CoinbaseMessage::from_public_announcement(&PublicAnnouncement::default()).is_none()
+        );
+
+        // a length prefix exceeding the data is rejected rather than panicking
+        let mut truncated = announcement.clone();
+        truncated.message.pop();
+        truncated.message.pop();
+        assert!(CoinbaseMessage::from_public_announcement(&truncated).is_none());
+        assert!(CoinbaseMessage::new("x".repeat(MAX_COINBASE_TAG_LENGTH + 1), 0).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+//! Data carriers: arbitrary application data embedded in the public
+//! announcements of a transaction, the analogue of `OP_RETURN` outputs.
+//!
+//! A data carrier holds no coins and creates no UTXO, so it does not grow the
+//! mutator set. Its size is bounded by relay policy rather than consensus, see
+//! [`MempoolPolicy::max_data_carrier_size`](crate::models::state::mempool_policy::MempoolPolicy).
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::coinbase_message::decoded_length;
+use super::{PublicAnnouncement, Transaction};
+use crate::models::state::wallet::address::generation_address::{bfes_to_bytes, bytes_to_bfes};
+use crate::prelude::twenty_first::math::b_field_element::BFieldElement;
+
+/// Marks a public announcement that carries a [`DataCarrier`]. Distinct from
+/// the flags of generation addresses and coinbase messages.
+pub const DATA_CARRIER_FLAG: BFieldElement = BFieldElement::new(81);
+
+/// Max length in bytes of the data in a data carrier
+pub const MAX_DATA_CARRIER_SIZE: usize = 80;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataCarrier {
+    pub data: Vec<u8>,
+}
+
+impl DataCarrier {
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        if data.len() > MAX_DATA_CARRIER_SIZE {
+            bail!(
+                "Data carrier holds {} bytes, max is {MAX_DATA_CARRIER_SIZE}",
+                data.len()
+            );
+        }
+
+        Ok(Self { data })
+    }
+
+    pub fn to_public_announcement(&self) -> PublicAnnouncement {
+        let mut message = vec![DATA_CARRIER_FLAG];
+        message.extend(bytes_to_bfes(&self.data));
+
+        PublicAnnouncement::new(message)
+    }
+
+    /// Whether the announcement is flagged as a data carrier, whether or not
+    /// it decodes as one
+    pub fn is_marked(announcement: &PublicAnnouncement) -> bool {
+        matches!(announcement.message.first(), Some(&DATA_CARRIER_FLAG))
+    }
+
+    /// Read the data carrier from a public announcement. Returns `None` if the
+    /// announcement does not hold one.
+    pub fn from_public_announcement(announcement: &PublicAnnouncement) -> Option<Self> {
+        let (flag, encoded_data) = announcement.message.split_first()?;
+        if *flag != DATA_CARRIER_FLAG || encoded_data.is_empty() {
+            return None;
+        }
+
+        // `bfes_to_bytes` panics on a length prefix exceeding the data, so
+        // check the prefix is consistent before decoding
+        let data_length = encoded_data[0].value() as usize;
+        if data_length > MAX_DATA_CARRIER_SIZE || data_length > decoded_length(&encoded_data[1..]) {
+            return None;
+        }
+
+        Self::new(bfes_to_bytes(encoded_data).ok()?).ok()
+    }
+
+    /// The data carriers of a transaction, in the order of its announcements.
+    /// Flagged announcements that do not decode are skipped.
+    pub fn all_in(transaction: &Transaction) -> Vec<Self> {
+        transaction
+            .kernel
+            .public_announcements
+            .iter()
+            .filter_map(Self::from_public_announcement)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod data_carrier_tests {
+    use super::*;
+
+    #[test]
+    fn data_carrier_round_trips_through_public_announcement() {
+        for length in [0, 1, 7, 8, 9, MAX_DATA_CARRIER_SIZE] {
+            let data_carrier = DataCarrier::new(vec![0xff; length]).unwrap();
+            let announcement = data_carrier.to_public_announcement();
+            assert!(DataCarrier::is_marked(&announcement));
+            assert_eq!(
+                Some(data_carrier),
+                DataCarrier::from_public_announcement(&announcement)
+            );
+        }
+
+        assert!(DataCarrier::new(vec![0; MAX_DATA_CARRIER_SIZE + 1]).is_err());
+        assert!(DataCarrier::from_public_announcement(&PublicAnnouncement::default()).is_none());
+
+        // a length prefix exceeding the data is rejected rather than panicking
+        let mut truncated = DataCarrier::new(b"hello, world".to_vec())
+            .unwrap()
+            .to_public_announcement();
+        truncated.message.pop();
+        assert!(DataCarrier::is_marked(&truncated));
+        assert!(DataCarrier::from_public_announcement(&truncated).is_none());
+    }
+}
@@ -3,10 +3,13 @@ use crate::models::consensus::mast_hash::MastHash;
 use crate::models::consensus::{ValidityTree, WitnessType};
 use crate::prelude::{triton_vm, twenty_first};
 
+pub mod coinbase_message;
+pub mod data_carrier;
 pub mod primitive_witness;
 pub mod transaction_kernel;
 pub mod utxo;
 pub mod validity;
+pub mod verification_cache;
 
 use anyhow::{bail, Result};
 use arbitrary::Arbitrary;
@@ -28,6 +31,7 @@ use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 use self::primitive_witness::PrimitiveWitness;
 use self::transaction_kernel::TransactionKernel;
 use self::validity::TransactionValidationLogic;
+use self::verification_cache::verification_cache;
 use super::block::Block;
 use super::shared::Hash;
 use super::type_scripts::TypeScript;
@@ -238,9 +242,77 @@ impl Transaction {
     /// Determine whether the transaction is valid (forget about confirmable).
     /// This method tests the transaction's internal consistency in isolation,
     /// without the context of the canonical chain.
+    ///
+    /// A kernel verified before with the same witness is not verified again.
+    /// Inputs unlocked before, possibly as part of another transaction, do not
+    /// run their lock scripts again, so a block transaction merged from
+    /// transactions in the mempool only runs the kernel-wide checks.
     pub fn is_valid(&self) -> bool {
         let kernel_hash = self.kernel.mast_hash();
-        self.witness.vast.verify(kernel_hash)
+        let transaction_key = Hash::hash_pair(kernel_hash, Hash::hash(&self.witness));
+        if verification_cache()
+            .lock()
+            .unwrap()
+            .contains(transaction_key)
+        {
+            return true;
+        }
+
+        let input_keys = self.input_keys();
+        let is_valid = match (&self.witness.maybe_primitive_witness, &input_keys) {
+            (Some(primitive_witness), Some(input_keys)) => {
+                let verified_inputs = {
+                    let cache = verification_cache().lock().unwrap();
+                    input_keys
+                        .iter()
+                        .map(|&input_key| cache.contains(input_key))
+                        .collect_vec()
+                };
+                TransactionValidationLogic::verify_primitive_witness(
+                    primitive_witness,
+                    &verified_inputs,
+                )
+            }
+            _ => self.witness.vast.verify(kernel_hash),
+        };
+
+        if is_valid {
+            let mut cache = verification_cache().lock().unwrap();
+            cache.insert(transaction_key);
+            for input_key in input_keys.into_iter().flatten() {
+                cache.insert(input_key);
+            }
+        }
+
+        is_valid
+    }
+
+    /// Identify every input by its index set together with the lock script and
+    /// witness that unlock it, independently of the kernel it is part of. Only
+    /// available when the primitive witness matches the kernel.
+    fn input_keys(&self) -> Option<Vec<Digest>> {
+        let primitive_witness = self.witness.maybe_primitive_witness.as_ref()?;
+        if primitive_witness.kernel != self.kernel
+            || primitive_witness.input_lock_scripts.len() != self.kernel.inputs.len()
+            || primitive_witness.lock_script_witnesses.len() != self.kernel.inputs.len()
+        {
+            return None;
+        }
+
+        let input_keys = self
+            .kernel
+            .inputs
+            .iter()
+            .zip(&primitive_witness.input_lock_scripts)
+            .zip(&primitive_witness.lock_script_witnesses)
+            .map(|((removal_record, lock_script), lock_script_witness)| {
+                Hash::hash_pair(
+                    Hash::hash(&removal_record.absolute_indices),
+                    Hash::hash_pair(lock_script.hash(), Hash::hash(lock_script_witness)),
+                )
+            })
+            .collect_vec();
+        Some(input_keys)
     }
 
     fn merge_primitive_witnesses(
@@ -7,7 +7,6 @@ use proptest::{
     strategy::{BoxedStrategy, Strategy},
 };
 use proptest_arbitrary_interop::arb;
-use rand::{thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 use tasm_lib::{
     structure::tasm_object::TasmObject,
@@ -18,6 +17,7 @@ use tasm_lib::{
     Digest,
 };
 
+use crate::util_types::entropy;
 use crate::{
     models::{
         blockchain::type_scripts::TypeScript, consensus::timestamp::Timestamp,
@@ -54,20 +54,20 @@ pub struct SaltedUtxos {
 
 impl SaltedUtxos {
     /// Takes a Vec of UTXOs and returns a `SaltedUtxos` object. The salt comes from
-    /// `thread_rng`.
+    /// the node's entropy source.
     pub fn new(utxos: Vec<Utxo>) -> Self {
         Self {
             utxos,
-            salt: thread_rng().gen(),
+            salt: entropy::random(),
         }
     }
 
     /// Generate a `SaltedUtxos` object that contains no UTXOs. There is a random salt
-    /// though, which comes from `thread_rng`.
+    /// though, which comes from the node's entropy source.
     pub fn empty() -> Self {
         Self {
             utxos: vec![],
-            salt: thread_rng().gen(),
+            salt: entropy::random(),
         }
     }
 
@@ -112,7 +112,7 @@ impl PrimitiveWitness {
             .collect_vec();
         let input_lock_script_witnesses = input_spending_keys
             .iter()
-            .map(|spending_key| spending_key.unlock_key.values().to_vec())
+            .map(|spending_key| spending_key.unlock_key.expose().values().to_vec())
             .collect_vec();
 
         let input_utxos = input_lock_scripts
@@ -388,9 +388,9 @@ pub(crate) fn arbitrary_primitive_witness_with(
 
 #[cfg(test)]
 mod test {
-    use super::PrimitiveWitness;
+    use super::{BFieldElement, PrimitiveWitness};
     use crate::models::blockchain::{
-        transaction::validity::TransactionValidationLogic,
+        transaction::{validity::TransactionValidationLogic, Transaction},
         type_scripts::neptune_coins::NeptuneCoins,
     };
     use crate::models::consensus::mast_hash::MastHash;
@@ -415,6 +415,27 @@ mod test {
         );
     }
 
+    #[proptest(cases = 3)]
+    fn verified_kernel_is_not_vouched_for_with_another_witness(
+        #[strategy(1usize..3)] _num_inputs: usize,
+        #[strategy(PrimitiveWitness::arbitrary_with((#_num_inputs, 1, 0)))]
+        primitive_witness: PrimitiveWitness,
+    ) {
+        let transaction = Transaction {
+            kernel: primitive_witness.kernel.clone(),
+            witness: TransactionValidationLogic::from(primitive_witness.clone()),
+        };
+        prop_assert!(transaction.is_valid());
+
+        let mut forged_witness = primitive_witness;
+        forged_witness.lock_script_witnesses[0].fill(BFieldElement::new(0));
+        let forged_transaction = Transaction {
+            kernel: forged_witness.kernel.clone(),
+            witness: TransactionValidationLogic::from(forged_witness),
+        };
+        prop_assert!(!forged_transaction.is_valid());
+    }
+
     #[proptest]
     fn amounts_balancer_works_with_coinbase(
         #[strategy(arb::<NeptuneCoins>())] total_input_amount: NeptuneCoins,
@@ -40,12 +40,13 @@ impl GetSize for Utxo {
         std::mem::size_of::<Self>()
     }
 
+    /// The lock script hash and the coins' type script hashes are stored
+    /// inline, so only the allocations of the coin list and the coin states
+    /// count.
     fn get_heap_size(&self) -> usize {
-        // self.lock_script.get_heap_size() + self.coins.len() * (std::mem::size_of::<Digest>())
-        let mut total = self.lock_script_hash.get_heap_size();
-        for v in self.coins.iter() {
-            total += std::mem::size_of::<Digest>();
-            total += v.state.len() * std::mem::size_of::<BFieldElement>();
+        let mut total = self.coins.capacity() * std::mem::size_of::<Coin>();
+        for coin in self.coins.iter() {
+            total += coin.state.capacity() * std::mem::size_of::<BFieldElement>();
         }
 
         total
@@ -289,6 +290,35 @@ mod utxo_tests {
         }
     }
 
+    #[test]
+    fn utxo_get_size_counts_coin_states() {
+        let lock_script_hash = LockScript::anyone_can_spend().hash();
+        let empty = Utxo {
+            lock_script_hash,
+            coins: vec![],
+        };
+        assert_eq!(std::mem::size_of::<Utxo>(), empty.get_size());
+
+        let coins = vec![
+            Coin {
+                type_script_hash: lock_script_hash,
+                state: vec![BFieldElement::new(1); 4],
+            },
+            Coin {
+                type_script_hash: lock_script_hash,
+                state: vec![],
+            },
+        ];
+        let utxo = Utxo {
+            lock_script_hash,
+            coins,
+        };
+        assert_eq!(
+            2 * std::mem::size_of::<Coin>() + 4 * std::mem::size_of::<BFieldElement>(),
+            utxo.get_heap_size()
+        );
+    }
+
     #[test]
     fn hash_utxo_test() {
         let output = make_random_utxo();
@@ -14,15 +14,17 @@ pub mod removal_records_integrity;
 pub mod tasm;
 pub mod typescripts_halt;
 use crate::models::blockchain::transaction;
+use crate::util_types::digest_ext::DigestExt;
 use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 
 use get_size::GetSize;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tasm_lib::triton_vm::proof::Claim;
 use tasm_lib::Digest;
 use twenty_first::math::bfield_codec::BFieldCodec;
 
-use self::lockscripts_halt::LockScriptsHalt;
+use self::lockscripts_halt::{LockScriptHalts, LockScriptsHalt};
 use self::removal_records_integrity::RemovalRecordsIntegrity;
 use self::{
     kernel_to_lock_scripts::KernelToLockScripts, kernel_to_type_scripts::KernelToTypeScripts,
@@ -101,7 +103,7 @@ impl TransactionValidationLogic {
             ValidityTree::none(),
             ValidityTree::none(),
             ValidityTree::new(
-                ValidityAstType::Atomic(None, Claim::new(Digest::default()), WhichProgram::Merger),
+                ValidityAstType::Atomic(None, Claim::new(Digest::ZERO), WhichProgram::Merger),
                 WitnessType::Faith,
             ),
         )
@@ -123,6 +125,33 @@ impl TransactionValidationLogic {
         )
     }
 
+    /// Verify a transaction from its primitive witness alone, skipping the lock
+    /// scripts of the inputs flagged as already verified.
+    pub fn verify_primitive_witness(
+        primitive_witness: &PrimitiveWitness,
+        verified_inputs: &[bool],
+    ) -> bool {
+        if primitive_witness.input_lock_scripts.len() != verified_inputs.len()
+            || primitive_witness.lock_script_witnesses.len() != verified_inputs.len()
+        {
+            return false;
+        }
+
+        let unverified_lock_scripts = LockScriptsHalt::from(primitive_witness.clone())
+            .witnesses
+            .into_iter()
+            .zip(verified_inputs)
+            .filter(|(_witness, &verified)| !verified)
+            .map(|(witness, _verified)| LockScriptHalts::from(witness).vast())
+            .collect_vec();
+        ValidityTree::all(vec![
+            ValidityTree::all(unverified_lock_scripts),
+            TypeScriptsHalt::from(primitive_witness.clone()).vast(),
+            RemovalRecordsIntegrity::from(primitive_witness.clone()).vast(),
+        ])
+        .verify(primitive_witness.kernel.mast_hash())
+    }
+
     pub fn validation_tree_from_mutator_set_update(
         old_tree: &ValidityTree,
         old_kernel: &TransactionKernel,
@@ -154,7 +183,7 @@ impl TransactionValidationLogic {
             ValidityTree::new(
                 ValidityAstType::Atomic(
                     None,
-                    Claim::new(Digest::default()),
+                    Claim::new(Digest::ZERO),
                     WhichProgram::MutatorSetUpdate,
                 ),
                 WitnessType::Faith,
@@ -0,0 +1,129 @@
+//! Caches the outcome of verifying transactions and their inputs.
+//!
+//! A transaction is verified when it enters the mempool, and again when it
+//! shows up in a block. A transaction is remembered by its kernel and the
+//! digest of its witness, so a different witness for the same kernel is
+//! verified anew. Its inputs are remembered by the lock script and witness
+//! that unlock them, so that a block transaction merged from known
+//! transactions need not run their lock scripts again.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use tasm_lib::Digest;
+
+/// Max number of verified transactions and inputs remembered. The oldest are
+/// forgotten first.
+pub const VERIFICATION_CACHE_CAPACITY: usize = 1 << 16;
+
+#[derive(Debug)]
+pub struct VerificationCache {
+    capacity: usize,
+    entries: HashSet<Digest>,
+
+    /// Insertion order, for eviction
+    order: VecDeque<Digest>,
+}
+
+impl VerificationCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, key: Digest) -> bool {
+        self.entries.contains(&key)
+    }
+
+    pub fn insert(&mut self, key: Digest) {
+        if self.capacity == 0 || !self.entries.insert(key) {
+            return;
+        }
+        self.order.push_back(key);
+
+        while self.entries.len() > self.capacity {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+}
+
+/// The cache shared by the mempool and block validation
+pub fn verification_cache() -> &'static Mutex<VerificationCache> {
+    static CACHE: OnceLock<Mutex<VerificationCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(VerificationCache::new(VERIFICATION_CACHE_CAPACITY)))
+}
+
+/// Whether `check` holds for all items, checking them in chunks on as many
+/// threads as there are cores
+pub fn all_in_parallel<T, F>(items: &[T], check: F) -> bool
+where
+    T: Sync,
+    F: Fn(&T) -> bool + Sync,
+{
+    let workers = thread::available_parallelism()
+        .map(|workers| workers.get())
+        .unwrap_or(1);
+    if workers < 2 || items.len() < 2 {
+        return items.iter().all(check);
+    }
+
+    let chunk_size = items.len().div_ceil(workers);
+    let check = &check;
+    thread::scope(|scope| {
+        items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || chunk.iter().all(check)))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .all(|worker| worker.join().unwrap_or(false))
+    })
+}
+
+#[cfg(test)]
+mod verification_cache_tests {
+    use rand::random;
+
+    use super::*;
+
+    #[test]
+    fn entries_are_evicted_oldest_first() {
+        let mut cache = VerificationCache::new(3);
+        let keys: [Digest; 4] = random();
+
+        for key in keys {
+            cache.insert(key);
+        }
+        assert_eq!(3, cache.len());
+        assert!(!cache.contains(keys[0]));
+        assert!(keys[1..].iter().all(|&key| cache.contains(key)));
+
+        // Inserting a known entry does not refresh or duplicate it
+        cache.insert(keys[1]);
+        assert_eq!(3, cache.len());
+        cache.insert(keys[0]);
+        assert!(!cache.contains(keys[1]));
+    }
+
+    #[test]
+    fn parallel_checks_agree_with_sequential_ones() {
+        let items = (0..1000).collect::<Vec<u32>>();
+        assert!(all_in_parallel(&items, |&item| item < 1000));
+        assert!(!all_in_parallel(&items, |&item| item != 777));
+        assert!(all_in_parallel(&[] as &[u32], |_| false));
+    }
+}
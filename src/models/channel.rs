@@ -8,7 +8,10 @@ use twenty_first::math::digest::Digest;
 use super::blockchain::block::block_header::PROOF_OF_WORK_COUNT_U32_SIZE;
 use super::blockchain::block::{block_height::BlockHeight, Block};
 use super::blockchain::transaction::Transaction;
+use super::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use super::consensus::timestamp::Timestamp;
 use super::peer::TransactionNotification;
+use super::state::alerts::SignedAlert;
 use super::state::wallet::utxo_notification_pool::ExpectedUtxo;
 
 #[derive(Clone, Debug)]
@@ -27,13 +30,24 @@ pub enum MainToMiner {
 
     StartSyncing,
     StopSyncing,
+
+    // Sent when our clock deviates too far from the network's and mining
+    // must be halted, and when it no longer does.
+    ClockSkewDetected,
+    ClockSkewResolved,
+
+    // Sent when the free disk space falls too low to store blocks, and when
+    // it recovers.
+    DiskSpaceLow,
+    DiskSpaceRecovered,
     // SetCoinbasePubkey,
 }
 
 #[derive(Clone, Debug)]
 pub struct NewBlockFound {
     pub block: Box<Block>,
-    pub coinbase_utxo_info: Box<ExpectedUtxo>,
+    /// Absent if the coinbase pays to an address outside the node's wallet
+    pub coinbase_utxo_info: Option<Box<ExpectedUtxo>>,
 }
 
 #[derive(Clone, Debug)]
@@ -51,6 +65,10 @@ pub enum MainToPeerThread {
     TransactionNotification(TransactionNotification), // Publish knowledge of a transaction
     Disconnect(SocketAddr),                       // Disconnect from a specific peer
     DisconnectAll(),                              // Disconnect from all peers
+    StemTransaction(Box<Transaction>, SocketAddr), // Pass a Dandelion stem-phase transaction to a specific peer
+    Alert(Box<SignedAlert>),                       // Relay a verified network alert
+    MempoolRequest(SocketAddr),                    // Ask a specific peer for its mempool
+    ClockRequest,                                  // Ask all peers that support it for their clocks
 }
 
 impl MainToPeerThread {
@@ -66,6 +84,10 @@ impl MainToPeerThread {
             MainToPeerThread::TransactionNotification(_) => "transaction notification".to_string(),
             MainToPeerThread::Disconnect(_) => "disconnect".to_string(),
             MainToPeerThread::DisconnectAll() => "disconnect all".to_string(),
+            MainToPeerThread::StemTransaction(_, _) => "stem transaction".to_string(),
+            MainToPeerThread::Alert(_) => "alert".to_string(),
+            MainToPeerThread::MempoolRequest(_) => "mempool req".to_string(),
+            MainToPeerThread::ClockRequest => "clock req".to_string(),
         }
     }
 }
@@ -77,12 +99,27 @@ pub enum PeerThreadToMain {
     RemovePeerMaxBlockHeight(SocketAddr),
     PeerDiscoveryAnswer((Vec<(SocketAddr, u128)>, SocketAddr, u8)), // ([(peer_listen_address)], reported_by, distance)
     Transaction(Box<PeerThreadToMainTransaction>),
+
+    /// Blocks that are valid except that their timestamps are slightly too far
+    /// in the future, to be validated again once the clock catches up
+    FutureBlocks(Vec<Block>),
+
+    /// A verified network alert not seen before, to be relayed to all peers
+    Alert(Box<SignedAlert>),
+
+    /// A connection to a peer that is also connected in the other direction,
+    /// and lost out to that connection. To be closed.
+    SupersededConnection(SocketAddr),
 }
 
 #[derive(Clone, Debug)]
 pub struct PeerThreadToMainTransaction {
     pub transaction: Transaction,
     pub confirmable_for_block: Digest,
+
+    /// The peer that passed the transaction in the stem phase of Dandelion
+    /// relay, or `None` if the transaction was diffused
+    pub stem_from: Option<SocketAddr>,
 }
 
 impl PeerThreadToMain {
@@ -95,6 +132,9 @@ impl PeerThreadToMain {
             }
             PeerThreadToMain::PeerDiscoveryAnswer(_) => "peer discovery answer".to_string(),
             PeerThreadToMain::Transaction(_) => "transaction".to_string(),
+            PeerThreadToMain::FutureBlocks(_) => "future blocks".to_string(),
+            PeerThreadToMain::Alert(_) => "alert".to_string(),
+            PeerThreadToMain::SupersededConnection(_) => "superseded connection".to_string(),
         }
     }
 }
@@ -105,6 +145,82 @@ pub enum RPCServerToMain {
     Shutdown,
     PauseMiner,
     RestartMiner,
+    BroadcastAlert(Box<SignedAlert>),
+    PreferTip(Digest),
+}
+
+/// Events published by the main loop when the chain tip or the wallet changes.
+#[derive(Clone, Debug)]
+pub enum ChainEvent {
+    NewBlock {
+        digest: Digest,
+        height: BlockHeight,
+        timestamp: Timestamp,
+    },
+
+    // The new tip is not a child of the previous tip.
+    Reorg {
+        old_tip_digest: Digest,
+        old_tip_height: BlockHeight,
+        new_tip_digest: Digest,
+        new_tip_height: BlockHeight,
+    },
+
+    // One or more UTXOs belonging to this wallet were confirmed in a block.
+    WalletReceive {
+        block_digest: Digest,
+        height: BlockHeight,
+        amount: NeptuneCoins,
+        utxo_count: usize,
+    },
+
+    // A transaction spent an input of an unconfirmed transaction sent by this
+    // wallet.
+    DoubleSpend {
+        own_transaction_digest: Digest,
+        conflicting_digest: Digest,
+        in_block: bool,
+    },
+
+    // A watched transaction reached the requested number of confirmations.
+    TransactionConfirmed {
+        transaction_digest: Digest,
+        block_digest: Digest,
+        confirmations: u64,
+    },
+
+    // The block that included a watched transaction left the canonical chain.
+    TransactionReorgedOut {
+        transaction_digest: Digest,
+        block_digest: Digest,
+    },
+
+    // The free disk space fell below the minimum, so blocks are no longer
+    // stored.
+    DiskSpaceLow {
+        available: u64,
+        min_free: u64,
+    },
+
+    // The free disk space recovered, and blocks are stored again.
+    DiskSpaceRecovered {
+        available: u64,
+    },
+}
+
+impl ChainEvent {
+    pub fn get_type(&self) -> String {
+        match self {
+            ChainEvent::NewBlock { .. } => "new_block".to_string(),
+            ChainEvent::Reorg { .. } => "reorg".to_string(),
+            ChainEvent::WalletReceive { .. } => "wallet_receive".to_string(),
+            ChainEvent::DoubleSpend { .. } => "double_spend".to_string(),
+            ChainEvent::TransactionConfirmed { .. } => "transaction_confirmed".to_string(),
+            ChainEvent::TransactionReorgedOut { .. } => "transaction_reorged_out".to_string(),
+            ChainEvent::DiskSpaceLow { .. } => "disk_space_low".to_string(),
+            ChainEvent::DiskSpaceRecovered { .. } => "disk_space_recovered".to_string(),
+        }
+    }
 }
 
 impl RPCServerToMain {
@@ -114,6 +230,7 @@ impl RPCServerToMain {
             RPCServerToMain::Shutdown => "shutdown".to_string(),
             RPCServerToMain::PauseMiner => "pause miner".to_owned(),
             RPCServerToMain::RestartMiner => "restart miner".to_owned(),
+            RPCServerToMain::BroadcastAlert(_) => "broadcast alert".to_owned(),
         }
     }
 }
@@ -9,6 +9,7 @@ use tasm_lib::twenty_first::{
 };
 
 use crate::models::blockchain::shared::Hash;
+use crate::util_types::digest_ext::DigestExt;
 
 pub trait HasDiscriminant: Clone {
     fn discriminant(&self) -> usize;
@@ -31,7 +32,7 @@ pub trait MastHash {
 
         // pad until length is a power of two
         while digests.len() & (digests.len() - 1) != 0 {
-            digests.push(Digest::default());
+            digests.push(Digest::ZERO);
         }
 
         CpuParallel::from_digests(&digests).unwrap()
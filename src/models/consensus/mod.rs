@@ -1,3 +1,4 @@
+use crate::models::blockchain::transaction::verification_cache::all_in_parallel;
 use crate::models::blockchain::type_scripts::native_currency::NativeCurrency;
 use crate::models::blockchain::type_scripts::time_lock::TimeLock;
 use crate::Hash;
@@ -10,7 +11,6 @@ use strum::Display;
 /// proofs. The concrete logic is specified in the directories `transaction` and `block`.
 use tasm_lib::triton_vm;
 use tasm_lib::triton_vm::program::Program;
-use tasm_lib::triton_vm::stark::Stark;
 use tasm_lib::twenty_first::math::b_field_element::BFieldElement;
 use tasm_lib::twenty_first::math::bfield_codec::BFieldCodec;
 use tasm_lib::Digest;
@@ -19,10 +19,12 @@ use triton_vm::prelude::NonDeterminism;
 use triton_vm::prelude::Proof;
 use triton_vm::prelude::PublicInput;
 
+use self::prover::{ProofJob, Prover};
 use self::tasm::program::ConsensusError;
 use self::tasm::program::ConsensusProgram;
 
 pub mod mast_hash;
+pub mod prover;
 pub mod tasm;
 pub mod timestamp;
 
@@ -186,7 +188,16 @@ impl ValidityTree {
                 clauses.iter().any(|clause| clause.verify(kernel_hash))
             }
             ValidityAstType::All(clauses) => {
-                clauses.iter().all(|clause| clause.verify(kernel_hash))
+                // Clauses that run programs, such as the lock scripts of a
+                // transaction's inputs, are worth spreading over cores
+                if clauses
+                    .iter()
+                    .all(|clause| matches!(clause.vast_type, ValidityAstType::Atomic(..)))
+                {
+                    all_in_parallel(clauses, |clause| clause.verify(kernel_hash))
+                } else {
+                    clauses.iter().all(|clause| clause.verify(kernel_hash))
+                }
             }
             ValidityAstType::Atomic(maybe_program, claim, which_program) => {
                 let WitnessType::RawWitness(raw_witness) = &self.witness_type else {
@@ -211,16 +222,40 @@ impl ValidityTree {
     }
 
     pub fn prove(&mut self) {
+        self.prove_with_progress(&Prover::Local, &mut || ());
+    }
+
+    /// Number of proofs that [`Self::prove`] produces
+    pub fn pending_proof_count(&self) -> usize {
+        match &self.vast_type {
+            ValidityAstType::Root(_object_digest, tree) => tree.pending_proof_count(),
+            ValidityAstType::Axiom => 0,
+            ValidityAstType::Any(branches) | ValidityAstType::All(branches) => branches
+                .iter()
+                .map(|branch| branch.pending_proof_count())
+                .sum(),
+            ValidityAstType::Atomic(program, _claim, _which_program) => {
+                let has_program = program
+                    .as_ref()
+                    .is_some_and(|program| !program.labelled_instructions().is_empty());
+                usize::from(has_program && matches!(self.witness_type, WitnessType::RawWitness(_)))
+            }
+        }
+    }
+
+    /// Like [`Self::prove`], but with the given prover, calling `on_proof`
+    /// after every proof produced
+    pub fn prove_with_progress(&mut self, prover: &Prover, on_proof: &mut dyn FnMut()) {
         match &mut self.vast_type {
             ValidityAstType::Root(_object_digest, tree) => {
-                tree.prove();
+                tree.prove_with_progress(prover, on_proof);
                 self.witness_type = tree.witness_type.clone();
                 tree.witness_type = WitnessType::None;
             }
             ValidityAstType::Axiom => {}
             ValidityAstType::Any(branches) => {
                 branches.iter_mut().for_each(|branch| {
-                    branch.prove();
+                    branch.prove_with_progress(prover, on_proof);
                 });
                 // can't use recursion yet, so faith instead
                 self.witness_type = WitnessType::Faith;
@@ -228,7 +263,7 @@ impl ValidityTree {
             }
             ValidityAstType::All(branches) => {
                 branches.iter_mut().for_each(|branch| {
-                    branch.prove();
+                    branch.prove_with_progress(prover, on_proof);
                 });
                 // can't use recursion yet, so faith instead
                 self.witness_type = WitnessType::Faith;
@@ -239,16 +274,17 @@ impl ValidityTree {
                     && !program.as_ref().unwrap().labelled_instructions().is_empty()
                 {
                     if let WitnessType::RawWitness(raw_witness) = &self.witness_type {
-                        let nondeterminism: NonDeterminism = raw_witness.clone().into();
-                        let proof = triton_vm::prove(
-                            Stark::default(),
-                            claim,
-                            program.as_deref().unwrap(),
-                            nondeterminism,
-                        )
-                        .unwrap_or_else(|_| panic!("proving {which_program} ..."));
+                        let job = ProofJob {
+                            claim: claim.clone(),
+                            program: program.as_deref().unwrap().clone(),
+                            raw_witness: raw_witness.clone(),
+                        };
+                        let proof = prover
+                            .prove(&job)
+                            .unwrap_or_else(|_| panic!("proving {which_program} ..."));
                         *program = None;
                         self.witness_type = WitnessType::Proof(proof);
+                        on_proof();
                     }
                 }
             }
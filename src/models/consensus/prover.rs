@@ -0,0 +1,235 @@
+//! Producing proofs, locally or by an external prover.
+//!
+//! Nodes on weak machines can delegate proving to a prover process, e.g.
+//! `neptune-prover` on another machine, given with `--prover-endpoint`. The
+//! node connects, sends one [`ProofJob`] and reads back a [`ProverResponse`],
+//! each as a frame: a 4-byte big-endian payload length followed by the
+//! bincode-encoded payload. If the prover cannot be reached or fails, the
+//! node proves locally.
+//!
+//! Frames are not encrypted, and a proof job carries the secret witness,
+//! e.g. the spending key of the inputs of a transaction. So the node only
+//! talks to provers on loopback addresses unless the operator explicitly
+//! allows remote ones, which should only be reached through a trusted
+//! channel like an SSH tunnel or a VPN.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tasm_lib::triton_vm;
+use tracing::{debug, warn};
+use triton_vm::prelude::{Claim, Proof};
+use triton_vm::program::Program;
+use triton_vm::stark::Stark;
+
+use super::RawWitness;
+
+/// Time to wait for a connection to the external prover
+const PROVER_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time to wait for the external prover to return a proof
+const PROVER_RESPONSE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Max size of a frame, so that a misbehaving peer of the protocol cannot
+/// make the other side allocate without bound
+pub const MAX_PROVER_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Everything needed to prove that a program halts gracefully
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofJob {
+    pub claim: Claim,
+    pub program: Program,
+    pub raw_witness: RawWitness,
+}
+
+/// A proof, or why none could be produced
+pub type ProverResponse = Result<Proof, String>;
+
+impl ProofJob {
+    pub fn prove_locally(&self) -> Result<Proof> {
+        triton_vm::prove(
+            Stark::default(),
+            &self.claim,
+            &self.program,
+            self.raw_witness.clone().into(),
+        )
+        .map_err(|err| anyhow::anyhow!("Failed to prove: {err}"))
+    }
+}
+
+pub fn write_frame<T: Serialize>(stream: &mut impl Write, payload: &T) -> Result<()> {
+    let bytes = bincode::serialize(payload)?;
+    ensure!(
+        bytes.len() <= MAX_PROVER_FRAME_SIZE,
+        "Frame of {} bytes is too large",
+        bytes.len()
+    );
+    stream.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    stream.write_all(&bytes)?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+pub fn read_frame<T: DeserializeOwned>(stream: &mut impl Read) -> Result<T> {
+    let mut length = [0u8; 4];
+    stream.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_PROVER_FRAME_SIZE {
+        bail!("Frame of {length} bytes is too large");
+    }
+
+    let mut bytes = vec![0u8; length];
+    stream.read_exact(&mut bytes)?;
+
+    Ok(bincode::deserialize(&bytes)?)
+}
+
+/// Where proofs are produced
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Prover {
+    #[default]
+    Local,
+
+    /// An external prover at the given `host:port`, falling back to local
+    /// proving. Unless `allow_remote` is set, only loopback addresses are
+    /// used, as jobs are sent unencrypted.
+    External {
+        endpoint: String,
+        allow_remote: bool,
+    },
+}
+
+impl Prover {
+    pub fn new(endpoint: Option<String>, allow_remote: bool) -> Self {
+        match endpoint {
+            Some(endpoint) => Self::External {
+                endpoint,
+                allow_remote,
+            },
+            None => Self::Local,
+        }
+    }
+
+    pub fn prove(&self, job: &ProofJob) -> Result<Proof> {
+        let Self::External {
+            endpoint,
+            allow_remote,
+        } = self
+        else {
+            return job.prove_locally();
+        };
+
+        match Self::prove_externally(endpoint, *allow_remote, job) {
+            Ok(proof) => {
+                debug!("Got proof from external prover {endpoint}");
+                Ok(proof)
+            }
+            Err(err) => {
+                warn!("External prover {endpoint} failed, proving locally: {err:#}");
+                job.prove_locally()
+            }
+        }
+    }
+
+    fn prove_externally(endpoint: &str, allow_remote: bool, job: &ProofJob) -> Result<Proof> {
+        let address = endpoint
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve {endpoint}"))?
+            .next()
+            .with_context(|| format!("{endpoint} resolves to no address"))?;
+        ensure!(
+            allow_remote || address.ip().is_loopback(),
+            "Refusing to send secret witnesses unencrypted to {address}, which is not a \
+            loopback address. Use a tunnel, or set --allow-remote-prover."
+        );
+        let mut stream = TcpStream::connect_timeout(&address, PROVER_CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(PROVER_RESPONSE_TIMEOUT))?;
+
+        write_frame(&mut stream, job)?;
+        let response: ProverResponse = read_frame(&mut stream)?;
+        let proof = response.map_err(|err| anyhow::anyhow!("Prover failed: {err}"))?;
+
+        // A prover that returns garbage is no better than no prover
+        ensure!(
+            triton_vm::verify(Stark::default(), &job.claim, &proof),
+            "Prover returned an invalid proof"
+        );
+
+        Ok(proof)
+    }
+}
+
+/// Serve one connection of an external prover: read a job, and answer with
+/// its proof
+pub fn serve_proof_job(stream: &mut (impl Read + Write)) -> Result<()> {
+    let job: ProofJob = read_frame(stream)?;
+    let response: ProverResponse = job.prove_locally().map_err(|err| format!("{err:#}"));
+    write_frame(stream, &response)
+}
+
+#[cfg(test)]
+mod prover_tests {
+    use std::net::TcpListener;
+
+    use tasm_lib::triton_vm::triton_asm;
+
+    use super::*;
+    use crate::models::blockchain::shared::Hash;
+
+    fn halting_job() -> ProofJob {
+        let program = Program::new(&triton_asm!(halt));
+        ProofJob {
+            claim: Claim::new(program.hash::<Hash>()),
+            program,
+            raw_witness: RawWitness::from(triton_vm::prelude::NonDeterminism::default()),
+        }
+    }
+
+    #[test]
+    fn external_prover_produces_proofs() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = listener.local_addr().unwrap().to_string();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            serve_proof_job(&mut stream).unwrap();
+        });
+
+        let job = halting_job();
+        let proof = Prover::prove_externally(&endpoint, false, &job).unwrap();
+        server.join().unwrap();
+        assert!(triton_vm::verify(Stark::default(), &job.claim, &proof));
+    }
+
+    #[test]
+    fn unreachable_prover_falls_back_to_local_proving() {
+        // Bind and drop, so that nothing listens on the port
+        let endpoint = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string();
+
+        let job = halting_job();
+        let proof = Prover::new(Some(endpoint), false).prove(&job).unwrap();
+        assert!(triton_vm::verify(Stark::default(), &job.claim, &proof));
+    }
+
+    #[test]
+    fn remote_prover_is_refused_unless_allowed() {
+        let job = halting_job();
+        let err = Prover::prove_externally("192.0.2.1:9800", false, &job).unwrap_err();
+        assert!(err.to_string().contains("not a loopback address"));
+    }
+
+    #[test]
+    fn oversized_frames_are_rejected() {
+        let mut frame = ((MAX_PROVER_FRAME_SIZE + 1) as u32).to_be_bytes().to_vec();
+        frame.extend([0u8; 16]);
+        assert!(read_frame::<ProofJob>(&mut frame.as_slice()).is_err());
+    }
+}
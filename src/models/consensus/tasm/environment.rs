@@ -11,6 +11,8 @@ use tasm_lib::{
     triton_vm::program::NonDeterminism, twenty_first::math::b_field_element::BFieldElement, Digest,
 };
 
+use crate::util_types::digest_ext::DigestExt;
+
 thread_local! {
     pub(super) static PUB_INPUT: RefCell<Vec<BFieldElement>> = const {RefCell::new(vec![])};
     pub(super) static PUB_OUTPUT: RefCell<Vec<BFieldElement>> = const { RefCell::new(vec![])};
@@ -20,7 +22,7 @@ thread_local! {
     pub(super) static ND_MEMORY: RefCell<HashMap<BFieldElement, BFieldElement>> =
         RefCell::new(HashMap::default());
 
-    pub(super) static PROGRAM_DIGEST: RefCell<Digest> = RefCell::new(Digest::default());
+    pub(super) static PROGRAM_DIGEST: RefCell<Digest> = RefCell::new(Digest::ZERO);
 }
 
 pub(crate) fn init(
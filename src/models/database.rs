@@ -8,10 +8,14 @@ use super::blockchain::block::block_header::BlockHeader;
 use super::blockchain::block::block_height::BlockHeight;
 use super::consensus::timestamp::Timestamp;
 use super::peer::PeerStanding;
+use super::state::peer_quality::PeerHistory;
 use crate::database::NeptuneLevelDb;
 
 pub const DATABASE_DIRECTORY_ROOT_NAME: &str = "databases";
 
+/// Width of the time buckets by which blocks are indexed, in milliseconds
+pub const TIME_BUCKET_MILLIS: u64 = 60 * 60 * 1000;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BlockFileLocation {
     pub file_index: u32,
@@ -78,11 +82,21 @@ pub struct LastFileRecord {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum BlockIndexKey {
-    Block(Digest),       // points to block headers and file locations
-    File(u32),           // points to file information
-    Height(BlockHeight), // Maps from block height to list of blocks
-    LastFile,            // points to last file used
-    BlockTipDigest,      // points to block digest of most canonical block known
+    Block(Digest),          // points to block headers and file locations
+    File(u32),              // points to file information
+    Height(BlockHeight),    // Maps from block height to list of blocks
+    LastFile,               // points to last file used
+    BlockTipDigest,         // points to block digest of most canonical block known
+    TimeBucket(u64),        // Maps from time bucket to list of blocks
+    FinalizedBlockDigest,   // points to block digest of the last finalized block
+    ValidatedBlock(Digest), // marks a block whose proofs verified, by its proofs digest
+}
+
+impl BlockIndexKey {
+    /// The time bucket that a block with the given timestamp falls in
+    pub fn time_bucket(timestamp: Timestamp) -> u64 {
+        timestamp.0.value() / TIME_BUCKET_MILLIS
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -92,6 +106,9 @@ pub enum BlockIndexValue {
     Height(Vec<Digest>),
     LastFile(LastFileRecord),
     BlockTipDigest(Digest),
+    TimeBucket(Vec<Digest>),
+    FinalizedBlockDigest(Digest),
+    ValidatedBlock,
 }
 
 impl BlockIndexValue {
@@ -129,11 +146,26 @@ impl BlockIndexValue {
             _ => panic!("Requested BlockTipDigest, found {:?}", self),
         }
     }
+
+    pub fn as_time_bucket_record(&self) -> Vec<Digest> {
+        match self {
+            BlockIndexValue::TimeBucket(rec) => rec.to_owned(),
+            _ => panic!("Requested TimeBucket, found {:?}", self),
+        }
+    }
+
+    pub fn as_finalized_block_digest(&self) -> Digest {
+        match self {
+            BlockIndexValue::FinalizedBlockDigest(digest) => digest.to_owned(),
+            _ => panic!("Requested FinalizedBlockDigest, found {:?}", self),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct PeerDatabases {
     pub peer_standings: NeptuneLevelDb<IpAddr, PeerStanding>,
+    pub peer_histories: NeptuneLevelDb<IpAddr, PeerHistory>,
 }
 
 impl fmt::Debug for PeerDatabases {
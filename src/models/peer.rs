@@ -1,9 +1,12 @@
+pub mod wire_format;
+
 use crate::prelude::twenty_first;
 
+use get_size::GetSize;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::net::SocketAddr;
-use std::time::SystemTime;
+use std::time::{Instant, SystemTime};
 use twenty_first::math::digest::Digest;
 
 use twenty_first::amount::u32s::U32s;
@@ -11,14 +14,18 @@ use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
 use super::blockchain::block::block_header::{BlockHeader, PROOF_OF_WORK_COUNT_U32_SIZE};
 use super::blockchain::block::block_height::BlockHeight;
+use super::blockchain::block::header_chain_proof::HeaderChainProof;
 use super::blockchain::block::transfer_block::TransferBlock;
 use super::blockchain::block::Block;
 use super::blockchain::shared::Hash;
 use super::blockchain::transaction::Transaction;
+use super::consensus::timestamp::Timestamp;
+use super::state::alerts::{AlertRateLimiter, SignedAlert};
 use crate::config_models::network::Network;
 
 const BAD_BLOCK_BATCH_REQUEST_SEVERITY: u16 = 10;
 const INVALID_BLOCK_SEVERITY: u16 = 10;
+const KNOWN_INVALID_BLOCK_SEVERITY: u16 = 20;
 const DIFFERENT_GENESIS_SEVERITY: u16 = u16::MAX;
 const SYNCHRONIZATION_TIMEOUT_SEVERITY: u16 = 5;
 const FLOODED_PEER_LIST_RESPONSE_SEVERITY: u16 = 2;
@@ -28,9 +35,31 @@ const UNKNOWN_BLOCK_HEIGHT: u16 = 1;
 const INVALID_TRANSACTION: u16 = 10;
 const UNCONFIRMABLE_TRANSACTION: u16 = 2;
 const NO_STANDING_FOUND_MAYBE_CRASH: u16 = 10;
+const INVALID_ALERT_SEVERITY: u16 = 20;
+const ALERT_FLOOD_SEVERITY: u16 = 5;
+const MEMPOOL_REQUEST_FLOOD_SEVERITY: u16 = 2;
+const CONTRADICTS_CHECKPOINT_SEVERITY: u16 = u16::MAX;
+const HEADER_CHAIN_PROOF_REQUEST_FLOOD_SEVERITY: u16 = 2;
 
 pub type InstanceId = u128;
 
+/// Max number of addresses a peer may advertise with
+/// [`PeerMessage::ListenAddresses`]
+pub const MAX_ADVERTISED_LISTEN_ADDRESSES: usize = 8;
+
+/// The first version that understands the peer messages that follow
+/// [`PeerMessage::ConnectionStatus`], e.g. [`PeerMessage::ClockRequest`].
+/// Older versions close the connection on a message they cannot decode, so
+/// these are only sent to peers of at least this version.
+pub const PEER_MESSAGE_EXTENSIONS_MIN_VERSION: &str = "0.0.6";
+
+/// True if a peer of the given version understands the messages of
+/// [`PEER_MESSAGE_EXTENSIONS_MIN_VERSION`]
+pub fn supports_message_extensions(version: &str) -> bool {
+    let min_version = semver::Version::parse(PEER_MESSAGE_EXTENSIONS_MIN_VERSION).unwrap();
+    semver::Version::parse(version).is_ok_and(|version| version >= min_version)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub struct PeerInfo {
     pub port_for_incoming_connections: Option<u16>,
@@ -41,6 +70,34 @@ pub struct PeerInfo {
     pub standing: PeerStanding,
     pub version: String,
     pub is_archival_node: bool,
+
+    /// The peer's clock minus ours, in milliseconds, as last measured with a
+    /// [`PeerMessage::ClockRequest`]. `None` until measured, or if the peer
+    /// does not support clock messages.
+    pub clock_offset_millis: Option<i64>,
+
+    /// The addresses the peer accepts incoming connections on, as advertised
+    /// with [`PeerMessage::ListenAddresses`]
+    pub advertised_listen_addresses: Vec<SocketAddr>,
+
+    /// The peer does not take transactions of other nodes, as announced with
+    /// [`PeerMessage::BlocksOnly`]
+    pub blocks_only: bool,
+}
+
+impl GetSize for PeerInfo {
+    fn get_stack_size() -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    fn get_heap_size(&self) -> usize {
+        self.version.capacity()
+            + self.advertised_listen_addresses.capacity() * std::mem::size_of::<SocketAddr>()
+    }
+
+    fn get_size(&self) -> usize {
+        Self::get_stack_size() + GetSize::get_heap_size(self)
+    }
 }
 
 impl PeerInfo {
@@ -50,6 +107,44 @@ impl PeerInfo {
         self.port_for_incoming_connections
             .map(|port| SocketAddr::new(self.connected_address.ip(), port))
     }
+
+    /// Return all addresses the peer is known to listen on. An advertised
+    /// unspecified IP stands for the IP the peer connects from, so it can only
+    /// be resolved for the family of the connected address.
+    pub fn known_listen_addresses(&self) -> Vec<SocketAddr> {
+        let connected_ip = self.connected_address.ip();
+        let mut addresses = self.listen_address().into_iter().collect::<Vec<_>>();
+        for advertised in self.advertised_listen_addresses.iter() {
+            let address = if !advertised.ip().is_unspecified() {
+                *advertised
+            } else if advertised.is_ipv4() == connected_ip.is_ipv4() {
+                SocketAddr::new(connected_ip, advertised.port())
+            } else {
+                continue;
+            };
+
+            if !addresses.contains(&address) {
+                addresses.push(address);
+            }
+        }
+
+        addresses
+    }
+
+    /// True if the peer's version understands the messages of
+    /// [`PEER_MESSAGE_EXTENSIONS_MIN_VERSION`]
+    pub fn supports_message_extensions(&self) -> bool {
+        supports_message_extensions(&self.version)
+    }
+
+    /// Record the peer's clock, as answered to a clock request sent at
+    /// `requested_at` and received at `now`. Half the round trip is assumed
+    /// to have passed when the peer read its clock.
+    pub fn record_clock(&mut self, peer_clock: Timestamp, requested_at: Timestamp, now: Timestamp) {
+        let requested_at = requested_at.0.value() as i64;
+        let midpoint = requested_at + (now.0.value() as i64 - requested_at) / 2;
+        self.clock_offset_millis = Some(peer_clock.0.value() as i64 - midpoint);
+    }
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -71,6 +166,18 @@ pub enum PeerSanctionReason {
     UnconfirmableTransaction,
 
     NoStandingFoundMaybeCrash,
+
+    // Offered a block already known to be invalid
+    KnownInvalidBlock((BlockHeight, Digest)),
+
+    InvalidAlert,
+    AlertFlood,
+    MempoolRequestFlood,
+
+    // Offered a block of a chain that contradicts a checkpoint
+    ContradictsCheckpoint(BlockHeight),
+
+    HeaderChainProofRequestFlood,
 }
 
 impl Display for PeerSanctionReason {
@@ -99,6 +206,12 @@ impl Display for PeerSanctionReason {
             PeerSanctionReason::NoStandingFoundMaybeCrash => {
                 "No standing found in map. Did peer thread crash?"
             }
+            PeerSanctionReason::KnownInvalidBlock(_) => "known invalid block",
+            PeerSanctionReason::InvalidAlert => "invalid alert",
+            PeerSanctionReason::AlertFlood => "alert flood",
+            PeerSanctionReason::MempoolRequestFlood => "mempool request flood",
+            PeerSanctionReason::ContradictsCheckpoint(_) => "contradicts checkpoint",
+            PeerSanctionReason::HeaderChainProofRequestFlood => "header chain proof request flood",
         };
         write!(f, "{string}")
     }
@@ -149,6 +262,14 @@ impl PeerSanctionReason {
             PeerSanctionReason::UnconfirmableTransaction => UNCONFIRMABLE_TRANSACTION,
             PeerSanctionReason::NonMinedTransactionHasCoinbase => INVALID_TRANSACTION,
             PeerSanctionReason::NoStandingFoundMaybeCrash => NO_STANDING_FOUND_MAYBE_CRASH,
+            PeerSanctionReason::KnownInvalidBlock(_) => KNOWN_INVALID_BLOCK_SEVERITY,
+            PeerSanctionReason::InvalidAlert => INVALID_ALERT_SEVERITY,
+            PeerSanctionReason::AlertFlood => ALERT_FLOOD_SEVERITY,
+            PeerSanctionReason::MempoolRequestFlood => MEMPOOL_REQUEST_FLOOD_SEVERITY,
+            PeerSanctionReason::ContradictsCheckpoint(_) => CONTRADICTS_CHECKPOINT_SEVERITY,
+            PeerSanctionReason::HeaderChainProofRequestFlood => {
+                HEADER_CHAIN_PROOF_REQUEST_FLOOD_SEVERITY
+            }
         }
     }
 }
@@ -297,6 +418,43 @@ pub enum PeerMessage {
     /// Inform peer that we are disconnecting them.
     Bye,
     ConnectionStatus(ConnectionStatus),
+    /// Pass a transaction in the stem phase of Dandelion relay. The receiver
+    /// either passes it on to one of its peers, or diffuses it.
+    StemTransaction(Box<Transaction>),
+    /// A network alert signed by a trusted key, relayed to all peers
+    Alert(Box<SignedAlert>),
+    /// Ask an archival node for a proof of the proof-of-work of its chain,
+    /// opening the blocks at the given heights. Answered at most once per
+    /// `HEADER_CHAIN_PROOF_REQUEST_INTERVAL`, except for whitelisted peers.
+    HeaderChainProofRequest(Vec<BlockHeight>),
+    HeaderChainProofResponse(Box<HeaderChainProof>),
+    /// Ask for a block with its proofs, also from a peer that otherwise
+    /// leaves them out for this node, see [`PeerMessage::OmitBlockProofsRequest`]
+    BlockRequestWithProofs(Digest),
+    /// Ask for the transactions in the peer's mempool, e.g. after starting up.
+    /// Answered at most once per `MEMPOOL_REQUEST_INTERVAL`, except for
+    /// whitelisted peers.
+    MempoolRequest,
+    /// The transactions in the mempool, highest fee density first, at most
+    /// `MAX_MEMPOOL_INVENTORY_SIZE`. The receiver requests those it lacks
+    /// with `TransactionRequest`.
+    MempoolResponse(Vec<TransactionNotification>),
+    /// Ask for the peer's wall clock, to detect clock skew. Only sent to peers
+    /// of at least [`PEER_MESSAGE_EXTENSIONS_MIN_VERSION`], as older ones
+    /// cannot decode it.
+    ClockRequest,
+    /// The sender's wall clock, in answer to a `ClockRequest`
+    Clock(Timestamp),
+    /// The addresses on which the sender accepts connections, of either
+    /// family, at most [`MAX_ADVERTISED_LISTEN_ADDRESSES`]. An unspecified IP
+    /// stands for the IP the sender connects from. Sent after the handshake.
+    ListenAddresses(Vec<SocketAddr>),
+    /// Ask the peer to leave the proofs out of the blocks it sends, see
+    /// `--omit-block-proofs`. Sent after the handshake.
+    OmitBlockProofsRequest,
+    /// The sender neither requests nor relays transactions of other nodes,
+    /// see `--blocks-only`. Sent after the handshake.
+    BlocksOnly,
 }
 
 impl PeerMessage {
@@ -317,6 +475,18 @@ impl PeerMessage {
             PeerMessage::PeerListResponse(_) => "peer list resp".to_string(),
             PeerMessage::Bye => "bye".to_string(),
             PeerMessage::ConnectionStatus(_) => "connection status".to_string(),
+            PeerMessage::StemTransaction(_) => "stem transaction".to_string(),
+            PeerMessage::Alert(_) => "alert".to_string(),
+            PeerMessage::HeaderChainProofRequest(_) => "header chain proof req".to_string(),
+            PeerMessage::HeaderChainProofResponse(_) => "header chain proof resp".to_string(),
+            PeerMessage::BlockRequestWithProofs(_) => "block req with proofs".to_string(),
+            PeerMessage::MempoolRequest => "mempool req".to_string(),
+            PeerMessage::MempoolResponse(_) => "mempool resp".to_string(),
+            PeerMessage::ClockRequest => "clock req".to_string(),
+            PeerMessage::Clock(_) => "clock".to_string(),
+            PeerMessage::ListenAddresses(_) => "listen addresses".to_string(),
+            PeerMessage::OmitBlockProofsRequest => "omit block proofs req".to_string(),
+            PeerMessage::BlocksOnly => "blocks only".to_string(),
         }
     }
 
@@ -337,6 +507,18 @@ impl PeerMessage {
             PeerMessage::PeerListResponse(_) => false,
             PeerMessage::Bye => false,
             PeerMessage::ConnectionStatus(_) => false,
+            PeerMessage::StemTransaction(_) => false,
+            PeerMessage::Alert(_) => false,
+            PeerMessage::HeaderChainProofRequest(_) => false,
+            PeerMessage::HeaderChainProofResponse(_) => false,
+            PeerMessage::BlockRequestWithProofs(_) => false,
+            PeerMessage::MempoolRequest => false,
+            PeerMessage::MempoolResponse(_) => false,
+            PeerMessage::ClockRequest => false,
+            PeerMessage::Clock(_) => false,
+            PeerMessage::ListenAddresses(_) => false,
+            PeerMessage::OmitBlockProofsRequest => false,
+            PeerMessage::BlocksOnly => false,
         }
     }
 
@@ -358,6 +540,18 @@ impl PeerMessage {
             PeerMessage::PeerListResponse(_) => false,
             PeerMessage::Bye => false,
             PeerMessage::ConnectionStatus(_) => false,
+            PeerMessage::StemTransaction(_) => true,
+            PeerMessage::Alert(_) => false,
+            PeerMessage::HeaderChainProofRequest(_) => false,
+            PeerMessage::HeaderChainProofResponse(_) => false,
+            PeerMessage::BlockRequestWithProofs(_) => false,
+            PeerMessage::MempoolRequest => false,
+            PeerMessage::MempoolResponse(_) => true,
+            PeerMessage::ClockRequest => false,
+            PeerMessage::Clock(_) => false,
+            PeerMessage::ListenAddresses(_) => false,
+            PeerMessage::OmitBlockProofsRequest => false,
+            PeerMessage::BlocksOnly => false,
         }
     }
 }
@@ -367,6 +561,27 @@ impl PeerMessage {
 pub struct MutablePeerState {
     pub highest_shared_block_height: BlockHeight,
     pub fork_reconciliation_blocks: Vec<Block>,
+    pub alert_rate_limiter: AlertRateLimiter,
+
+    /// When a mempool request of this peer was last answered
+    pub last_mempool_request_served: Option<Instant>,
+
+    /// True while a mempool request to this peer is unanswered
+    pub mempool_requested: bool,
+
+    /// When a header chain proof request of this peer was last answered
+    pub last_header_chain_proof_served: Option<Instant>,
+
+    /// When the unanswered clock request to this peer was sent, if any
+    pub clock_requested_at: Option<Timestamp>,
+
+    /// The peer asked for blocks without proofs, with
+    /// [`PeerMessage::OmitBlockProofsRequest`]
+    pub omit_block_proofs: bool,
+
+    /// The peer takes no transactions of other nodes, as announced with
+    /// [`PeerMessage::BlocksOnly`]
+    pub blocks_only: bool,
 }
 
 impl MutablePeerState {
@@ -374,6 +589,13 @@ impl MutablePeerState {
         Self {
             highest_shared_block_height: block_height,
             fork_reconciliation_blocks: vec![],
+            alert_rate_limiter: AlertRateLimiter::default(),
+            last_mempool_request_served: None,
+            mempool_requested: false,
+            last_header_chain_proof_served: None,
+            clock_requested_at: None,
+            omit_block_proofs: false,
+            blocks_only: false,
         }
     }
 }
@@ -0,0 +1,298 @@
+//! The wire format of the peer protocol, and conformance vectors for it.
+//!
+//! Peers exchange [`PeerMessage`]s as frames: a 4-byte big-endian payload
+//! length, followed by the message encoded with bincode as done by
+//! `tokio-serde`. [`conformance_vectors`] holds one message of every variant,
+//! and [`VECTORS_DIR`] their frames, hex-encoded, so that alternative
+//! implementations can check their encoding against this one. The tests fail
+//! if the encoding of any vector changes. A missing frame, e.g. of a new
+//! variant, is written by the tests, to be committed; all frames are
+//! rewritten with [`REGENERATE_ENV_VAR`] set.
+//!
+//! Vectors hold zero digests, heights and timestamps where they can, as those
+//! encode the same whatever the representation of field elements, so that
+//! their frames can be written by hand.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+
+use anyhow::{bail, Context, Result};
+use bytes::{Bytes, BytesMut};
+use tokio_serde::formats::SymmetricalBincode;
+use tokio_serde::{Deserializer, Serializer};
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::{
+    ConnectionRefusedReason, ConnectionStatus, HandshakeData, PeerBlockNotification, PeerMessage,
+    TransactionNotification,
+};
+use crate::config_models::network::Network;
+use crate::connect_to_peers::get_codec_rules;
+use crate::models::blockchain::block::block_body::BlockBodyField;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::block::header_chain_proof::{HeaderChainProof, ProvenHeader};
+use crate::models::blockchain::block::transfer_block::{ProofType, TransferBlock};
+use crate::models::blockchain::block::Block;
+use crate::models::consensus::mast_hash::MastHash;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::alerts::{Alert, AlertSecretKey, AlertSeverity};
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::MAGIC_STRING_REQUEST;
+
+/// Directory of the hex-encoded conformance vectors, relative to the crate root
+pub const VECTORS_DIR: &str = "test_data/peer_protocol";
+
+/// Set to overwrite the recorded frames with the current encoding
+pub const REGENERATE_ENV_VAR: &str = "NEPTUNE_REGENERATE_PEER_PROTOCOL_VECTORS";
+
+/// Size of the length prefix of a frame
+const FRAME_LENGTH_SIZE: usize = 4;
+
+/// Encode a message as one frame, as sent to peers
+pub fn encode_frame(message: &PeerMessage) -> Result<Bytes> {
+    let payload = Pin::new(&mut SymmetricalBincode::<PeerMessage>::default()).serialize(message)?;
+    let mut frame = BytesMut::new();
+    get_codec_rules().encode(payload, &mut frame)?;
+
+    Ok(frame.freeze())
+}
+
+/// Decode the message of the first frame in `buffer`, removing the frame.
+/// Returns `None` if the buffer does not hold a complete frame.
+pub fn decode_frame(buffer: &mut BytesMut) -> Result<Option<PeerMessage>> {
+    // The codec consumes the length of a partial frame, so only hand it whole
+    // frames
+    let Some(length) = buffer.get(..FRAME_LENGTH_SIZE) else {
+        return Ok(None);
+    };
+    let payload_length = u32::from_be_bytes(length.try_into().unwrap()) as usize;
+    if buffer.len() < FRAME_LENGTH_SIZE + payload_length {
+        return Ok(None);
+    }
+
+    let payload = get_codec_rules()
+        .decode(buffer)?
+        .expect("buffer holds a whole frame");
+    let message =
+        Pin::new(&mut SymmetricalBincode::<PeerMessage>::default()).deserialize(&payload)?;
+
+    Ok(Some(message))
+}
+
+/// Decode the messages of consecutive frames, as received from a peer. Fails
+/// if the bytes end in a partial frame.
+pub fn decode_frames(bytes: &[u8]) -> Result<Vec<PeerMessage>> {
+    let mut buffer = BytesMut::from(bytes);
+    let mut messages = vec![];
+    while let Some(message) = decode_frame(&mut buffer)
+        .with_context(|| format!("Failed to decode frame {}", messages.len()))?
+    {
+        messages.push(message);
+    }
+
+    if !buffer.is_empty() {
+        bail!("{} bytes of an incomplete frame at the end", buffer.len());
+    }
+
+    Ok(messages)
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decode hex, ignoring whitespace
+pub fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<Vec<_>>();
+    if digits.len() % 2 != 0 {
+        bail!("Odd number of hex digits");
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = pair.iter().collect::<String>();
+            u8::from_str_radix(&pair, 16).with_context(|| format!("Invalid hex byte {pair:?}"))
+        })
+        .collect()
+}
+
+/// Name of the message variant, as used for the vector files
+pub fn variant_name(message: &PeerMessage) -> &'static str {
+    match message {
+        PeerMessage::Handshake(_) => "handshake",
+        PeerMessage::Block(_) => "block",
+        PeerMessage::BlockNotificationRequest => "block_notification_request",
+        PeerMessage::BlockNotification(_) => "block_notification",
+        PeerMessage::BlockRequestByHeight(_) => "block_request_by_height",
+        PeerMessage::BlockRequestByHash(_) => "block_request_by_hash",
+        PeerMessage::BlockRequestBatch(_, _) => "block_request_batch",
+        PeerMessage::BlockResponseBatch(_) => "block_response_batch",
+        PeerMessage::Transaction(_) => "transaction",
+        PeerMessage::TransactionNotification(_) => "transaction_notification",
+        PeerMessage::TransactionRequest(_) => "transaction_request",
+        PeerMessage::PeerListRequest => "peer_list_request",
+        PeerMessage::PeerListResponse(_) => "peer_list_response",
+        PeerMessage::Bye => "bye",
+        PeerMessage::ConnectionStatus(_) => "connection_status",
+        PeerMessage::StemTransaction(_) => "stem_transaction",
+        PeerMessage::Alert(_) => "alert",
+        PeerMessage::HeaderChainProofRequest(_) => "header_chain_proof_request",
+        PeerMessage::HeaderChainProofResponse(_) => "header_chain_proof_response",
+        PeerMessage::BlockRequestWithProofs(_) => "block_request_with_proofs",
+        PeerMessage::MempoolRequest => "mempool_request",
+        PeerMessage::MempoolResponse(_) => "mempool_response",
+        PeerMessage::ClockRequest => "clock_request",
+        PeerMessage::Clock(_) => "clock",
+        PeerMessage::ListenAddresses(_) => "listen_addresses",
+        PeerMessage::OmitBlockProofsRequest => "omit_block_proofs_request",
+        PeerMessage::BlocksOnly => "blocks_only",
+    }
+}
+
+/// One message of every variant, built from fixed data only, so that the
+/// vectors do not change between runs
+pub fn conformance_vectors() -> Vec<PeerMessage> {
+    let genesis = Block::genesis_block(Network::Main);
+    let transaction = genesis.body().transaction.clone();
+    let launch_date = genesis.header().timestamp;
+    let handshake = HandshakeData {
+        tip_header: genesis.header().clone(),
+        listen_port: Some(9798),
+        network: Network::Main,
+        instance_id: 0x0123_4567_89ab_cdef_0123_4567_89ab_cdef,
+        version: "0.0.0".to_owned(),
+        is_archival_node: true,
+    };
+    let alert = Alert {
+        id: 1,
+        network: Network::Main,
+        severity: AlertSeverity::Warning,
+        message: "Upgrade to the latest version".to_owned(),
+        created: launch_date,
+        expires: launch_date + Timestamp::days(7),
+    }
+    .sign(&AlertSecretKey([7u8; 32]));
+
+    // The genesis block itself cannot be transferred, but its contents can
+    let transfer_block = TransferBlock {
+        header: genesis.header().clone(),
+        body: genesis.body().clone(),
+        proof_type: ProofType::Unimplemented,
+    };
+    let header_chain_proof = HeaderChainProof {
+        tip: ProvenHeader::from_block(&genesis),
+        tip_parent: None,
+        block_mmr_accumulator: genesis.body().block_mmr_accumulator.clone(),
+        block_mmr_accumulator_path: genesis
+            .body()
+            .mast_path(BlockBodyField::BlockMmrAccumulator),
+        samples: vec![],
+    };
+    let zero_transaction_notification = TransactionNotification {
+        transaction_digest: Digest::default(),
+    };
+    let peer_addresses: Vec<(SocketAddr, u128)> = vec![
+        ("192.0.2.1:9798".parse().unwrap(), 1),
+        ("[2001:db8::1]:9798".parse().unwrap(), u128::MAX),
+    ];
+
+    vec![
+        PeerMessage::Handshake(Box::new((MAGIC_STRING_REQUEST.to_vec(), handshake))),
+        PeerMessage::Block(Box::new(transfer_block.clone())),
+        PeerMessage::BlockNotificationRequest,
+        PeerMessage::BlockNotification(PeerBlockNotification::from(&genesis)),
+        PeerMessage::BlockRequestByHeight(BlockHeight::genesis()),
+        PeerMessage::BlockRequestByHash(Digest::default()),
+        PeerMessage::BlockRequestBatch(vec![Digest::default()], 100),
+        PeerMessage::BlockResponseBatch(vec![transfer_block]),
+        PeerMessage::Transaction(Box::new(transaction.clone())),
+        PeerMessage::TransactionNotification(zero_transaction_notification),
+        PeerMessage::TransactionRequest(Digest::default()),
+        PeerMessage::PeerListRequest,
+        PeerMessage::PeerListResponse(peer_addresses),
+        PeerMessage::Bye,
+        PeerMessage::ConnectionStatus(ConnectionStatus::Refused(
+            ConnectionRefusedReason::MaxPeerNumberExceeded,
+        )),
+        PeerMessage::StemTransaction(Box::new(transaction)),
+        PeerMessage::Alert(Box::new(alert)),
+        PeerMessage::HeaderChainProofRequest(vec![BlockHeight::genesis()]),
+        PeerMessage::HeaderChainProofResponse(Box::new(header_chain_proof)),
+        PeerMessage::BlockRequestWithProofs(Digest::default()),
+        PeerMessage::MempoolRequest,
+        PeerMessage::MempoolResponse(vec![zero_transaction_notification]),
+        PeerMessage::ClockRequest,
+        PeerMessage::Clock(Timestamp::zero()),
+        PeerMessage::ListenAddresses(vec![
+            "0.0.0.0:9798".parse().unwrap(),
+            "[::]:9798".parse().unwrap(),
+        ]),
+        PeerMessage::OmitBlockProofsRequest,
+        PeerMessage::BlocksOnly,
+    ]
+}
+
+#[cfg(test)]
+mod wire_format_tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    fn vector_path(message: &PeerMessage) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join(VECTORS_DIR)
+            .join(variant_name(message))
+            .with_extension("hex")
+    }
+
+    #[test]
+    fn conformance_vectors_match_recorded_frames() {
+        for message in conformance_vectors() {
+            let frame = encode_frame(&message).unwrap();
+            assert_eq!(vec![message.clone()], decode_frames(&frame).unwrap());
+
+            let path = vector_path(&message);
+            if std::env::var_os(REGENERATE_ENV_VAR).is_some() || !path.exists() {
+                println!(
+                    "Recording frame of {} in {VECTORS_DIR}, commit it",
+                    variant_name(&message)
+                );
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, to_hex(&frame) + "\n").unwrap();
+            }
+
+            let recorded = fs::read_to_string(&path).unwrap();
+            let recorded = from_hex(&recorded).unwrap();
+            assert_eq!(
+                to_hex(&recorded),
+                to_hex(&frame),
+                "Encoding of {} changed",
+                variant_name(&message)
+            );
+            assert_eq!(vec![message], decode_frames(&recorded).unwrap());
+        }
+    }
+
+    #[test]
+    fn decoding_rejects_incomplete_frames() {
+        let frames = [PeerMessage::Bye, PeerMessage::PeerListRequest]
+            .iter()
+            .flat_map(|message| encode_frame(message).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![PeerMessage::Bye, PeerMessage::PeerListRequest],
+            decode_frames(&frames).unwrap()
+        );
+        assert!(decode_frames(&frames[..frames.len() - 1]).is_err());
+        assert_eq!(
+            frames,
+            from_hex(&format!(" {}\n", to_hex(&frames))).unwrap()
+        );
+        assert!(from_hex("abc").is_err());
+    }
+}
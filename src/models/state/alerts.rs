@@ -0,0 +1,438 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config_models::network::Network;
+use crate::models::consensus::timestamp::Timestamp;
+
+/// Public keys of the developers whose alerts are trusted by every node,
+/// hex-encoded. No keys are configured yet, so alerts are only accepted from
+/// keys added with `--alert-key`.
+pub const DEVELOPER_ALERT_KEYS: &[&str] = &[];
+
+/// Max length of an alert message, in bytes
+pub const MAX_ALERT_MESSAGE_LENGTH: usize = 256;
+
+/// Max time from the creation of an alert until it expires
+pub const MAX_ALERT_LIFETIME_IN_DAYS: usize = 30;
+
+/// Max number of alerts kept. Storing more drops the least severe one that
+/// expires first.
+pub const MAX_STORED_ALERTS: usize = 20;
+
+/// Max number of new alerts accepted from a single peer per hour. Alerts
+/// already known do not count.
+pub const MAX_NEW_ALERTS_PER_PEER_PER_HOUR: usize = 5;
+
+/// How far into the future the creation time of an alert may lie
+const ALERT_FUTUREDATING_LIMIT_IN_MINUTES: usize = 10;
+
+/// Prefixed to the encoding of an alert before signing, so that alert
+/// signatures cannot be passed off as signatures of anything else
+const ALERT_SIGNING_CONTEXT: &[u8] = b"neptune-core/alert/v1";
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, clap::ValueEnum,
+)]
+pub enum AlertSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Display for AlertSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let string = match self {
+            AlertSeverity::Info => "info",
+            AlertSeverity::Warning => "warning",
+            AlertSeverity::Critical => "critical",
+        };
+        write!(f, "{string}")
+    }
+}
+
+/// A message from the developers to all node operators, e.g. "critical bug,
+/// upgrade to v0.0.6".
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alert {
+    /// Identifies the alert among those signed with the same key
+    pub id: u64,
+    pub network: Network,
+    pub severity: AlertSeverity,
+    pub message: String,
+    pub created: Timestamp,
+    pub expires: Timestamp,
+}
+
+impl Alert {
+    fn signing_payload(&self) -> Vec<u8> {
+        let mut payload = ALERT_SIGNING_CONTEXT.to_vec();
+        payload.extend(bincode::serialize(self).expect("alert must serialize"));
+        payload
+    }
+
+    pub fn sign(self, secret_key: &AlertSecretKey) -> SignedAlert {
+        let signing_key = SigningKey::from_bytes(&secret_key.0);
+        let signature = signing_key.sign(&self.signing_payload());
+
+        SignedAlert {
+            public_key: AlertPublicKey(signing_key.verifying_key().to_bytes()),
+            signature: signature.to_bytes().to_vec(),
+            alert: self,
+        }
+    }
+}
+
+/// Ed25519 public key of an alert signer, hex-encoded when displayed or parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AlertPublicKey(pub [u8; 32]);
+
+/// Ed25519 secret key of an alert signer, hex-encoded when parsed
+#[derive(Clone)]
+pub struct AlertSecretKey(pub [u8; 32]);
+
+impl AlertSecretKey {
+    pub fn generate() -> Self {
+        Self(rand::random())
+    }
+
+    pub fn public_key(&self) -> AlertPublicKey {
+        AlertPublicKey(SigningKey::from_bytes(&self.0).verifying_key().to_bytes())
+    }
+
+    pub fn to_hex(&self) -> String {
+        to_hex(&self.0)
+    }
+}
+
+impl FromStr for AlertPublicKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(from_hex(s)?))
+    }
+}
+
+impl FromStr for AlertSecretKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(from_hex(s.trim())?))
+    }
+}
+
+impl Display for AlertPublicKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", to_hex(&self.0))
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(s: &str) -> anyhow::Result<[u8; 32]> {
+    if s.len() != 64 || !s.is_ascii() {
+        anyhow::bail!("expected 64 hex characters");
+    }
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)?;
+    }
+
+    Ok(bytes)
+}
+
+/// Return the built-in developer keys along with the given additional keys.
+pub fn trusted_alert_keys(additional_keys: &[AlertPublicKey]) -> Vec<AlertPublicKey> {
+    DEVELOPER_ALERT_KEYS
+        .iter()
+        .map(|key| key.parse().expect("built-in alert keys must be valid"))
+        .chain(additional_keys.iter().copied())
+        .collect()
+}
+
+/// The reason an alert was rejected
+#[derive(Clone, Debug, Error, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertError {
+    #[error("alert is not signed by a trusted key")]
+    UntrustedKey,
+
+    #[error("alert signature is invalid")]
+    InvalidSignature,
+
+    #[error(
+        "alert message must be 1 to {} bytes without control characters",
+        MAX_ALERT_MESSAGE_LENGTH
+    )]
+    InvalidMessage,
+
+    #[error("alert is for network {0}")]
+    WrongNetwork(Network),
+
+    #[error(
+        "alert expires before it is created, or lives longer than {} days",
+        MAX_ALERT_LIFETIME_IN_DAYS
+    )]
+    InvalidLifetime,
+
+    #[error("alert is created in the future")]
+    CreatedInFuture,
+
+    #[error("alert has expired")]
+    Expired,
+}
+
+impl AlertError {
+    /// True if the alert was rejected for reasons that honest peers can
+    /// disagree on, because they trust different keys or their clocks differ.
+    pub fn is_local(&self) -> bool {
+        matches!(
+            self,
+            AlertError::UntrustedKey | AlertError::CreatedInFuture | AlertError::Expired
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedAlert {
+    pub alert: Alert,
+    pub public_key: AlertPublicKey,
+    pub signature: Vec<u8>,
+}
+
+impl SignedAlert {
+    /// Check that the alert is well-formed, current, and signed by one of the
+    /// trusted keys. The signature is checked last, as it is the most
+    /// expensive check.
+    pub fn verify(
+        &self,
+        trusted_keys: &[AlertPublicKey],
+        network: Network,
+        now: Timestamp,
+    ) -> Result<(), AlertError> {
+        let alert = &self.alert;
+        if !trusted_keys.contains(&self.public_key) {
+            return Err(AlertError::UntrustedKey);
+        }
+        if alert.network != network {
+            return Err(AlertError::WrongNetwork(alert.network));
+        }
+        if alert.message.is_empty()
+            || alert.message.len() > MAX_ALERT_MESSAGE_LENGTH
+            || alert.message.chars().any(char::is_control)
+        {
+            return Err(AlertError::InvalidMessage);
+        }
+        if alert.expires <= alert.created
+            || alert.expires - alert.created > Timestamp::days(MAX_ALERT_LIFETIME_IN_DAYS)
+        {
+            return Err(AlertError::InvalidLifetime);
+        }
+        if alert.created > now + Timestamp::minutes(ALERT_FUTUREDATING_LIMIT_IN_MINUTES) {
+            return Err(AlertError::CreatedInFuture);
+        }
+        if alert.expires <= now {
+            return Err(AlertError::Expired);
+        }
+
+        let verifying_key = VerifyingKey::from_bytes(&self.public_key.0)
+            .map_err(|_| AlertError::InvalidSignature)?;
+        let signature =
+            Signature::from_slice(&self.signature).map_err(|_| AlertError::InvalidSignature)?;
+        verifying_key
+            .verify_strict(&alert.signing_payload(), &signature)
+            .map_err(|_| AlertError::InvalidSignature)
+    }
+}
+
+/// Alerts that passed verification and have not expired
+#[derive(Clone, Debug, Default)]
+pub struct AlertStore {
+    alerts: Vec<SignedAlert>,
+}
+
+impl AlertStore {
+    /// Return true if an alert with the same signer and id is stored.
+    pub fn contains(&self, alert: &SignedAlert) -> bool {
+        self.alerts
+            .iter()
+            .any(|a| a.public_key == alert.public_key && a.alert.id == alert.alert.id)
+    }
+
+    /// Store a verified alert. Returns false if an alert with the same signer
+    /// and id is already stored, or if the store is full of more severe alerts.
+    pub fn insert(&mut self, alert: SignedAlert, now: Timestamp) -> bool {
+        self.alerts.retain(|a| a.alert.expires > now);
+        if self.contains(&alert) {
+            return false;
+        }
+
+        let priority = |a: &SignedAlert| (a.alert.severity, a.alert.expires);
+        if self.alerts.len() >= MAX_STORED_ALERTS {
+            let (index, lowest) = self
+                .alerts
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, a)| priority(a))
+                .unwrap();
+            if priority(lowest) >= priority(&alert) {
+                return false;
+            }
+            self.alerts.remove(index);
+        }
+
+        self.alerts.push(alert);
+        true
+    }
+
+    /// Return the alerts that have not expired, most severe first.
+    pub fn active(&self, now: Timestamp) -> Vec<SignedAlert> {
+        let mut active = self
+            .alerts
+            .iter()
+            .filter(|a| a.alert.expires > now)
+            .cloned()
+            .collect::<Vec<_>>();
+        active.sort_by_key(|a| std::cmp::Reverse(a.alert.severity));
+
+        active
+    }
+}
+
+/// Limits how many new alerts a single peer can make this node verify.
+#[derive(Clone, Debug, Default)]
+pub struct AlertRateLimiter {
+    received: VecDeque<Instant>,
+}
+
+impl AlertRateLimiter {
+    /// Record a new alert received at time `now`. Returns false if the peer
+    /// has already sent the max number of new alerts in the past hour, in
+    /// which case the alert should be dropped.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let hour = Duration::from_secs(60 * 60);
+        while self
+            .received
+            .front()
+            .is_some_and(|received| now.duration_since(*received) > hour)
+        {
+            self.received.pop_front();
+        }
+
+        if self.received.len() >= MAX_NEW_ALERTS_PER_PEER_PER_HOUR {
+            return false;
+        }
+        self.received.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod alerts_tests {
+    use super::*;
+
+    fn alert(id: u64, severity: AlertSeverity, now: Timestamp) -> Alert {
+        Alert {
+            id,
+            network: Network::RegTest,
+            severity,
+            message: "critical bug, upgrade to v0.0.6".to_string(),
+            created: now,
+            expires: now + Timestamp::days(1),
+        }
+    }
+
+    #[test]
+    fn alerts_signed_by_trusted_keys_verify() {
+        let now = Timestamp::now();
+        let secret_key = AlertSecretKey::generate();
+        let trusted_keys = trusted_alert_keys(&[secret_key.public_key()]);
+        let signed = alert(1, AlertSeverity::Critical, now).sign(&secret_key);
+        assert!(signed.verify(&trusted_keys, Network::RegTest, now).is_ok());
+
+        assert_eq!(
+            Err(AlertError::UntrustedKey),
+            signed.verify(&trusted_alert_keys(&[]), Network::RegTest, now)
+        );
+        assert_eq!(
+            Err(AlertError::WrongNetwork(Network::RegTest)),
+            signed.verify(&trusted_keys, Network::Alpha, now)
+        );
+        assert_eq!(
+            Err(AlertError::Expired),
+            signed.verify(&trusted_keys, Network::RegTest, now + Timestamp::days(2))
+        );
+
+        let mut tampered = signed.clone();
+        tampered.alert.severity = AlertSeverity::Info;
+        assert_eq!(
+            Err(AlertError::InvalidSignature),
+            tampered.verify(&trusted_keys, Network::RegTest, now)
+        );
+
+        let mut overlong = alert(2, AlertSeverity::Info, now);
+        overlong.message = "a".repeat(MAX_ALERT_MESSAGE_LENGTH + 1);
+        assert_eq!(
+            Err(AlertError::InvalidMessage),
+            overlong
+                .sign(&secret_key)
+                .verify(&trusted_keys, Network::RegTest, now)
+        );
+
+        let mut long_lived = alert(3, AlertSeverity::Info, now);
+        long_lived.expires = now + Timestamp::days(MAX_ALERT_LIFETIME_IN_DAYS + 1);
+        assert_eq!(
+            Err(AlertError::InvalidLifetime),
+            long_lived
+                .sign(&secret_key)
+                .verify(&trusted_keys, Network::RegTest, now)
+        );
+    }
+
+    #[test]
+    fn keys_round_trip_through_hex() {
+        let secret_key = AlertSecretKey::generate();
+        let parsed_secret_key: AlertSecretKey = secret_key.to_hex().parse().unwrap();
+        assert_eq!(secret_key.public_key(), parsed_secret_key.public_key());
+
+        let public_key = secret_key.public_key();
+        assert_eq!(public_key, public_key.to_string().parse().unwrap());
+        assert!("abcd".parse::<AlertPublicKey>().is_err());
+    }
+
+    #[test]
+    fn store_keeps_most_severe_alerts() {
+        let now = Timestamp::now();
+        let secret_key = AlertSecretKey::generate();
+        let mut store = AlertStore::default();
+        for id in 0..MAX_STORED_ALERTS as u64 {
+            assert!(store.insert(alert(id, AlertSeverity::Info, now).sign(&secret_key), now));
+        }
+        assert!(!store.insert(alert(0, AlertSeverity::Info, now).sign(&secret_key), now));
+
+        let critical = alert(1000, AlertSeverity::Critical, now).sign(&secret_key);
+        assert!(store.insert(critical.clone(), now));
+        let active = store.active(now);
+        assert_eq!(MAX_STORED_ALERTS, active.len());
+        assert_eq!(critical, active[0]);
+
+        assert!(store.active(now + Timestamp::days(2)).is_empty());
+    }
+
+    #[test]
+    fn new_alerts_per_peer_are_rate_limited() {
+        let start = Instant::now();
+        let mut rate_limiter = AlertRateLimiter::default();
+        for _ in 0..MAX_NEW_ALERTS_PER_PEER_PER_HOUR {
+            assert!(rate_limiter.allow(start));
+        }
+        assert!(!rate_limiter.allow(start + Duration::from_secs(60)));
+        assert!(rate_limiter.allow(start + Duration::from_secs(60 * 60 + 1)));
+    }
+}
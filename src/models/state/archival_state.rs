@@ -2,32 +2,60 @@ use crate::config_models::network::Network;
 use crate::prelude::twenty_first;
 
 use crate::database::storage::storage_schema::traits::*;
-use anyhow::Result;
+use crate::database::storage::storage_vec::traits::{pin_mut, StreamExt};
+use anyhow::{bail, Context, Result};
+use async_stream::stream;
+use futures::stream::{self, Stream};
+use itertools::Itertools;
 use memmap2::MmapOptions;
 use num_traits::Zero;
-use std::ops::DerefMut;
+use std::ops::{DerefMut, RangeInclusive};
 use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::AsyncReadExt;
 use tokio::io::AsyncSeekExt;
 use tokio::io::AsyncWriteExt;
 use tokio::io::SeekFrom;
+use tokio::sync::{Mutex, Semaphore};
 use tracing::{debug, warn};
 use twenty_first::math::digest::Digest;
 
+use super::finality::{height_final_at_depth, FinalityError, FinalizedBlock};
+use super::read_replica::DatabaseSnapshot;
 use super::shared::new_block_file_is_needed;
+use super::supply_audit::SupplyAudit;
 use crate::config_models::data_directory::DataDirectory;
+#[cfg(test)]
+use crate::database::fault_injection::{FaultInjector, WritePoint};
+use crate::database::storage::storage_vec::OrdinaryVec;
 use crate::database::{create_db_if_missing, NeptuneLevelDb, WriteBatchAsync};
+use crate::models::blockchain::block::block_body::BlockBodyField;
 use crate::models::blockchain::block::block_header::BlockHeader;
-use crate::models::blockchain::block::{block_height::BlockHeight, Block};
+use crate::models::blockchain::block::header_chain_proof::{
+    HeaderChainProof, HeaderChainProofOutline, ProvenHeader,
+};
+use crate::models::blockchain::block::{
+    block_height::BlockHeight, Block, FUTUREDATING_LIMIT_IN_HOURS,
+};
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::mast_hash::MastHash;
+use crate::models::consensus::timestamp::Timestamp;
 use crate::models::database::{
     BlockFileLocation, BlockIndexKey, BlockIndexValue, BlockRecord, FileRecord, LastFileRecord,
 };
 use crate::util_types::mutator_set::addition_record::AdditionRecord;
+use crate::util_types::mutator_set::archival_mmr::ArchivalMmr;
 use crate::util_types::mutator_set::removal_record::RemovalRecord;
 use crate::util_types::mutator_set::rusty_archival_mutator_set::RustyArchivalMutatorSet;
 
 pub const BLOCK_INDEX_DB_NAME: &str = "block_index";
 pub const MUTATOR_SET_DIRECTORY_NAME: &str = "mutator_set";
 
+/// Encoded size of the blocks that [`ArchivalState::get_blocks`] may decode
+/// ahead of the consumer, in KiB
+const BLOCK_DECODE_MEMORY_BUDGET_IN_KIB: usize = 256 * 1024;
+
 /// Provides interface to historic blockchain data which consists of
 ///  * block-data stored in individual files (append-only)
 ///  * block-index database stored in levelDB
@@ -45,9 +73,12 @@ pub struct ArchivalState {
     ///   Height(BlockHeight)  -> Height(Vec<Digest>)
     ///   LastFile             -> LastFile(LastFileRecord)
     ///   BlockTipDigest       -> BlockTipDigest(Digest)
+    ///   TimeBucket(u64)      -> TimeBucket(Vec<Digest>)
+    ///   FinalizedBlockDigest -> FinalizedBlockDigest(Digest)
+    ///   ValidatedBlock(Digest) -> ValidatedBlock
     /// ```
     ///
-    /// So this is effectively 5 logical indexes.
+    /// So this is effectively 8 logical indexes.
     pub block_index_db: NeptuneLevelDb<BlockIndexKey, BlockIndexValue>,
 
     // The genesis block is stored on the heap, as we would otherwise get stack overflows whenever we instantiate
@@ -57,6 +88,21 @@ pub struct ArchivalState {
     // The archival mutator set is persisted to one database that also records a sync label,
     // which corresponds to the hash of the block to which the mutator set is synced.
     pub archival_mutator_set: RustyArchivalMutatorSet,
+
+    // Set if the databases are a snapshot of another process' databases. See
+    // [`read_replica`](super::read_replica).
+    snapshot: Option<DatabaseSnapshot>,
+
+    // Set if opened with `open_read_only` or taken with `read_only_snapshot`
+    read_only: bool,
+
+    // The block MMR of the canonical chain as of the last header chain proof,
+    // so that the next one only needs the blocks that changed since. Built on
+    // first use.
+    block_mmr_cache: Mutex<Option<ArchivalMmr<Hash, OrdinaryVec<Digest>>>>,
+
+    #[cfg(test)]
+    pub fault_injector: FaultInjector,
 }
 
 // The only reason we have this `Debug` implementation is that it's required
@@ -209,15 +255,162 @@ impl ArchivalState {
             block_index_db,
             genesis_block,
             archival_mutator_set,
+            snapshot: None,
+            read_only: false,
+            block_mmr_cache: Mutex::new(None),
+            #[cfg(test)]
+            fault_injector: FaultInjector::default(),
+        }
+    }
+
+    /// Open the archival state of a data directory read-only, while the node
+    /// that owns the data directory may keep running. The state is that of
+    /// the moment of opening. See [`read_replica`](super::read_replica).
+    pub async fn open_read_only(data_dir: DataDirectory, network: Network) -> Result<Self> {
+        let snapshot = DatabaseSnapshot::take(&data_dir).await?;
+
+        let block_index_db = NeptuneLevelDb::<BlockIndexKey, BlockIndexValue>::new(
+            &snapshot.block_index_database_dir_path(),
+            &create_db_if_missing(),
+        )
+        .await?;
+        let ms_db = NeptuneLevelDb::new(
+            &snapshot.mutator_set_database_dir_path(),
+            &create_db_if_missing(),
+        )
+        .await?;
+        let mut archival_mutator_set = RustyArchivalMutatorSet::connect(ms_db).await;
+        archival_mutator_set.restore_or_new().await;
+        if archival_mutator_set.ams().aocl.is_empty().await {
+            bail!(
+                "There is no archival state in {}",
+                data_dir.root_dir_path().display()
+            );
+        }
+
+        Ok(Self {
+            data_dir,
+            block_index_db,
+            genesis_block: Box::new(Block::genesis_block(network)),
+            archival_mutator_set,
+            snapshot: Some(snapshot),
+            read_only: true,
+            block_mmr_cache: Mutex::new(None),
+            #[cfg(test)]
+            fault_injector: FaultInjector::default(),
+        })
+    }
+
+    /// A read-only view of this archival state as of now, which the node can
+    /// keep updating meanwhile. Reads the databases through LevelDB
+    /// snapshots, so taking it copies nothing. Blocks are read from the
+    /// block files, which are only appended to.
+    pub async fn read_only_snapshot(&self) -> Self {
+        Self {
+            data_dir: self.data_dir.clone(),
+            block_index_db: self.block_index_db.snapshot(),
+            genesis_block: self.genesis_block.clone(),
+            archival_mutator_set: self.archival_mutator_set.snapshot().await,
+            snapshot: None,
+            read_only: true,
+            block_mmr_cache: Mutex::new(None),
+            #[cfg(test)]
+            fault_injector: FaultInjector::default(),
         }
     }
 
+    /// Whether the archival state was opened with
+    /// [`open_read_only`](Self::open_read_only) or taken with
+    /// [`read_only_snapshot`](Self::read_only_snapshot)
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.is_read_only() {
+            bail!("The archival state is opened read-only");
+        }
+
+        Ok(())
+    }
+
+    /// Bring the mutator set in sync with the tip, if a crash interrupted
+    /// storing the tip.
+    ///
+    /// A new tip is first written to the block files and the block index,
+    /// and then applied to the mutator set. Writes to the block index are
+    /// atomic, so the only inconsistency a crash can leave is a mutator set
+    /// synced to an earlier block than the tip, which is repaired by applying
+    /// the blocks in between.
+    pub async fn recover_interrupted_tip_update(&mut self) -> Result<()> {
+        let tip = self.get_tip().await;
+        let sync_label = self.archival_mutator_set.get_sync_label().await;
+        if sync_label == tip.hash() {
+            return Ok(());
+        }
+
+        if self.get_block_header(sync_label).await.is_none() {
+            bail!(
+                "Mutator set is synced to unknown block {sync_label}. \
+                Consider resyncing from scratch."
+            );
+        }
+
+        warn!(
+            "Mutator set is synced to {sync_label} rather than to tip {}, \
+            probably because of a crash. Updating it.",
+            tip.hash()
+        );
+        self.update_mutator_set(&tip).await
+    }
+
+    pub fn data_dir(&self) -> &DataDirectory {
+        &self.data_dir
+    }
+
     pub fn genesis_block(&self) -> &Block {
         &self.genesis_block
     }
 
     /// Write a newly found block to database and to disk, and set it as tip.
+    /// A block that is stored already, e.g. a competing tip, is only set as
+    /// tip.
     pub async fn write_block_as_tip(&mut self, new_block: &Block) -> Result<()> {
+        self.check_writable()?;
+        if self.block_is_stored(new_block.hash()).await {
+            self.block_index_db
+                .put(
+                    BlockIndexKey::BlockTipDigest,
+                    BlockIndexValue::BlockTipDigest(new_block.hash()),
+                )
+                .await;
+            return Ok(());
+        }
+
+        self.write_block(new_block, true).await
+    }
+
+    /// Write a block to database and to disk without setting it as tip, e.g. a
+    /// competing tip with the same accumulated proof-of-work as the tip. Does
+    /// nothing if the block is stored already.
+    pub async fn write_block_as_competing_tip(&mut self, block: &Block) -> Result<()> {
+        self.check_writable()?;
+        if self.block_is_stored(block.hash()).await {
+            return Ok(());
+        }
+
+        self.write_block(block, false).await
+    }
+
+    /// Whether the block is stored on disk, which the genesis block is not
+    async fn block_is_stored(&self, block_digest: Digest) -> bool {
+        self.block_index_db
+            .get(BlockIndexKey::Block(block_digest))
+            .await
+            .is_some()
+    }
+
+    async fn write_block(&mut self, new_block: &Block, as_tip: bool) -> Result<()> {
         // Fetch last file record to find disk location to store block.
         // This record must exist in the DB already, unless this is the first block
         // stored on disk.
@@ -291,6 +484,17 @@ impl ArchivalState {
                 Some(rec) => rec.as_height_record(),
                 None => vec![],
             };
+        let time_bucket_key = BlockIndexKey::TimeBucket(BlockIndexKey::time_bucket(
+            new_block.kernel.header.timestamp,
+        ));
+        let mut blocks_in_same_time_bucket: Vec<Digest> =
+            match self.block_index_db.get(time_bucket_key.clone()).await {
+                Some(rec) => rec.as_time_bucket_record(),
+                None => vec![],
+            };
+
+        #[cfg(test)]
+        self.fault_injector.before_write(WritePoint::BlockFile)?;
 
         // Write to file with mmap, only map relevant part of file into memory
         // we use spawn_blocking to make the blocking mmap async-friendly.
@@ -328,18 +532,26 @@ impl ArchivalState {
             height_record_key,
             BlockIndexValue::Height(blocks_at_same_height),
         ));
-
-        // Mark block as tip
+        blocks_in_same_time_bucket.push(new_block.hash());
         block_index_entries.push((
-            BlockIndexKey::BlockTipDigest,
-            BlockIndexValue::BlockTipDigest(new_block.hash()),
+            time_bucket_key,
+            BlockIndexValue::TimeBucket(blocks_in_same_time_bucket),
         ));
 
+        if as_tip {
+            block_index_entries.push((
+                BlockIndexKey::BlockTipDigest,
+                BlockIndexValue::BlockTipDigest(new_block.hash()),
+            ));
+        }
+
         let mut batch = WriteBatchAsync::new();
         for (k, v) in block_index_entries.into_iter() {
             batch.op_write(k, v);
         }
 
+        #[cfg(test)]
+        self.fault_injector.before_write(WritePoint::BlockIndex)?;
         self.block_index_db.batch_write(batch).await;
 
         Ok(())
@@ -470,6 +682,338 @@ impl ArchivalState {
         Ok(Some(block))
     }
 
+    /// Stream the blocks with the given digests, in the given order, or `None`
+    /// for blocks that are not stored.
+    ///
+    /// Blocks are decoded from their files on as many threads as there are
+    /// cores, ahead of the consumer, until their encoded sizes add up to
+    /// [`BLOCK_DECODE_MEMORY_BUDGET_IN_KIB`]. Memory is reserved in order of
+    /// the digests, so the next block to be consumed is never starved by the
+    /// ones decoded ahead of it.
+    pub fn get_blocks(
+        &self,
+        block_digests: Vec<Digest>,
+    ) -> impl Stream<Item = Result<Option<Block>>> + '_ {
+        let workers = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+        let memory_budget = Arc::new(Semaphore::new(BLOCK_DECODE_MEMORY_BUDGET_IN_KIB));
+
+        stream::iter(block_digests)
+            .then(move |digest| {
+                let memory_budget = memory_budget.clone();
+                async move {
+                    let Some(record) = self
+                        .block_index_db
+                        .get(BlockIndexKey::Block(digest))
+                        .await
+                        .map(|x| x.as_block_record())
+                    else {
+                        return Ok((digest, None));
+                    };
+
+                    // Blocks larger than the budget are decoded on their own
+                    let kib = record
+                        .file_location
+                        .block_length
+                        .div_ceil(1024)
+                        .min(BLOCK_DECODE_MEMORY_BUDGET_IN_KIB);
+                    let permit = memory_budget.acquire_many_owned(kib as u32).await?;
+                    anyhow::Ok((digest, Some((record, permit))))
+                }
+            })
+            .map(|reserved| async move {
+                match reserved? {
+                    (_, Some((record, permit))) => {
+                        let block = self.get_block_from_block_record(record).await?;
+                        anyhow::Ok((Some(block), Some(permit)))
+                    }
+                    (digest, None) if digest == self.genesis_block.hash() => {
+                        Ok((Some(*self.genesis_block.clone()), None))
+                    }
+                    (_, None) => Ok((None, None)),
+                }
+            })
+            // `buffered` keeps the order of the blocks
+            .buffered(workers)
+            // The memory is released once the consumer takes the block
+            .map(|decoded| decoded.map(|(block, _permit)| block))
+    }
+
+    /// Stream the blocks of the canonical chain with heights in `range`, in
+    /// order of height.
+    ///
+    /// A block is only ever stored after its parent, so the blocks of a chain
+    /// appear in the block files in order of height. The files are therefore
+    /// read front to back, with one open file at a time, seeking only to skip
+    /// blocks of other chains. This is the way to walk many blocks, e.g. for
+    /// rescans, audits or exports; [`Self::get_block`] maps a file for each
+    /// block.
+    pub fn iter_canonical_blocks(
+        &self,
+        range: RangeInclusive<BlockHeight>,
+    ) -> impl Stream<Item = Result<Block>> + '_ {
+        stream! {
+            let tip_digest = match self.block_index_db.get(BlockIndexKey::BlockTipDigest).await {
+                Some(digest) => digest.as_tip_digest(),
+                None => self.genesis_block.hash(),
+            };
+            let blocks = self.iter_chain_blocks(tip_digest, range);
+            pin_mut!(blocks);
+            while let Some(block) = blocks.next().await {
+                yield block;
+            }
+        }
+    }
+
+    /// Map the blocks of the canonical chain with heights in `range`, in
+    /// parallel, and stream the results in order of height.
+    ///
+    /// Blocks are read as by [`Self::iter_canonical_blocks`] and handed to
+    /// `mapper` on as many blocking threads as there are cores. However the
+    /// threads are scheduled, results arrive in order of height, so folding
+    /// them is deterministic. This is the way to compute over many blocks,
+    /// e.g. for analytics or audits.
+    pub fn scan_chain<T, F>(
+        &self,
+        range: RangeInclusive<BlockHeight>,
+        mapper: F,
+    ) -> impl Stream<Item = Result<T>> + '_
+    where
+        F: Fn(Block) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        Self::map_blocks(self.iter_canonical_blocks(range), mapper)
+    }
+
+    fn map_blocks<'a, T, F>(
+        blocks: impl Stream<Item = Result<Block>> + 'a,
+        mapper: F,
+    ) -> impl Stream<Item = Result<T>> + 'a
+    where
+        F: Fn(Block) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        let workers = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+        let mapper = Arc::new(mapper);
+
+        blocks
+            .map(move |block| {
+                let mapper = mapper.clone();
+                async move {
+                    let block = block?;
+                    let mapped = tokio::task::spawn_blocking(move || mapper(block)).await?;
+                    anyhow::Ok(mapped)
+                }
+            })
+            // `buffered` keeps the order of the blocks
+            .buffered(workers)
+    }
+
+    /// Stream the blocks with heights in `range` of the chain ending in
+    /// `tip_digest`, in order of height
+    fn iter_chain_blocks(
+        &self,
+        tip_digest: Digest,
+        range: RangeInclusive<BlockHeight>,
+    ) -> impl Stream<Item = Result<Block>> + '_ {
+        stream! {
+            match self.chain_block_records(tip_digest, &range).await {
+                Err(err) => {
+                    yield Err(err);
+                }
+                Ok(records) => {
+                    if range.contains(&BlockHeight::genesis()) {
+                        yield Ok(*self.genesis_block.clone());
+                    }
+
+                    let mut open_file: Option<(u32, tokio::fs::File, u64)> = None;
+                    for record in records {
+                        yield self.read_block_sequentially(&mut open_file, record.file_location).await;
+                    }
+                }
+            }
+        }
+    }
+
+    /// The records of the blocks with heights in `range` of the chain ending
+    /// in `tip_digest`, in order of height. The genesis block has no record.
+    async fn chain_block_records(
+        &self,
+        tip_digest: Digest,
+        range: &RangeInclusive<BlockHeight>,
+    ) -> Result<Vec<BlockRecord>> {
+        let genesis_digest = self.genesis_block.hash();
+        let mut records = vec![];
+        let mut digest = tip_digest;
+        while digest != genesis_digest {
+            let record = self
+                .block_index_db
+                .get(BlockIndexKey::Block(digest))
+                .await
+                .map(|x| x.as_block_record())
+                .ok_or_else(|| anyhow::anyhow!("Missing block record for {digest}"))?;
+            let height = record.block_header.height;
+            if height < *range.start() {
+                break;
+            }
+
+            digest = record.block_header.prev_block_digest;
+            if range.contains(&height) {
+                records.push(record);
+            }
+        }
+        records.reverse();
+
+        Ok(records)
+    }
+
+    /// Read a block from `open_file`, if it is the block's file, seeking only
+    /// if the block does not directly follow the previously read one.
+    async fn read_block_sequentially(
+        &self,
+        open_file: &mut Option<(u32, tokio::fs::File, u64)>,
+        location: BlockFileLocation,
+    ) -> Result<Block> {
+        if !matches!(open_file, Some((file_index, _, _)) if *file_index == location.file_index) {
+            let file =
+                tokio::fs::File::open(self.data_dir.block_file_path(location.file_index)).await?;
+            *open_file = Some((location.file_index, file, 0));
+        }
+        let (_, file, position) = open_file.as_mut().unwrap();
+
+        if *position != location.offset {
+            file.seek(SeekFrom::Start(location.offset)).await?;
+        }
+        let mut bytes = vec![0u8; location.block_length];
+        file.read_exact(&mut bytes).await?;
+        *position = location.offset + location.block_length as u64;
+
+        Ok(bincode::deserialize(&bytes)?)
+    }
+
+    /// Prove the proof-of-work of the canonical chain to a light client,
+    /// opening the blocks at `sample_heights`. Heights that are genesis or not
+    /// below the tip are skipped.
+    pub async fn header_chain_proof(
+        &self,
+        sample_heights: &[BlockHeight],
+    ) -> Result<HeaderChainProof> {
+        self.header_chain_proof_outline(sample_heights)
+            .await?
+            .complete(|digest| self.proven_header(digest))
+            .await
+    }
+
+    /// The part of [`Self::header_chain_proof`] that depends on the tip, with
+    /// the headers to be read left out. A caller that holds this state behind
+    /// a lock can complete the outline with [`Self::proven_header`] one
+    /// header at a time, rather than holding the lock for all of them.
+    ///
+    /// The block MMR is kept between calls and only updated with the blocks
+    /// that changed since the last call, so only the first call after
+    /// startup looks up every block.
+    pub async fn header_chain_proof_outline(
+        &self,
+        sample_heights: &[BlockHeight],
+    ) -> Result<HeaderChainProofOutline> {
+        let tip = self.get_tip().await;
+        let tip_height = u64::from(tip.kernel.header.height);
+
+        let mut block_mmr_cache = self.block_mmr_cache.lock().await;
+        if block_mmr_cache.is_none() {
+            *block_mmr_cache = Some(ArchivalMmr::new(OrdinaryVec::from(vec![])).await);
+        }
+        let block_mmr = block_mmr_cache.as_mut().unwrap();
+        self.sync_block_mmr(block_mmr, &tip).await?;
+
+        let block_mmr_accumulator = block_mmr.to_accumulator_async().await;
+        if block_mmr_accumulator != tip.kernel.body.block_mmr_accumulator {
+            *block_mmr_cache = None;
+            bail!("Block MMR rebuilt from the block index does not match the tip");
+        }
+
+        let mut samples = vec![];
+        for height in sample_heights
+            .iter()
+            .map(|&height| u64::from(height))
+            .sorted()
+            .dedup()
+        {
+            if height == 0 || height >= tip_height {
+                continue;
+            }
+
+            samples.push((
+                block_mmr.get_leaf_async(height - 1).await,
+                block_mmr.get_leaf_async(height).await,
+                block_mmr.prove_membership_async(height).await,
+            ));
+        }
+
+        Ok(HeaderChainProofOutline {
+            tip: ProvenHeader::from_block(&tip),
+            tip_parent_digest: (tip_height > 0).then_some(tip.kernel.header.prev_block_digest),
+            block_mmr_accumulator,
+            block_mmr_accumulator_path: tip
+                .kernel
+                .body
+                .mast_path(BlockBodyField::BlockMmrAccumulator),
+            samples,
+        })
+    }
+
+    /// The header of a stored block, with the MAST hash of its body
+    pub async fn proven_header(&self, block_digest: Digest) -> Result<ProvenHeader> {
+        self.get_block(block_digest)
+            .await?
+            .map(|block| ProvenHeader::from_block(&block))
+            .with_context(|| format!("Block {block_digest} is not stored"))
+    }
+
+    /// Make `block_mmr` the MMR of the digests of the canonical blocks before
+    /// `tip`. Walks back from the tip only until its chain meets the leaves
+    /// of `block_mmr`, and replaces the leaves above that point. Leaves
+    /// `block_mmr` untouched if an ancestor of the tip is not stored.
+    async fn sync_block_mmr(
+        &self,
+        block_mmr: &mut ArchivalMmr<Hash, OrdinaryVec<Digest>>,
+        tip: &Block,
+    ) -> Result<()> {
+        // Digests of the blocks above the meeting point, from the top down
+        let mut new_digests = vec![];
+        let mut kept_leaf_count = 0;
+        let mut digest = tip.kernel.header.prev_block_digest;
+        for height in (0..u64::from(tip.kernel.header.height)).rev() {
+            if height < block_mmr.count_leaves().await
+                && block_mmr.get_leaf_async(height).await == digest
+            {
+                kept_leaf_count = height + 1;
+                break;
+            }
+
+            new_digests.push(digest);
+            if height > 0 {
+                digest = self
+                    .get_block_header(digest)
+                    .await
+                    .with_context(|| format!("Ancestor {digest} of the tip is not stored"))?
+                    .prev_block_digest;
+            }
+        }
+
+        while block_mmr.count_leaves().await > kept_leaf_count {
+            block_mmr.remove_last_leaf_async().await;
+        }
+        for &digest in new_digests.iter().rev() {
+            block_mmr.append(digest).await;
+        }
+
+        Ok(())
+    }
+
     /// Return the number of blocks with the given height
     async fn block_height_to_block_count(&self, height: BlockHeight) -> usize {
         match self
@@ -602,6 +1146,59 @@ impl ArchivalState {
         downstream_children
     }
 
+    /// Return the digests of the known blocks with a timestamp in the range
+    /// `from..=to`, ordered by timestamp. Only the time buckets overlapping
+    /// the range are read, not every header. The range is first clamped to
+    /// the timestamps that stored blocks can have: none is before the genesis
+    /// block, and none is later than the tip or the futuredating limit ahead
+    /// of the local clock.
+    pub async fn get_blocks_in_time_range(&self, from: Timestamp, to: Timestamp) -> Vec<Digest> {
+        let genesis_header = &self.genesis_block.kernel.header;
+        let tip_timestamp = match self.block_index_db.get(BlockIndexKey::BlockTipDigest).await {
+            Some(digest) => self
+                .get_block_header(digest.as_tip_digest())
+                .await
+                .map(|header| header.timestamp),
+            None => None,
+        };
+        let latest = tip_timestamp
+            .unwrap_or(genesis_header.timestamp)
+            .max(Timestamp::now() + Timestamp::hours(FUTUREDATING_LIMIT_IN_HOURS));
+        let from = from.max(genesis_header.timestamp);
+        let to = to.min(latest);
+        if from > to {
+            return vec![];
+        }
+
+        let mut blocks = vec![];
+        if (from..=to).contains(&genesis_header.timestamp) {
+            blocks.push((genesis_header.timestamp, self.genesis_block.hash()));
+        }
+
+        for bucket in BlockIndexKey::time_bucket(from)..=BlockIndexKey::time_bucket(to) {
+            let Some(digests) = self
+                .block_index_db
+                .get(BlockIndexKey::TimeBucket(bucket))
+                .await
+                .map(|x| x.as_time_bucket_record())
+            else {
+                continue;
+            };
+            for digest in digests {
+                let Some(header) = self.get_block_header(digest).await else {
+                    warn!("Skipping block {digest} of time bucket {bucket}, which has no header");
+                    continue;
+                };
+                if (from..=to).contains(&header.timestamp) {
+                    blocks.push((header.timestamp, digest));
+                }
+            }
+        }
+
+        blocks.sort_by_key(|(timestamp, _)| *timestamp);
+        blocks.into_iter().map(|(_, digest)| digest).collect()
+    }
+
     /// Return a boolean indicating if block belongs to most canonical chain
     pub async fn block_belongs_to_canonical_chain(
         &self,
@@ -649,6 +1246,148 @@ impl ArchivalState {
         backwards.is_empty()
     }
 
+    /// Record that the proofs of a block verified, so that they need not be
+    /// verified again, also after a restart. Takes the block's
+    /// [`proofs_digest`](Block::proofs_digest). Blocks received without their
+    /// proofs must not be marked. Does nothing if the archival state is
+    /// read-only, as the mark is only an optimization.
+    pub async fn mark_block_validated(&mut self, proofs_digest: Digest) {
+        if self.is_read_only() {
+            return;
+        }
+
+        self.block_index_db
+            .put(
+                BlockIndexKey::ValidatedBlock(proofs_digest),
+                BlockIndexValue::ValidatedBlock,
+            )
+            .await;
+    }
+
+    /// Whether the proofs of a block were verified before
+    pub async fn is_block_validated(&self, proofs_digest: Digest) -> bool {
+        self.block_index_db
+            .get(BlockIndexKey::ValidatedBlock(proofs_digest))
+            .await
+            .is_some()
+    }
+
+    /// The last block marked final, if any. See [`finality`](super::finality).
+    pub async fn finalized_block(&self) -> Option<FinalizedBlock> {
+        let digest = self
+            .block_index_db
+            .get(BlockIndexKey::FinalizedBlockDigest)
+            .await?
+            .as_finalized_block_digest();
+        let header = self.get_block_header(digest).await?;
+
+        Some(FinalizedBlock {
+            digest,
+            height: header.height,
+        })
+    }
+
+    /// Mark a block of the canonical chain, and thereby its ancestors, final.
+    /// Returns the last finalized block, which is unchanged if it is at or
+    /// above the block.
+    pub async fn mark_block_final(
+        &mut self,
+        block_digest: Digest,
+        tip_digest: Digest,
+    ) -> Result<FinalizedBlock, FinalityError> {
+        if self.is_read_only() {
+            return Err(FinalityError::ReadOnly);
+        }
+
+        let header = self
+            .get_block_header(block_digest)
+            .await
+            .ok_or(FinalityError::UnknownBlock(block_digest))?;
+        if !self
+            .block_belongs_to_canonical_chain(block_digest, tip_digest)
+            .await
+        {
+            return Err(FinalityError::NotCanonical(block_digest));
+        }
+        if let Some(finalized) = self.finalized_block().await {
+            if finalized.height >= header.height {
+                return Ok(finalized);
+            }
+        }
+
+        self.block_index_db
+            .put(
+                BlockIndexKey::FinalizedBlockDigest,
+                BlockIndexValue::FinalizedBlockDigest(block_digest),
+            )
+            .await;
+        debug!("Block {block_digest} at height {} is final", header.height);
+
+        Ok(FinalizedBlock {
+            digest: block_digest,
+            height: header.height,
+        })
+    }
+
+    /// Mark the canonical block `depth` blocks below the tip final
+    pub async fn apply_finality_depth(
+        &mut self,
+        depth: u64,
+        tip_digest: Digest,
+        tip_height: BlockHeight,
+    ) -> Result<(), FinalityError> {
+        let Some(final_height) = height_final_at_depth(tip_height, depth) else {
+            return Ok(());
+        };
+        let Some(final_digest) = self
+            .block_height_to_canonical_block_digest(final_height, tip_digest)
+            .await
+        else {
+            return Ok(());
+        };
+
+        self.mark_block_final(final_digest, tip_digest)
+            .await
+            .map(|_| ())
+    }
+
+    /// Whether a block is final: in the canonical chain, and at or below the
+    /// last finalized block
+    pub async fn is_block_final(&self, block_digest: Digest, tip_digest: Digest) -> bool {
+        let Some(finalized) = self.finalized_block().await else {
+            return block_digest == self.genesis_block.hash();
+        };
+        let Some(header) = self.get_block_header(block_digest).await else {
+            return false;
+        };
+
+        header.height <= finalized.height
+            && self
+                .block_belongs_to_canonical_chain(block_digest, tip_digest)
+                .await
+    }
+
+    /// Whether a chain continuing from `new_parent` would roll back the last
+    /// finalized block, i.e. does not contain it
+    pub async fn reverts_finalized_block(&self, new_parent: Digest, tip_digest: Digest) -> bool {
+        // The canonical chain always contains the finalized block
+        if new_parent == tip_digest {
+            return false;
+        }
+        let Some(finalized) = self.finalized_block().await else {
+            return false;
+        };
+        let Some(parent_header) = self.get_block_header(new_parent).await else {
+            return false;
+        };
+        if parent_header.height < finalized.height {
+            return true;
+        }
+
+        let (backwards, _, _) = self.find_path(finalized.digest, new_parent).await;
+        !backwards.is_empty()
+    }
+
     /// Return a list of digests of the ancestors to the requested digest. Does not include the input
     /// digest. If no ancestors can be found, returns the empty list. The count is the maximum length
     /// of the returned list. E.g. if the input digest corresponds to height 2 and count is 5, the
@@ -675,11 +1414,106 @@ impl ArchivalState {
         ret
     }
 
+    /// Audit the coin supply of the chain that the mutator set is synced to.
+    ///
+    /// Walks all blocks from genesis to the mutator set's sync label, summing
+    /// the premine, the coinbases and the fees, and checking each coinbase
+    /// against the reward schedule. At the same time, the addition records
+    /// of the blocks are compared, in order, against the leafs of the
+    /// append-only commitment list (AOCL).
+    pub async fn audit_supply(&self) -> Result<SupplyAudit> {
+        let tip_digest = self.archival_mutator_set.get_sync_label().await;
+        let tip_height = self
+            .get_block_header(tip_digest)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Unknown mutator set sync label {tip_digest}"))?
+            .height;
+
+        let ams = self.archival_mutator_set.ams();
+        let aocl_leafs = ams.stream_aocl_leafs().await;
+        pin_mut!(aocl_leafs);
+
+        let mut audit = SupplyAudit {
+            tip_digest,
+            tip_height,
+            premine: NeptuneCoins::zero(),
+            total_coinbase: NeptuneCoins::zero(),
+            total_fees: NeptuneCoins::zero(),
+            scheduled_rewards: NeptuneCoins::zero(),
+            blocks_exceeding_schedule: vec![],
+            aocl_leaf_count: ams.aocl.count_leaves().await,
+            output_count: 0,
+            first_aocl_mismatch: None,
+        };
+
+        // Blocks are summarized in parallel, and the summaries folded in order
+        // of height
+        let summaries = Self::map_blocks(
+            self.iter_chain_blocks(tip_digest, BlockHeight::genesis()..=tip_height),
+            |block| {
+                let height = block.kernel.header.height;
+                let kernel = block.kernel.body.transaction.kernel;
+                let commitments = kernel
+                    .outputs
+                    .iter()
+                    .map(|addition_record| addition_record.canonical_commitment)
+                    .collect_vec();
+
+                (
+                    height,
+                    kernel.coinbase.unwrap_or_else(NeptuneCoins::zero),
+                    kernel.fee,
+                    commitments,
+                )
+            },
+        );
+        pin_mut!(summaries);
+        while let Some(summary) = summaries.next().await {
+            let (height, coinbase, fee, commitments) = summary?;
+
+            if height.is_genesis() {
+                audit.premine = coinbase;
+            } else {
+                let reward = Block::get_mining_reward(height);
+                audit.total_coinbase = audit.total_coinbase + coinbase;
+                audit.total_fees = audit.total_fees + fee;
+                audit.scheduled_rewards = audit.scheduled_rewards + reward;
+                if coinbase > reward + fee {
+                    audit.blocks_exceeding_schedule.push(height);
+                }
+            }
+
+            for commitment in commitments {
+                let output_index = audit.output_count;
+                audit.output_count += 1;
+                if audit.first_aocl_mismatch.is_some() {
+                    continue;
+                }
+
+                let aocl_leaf = aocl_leafs.next().await;
+                if aocl_leaf != Some((output_index, commitment)) {
+                    audit.first_aocl_mismatch = Some(output_index);
+                }
+            }
+        }
+
+        Ok(audit)
+    }
+
     /// Update the mutator set with a block after this block has been stored to the database.
     /// Handles rollback of the mutator set if needed but requires that all blocks that are
     /// rolled back are present in the DB. The input block is considered chain tip. All blocks
     /// stored in the database are assumed to be valid.
+    ///
+    /// No undo log of the mutator set is kept: a rollback reverts the additions
+    /// and removals of the stored blocks themselves, so there are no per-block
+    /// diffs to prune, and pruning blocks would break rollbacks past them.
     pub async fn update_mutator_set(&mut self, new_block: &Block) -> Result<()> {
+        self.check_writable()?;
+
+        #[cfg(test)]
+        self.fault_injector.before_write(WritePoint::MutatorSet)?;
+
         let (forwards, backwards) = {
             // Get the block digest that the mutator set was most recently synced to
             let ms_block_sync_digest = self.archival_mutator_set.get_sync_label().await;
@@ -717,35 +1551,16 @@ impl ArchivalState {
             );
 
             // Roll back all addition records contained in block
-            for addition_record in roll_back_block
-                .kernel
-                .body
-                .transaction
-                .kernel
-                .outputs
-                .iter()
-                .rev()
-            {
-                assert!(
-                    self.archival_mutator_set
-                        .ams_mut()
-                        .add_is_reversible(addition_record)
-                        .await,
-                    "Addition record must be in sync with block being rolled back."
-                );
-                self.archival_mutator_set
-                    .ams_mut()
-                    .revert_add(addition_record)
-                    .await;
-            }
+            self.archival_mutator_set
+                .ams_mut()
+                .batch_revert_add(&roll_back_block.kernel.body.transaction.kernel.outputs)
+                .await;
 
             // Roll back all removal records contained in block
-            for removal_record in roll_back_block.kernel.body.transaction.kernel.inputs.iter() {
-                self.archival_mutator_set
-                    .ams_mut()
-                    .revert_remove(removal_record)
-                    .await;
-            }
+            self.archival_mutator_set
+                .ams_mut()
+                .batch_revert_remove(&roll_back_block.kernel.body.transaction.kernel.inputs)
+                .await;
         }
 
         for digest in forwards {
@@ -842,6 +1657,7 @@ mod archival_state_tests {
     use super::*;
 
     use crate::config_models::network::Network;
+    use crate::database::fault_injection::WritePoint;
     use crate::database::storage::storage_vec::traits::*;
     use crate::models::blockchain::transaction::utxo::LockScript;
     use crate::models::blockchain::transaction::utxo::Utxo;
@@ -862,6 +1678,7 @@ mod archival_state_tests {
     use rand::SeedableRng;
     use rand::{random, thread_rng, RngCore};
     use tracing_test::traced_test;
+    use twenty_first::math::b_field_element::BFieldElement;
 
     async fn make_test_archival_state(network: Network) -> ArchivalState {
         let (block_index_db, _peer_db_lock, data_dir) = unit_test_databases(network).await.unwrap();
@@ -873,6 +1690,114 @@ mod archival_state_tests {
         ArchivalState::new(data_dir, block_index_db, ams, network).await
     }
 
+    /// Open the archival state in `data_dir` again, as on a restart, and run
+    /// the startup recovery
+    async fn restart_archival_state(data_dir: &DataDirectory, network: Network) -> ArchivalState {
+        let block_index_db = ArchivalState::initialize_block_index_database(data_dir)
+            .await
+            .unwrap();
+        let ams = ArchivalState::initialize_mutator_set(data_dir)
+            .await
+            .unwrap();
+        let mut archival_state =
+            ArchivalState::new(data_dir.clone(), block_index_db, ams, network).await;
+        archival_state
+            .recover_interrupted_tip_update()
+            .await
+            .unwrap();
+
+        archival_state
+    }
+
+    /// Assert that the mutator set is synced to the tip
+    async fn assert_consistent(archival_state: &ArchivalState) {
+        let tip = archival_state.get_tip().await;
+        assert_eq!(
+            tip.hash(),
+            archival_state.archival_mutator_set.get_sync_label().await
+        );
+        assert_eq!(
+            tip.kernel.body.mutator_set_accumulator.hash(),
+            archival_state.archival_mutator_set.ams().hash().await
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn validated_block_markers_survive_restart() {
+        let network = Network::RegTest;
+        let (mut archival_state, _peer_db_lock, data_dir) =
+            mock_genesis_archival_state(network).await;
+        let validated: Digest = random();
+        let unvalidated: Digest = random();
+
+        archival_state.mark_block_validated(validated).await;
+        assert!(archival_state.is_block_validated(validated).await);
+        assert!(!archival_state.is_block_validated(unvalidated).await);
+        drop(archival_state);
+
+        let archival_state = restart_archival_state(&data_dir, network).await;
+        assert!(archival_state.is_block_validated(validated).await);
+        assert!(!archival_state.is_block_validated(unvalidated).await);
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn recover_from_power_cut_between_block_write_and_mutator_set_update() {
+        let network = Network::RegTest;
+        let (mut archival_state, _peer_db_lock, data_dir) =
+            mock_genesis_archival_state(network).await;
+        let genesis = archival_state.genesis_block().clone();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, random());
+
+        archival_state
+            .fault_injector
+            .cut_power_before(WritePoint::MutatorSet);
+        archival_state.write_block_as_tip(&block_1).await.unwrap();
+        assert!(archival_state.update_mutator_set(&block_1).await.is_err());
+        drop(archival_state);
+
+        let archival_state = restart_archival_state(&data_dir, network).await;
+        assert_eq!(block_1.hash(), archival_state.get_tip().await.hash());
+        assert_consistent(&archival_state).await;
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn recover_from_failure_of_any_write() {
+        let network = Network::RegTest;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        // Storing two blocks makes six writes
+        for n in 1..=6 {
+            let (mut archival_state, _peer_db_lock, data_dir) =
+                mock_genesis_archival_state(network).await;
+            let genesis = archival_state.genesis_block().clone();
+            let (block_1, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, random());
+            let (block_2, _, _) = make_mock_block_with_valid_pow(&block_1, None, address, random());
+
+            archival_state.fault_injector.fail_nth_write(n);
+            for block in [&block_1, &block_2] {
+                let stored = match archival_state.write_block_as_tip(block).await {
+                    Ok(()) => archival_state.update_mutator_set(block).await,
+                    Err(err) => Err(err),
+                };
+                if stored.is_err() {
+                    break;
+                }
+            }
+            drop(archival_state);
+
+            let archival_state = restart_archival_state(&data_dir, network).await;
+            assert_consistent(&archival_state).await;
+        }
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn initialize_archival_state_test() -> Result<()> {
@@ -1106,6 +2031,110 @@ mod archival_state_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn read_only_snapshot_is_unaffected_by_later_blocks() -> Result<()> {
+        let network = Network::Alpha;
+        let (mut archival_state, _peer_db_lock, _data_dir) =
+            mock_genesis_archival_state(network).await;
+        let own_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        let snapshot = archival_state.read_only_snapshot().await;
+        assert!(snapshot.is_read_only());
+
+        let (block_1, _, _) = make_mock_block_with_valid_pow(
+            &archival_state.genesis_block,
+            None,
+            own_receiving_address,
+            random(),
+        );
+        add_block_to_archival_state(&mut archival_state, block_1.clone()).await?;
+        assert_eq!(block_1.hash(), archival_state.get_tip().await.hash());
+
+        let genesis_digest = archival_state.genesis_block.hash();
+        assert_eq!(genesis_digest, snapshot.get_tip().await.hash());
+        assert_eq!(
+            genesis_digest,
+            snapshot.archival_mutator_set.get_sync_label().await
+        );
+        let audit = snapshot.audit_supply().await?;
+        assert!(audit.is_consistent());
+        assert!(audit.tip_height.is_genesis());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn audit_supply_follows_mutator_set_through_rollback_test() -> Result<()> {
+        let mut rng = thread_rng();
+        let network = Network::Alpha;
+        let (mut archival_state, _peer_db_lock, _data_dir) =
+            mock_genesis_archival_state(network).await;
+        let own_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        let genesis_audit = archival_state.audit_supply().await?;
+        let genesis_block = archival_state.genesis_block();
+        assert!(genesis_audit.is_consistent());
+        assert!(genesis_audit.tip_height.is_genesis());
+        assert_eq!(
+            genesis_block.kernel.body.transaction.kernel.coinbase,
+            Some(genesis_audit.premine)
+        );
+        assert_eq!(
+            genesis_block.kernel.body.transaction.kernel.outputs.len() as u64,
+            genesis_audit.aocl_leaf_count
+        );
+        assert!(genesis_audit.total_coinbase.is_zero());
+        assert_eq!(Some(genesis_audit.premine), genesis_audit.emitted_supply());
+
+        // Fees exceeding the coinbases are reported, not a panic
+        let mut inconsistent_audit = genesis_audit.clone();
+        inconsistent_audit.total_fees = genesis_audit
+            .premine
+            .safe_add(NeptuneCoins::new(1))
+            .unwrap();
+        assert!(inconsistent_audit.emitted_supply().is_none());
+
+        let (mock_block_1a, _, _) = make_mock_block_with_valid_pow(
+            &archival_state.genesis_block,
+            None,
+            own_receiving_address,
+            rng.gen(),
+        );
+        archival_state.write_block_as_tip(&mock_block_1a).await?;
+        archival_state.update_mutator_set(&mock_block_1a).await?;
+
+        let (mock_block_1b, _, _) = make_mock_block_with_valid_pow(
+            &archival_state.genesis_block,
+            None,
+            own_receiving_address,
+            rng.gen(),
+        );
+        archival_state.write_block_as_tip(&mock_block_1b).await?;
+        archival_state.update_mutator_set(&mock_block_1b).await?;
+
+        let audit = archival_state.audit_supply().await?;
+        assert!(audit.is_consistent());
+        assert_eq!(mock_block_1b.hash(), audit.tip_digest);
+        assert_eq!(genesis_audit.premine, audit.premine);
+        assert_eq!(
+            Block::get_mining_reward(1u64.into()),
+            audit.scheduled_rewards
+        );
+        assert_eq!(
+            genesis_audit.output_count
+                + mock_block_1b.kernel.body.transaction.kernel.outputs.len() as u64,
+            audit.aocl_leaf_count
+        );
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn update_mutator_set_rollback_ms_block_sync_multiple_inputs_outputs_in_block_test() {
@@ -1569,7 +2598,7 @@ mod archival_state_tests {
                 .add_expected_utxo(
                     cb_utxo,
                     cb_output_randomness,
-                    genesis_spending_key.privacy_preimage,
+                    genesis_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::OwnMiner,
                 )
                 .unwrap();
@@ -1583,7 +2612,7 @@ mod archival_state_tests {
                     .add_expected_utxo(
                         rec_data.utxo.clone(),
                         rec_data.sender_randomness,
-                        alice_spending_key.privacy_preimage,
+                        alice_spending_key.privacy_preimage.expose(),
                         UtxoNotifier::Cli,
                     )
                     .unwrap();
@@ -1599,7 +2628,7 @@ mod archival_state_tests {
                     .add_expected_utxo(
                         rec_data.utxo.clone(),
                         rec_data.sender_randomness,
-                        bob_spending_key.privacy_preimage,
+                        bob_spending_key.privacy_preimage.expose(),
                         UtxoNotifier::Cli,
                     )
                     .unwrap();
@@ -1751,7 +2780,7 @@ mod archival_state_tests {
                 .add_expected_utxo(
                     rec_data.utxo.clone(),
                     rec_data.sender_randomness,
-                    genesis_spending_key.privacy_preimage,
+                    genesis_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::Cli,
                 )
                 .unwrap();
@@ -1766,7 +2795,7 @@ mod archival_state_tests {
                 .add_expected_utxo(
                     rec_data.utxo.clone(),
                     rec_data.sender_randomness,
-                    genesis_spending_key.privacy_preimage,
+                    genesis_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::Cli,
                 )
                 .unwrap();
@@ -1780,7 +2809,7 @@ mod archival_state_tests {
             .add_expected_utxo(
                 cb_utxo_block_2,
                 cb_sender_randomness_block_2,
-                genesis_spending_key.privacy_preimage,
+                genesis_spending_key.privacy_preimage.expose(),
                 UtxoNotifier::Cli,
             )
             .unwrap();
@@ -1997,6 +3026,76 @@ mod archival_state_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn finalized_blocks_cannot_be_reorganized_away_test() -> Result<()> {
+        let mut rng = thread_rng();
+        let network = Network::Alpha;
+        let mut archival_state = make_test_archival_state(network).await;
+        let genesis = *archival_state.genesis_block.clone();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        // genesis <- 1a <- 2a is canonical, genesis <- 1b is a fork
+        let (block_1a, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, rng.gen());
+        let (block_1b, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, rng.gen());
+        let (block_2a, _, _) = make_mock_block_with_valid_pow(&block_1a, None, address, rng.gen());
+        for block in [&block_1a, &block_1b, &block_2a] {
+            add_block_to_archival_state(&mut archival_state, block.clone()).await?;
+        }
+        let tip = block_2a.hash();
+
+        assert!(archival_state.finalized_block().await.is_none());
+        assert!(archival_state.is_block_final(genesis.hash(), tip).await);
+        assert!(!archival_state.is_block_final(block_1a.hash(), tip).await);
+        assert!(
+            !archival_state
+                .reverts_finalized_block(block_1b.hash(), tip)
+                .await
+        );
+
+        assert_eq!(
+            Err(FinalityError::NotCanonical(block_1b.hash())),
+            archival_state.mark_block_final(block_1b.hash(), tip).await
+        );
+        let finalized = archival_state
+            .mark_block_final(block_1a.hash(), tip)
+            .await?;
+        assert_eq!(block_1a.hash(), finalized.digest);
+        assert_eq!(Some(finalized), archival_state.finalized_block().await);
+
+        // Finality does not move backwards
+        assert_eq!(
+            Ok(finalized),
+            archival_state.mark_block_final(genesis.hash(), tip).await
+        );
+
+        assert!(archival_state.is_block_final(genesis.hash(), tip).await);
+        assert!(archival_state.is_block_final(block_1a.hash(), tip).await);
+        assert!(!archival_state.is_block_final(block_1b.hash(), tip).await);
+        assert!(!archival_state.is_block_final(block_2a.hash(), tip).await);
+
+        assert!(
+            archival_state
+                .reverts_finalized_block(genesis.hash(), tip)
+                .await
+        );
+        assert!(
+            archival_state
+                .reverts_finalized_block(block_1b.hash(), tip)
+                .await
+        );
+        assert!(
+            !archival_state
+                .reverts_finalized_block(block_1a.hash(), tip)
+                .await
+        );
+        assert!(!archival_state.reverts_finalized_block(tip, tip).await);
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn find_path_simple_test() -> Result<()> {
@@ -2716,6 +3815,209 @@ mod archival_state_tests {
             .is_empty());
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn blocks_in_time_range_test() {
+        let mut rng = thread_rng();
+        let mut archival_state = make_test_archival_state(Network::Alpha).await;
+        let genesis = *archival_state.genesis_block.clone();
+        let own_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        // Blocks an hour and a half apart, so that each lands in its own time
+        // bucket
+        let mut blocks = vec![genesis.clone()];
+        for i in 1..=3 {
+            let timestamp = genesis.kernel.header.timestamp + Timestamp::minutes(90 * i);
+            let (block, _, _) = make_mock_block_with_valid_pow(
+                blocks.last().unwrap(),
+                Some(timestamp),
+                own_receiving_address,
+                rng.gen(),
+            );
+            add_block_to_archival_state(&mut archival_state, block.clone())
+                .await
+                .unwrap();
+            blocks.push(block);
+        }
+        let timestamp = |i: usize| blocks[i].kernel.header.timestamp;
+        let digests = |range: std::ops::RangeInclusive<usize>| {
+            blocks[range].iter().map(|block| block.hash()).collect_vec()
+        };
+
+        assert_eq!(
+            digests(0..=3),
+            archival_state
+                .get_blocks_in_time_range(timestamp(0), timestamp(3))
+                .await
+        );
+        assert_eq!(
+            digests(1..=2),
+            archival_state
+                .get_blocks_in_time_range(timestamp(1), timestamp(2))
+                .await
+        );
+        assert_eq!(
+            digests(2..=2),
+            archival_state
+                .get_blocks_in_time_range(
+                    timestamp(1) + Timestamp::millis(1),
+                    timestamp(3) - Timestamp::millis(1)
+                )
+                .await
+        );
+        assert!(archival_state
+            .get_blocks_in_time_range(timestamp(3), timestamp(0))
+            .await
+            .is_empty());
+
+        // Ranges reaching beyond any stored block are clamped, rather than
+        // read bucket by bucket
+        assert_eq!(
+            digests(0..=3),
+            archival_state
+                .get_blocks_in_time_range(Timestamp::zero(), Timestamp::millis(BFieldElement::MAX))
+                .await
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn iter_canonical_blocks_test() {
+        let mut rng = thread_rng();
+        let mut archival_state = make_test_archival_state(Network::Alpha).await;
+        let genesis = *archival_state.genesis_block.clone();
+        let own_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        let mut canonical = vec![genesis.clone()];
+        for _ in 1..=2 {
+            let (block, _, _) = make_mock_block_with_valid_pow(
+                canonical.last().unwrap(),
+                None,
+                own_receiving_address,
+                rng.gen(),
+            );
+            add_block_to_archival_state(&mut archival_state, block.clone())
+                .await
+                .unwrap();
+            canonical.push(block);
+        }
+
+        // A block of a fork, stored between canonical blocks, must be skipped
+        let (fork_block, _, _) =
+            make_mock_block_with_valid_pow(&canonical[1], None, own_receiving_address, rng.gen());
+        add_block_to_archival_state(&mut archival_state, fork_block)
+            .await
+            .unwrap();
+        let (block_3, _, _) =
+            make_mock_block_with_valid_pow(&canonical[2], None, own_receiving_address, rng.gen());
+        add_block_to_archival_state(&mut archival_state, block_3.clone())
+            .await
+            .unwrap();
+        canonical.push(block_3);
+
+        let digests = |from: u64, to: u64| {
+            let blocks = archival_state.iter_canonical_blocks(from.into()..=to.into());
+            async move {
+                pin_mut!(blocks);
+                let mut digests = vec![];
+                while let Some(block) = blocks.next().await {
+                    digests.push(block.unwrap().hash());
+                }
+                digests
+            }
+        };
+        let expected = |range: std::ops::RangeInclusive<usize>| {
+            canonical[range]
+                .iter()
+                .map(|block| block.hash())
+                .collect_vec()
+        };
+
+        assert_eq!(expected(0..=3), digests(0, 3).await);
+        assert_eq!(expected(2..=3), digests(2, 10).await);
+        assert_eq!(expected(0..=1), digests(0, 1).await);
+        assert!(digests(4, 10).await.is_empty());
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn scan_chain_yields_results_in_order_of_height() {
+        let mut rng = thread_rng();
+        let mut archival_state = make_test_archival_state(Network::Alpha).await;
+        let own_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        let mut tip = *archival_state.genesis_block.clone();
+        for _ in 1..=5 {
+            let (block, _, _) =
+                make_mock_block_with_valid_pow(&tip, None, own_receiving_address, rng.gen());
+            add_block_to_archival_state(&mut archival_state, block.clone())
+                .await
+                .unwrap();
+            tip = block;
+        }
+
+        // Lower blocks take longer to map, so they finish last
+        let heights = archival_state.scan_chain(1u64.into()..=5u64.into(), |block| {
+            let height = block.kernel.header.height;
+            std::thread::sleep(std::time::Duration::from_millis(
+                50 * (6 - u64::from(height)),
+            ));
+            height
+        });
+        pin_mut!(heights);
+        let mut scanned = vec![];
+        while let Some(height) = heights.next().await {
+            scanned.push(u64::from(height.unwrap()));
+        }
+
+        assert_eq!(vec![1, 2, 3, 4, 5], scanned);
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn get_blocks_keeps_the_order_of_the_digests() {
+        let mut rng = thread_rng();
+        let mut archival_state = make_test_archival_state(Network::Alpha).await;
+        let genesis = *archival_state.genesis_block.clone();
+        let own_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        let mut blocks = vec![genesis];
+        for _ in 1..=6 {
+            let (block, _, _) = make_mock_block_with_valid_pow(
+                blocks.last().unwrap(),
+                None,
+                own_receiving_address,
+                rng.gen(),
+            );
+            add_block_to_archival_state(&mut archival_state, block.clone())
+                .await
+                .unwrap();
+            blocks.push(block);
+        }
+
+        let unknown_digest: Digest = rng.gen();
+        let mut digests = blocks.iter().rev().map(|block| block.hash()).collect_vec();
+        digests.insert(3, unknown_digest);
+        let mut expected = digests.iter().map(|digest| Some(*digest)).collect_vec();
+        expected[3] = None;
+
+        let decoded = archival_state.get_blocks(digests);
+        pin_mut!(decoded);
+        let mut decoded_digests = vec![];
+        while let Some(block) = decoded.next().await {
+            decoded_digests.push(block.unwrap().map(|block| block.hash()));
+        }
+        assert_eq!(expected, decoded_digests);
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn write_block_db_test() -> Result<()> {
@@ -0,0 +1,231 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+/// Number of recently announced blocks whose propagation is kept in memory
+pub const BLOCK_PROPAGATION_HISTORY_SIZE: usize = 100;
+
+/// Max number of peers statistics are kept for. Tracking more forgets the peer
+/// with the fewest announcements.
+pub const MAX_PROPAGATION_TRACKED_PEERS: usize = 1000;
+
+/// How a single block reached this node
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockPropagationRecord {
+    #[serde(with = "digest_serde")]
+    pub digest: Digest,
+    pub height: BlockHeight,
+
+    /// When the block was first announced, and by whom
+    pub first_announced: Timestamp,
+    pub first_announcer: SocketAddr,
+
+    /// All peers that announced the block, first announcer first
+    pub announcers: Vec<SocketAddr>,
+
+    /// Time from the first announcement until the block was validated, if it was
+    pub validated_after_millis: Option<u64>,
+}
+
+/// How quickly a peer announces new blocks, compared to the other peers
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerPropagationStats {
+    pub peer: SocketAddr,
+    pub announcements: u64,
+
+    /// Number of blocks this peer was the first to announce
+    pub first_announcements: u64,
+
+    /// Sum of the delays behind the first announcer, over the announcements
+    /// this peer was not first with
+    pub total_delay_millis: u64,
+}
+
+impl PeerPropagationStats {
+    fn new(peer: SocketAddr) -> Self {
+        Self {
+            peer,
+            announcements: 0,
+            first_announcements: 0,
+            total_delay_millis: 0,
+        }
+    }
+
+    /// Mean delay behind the first announcer, over the announcements this
+    /// peer was not first with
+    pub fn mean_delay_millis(&self) -> Option<u64> {
+        let late_announcements = self.announcements - self.first_announcements;
+        (late_announcements > 0).then(|| self.total_delay_millis / late_announcements)
+    }
+}
+
+/// Recent block propagation and per-peer statistics, as returned over RPC
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockPropagationReport {
+    /// Newest first
+    pub blocks: Vec<BlockPropagationRecord>,
+
+    /// Most first announcements first
+    pub peers: Vec<PeerPropagationStats>,
+}
+
+/// Tracks when blocks are announced by peers and when they finish validation.
+#[derive(Debug, Default)]
+pub struct BlockPropagation {
+    records: VecDeque<BlockPropagationRecord>,
+    peers: HashMap<SocketAddr, PeerPropagationStats>,
+}
+
+impl BlockPropagation {
+    /// Record that a peer announced a block, by notification or by sending it.
+    /// Repeated announcements of the same block by the same peer are ignored.
+    pub fn record_announcement(
+        &mut self,
+        digest: Digest,
+        height: BlockHeight,
+        peer: SocketAddr,
+        now: Timestamp,
+    ) {
+        let delay_millis = match self.records.iter_mut().rfind(|r| r.digest == digest) {
+            Some(record) => {
+                if record.announcers.contains(&peer) {
+                    return;
+                }
+                record.announcers.push(peer);
+                Some(millis_between(record.first_announced, now))
+            }
+            None => {
+                if self.records.len() == BLOCK_PROPAGATION_HISTORY_SIZE {
+                    self.records.pop_front();
+                }
+                self.records.push_back(BlockPropagationRecord {
+                    digest,
+                    height,
+                    first_announced: now,
+                    first_announcer: peer,
+                    announcers: vec![peer],
+                    validated_after_millis: None,
+                });
+                None
+            }
+        };
+
+        if !self.peers.contains_key(&peer) && self.peers.len() >= MAX_PROPAGATION_TRACKED_PEERS {
+            let least_active = self
+                .peers
+                .values()
+                .min_by_key(|stats| stats.announcements)
+                .map(|stats| stats.peer)
+                .unwrap();
+            self.peers.remove(&least_active);
+        }
+        let stats = self
+            .peers
+            .entry(peer)
+            .or_insert_with(|| PeerPropagationStats::new(peer));
+        stats.announcements += 1;
+        match delay_millis {
+            Some(delay_millis) => stats.total_delay_millis += delay_millis,
+            None => stats.first_announcements += 1,
+        }
+    }
+
+    /// Whether announcements of the block are being recorded. Announcements
+    /// of a block that is no longer new to this node are only of interest
+    /// if it is, to time the peers that announce it late.
+    pub fn is_tracked(&self, digest: Digest) -> bool {
+        self.records.iter().any(|r| r.digest == digest)
+    }
+
+    /// Record that a block passed validation. Ignored for blocks that were not
+    /// announced recently, or that were validated before.
+    pub fn record_validation(&mut self, digest: Digest, now: Timestamp) {
+        if let Some(record) = self.records.iter_mut().rfind(|r| r.digest == digest) {
+            if record.validated_after_millis.is_none() {
+                record.validated_after_millis = Some(millis_between(record.first_announced, now));
+            }
+        }
+    }
+
+    /// Return up to `n` blocks, newest first, and the statistics of all peers.
+    pub fn report(&self, n: usize) -> BlockPropagationReport {
+        let mut peers = self.peers.values().cloned().collect::<Vec<_>>();
+        peers.sort_by_key(|stats| std::cmp::Reverse(stats.first_announcements));
+
+        BlockPropagationReport {
+            blocks: self.records.iter().rev().take(n).cloned().collect(),
+            peers,
+        }
+    }
+}
+
+fn millis_between(earlier: Timestamp, later: Timestamp) -> u64 {
+    if later > earlier {
+        (later - earlier).0.value()
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod block_propagation_tests {
+    use super::*;
+
+    #[test]
+    fn first_announcer_and_delays_are_tracked() {
+        let peer_a: SocketAddr = "123.123.123.1:8080".parse().unwrap();
+        let peer_b: SocketAddr = "123.123.123.2:8080".parse().unwrap();
+        let now = Timestamp::now();
+        let mut propagation = BlockPropagation::default();
+
+        let digest = Digest::new([1u64.into(); 5]);
+        let height = BlockHeight::from(1u64);
+        propagation.record_announcement(digest, height, peer_a, now);
+        propagation.record_announcement(digest, height, peer_b, now + Timestamp::millis(200));
+        propagation.record_announcement(digest, height, peer_b, now + Timestamp::millis(900));
+        propagation.record_validation(digest, now + Timestamp::millis(500));
+        propagation.record_validation(digest, now + Timestamp::millis(700));
+
+        assert!(propagation.is_tracked(digest));
+        assert!(!propagation.is_tracked(Digest::new([2u64.into(); 5])));
+
+        let report = propagation.report(10);
+        assert_eq!(1, report.blocks.len());
+        assert_eq!(peer_a, report.blocks[0].first_announcer);
+        assert_eq!(vec![peer_a, peer_b], report.blocks[0].announcers);
+        assert_eq!(Some(500), report.blocks[0].validated_after_millis);
+
+        assert_eq!(peer_a, report.peers[0].peer);
+        assert_eq!(1, report.peers[0].first_announcements);
+        assert_eq!(None, report.peers[0].mean_delay_millis());
+        assert_eq!(1, report.peers[1].announcements);
+        assert_eq!(Some(200), report.peers[1].mean_delay_millis());
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let peer: SocketAddr = "123.123.123.1:8080".parse().unwrap();
+        let now = Timestamp::now();
+        let mut propagation = BlockPropagation::default();
+        for i in 0..(BLOCK_PROPAGATION_HISTORY_SIZE as u64 + 5) {
+            propagation.record_announcement(Digest::new([i.into(); 5]), i.into(), peer, now);
+        }
+
+        let report = propagation.report(usize::MAX);
+        assert_eq!(BLOCK_PROPAGATION_HISTORY_SIZE, report.blocks.len());
+        assert_eq!(
+            BlockHeight::from(BLOCK_PROPAGATION_HISTORY_SIZE as u64 + 4),
+            report.blocks[0].height
+        );
+        assert_eq!(
+            BLOCK_PROPAGATION_HISTORY_SIZE as u64 + 5,
+            report.peers[0].first_announcements
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Throttling of the blocks served to peers.
+//!
+//! An archival node serves the blocks of the whole chain to syncing peers,
+//! which reads from disk and uses uplink. At most `--block-serving-limit` of
+//! blocks is served per [`BLOCK_SERVING_WINDOW`], and the limit is shared
+//! evenly between the peers that request blocks in the window, so that one
+//! peer cannot take it all. Requests beyond a peer's share go unanswered, and
+//! syncing peers turn to other peers or ask again in the next window.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+pub const BLOCK_SERVING_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Default)]
+pub struct BlockServingThrottle {
+    window_start: Option<Instant>,
+
+    /// Bytes served in the current window, by requesting peer
+    served: HashMap<SocketAddr, u64>,
+}
+
+impl BlockServingThrottle {
+    /// Whether `peer` may be served blocks at time `now`, under a limit of
+    /// `limit` bytes per window. Counts the peer as requesting in the window,
+    /// which shrinks the share of the other peers. A zero limit disables the
+    /// throttling.
+    pub fn allows(&mut self, peer: SocketAddr, now: Instant, limit: u64) -> bool {
+        if limit == 0 {
+            return true;
+        }
+        self.start_window_if_elapsed(now);
+
+        let served_by_peer = *self.served.entry(peer).or_default();
+        let served_in_total = self.served.values().sum::<u64>();
+        let fair_share = limit / self.served.len() as u64;
+
+        served_in_total < limit && served_by_peer < fair_share
+    }
+
+    /// Record that `bytes` of blocks were served to `peer` at time `now`
+    pub fn record(&mut self, peer: SocketAddr, bytes: u64, now: Instant) {
+        self.start_window_if_elapsed(now);
+        *self.served.entry(peer).or_default() += bytes;
+    }
+
+    /// Bytes served to each peer in the current window
+    pub fn served_in_window(&self) -> &HashMap<SocketAddr, u64> {
+        &self.served
+    }
+
+    fn start_window_if_elapsed(&mut self, now: Instant) {
+        let elapsed = self.window_start.map_or(true, |start| {
+            now.duration_since(start) >= BLOCK_SERVING_WINDOW
+        });
+        if elapsed {
+            self.window_start = Some(now);
+            self.served.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod block_serving_tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+
+    fn peer(i: u8) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, i)), 9798)
+    }
+
+    #[test]
+    fn peers_share_the_limit_evenly() {
+        let limit = 1000;
+        let start = Instant::now();
+        let mut throttle = BlockServingThrottle::default();
+
+        // A peer alone may use the whole limit
+        assert!(throttle.allows(peer(1), start, limit));
+        throttle.record(peer(1), 600, start);
+        assert!(throttle.allows(peer(1), start, limit));
+
+        // Another peer requesting halves the share of the first
+        assert!(throttle.allows(peer(2), start, limit));
+        assert!(!throttle.allows(peer(1), start, limit));
+        throttle.record(peer(2), 400, start);
+        assert!(!throttle.allows(peer(2), start, limit), "limit is used up");
+
+        // The next window starts afresh
+        let next_window = start + BLOCK_SERVING_WINDOW;
+        assert!(throttle.allows(peer(1), next_window, limit));
+        assert_eq!(1, throttle.served_in_window().len());
+
+        assert!(
+            throttle.allows(peer(1), start, 0),
+            "zero disables the limit"
+        );
+    }
+}
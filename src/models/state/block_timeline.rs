@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+/// Number of block-processing timelines kept in memory
+pub const BLOCK_TIMELINE_HISTORY_SIZE: usize = 100;
+
+/// The names of the stages a block passes through, in order
+pub const STAGE_VALIDATE: &str = "validate";
+pub const STAGE_WRITE_BLOCK: &str = "write_block";
+pub const STAGE_UPDATE_MUTATOR_SET: &str = "update_mutator_set";
+pub const STAGE_UPDATE_WALLET: &str = "update_wallet";
+pub const STAGE_UPDATE_MEMPOOL: &str = "update_mempool";
+pub const STAGE_FLUSH_DATABASES: &str = "flush_databases";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockStageTiming {
+    pub stage: String,
+    pub duration_secs: f64,
+}
+
+/// How long each stage of processing a single block took.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlockProcessingTimeline {
+    #[serde(with = "digest_serde")]
+    pub digest: Digest,
+    pub height: BlockHeight,
+
+    /// When the first stage was recorded
+    pub started: Timestamp,
+
+    pub stages: Vec<BlockStageTiming>,
+}
+
+impl BlockProcessingTimeline {
+    pub fn new(digest: Digest, height: BlockHeight) -> Self {
+        Self {
+            digest,
+            height,
+            started: Timestamp::now(),
+            stages: vec![],
+        }
+    }
+
+    pub fn add_stage(&mut self, stage: &str, duration_secs: f64) {
+        self.stages.push(BlockStageTiming {
+            stage: stage.to_owned(),
+            duration_secs,
+        });
+    }
+
+    pub fn total_duration_secs(&self) -> f64 {
+        self.stages.iter().map(|s| s.duration_secs).sum()
+    }
+}
+
+/// The timelines of the most recently processed blocks, oldest first.
+#[derive(Debug, Default)]
+pub struct BlockTimelines {
+    timelines: VecDeque<BlockProcessingTimeline>,
+}
+
+impl BlockTimelines {
+    /// Return the timeline of the given block, starting a new one if the
+    /// block has not been seen recently.
+    pub fn entry(&mut self, digest: Digest, height: BlockHeight) -> &mut BlockProcessingTimeline {
+        match self.timelines.iter().rposition(|t| t.digest == digest) {
+            Some(index) => &mut self.timelines[index],
+            None => {
+                if self.timelines.len() == BLOCK_TIMELINE_HISTORY_SIZE {
+                    self.timelines.pop_front();
+                }
+                self.timelines
+                    .push_back(BlockProcessingTimeline::new(digest, height));
+                self.timelines.back_mut().unwrap()
+            }
+        }
+    }
+
+    /// Return up to `n` timelines, newest first.
+    pub fn latest(&self, n: usize) -> Vec<BlockProcessingTimeline> {
+        self.timelines.iter().rev().take(n).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod block_timeline_tests {
+    use super::*;
+
+    #[test]
+    fn history_is_bounded_and_stages_accumulate() {
+        let mut timelines = BlockTimelines::default();
+        for i in 0..(BLOCK_TIMELINE_HISTORY_SIZE as u64 + 5) {
+            let digest = Digest::new([i.into(); 5]);
+            timelines
+                .entry(digest, i.into())
+                .add_stage(STAGE_VALIDATE, 1.0);
+            timelines
+                .entry(digest, i.into())
+                .add_stage(STAGE_WRITE_BLOCK, 0.5);
+        }
+
+        let all = timelines.latest(usize::MAX);
+        assert_eq!(BLOCK_TIMELINE_HISTORY_SIZE, all.len());
+        assert_eq!(
+            BlockHeight::from(BLOCK_TIMELINE_HISTORY_SIZE as u64 + 4),
+            all[0].height
+        );
+        assert_eq!(2, all[0].stages.len());
+        assert_eq!(1.5, all[0].total_duration_secs());
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::models::blockchain::block::block_header::PROOF_OF_WORK_COUNT_U32_SIZE;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::prelude::twenty_first::prelude::U32s;
+use crate::util_types::digest_serde;
+
+use super::archival_state::ArchivalState;
+
+/// Max number of heights below the tip that a block tree covers
+pub const MAX_BLOCK_TREE_DEPTH: u64 = 1000;
+
+/// Number of hex characters of a digest shown in node labels
+const DOT_LABEL_DIGEST_LENGTH: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockTreeNode {
+    #[serde(with = "digest_serde")]
+    pub digest: Digest,
+    #[serde(with = "digest_serde")]
+    pub parent: Digest,
+    pub height: BlockHeight,
+
+    /// The accumulated proof-of-work of the chain ending in this block
+    pub proof_of_work_family: U32s<PROOF_OF_WORK_COUNT_U32_SIZE>,
+
+    pub canonical: bool,
+    pub is_tip: bool,
+}
+
+/// All stored blocks from some depth below the tip and up, forks included, for
+/// visualizing forks. Nodes are sorted by height.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockTree {
+    pub nodes: Vec<BlockTreeNode>,
+}
+
+impl BlockTree {
+    /// Collect the blocks from `depth` heights below the tip, at most
+    /// [`MAX_BLOCK_TREE_DEPTH`], up to the highest stored block
+    pub async fn collect(archival_state: &ArchivalState, tip_digest: Digest, depth: u64) -> Self {
+        let Some(tip_header) = archival_state.get_block_header(tip_digest).await else {
+            return Self { nodes: vec![] };
+        };
+        let depth = depth.min(MAX_BLOCK_TREE_DEPTH);
+        let mut height = BlockHeight::from(u64::from(tip_header.height).saturating_sub(depth));
+
+        let mut nodes = vec![];
+        loop {
+            let digests = archival_state.block_height_to_block_digests(height).await;
+            // Forks may be longer than the canonical chain, so continue past
+            // the tip until a height has no blocks
+            if digests.is_empty() && height > tip_header.height {
+                break;
+            }
+
+            for digest in digests {
+                let Some(header) = archival_state.get_block_header(digest).await else {
+                    continue;
+                };
+                nodes.push(BlockTreeNode {
+                    digest,
+                    parent: header.prev_block_digest,
+                    height: header.height,
+                    proof_of_work_family: header.proof_of_work_family,
+                    canonical: archival_state
+                        .block_belongs_to_canonical_chain(digest, tip_digest)
+                        .await,
+                    is_tip: digest == tip_digest,
+                });
+            }
+            height = height.next();
+        }
+
+        Self { nodes }
+    }
+
+    /// Render in the DOT language of graphviz, with edges from blocks to their
+    /// parents. The canonical chain is bold and the tip is filled.
+    pub fn to_dot(&self) -> String {
+        let digests: HashSet<Digest> = self.nodes.iter().map(|node| node.digest).collect();
+
+        let mut dot = String::from("digraph blocks {\n    rankdir=RL;\n");
+        for node in &self.nodes {
+            let hex = node.digest.to_hex();
+            let mut attributes = format!(
+                "label=\"{}\\n{}\\npow {}\"",
+                node.height,
+                &hex[..DOT_LABEL_DIGEST_LENGTH.min(hex.len())],
+                node.proof_of_work_family
+            );
+            if node.is_tip {
+                attributes.push_str(", style=\"bold,filled\"");
+            } else if node.canonical {
+                attributes.push_str(", style=bold");
+            }
+            let _ = writeln!(dot, "    \"{hex}\" [{attributes}];");
+        }
+        for node in &self.nodes {
+            // Parents below the exported depth are left out
+            if digests.contains(&node.parent) {
+                let _ = writeln!(
+                    dot,
+                    "    \"{}\" -> \"{}\";",
+                    node.digest.to_hex(),
+                    node.parent.to_hex()
+                );
+            }
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+#[cfg(test)]
+mod block_tree_tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{
+        add_block_to_archival_state, make_mock_block_with_valid_pow, mock_genesis_archival_state,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn block_tree_contains_forks() {
+        let mut rng = thread_rng();
+        let network = Network::Alpha;
+        let (mut archival_state, _peer_db_lock, _data_dir) =
+            mock_genesis_archival_state(network).await;
+        let genesis = archival_state.genesis_block().clone();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        let (block_1a, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, rng.gen());
+        let (block_1b, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, rng.gen());
+        let (block_2a, _, _) = make_mock_block_with_valid_pow(&block_1a, None, address, rng.gen());
+        for block in [&block_1a, &block_1b, &block_2a] {
+            add_block_to_archival_state(&mut archival_state, block.clone())
+                .await
+                .unwrap();
+        }
+
+        let tree = BlockTree::collect(&archival_state, block_2a.hash(), 10).await;
+        assert_eq!(4, tree.nodes.len());
+        let node = |digest: Digest| {
+            tree.nodes
+                .iter()
+                .find(|node| node.digest == digest)
+                .unwrap()
+        };
+        assert!(node(genesis.hash()).canonical);
+        assert!(node(block_1a.hash()).canonical);
+        assert!(!node(block_1b.hash()).canonical);
+        assert!(node(block_2a.hash()).is_tip);
+        assert_eq!(genesis.hash(), node(block_1b.hash()).parent);
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph blocks {"));
+        assert_eq!(3, dot.matches(" -> ").count());
+
+        // Depth is counted from the tip
+        let shallow_tree = BlockTree::collect(&archival_state, block_2a.hash(), 1).await;
+        assert_eq!(3, shallow_tree.nodes.len());
+        assert_eq!(1, shallow_tree.to_dot().matches(" -> ").count());
+    }
+}
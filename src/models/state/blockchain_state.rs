@@ -1,4 +1,9 @@
-use super::{archival_state::ArchivalState, light_state::LightState};
+use crate::config_models::network::Network;
+
+use super::{
+    archival_state::ArchivalState,
+    light_state::{LightState, LightStateStore},
+};
 
 /// `BlockChainState` provides an `Archival` variant
 /// for full nodes and a `Light` variant for light nodes.
@@ -18,7 +23,7 @@ pub enum BlockchainState {
     /// represents a Archival blockchain state
     Archival(BlockchainArchivalState),
     /// represents Light node blockchain state (ie the current tip)
-    Light(LightState),
+    Light(BlockchainLightState),
 }
 
 impl BlockchainState {
@@ -66,7 +71,7 @@ impl BlockchainState {
     pub fn light_state(&self) -> &LightState {
         match self {
             Self::Archival(bac) => &bac.light_state,
-            Self::Light(light_state) => light_state,
+            Self::Light(blc) => &blc.light_state,
         }
     }
 
@@ -75,7 +80,7 @@ impl BlockchainState {
     pub fn light_state_mut(&mut self) -> &mut LightState {
         match self {
             Self::Archival(bac) => &mut bac.light_state,
-            Self::Light(light_state) => light_state,
+            Self::Light(blc) => &mut blc.light_state,
         }
     }
 }
@@ -91,3 +96,24 @@ pub struct BlockchainArchivalState {
     /// The present tip.
     pub light_state: LightState,
 }
+
+/// The `BlockchainLightState` is the tip of a light node, persisted so that
+/// it survives restarts.
+#[derive(Debug)]
+pub struct BlockchainLightState {
+    /// The present tip.
+    pub light_state: LightState,
+
+    /// Where the tip is persisted
+    pub store: LightStateStore,
+}
+
+impl BlockchainLightState {
+    /// Restore the tip persisted in `store`, or start from genesis
+    pub async fn restore(store: LightStateStore, network: Network) -> Self {
+        Self {
+            light_state: store.load(network).await,
+            store,
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::consensus::timestamp::Timestamp;
+
+/// An assessment of how far this node's wall clock deviates from the network.
+///
+/// Two sources are used: the clocks peers report when asked, and
+/// the timestamp of the tip. A tip timestamp in the future means our clock is
+/// behind, since valid blocks cannot be mined ahead of time. Old tips say
+/// nothing about our clock, as block intervals vary.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClockSkewStatus {
+    /// Median of (peer clock - own clock) over connected peers, in milliseconds
+    pub median_peer_offset_millis: Option<i64>,
+
+    /// How far the tip's timestamp is ahead of our clock, in milliseconds
+    pub tip_ahead_millis: i64,
+
+    /// True if either measure exceeds the allowed skew
+    pub skewed: bool,
+}
+
+impl ClockSkewStatus {
+    pub fn assess(
+        peer_offsets_millis: &[i64],
+        tip_timestamp: Timestamp,
+        now: Timestamp,
+        max_skew_secs: u64,
+    ) -> Self {
+        let max_skew_millis = (max_skew_secs as i64).saturating_mul(1000);
+
+        // The median is robust against a few peers with bad clocks
+        let median_peer_offset_millis = if peer_offsets_millis.is_empty() {
+            None
+        } else {
+            let mut sorted_offsets = peer_offsets_millis.to_vec();
+            sorted_offsets.sort_unstable();
+            Some(sorted_offsets[sorted_offsets.len() / 2])
+        };
+
+        let tip_ahead_millis = (tip_timestamp.0.value() as i64 - now.0.value() as i64).max(0);
+
+        let peers_disagree = median_peer_offset_millis
+            .is_some_and(|offset| offset.unsigned_abs() > max_skew_millis as u64);
+        let tip_in_future = tip_ahead_millis > max_skew_millis;
+
+        Self {
+            median_peer_offset_millis,
+            tip_ahead_millis,
+            skewed: peers_disagree || tip_in_future,
+        }
+    }
+}
+
+#[cfg(test)]
+mod clock_skew_tests {
+    use num_traits::Zero;
+
+    use super::*;
+
+    #[test]
+    fn skew_is_detected_from_peers_and_tip() {
+        let now = Timestamp::now();
+        let minute = 60 * 1000;
+
+        let in_sync = ClockSkewStatus::assess(&[-500, 200, 1000], now, now, 120);
+        assert_eq!(Some(200), in_sync.median_peer_offset_millis);
+        assert!(!in_sync.skewed);
+
+        // One peer with a bad clock is outvoted
+        let one_bad_peer = ClockSkewStatus::assess(&[0, 100, 60 * minute], now, now, 120);
+        assert!(!one_bad_peer.skewed);
+
+        let peers_ahead = ClockSkewStatus::assess(&[5 * minute, 6 * minute], now, now, 120);
+        assert!(peers_ahead.skewed);
+
+        let tip_ahead = ClockSkewStatus::assess(&[], now + Timestamp::minutes(5), now, 120);
+        assert_eq!(None, tip_ahead.median_peer_offset_millis);
+        assert!(tip_ahead.skewed);
+
+        let old_tip = ClockSkewStatus::assess(&[], Timestamp::zero(), now, 120);
+        assert!(!old_tip.skewed);
+    }
+}
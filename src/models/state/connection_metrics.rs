@@ -0,0 +1,370 @@
+//! Metrics of the connections to peers, for diagnosing flaky connectivity and
+//! protocol incompatibilities in the wild.
+//!
+//! Per direction of connection, the failed handshakes are counted by reason,
+//! and the durations of closed connections are summed up and bucketed. Per
+//! connected peer, the messages sent and received are counted in number and
+//! bytes, by message type. The traffic of a closed connection is added to the
+//! totals of its direction.
+//!
+//! The peer threads count their traffic through a [`MeteredPeer`], which
+//! shares the counters with the state, so that no lock on the global state is
+//! needed per message.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+
+use futures::sink::Sink;
+use futures::stream::{Stream, TryStream};
+use serde::{Deserialize, Serialize};
+
+use crate::models::peer::wire_format::variant_name;
+use crate::models::peer::{ConnectionRefusedReason, PeerMessage};
+
+/// Upper bounds of the buckets that connection durations are counted in. The
+/// last bucket counts all longer connections.
+pub const CONNECTION_DURATION_BUCKETS: [Duration; 5] = [
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+    Duration::from_secs(10 * 60),
+    Duration::from_secs(60 * 60),
+    Duration::from_secs(24 * 60 * 60),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConnectionDirection {
+    Inbound,
+    Outbound,
+}
+
+impl ConnectionDirection {
+    pub fn new(inbound: bool) -> Self {
+        if inbound {
+            Self::Inbound
+        } else {
+            Self::Outbound
+        }
+    }
+}
+
+/// Why a connection was closed before the handshake completed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandshakeFailure {
+    /// None of the peer's addresses could be connected to
+    Unreachable,
+
+    /// The connection failed or closed while handshaking
+    Transport,
+
+    /// A phase of the handshake, or the whole connection setup, took too long
+    TimedOut,
+
+    BadMagicValue,
+    NetworkMismatch,
+    UnexpectedMessage,
+    RefusedByUs(ConnectionRefusedReason),
+    RefusedByPeer(ConnectionRefusedReason),
+}
+
+impl Display for HandshakeFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandshakeFailure::Unreachable => write!(f, "unreachable"),
+            HandshakeFailure::Transport => write!(f, "transport"),
+            HandshakeFailure::TimedOut => write!(f, "timed_out"),
+            HandshakeFailure::BadMagicValue => write!(f, "bad_magic_value"),
+            HandshakeFailure::NetworkMismatch => write!(f, "network_mismatch"),
+            HandshakeFailure::UnexpectedMessage => write!(f, "unexpected_message"),
+            HandshakeFailure::RefusedByUs(reason) => write!(f, "refused_by_us_{reason:?}"),
+            HandshakeFailure::RefusedByPeer(reason) => write!(f, "refused_by_peer_{reason:?}"),
+        }
+    }
+}
+
+/// Number and bytes of the messages of one type sent to and received from
+/// peers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageTraffic {
+    pub sent_count: u64,
+    pub sent_bytes: u64,
+    pub received_count: u64,
+    pub received_bytes: u64,
+}
+
+impl MessageTraffic {
+    fn add(&mut self, other: &Self) {
+        self.sent_count += other.sent_count;
+        self.sent_bytes += other.sent_bytes;
+        self.received_count += other.received_count;
+        self.received_bytes += other.received_bytes;
+    }
+}
+
+/// Traffic by message type, as named by [`variant_name`]
+pub type TrafficByMessageType = BTreeMap<String, MessageTraffic>;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectionMetrics {
+    /// Failed handshakes, by the reason as displayed by [`HandshakeFailure`]
+    pub handshake_failures: BTreeMap<String, u64>,
+
+    pub connections_opened: u64,
+    pub connections_closed: u64,
+
+    /// Sum of the durations of the closed connections
+    pub total_duration_secs: u64,
+
+    /// Closed connections, counted in the buckets of
+    /// [`CONNECTION_DURATION_BUCKETS`] plus one for longer connections
+    pub duration_buckets: Vec<u64>,
+
+    /// Traffic of the closed connections
+    pub traffic: TrafficByMessageType,
+}
+
+impl DirectionMetrics {
+    fn record_duration(&mut self, duration: Duration) {
+        if self.duration_buckets.is_empty() {
+            self.duration_buckets = vec![0; CONNECTION_DURATION_BUCKETS.len() + 1];
+        }
+        let bucket = CONNECTION_DURATION_BUCKETS
+            .iter()
+            .position(|bound| duration <= *bound)
+            .unwrap_or(CONNECTION_DURATION_BUCKETS.len());
+        self.duration_buckets[bucket] += 1;
+        self.connections_closed += 1;
+        self.total_duration_secs += duration.as_secs();
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerConnectionMetrics {
+    pub address: SocketAddr,
+    pub direction: ConnectionDirection,
+    pub connected_secs: u64,
+    pub traffic: TrafficByMessageType,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConnectionMetricsReport {
+    pub inbound: DirectionMetrics,
+    pub outbound: DirectionMetrics,
+    pub peers: Vec<PeerConnectionMetrics>,
+}
+
+#[derive(Debug, Clone)]
+struct OpenConnection {
+    direction: ConnectionDirection,
+    opened_at: SystemTime,
+    traffic: Arc<Mutex<TrafficByMessageType>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionMetrics {
+    inbound: DirectionMetrics,
+    outbound: DirectionMetrics,
+    open: HashMap<SocketAddr, OpenConnection>,
+}
+
+impl ConnectionMetrics {
+    fn direction_mut(&mut self, direction: ConnectionDirection) -> &mut DirectionMetrics {
+        match direction {
+            ConnectionDirection::Inbound => &mut self.inbound,
+            ConnectionDirection::Outbound => &mut self.outbound,
+        }
+    }
+
+    pub fn record_handshake_failure(
+        &mut self,
+        direction: ConnectionDirection,
+        failure: HandshakeFailure,
+    ) {
+        *self
+            .direction_mut(direction)
+            .handshake_failures
+            .entry(failure.to_string())
+            .or_default() += 1;
+    }
+
+    /// Start measuring a connection with a completed handshake. Returns the
+    /// traffic counters of the connection, for a [`MeteredPeer`].
+    pub fn connection_opened(
+        &mut self,
+        address: SocketAddr,
+        direction: ConnectionDirection,
+        now: SystemTime,
+    ) -> Arc<Mutex<TrafficByMessageType>> {
+        self.direction_mut(direction).connections_opened += 1;
+        let connection = OpenConnection {
+            direction,
+            opened_at: now,
+            traffic: Arc::default(),
+        };
+        let traffic = connection.traffic.clone();
+        self.open.insert(address, connection);
+
+        traffic
+    }
+
+    /// Stop measuring a connection, and add its duration and traffic to the
+    /// totals of its direction
+    pub fn connection_closed(&mut self, address: SocketAddr, now: SystemTime) {
+        let Some(connection) = self.open.remove(&address) else {
+            return;
+        };
+        let duration = now.duration_since(connection.opened_at).unwrap_or_default();
+        let traffic = connection.traffic.lock().unwrap().clone();
+
+        let direction_metrics = self.direction_mut(connection.direction);
+        direction_metrics.record_duration(duration);
+        for (message_type, message_traffic) in traffic {
+            direction_metrics
+                .traffic
+                .entry(message_type)
+                .or_default()
+                .add(&message_traffic);
+        }
+    }
+
+    pub fn report(&self, now: SystemTime) -> ConnectionMetricsReport {
+        let mut peers = self
+            .open
+            .iter()
+            .map(|(address, connection)| PeerConnectionMetrics {
+                address: *address,
+                direction: connection.direction,
+                connected_secs: now
+                    .duration_since(connection.opened_at)
+                    .unwrap_or_default()
+                    .as_secs(),
+                traffic: connection.traffic.lock().unwrap().clone(),
+            })
+            .collect::<Vec<_>>();
+        peers.sort_by_key(|peer| peer.address);
+
+        ConnectionMetricsReport {
+            inbound: self.inbound.clone(),
+            outbound: self.outbound.clone(),
+            peers,
+        }
+    }
+}
+
+/// A connection to a peer that counts the messages passing through it into
+/// the traffic counters of the connection
+pub struct MeteredPeer<S> {
+    inner: S,
+    traffic: Arc<Mutex<TrafficByMessageType>>,
+}
+
+impl<S> MeteredPeer<S> {
+    pub fn new(inner: S, traffic: Arc<Mutex<TrafficByMessageType>>) -> Self {
+        Self { inner, traffic }
+    }
+
+    fn count(&self, message: &PeerMessage, update: impl FnOnce(&mut MessageTraffic, u64)) {
+        let bytes = bincode::serialized_size(message).unwrap_or_default();
+        let mut traffic = self.traffic.lock().unwrap();
+        update(
+            traffic.entry(variant_name(message).to_owned()).or_default(),
+            bytes,
+        );
+    }
+}
+
+impl<S: Sink<PeerMessage> + Unpin> Sink<PeerMessage> for MeteredPeer<S> {
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: PeerMessage) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        this.count(&item, |traffic, bytes| {
+            traffic.sent_count += 1;
+            traffic.sent_bytes += bytes;
+        });
+        Pin::new(&mut this.inner).start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_close(cx)
+    }
+}
+
+impl<S: TryStream<Ok = PeerMessage> + Unpin> Stream for MeteredPeer<S> {
+    type Item = Result<PeerMessage, S::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let polled = Pin::new(&mut this.inner).try_poll_next(cx);
+        if let Poll::Ready(Some(Ok(message))) = &polled {
+            this.count(message, |traffic, bytes| {
+                traffic.received_count += 1;
+                traffic.received_bytes += bytes;
+            });
+        }
+
+        polled
+    }
+}
+
+#[cfg(test)]
+mod connection_metrics_tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use futures::{SinkExt, StreamExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn traffic_and_durations_are_added_to_the_direction_on_close() {
+        let address = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9798);
+        let opened_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut metrics = ConnectionMetrics::default();
+        metrics.record_handshake_failure(
+            ConnectionDirection::Inbound,
+            HandshakeFailure::RefusedByUs(ConnectionRefusedReason::MaxPeerNumberExceeded),
+        );
+        let traffic = metrics.connection_opened(address, ConnectionDirection::Outbound, opened_at);
+
+        let (sink, stream) = futures::channel::mpsc::unbounded::<PeerMessage>();
+        let mut sender = MeteredPeer::new(sink.sink_map_err(|_| std::fmt::Error), traffic.clone());
+        let mut receiver = MeteredPeer::new(stream.map(Ok::<_, std::fmt::Error>), traffic);
+        sender.send(PeerMessage::Bye).await.unwrap();
+        sender.send(PeerMessage::Bye).await.unwrap();
+        receiver.next().await.unwrap().unwrap();
+
+        let report = metrics.report(opened_at + Duration::from_secs(30));
+        assert_eq!(1, report.peers.len());
+        assert_eq!(30, report.peers[0].connected_secs);
+        let bye = report.peers[0].traffic["bye"];
+        assert_eq!((2, 1), (bye.sent_count, bye.received_count));
+        assert_eq!(bye.sent_bytes, 2 * bye.received_bytes);
+        assert_eq!(
+            Some(&1),
+            report
+                .inbound
+                .handshake_failures
+                .get("refused_by_us_MaxPeerNumberExceeded")
+        );
+
+        metrics.connection_closed(address, opened_at + Duration::from_secs(90));
+        let report = metrics.report(opened_at + Duration::from_secs(90));
+        assert!(report.peers.is_empty());
+        assert_eq!(1, report.outbound.connections_closed);
+        assert_eq!(90, report.outbound.total_duration_secs);
+        assert_eq!(vec![0, 0, 1, 0, 0, 0], report.outbound.duration_buckets);
+        assert_eq!(bye, report.outbound.traffic["bye"]);
+    }
+}
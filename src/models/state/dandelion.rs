@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use bytesize::ByteSize;
+use get_size::GetSize;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::prelude::twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+/// Probability that a node diffuses a stem-phase transaction instead of passing
+/// it on to a single peer. Makes the stem four hops long on average.
+pub const FLUFF_PROBABILITY: f64 = 0.25;
+
+/// Min time a node holds a stem-phase transaction before diffusing it itself,
+/// in case the stem was cut short by a peer dropping it
+pub const STEM_EMBARGO_IN_SECS: u64 = 30;
+
+/// Max random addition to the embargo. Randomizing the embargo prevents the
+/// node right after the originator from always being the first to diffuse.
+pub const STEM_EMBARGO_JITTER_IN_SECS: u64 = 15;
+
+/// Max number of stem-phase transactions held. Holding more, or exceeding the
+/// pool's size limit, evicts the one whose embargo ends first, which is then
+/// diffused.
+pub const MAX_STEM_POOL_SIZE: usize = 100;
+
+/// Transactions in the stem phase of Dandelion relay: they are passed from peer
+/// to single peer before being diffused to all peers, which makes it hard to
+/// tell from the diffusion pattern which node created them.
+///
+/// Stem-phase transactions are held here, outside of the mempool, so that they
+/// are neither announced nor served to peers until diffused.
+#[derive(Clone, Debug)]
+pub struct StemPool {
+    max_total_size: usize,
+    transactions: HashMap<Digest, (Transaction, Timestamp)>,
+}
+
+impl GetSize for StemPool {
+    fn get_stack_size() -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    fn get_heap_size(&self) -> usize {
+        let mut total = self.transactions.capacity()
+            * std::mem::size_of::<(Digest, (Transaction, Timestamp))>();
+        for (transaction, _) in self.transactions.values() {
+            total += transaction.get_heap_size();
+        }
+
+        total
+    }
+
+    fn get_size(&self) -> usize {
+        Self::get_stack_size() + GetSize::get_heap_size(self)
+    }
+}
+
+impl StemPool {
+    pub fn new(max_total_size: ByteSize) -> Self {
+        Self {
+            max_total_size: max_total_size.0.try_into().unwrap(),
+            transactions: HashMap::default(),
+        }
+    }
+
+    /// Hold a stem-phase transaction until diffused, or until its embargo ends.
+    /// Returns the transactions evicted to make room.
+    pub fn insert<R: Rng>(
+        &mut self,
+        transaction: Transaction,
+        now: Timestamp,
+        rng: &mut R,
+    ) -> Vec<Transaction> {
+        let jitter = rng.gen_range(0..=STEM_EMBARGO_JITTER_IN_SECS);
+        let embargo_end = now + Timestamp::seconds(STEM_EMBARGO_IN_SECS + jitter);
+        self.transactions
+            .insert(Hash::hash(&transaction), (transaction, embargo_end));
+
+        let mut evicted = vec![];
+        while self.transactions.len() > MAX_STEM_POOL_SIZE
+            || (self.get_size() > self.max_total_size && !self.transactions.is_empty())
+        {
+            let first_to_expire = self
+                .transactions
+                .iter()
+                .min_by_key(|(_, (_, embargo_end))| *embargo_end)
+                .map(|(digest, _)| *digest)
+                .unwrap();
+            evicted.extend(self.remove(first_to_expire));
+        }
+
+        evicted
+    }
+
+    /// The size above which transactions are evicted, in bytes
+    pub fn max_total_size(&self) -> usize {
+        self.max_total_size
+    }
+
+    pub fn contains(&self, transaction_digest: Digest) -> bool {
+        self.transactions.contains_key(&transaction_digest)
+    }
+
+    /// Forget a transaction, e.g. because it has been diffused
+    pub fn remove(&mut self, transaction_digest: Digest) -> Option<Transaction> {
+        self.transactions
+            .remove(&transaction_digest)
+            .map(|(transaction, _)| transaction)
+    }
+
+    /// Remove and return the transactions whose embargo has ended. They should
+    /// be diffused.
+    pub fn take_expired(&mut self, now: Timestamp) -> Vec<Transaction> {
+        let expired = self
+            .transactions
+            .iter()
+            .filter(|(_, (_, embargo_end))| *embargo_end <= now)
+            .map(|(digest, _)| *digest)
+            .collect::<Vec<_>>();
+
+        expired
+            .into_iter()
+            .filter_map(|digest| self.remove(digest))
+            .collect()
+    }
+
+    /// The held transactions, in no particular order
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.transactions
+            .values()
+            .map(|(transaction, _)| transaction)
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+}
+
+/// Choose the peer to pass a stem-phase transaction on to, never the peer it
+/// came from. Returns `None` if the transaction should be diffused instead.
+///
+/// Own transactions, which did not come from a peer, are always passed on if
+/// there is a peer to pass them to.
+pub fn choose_stem_peer<R: Rng>(
+    peers: impl IntoIterator<Item = SocketAddr>,
+    came_from: Option<SocketAddr>,
+    rng: &mut R,
+) -> Option<SocketAddr> {
+    if came_from.is_some() && rng.gen_bool(FLUFF_PROBABILITY) {
+        return None;
+    }
+
+    peers
+        .into_iter()
+        .filter(|peer| Some(*peer) != came_from)
+        .choose(rng)
+}
+
+#[cfg(test)]
+mod dandelion_tests {
+    use rand::thread_rng;
+
+    use crate::config_models::network::Network;
+    use crate::models::blockchain::block::Block;
+    use crate::tests::shared::get_dummy_socket_address;
+
+    use super::*;
+
+    #[test]
+    fn stem_transactions_are_held_until_embargo_ends() {
+        let mut rng = thread_rng();
+        let now = Timestamp::now();
+        let transaction = Block::genesis_block(Network::RegTest)
+            .kernel
+            .body
+            .transaction;
+        let digest = Hash::hash(&transaction);
+
+        let mut stem_pool = StemPool::new(ByteSize::mb(10));
+        assert!(stem_pool.insert(transaction, now, &mut rng).is_empty());
+        assert!(stem_pool.contains(digest));
+
+        let before_embargo_end = now + Timestamp::seconds(STEM_EMBARGO_IN_SECS - 1);
+        assert!(stem_pool.take_expired(before_embargo_end).is_empty());
+        assert!(stem_pool.contains(digest));
+
+        let after_embargo_end =
+            now + Timestamp::seconds(STEM_EMBARGO_IN_SECS + STEM_EMBARGO_JITTER_IN_SECS);
+        let expired = stem_pool.take_expired(after_embargo_end);
+        assert_eq!(
+            vec![digest],
+            expired.iter().map(Hash::hash).collect::<Vec<_>>()
+        );
+        assert!(stem_pool.is_empty());
+    }
+
+    #[test]
+    fn stem_pool_size_limit_evicts_first_to_expire() {
+        let mut rng = thread_rng();
+        let now = Timestamp::now();
+        let transaction = Block::genesis_block(Network::RegTest)
+            .kernel
+            .body
+            .transaction;
+
+        let mut unbounded = StemPool::new(ByteSize::gb(1));
+        unbounded.insert(transaction.clone(), now, &mut rng);
+        let size_with_one = unbounded.get_size();
+        assert!(size_with_one > StemPool::get_stack_size());
+
+        // room for one transaction only
+        let mut stem_pool = StemPool::new(ByteSize::b(size_with_one as u64));
+        let mut first = transaction.clone();
+        first.kernel.timestamp = now;
+        let mut second = transaction;
+        second.kernel.timestamp = now + Timestamp::seconds(1);
+
+        assert!(stem_pool.insert(first.clone(), now, &mut rng).is_empty());
+        let later = now + Timestamp::seconds(STEM_EMBARGO_IN_SECS + STEM_EMBARGO_JITTER_IN_SECS);
+        let evicted = stem_pool.insert(second.clone(), later, &mut rng);
+        assert_eq!(vec![first], evicted);
+        assert!(stem_pool.contains(Hash::hash(&second)));
+        assert!(stem_pool.get_size() <= stem_pool.max_total_size());
+    }
+
+    #[test]
+    fn stem_is_never_passed_back() {
+        let mut rng = thread_rng();
+        let peers: Vec<SocketAddr> = (1..=3).map(get_dummy_socket_address).collect();
+
+        // own transactions always enter the stem
+        for _ in 0..100 {
+            assert!(choose_stem_peer(peers.clone(), None, &mut rng).is_some());
+        }
+        assert!(choose_stem_peer(vec![], None, &mut rng).is_none());
+
+        let mut fluff_count = 0;
+        for _ in 0..1000 {
+            match choose_stem_peer(peers.clone(), Some(peers[0]), &mut rng) {
+                Some(peer) => assert_ne!(peers[0], peer),
+                None => fluff_count += 1,
+            }
+        }
+        assert!((150..350).contains(&fluff_count));
+
+        // with only the sender to pass it to, the transaction is diffused
+        assert!(choose_stem_peer(vec![peers[0]], Some(peers[0]), &mut rng).is_none());
+    }
+}
@@ -0,0 +1,211 @@
+use get_size::GetSize;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::models::blockchain::block::block_header::BlockHeader;
+use crate::models::blockchain::block::block_info::BlockInfo;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+use super::GlobalState;
+
+/// Number of recent blocks in a snapshot
+pub const DASHBOARD_RECENT_BLOCK_COUNT: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeersSummary {
+    pub connected: usize,
+    pub inbound: usize,
+    pub outbound: usize,
+    pub archival: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MempoolSummary {
+    pub transaction_count: usize,
+    pub size: usize,
+    pub max_size: usize,
+    pub total_fees: NeptuneCoins,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletBalance {
+    pub available: NeptuneCoins,
+    pub timelocked: NeptuneCoins,
+}
+
+/// Everything a terminal dashboard shows, read under a single lock so that it
+/// is consistent and takes one round trip
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DashboardSnapshot {
+    pub taken_at: Timestamp,
+
+    #[serde(with = "digest_serde")]
+    pub tip_digest: Digest,
+    pub tip_header: BlockHeader,
+
+    pub syncing: bool,
+
+    /// Estimated fraction of the chain that is synced, from 0.0 to 1.0, by
+    /// the time elapsed between the genesis block and the tip
+    pub sync_progress: f64,
+
+    pub is_mining: bool,
+    pub peers: PeersSummary,
+    pub mempool: MempoolSummary,
+
+    /// The most recent canonical blocks, newest first. Empty on light nodes.
+    pub recent_blocks: Vec<BlockInfo>,
+
+    /// `None` if the wallet is disabled
+    pub wallet_balance: Option<WalletBalance>,
+}
+
+impl DashboardSnapshot {
+    pub async fn from_state(global_state: &GlobalState, now: Timestamp) -> Self {
+        let tip = global_state.chain.light_state();
+        let tip_digest = tip.hash();
+        let tip_header = tip.header().clone();
+
+        let peer_map = &global_state.net.peer_map;
+        let inbound = peer_map.values().filter(|peer| peer.inbound).count();
+        let peers = PeersSummary {
+            connected: peer_map.len(),
+            inbound,
+            outbound: peer_map.len() - inbound,
+            archival: peer_map
+                .values()
+                .filter(|peer| peer.is_archival_node)
+                .count(),
+        };
+
+        let mempool = &global_state.mempool;
+        let mempool = MempoolSummary {
+            transaction_count: mempool.len(),
+            size: mempool.get_size(),
+            max_size: mempool.max_total_size(),
+            total_fees: mempool
+                .get_sorted_iter()
+                .filter_map(|(digest, _)| mempool.get(digest))
+                .map(|transaction| transaction.kernel.fee)
+                .sum(),
+        };
+
+        let wallet_balance = if global_state.cli().no_wallet {
+            None
+        } else {
+            let wallet_status = global_state.get_wallet_status_for_tip().await;
+            Some(WalletBalance {
+                available: wallet_status.synced_unspent_available_amount(now),
+                timelocked: wallet_status.synced_unspent_timelocked_amount(now),
+            })
+        };
+
+        Self {
+            taken_at: now,
+            tip_digest,
+            sync_progress: sync_progress(
+                global_state.cli().network.launch_date(),
+                tip_header.timestamp,
+                now,
+            ),
+            tip_header,
+            syncing: global_state.net.syncing,
+            is_mining: global_state.mining,
+            peers,
+            mempool,
+            recent_blocks: Self::recent_blocks(global_state, tip_digest).await,
+            wallet_balance,
+        }
+    }
+
+    async fn recent_blocks(global_state: &GlobalState, tip_digest: Digest) -> Vec<BlockInfo> {
+        if !global_state.chain.is_archival_node() {
+            return vec![];
+        }
+
+        let archival_state = global_state.chain.archival_state();
+        let genesis_digest = archival_state.genesis_block().hash();
+        let mut digests = vec![tip_digest];
+        digests.extend(
+            archival_state
+                .get_ancestor_block_digests(tip_digest, DASHBOARD_RECENT_BLOCK_COUNT - 1)
+                .await,
+        );
+
+        let mut recent_blocks = vec![];
+        for digest in digests {
+            match archival_state.get_block(digest).await {
+                Ok(Some(block)) => {
+                    let mut block_info =
+                        BlockInfo::from_block_and_digests(&block, genesis_digest, tip_digest);
+                    block_info.is_final = archival_state.is_block_final(digest, tip_digest).await;
+                    recent_blocks.push(block_info);
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("Failed to read block {digest} for the dashboard: {err}");
+                    break;
+                }
+            }
+        }
+
+        recent_blocks
+    }
+}
+
+/// The fraction of the time since `launch` that the tip covers
+fn sync_progress(launch: Timestamp, tip: Timestamp, now: Timestamp) -> f64 {
+    let elapsed = now.0.value().saturating_sub(launch.0.value());
+    if elapsed == 0 {
+        return 1.0;
+    }
+    let covered = tip.0.value().saturating_sub(launch.0.value());
+
+    (covered as f64 / elapsed as f64).min(1.0)
+}
+
+#[cfg(test)]
+mod dashboard_snapshot_tests {
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::mock_genesis_global_state;
+
+    use super::*;
+
+    #[test]
+    fn sync_progress_is_the_fraction_of_time_covered() {
+        let launch = Timestamp::hours(100);
+        assert_eq!(
+            0.0,
+            sync_progress(launch, launch, launch + Timestamp::hours(10))
+        );
+        assert_eq!(
+            0.5,
+            sync_progress(
+                launch,
+                launch + Timestamp::hours(5),
+                launch + Timestamp::hours(10)
+            )
+        );
+        assert_eq!(1.0, sync_progress(launch, launch, launch));
+    }
+
+    #[tokio::test]
+    async fn snapshot_of_genesis_state() {
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 0, WalletSecret::devnet_wallet()).await;
+        let global_state = global_state_lock.lock_guard().await;
+        let snapshot = DashboardSnapshot::from_state(&global_state, Timestamp::now()).await;
+
+        assert_eq!(global_state.chain.light_state().hash(), snapshot.tip_digest);
+        assert_eq!(0, snapshot.peers.connected);
+        assert_eq!(0, snapshot.mempool.transaction_count);
+        assert_eq!(1, snapshot.recent_blocks.len());
+        assert!(snapshot.recent_blocks[0].is_genesis);
+        assert!(snapshot.wallet_balance.is_some());
+    }
+}
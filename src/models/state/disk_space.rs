@@ -0,0 +1,96 @@
+//! Protection against filling up the disk that holds the data directory.
+//!
+//! The free space is checked periodically. While it is below
+//! `--min-free-disk-space`, the node stores no new blocks: it stops syncing,
+//! ignores blocks from peers and halts mining, so the databases are not left
+//! half-written by a full disk. Peers offer the missed blocks again once
+//! syncing resumes.
+//!
+//! To not flap around the threshold, the free space must exceed it by
+//! [`DISK_SPACE_RECOVERY_MARGIN_PERCENT`] before blocks are stored again.
+
+use std::path::Path;
+
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+use systemstat::{Platform, System};
+
+/// How far above the threshold the free space must be before storing resumes
+pub const DISK_SPACE_RECOVERY_MARGIN_PERCENT: u64 = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskSpaceStatus {
+    /// Free bytes on the filesystem holding the data directory, if they could
+    /// be determined
+    pub available: Option<u64>,
+
+    /// True while the free space is too low to store blocks
+    pub low: bool,
+}
+
+impl DiskSpaceStatus {
+    /// Free bytes on the filesystem holding `path`, if they can be determined.
+    ///
+    /// `path` need not be a mount point: the filesystem holding it is the one
+    /// mounted at its longest ancestor, with symlinks resolved.
+    pub fn measure(path: &Path) -> Option<u64> {
+        let path = path.canonicalize().ok()?;
+        System::new()
+            .mounts()
+            .ok()?
+            .into_iter()
+            .filter(|filesystem| path.starts_with(&filesystem.fs_mounted_on))
+            .max_by_key(|filesystem| Path::new(&filesystem.fs_mounted_on).components().count())
+            .map(|filesystem| filesystem.avail.as_u64())
+    }
+
+    /// Reassess with a new measurement. A failed measurement keeps the
+    /// previous assessment, and a zero threshold disables the protection.
+    pub fn assess(self, available: Option<u64>, min_free: ByteSize) -> Self {
+        let Some(free) = available else {
+            return Self { available, ..self };
+        };
+        let threshold = if self.low {
+            min_free.as_u64() / 100 * (100 + DISK_SPACE_RECOVERY_MARGIN_PERCENT)
+        } else {
+            min_free.as_u64()
+        };
+
+        Self {
+            available,
+            low: free < threshold,
+        }
+    }
+}
+
+#[cfg(test)]
+mod disk_space_tests {
+    use super::*;
+    use crate::config_models::network::Network;
+    use crate::tests::shared::unit_test_data_directory;
+
+    #[test]
+    fn free_space_is_measured_below_a_mount_point() {
+        let data_dir = unit_test_data_directory(Network::RegTest).unwrap();
+        let path = data_dir.root_dir_path();
+        std::fs::create_dir_all(&path).unwrap();
+
+        assert!(DiskSpaceStatus::measure(&path).is_some());
+        assert!(DiskSpaceStatus::measure(&path.join("missing")).is_none());
+    }
+
+    #[test]
+    fn storing_resumes_only_above_the_margin() {
+        let min_free = ByteSize::gb(1);
+        let status = DiskSpaceStatus::default();
+        assert!(!status.assess(Some(ByteSize::gb(2).as_u64()), min_free).low);
+
+        let low = status.assess(Some(ByteSize::mb(900).as_u64()), min_free);
+        assert!(low.low);
+        assert!(low.assess(None, min_free).low);
+        assert!(low.assess(Some(ByteSize::mb(1050).as_u64()), min_free).low);
+        assert!(!low.assess(Some(ByteSize::mb(1200).as_u64()), min_free).low);
+
+        assert!(!low.assess(Some(0), ByteSize::b(0)).low);
+    }
+}
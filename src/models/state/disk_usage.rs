@@ -0,0 +1,139 @@
+use std::fmt::Display;
+use std::path::Path;
+
+use anyhow::Result;
+use bytesize::ByteSize;
+use serde::{Deserialize, Serialize};
+
+use crate::config_models::data_directory::DataDirectory;
+
+use super::event_journal::{EventJournal, JournalRetention};
+
+/// Disk used by the event journal, and how much of it is kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalDiskUsage {
+    pub bytes: u64,
+    pub files: usize,
+    pub retention: JournalRetention,
+}
+
+impl JournalDiskUsage {
+    pub async fn from_journal(journal: &EventJournal) -> Result<Self> {
+        let (files, bytes) = journal.disk_usage().await?;
+        Ok(Self {
+            bytes,
+            files,
+            retention: journal.retention(),
+        })
+    }
+}
+
+/// The disk used by the node's data directory. Logs go to stdout, so the node
+/// writes no log files of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub blocks: u64,
+    pub databases: u64,
+    pub journal: JournalDiskUsage,
+
+    /// The whole data directory, including the above
+    pub total: u64,
+}
+
+impl DiskUsage {
+    /// Measure the data directory. Walks the directory tree, so this should
+    /// not be called while holding the global state lock.
+    pub async fn measure(data_dir: &DataDirectory, journal: JournalDiskUsage) -> Result<Self> {
+        let data_dir = data_dir.clone();
+        tokio::task::spawn_blocking(move || {
+            Ok(Self {
+                blocks: directory_size(&data_dir.block_dir_path())?,
+                databases: directory_size(&data_dir.database_dir_path())?,
+                journal,
+                total: directory_size(&data_dir.root_dir_path())?,
+            })
+        })
+        .await?
+    }
+}
+
+/// Total size of the files in a directory and its subdirectories. A missing
+/// directory has size zero.
+fn directory_size(path: &Path) -> Result<u64> {
+    if !path.exists() {
+        return Ok(0);
+    }
+
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += directory_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+
+    Ok(size)
+}
+
+impl Display for DiskUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let retention = self.journal.retention;
+        writeln!(f, "blocks:    {}", ByteSize(self.blocks))?;
+        writeln!(f, "databases: {}", ByteSize(self.databases))?;
+        write!(
+            f,
+            "journal:   {} in {} files (keeping {} files of up to {}",
+            ByteSize(self.journal.bytes),
+            self.journal.files,
+            retention.max_file_count,
+            ByteSize(retention.max_file_size),
+        )?;
+        match retention.max_age {
+            Some(max_age) => writeln!(
+                f,
+                ", for {} days)",
+                max_age.0.value() / (24 * 60 * 60 * 1000)
+            )?,
+            None => writeln!(f, ")")?,
+        }
+        write!(f, "total:     {}", ByteSize(self.total))
+    }
+}
+
+#[cfg(test)]
+mod disk_usage_tests {
+    use crate::config_models::network::Network;
+    use crate::models::state::event_journal::JournalEvent;
+    use crate::tests::shared::unit_test_data_directory;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn journal_is_part_of_the_total() -> Result<()> {
+        let data_dir = unit_test_data_directory(Network::RegTest)?;
+        let mut journal = EventJournal::open(
+            data_dir.event_journal_dir_path(),
+            JournalRetention::default(),
+        )
+        .await?;
+        journal
+            .append(JournalEvent::BlockConnected {
+                digest: Default::default(),
+                height: 1u64.into(),
+            })
+            .await?;
+
+        let journal_usage = JournalDiskUsage::from_journal(&journal).await?;
+        assert_eq!(1, journal_usage.files);
+        assert!(journal_usage.bytes > 0);
+
+        let usage = DiskUsage::measure(&data_dir, journal_usage).await?;
+        assert_eq!(0, usage.blocks);
+        assert!(usage.total >= usage.journal.bytes);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,467 @@
+//! An append-only, rotating on-disk journal of chain, wallet and peer events.
+//!
+//! Every entry is assigned a sequence number that increases by one per entry
+//! and is never reused, so external indexers can remember the last sequence
+//! number they processed and replay everything they missed after a restart.
+//!
+//! Entries are stored as JSON lines in files named after the sequence number
+//! of their first entry. When the newest file exceeds the size allowed by the
+//! [`JournalRetention`] a new file is started. The oldest files are deleted
+//! once there are more than the retention allows, or once they are older
+//! than it allows.
+
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::{debug, warn};
+
+use crate::config_models::data_directory::DataDirectory;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::peer_loop::PeerStandingNumber;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+pub const EVENT_JOURNAL_DIRECTORY_NAME: &str = "journal";
+const JOURNAL_FILENAME_PREFIX: &str = "events-";
+const JOURNAL_FILENAME_EXTENSION: &str = "jsonl";
+
+/// Default size beyond which a new journal file is started
+pub const MAX_JOURNAL_FILE_SIZE_IN_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Default number of journal files to keep
+pub const MAX_JOURNAL_FILE_COUNT: usize = 8;
+
+/// Max number of entries returned by a single replay request.
+pub const MAX_JOURNAL_REPLAY_COUNT: usize = 1000;
+
+/// How much of the journal is kept on disk
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalRetention {
+    /// Start a new file once the current one grows beyond this size
+    pub max_file_size: u64,
+
+    /// Number of files to keep, including the one being written
+    pub max_file_count: usize,
+
+    /// Delete files that were last written to longer ago than this. The
+    /// file being written is never deleted.
+    pub max_age: Option<Timestamp>,
+}
+
+impl Default for JournalRetention {
+    fn default() -> Self {
+        Self {
+            max_file_size: MAX_JOURNAL_FILE_SIZE_IN_BYTES,
+            max_file_count: MAX_JOURNAL_FILE_COUNT,
+            max_age: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalEvent {
+    BlockConnected {
+        #[serde(with = "digest_serde")]
+        digest: Digest,
+        height: BlockHeight,
+    },
+    BlockDisconnected {
+        #[serde(with = "digest_serde")]
+        digest: Digest,
+        height: BlockHeight,
+    },
+    TransactionReceived {
+        #[serde(with = "digest_serde")]
+        block_digest: Digest,
+        height: BlockHeight,
+        amount: NeptuneCoins,
+    },
+    DataCarrier {
+        #[serde(with = "digest_serde")]
+        block_digest: Digest,
+        height: BlockHeight,
+        data: Vec<u8>,
+    },
+    TransactionSent {
+        #[serde(with = "digest_serde")]
+        transaction_digest: Digest,
+        fee: NeptuneCoins,
+    },
+    PeerBanned {
+        ip: IpAddr,
+        standing: PeerStandingNumber,
+    },
+    DoubleSpend {
+        #[serde(with = "digest_serde")]
+        own_transaction_digest: Digest,
+        #[serde(with = "digest_serde")]
+        conflicting_digest: Digest,
+        in_block: bool,
+    },
+    TransactionConfirmed {
+        #[serde(with = "digest_serde")]
+        transaction_digest: Digest,
+        #[serde(with = "digest_serde")]
+        block_digest: Digest,
+        confirmations: u64,
+    },
+    TransactionReorgedOut {
+        #[serde(with = "digest_serde")]
+        transaction_digest: Digest,
+        #[serde(with = "digest_serde")]
+        block_digest: Digest,
+    },
+    TipPreferred {
+        #[serde(with = "digest_serde")]
+        digest: Digest,
+        height: BlockHeight,
+        #[serde(with = "digest_serde")]
+        previous_tip_digest: Digest,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub sequence: u64,
+    pub timestamp: Timestamp,
+    pub event: JournalEvent,
+}
+
+#[derive(Debug)]
+pub struct EventJournal {
+    directory: PathBuf,
+    retention: JournalRetention,
+
+    /// Sequence number of the first entry in each journal file, ascending
+    file_start_sequences: Vec<u64>,
+
+    /// Size of the newest journal file
+    current_file_size: u64,
+
+    next_sequence: u64,
+}
+
+impl EventJournal {
+    /// Open the journal in the given directory, creating it if needed, and
+    /// continue the sequence numbering where it left off. Files beyond the
+    /// retention are deleted.
+    pub async fn open(directory: PathBuf, retention: JournalRetention) -> Result<Self> {
+        DataDirectory::create_dir_if_not_exists(&directory).await?;
+
+        let mut file_start_sequences = vec![];
+        let mut dir_entries = tokio::fs::read_dir(&directory).await?;
+        while let Some(dir_entry) = dir_entries.next_entry().await? {
+            if let Some(start_sequence) = Self::parse_file_name(&dir_entry.path()) {
+                file_start_sequences.push(start_sequence);
+            }
+        }
+        file_start_sequences.sort_unstable();
+
+        let mut journal = Self {
+            directory,
+            retention,
+            file_start_sequences,
+            current_file_size: 0,
+            next_sequence: 0,
+        };
+
+        if let Some(&last_start_sequence) = journal.file_start_sequences.last() {
+            let last_file_path = journal.file_path(last_start_sequence);
+            journal.current_file_size = tokio::fs::metadata(&last_file_path).await?.len();
+            journal.next_sequence = match Self::read_file(&last_file_path).await?.last() {
+                Some(entry) => entry.sequence + 1,
+                None => last_start_sequence,
+            };
+        }
+        journal.prune().await?;
+
+        debug!(
+            "Opened event journal with {} files. Next sequence number: {}",
+            journal.file_start_sequences.len(),
+            journal.next_sequence
+        );
+
+        Ok(journal)
+    }
+
+    /// The sequence number that will be assigned to the next entry
+    pub fn next_sequence(&self) -> u64 {
+        self.next_sequence
+    }
+
+    pub fn retention(&self) -> JournalRetention {
+        self.retention
+    }
+
+    /// Number of journal files, and their total size in bytes
+    pub async fn disk_usage(&self) -> Result<(usize, u64)> {
+        let mut total_size = 0;
+        for &start_sequence in &self.file_start_sequences {
+            total_size += tokio::fs::metadata(self.file_path(start_sequence))
+                .await?
+                .len();
+        }
+
+        Ok((self.file_start_sequences.len(), total_size))
+    }
+
+    /// Append an event to the journal and return its sequence number.
+    pub async fn append(&mut self, event: JournalEvent) -> Result<u64> {
+        if self.file_start_sequences.is_empty()
+            || self.current_file_size >= self.retention.max_file_size
+        {
+            self.rotate().await?;
+        }
+        self.prune().await?;
+
+        let entry = JournalEntry {
+            sequence: self.next_sequence,
+            timestamp: Timestamp::now(),
+            event,
+        };
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+
+        let current_file_path = self.file_path(*self.file_start_sequences.last().unwrap());
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&current_file_path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+
+        self.current_file_size += line.len() as u64;
+        self.next_sequence += 1;
+
+        Ok(entry.sequence)
+    }
+
+    /// Append an event, logging instead of failing if the journal cannot be
+    /// written. Losing a journal entry must never halt the node.
+    pub async fn record(&mut self, event: JournalEvent) {
+        if let Err(err) = self.append(event).await {
+            warn!("Failed to write to event journal: {err:#}");
+        }
+    }
+
+    /// Return up to `max_count` entries with a sequence number of at least
+    /// `sequence`, in order. Entries that have been rotated out are skipped,
+    /// so the first returned entry may have a higher sequence number than
+    /// requested.
+    pub async fn entries_since(
+        &self,
+        sequence: u64,
+        max_count: usize,
+    ) -> Result<Vec<JournalEntry>> {
+        // Start with the last file that begins at or before `sequence`
+        let first_file_index = self
+            .file_start_sequences
+            .iter()
+            .rposition(|&start| start <= sequence)
+            .unwrap_or(0);
+
+        let mut entries = vec![];
+        for &start_sequence in &self.file_start_sequences[first_file_index..] {
+            let file_entries = Self::read_file(&self.file_path(start_sequence)).await?;
+            for entry in file_entries {
+                if entry.sequence < sequence {
+                    continue;
+                }
+                if entries.len() == max_count {
+                    return Ok(entries);
+                }
+                entries.push(entry);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Start a new journal file.
+    async fn rotate(&mut self) -> Result<()> {
+        self.file_start_sequences.push(self.next_sequence);
+        self.current_file_size = 0;
+
+        Ok(())
+    }
+
+    /// Delete the oldest files while there are more than the retention
+    /// allows, or while they are older than it allows. The newest file is
+    /// kept.
+    async fn prune(&mut self) -> Result<()> {
+        while self.file_start_sequences.len() > 1 {
+            let oldest_file_path = self.file_path(self.file_start_sequences[0]);
+            let too_many = self.file_start_sequences.len() > self.retention.max_file_count.max(1);
+            if !too_many && !self.is_expired(&oldest_file_path).await? {
+                break;
+            }
+
+            debug!("Removing old journal file {}", oldest_file_path.display());
+            tokio::fs::remove_file(&oldest_file_path)
+                .await
+                .with_context(|| format!("Failed to remove {}", oldest_file_path.display()))?;
+            self.file_start_sequences.remove(0);
+        }
+
+        Ok(())
+    }
+
+    /// Whether the file was last written to longer ago than the retention
+    /// allows
+    async fn is_expired(&self, path: &Path) -> Result<bool> {
+        let Some(max_age) = self.retention.max_age else {
+            return Ok(false);
+        };
+        let modified = tokio::fs::metadata(path).await?.modified()?;
+        let modified = Timestamp::millis(modified.duration_since(UNIX_EPOCH)?.as_millis() as u64);
+
+        Ok(modified + max_age < Timestamp::now())
+    }
+
+    fn file_path(&self, start_sequence: u64) -> PathBuf {
+        let prefix = JOURNAL_FILENAME_PREFIX;
+        let extension = JOURNAL_FILENAME_EXTENSION;
+        // Zero-padding keeps the files sorted by name.
+        self.directory
+            .join(format!("{prefix}{start_sequence:020}.{extension}"))
+    }
+
+    fn parse_file_name(path: &Path) -> Option<u64> {
+        if path.extension()? != JOURNAL_FILENAME_EXTENSION {
+            return None;
+        }
+        path.file_stem()?
+            .to_str()?
+            .strip_prefix(JOURNAL_FILENAME_PREFIX)?
+            .parse()
+            .ok()
+    }
+
+    async fn read_file(path: &Path) -> Result<Vec<JournalEntry>> {
+        let file = OpenOptions::new().read(true).open(path).await?;
+        let mut lines = BufReader::new(file).lines();
+        let mut entries = vec![];
+        while let Some(line) = lines.next_line().await? {
+            // A crash can leave a partially written last line behind.
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => warn!("Skipping corrupt line in {}: {err}", path.display()),
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod event_journal_tests {
+    use crate::config_models::network::Network;
+    use crate::tests::shared::unit_test_data_directory;
+
+    use super::*;
+
+    fn unit_test_journal_dir() -> PathBuf {
+        unit_test_data_directory(Network::RegTest)
+            .unwrap()
+            .event_journal_dir_path()
+    }
+
+    fn block_connected(height: u64) -> JournalEvent {
+        JournalEvent::BlockConnected {
+            digest: Digest::default(),
+            height: height.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn sequence_numbers_survive_reopening() -> Result<()> {
+        let directory = unit_test_journal_dir();
+        let mut journal =
+            EventJournal::open(directory.clone(), JournalRetention::default()).await?;
+        assert_eq!(0, journal.append(block_connected(1)).await?);
+        assert_eq!(1, journal.append(block_connected(2)).await?);
+        drop(journal);
+
+        let mut journal = EventJournal::open(directory, JournalRetention::default()).await?;
+        assert_eq!(2, journal.next_sequence());
+        assert_eq!(2, journal.append(block_connected(3)).await?);
+
+        let replayed = journal.entries_since(1, MAX_JOURNAL_REPLAY_COUNT).await?;
+        assert_eq!(
+            vec![1, 2],
+            replayed
+                .iter()
+                .map(|entry| entry.sequence)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(block_connected(3), replayed[1].event);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rotation_keeps_newest_files() -> Result<()> {
+        let mut journal =
+            EventJournal::open(unit_test_journal_dir(), JournalRetention::default()).await?;
+        for height in 0..(MAX_JOURNAL_FILE_COUNT as u64 + 3) {
+            // Force a new file for every entry
+            journal.current_file_size = MAX_JOURNAL_FILE_SIZE_IN_BYTES;
+            journal.append(block_connected(height)).await?;
+        }
+
+        assert_eq!(MAX_JOURNAL_FILE_COUNT, journal.file_start_sequences.len());
+        let replayed = journal.entries_since(0, 2).await?;
+        assert_eq!(
+            vec![3, 4],
+            replayed
+                .iter()
+                .map(|entry| entry.sequence)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+    #[tokio::test]
+    async fn expired_files_are_pruned() -> Result<()> {
+        let directory = unit_test_journal_dir();
+        let mut journal =
+            EventJournal::open(directory.clone(), JournalRetention::default()).await?;
+        for height in 0..3 {
+            journal.current_file_size = MAX_JOURNAL_FILE_SIZE_IN_BYTES;
+            journal.append(block_connected(height)).await?;
+        }
+        assert_eq!(3, journal.disk_usage().await?.0);
+        drop(journal);
+
+        let retention = JournalRetention {
+            max_age: Some(Timestamp::millis(100)),
+            ..Default::default()
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        let journal = EventJournal::open(directory, retention).await?;
+
+        // Only the newest file survives, even though it expired too
+        let (file_count, size) = journal.disk_usage().await?;
+        assert_eq!(1, file_count);
+        assert!(size > 0);
+        assert_eq!(3, journal.next_sequence());
+        let replayed = journal.entries_since(0, MAX_JOURNAL_REPLAY_COUNT).await?;
+        assert_eq!(
+            vec![2],
+            replayed
+                .iter()
+                .map(|entry| entry.sequence)
+                .collect::<Vec<_>>()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+//! Blocks that the chain can no longer be reorganized away from.
+//!
+//! A block becomes final when an external finality provider marks it so,
+//! through the `mark_block_final` RPC, or when it is `--finality-depth` blocks
+//! below the tip. Finality is monotone: finalizing a block finalizes all its
+//! ancestors, so only the last finalized block is stored, in the block index.
+//! The fork choice refuses any chain that does not contain it.
+//!
+//! Finality needs the block index, so light nodes do not track it.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalizedBlock {
+    #[serde(with = "digest_serde")]
+    pub digest: Digest,
+    pub height: BlockHeight,
+}
+
+/// Why a block cannot be marked final
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum FinalityError {
+    #[error("block {0} is unknown")]
+    UnknownBlock(#[serde(with = "digest_serde")] Digest),
+
+    #[error("block {0} is not in the canonical chain")]
+    NotCanonical(#[serde(with = "digest_serde")] Digest),
+
+    #[error("finality needs the block index of an archival node")]
+    NotArchival,
+
+    #[error("the archival state is opened read-only")]
+    ReadOnly,
+}
+
+/// The height that becomes final when the tip is at `tip_height`, if any
+pub fn height_final_at_depth(tip_height: BlockHeight, depth: u64) -> Option<BlockHeight> {
+    u64::from(tip_height)
+        .checked_sub(depth)
+        .map(BlockHeight::from)
+}
+
+#[cfg(test)]
+mod finality_tests {
+    use super::*;
+
+    #[test]
+    fn blocks_become_final_at_depth() {
+        let height = |h: u64| BlockHeight::from(h);
+        assert_eq!(None, height_final_at_depth(height(5), 6));
+        assert_eq!(Some(height(0)), height_final_at_depth(height(6), 6));
+        assert_eq!(Some(height(94)), height_final_at_depth(height(100), 6));
+        assert_eq!(Some(height(100)), height_final_at_depth(height(100), 0));
+    }
+}
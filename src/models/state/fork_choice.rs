@@ -0,0 +1,179 @@
+//! Competing chain tips, and the operator's choice between tips of equal work.
+//!
+//! The node follows the tip with the most accumulated proof-of-work, and does
+//! not switch to a tip with only as much. Archival nodes store the competing
+//! tips of equal work that peers offer, so that an operator can list them and
+//! pin the node to one of them. The pin holds until a block on either branch
+//! makes their work differ, upon which the heavier branch is followed again.
+
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::blockchain::block::block_header::PROOF_OF_WORK_COUNT_U32_SIZE;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::prelude::twenty_first::prelude::U32s;
+use crate::util_types::digest_serde;
+
+use super::archival_state::ArchivalState;
+use super::block_tree::BlockTree;
+
+/// Number of heights below the tip searched for competing tips
+pub const FORK_SEARCH_DEPTH: u64 = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForkTip {
+    #[serde(with = "digest_serde")]
+    pub digest: Digest,
+    pub height: BlockHeight,
+
+    /// The accumulated proof-of-work of the chain ending in this block
+    pub proof_of_work_family: U32s<PROOF_OF_WORK_COUNT_U32_SIZE>,
+
+    pub is_tip: bool,
+
+    /// True if the chain ending in this block has as much proof-of-work as
+    /// the tip, so that the node may be pinned to it
+    pub ties_with_tip: bool,
+
+    /// True if the operator pinned the node to this block
+    pub preferred: bool,
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PreferTipError {
+    #[error("competing tips are only stored by archival nodes")]
+    NotArchival,
+
+    #[error("block {0} is not stored")]
+    UnknownBlock(Digest),
+
+    #[error("block {0} does not have as much accumulated proof-of-work as the tip")]
+    UnequalWork(Digest),
+
+    #[error("block {0} is on a branch that rolls back the finalized block")]
+    RevertsFinalizedBlock(Digest),
+}
+
+/// The stored blocks without children, from [`FORK_SEARCH_DEPTH`] heights
+/// below the tip and up, the heaviest first
+pub async fn fork_tips(
+    archival_state: &ArchivalState,
+    tip_digest: Digest,
+    preferred_tip: Option<Digest>,
+) -> Vec<ForkTip> {
+    let tree = BlockTree::collect(archival_state, tip_digest, FORK_SEARCH_DEPTH).await;
+    let Some(tip) = tree.nodes.iter().find(|node| node.is_tip) else {
+        return vec![];
+    };
+    let parents: HashSet<Digest> = tree.nodes.iter().map(|node| node.parent).collect();
+
+    let mut tips = tree
+        .nodes
+        .iter()
+        .filter(|node| !parents.contains(&node.digest))
+        .map(|node| ForkTip {
+            digest: node.digest,
+            height: node.height,
+            proof_of_work_family: node.proof_of_work_family,
+            is_tip: node.is_tip,
+            ties_with_tip: node.proof_of_work_family == tip.proof_of_work_family,
+            preferred: Some(node.digest) == preferred_tip,
+        })
+        .collect::<Vec<_>>();
+    tips.sort_by(|a, b| b.proof_of_work_family.cmp(&a.proof_of_work_family));
+
+    tips
+}
+
+/// Check that the node may be pinned to the stored block `digest` instead of
+/// the tip `tip_digest`
+pub async fn check_preferable_tip(
+    archival_state: &ArchivalState,
+    tip_digest: Digest,
+    digest: Digest,
+) -> Result<(), PreferTipError> {
+    let Some(header) = archival_state.get_block_header(digest).await else {
+        return Err(PreferTipError::UnknownBlock(digest));
+    };
+    let tip_header = archival_state
+        .get_block_header(tip_digest)
+        .await
+        .expect("tip must be stored");
+    if header.proof_of_work_family != tip_header.proof_of_work_family {
+        return Err(PreferTipError::UnequalWork(digest));
+    }
+    if archival_state
+        .reverts_finalized_block(header.prev_block_digest, tip_digest)
+        .await
+    {
+        return Err(PreferTipError::RevertsFinalizedBlock(digest));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod fork_choice_tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{
+        add_block_to_archival_state, make_mock_block_with_valid_pow, mock_genesis_archival_state,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn competing_tips_of_equal_work_can_be_preferred() {
+        let mut rng = thread_rng();
+        let network = Network::Alpha;
+        let (mut archival_state, _peer_db_lock, _data_dir) =
+            mock_genesis_archival_state(network).await;
+        let genesis = archival_state.genesis_block().clone();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        let (block_1a, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, rng.gen());
+        let (block_1b, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, rng.gen());
+        add_block_to_archival_state(&mut archival_state, block_1a.clone())
+            .await
+            .unwrap();
+        archival_state
+            .write_block_as_competing_tip(&block_1b)
+            .await
+            .unwrap();
+        assert_eq!(block_1a.hash(), archival_state.get_tip().await.hash());
+
+        let tips = fork_tips(&archival_state, block_1a.hash(), Some(block_1b.hash())).await;
+        assert_eq!(2, tips.len());
+        assert!(tips.iter().all(|tip| tip.ties_with_tip));
+        let tip_1b = tips.iter().find(|tip| !tip.is_tip).unwrap();
+        assert_eq!(block_1b.hash(), tip_1b.digest);
+        assert!(tip_1b.preferred);
+
+        assert_eq!(
+            Ok(()),
+            check_preferable_tip(&archival_state, block_1a.hash(), block_1b.hash()).await
+        );
+        assert_eq!(
+            Err(PreferTipError::UnequalWork(genesis.hash())),
+            check_preferable_tip(&archival_state, block_1a.hash(), genesis.hash()).await
+        );
+
+        // Setting a stored block as tip does not store it twice
+        archival_state.write_block_as_tip(&block_1b).await.unwrap();
+        assert_eq!(block_1b.hash(), archival_state.get_tip().await.hash());
+        assert_eq!(
+            2,
+            archival_state
+                .block_height_to_block_digests(1u64.into())
+                .await
+                .len()
+        );
+    }
+}
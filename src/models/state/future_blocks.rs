@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use get_size::GetSize;
+
+use crate::models::blockchain::block::Block;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+
+/// Max time a block that is too far in the future is held until it becomes
+/// valid. Blocks that would have to be held longer are rejected.
+pub const FUTURE_BLOCK_MAX_HOLD_IN_MINUTES: usize = 10;
+
+/// Max number of future blocks held. Holding more drops the one that becomes
+/// valid last.
+pub const MAX_FUTURE_BLOCKS: usize = 20;
+
+/// Blocks whose timestamp is slightly too far ahead of the local clock, held
+/// so that they can be validated again once the clock has caught up, instead
+/// of being rejected for good.
+#[derive(Debug, Default)]
+pub struct FutureBlocks {
+    blocks: HashMap<Digest, (Block, Instant)>,
+}
+
+impl FutureBlocks {
+    /// Return true if a block that is not yet valid at time `now` will be
+    /// within the max hold time.
+    pub fn can_hold(block: &Block, now: Timestamp) -> bool {
+        let earliest_valid_time = block.earliest_valid_time();
+        earliest_valid_time > now
+            && earliest_valid_time <= now + Timestamp::minutes(FUTURE_BLOCK_MAX_HOLD_IN_MINUTES)
+    }
+
+    /// Hold a block until it becomes valid. Returns false if the block was not
+    /// held, either because it cannot be or because the pool is full of
+    /// blocks that become valid sooner.
+    pub fn insert(&mut self, block: Block, now: Timestamp) -> bool {
+        if !Self::can_hold(&block, now) {
+            return false;
+        }
+
+        if self.blocks.len() >= MAX_FUTURE_BLOCKS {
+            let (last_digest, last_valid_time) = self
+                .blocks
+                .iter()
+                .map(|(digest, (held, _))| (*digest, held.earliest_valid_time()))
+                .max_by_key(|(_, valid_time)| *valid_time)
+                .unwrap();
+            if last_valid_time <= block.earliest_valid_time() {
+                return false;
+            }
+            self.blocks.remove(&last_digest);
+        }
+
+        self.blocks.insert(block.hash(), (block, Instant::now()));
+        true
+    }
+
+    /// Remove and return the blocks that are no longer too far in the future,
+    /// lowest first. Blocks held for twice the max hold time, which can happen
+    /// if the local clock is set back, are dropped.
+    pub fn take_released(&mut self, now: Timestamp) -> Vec<Block> {
+        let max_age = Duration::from_secs(2 * 60 * FUTURE_BLOCK_MAX_HOLD_IN_MINUTES as u64);
+        self.blocks.retain(|_, (block, received)| {
+            received.elapsed() <= max_age || block.earliest_valid_time() <= now
+        });
+
+        let released_digests = self
+            .blocks
+            .iter()
+            .filter(|(_, (block, _))| block.earliest_valid_time() <= now)
+            .map(|(digest, _)| *digest)
+            .collect::<Vec<_>>();
+
+        let mut released = released_digests
+            .into_iter()
+            .filter_map(|digest| self.blocks.remove(&digest))
+            .map(|(block, _)| block)
+            .collect::<Vec<_>>();
+        released.sort_by_key(|block| block.kernel.header.height);
+
+        released
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+impl GetSize for FutureBlocks {
+    fn get_stack_size() -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    fn get_heap_size(&self) -> usize {
+        let mut total = self.blocks.capacity() * std::mem::size_of::<(Digest, (Block, Instant))>();
+        for (block, _) in self.blocks.values() {
+            total += block.get_heap_size();
+        }
+
+        total
+    }
+
+    fn get_size(&self) -> usize {
+        Self::get_stack_size() + GetSize::get_heap_size(self)
+    }
+}
+
+#[cfg(test)]
+mod future_blocks_tests {
+    use crate::config_models::network::Network;
+    use crate::models::blockchain::block::FUTUREDATING_LIMIT_IN_HOURS;
+
+    use super::*;
+
+    fn block_ahead_by(now: Timestamp, ahead: Timestamp) -> Block {
+        let mut block = Block::genesis_block(Network::RegTest);
+        block.set_header_timestamp(now + ahead);
+        block
+    }
+
+    #[test]
+    fn slightly_future_blocks_are_held_until_valid() {
+        let now = Timestamp::now();
+        let limit = Timestamp::hours(FUTUREDATING_LIMIT_IN_HOURS);
+        let mut future_blocks = FutureBlocks::default();
+
+        // valid already, or too far ahead to hold
+        assert!(!future_blocks.insert(block_ahead_by(now, Timestamp::minutes(1)), now));
+        let too_far = limit + Timestamp::minutes(FUTURE_BLOCK_MAX_HOLD_IN_MINUTES + 1);
+        assert!(!future_blocks.insert(block_ahead_by(now, too_far), now));
+
+        let block = block_ahead_by(now, limit + Timestamp::minutes(1));
+        assert!(future_blocks.insert(block.clone(), now));
+        assert!(future_blocks.take_released(now).is_empty());
+
+        let released = future_blocks.take_released(now + Timestamp::minutes(2));
+        assert_eq!(
+            vec![block.hash()],
+            released.iter().map(Block::hash).collect::<Vec<_>>()
+        );
+        assert!(future_blocks.is_empty());
+    }
+
+    #[test]
+    fn held_blocks_are_bounded() {
+        let now = Timestamp::now();
+        let limit = Timestamp::hours(FUTUREDATING_LIMIT_IN_HOURS);
+        let mut future_blocks = FutureBlocks::default();
+        for i in 0..MAX_FUTURE_BLOCKS as u64 {
+            let block = block_ahead_by(now, limit + Timestamp::seconds(10 + i));
+            assert!(future_blocks.insert(block, now));
+        }
+
+        // becoming valid after all held blocks, so not held
+        let latest = block_ahead_by(now, limit + Timestamp::minutes(5));
+        assert!(!future_blocks.insert(latest, now));
+
+        // becoming valid before some held blocks, so held in their place
+        let soonest = block_ahead_by(now, limit + Timestamp::seconds(1));
+        assert!(future_blocks.insert(soonest, now));
+        assert_eq!(MAX_FUTURE_BLOCKS, future_blocks.len());
+
+        let released = future_blocks.take_released(now + Timestamp::seconds(2));
+        assert_eq!(1, released.len());
+        assert_eq!(MAX_FUTURE_BLOCKS - 1, future_blocks.len());
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+use std::fmt::Display;
+
+use get_size::GetSize;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::twenty_first::math::digest::Digest;
+
+/// Number of invalid block digests remembered. Remembering more forgets the
+/// least recently offered one.
+pub const INVALID_BLOCK_CACHE_SIZE: usize = 1000;
+
+/// Why a block failed validation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InvalidBlockReason {
+    InsufficientProofOfWork,
+    InvalidBlock,
+}
+
+impl Display for InvalidBlockReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let string = match self {
+            InvalidBlockReason::InsufficientProofOfWork => "insufficient proof-of-work",
+            InvalidBlockReason::InvalidBlock => "invalid block",
+        };
+        write!(f, "{string}")
+    }
+}
+
+/// Proofs digests of blocks that failed validation, so that blocks offered
+/// again can be rejected without validating them again. The block's digest
+/// does not cover its proofs, so keying by it would reject the same block
+/// with valid proofs too, see
+/// [`Block::proofs_digest`](crate::models::blockchain::block::Block::proofs_digest).
+/// Least recently used entries are evicted first.
+///
+/// Only failures that do not depend on the time of validation belong here.
+#[derive(Debug, Default)]
+pub struct InvalidBlockCache {
+    entries: VecDeque<(Digest, InvalidBlockReason)>,
+}
+
+impl InvalidBlockCache {
+    pub fn insert(&mut self, digest: Digest, reason: InvalidBlockReason) {
+        if self.get(digest).is_some() {
+            return;
+        }
+
+        if self.entries.len() == INVALID_BLOCK_CACHE_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((digest, reason));
+    }
+
+    /// Return the reason the block with the given digest is known to be
+    /// invalid, if it is, and mark it as recently used.
+    pub fn get(&mut self, digest: Digest) -> Option<InvalidBlockReason> {
+        let index = self.entries.iter().position(|(d, _)| *d == digest)?;
+        let entry = self.entries.remove(index).unwrap();
+        self.entries.push_back(entry);
+
+        Some(entry.1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The size of the cache when it is full
+    pub fn max_total_size() -> usize {
+        Self::get_stack_size()
+            + INVALID_BLOCK_CACHE_SIZE * std::mem::size_of::<(Digest, InvalidBlockReason)>()
+    }
+}
+
+impl GetSize for InvalidBlockCache {
+    fn get_stack_size() -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    fn get_heap_size(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<(Digest, InvalidBlockReason)>()
+    }
+
+    fn get_size(&self) -> usize {
+        Self::get_stack_size() + GetSize::get_heap_size(self)
+    }
+}
+
+#[cfg(test)]
+mod invalid_blocks_tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_offered_block_is_forgotten() {
+        let digest = |i: u64| Digest::new([i.into(); 5]);
+        let mut cache = InvalidBlockCache::default();
+        for i in 0..INVALID_BLOCK_CACHE_SIZE as u64 {
+            cache.insert(digest(i), InvalidBlockReason::InvalidBlock);
+        }
+
+        // offering the oldest entry again makes it the most recently used one
+        assert_eq!(Some(InvalidBlockReason::InvalidBlock), cache.get(digest(0)));
+
+        cache.insert(
+            digest(1_000_000),
+            InvalidBlockReason::InsufficientProofOfWork,
+        );
+        assert_eq!(INVALID_BLOCK_CACHE_SIZE, cache.len());
+        assert!(cache.get(digest(1)).is_none());
+        assert!(cache.get(digest(0)).is_some());
+        assert_eq!(
+            Some(InvalidBlockReason::InsufficientProofOfWork),
+            cache.get(digest(1_000_000))
+        );
+    }
+}
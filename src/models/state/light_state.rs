@@ -1,5 +1,148 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config_models::network::Network;
 use crate::models::blockchain::block::Block;
+use crate::prelude::twenty_first::math::digest::Digest;
 
 /// LightState is just a thread-safe Block.
 /// (always representing the latest block)
 pub type LightState = Block;
+
+pub const LIGHT_STATE_FILE_NAME: &str = "light_state.dat";
+
+/// The tip as written to disk, with its digest to detect a corrupted file
+#[derive(Serialize, Deserialize)]
+struct PersistedLightState {
+    tip_digest: Digest,
+    tip: Block,
+}
+
+/// Persists the tip of a light node, so that it continues from that tip
+/// after a restart rather than downloading the chain again from genesis.
+#[derive(Debug, Clone)]
+pub struct LightStateStore {
+    path: PathBuf,
+}
+
+impl LightStateStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Restore the persisted tip. Returns the genesis block if none was
+    /// persisted, or if the file cannot be read back, in which case the
+    /// chain is downloaded again.
+    pub async fn load(&self, network: Network) -> LightState {
+        match self.try_load().await {
+            Ok(Some(tip)) => {
+                info!(
+                    "Restored light state at height {} from {}",
+                    tip.kernel.header.height,
+                    self.path.display()
+                );
+                tip
+            }
+            Ok(None) => Block::genesis_block(network),
+            Err(err) => {
+                warn!("Discarding light state, starting from genesis: {err:#}");
+                Block::genesis_block(network)
+            }
+        }
+    }
+
+    async fn try_load(&self) -> Result<Option<LightState>> {
+        let bytes = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read {}", self.path.display()))
+            }
+        };
+
+        let persisted: PersistedLightState = bincode::deserialize(&bytes)
+            .with_context(|| format!("Failed to decode {}", self.path.display()))?;
+        anyhow::ensure!(
+            persisted.tip.hash() == persisted.tip_digest,
+            "Digest of the tip in {} does not match",
+            self.path.display()
+        );
+
+        Ok(Some(persisted.tip))
+    }
+
+    /// Write the tip to disk. The previous tip stays in place until the new
+    /// one is completely written.
+    pub async fn save(&self, light_state: &LightState) -> Result<()> {
+        let persisted = PersistedLightState {
+            tip_digest: light_state.hash(),
+            tip: light_state.clone(),
+        };
+        let bytes = bincode::serialize(&persisted)?;
+
+        let temporary_path = self.path.with_extension("tmp");
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&temporary_path, bytes)
+            .await
+            .with_context(|| format!("Failed to write {}", temporary_path.display()))?;
+        tokio::fs::rename(&temporary_path, &self.path)
+            .await
+            .with_context(|| format!("Failed to replace {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod light_state_tests {
+    use rand::random;
+
+    use super::*;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{make_mock_block, unit_test_data_directory};
+
+    #[tokio::test]
+    async fn tip_is_restored_after_restart() {
+        let network = Network::RegTest;
+        let data_dir = unit_test_data_directory(network).unwrap();
+        let store = LightStateStore::new(data_dir.light_state_file_path());
+        let genesis = Block::genesis_block(network);
+        assert_eq!(genesis.hash(), store.load(network).await.hash());
+
+        let address = WalletSecret::devnet_wallet()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) = make_mock_block(&genesis, None, address, random());
+        let (block_2, _, _) = make_mock_block(&block_1, None, address, random());
+        store.save(&block_1).await.unwrap();
+        store.save(&block_2).await.unwrap();
+
+        let restored = LightStateStore::new(data_dir.light_state_file_path())
+            .load(network)
+            .await;
+        assert_eq!(block_2.hash(), restored.hash());
+        assert_eq!(block_2.kernel, restored.kernel);
+    }
+
+    #[tokio::test]
+    async fn corrupted_light_state_is_discarded() {
+        let network = Network::RegTest;
+        let data_dir = unit_test_data_directory(network).unwrap();
+        let path = data_dir.light_state_file_path();
+        tokio::fs::create_dir_all(path.parent().unwrap())
+            .await
+            .unwrap();
+        tokio::fs::write(&path, b"not a block").await.unwrap();
+
+        let store = LightStateStore::new(path);
+        assert_eq!(
+            Block::genesis_block(network).hash(),
+            store.load(network).await.hash()
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::net::SocketAddr;
+
+use bytesize::ByteSize;
+use get_size::GetSize;
+use serde::{Deserialize, Serialize};
+
+use crate::models::blockchain::block::Block;
+use crate::models::peer::PeerInfo;
+
+use super::invalid_blocks::InvalidBlockCache;
+use super::GlobalState;
+
+/// RAM used by one subsystem
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryUsage {
+    pub bytes: u64,
+
+    /// The size above which the subsystem evicts entries, if it has one
+    pub soft_limit: Option<u64>,
+}
+
+impl MemoryUsage {
+    fn new(bytes: usize, soft_limit: Option<usize>) -> Self {
+        Self {
+            bytes: bytes as u64,
+            soft_limit: soft_limit.map(|limit| limit as u64),
+        }
+    }
+}
+
+impl Display for MemoryUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.soft_limit {
+            Some(limit) => write!(f, "{} of {}", ByteSize(self.bytes), ByteSize(limit)),
+            None => write!(f, "{}", ByteSize(self.bytes)),
+        }
+    }
+}
+
+/// Sizes of the block buffers that the main and peer threads keep outside of
+/// the global state, as last recorded by the threads
+#[derive(Clone, Debug, Default)]
+pub struct BufferSizes {
+    /// Blocks the main thread holds until they are no longer too far in the
+    /// future
+    pub future_blocks: usize,
+
+    /// Orphan blocks each peer thread holds while it requests their parents
+    orphan_blocks: HashMap<SocketAddr, usize>,
+}
+
+impl BufferSizes {
+    /// Record the orphan blocks the thread of the given peer holds
+    pub fn record_orphan_blocks(&mut self, peer_address: SocketAddr, blocks: &[Block]) {
+        if blocks.is_empty() {
+            self.orphan_blocks.remove(&peer_address);
+            return;
+        }
+
+        let size = blocks.capacity() * std::mem::size_of::<Block>()
+            + blocks
+                .iter()
+                .map(|block| block.get_heap_size())
+                .sum::<usize>();
+        self.orphan_blocks.insert(peer_address, size);
+    }
+
+    /// Forget the buffers of a disconnected peer
+    pub fn remove_peer(&mut self, peer_address: SocketAddr) {
+        self.orphan_blocks.remove(&peer_address);
+    }
+
+    /// Size of the orphan blocks held by all peer threads
+    pub fn orphan_blocks(&self) -> usize {
+        self.orphan_blocks.values().sum()
+    }
+}
+
+/// The RAM used by the node's in-memory pools and caches, as estimated by
+/// [`GetSize`]. Databases and their caches are not included.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryStatus {
+    pub mempool: MemoryUsage,
+    pub stem_pool: MemoryUsage,
+    pub expected_utxos: MemoryUsage,
+    pub prepared_sends: MemoryUsage,
+    pub peer_map: MemoryUsage,
+    pub future_blocks: MemoryUsage,
+    pub invalid_blocks: MemoryUsage,
+
+    /// Blocks held by peer threads while they request their parents
+    pub orphan_blocks: MemoryUsage,
+}
+
+impl MemoryStatus {
+    pub fn from_state(global_state: &GlobalState) -> Self {
+        let wallet_state = &global_state.wallet_state;
+        let stem_pool = &global_state.net.stem_pool;
+        let peer_map = &global_state.net.peer_map;
+        let buffer_sizes = &global_state.net.buffer_sizes;
+
+        let peer_map_size = peer_map.capacity() * std::mem::size_of::<(SocketAddr, PeerInfo)>()
+            + peer_map
+                .values()
+                .map(|peer_info| peer_info.get_heap_size())
+                .sum::<usize>();
+
+        Self {
+            mempool: MemoryUsage::new(
+                global_state.mempool.get_size(),
+                Some(global_state.mempool.max_total_size()),
+            ),
+            stem_pool: MemoryUsage::new(stem_pool.get_size(), Some(stem_pool.max_total_size())),
+            expected_utxos: MemoryUsage::new(
+                wallet_state.expected_utxos.get_size(),
+                Some(wallet_state.expected_utxos.max_total_size()),
+            ),
+            prepared_sends: MemoryUsage::new(wallet_state.prepared_sends.get_size(), None),
+            peer_map: MemoryUsage::new(peer_map_size, None),
+            future_blocks: MemoryUsage::new(buffer_sizes.future_blocks, None),
+            invalid_blocks: MemoryUsage::new(
+                global_state.invalid_blocks.get_size(),
+                Some(InvalidBlockCache::max_total_size()),
+            ),
+            orphan_blocks: MemoryUsage::new(buffer_sizes.orphan_blocks(), None),
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.mempool.bytes
+            + self.stem_pool.bytes
+            + self.expected_utxos.bytes
+            + self.prepared_sends.bytes
+            + self.peer_map.bytes
+            + self.future_blocks.bytes
+            + self.invalid_blocks.bytes
+            + self.orphan_blocks.bytes
+    }
+}
+
+impl Display for MemoryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "mempool:        {}", self.mempool)?;
+        writeln!(f, "stem pool:      {}", self.stem_pool)?;
+        writeln!(f, "expected UTXOs: {}", self.expected_utxos)?;
+        writeln!(f, "prepared sends: {}", self.prepared_sends)?;
+        writeln!(f, "peers:          {}", self.peer_map)?;
+        writeln!(f, "future blocks:  {}", self.future_blocks)?;
+        writeln!(f, "invalid blocks: {}", self.invalid_blocks)?;
+        writeln!(f, "orphan blocks:  {}", self.orphan_blocks)?;
+        write!(f, "total:          {}", ByteSize(self.total_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod memory_status_tests {
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::get_dummy_socket_address;
+    use crate::tests::shared::mock_genesis_global_state;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_status_reports_limits_and_peers() {
+        let network = Network::RegTest;
+        let state_lock = mock_genesis_global_state(network, 2, WalletSecret::new_random()).await;
+        let global_state = state_lock.lock_guard().await;
+        let status = MemoryStatus::from_state(&global_state);
+
+        assert_eq!(global_state.mempool.get_size() as u64, status.mempool.bytes);
+        assert_eq!(
+            Some(global_state.mempool.max_total_size() as u64),
+            status.mempool.soft_limit
+        );
+        assert_eq!(
+            Some(global_state.net.stem_pool.max_total_size() as u64),
+            status.stem_pool.soft_limit
+        );
+        assert!(status.peer_map.bytes >= 2 * std::mem::size_of::<PeerInfo>() as u64);
+        assert!(status.total_bytes() >= status.mempool.bytes + status.peer_map.bytes);
+        assert_eq!(
+            Some(InvalidBlockCache::max_total_size() as u64),
+            status.invalid_blocks.soft_limit
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_status_includes_thread_buffers() {
+        let network = Network::RegTest;
+        let state_lock = mock_genesis_global_state(network, 2, WalletSecret::new_random()).await;
+        let mut global_state = state_lock.lock_guard_mut().await;
+        let peer_address = get_dummy_socket_address(0);
+        let orphans = vec![Block::genesis_block(network)];
+
+        global_state.net.buffer_sizes.future_blocks = 1000;
+        global_state
+            .net
+            .buffer_sizes
+            .record_orphan_blocks(peer_address, &orphans);
+        let status = MemoryStatus::from_state(&global_state);
+        assert_eq!(1000, status.future_blocks.bytes);
+        assert!(status.orphan_blocks.bytes >= std::mem::size_of::<Block>() as u64);
+        assert!(
+            status.total_bytes()
+                >= status.future_blocks.bytes
+                    + status.orphan_blocks.bytes
+                    + status.invalid_blocks.bytes
+        );
+
+        global_state.net.buffer_sizes.remove_peer(peer_address);
+        let status = MemoryStatus::from_state(&global_state);
+        assert_eq!(0, status.orphan_blocks.bytes);
+    }
+}
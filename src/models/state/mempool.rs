@@ -17,10 +17,10 @@ use crate::{
     util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator,
 };
 
-use bytesize::ByteSize;
 use get_size::GetSize;
 use num_traits::Zero;
 use priority_queue::{double_priority_queue::iterators::IntoSortedIter, DoublePriorityQueue};
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{hash_map::RandomState, HashMap, HashSet},
     iter::Rev,
@@ -32,6 +32,8 @@ use crate::models::blockchain::block::Block;
 use crate::models::blockchain::shared::Hash;
 use crate::models::blockchain::transaction::Transaction;
 
+use super::mempool_policy::MempoolPolicy;
+
 /// `FeeDensity` is a measure of 'Fee/Bytes' or 'reward per storage unit' for a
 /// transactions.  Different strategies are possible for selecting transactions
 /// to mine, but a simple one is to pick transactions in descending order of
@@ -51,22 +53,40 @@ use crate::models::blockchain::transaction::Transaction;
 /// TransactionC }.
 use num_rational::BigRational as FeeDensity;
 
-// 72 hours in secs
-pub const MEMPOOL_TX_THRESHOLD_AGE_IN_SECS: u64 = 72 * 60 * 60;
-// 5 minutes in secs
-pub const MEMPOOL_IGNORE_TRANSACTIONS_THIS_MANY_SECS_AHEAD: u64 = 5 * 60;
-
 pub const TRANSACTION_NOTIFICATION_AGE_LIMIT_IN_SECS: u64 = 60 * 60 * 24;
 
 type LookupItem<'a> = (Digest, &'a Transaction);
 
+/// What the mempool knows about one of its transactions.
+///
+/// Inputs are removal records of outputs already in the mutator set, so a
+/// transaction cannot spend the outputs of another unconfirmed transaction,
+/// and mempool transactions have no ancestors or descendants to report.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MempoolEntry {
+    pub fee: NeptuneCoins,
+
+    /// Size of the serialized transaction, in bytes
+    pub size: usize,
+
+    /// Fee per byte of serialized transaction, in nau
+    pub fee_density: f64,
+
+    pub inserted_at: Timestamp,
+    pub time_in_pool: Timestamp,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, GetSize)]
 pub struct Mempool {
-    max_total_size: usize,
+    #[get_size(ignore)]
+    policy: MempoolPolicy,
 
     // Maintain for constant lookup
     tx_dictionary: HashMap<Digest, Transaction>,
 
+    // When each transaction entered the mempool
+    insertion_times: HashMap<Digest, Timestamp>,
+
     // Maintain for fast min and max
     #[get_size(ignore)] // This is relatively small compared to `LookupTable`
     queue: DoublePriorityQueue<Digest, FeeDensity>,
@@ -74,13 +94,13 @@ pub struct Mempool {
 
 impl Mempool {
     /// instantiate a new `Mempool`
-    pub fn new(max_total_size: ByteSize) -> Self {
+    pub fn new(policy: MempoolPolicy) -> Self {
         let table = Default::default();
         let queue = Default::default();
-        let max_total_size = max_total_size.0.try_into().unwrap();
         Self {
-            max_total_size,
+            policy,
             tx_dictionary: table,
+            insertion_times: Default::default(),
             queue,
         }
     }
@@ -99,6 +119,22 @@ impl Mempool {
         self.tx_dictionary.get(&transaction_id)
     }
 
+    /// Describe a transaction in the mempool, as of now
+    pub fn entry(&self, transaction_id: Digest) -> Option<MempoolEntry> {
+        let transaction = self.get(transaction_id)?;
+        let inserted_at = self.insertion_times[&transaction_id];
+        let size = bincode::serialize(transaction).unwrap().len();
+        let fee_density = transaction.kernel.fee.to_nau_f64() / size.max(1) as f64;
+
+        Some(MempoolEntry {
+            fee: transaction.kernel.fee,
+            size,
+            fee_density,
+            inserted_at,
+            time_in_pool: Timestamp::now() - inserted_at,
+        })
+    }
+
     /// Returns `Some(txid, transaction)` iff a transcation conflicts with a block that's already in
     /// the mempool. Returns `None` otherwise.
     fn transaction_conflicts_with(
@@ -138,13 +174,15 @@ impl Mempool {
         // If transaction to be inserted conflicts with a transaction that's already
         // in the mempool we preserve only the one with the highest fee density.
         if let Some((txid, tx)) = self.transaction_conflicts_with(transaction) {
-            if tx.fee_density() < transaction.fee_density() {
-                // If new transaction has a higher fee density than the one previously seen
-                // remove the old one.
+            if self
+                .policy
+                .replaces(&transaction.fee_density(), &tx.fee_density())
+            {
+                // If new transaction has a high enough fee density compared to the
+                // one previously seen, remove the old one.
                 self.remove(txid);
             } else {
-                // If new transaction has a lower fee density than the one previous seen,
-                // ignore it. Stop execution here.
+                // Otherwise, ignore it. Stop execution here.
                 return Some(txid);
             }
         };
@@ -154,6 +192,9 @@ impl Mempool {
         self.queue.push(transaction_id, transaction.fee_density());
         self.tx_dictionary
             .insert(transaction_id, transaction.to_owned());
+        self.insertion_times
+            .entry(transaction_id)
+            .or_insert_with(Timestamp::now);
         assert_eq!(
             self.tx_dictionary.len(),
             self.queue.len(),
@@ -172,6 +213,7 @@ impl Mempool {
     pub fn remove(&mut self, transaction_id: Digest) -> Option<Transaction> {
         if let rv @ Some(_) = self.tx_dictionary.remove(&transaction_id) {
             self.queue.remove(&transaction_id);
+            self.insertion_times.remove(&transaction_id);
             debug_assert_eq!(self.tx_dictionary.len(), self.queue.len());
             return rv;
         }
@@ -179,6 +221,20 @@ impl Mempool {
         None
     }
 
+    /// The size above which the lowest fee density transactions are evicted, in bytes
+    pub fn max_total_size(&self) -> usize {
+        self.policy
+            .max_total_size
+            .0
+            .try_into()
+            .unwrap_or(usize::MAX)
+    }
+
+    /// The rules by which transactions are accepted, replaced and evicted
+    pub fn policy(&self) -> &MempoolPolicy {
+        &self.policy
+    }
+
     /// Return the number of transactions currently stored in the Mempool.
     /// Computes in O(1)
     pub fn len(&self) -> usize {
@@ -194,7 +250,16 @@ impl Mempool {
 
     /// Return a vector with copies of the transactions, in descending order by fee
     /// density and using at most `remaining_storage` bytes.
-    pub fn get_transactions_for_block(&self, mut remaining_storage: usize) -> Vec<Transaction> {
+    ///
+    /// Transactions timestamped after `block_timestamp` are left out, as a block
+    /// may not include transactions from its future. The merged transaction takes
+    /// the latest timestamp of those it merges, so including one would invalidate
+    /// the block.
+    pub fn get_transactions_for_block(
+        &self,
+        mut remaining_storage: usize,
+        block_timestamp: Timestamp,
+    ) -> Vec<Transaction> {
         let mut transactions = vec![];
         let mut _fee_acc = NeptuneCoins::zero();
 
@@ -205,6 +270,11 @@ impl Mempool {
             }
 
             if let Some(transaction_ptr) = self.get(transaction_digest) {
+                // Not yet valid
+                if transaction_ptr.kernel.timestamp > block_timestamp {
+                    continue;
+                }
+
                 let transaction_copy = transaction_ptr.to_owned();
                 let transaction_size = transaction_copy.get_size();
 
@@ -228,6 +298,7 @@ impl Mempool {
     pub fn pop_max(&mut self) -> Option<(Transaction, FeeDensity)> {
         if let Some((transaction_digest, fee_density)) = self.queue.pop_max() {
             let transaction = self.tx_dictionary.remove(&transaction_digest).unwrap();
+            self.insertion_times.remove(&transaction_digest);
             debug_assert_eq!(self.tx_dictionary.len(), self.queue.len());
             Some((transaction, fee_density))
         } else {
@@ -239,6 +310,7 @@ impl Mempool {
     pub fn pop_min(&mut self) -> Option<(Transaction, FeeDensity)> {
         if let Some((transaction_digest, fee_density)) = self.queue.pop_min() {
             let transaction = self.tx_dictionary.remove(&transaction_digest).unwrap();
+            self.insertion_times.remove(&transaction_digest);
             debug_assert_eq!(self.tx_dictionary.len(), self.queue.len());
             Some((transaction, fee_density))
         } else {
@@ -273,7 +345,7 @@ impl Mempool {
     /// Prune based on `Transaction.timestamp`
     /// Computes in O(n)
     pub fn prune_stale_transactions(&mut self) {
-        let cutoff = Timestamp::now() - Timestamp::seconds(MEMPOOL_TX_THRESHOLD_AGE_IN_SECS);
+        let cutoff = Timestamp::now() - self.policy.max_age;
 
         let keep = |(_transaction_id, transaction): LookupItem| -> bool {
             cutoff < transaction.kernel.timestamp
@@ -345,7 +417,7 @@ impl Mempool {
     /// Likely computes in O(n)
     fn shrink_to_max_size(&mut self) {
         // Repeately remove the least valuable transaction
-        while self.get_size() > self.max_total_size && self.pop_min().is_some() {
+        while self.get_size() > self.max_total_size() && self.pop_min().is_some() {
             continue;
         }
 
@@ -356,7 +428,8 @@ impl Mempool {
     /// Computes in O(n) (Likely)
     fn shrink_to_fit(&mut self) {
         self.queue.shrink_to_fit();
-        self.tx_dictionary.shrink_to_fit()
+        self.tx_dictionary.shrink_to_fit();
+        self.insertion_times.shrink_to_fit()
     }
 
     /// Produce a sorted iterator over a snapshot of the Double-Ended Priority Queue.
@@ -364,10 +437,11 @@ impl Mempool {
     /// # Example
     ///
     /// ```
+    /// use neptune_core::config_models::network::Network;
     /// use neptune_core::models::state::mempool::Mempool;
-    /// use bytesize::ByteSize;
+    /// use neptune_core::models::state::mempool_policy::MempoolPolicy;
     ///
-    /// let mempool = Mempool::new(ByteSize::gb(1));
+    /// let mempool = Mempool::new(MempoolPolicy::for_network(Network::Main));
     /// // insert transactions here.
     /// let mut most_valuable_transactions = vec![];
     /// for (transaction_digest, fee_density) in mempool.get_sorted_iter() {
@@ -417,8 +491,8 @@ mod tests {
 
     #[tokio::test]
     pub async fn insert_then_get_then_remove_then_get() {
-        let mut mempool = Mempool::new(ByteSize::gb(1));
         let network = Network::Alpha;
+        let mut mempool = Mempool::new(MempoolPolicy::for_network(network));
         let wallet_state = mock_genesis_wallet_state(WalletSecret::devnet_wallet(), network).await;
         let transaction = make_mock_transaction_with_wallet(
             vec![],
@@ -436,9 +510,15 @@ mod tests {
         assert_eq!(Some(&transaction), transaction_get_option);
         assert!(mempool.contains(transaction_digest));
 
+        let entry = mempool.entry(transaction_digest).unwrap();
+        assert_eq!(transaction.kernel.fee, entry.fee);
+        assert_eq!(bincode::serialize(&transaction).unwrap().len(), entry.size);
+        assert!(entry.inserted_at <= Timestamp::now());
+
         let transaction_remove_option = mempool.remove(transaction_digest);
         assert_eq!(Some(transaction), transaction_remove_option);
         assert!(!mempool.contains(transaction_digest));
+        assert!(mempool.entry(transaction_digest).is_none());
 
         let transaction_second_remove_option = mempool.remove(transaction_digest);
         assert_eq!(None, transaction_second_remove_option);
@@ -447,7 +527,7 @@ mod tests {
 
     // Create a mempool with n transactions.
     async fn setup(transactions_count: u32, network: Network) -> Mempool {
-        let mut mempool = Mempool::new(ByteSize::gb(1));
+        let mut mempool = Mempool::new(MempoolPolicy::for_network(network));
         let wallet_state = mock_genesis_wallet_state(WalletSecret::devnet_wallet(), network).await;
         for i in 0..transactions_count {
             let t = make_mock_transaction_with_wallet(
@@ -470,7 +550,9 @@ mod tests {
 
         let max_fee_density: FeeDensity = FeeDensity::new(BigInt::from(u128::MAX), BigInt::from(1));
         let mut prev_fee_density = max_fee_density;
-        for curr_transaction in mempool.get_transactions_for_block(SIZE_20MB_IN_BYTES) {
+        for curr_transaction in
+            mempool.get_transactions_for_block(SIZE_20MB_IN_BYTES, Timestamp::now())
+        {
             let curr_fee_density = curr_transaction.fee_density();
             assert!(curr_fee_density <= prev_fee_density);
             prev_fee_density = curr_fee_density;
@@ -478,6 +560,39 @@ mod tests {
         assert!(!mempool.is_empty())
     }
 
+    #[tokio::test]
+    async fn transactions_from_the_future_of_the_block_are_left_out() {
+        let network = Network::RegTest;
+        let mut mempool = Mempool::new(MempoolPolicy::for_network(network));
+        let wallet_state = mock_genesis_wallet_state(WalletSecret::devnet_wallet(), network).await;
+        let now = Timestamp::now();
+        let current = make_mock_transaction_with_wallet(
+            vec![],
+            vec![],
+            NeptuneCoins::new(1),
+            &wallet_state,
+            Some(now),
+        );
+        let later = make_mock_transaction_with_wallet(
+            vec![],
+            vec![],
+            NeptuneCoins::new(2),
+            &wallet_state,
+            Some(now + Timestamp::minutes(2)),
+        );
+        mempool.insert(&current);
+        mempool.insert(&later);
+
+        assert_eq!(
+            vec![current.clone()],
+            mempool.get_transactions_for_block(SIZE_20MB_IN_BYTES, now)
+        );
+        assert_eq!(
+            vec![later, current],
+            mempool.get_transactions_for_block(SIZE_20MB_IN_BYTES, now + Timestamp::minutes(2))
+        );
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn get_sorted_iter() {
@@ -498,7 +613,7 @@ mod tests {
     async fn prune_stale_transactions() {
         let wallet_state =
             mock_genesis_wallet_state(WalletSecret::devnet_wallet(), Network::Alpha).await;
-        let mut mempool = Mempool::new(ByteSize::gb(1));
+        let mut mempool = Mempool::new(MempoolPolicy::for_network(Network::Alpha));
         assert!(
             mempool.is_empty(),
             "Mempool must be empty after initialization"
@@ -596,7 +711,7 @@ mod tests {
             .add_expected_utxo(
                 coinbase_utxo_1,
                 cb_sender_randomness_1,
-                other_receiver_spending_key.privacy_preimage,
+                other_receiver_spending_key.privacy_preimage.expose(),
                 UtxoNotifier::OwnMiner,
             )
             .expect("UTXO notification from miner must be accepted");
@@ -639,7 +754,7 @@ mod tests {
             .await?;
 
         // Add this transaction to the mempool
-        let mut mempool = Mempool::new(ByteSize::gb(1));
+        let mut mempool = Mempool::new(MempoolPolicy::for_network(network));
         mempool.insert(&tx_by_preminer);
 
         // Create another transaction that's valid to be included in block 2, but isn't actually
@@ -686,7 +801,7 @@ mod tests {
         // Create a new block to verify that the non-mined transaction contains
         // updated and valid-again mutator set data
         let mut tx_by_other_updated: Transaction =
-            mempool.get_transactions_for_block(usize::MAX)[0].clone();
+            mempool.get_transactions_for_block(usize::MAX, now + seven_months)[0].clone();
 
         debug!(
             "mempool now has transaction relative to mutator set hash {}",
@@ -745,7 +860,8 @@ mod tests {
         let (mut block_14, _, _) =
             make_mock_block(&previous_block, None, other_receiver_address, rng.gen());
         assert_eq!(Into::<BlockHeight>::into(14), block_14.kernel.header.height);
-        tx_by_other_updated = mempool.get_transactions_for_block(usize::MAX)[0].clone();
+        tx_by_other_updated =
+            mempool.get_transactions_for_block(usize::MAX, now + seven_months)[0].clone();
         block_14
             .accumulate_transaction(
                 tx_by_other_updated,
@@ -0,0 +1,306 @@
+//! The rules by which the mempool accepts, replaces and evicts transactions.
+//!
+//! These are relay policy, not consensus: a block may contain transactions
+//! that this node would not have accepted into its mempool. Each network has
+//! its defaults, which `--mempool-policy` can override from a JSON file, e.g.
+//!
+//! ```json
+//! { "min_fee": "0.001", "max_transaction_size": "2M", "max_age_secs": 86400,
+//!   "max_data_carrier_size": 0 }
+//! ```
+//!
+//! Some policies of other chains do not apply here. Output amounts are
+//! hidden, so there is no dust limit. Inputs spend outputs already in the
+//! mutator set, so no transaction depends on another, and there are no
+//! orphan transactions to limit.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use bytesize::ByteSize;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::config_models::cli_args::Args;
+use crate::config_models::network::Network;
+use crate::models::blockchain::transaction::data_carrier::{DataCarrier, MAX_DATA_CARRIER_SIZE};
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MempoolPolicy {
+    /// The size above which the lowest fee density transactions are evicted
+    pub max_total_size: ByteSize,
+
+    pub min_fee: NeptuneCoins,
+
+    /// Max size of a serialized transaction
+    pub max_transaction_size: ByteSize,
+
+    /// Transactions with older timestamps are rejected, and pruned
+    pub max_age: Timestamp,
+
+    /// Transactions with timestamps further ahead of our clock are rejected.
+    /// Those accepted are left out of block templates until the block
+    /// timestamp catches up with them.
+    pub max_future_drift: Timestamp,
+
+    /// By how much a transaction must beat the fee density of a transaction
+    /// it conflicts with, to replace it
+    pub replacement_fee_density_increase_percent: u32,
+
+    /// Max number of bytes in the data carriers of a transaction, summed. Zero
+    /// rejects all transactions with data carriers.
+    pub max_data_carrier_size: usize,
+}
+
+/// Why a transaction is not accepted into the mempool
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum PolicyViolation {
+    #[error("fee {fee} is below the minimum of {min_fee}")]
+    FeeTooLow {
+        fee: NeptuneCoins,
+        min_fee: NeptuneCoins,
+    },
+
+    #[error("size {size} exceeds the maximum of {max_size}")]
+    TooLarge { size: ByteSize, max_size: ByteSize },
+
+    #[error("timestamp {0} is too old")]
+    TooOld(Timestamp),
+
+    #[error("timestamp {0} is too far in the future")]
+    TooFarInFuture(Timestamp),
+
+    #[error("data carriers hold {size} bytes, exceeding the maximum of {max_size}")]
+    DataCarrierTooLarge { size: usize, max_size: usize },
+
+    #[error("a public announcement is flagged as a data carrier but does not decode as one")]
+    MalformedDataCarrier,
+}
+
+/// The overrides of `--mempool-policy`. Amounts and sizes are strings, as
+/// on the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MempoolPolicyFile {
+    min_fee: Option<String>,
+    max_transaction_size: Option<String>,
+    max_age_secs: Option<u64>,
+    max_future_drift_secs: Option<u64>,
+    replacement_fee_density_increase_percent: Option<u32>,
+    max_data_carrier_size: Option<usize>,
+}
+
+impl MempoolPolicy {
+    pub fn for_network(network: Network) -> Self {
+        let max_total_size = ByteSize::gb(1);
+        let max_age = Timestamp::hours(72);
+        let max_future_drift = Timestamp::minutes(5);
+        match network {
+            Network::Main | Network::Testnet | Network::Beta => Self {
+                max_total_size,
+                min_fee: NeptuneCoins::zero(),
+                max_transaction_size: ByteSize::mb(8),
+                max_age,
+                max_future_drift,
+                replacement_fee_density_increase_percent: 10,
+                max_data_carrier_size: MAX_DATA_CARRIER_SIZE,
+            },
+
+            // Development networks take what tests and developers throw at
+            // them
+            Network::Alpha | Network::RegTest => Self {
+                max_total_size,
+                min_fee: NeptuneCoins::zero(),
+                max_transaction_size: ByteSize::mb(64),
+                max_age,
+                max_future_drift,
+                replacement_fee_density_increase_percent: 0,
+                max_data_carrier_size: 4 * MAX_DATA_CARRIER_SIZE,
+            },
+        }
+    }
+
+    /// The defaults of the network, overridden by `--mempool-policy` and
+    /// `--max-mempool-size`
+    pub fn from_cli(cli: &Args) -> Result<Self> {
+        let mut policy = Self::for_network(cli.network);
+        if let Some(path) = &cli.mempool_policy {
+            policy = policy.overridden_by_file(path)?;
+        }
+        policy.max_total_size = cli.max_mempool_size;
+
+        Ok(policy)
+    }
+
+    fn overridden_by_file(self, path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read mempool policy {}", path.display()))?;
+        let overrides: MempoolPolicyFile = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse mempool policy {}", path.display()))?;
+
+        self.overridden_by(overrides)
+    }
+
+    fn overridden_by(mut self, overrides: MempoolPolicyFile) -> Result<Self> {
+        if let Some(min_fee) = overrides.min_fee {
+            self.min_fee = NeptuneCoins::from_str(&min_fee)?;
+        }
+        if let Some(max_transaction_size) = overrides.max_transaction_size {
+            self.max_transaction_size = ByteSize::from_str(&max_transaction_size)
+                .map_err(|err| anyhow::anyhow!("Invalid max_transaction_size: {err}"))?;
+        }
+        if let Some(max_age_secs) = overrides.max_age_secs {
+            self.max_age = Timestamp::seconds(max_age_secs);
+        }
+        if let Some(max_future_drift_secs) = overrides.max_future_drift_secs {
+            self.max_future_drift = Timestamp::seconds(max_future_drift_secs);
+        }
+        if let Some(percent) = overrides.replacement_fee_density_increase_percent {
+            self.replacement_fee_density_increase_percent = percent;
+        }
+        if let Some(max_data_carrier_size) = overrides.max_data_carrier_size {
+            self.max_data_carrier_size = max_data_carrier_size;
+        }
+
+        Ok(self)
+    }
+
+    /// Check a transaction against the policy, before it is relayed or
+    /// inserted into the mempool
+    pub fn check(&self, transaction: &Transaction, now: Timestamp) -> Result<(), PolicyViolation> {
+        let fee = transaction.kernel.fee;
+        if fee < self.min_fee {
+            return Err(PolicyViolation::FeeTooLow {
+                fee,
+                min_fee: self.min_fee,
+            });
+        }
+
+        let size = ByteSize(bincode::serialized_size(transaction).unwrap_or(u64::MAX));
+        if size > self.max_transaction_size {
+            return Err(PolicyViolation::TooLarge {
+                size,
+                max_size: self.max_transaction_size,
+            });
+        }
+
+        let timestamp = transaction.kernel.timestamp;
+        if timestamp < now - self.max_age {
+            return Err(PolicyViolation::TooOld(timestamp));
+        }
+        if timestamp > now + self.max_future_drift {
+            return Err(PolicyViolation::TooFarInFuture(timestamp));
+        }
+
+        let mut data_carrier_size = 0;
+        for announcement in &transaction.kernel.public_announcements {
+            if !DataCarrier::is_marked(announcement) {
+                continue;
+            }
+            let Some(data_carrier) = DataCarrier::from_public_announcement(announcement) else {
+                return Err(PolicyViolation::MalformedDataCarrier);
+            };
+            data_carrier_size += data_carrier.data.len();
+        }
+        if data_carrier_size > self.max_data_carrier_size {
+            return Err(PolicyViolation::DataCarrierTooLarge {
+                size: data_carrier_size,
+                max_size: self.max_data_carrier_size,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Whether a transaction with fee density `new` replaces a conflicting
+    /// one with fee density `old`
+    pub fn replaces(&self, new: &BigRational, old: &BigRational) -> bool {
+        let increase = BigRational::new(
+            BigInt::from(100 + self.replacement_fee_density_increase_percent),
+            BigInt::from(100),
+        );
+
+        new > old && *new >= old * increase
+    }
+}
+
+#[cfg(test)]
+mod mempool_policy_tests {
+    use crate::tests::shared::make_mock_transaction;
+
+    use super::*;
+
+    #[test]
+    fn overrides_replace_network_defaults() {
+        let defaults = MempoolPolicy::for_network(Network::Main);
+        let overrides: MempoolPolicyFile =
+            serde_json::from_str(r#"{ "min_fee": "0.5", "max_transaction_size": "2M" }"#).unwrap();
+        let policy = defaults.clone().overridden_by(overrides).unwrap();
+
+        assert_eq!(NeptuneCoins::from_str("0.5").unwrap(), policy.min_fee);
+        assert_eq!(ByteSize::mb(2), policy.max_transaction_size);
+        assert_eq!(defaults.max_age, policy.max_age);
+
+        assert!(serde_json::from_str::<MempoolPolicyFile>(r#"{ "dust": "1" }"#).is_err());
+    }
+
+    #[test]
+    fn data_carriers_are_limited_in_size() {
+        let with_data = |sizes: &[usize]| {
+            let mut transaction = make_mock_transaction(vec![], vec![]);
+            transaction.kernel.public_announcements = sizes
+                .iter()
+                .map(|size| {
+                    DataCarrier::new(vec![7; *size])
+                        .unwrap()
+                        .to_public_announcement()
+                })
+                .collect();
+            transaction
+        };
+        let now = Timestamp::now();
+        let policy = MempoolPolicy::for_network(Network::Main);
+        assert!(policy
+            .check(&with_data(&[MAX_DATA_CARRIER_SIZE]), now)
+            .is_ok());
+        assert_eq!(
+            Err(PolicyViolation::DataCarrierTooLarge {
+                size: MAX_DATA_CARRIER_SIZE + 1,
+                max_size: MAX_DATA_CARRIER_SIZE
+            }),
+            policy.check(&with_data(&[MAX_DATA_CARRIER_SIZE, 1]), now)
+        );
+
+        let mut malformed = with_data(&[10]);
+        malformed.kernel.public_announcements[0].message.pop();
+        assert_eq!(
+            Err(PolicyViolation::MalformedDataCarrier),
+            policy.check(&malformed, now)
+        );
+
+        let overrides: MempoolPolicyFile =
+            serde_json::from_str(r#"{ "max_data_carrier_size": 0 }"#).unwrap();
+        let policy = policy.overridden_by(overrides).unwrap();
+        assert!(policy.check(&with_data(&[]), now).is_ok());
+        assert!(policy.check(&with_data(&[1]), now).is_err());
+    }
+
+    #[test]
+    fn replacements_must_beat_the_required_increase() {
+        let density = |fee: i64| BigRational::new(BigInt::from(fee), BigInt::from(1));
+        let policy = MempoolPolicy::for_network(Network::Main);
+        assert!(!policy.replaces(&density(105), &density(100)));
+        assert!(policy.replaces(&density(110), &density(100)));
+
+        let policy = MempoolPolicy::for_network(Network::RegTest);
+        assert!(!policy.replaces(&density(100), &density(100)));
+        assert!(policy.replaces(&density(101), &density(100)));
+    }
+}
@@ -0,0 +1,215 @@
+//! A compact history of the node's vital metrics, for spotting trends without
+//! an external monitoring system.
+//!
+//! Snapshots are appended to a ring buffer in a single file in the data
+//! directory, which overwrites the oldest snapshot once it holds
+//! [`METRICS_HISTORY_CAPACITY`] of them, so the file never grows beyond a few
+//! hundred kilobytes. The file starts with the number of snapshots ever
+//! written, followed by fixed-size slots.
+
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::models::blockchain::block::block_header::TARGET_DIFFICULTY_U32_SIZE;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::prelude::U32s;
+
+pub const METRICS_HISTORY_FILE_NAME: &str = "metrics_history.dat";
+
+/// Number of snapshots kept: 30 days at one snapshot every 10 minutes
+pub const METRICS_HISTORY_CAPACITY: u64 = 30 * 24 * 6;
+
+const HEADER_LENGTH: u64 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub taken_at: Timestamp,
+    pub tip_height: BlockHeight,
+    pub difficulty: U32s<TARGET_DIFFICULTY_U32_SIZE>,
+    pub peer_count: u64,
+    pub mempool_transaction_count: u64,
+    pub mempool_size: u64,
+
+    /// Bytes used by the data directory, or zero if it could not be measured
+    pub disk_usage: u64,
+}
+
+impl MetricsSnapshot {
+    /// All fields have fixed-size encodings, so every snapshot encodes to this
+    /// length
+    fn encoded_length() -> u64 {
+        let sample = Self {
+            taken_at: Timestamp::millis(0),
+            tip_height: BlockHeight::genesis(),
+            difficulty: U32s::zero(),
+            peer_count: 0,
+            mempool_transaction_count: 0,
+            mempool_size: 0,
+            disk_usage: 0,
+        };
+
+        bincode::serialized_size(&sample).expect("snapshots can be encoded")
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MetricsHistory {
+    path: PathBuf,
+    capacity: u64,
+}
+
+impl MetricsHistory {
+    pub fn new(path: &Path) -> Self {
+        Self::with_capacity(path, METRICS_HISTORY_CAPACITY)
+    }
+
+    fn with_capacity(path: &Path, capacity: u64) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            capacity,
+        }
+    }
+
+    /// Append a snapshot, overwriting the oldest one if the history is full
+    pub async fn append(&self, snapshot: &MetricsSnapshot) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&self.path)
+            .await?;
+        let written = Self::read_written_count(&mut file).await?;
+
+        let slot = written % self.capacity;
+        file.seek(SeekFrom::Start(
+            HEADER_LENGTH + slot * MetricsSnapshot::encoded_length(),
+        ))
+        .await?;
+        file.write_all(&bincode::serialize(snapshot)?).await?;
+
+        file.seek(SeekFrom::Start(0)).await?;
+        file.write_all(&(written + 1).to_le_bytes()).await?;
+        file.flush().await?;
+
+        Ok(())
+    }
+
+    /// The snapshots taken at or after `since`, oldest first
+    pub async fn read_since(&self, since: Timestamp) -> Result<Vec<MetricsSnapshot>> {
+        let bytes = match tokio::fs::read(&self.path).await {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+        if bytes.len() < HEADER_LENGTH as usize {
+            bail!("Metrics history file {} is truncated", self.path.display());
+        }
+        let (header, slots) = bytes.split_at(HEADER_LENGTH as usize);
+        let written = u64::from_le_bytes(header.try_into()?);
+
+        let record_length = MetricsSnapshot::encoded_length() as usize;
+        let stored = written.min(self.capacity);
+        let oldest = if written > self.capacity {
+            written % self.capacity
+        } else {
+            0
+        };
+
+        let mut snapshots = vec![];
+        for i in 0..stored {
+            let slot = ((oldest + i) % self.capacity) as usize;
+            let Some(record) = slots.get(slot * record_length..(slot + 1) * record_length) else {
+                bail!("Metrics history file {} is truncated", self.path.display());
+            };
+            let snapshot: MetricsSnapshot = bincode::deserialize(record)?;
+            if snapshot.taken_at >= since {
+                snapshots.push(snapshot);
+            }
+        }
+
+        Ok(snapshots)
+    }
+
+    async fn read_written_count(file: &mut tokio::fs::File) -> Result<u64> {
+        if file.metadata().await?.len() < HEADER_LENGTH {
+            return Ok(0);
+        }
+
+        let mut header = [0u8; HEADER_LENGTH as usize];
+        file.seek(SeekFrom::Start(0)).await?;
+        file.read_exact(&mut header).await?;
+
+        Ok(u64::from_le_bytes(header))
+    }
+}
+
+#[cfg(test)]
+mod metrics_history_tests {
+    use crate::config_models::network::Network;
+    use crate::tests::shared::unit_test_data_directory;
+
+    use super::*;
+
+    fn snapshot(minutes: u64) -> MetricsSnapshot {
+        MetricsSnapshot {
+            taken_at: Timestamp::millis(minutes * 60 * 1000),
+            tip_height: BlockHeight::from(minutes),
+            difficulty: U32s::new([minutes as u32, 0, 0, 0, 0]),
+            peer_count: 8,
+            mempool_transaction_count: 3,
+            mempool_size: 4096,
+            disk_usage: 1 << 30,
+        }
+    }
+
+    #[tokio::test]
+    async fn history_is_a_ring_buffer() {
+        let data_dir = unit_test_data_directory(Network::RegTest).unwrap();
+        let history = MetricsHistory::with_capacity(
+            &data_dir.root_dir_path().join(METRICS_HISTORY_FILE_NAME),
+            3,
+        );
+        assert!(history
+            .read_since(Timestamp::zero())
+            .await
+            .unwrap()
+            .is_empty());
+
+        for minutes in 1..=2 {
+            history.append(&snapshot(minutes)).await.unwrap();
+        }
+        assert_eq!(
+            vec![snapshot(1), snapshot(2)],
+            history.read_since(Timestamp::zero()).await.unwrap()
+        );
+
+        // Once full, the oldest snapshots are overwritten
+        for minutes in 3..=5 {
+            history.append(&snapshot(minutes)).await.unwrap();
+        }
+        assert_eq!(
+            vec![snapshot(3), snapshot(4), snapshot(5)],
+            history.read_since(Timestamp::zero()).await.unwrap()
+        );
+        assert_eq!(
+            vec![snapshot(4), snapshot(5)],
+            history.read_since(snapshot(4).taken_at).await.unwrap()
+        );
+
+        let file_length = tokio::fs::metadata(&history.path).await.unwrap().len();
+        assert_eq!(
+            HEADER_LENGTH + 3 * MetricsSnapshot::encoded_length(),
+            file_length
+        );
+    }
+}
@@ -4,26 +4,50 @@ use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulat
 use crate::database::storage::storage_schema::traits::StorageWriter as SW;
 use crate::database::storage::storage_vec::traits::*;
 use crate::database::storage::storage_vec::Index;
+use crate::util_types::entropy;
 use crate::util_types::mutator_set::commit;
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use get_size::GetSize;
 use itertools::Itertools;
 use num_traits::CheckedSub;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use std::cmp::max;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
 use std::ops::{Deref, DerefMut};
-use tracing::{debug, info, warn};
+use tracing::{debug, info, warn, Instrument};
+use twenty_first::math::b_field_element::BFieldElement;
 use twenty_first::math::bfield_codec::BFieldCodec;
 use twenty_first::math::digest::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
+use self::alerts::{trusted_alert_keys, AlertError, SignedAlert};
+use self::block_propagation::BlockPropagation;
+use self::block_timeline::{
+    BlockTimelines, STAGE_FLUSH_DATABASES, STAGE_UPDATE_MEMPOOL, STAGE_UPDATE_MUTATOR_SET,
+    STAGE_UPDATE_WALLET, STAGE_WRITE_BLOCK,
+};
 use self::blockchain_state::BlockchainState;
+use self::event_journal::EventJournal;
+use self::invalid_blocks::InvalidBlockCache;
 use self::mempool::Mempool;
 use self::networking_state::NetworkingState;
-use self::wallet::address::generation_address::SpendingKey;
+use self::proving_queue::{ProvingProgress, ProvingQueue, ProvingTicket};
+use self::transaction_watch::TransactionWatches;
+use self::validation_limits::{ValidationLimits, ValidationLimitsConfig};
+use self::verified_blocks::VerifiedBlockCache;
+use self::wallet::address::generation_address::{ReceivingAddress, SpendingKey};
+use self::wallet::historical_index::HistoricalQueryError;
+use self::wallet::shared_control::SharedSpend;
 use self::wallet::utxo_notification_pool::UtxoNotifier;
 use self::wallet::wallet_state::WalletState;
-use self::wallet::wallet_status::WalletStatus;
+use self::wallet::wallet_status::{WalletBalance, WalletStatus};
+use self::wallet_rescan::WalletRescan;
 use super::blockchain::block::block_height::BlockHeight;
 use super::blockchain::block::Block;
+use super::blockchain::transaction::data_carrier::DataCarrier;
 use super::blockchain::transaction::primitive_witness::{PrimitiveWitness, SaltedUtxos};
 use super::blockchain::transaction::transaction_kernel::TransactionKernel;
 use super::blockchain::transaction::utxo::{LockScript, Utxo};
@@ -34,12 +58,18 @@ use super::blockchain::type_scripts::native_currency::NativeCurrency;
 use super::blockchain::type_scripts::neptune_coins::NeptuneCoins;
 use super::blockchain::type_scripts::time_lock::TimeLock;
 use super::blockchain::type_scripts::TypeScript;
+use super::consensus::prover::Prover;
 use super::consensus::tasm::program::ConsensusProgram;
 use super::consensus::timestamp::Timestamp;
 use crate::config_models::cli_args;
 use crate::locks::tokio as sync_tokio;
 use crate::models::peer::HandshakeData;
-use crate::models::state::wallet::monitored_utxo::MonitoredUtxo;
+use crate::models::state::wallet::monitored_utxo::{
+    MonitoredUtxo, TransactionHistoryEntry, UtxoOrigin,
+};
+use crate::models::state::wallet::prepared_send::{
+    PreparedSend, PreviewedInput, SendPreview, PREPARED_SEND_LIFETIME_IN_MINUTES,
+};
 use crate::models::state::wallet::utxo_notification_pool::ExpectedUtxo;
 use crate::time_fn_call_async;
 use crate::util_types::mutator_set::addition_record::AdditionRecord;
@@ -48,13 +78,44 @@ use crate::util_types::mutator_set::removal_record::RemovalRecord;
 
 use crate::{Hash, VERSION};
 
+pub mod alerts;
 pub mod archival_state;
+pub mod block_propagation;
+pub mod block_serving;
+pub mod block_timeline;
+pub mod block_tree;
 pub mod blockchain_state;
+pub mod clock_skew;
+pub mod connection_metrics;
+pub mod dandelion;
+pub mod dashboard_snapshot;
+pub mod disk_space;
+pub mod disk_usage;
+pub mod event_journal;
+pub mod finality;
+pub mod fork_choice;
+pub mod future_blocks;
+pub mod invalid_blocks;
 pub mod light_state;
+pub mod memory_status;
 pub mod mempool;
+pub mod mempool_policy;
+pub mod metrics_history;
 pub mod networking_state;
+pub mod peer_quality;
+pub mod peer_reconnect;
+pub mod proving_queue;
+pub mod read_replica;
 pub mod shared;
+pub mod slow_peers;
+pub mod supply_audit;
+pub mod sweep;
+pub mod tip_staleness;
+pub mod transaction_watch;
+pub mod validation_limits;
+pub mod verified_blocks;
 pub mod wallet;
+pub mod wallet_rescan;
 
 /// `GlobalStateLock` holds a [`tokio::AtomicRw`](crate::locks::tokio::AtomicRw)
 /// ([`RwLock`](std::sync::RwLock)) over [`GlobalState`].
@@ -116,6 +177,14 @@ pub struct GlobalStateLock {
 
     /// The `cli_args::Args` are read-only and accessible by all threads.
     cli: cli_args::Args,
+
+    /// Shared with `GlobalState`, so that jobs can be listed while a send
+    /// holds the lock
+    proving_queue: ProvingQueue,
+
+    /// Taken without locking the global state, so that validations do not
+    /// hold the lock while waiting for each other
+    validation_limits: ValidationLimits,
 }
 
 impl GlobalStateLock {
@@ -125,9 +194,20 @@ impl GlobalStateLock {
         net: NetworkingState,
         cli: cli_args::Args,
         mempool: Mempool,
+        journal: EventJournal,
         mining: bool,
     ) -> Self {
-        let global_state = GlobalState::new(wallet_state, chain, net, cli.clone(), mempool, mining);
+        let global_state = GlobalState::new(
+            wallet_state,
+            chain,
+            net,
+            cli.clone(),
+            mempool,
+            journal,
+            mining,
+        );
+        let proving_queue = global_state.proving_queue.clone();
+        let validation_limits = ValidationLimits::new(ValidationLimitsConfig::from_cli_args(&cli));
         let global_state_lock = sync_tokio::AtomicRw::from((
             global_state,
             Some("GlobalState"),
@@ -137,9 +217,22 @@ impl GlobalStateLock {
         Self {
             global_state_lock,
             cli,
+            proving_queue,
+            validation_limits,
         }
     }
 
+    /// The proving jobs, accessible without locking the global state
+    pub fn proving_queue(&self) -> &ProvingQueue {
+        &self.proving_queue
+    }
+
+    /// The limits on concurrent validation work, see
+    /// [`validation_limits`](self::validation_limits)
+    pub fn validation_limits(&self) -> &ValidationLimits {
+        &self.validation_limits
+    }
+
     // check if mining
     pub async fn mining(&self) -> bool {
         self.lock(|s| s.mining).await
@@ -234,11 +327,37 @@ pub struct GlobalState {
     /// The `Mempool` may only be updated by the main thread.
     pub mempool: Mempool,
 
+    /// The `EventJournal` may be appended to by the main thread and peer threads.
+    pub journal: EventJournal,
+
+    /// Timings of recently processed blocks. Written by the main thread and peer threads.
+    pub block_timelines: BlockTimelines,
+
+    /// When recent blocks were announced by peers and validated. Written by peer threads.
+    pub block_propagation: BlockPropagation,
+
+    /// Blocks that failed validation. Written by peer threads.
+    pub invalid_blocks: InvalidBlockCache,
+
+    /// Blocks whose proofs verified. Written by peer threads. Archival nodes
+    /// also mark them in the block index, see [`Self::block_proofs_verified`].
+    pub verified_blocks: VerifiedBlockCache,
+
+    /// Jobs producing proofs for transactions this node creates
+    pub proving_queue: ProvingQueue,
+
+    /// Transactions watched for confirmations. Written by the RPC server and
+    /// the main thread.
+    pub transaction_watches: TransactionWatches,
+
+    /// The running, or the last, rescan of the chain for wallet UTXOs
+    pub wallet_rescan: WalletRescan,
+
     // Only the mining thread should write to this, anyone can read.
     pub mining: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, GetSize)]
 pub struct UtxoReceiverData {
     pub utxo: Utxo,
     pub sender_randomness: Digest,
@@ -246,6 +365,55 @@ pub struct UtxoReceiverData {
     pub public_announcement: PublicAnnouncement,
 }
 
+/// A transaction with everything needed to prove it, gathered from the
+/// global state. Proving can take minutes, so it happens without holding the
+/// lock on the global state.
+pub struct TransactionDraft {
+    pub kernel: TransactionKernel,
+    primitive_witness: PrimitiveWitness,
+    prover: Prover,
+
+    /// The change output, if any, which the wallet expects
+    pub change_addition_record: Option<AdditionRecord>,
+}
+
+impl TransactionDraft {
+    /// Digest of the transaction that proving the draft yields, which does
+    /// not depend on the proofs
+    pub fn transaction_digest(&self) -> Digest {
+        Hash::hash(&Transaction {
+            kernel: self.kernel.clone(),
+            witness: TransactionValidationLogic::default(),
+        })
+    }
+
+    /// Prove the transaction, as the job of `ticket` on the proving queue
+    pub async fn prove(self, ticket: ProvingTicket) -> Result<Transaction> {
+        // note: this executes the prover which can take a very
+        //       long time, perhaps minutes.  As such, it runs as a
+        //       job of the proving queue, on tokio's blocking
+        //       threadpool, so as not to block the tokio executor
+        //       and other async tasks.
+        ticket.describe(format!(
+            "transaction with {} inputs and {} outputs",
+            self.kernel.inputs.len(),
+            self.kernel.outputs.len()
+        ));
+        let Self {
+            kernel,
+            primitive_witness,
+            prover,
+            ..
+        } = self;
+        let transaction = ticket
+            .run(move |progress| {
+                GlobalState::prove_primitive_witness(progress, &prover, kernel, primitive_witness)
+            })
+            .await?;
+        Ok(transaction)
+    }
+}
+
 impl GlobalState {
     pub fn new(
         wallet_state: WalletState,
@@ -253,18 +421,49 @@ impl GlobalState {
         net: NetworkingState,
         cli: cli_args::Args,
         mempool: Mempool,
+        journal: EventJournal,
         mining: bool,
     ) -> Self {
+        let proving_queue = ProvingQueue::new(
+            cli.max_proving_jobs
+                .unwrap_or_else(ProvingQueue::default_max_concurrent_jobs),
+        );
         Self {
             wallet_state,
             chain,
             net,
             cli,
             mempool,
+            journal,
+            block_timelines: BlockTimelines::default(),
+            block_propagation: BlockPropagation::default(),
+            invalid_blocks: InvalidBlockCache::default(),
+            verified_blocks: VerifiedBlockCache::default(),
+            proving_queue,
+            transaction_watches: TransactionWatches::default(),
+            wallet_rescan: WalletRescan::default(),
             mining,
         }
     }
 
+    /// Verify a network alert against the trusted alert keys and store it.
+    /// Returns true if the alert was not known before, in which case it is
+    /// logged.
+    pub fn accept_alert(&mut self, alert: SignedAlert, now: Timestamp) -> Result<bool, AlertError> {
+        let trusted_keys = trusted_alert_keys(&self.cli.alert_key);
+        alert.verify(&trusted_keys, self.cli.network, now)?;
+
+        let is_new = self.net.alerts.insert(alert.clone(), now);
+        if is_new {
+            warn!(
+                "Network alert ({}) signed by {}: {}",
+                alert.alert.severity, alert.public_key, alert.alert.message
+            );
+        }
+
+        Ok(is_new)
+    }
+
     pub async fn get_wallet_status_for_tip(&self) -> WalletStatus {
         let tip_digest = self.chain.light_state().hash();
         self.wallet_state
@@ -272,6 +471,59 @@ impl GlobalState {
             .await
     }
 
+    /// The balance of the wallet as of the tip, and with the transactions in
+    /// the mempool applied, as well as those still in the Dandelion stem
+    /// phase, which include the wallet's own sends. Only expected UTXOs are
+    /// recognized as incoming, i.e. those announced to the wallet.
+    pub async fn get_wallet_balance(&self, now: Timestamp) -> WalletBalance {
+        let wallet_status = self.get_wallet_status_for_tip().await;
+        let confirmed = wallet_status.synced_unspent_available_amount(now);
+
+        let mut mempool_inputs = HashSet::new();
+        let mut mempool_outputs = HashSet::new();
+        let mempool_transactions = self
+            .mempool
+            .get_sorted_iter()
+            .filter_map(|(transaction_digest, _fee_density)| self.mempool.get(transaction_digest));
+        for transaction in mempool_transactions.chain(self.net.stem_pool.transactions()) {
+            mempool_inputs.extend(
+                transaction
+                    .kernel
+                    .inputs
+                    .iter()
+                    .map(|input| input.absolute_indices.to_array()),
+            );
+            mempool_outputs.extend(transaction.kernel.outputs.iter().copied());
+        }
+
+        let outgoing = wallet_status
+            .synced_unspent
+            .iter()
+            .filter(|(wse, msmp)| {
+                wse.utxo.can_spend_at(now)
+                    && mempool_inputs
+                        .contains(&msmp.compute_indices(Hash::hash(&wse.utxo)).to_array())
+            })
+            .map(|(wse, _msmp)| wse.utxo.get_native_currency_amount())
+            .sum::<NeptuneCoins>();
+        let incoming = self
+            .wallet_state
+            .expected_utxos
+            .get_all_expected_utxos()
+            .into_iter()
+            .filter(|expected| {
+                expected.mined_in_block.is_none()
+                    && mempool_outputs.contains(&expected.addition_record)
+            })
+            .map(|expected| expected.utxo.get_native_currency_amount())
+            .sum::<NeptuneCoins>();
+
+        WalletBalance {
+            confirmed,
+            unconfirmed: confirmed - outgoing + incoming,
+        }
+    }
+
     pub async fn get_latest_balance_height(&self) -> Option<BlockHeight> {
         let (height, time_secs) =
             time_fn_call_async(self.get_latest_balance_height_internal()).await;
@@ -352,11 +604,30 @@ impl GlobalState {
 
     /// Retrieve wallet balance history
     pub async fn get_balance_history(&self) -> Vec<(Digest, Timestamp, BlockHeight, NeptuneCoins)> {
+        self.get_transaction_history(None)
+            .await
+            .into_iter()
+            .map(|entry| {
+                (
+                    entry.block_digest,
+                    entry.timestamp,
+                    entry.height,
+                    entry.amount,
+                )
+            })
+            .collect()
+    }
+
+    /// Retrieve wallet balance history, optionally only the entries for UTXOs
+    /// of the given origin
+    pub async fn get_transaction_history(
+        &self,
+        origin: Option<UtxoOrigin>,
+    ) -> Vec<TransactionHistoryEntry> {
         let current_tip_digest = self.chain.light_state().hash();
 
         let monitored_utxos = self.wallet_state.wallet_db.monitored_utxos();
 
-        // let num_monitored_utxos = monitored_utxos.len();
         let mut history = vec![];
 
         let stream = monitored_utxos.stream_values().await;
@@ -365,6 +636,7 @@ impl GlobalState {
             if monitored_utxo
                 .get_membership_proof_for_block(current_tip_digest)
                 .is_none()
+                || origin.is_some_and(|origin| origin != monitored_utxo.origin)
             {
                 continue;
             }
@@ -373,27 +645,195 @@ impl GlobalState {
                 monitored_utxo.confirmed_in_block
             {
                 let amount = monitored_utxo.utxo.get_native_currency_amount();
-                history.push((
-                    confirming_block,
-                    confirmation_timestamp,
-                    confirmation_height,
+                history.push(TransactionHistoryEntry {
+                    block_digest: confirming_block,
+                    height: confirmation_height,
+                    timestamp: confirmation_timestamp,
                     amount,
-                ));
+                    origin: monitored_utxo.origin,
+                });
                 if let Some((spending_block, spending_timestamp, spending_height)) =
                     monitored_utxo.spent_in_block
                 {
-                    history.push((spending_block, spending_timestamp, spending_height, -amount));
+                    history.push(TransactionHistoryEntry {
+                        block_digest: spending_block,
+                        height: spending_height,
+                        timestamp: spending_timestamp,
+                        amount: -amount,
+                        origin: monitored_utxo.origin,
+                    });
                 }
             }
         }
         history
     }
 
+    /// Get the wallet's balance, including time-locked funds, as of the block
+    /// at `height` on the current chain.
+    ///
+    /// Requires the historical index to cover the blocks above `height`.
+    pub async fn get_balance_at(
+        &self,
+        height: BlockHeight,
+    ) -> Result<NeptuneCoins, HistoricalQueryError> {
+        Ok(self.own_unspent_utxos_at(height).await?.into_values().sum())
+    }
+
+    /// Determine whether the UTXO with the given addition record was
+    /// confirmed and unspent as of the block at `height` on the current chain.
+    ///
+    /// Archival nodes find any addition record in the append-only commitment
+    /// list (AOCL) of the mutator set at that height. Whether a confirmed UTXO
+    /// was spent can only be told for the wallet's own UTXOs, as the mutator
+    /// set does not reveal which UTXO an input spends without the UTXO's
+    /// secrets, and requires the historical index to cover the blocks above
+    /// `height`.
+    pub async fn was_utxo_unspent_at(
+        &self,
+        addition_record: AdditionRecord,
+        height: BlockHeight,
+    ) -> Result<bool, HistoricalQueryError> {
+        let tip_height = self.chain.light_state().kernel.header.height;
+        if height > tip_height {
+            return Err(HistoricalQueryError::AboveTip { height, tip_height });
+        }
+
+        if self.chain.is_archival_node() {
+            if !self.aocl_contains_at(addition_record, height).await? {
+                return Ok(false);
+            }
+            if !self
+                .wallet_state
+                .monitors_addition_record(&addition_record)
+                .await
+            {
+                return Err(HistoricalQueryError::NotOwned);
+            }
+        }
+
+        Ok(self
+            .own_unspent_utxos_at(height)
+            .await?
+            .contains_key(&addition_record))
+    }
+
+    /// Determine whether the AOCL of the canonical block at `height` contains
+    /// the given addition record. Only the leaves that were in the AOCL at
+    /// that height are read.
+    async fn aocl_contains_at(
+        &self,
+        addition_record: AdditionRecord,
+        height: BlockHeight,
+    ) -> Result<bool, HistoricalQueryError> {
+        let archival_state = self.chain.archival_state();
+        let tip_digest = self.chain.light_state().hash();
+        let block = match archival_state
+            .block_height_to_canonical_block_digest(height, tip_digest)
+            .await
+        {
+            Some(block_digest) => archival_state.get_block(block_digest).await,
+            None => Ok(None),
+        };
+        let block = match block {
+            Ok(Some(block)) => block,
+            Ok(None) => {
+                return Err(HistoricalQueryError::Failed(format!(
+                    "no canonical block is stored at height {height}"
+                )))
+            }
+            Err(err) => return Err(HistoricalQueryError::Failed(err.to_string())),
+        };
+        let leaf_count = block
+            .kernel
+            .body
+            .mutator_set_accumulator
+            .aocl
+            .count_leaves();
+
+        // The AOCL only grows along the canonical chain, so the leaves it had
+        // at `height` are the first ones of the tip's AOCL
+        let leafs = archival_state
+            .archival_mutator_set
+            .ams()
+            .stream_aocl_leafs()
+            .await;
+        pin_mut!(leafs);
+        while let Some((leaf_index, commitment)) = leafs.next().await {
+            if leaf_index >= leaf_count {
+                break;
+            }
+            if commitment == addition_record.canonical_commitment {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// The wallet's unspent UTXOs as of the block at `height`, found by
+    /// undoing the recorded deltas of the blocks from the tip down to it.
+    async fn own_unspent_utxos_at(
+        &self,
+        height: BlockHeight,
+    ) -> Result<HashMap<AdditionRecord, NeptuneCoins>, HistoricalQueryError> {
+        let tip_digest = self.chain.light_state().hash();
+        let tip_height = self.chain.light_state().kernel.header.height;
+        if height > tip_height {
+            return Err(HistoricalQueryError::AboveTip { height, tip_height });
+        }
+        if self.wallet_state.wallet_db.get_sync_label().await != tip_digest {
+            return Err(HistoricalQueryError::NotSynced);
+        }
+
+        let mut unspent_utxos: HashMap<AdditionRecord, NeptuneCoins> = self
+            .get_wallet_status_for_tip()
+            .await
+            .synced_unspent
+            .into_iter()
+            .map(|(wse, ms_mp)| {
+                let addition_record = commit(
+                    Hash::hash(&wse.utxo),
+                    ms_mp.sender_randomness,
+                    ms_mp.receiver_preimage.hash::<Hash>(),
+                );
+                (addition_record, wse.utxo.get_native_currency_amount())
+            })
+            .collect();
+
+        // Deltas are recorded as blocks are applied, so those of the blocks
+        // above `height` are found by reading backwards from the latest one,
+        // skipping the deltas of blocks that were since reorganized away.
+        let wallet_db = &self.wallet_state.wallet_db;
+        let mut delta_index = wallet_db.block_delta_count().await;
+        let mut block_digest = tip_digest;
+        let mut block_height = tip_height;
+        while block_height > height {
+            let block_delta = loop {
+                if delta_index == 0 {
+                    return Err(HistoricalQueryError::NotIndexed {
+                        block_digest,
+                        height: block_height,
+                    });
+                }
+                delta_index -= 1;
+                let block_delta = wallet_db.block_delta(delta_index).await;
+                if block_delta.block_digest == block_digest {
+                    break block_delta;
+                }
+            };
+            block_delta.undo(&mut unspent_utxos);
+            block_digest = block_delta.prev_block_digest;
+            block_height = block_height.previous();
+        }
+
+        Ok(unspent_utxos)
+    }
+
     /// Given the desired outputs, assemble UTXOs that are both spendable
     /// (*i.e.*, synced and never or no longer timelocked) and that sum to
     /// enough funds.
     pub async fn assemble_inputs_for_transaction(
-        &mut self,
+        &self,
         total_spend: NeptuneCoins,
         timestamp: Timestamp,
     ) -> Result<Vec<(Utxo, LockScript, MsMembershipProof)>> {
@@ -441,46 +881,89 @@ impl GlobalState {
     /// Generate a change UTXO and transaction output to ensure that the difference
     /// in input amount and output amount goes back to us. Also, make sure to expect
     /// the UTXO so that we can synchronize it after it is confirmed.
-    pub async fn add_change(&mut self, change_amount: NeptuneCoins) -> (AdditionRecord, Utxo) {
+    ///
+    /// The change output is announced like a payment, so that it cannot be told
+    /// apart from one by its structure. The wallet has a single generation address,
+    /// so change goes back to that address, but with sender randomness that is
+    /// unique to the output.
+    pub async fn add_change(&mut self, change_amount: NeptuneCoins) -> Result<UtxoReceiverData> {
+        self.add_own_output(change_amount, UtxoNotifier::Myself)
+            .await
+    }
+
+    /// Generate a UTXO and transaction output paying `amount` to the wallet, and
+    /// expect the UTXO as notified by `notifier`. See [`Self::add_change`].
+    pub async fn add_own_output(
+        &mut self,
+        amount: NeptuneCoins,
+        notifier: UtxoNotifier,
+    ) -> Result<UtxoReceiverData> {
         // generate utxo
-        let own_spending_key_for_change = self
+        let own_spending_key = self
             .wallet_state
             .wallet_secret
             .nth_generation_spending_key(0);
-        let own_receiving_address = own_spending_key_for_change.to_address();
+        let own_receiving_address = own_spending_key.to_address();
         let lock_script = own_receiving_address.lock_script();
         let lock_script_hash = lock_script.hash();
-        let change_utxo = Utxo {
-            coins: change_amount.to_native_coins(),
+        let utxo = Utxo {
+            coins: amount.to_native_coins(),
             lock_script_hash,
         };
 
-        // generate addition record
+        // generate sender randomness, mixing in the count of outputs generated by
+        // this wallet so that two of its outputs never share a commitment
         let receiver_digest = own_receiving_address.privacy_digest;
-        let change_sender_randomness = self.wallet_state.wallet_secret.generate_sender_randomness(
-            self.chain.light_state().kernel.header.height,
-            receiver_digest,
-        );
-        let change_addition_record = commit(
-            Hash::hash(&change_utxo),
-            change_sender_randomness,
-            receiver_digest,
+        let output_count = self.wallet_state.wallet_db.get_counter().await;
+        self.wallet_state
+            .wallet_db
+            .set_counter(output_count + 1)
+            .await;
+        self.wallet_state.record_wallet_changes(1).await;
+        let sender_randomness = Hash::hash_pair(
+            self.wallet_state.wallet_secret.generate_sender_randomness(
+                self.chain.light_state().kernel.header.height,
+                receiver_digest,
+            ),
+            Hash::hash(&output_count),
         );
+        let public_announcement = own_receiving_address
+            .generate_public_announcement(&utxo, sender_randomness)
+            .context("could not encrypt to own address")?;
 
-        // Add change UTXO to pool of expected incoming UTXOs
-        let receiver_preimage = own_spending_key_for_change.privacy_preimage;
-        let _change_addition_record = self
+        // Add UTXO to pool of expected incoming UTXOs
+        let receiver_preimage = own_spending_key.privacy_preimage.expose();
+        let _addition_record = self
             .wallet_state
             .expected_utxos
-            .add_expected_utxo(
-                change_utxo.clone(),
-                change_sender_randomness,
-                receiver_preimage,
-                UtxoNotifier::Myself,
-            )
-            .expect("Adding change UTXO to UTXO notification pool must succeed");
+            .add_expected_utxo(utxo.clone(), sender_randomness, receiver_preimage, notifier)
+            .expect("Adding own UTXO to UTXO notification pool must succeed");
+
+        Ok(UtxoReceiverData {
+            utxo: utxo,
+            sender_randomness: sender_randomness,
+            receiver_privacy_digest: receiver_digest,
+            public_announcement,
+        })
+    }
+
+    /// Put the outputs of a transaction in an order that reveals nothing about
+    /// which of them is change. The order is determined by the outputs' sender
+    /// randomness, which only the sender knows, so building the same transaction
+    /// twice gives the same order.
+    pub fn shuffle_outputs(receiver_data: &mut [UtxoReceiverData]) {
+        let seed_digest = Hash::hash_varlen(
+            &receiver_data
+                .iter()
+                .flat_map(|rd| rd.sender_randomness.values())
+                .collect_vec(),
+        );
+        let mut seed = [0u8; 32];
+        for (seed_bytes, element) in seed.chunks_mut(8).zip(seed_digest.values()) {
+            seed_bytes.copy_from_slice(&element.value().to_le_bytes());
+        }
 
-        (change_addition_record, change_utxo)
+        receiver_data.shuffle(&mut StdRng::from_seed(seed));
     }
 
     /// Generate a primitive witness for a transaction from various disparate witness data.
@@ -490,6 +973,26 @@ impl GlobalState {
         output_utxos: &[Utxo],
         transaction_kernel: &TransactionKernel,
         mutator_set_accumulator: MutatorSetAccumulator,
+    ) -> PrimitiveWitness {
+        let secret_input = spending_key.unlock_key.expose().encode();
+
+        Self::generate_primitive_witness_with_lock_script_witnesses(
+            vec![secret_input; spendable_utxos_and_mps.len()],
+            spendable_utxos_and_mps,
+            output_utxos,
+            transaction_kernel,
+            mutator_set_accumulator,
+        )
+    }
+
+    /// Generate a primitive witness for a transaction whose inputs are unlocked
+    /// by the given lock script witnesses, one per input.
+    pub fn generate_primitive_witness_with_lock_script_witnesses(
+        lock_script_witnesses: Vec<Vec<BFieldElement>>,
+        spendable_utxos_and_mps: &[(Utxo, LockScript, MsMembershipProof)],
+        output_utxos: &[Utxo],
+        transaction_kernel: &TransactionKernel,
+        mutator_set_accumulator: MutatorSetAccumulator,
     ) -> PrimitiveWitness {
         let type_scripts = [NativeCurrency.program(), TimeLock.program()]
             .map(TypeScript::new)
@@ -508,13 +1011,11 @@ impl GlobalState {
             .cloned()
             .collect_vec();
 
-        let secret_input = spending_key.unlock_key.encode();
-
         PrimitiveWitness {
             input_utxos: SaltedUtxos::new(input_utxos),
             input_lock_scripts,
             type_scripts,
-            lock_script_witnesses: vec![secret_input; spendable_utxos_and_mps.len()],
+            lock_script_witnesses,
             input_membership_proofs,
             output_utxos: SaltedUtxos::new(output_utxos.to_vec()),
             mutator_set_accumulator,
@@ -522,6 +1023,84 @@ impl GlobalState {
         }
     }
 
+    /// Select inputs for sending `amount` to `address`, and derive the output,
+    /// without creating the transaction. Nothing is spent, and no change is
+    /// expected, until the result is passed to
+    /// [`Self::create_transaction_with_inputs`].
+    ///
+    /// If `release_date` is given, the output is time-locked: the recipient
+    /// cannot spend it in a transaction dated before the release date.
+    pub async fn prepare_send(
+        &self,
+        amount: NeptuneCoins,
+        address: ReceivingAddress,
+        fee: NeptuneCoins,
+        release_date: Option<Timestamp>,
+        timestamp: Timestamp,
+    ) -> Result<PreparedSend> {
+        let mut coins = amount.to_native_coins();
+        if let Some(release_date) = release_date {
+            if release_date <= timestamp {
+                bail!("The release date of a time-locked output must be in the future.");
+            }
+            coins.push(TimeLock::until(release_date));
+        }
+        let utxo = Utxo::new(address.lock_script(), coins);
+        let tip = self.chain.light_state();
+        let receiver_privacy_digest = address.privacy_digest;
+        let sender_randomness = self
+            .wallet_state
+            .wallet_secret
+            .generate_sender_randomness(tip.header().height, receiver_privacy_digest);
+        let public_announcement = address
+            .generate_public_announcement(&utxo, sender_randomness)
+            .context("could not encrypt to address")?;
+        let receiver_data = vec![UtxoReceiverData {
+            utxo,
+            sender_randomness,
+            receiver_privacy_digest,
+            public_announcement,
+        }];
+
+        let total_spend = Self::total_spend(&receiver_data, fee);
+        let spendable_utxos_and_mps = self
+            .assemble_inputs_for_transaction(total_spend, timestamp)
+            .await?;
+        let inputs = spendable_utxos_and_mps
+            .iter()
+            .map(|(utxo, _lock_script, mp)| PreviewedInput {
+                aocl_leaf_index: mp.auth_path_aocl.leaf_index,
+                amount: utxo.get_native_currency_amount(),
+            })
+            .collect_vec();
+        let input_amount = inputs
+            .iter()
+            .map(|input| input.amount)
+            .sum::<NeptuneCoins>();
+        let Some(change) = input_amount.checked_sub(&total_spend) else {
+            bail!("Not enough available funds.");
+        };
+
+        let preview = SendPreview {
+            token: entropy::random(),
+            tip_digest: tip.hash(),
+            inputs,
+            amount,
+            change,
+            fee,
+            release_date,
+            expires_at: timestamp + Timestamp::minutes(PREPARED_SEND_LIFETIME_IN_MINUTES),
+        };
+
+        Ok(PreparedSend {
+            preview,
+            address,
+            receiver_data,
+            spendable_utxos_and_mps,
+            timestamp,
+        })
+    }
+
     /// Create a transaction that sends coins to the given
     /// `recipient_utxos` from some selection of owned UTXOs.
     /// A change UTXO will be added if needed; the caller
@@ -537,126 +1116,184 @@ impl GlobalState {
         fee: NeptuneCoins,
         timestamp: Timestamp,
     ) -> Result<Transaction> {
-        // UTXO data: inputs, outputs, and supporting witness data
-        let (inputs, spendable_utxos_and_mps, outputs, output_utxos) = self
-            .generate_utxo_data_for_transaction(&receiver_data, fee, timestamp)
+        let total_spend = Self::total_spend(&receiver_data, fee);
+        let spendable_utxos_and_mps = self
+            .assemble_inputs_for_transaction(total_spend, timestamp)
             .await?;
 
-        // other data
-        let public_announcements = receiver_data
-            .iter()
-            .map(|x| x.public_announcement.clone())
-            .collect_vec();
-        let mutator_set_accumulator = self
-            .chain
-            .light_state()
-            .kernel
-            .body
-            .mutator_set_accumulator
-            .clone();
-        let privacy = self.cli().privacy;
-
-        // TODO: The spending key can be different for each UTXO, and therefore must be supplied by `spendable_utxos_and_mps`.
-        let spending_key = self
-            .wallet_state
-            .wallet_secret
-            .nth_generation_spending_key(0);
-
-        // assemble transaction object (lengthy operation)
-        Self::create_transaction_from_data(
-            spending_key,
-            inputs,
+        let ticket = self.proving_queue.ticket("transaction");
+        self.create_transaction_with_inputs(
+            receiver_data,
+            fee,
+            timestamp,
             spendable_utxos_and_mps,
-            outputs,
-            output_utxos,
+            ticket,
+        )
+        .await
+    }
+
+    /// Like [`Self::create_transaction`], but spending the given inputs, which
+    /// must be synced to the tip and sum to at least the outputs plus the fee.
+    /// The proofs are produced in the job of `ticket`.
+    pub async fn create_transaction_with_inputs(
+        &mut self,
+        receiver_data: Vec<UtxoReceiverData>,
+        fee: NeptuneCoins,
+        timestamp: Timestamp,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+        ticket: ProvingTicket,
+    ) -> Result<Transaction> {
+        self.create_transaction_with_announcements(
+            receiver_data,
+            vec![],
             fee,
-            public_announcements,
             timestamp,
-            mutator_set_accumulator,
-            privacy,
+            spendable_utxos_and_mps,
+            ticket,
         )
         .await
     }
 
-    /// Given a list of UTXOs with receiver data, assemble owned and synced and spendable
-    /// UTXOs that unlock enough funds, add (and track) a change UTXO if necessary, and
-    /// and produce a list of removal records, input UTXOs (with lock scripts and
-    /// membership proofs), addition records, and output UTXOs.
-    async fn generate_utxo_data_for_transaction(
+    /// Create a transaction that carries `data_carrier` in its public
+    /// announcements and pays `fee` from some selection of owned UTXOs, with
+    /// the remainder as change. The proofs are produced in the job of `ticket`.
+    pub async fn create_data_carrier_transaction(
         &mut self,
-        receiver_data: &[UtxoReceiverData],
+        data_carrier: DataCarrier,
         fee: NeptuneCoins,
         timestamp: Timestamp,
-    ) -> Result<(
-        Vec<RemovalRecord>,
-        Vec<(Utxo, LockScript, MsMembershipProof)>,
-        Vec<AdditionRecord>,
-        Vec<Utxo>,
-    )> {
-        // total amount to be spent -- determines how many and which UTXOs to use
-        let total_spend: NeptuneCoins = receiver_data
-            .iter()
-            .map(|x| x.utxo.get_native_currency_amount())
-            .sum::<NeptuneCoins>()
-            + fee;
+        ticket: ProvingTicket,
+    ) -> Result<Transaction> {
+        let spendable_utxos_and_mps = self.assemble_inputs_for_transaction(fee, timestamp).await?;
 
-        // collect enough spendable UTXOs
-        let spendable_utxos_and_mps = self
-            .assemble_inputs_for_transaction(total_spend, timestamp)
+        self.create_transaction_with_announcements(
+            vec![],
+            vec![data_carrier.to_public_announcement()],
+            fee,
+            timestamp,
+            spendable_utxos_and_mps,
+            ticket,
+        )
+        .await
+    }
+
+    /// Like [`Self::create_transaction_with_inputs`], with public announcements
+    /// that belong to no output, such as data carriers, appended to those of
+    /// the outputs.
+    ///
+    /// If the transaction cannot be created, e.g. because its proving job is
+    /// cancelled or times out, its change is no longer expected.
+    async fn create_transaction_with_announcements(
+        &mut self,
+        receiver_data: Vec<UtxoReceiverData>,
+        other_announcements: Vec<PublicAnnouncement>,
+        fee: NeptuneCoins,
+        timestamp: Timestamp,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+        ticket: ProvingTicket,
+    ) -> Result<Transaction> {
+        let draft = self
+            .draft_transaction_with_announcements(
+                receiver_data,
+                other_announcements,
+                fee,
+                timestamp,
+                spendable_utxos_and_mps,
+            )
             .await?;
-        let input_amount = spendable_utxos_and_mps
-            .iter()
-            .map(|(utxo, _lock_script, _mp)| utxo.get_native_currency_amount())
-            .sum::<NeptuneCoins>();
+        let change_addition_record = draft.change_addition_record;
 
-        // sanity check: do we even have enough funds?
-        if total_spend > input_amount {
-            bail!("Not enough available funds.");
+        let transaction = draft.prove(ticket).await;
+        if let (Err(err), Some(change_addition_record)) = (&transaction, change_addition_record) {
+            self.forget_change_of_failed_transaction(change_addition_record, err);
         }
 
-        // create removal records (inputs)
-        let inputs = Self::generate_removal_records(
-            &spendable_utxos_and_mps,
-            &self.chain.light_state().kernel.body.mutator_set_accumulator,
-        );
+        transaction
+    }
 
-        // create addition records (outputs)
-        let mut outputs = Self::generate_addition_records(receiver_data);
-        let mut output_utxos = receiver_data.iter().map(|rd| rd.utxo.clone()).collect_vec();
+    /// Gather everything needed to prove the transaction that
+    /// [`Self::create_transaction_with_inputs`] would create, and expect its
+    /// change. The draft can be proven without holding the lock on the global
+    /// state; if that fails, pass its change to
+    /// [`Self::forget_change_of_failed_transaction`].
+    pub async fn draft_transaction_with_inputs(
+        &mut self,
+        receiver_data: Vec<UtxoReceiverData>,
+        fee: NeptuneCoins,
+        timestamp: Timestamp,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+    ) -> Result<TransactionDraft> {
+        self.draft_transaction_with_announcements(
+            receiver_data,
+            vec![],
+            fee,
+            timestamp,
+            spendable_utxos_and_mps,
+        )
+        .await
+    }
+
+    async fn draft_transaction_with_announcements(
+        &mut self,
+        mut receiver_data: Vec<UtxoReceiverData>,
+        other_announcements: Vec<PublicAnnouncement>,
+        fee: NeptuneCoins,
+        timestamp: Timestamp,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+    ) -> Result<TransactionDraft> {
+        let total_spend = Self::total_spend(&receiver_data, fee);
+        let input_amount = spendable_utxos_and_mps
+            .iter()
+            .map(|(utxo, _lock_script, _mp)| utxo.get_native_currency_amount())
+            .sum::<NeptuneCoins>();
+
+        // sanity check: do we even have enough funds?
+        if total_spend > input_amount {
+            bail!("Not enough available funds.");
+        }
 
         // keep track of change (if any)
+        let mut change_addition_record = None;
         if total_spend < input_amount {
             let change_amount = input_amount.checked_sub(&total_spend).unwrap();
-            let (change_addition_record, change_utxo) = self.add_change(change_amount).await;
-            outputs.push(change_addition_record);
-            output_utxos.push(change_utxo.clone());
+            let change = self.add_change(change_amount).await?;
+            change_addition_record = Some(commit(
+                Hash::hash(&change.utxo),
+                change.sender_randomness,
+                change.receiver_privacy_digest,
+            ));
+            receiver_data.push(change);
         }
+        Self::shuffle_outputs(&mut receiver_data);
 
-        Ok((inputs, spendable_utxos_and_mps, outputs, output_utxos))
-    }
+        // UTXO data: inputs, outputs, and supporting witness data
+        let (inputs, outputs, output_utxos) =
+            self.generate_utxo_data_from_inputs(&receiver_data, &spendable_utxos_and_mps);
 
-    /// Assembles a transaction kernel and supporting witness or proof(s) from
-    /// the given transaction data.
-    #[allow(clippy::too_many_arguments)]
-    async fn create_transaction_from_data(
-        spending_key: SpendingKey,
-        inputs: Vec<RemovalRecord>,
-        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
-        outputs: Vec<AdditionRecord>,
-        output_utxos: Vec<Utxo>,
-        fee: NeptuneCoins,
-        public_announcements: Vec<PublicAnnouncement>,
-        timestamp: Timestamp,
-        mutator_set_accumulator: MutatorSetAccumulator,
-        privacy: bool,
-    ) -> Result<Transaction> {
-        // note: this executes the prover which can take a very
-        //       long time, perhaps minutes.  As such, we use
-        //       spawn_blocking() to execute on tokio's blocking
-        //       threadpool and avoid blocking the tokio executor
-        //       and other async tasks.
-        let transaction = tokio::task::spawn_blocking(move || {
-            Self::create_transaction_from_data_worker(
+        // other data
+        let public_announcements = receiver_data
+            .iter()
+            .map(|x| x.public_announcement.clone())
+            .chain(other_announcements)
+            .collect_vec();
+        let mutator_set_accumulator = self
+            .chain
+            .light_state()
+            .kernel
+            .body
+            .mutator_set_accumulator
+            .clone();
+
+        // TODO: The spending key can be different for each UTXO, and therefore must be supplied by `spendable_utxos_and_mps`.
+        let spending_key = self
+            .wallet_state
+            .wallet_secret
+            .nth_generation_spending_key(0);
+
+        Ok(TransactionDraft {
+            change_addition_record,
+            ..Self::draft_transaction_from_data(
+                self.cli().prover(),
                 spending_key,
                 inputs,
                 spendable_utxos_and_mps,
@@ -666,19 +1303,15 @@ impl GlobalState {
                 public_announcements,
                 timestamp,
                 mutator_set_accumulator,
-                privacy,
             )
         })
-        .await?;
-        Ok(transaction)
     }
 
-    // note: this executes the prover which can take a very
-    //       long time, perhaps minutes. It should never be
-    //       called directly.
-    //       Use create_transaction_from_data() instead.
+    /// Assembles a transaction kernel and its primitive witness from the
+    /// given transaction data, to be proven with [`TransactionDraft::prove`].
     #[allow(clippy::too_many_arguments)]
-    fn create_transaction_from_data_worker(
+    fn draft_transaction_from_data(
+        prover: Prover,
         spending_key: SpendingKey,
         inputs: Vec<RemovalRecord>,
         spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
@@ -688,13 +1321,12 @@ impl GlobalState {
         public_announcements: Vec<PublicAnnouncement>,
         timestamp: Timestamp,
         mutator_set_accumulator: MutatorSetAccumulator,
-        _privacy: bool,
-    ) -> Transaction {
+    ) -> TransactionDraft {
         // complete transaction kernel
         let kernel = TransactionKernel {
             inputs,
             outputs,
-            public_announcements: public_announcements.clone(),
+            public_announcements,
             fee,
             timestamp,
             coinbase: None,
@@ -710,12 +1342,82 @@ impl GlobalState {
             mutator_set_accumulator,
         );
 
+        TransactionDraft {
+            kernel,
+            primitive_witness,
+            prover,
+            change_addition_record: None,
+        }
+    }
+
+    /// Stop expecting the change of a transaction that could not be created
+    pub fn forget_change_of_failed_transaction(
+        &mut self,
+        change_addition_record: AdditionRecord,
+        err: &anyhow::Error,
+    ) {
+        info!("Could not create transaction, no longer expecting its change: {err:#}");
+        self.wallet_state
+            .expected_utxos
+            .drop_expected_utxo(change_addition_record);
+    }
+
+    /// Total amount that sending to the receivers costs, including the fee
+    fn total_spend(receiver_data: &[UtxoReceiverData], fee: NeptuneCoins) -> NeptuneCoins {
+        receiver_data
+            .iter()
+            .map(|x| x.utxo.get_native_currency_amount())
+            .sum::<NeptuneCoins>()
+            + fee
+    }
+
+    /// Produce the removal records of the given inputs, and the addition records
+    /// and UTXOs of the outputs, in the order of the receivers. Change, if any,
+    /// must be among the receivers.
+    fn generate_utxo_data_from_inputs(
+        &self,
+        receiver_data: &[UtxoReceiverData],
+        spendable_utxos_and_mps: &[(Utxo, LockScript, MsMembershipProof)],
+    ) -> (Vec<RemovalRecord>, Vec<AdditionRecord>, Vec<Utxo>) {
+        // create removal records (inputs)
+        let inputs = Self::generate_removal_records(
+            spendable_utxos_and_mps,
+            &self.chain.light_state().kernel.body.mutator_set_accumulator,
+        );
+
+        // create addition records (outputs)
+        let outputs = Self::generate_addition_records(receiver_data);
+        let output_utxos = receiver_data.iter().map(|rd| rd.utxo.clone()).collect_vec();
+
+        (inputs, outputs, output_utxos)
+    }
+
+    /// Create a transaction with the given kernel, proving its validity from
+    /// the primitive witness.
+    //
+    // note: this executes the prover which can take a very
+    //       long time, perhaps minutes. It should only be called
+    //       from a job of the proving queue.
+    fn prove_primitive_witness(
+        progress: &ProvingProgress,
+        prover: &Prover,
+        kernel: TransactionKernel,
+        primitive_witness: PrimitiveWitness,
+    ) -> Transaction {
         // Convert the validity tree into a single proof.
         // Down the line we want to support proving only the lock scripts, or only
         // the lock scripts and removal records integrity, but nothing else.
         // That's a concern for later though.
         let mut transaction_validity_logic = TransactionValidationLogic::from(primitive_witness);
-        transaction_validity_logic.vast.prove();
+        let proofs_total = transaction_validity_logic.vast.pending_proof_count();
+        let mut proofs_done = 0;
+        progress.report(proofs_done, proofs_total);
+        transaction_validity_logic
+            .vast
+            .prove_with_progress(prover, &mut || {
+                proofs_done += 1;
+                progress.report(proofs_done, proofs_total);
+            });
         transaction_validity_logic.maybe_primitive_witness = None;
         Transaction {
             kernel,
@@ -723,11 +1425,51 @@ impl GlobalState {
         }
     }
 
+    /// Create the transaction of a shared spend that enough cosigners have
+    /// signed. Runs as a job of the proving queue.
+    pub async fn create_shared_spend_transaction(
+        &self,
+        shared_spend: &SharedSpend,
+    ) -> Result<Transaction> {
+        let primitive_witness = shared_spend.primitive_witness()?;
+        let kernel = shared_spend.kernel.clone();
+        let prover = self.cli().prover();
+        let description = format!(
+            "shared spend with {} inputs and {} outputs",
+            kernel.inputs.len(),
+            kernel.outputs.len()
+        );
+        let transaction = self
+            .proving_queue
+            .run(description, move |progress| {
+                Self::prove_primitive_witness(progress, &prover, kernel, primitive_witness)
+            })
+            .await?;
+
+        Ok(transaction)
+    }
+
+    /// The addresses on which peers can connect to this node: those given
+    /// with `--announce-address`, or else those it listens on.
+    pub fn advertised_listen_addresses(&self) -> Vec<SocketAddr> {
+        if self.cli().announce_address.is_empty() {
+            self.net.listen_addresses.clone()
+        } else {
+            self.cli().announce_address.clone()
+        }
+    }
+
     pub async fn get_own_handshakedata(&self) -> HandshakeData {
+        let listen_addresses = self.advertised_listen_addresses();
         HandshakeData {
             tip_header: self.chain.light_state().header().clone(),
             // TODO: Should be `None` if incoming connections are not accepted
-            listen_port: Some(self.cli().peer_port),
+            listen_port: Some(
+                listen_addresses
+                    .first()
+                    .map(|address| address.port())
+                    .unwrap_or(self.cli().peer_port),
+            ),
             network: self.cli().network,
             instance_id: self.net.instance_id,
             version: VERSION.to_string(),
@@ -809,6 +1551,7 @@ impl GlobalState {
         );
         let current_aocl_leaf_count = ams_ref.ams().aocl.count_leaves().await;
         let mut restored_mutxos = 0;
+        let mut restored_indices = vec![];
         for incoming_utxo in recovery_data_for_missing_mutxos {
             // If the referenced UTXO is in the future from our tip, do not attempt to recover it. Instead: warn the user of this.
             if current_aocl_leaf_count <= incoming_utxo.aocl_index {
@@ -842,6 +1585,7 @@ impl GlobalState {
                 MonitoredUtxo::new(incoming_utxo.utxo, self.wallet_state.number_of_mps_per_utxo);
             restored_mutxo.add_membership_proof_for_tip(tip_hash, restored_msmp);
 
+            restored_indices.push(self.wallet_state.wallet_db.monitored_utxos().len().await);
             self.wallet_state
                 .wallet_db
                 .monitored_utxos_mut()
@@ -849,6 +1593,10 @@ impl GlobalState {
                 .await;
             restored_mutxos += 1;
         }
+        self.wallet_state
+            .wallet_db
+            .record_wallet_change(tip_hash, restored_indices)
+            .await;
 
         self.wallet_state.wallet_db.persist().await;
         info!("Successfully restored {restored_mutxos} monitored UTXOs to wallet database");
@@ -862,15 +1610,21 @@ impl GlobalState {
         &mut self,
         tip_hash: Digest,
     ) -> Result<()> {
-        // loop over all monitored utxos
-        let monitored_utxos = self.wallet_state.wallet_db.monitored_utxos_mut();
+        let tip_header = match self.chain.archival_state().get_block_header(tip_hash).await {
+            Some(header) => header,
+            None => bail!("Cannot resync membership proofs to unknown block {tip_hash}"),
+        };
+        let tip_info = (tip_hash, tip_header.timestamp, tip_header.height);
 
-        'outer: for i in 0..monitored_utxos.len().await {
+        // loop over all monitored utxos
+        let mut resynced_indices = vec![];
+        for i in 0..self.wallet_state.wallet_db.monitored_utxos().len().await {
             let i = i as Index;
-            let monitored_utxo = monitored_utxos.get(i).await;
+            let monitored_utxo = self.wallet_state.wallet_db.monitored_utxos().get(i).await;
 
-            // Ignore those MUTXOs that were marked as abandoned
-            if monitored_utxo.abandoned_at.is_some() {
+            // Ignore those MUTXOs that were marked as abandoned, or whose membership
+            // proof was already found to be unrecoverable
+            if monitored_utxo.abandoned_at.is_some() || monitored_utxo.unrecoverable_at.is_some() {
                 continue;
             }
 
@@ -885,153 +1639,189 @@ impl GlobalState {
                 Hash::hash(&monitored_utxo.utxo)
             );
 
-            // If the UTXO was not confirmed yet, there is no
-            // point in synchronizing its membership proof.
-            let (confirming_block_digest, confirming_block_height) =
-                match monitored_utxo.confirmed_in_block {
-                    Some((confirmed_block_hash, _timestamp, block_height)) => {
-                        (confirmed_block_hash, block_height)
-                    }
-                    None => {
-                        continue;
-                    }
-                };
+            let resynced_monitored_utxo = self
+                .resync_monitored_utxo_from_stored_blocks(&monitored_utxo, tip_info)
+                .await?;
+
+            // update storage.
+            if let Some(resynced_monitored_utxo) = resynced_monitored_utxo {
+                self.wallet_state
+                    .wallet_db
+                    .monitored_utxos_mut()
+                    .set(i, resynced_monitored_utxo)
+                    .await;
+                resynced_indices.push(i);
+            }
+        }
+        self.wallet_state
+            .wallet_db
+            .record_wallet_change(tip_hash, resynced_indices)
+            .await;
 
-            // try latest (block hash, membership proof) entry
-            let (block_hash, mut membership_proof) = monitored_utxo
-                .get_latest_membership_proof_entry()
-                .expect("Database not in consistent state. Monitored UTXO must have at least one membership proof.");
+        // Update sync label and persist
+        self.wallet_state.wallet_db.set_sync_label(tip_hash).await;
+        self.wallet_state.wallet_db.persist().await;
 
-            // request path-to-tip
-            let (backwards, _luca, forwards) = self
-                .chain
-                .archival_state()
-                .find_path(block_hash, tip_hash)
-                .await;
+        Ok(())
+    }
 
-            // after this point, we may be modifying it.
+    /// Derive a membership proof for the given tip from the monitored UTXO's
+    /// latest one, by walking the stored blocks between them.
+    ///
+    /// Returns the updated monitored UTXO, the monitored UTXO marked as
+    /// unrecoverable if the walk is impossible, or `None` if the UTXO was not
+    /// confirmed or was confirmed on a chain that the walk abandons. In the
+    /// last case, there is nothing we can do except orphan the UTXO: that
+    /// is, leave it without a synced membership proof. Whenever current
+    /// owned UTXOs are queried, one should take care to filter for UTXOs that
+    /// have a membership proof synced to the current block tip.
+    async fn resync_monitored_utxo_from_stored_blocks(
+        &self,
+        monitored_utxo: &MonitoredUtxo,
+        tip_info: (Digest, Timestamp, BlockHeight),
+    ) -> Result<Option<MonitoredUtxo>> {
+        let (tip_hash, _, _) = tip_info;
+        let unrecoverable = |reason: &str| {
+            warn!(
+                "Membership proof of monitored UTXO with hash {} is unrecoverable: {reason}",
+                Hash::hash(&monitored_utxo.utxo)
+            );
             let mut monitored_utxo = monitored_utxo.clone();
+            monitored_utxo.mark_unrecoverable(tip_info);
+            Ok(Some(monitored_utxo))
+        };
 
-            // walk backwards, reverting
-            for revert_block_hash in backwards.into_iter() {
-                // Was the UTXO confirmed in this block? If so, there
-                // is nothing we can do except orphan the UTXO: that
-                // is, leave it without a synced membership proof.
-                // Whenever current owned UTXOs are queried, one
-                // should take care to filter for UTXOs that have a
-                // membership proof synced to the current block tip.
-                if confirming_block_digest == revert_block_hash {
-                    warn!(
-                        "Could not recover MSMP as transaction appears to be on an abandoned chain"
-                    );
-                    break 'outer;
+        // If the UTXO was not confirmed yet, there is no
+        // point in synchronizing its membership proof.
+        let (confirming_block_digest, confirming_block_height) =
+            match monitored_utxo.confirmed_in_block {
+                Some((confirmed_block_hash, _timestamp, block_height)) => {
+                    (confirmed_block_hash, block_height)
                 }
-
-                let revert_block = self
-                    .chain
-                    .archival_state()
-                    .get_block(revert_block_hash)
-                    .await?
-                    .unwrap();
-                let maybe_revert_block_predecessor = self
-                    .chain
-                    .archival_state()
-                    .get_block(revert_block.kernel.header.prev_block_digest)
-                    .await?;
-                let previous_mutator_set = match maybe_revert_block_predecessor {
-                    Some(block) => block.kernel.body.mutator_set_accumulator.clone(),
-                    None => MutatorSetAccumulator::default(),
-                };
-
-                debug!("MUTXO confirmed at height {confirming_block_height}, reverting for height {} on abandoned chain", revert_block.kernel.header.height);
-
-                // revert removals
-                let removal_records = revert_block.kernel.body.transaction.kernel.inputs.clone();
-                for removal_record in removal_records.iter().rev() {
-                    // membership_proof.revert_update_from_removal(&removal);
-                    membership_proof
-                        .revert_update_from_remove(removal_record)
-                        .expect("Could not revert membership proof from removal record.");
+                None => {
+                    return Ok(None);
                 }
+            };
+
+        // try latest (block hash, membership proof) entry
+        let Some((block_hash, mut membership_proof)) =
+            monitored_utxo.get_latest_membership_proof_entry()
+        else {
+            return unrecoverable("no membership proof is stored");
+        };
 
-                // revert additions
-                membership_proof.revert_update_from_batch_addition(&previous_mutator_set);
+        let archival_state = self.chain.archival_state();
+        if archival_state.get_block_header(block_hash).await.is_none() {
+            return unrecoverable("the block of the latest membership proof is unknown");
+        }
 
-                // unset spent_in_block field if the UTXO was spent in this block
-                if let Some((spent_block_hash, _, _)) = monitored_utxo.spent_in_block {
-                    if spent_block_hash == revert_block_hash {
-                        monitored_utxo.spent_in_block = None;
-                    }
-                }
+        // request path-to-tip
+        let (backwards, _luca, forwards) = archival_state.find_path(block_hash, tip_hash).await;
 
-                // assert valid (if unspent)
-                assert!(monitored_utxo.spent_in_block.is_some() || previous_mutator_set
-                    .verify(Hash::hash(&monitored_utxo.utxo), &membership_proof), "Failed to verify monitored UTXO {monitored_utxo:?}\n against previous MSA in block {revert_block:?}");
+        // after this point, we may be modifying it.
+        let mut monitored_utxo = monitored_utxo.clone();
+
+        // walk backwards, reverting
+        for revert_block_hash in backwards.into_iter() {
+            // Was the UTXO confirmed in this block? If so, the UTXO
+            // is on an abandoned chain.
+            if confirming_block_digest == revert_block_hash {
+                warn!("Could not recover MSMP as transaction appears to be on an abandoned chain");
+                return Ok(None);
             }
 
-            // walk forwards, applying
-            for apply_block_hash in forwards.into_iter() {
-                // Was the UTXO confirmed in this block?
-                // This can occur in some edge cases of forward-only
-                // resynchronization. In this case, assume the
-                // membership proof is already synced to this block.
-                if confirming_block_digest == apply_block_hash {
-                    continue;
-                }
+            let Some(revert_block) = archival_state.get_block(revert_block_hash).await? else {
+                return unrecoverable("a block to revert is not stored");
+            };
+            let maybe_revert_block_predecessor = archival_state
+                .get_block(revert_block.kernel.header.prev_block_digest)
+                .await?;
+            let previous_mutator_set = match maybe_revert_block_predecessor {
+                Some(block) => block.kernel.body.mutator_set_accumulator.clone(),
+                None => MutatorSetAccumulator::default(),
+            };
 
-                let apply_block = self
-                    .chain
-                    .archival_state()
-                    .get_block(apply_block_hash)
-                    .await?
-                    .unwrap();
-                let maybe_apply_block_predecessor = self
-                    .chain
-                    .archival_state()
-                    .get_block(apply_block.kernel.header.prev_block_digest)
-                    .await?;
-                let mut block_msa = match maybe_apply_block_predecessor {
-                    Some(block) => block.kernel.body.mutator_set_accumulator.clone(),
-                    None => MutatorSetAccumulator::default(),
-                };
-                let addition_records = apply_block.kernel.body.transaction.kernel.outputs.clone();
-                let removal_records = apply_block.kernel.body.transaction.kernel.inputs.clone();
-
-                // apply additions
-                for addition_record in addition_records.iter() {
-                    membership_proof
-                        .update_from_addition(
-                            Hash::hash(&monitored_utxo.utxo),
-                            &block_msa,
-                            addition_record,
-                        )
-                        .expect("Could not update membership proof with addition record.");
-                    block_msa.add(addition_record);
+            debug!("MUTXO confirmed at height {confirming_block_height}, reverting for height {} on abandoned chain", revert_block.kernel.header.height);
+
+            // revert removals
+            let removal_records = revert_block.kernel.body.transaction.kernel.inputs.clone();
+            for removal_record in removal_records.iter().rev() {
+                if membership_proof
+                    .revert_update_from_remove(removal_record)
+                    .is_err()
+                {
+                    return unrecoverable("a removal record could not be reverted");
                 }
+            }
 
-                // apply removals
-                for removal_record in removal_records.iter() {
-                    membership_proof
-                        .update_from_remove(removal_record)
-                        .expect("Could not update membership proof from removal record.");
-                    block_msa.remove(removal_record);
+            // revert additions
+            membership_proof.revert_update_from_batch_addition(&previous_mutator_set);
+
+            // unset spent_in_block field if the UTXO was spent in this block
+            if let Some((spent_block_hash, _, _)) = monitored_utxo.spent_in_block {
+                if spent_block_hash == revert_block_hash {
+                    monitored_utxo.spent_in_block = None;
                 }
+            }
 
-                assert_eq!(block_msa, apply_block.kernel.body.mutator_set_accumulator);
+            // assert valid (if unspent)
+            assert!(monitored_utxo.spent_in_block.is_some() || previous_mutator_set
+                .verify(Hash::hash(&monitored_utxo.utxo), &membership_proof), "Failed to verify monitored UTXO {monitored_utxo:?}\n against previous MSA in block {revert_block:?}");
+        }
+
+        // walk forwards, applying
+        for apply_block_hash in forwards.into_iter() {
+            // Was the UTXO confirmed in this block?
+            // This can occur in some edge cases of forward-only
+            // resynchronization. In this case, assume the
+            // membership proof is already synced to this block.
+            if confirming_block_digest == apply_block_hash {
+                continue;
+            }
+
+            let Some(apply_block) = archival_state.get_block(apply_block_hash).await? else {
+                return unrecoverable("a block to apply is not stored");
+            };
+            let maybe_apply_block_predecessor = archival_state
+                .get_block(apply_block.kernel.header.prev_block_digest)
+                .await?;
+            let mut block_msa = match maybe_apply_block_predecessor {
+                Some(block) => block.kernel.body.mutator_set_accumulator.clone(),
+                None => MutatorSetAccumulator::default(),
+            };
+            let addition_records = apply_block.kernel.body.transaction.kernel.outputs.clone();
+            let removal_records = apply_block.kernel.body.transaction.kernel.inputs.clone();
+
+            // apply additions
+            for addition_record in addition_records.iter() {
+                if membership_proof
+                    .update_from_addition(
+                        Hash::hash(&monitored_utxo.utxo),
+                        &block_msa,
+                        addition_record,
+                    )
+                    .is_err()
+                {
+                    return unrecoverable("an addition record could not be applied");
+                }
+                block_msa.add(addition_record);
             }
 
-            // store updated membership proof
-            monitored_utxo.add_membership_proof_for_tip(tip_hash, membership_proof);
+            // apply removals
+            for removal_record in removal_records.iter() {
+                if membership_proof.update_from_remove(removal_record).is_err() {
+                    return unrecoverable("a removal record could not be applied");
+                }
+                block_msa.remove(removal_record);
+            }
 
-            // update storage.
-            monitored_utxos.set(i, monitored_utxo).await
+            assert_eq!(block_msa, apply_block.kernel.body.mutator_set_accumulator);
         }
 
-        // Update sync label and persist
-        self.wallet_state.wallet_db.set_sync_label(tip_hash).await;
-        self.wallet_state.wallet_db.persist().await;
+        // store updated membership proof
+        monitored_utxo.add_membership_proof_for_tip(tip_hash, membership_proof);
 
-        Ok(())
+        Ok(Some(monitored_utxo))
     }
 
     /// Delete from the database all monitored UTXOs from abandoned chains with a depth deeper than
@@ -1064,7 +1854,7 @@ impl GlobalState {
         );
 
         let monitored_utxos = self.wallet_state.wallet_db.monitored_utxos_mut();
-        let mut removed_count = 0;
+        let mut abandoned_indices = vec![];
 
         // Find monitored_utxo for updating
         for i in 0..monitored_utxos.len().await {
@@ -1095,11 +1885,17 @@ impl GlobalState {
                 if abandoned {
                     mutxo.abandoned_at = Some(current_tip_info);
                     monitored_utxos.set(i, mutxo).await;
-                    removed_count += 1;
+                    abandoned_indices.push(i);
                 }
             }
         }
 
+        let removed_count = abandoned_indices.len();
+        self.wallet_state
+            .wallet_db
+            .record_wallet_change(current_tip_digest, abandoned_indices)
+            .await;
+
         Ok(removed_count)
     }
 
@@ -1107,22 +1903,27 @@ impl GlobalState {
         // flush wallet databases
         self.wallet_state.wallet_db.persist().await;
 
-        // flush block_index database
-        self.chain.archival_state_mut().block_index_db.flush().await;
+        // a light node only has its tip to persist
+        if let BlockchainState::Light(blc) = &self.chain {
+            blc.store.save(&blc.light_state).await?;
+        } else {
+            // flush block_index database
+            self.chain.archival_state_mut().block_index_db.flush().await;
 
-        // persist archival_mutator_set, with sync label
-        let hash = self.chain.archival_state().get_tip().await.hash();
-        self.chain
-            .archival_state_mut()
-            .archival_mutator_set
-            .set_sync_label(hash)
-            .await;
+            // persist archival_mutator_set, with sync label
+            let hash = self.chain.archival_state().get_tip().await.hash();
+            self.chain
+                .archival_state_mut()
+                .archival_mutator_set
+                .set_sync_label(hash)
+                .await;
 
-        self.chain
-            .archival_state_mut()
-            .archival_mutator_set
-            .persist()
-            .await;
+            self.chain
+                .archival_state_mut()
+                .archival_mutator_set
+                .persist()
+                .await;
+        }
 
         // flush peer_standings
         self.net.peer_databases.peer_standings.flush().await;
@@ -1165,20 +1966,29 @@ impl GlobalState {
             new_block: Block,
             coinbase_utxo_info: Option<ExpectedUtxo>,
         ) -> Result<()> {
+            let mut stage_timings = vec![];
+
             // Apply the updates
-            myself
-                .chain
-                .archival_state_mut()
-                .write_block_as_tip(&new_block)
-                .await?;
+            let (write_result, duration) = time_fn_call_async(
+                myself
+                    .chain
+                    .archival_state_mut()
+                    .write_block_as_tip(&new_block),
+            )
+            .await;
+            write_result?;
+            stage_timings.push((STAGE_WRITE_BLOCK, duration));
 
             // update the mutator set with the UTXOs from this block
-            myself
-                .chain
-                .archival_state_mut()
-                .update_mutator_set(&new_block)
-                .await
-                .expect("Updating mutator set must succeed");
+            let (mutator_set_result, duration) = time_fn_call_async(
+                myself
+                    .chain
+                    .archival_state_mut()
+                    .update_mutator_set(&new_block),
+            )
+            .await;
+            mutator_set_result.expect("Updating mutator set must succeed");
+            stage_timings.push((STAGE_UPDATE_MUTATOR_SET, duration));
 
             if let Some(coinbase_info) = coinbase_utxo_info {
                 // Notify wallet to expect the coinbase UTXO, as we mined this block
@@ -1213,36 +2023,82 @@ impl GlobalState {
             );
             let previous_ms_accumulator = tip_parent.body().mutator_set_accumulator.clone();
 
-            // update wallet state with relevant UTXOs from this block
-            myself
-                .wallet_state
-                .update_wallet_state_with_new_block(&previous_ms_accumulator, &new_block)
-                .await?;
+            // update wallet state with relevant UTXOs from this block, unless
+            // the node runs without a wallet
+            if !myself.cli().no_wallet {
+                let (wallet_result, duration) = time_fn_call_async(
+                    myself
+                        .wallet_state
+                        .update_wallet_state_with_new_block(&previous_ms_accumulator, &new_block),
+                )
+                .await;
+                wallet_result?;
+                stage_timings.push((STAGE_UPDATE_WALLET, duration));
+            }
 
             // Update mempool with UTXOs from this block. This is done by removing all transaction
             // that became invalid/was mined by this block.
-            myself
-                .mempool
-                .update_with_block(previous_ms_accumulator, &new_block)
-                .await;
+            let ((), duration) = time_fn_call_async(
+                myself
+                    .mempool
+                    .update_with_block(previous_ms_accumulator, &new_block),
+            )
+            .await;
+            stage_timings.push((STAGE_UPDATE_MEMPOOL, duration));
 
+            let new_block_digest = new_block.hash();
+            let new_block_height = new_block.kernel.header.height;
             myself.chain.light_state_mut().set_block(new_block);
 
+            if let Some(depth) = myself.cli().finality_depth {
+                if myself.chain.is_archival_node() {
+                    if let Err(err) = myself
+                        .chain
+                        .archival_state_mut()
+                        .apply_finality_depth(depth, new_block_digest, new_block_height)
+                        .await
+                    {
+                        warn!("Failed to apply finality depth: {err}");
+                    }
+                }
+            }
+
             // Flush databases
-            myself.flush_databases().await?;
+            let (flush_result, duration) = time_fn_call_async(myself.flush_databases()).await;
+            flush_result?;
+            stage_timings.push((STAGE_FLUSH_DATABASES, duration));
+
+            let timeline = myself
+                .block_timelines
+                .entry(new_block_digest, new_block_height);
+            for (stage, duration) in stage_timings {
+                debug!("Block {new_block_digest} stage {stage} took {duration} secs");
+                timeline.add_stage(stage, duration);
+            }
 
             Ok(())
         }
 
+        let span = tracing::info_span!(
+            "set_new_tip",
+            block_digest = %new_block.hash(),
+            height = %new_block.kernel.header.height,
+        );
         crate::macros::duration_async_info!(set_new_tip_internal_worker(
             self,
             new_block,
             coinbase_utxo_info
-        ))
+        )
+        .instrument(span))
     }
 
     /// resync membership proofs
     pub async fn resync_membership_proofs(&mut self) -> Result<()> {
+        // Without a wallet there are no membership proofs to keep in sync
+        if self.cli().no_wallet {
+            return Ok(());
+        }
+
         // Do not fix memberhip proofs if node is in sync mode, as we would otherwise
         // have to sync many times, instead of just *one* time once we have caught up.
         if self.net.syncing {
@@ -1270,6 +2126,36 @@ impl GlobalState {
         // Ok(())
     }
 
+    /// Whether the proofs of a block were verified before, by this run or, on
+    /// archival nodes, by an earlier one. Takes the block's
+    /// [`proofs_digest`](Block::proofs_digest), as the block's digest does not
+    /// cover its proofs.
+    pub async fn block_proofs_verified(&self, proofs_digest: Digest) -> bool {
+        if self.verified_blocks.contains(proofs_digest) {
+            return true;
+        }
+
+        self.chain.is_archival_node()
+            && self
+                .chain
+                .archival_state()
+                .is_block_validated(proofs_digest)
+                .await
+    }
+
+    /// Record that the proofs of a block verified, by its
+    /// [`proofs_digest`](Block::proofs_digest). Blocks received without their
+    /// proofs must not be recorded.
+    pub async fn mark_block_proofs_verified(&mut self, proofs_digest: Digest) {
+        self.verified_blocks.insert(proofs_digest);
+        if self.chain.is_archival_node() {
+            self.chain
+                .archival_state_mut()
+                .mark_block_validated(proofs_digest)
+                .await;
+        }
+    }
+
     #[inline]
     pub fn cli(&self) -> &cli_args::Args {
         &self.cli
@@ -1280,17 +2166,25 @@ impl GlobalState {
 mod global_state_tests {
     use crate::{
         config_models::network::Network,
-        models::{blockchain::block::Block, state::wallet::utxo_notification_pool::UtxoNotifier},
+        models::{
+            blockchain::block::{transfer_block::TransferBlock, Block},
+            state::wallet::utxo_notification_pool::UtxoNotifier,
+        },
         tests::shared::{
             add_block_to_light_state, make_mock_block, make_mock_block_with_valid_pow,
-            mock_genesis_global_state, mock_genesis_wallet_state,
+            make_mock_transaction_with_wallet, mock_genesis_global_state,
+            mock_genesis_wallet_state,
+        },
+        util_types::mutator_set::{
+            addition_record::pseudorandom_addition_record,
+            removal_record::pseudorandom_removal_record,
         },
     };
     use num_traits::{One, Zero};
     use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
     use tracing_test::traced_test;
 
-    use super::{wallet::WalletSecret, *};
+    use super::{wallet::monitored_utxo::MonitoredUtxoState, wallet::WalletSecret, *};
 
     async fn wallet_state_has_all_valid_mps_for(
         wallet_state: &WalletState,
@@ -1318,59 +2212,51 @@ mod global_state_tests {
         true
     }
 
-    /// Similar to `GlobalState::create_transaction` but with a given timestamp,
-    /// as opposed to now.
+    /// Similar to `GlobalState::create_transaction` but taking the lock and
+    /// borrowed receiver data.
     pub(super) async fn create_transaction_with_timestamp(
         global_state_lock: &GlobalStateLock,
         receiver_data: &[UtxoReceiverData],
         fee: NeptuneCoins,
         timestamp: Timestamp,
     ) -> Result<Transaction> {
-        // UTXO data: inputs, outputs, and supporting witness data
-        let (inputs, spendable_utxos_and_mps, outputs, output_utxos) = global_state_lock
+        global_state_lock
             .lock_guard_mut()
             .await
-            .generate_utxo_data_for_transaction(receiver_data, fee, timestamp)
-            .await?;
-
-        // other data
-        let public_announcements = receiver_data
-            .iter()
-            .map(|x| x.public_announcement.clone())
-            .collect_vec();
-        let mutator_set_accumulator = global_state_lock
-            .lock_guard_mut()
+            .create_transaction(receiver_data.to_vec(), fee, timestamp)
             .await
-            .chain
-            .light_state()
-            .kernel
-            .body
-            .mutator_set_accumulator
-            .clone();
-        let privacy = global_state_lock.cli().privacy;
+    }
 
-        // TODO: The spending key can be different for each UTXO, and therefore must be supplied by `spendable_utxos_and_mps`.
-        let spending_key = global_state_lock
-            .lock_guard_mut()
-            .await
-            .wallet_state
-            .wallet_secret
-            .nth_generation_spending_key(0);
+    #[traced_test]
+    #[tokio::test]
+    async fn verified_block_proofs_are_remembered_beyond_the_cache() {
+        let global_state_lock =
+            mock_genesis_global_state(Network::RegTest, 0, WalletSecret::devnet_wallet()).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        let proofs_digest: Digest = thread_rng().gen();
+        assert!(!global_state.block_proofs_verified(proofs_digest).await);
 
-        // assemble transaction object
-        GlobalState::create_transaction_from_data(
-            spending_key,
-            inputs,
-            spendable_utxos_and_mps,
-            outputs,
-            output_utxos,
-            fee,
-            public_announcements,
-            timestamp,
-            mutator_set_accumulator,
-            privacy,
-        )
-        .await
+        global_state.mark_block_proofs_verified(proofs_digest).await;
+
+        // As after a restart, which empties the cache
+        global_state.verified_blocks = VerifiedBlockCache::default();
+        assert!(global_state.block_proofs_verified(proofs_digest).await);
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn block_proofs_digest_covers_the_proofs() {
+        let network = Network::RegTest;
+        let genesis_block = Block::genesis_block(network);
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) =
+            make_mock_block_with_valid_pow(&genesis_block, None, address, thread_rng().gen());
+        let stripped_block = Block::from(TransferBlock::from(block_1.clone()).without_proofs());
+
+        assert_eq!(block_1.hash(), stripped_block.hash());
+        assert_ne!(block_1.proofs_digest(), stripped_block.proofs_digest());
     }
 
     #[traced_test]
@@ -1447,6 +2333,11 @@ mod global_state_tests {
             tx.kernel.outputs.len(),
             "tx must have a send output and a change output"
         );
+        assert_eq!(
+            tx.kernel.outputs.len(),
+            tx.kernel.public_announcements.len(),
+            "change must be announced like a payment"
+        );
         assert_eq!(
             1,
             tx.kernel.inputs.len(),
@@ -1500,6 +2391,263 @@ mod global_state_tests {
         );
     }
 
+    #[test]
+    fn output_order_is_deterministic_and_does_not_reveal_change() {
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let receiver_data = |rng: &mut StdRng| {
+            (0..3)
+                .map(|i| {
+                    let utxo = Utxo::new(
+                        address.lock_script(),
+                        NeptuneCoins::new(i).to_native_coins(),
+                    );
+                    let sender_randomness: Digest = rng.gen();
+                    UtxoReceiverData {
+                        public_announcement: address
+                            .generate_public_announcement(&utxo, sender_randomness)
+                            .unwrap(),
+                        utxo,
+                        sender_randomness,
+                        receiver_privacy_digest: address.privacy_digest,
+                    }
+                })
+                .collect_vec()
+        };
+
+        let mut rng: StdRng =
+            SeedableRng::from_rng(thread_rng()).expect("failure lifting thread_rng to StdRng");
+        let original = receiver_data(&mut rng);
+        let mut shuffled = original.clone();
+        GlobalState::shuffle_outputs(&mut shuffled);
+        let mut shuffled_again = original.clone();
+        GlobalState::shuffle_outputs(&mut shuffled_again);
+        assert_eq!(
+            shuffled.iter().map(|rd| rd.sender_randomness).collect_vec(),
+            shuffled_again
+                .iter()
+                .map(|rd| rd.sender_randomness)
+                .collect_vec()
+        );
+
+        // The last output, where change is added, must end up in every position
+        let num_trials = 300;
+        let mut last_output_positions = [0; 3];
+        for _ in 0..num_trials {
+            let mut outputs = receiver_data(&mut rng);
+            let last_output = outputs[2].sender_randomness;
+            GlobalState::shuffle_outputs(&mut outputs);
+            let position = outputs
+                .iter()
+                .position(|rd| rd.sender_randomness == last_output)
+                .unwrap();
+            last_output_positions[position] += 1;
+        }
+        for count in last_output_positions {
+            assert!(
+                (50..150).contains(&count),
+                "last output ended up in positions {last_output_positions:?}"
+            );
+        }
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn time_locked_sends_lock_the_output_until_the_release_date() {
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 0, WalletSecret::devnet_wallet()).await;
+        let global_state = global_state_lock.lock_guard().await;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let amount = NeptuneCoins::new(1);
+        let fee = NeptuneCoins::new(1);
+
+        // The premine itself is time-locked
+        let premine_release_date = network.launch_date() + Timestamp::months(6);
+        assert!(global_state
+            .prepare_send(
+                amount,
+                address,
+                fee,
+                None,
+                premine_release_date - Timestamp::days(1)
+            )
+            .await
+            .is_err());
+
+        let now = premine_release_date + Timestamp::days(1);
+        let release_date = now + Timestamp::days(30);
+        let prepared_send = global_state
+            .prepare_send(amount, address, fee, Some(release_date), now)
+            .await
+            .unwrap();
+        assert_eq!(Some(release_date), prepared_send.preview.release_date);
+        let output = &prepared_send.receiver_data[0].utxo;
+        assert_eq!(Some(release_date), output.release_date());
+        assert!(!output.can_spend_at(release_date - Timestamp::days(1)));
+        assert!(output.can_spend_at(release_date + Timestamp::days(1)));
+
+        assert!(global_state
+            .prepare_send(amount, address, fee, Some(now - Timestamp::days(1)), now)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn cancelled_transaction_no_longer_expects_its_change() {
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 0, WalletSecret::devnet_wallet()).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let now = network.launch_date() + Timestamp::months(6) + Timestamp::days(1);
+        let prepared_send = global_state
+            .prepare_send(
+                NeptuneCoins::new(1),
+                address,
+                NeptuneCoins::new(1),
+                None,
+                now,
+            )
+            .await
+            .unwrap();
+        assert!(!prepared_send.preview.change.is_zero());
+        let expected_utxo_count = global_state.wallet_state.expected_utxos.len();
+
+        let ticket = global_state.proving_queue.ticket("send");
+        assert!(global_state.proving_queue.cancel(ticket.id()));
+        assert!(global_state
+            .create_transaction_with_inputs(
+                prepared_send.receiver_data,
+                prepared_send.preview.fee,
+                prepared_send.timestamp,
+                prepared_send.spendable_utxos_and_mps,
+                ticket,
+            )
+            .await
+            .is_err());
+        assert_eq!(
+            expected_utxo_count,
+            global_state.wallet_state.expected_utxos.len()
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn drafted_transaction_is_proven_without_the_lock() {
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 0, WalletSecret::devnet_wallet()).await;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let now = network.launch_date() + Timestamp::months(6) + Timestamp::days(1);
+        let (draft, ticket) = {
+            let mut global_state = global_state_lock.lock_guard_mut().await;
+            let prepared_send = global_state
+                .prepare_send(
+                    NeptuneCoins::new(1),
+                    address,
+                    NeptuneCoins::new(1),
+                    None,
+                    now,
+                )
+                .await
+                .unwrap();
+            let draft = global_state
+                .draft_transaction_with_inputs(
+                    prepared_send.receiver_data,
+                    prepared_send.preview.fee,
+                    prepared_send.timestamp,
+                    prepared_send.spendable_utxos_and_mps,
+                )
+                .await
+                .unwrap();
+            (draft, global_state.proving_queue.ticket("send"))
+        };
+
+        // The lock is free while proving
+        let digest = draft.transaction_digest();
+        let proving = tokio::spawn(draft.prove(ticket));
+        drop(global_state_lock.lock_guard_mut().await);
+
+        let transaction = proving.await.unwrap().unwrap();
+        assert_eq!(digest, Hash::hash(&transaction));
+        assert!(transaction.is_valid());
+    }
+
+    #[tokio::test]
+    async fn unconfirmed_balance_applies_mempool_transactions() {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 0, WalletSecret::devnet_wallet()).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        let own_address = global_state
+            .wallet_state
+            .wallet_secret
+            .nth_generation_spending_key(0)
+            .to_address();
+        let now = network.launch_date() + Timestamp::months(6) + Timestamp::days(1);
+
+        let balance = global_state.get_wallet_balance(now).await;
+        assert!(!balance.confirmed.is_zero());
+        assert_eq!(balance.confirmed, balance.unconfirmed);
+
+        // A transaction in the mempool spends the premine, and sends some of
+        // it back to this wallet
+        let (premine, premine_msmp) = global_state
+            .get_wallet_status_for_tip()
+            .await
+            .synced_unspent[0]
+            .clone();
+        let mut input = pseudorandom_removal_record(rng.gen());
+        input.absolute_indices = premine_msmp.compute_indices(Hash::hash(&premine.utxo));
+        let received_amount = NeptuneCoins::new(3);
+        let output = global_state
+            .wallet_state
+            .expected_utxos
+            .add_expected_utxo(
+                Utxo::new(own_address.lock_script(), received_amount.to_native_coins()),
+                rng.gen(),
+                rng.gen(),
+                UtxoNotifier::Myself,
+            )
+            .unwrap();
+        let transaction = make_mock_transaction_with_wallet(
+            vec![input],
+            vec![output, AdditionRecord::new(rng.gen())],
+            NeptuneCoins::new(1),
+            &global_state.wallet_state,
+            Some(now),
+        );
+        global_state.mempool.insert(&transaction);
+
+        let premine_amount = premine.utxo.get_native_currency_amount();
+        let balance_with_mempool = global_state.get_wallet_balance(now).await;
+        assert_eq!(balance.confirmed, balance_with_mempool.confirmed);
+        assert_eq!(
+            balance.confirmed + received_amount,
+            balance_with_mempool.unconfirmed + premine_amount
+        );
+
+        // Own transactions in the Dandelion stem phase are not in the mempool yet
+        global_state.mempool.remove(Hash::hash(&transaction));
+        global_state
+            .net
+            .stem_pool
+            .insert(transaction, now, &mut rng);
+        assert_eq!(
+            balance_with_mempool,
+            global_state.get_wallet_balance(now).await
+        );
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn restore_monitored_utxos_from_recovery_data_test() {
@@ -1647,6 +2795,77 @@ mod global_state_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn resync_marks_membership_proof_of_unknown_block_as_unrecoverable_test() -> Result<()> {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 2, WalletSecret::devnet_wallet()).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+
+        let genesis_block = Block::genesis_block(network);
+        let (mock_block_1a, _, _) = make_mock_block(
+            &genesis_block,
+            None,
+            WalletSecret::new_random()
+                .nth_generation_spending_key(0)
+                .to_address(),
+            rng.gen(),
+        );
+        global_state
+            .chain
+            .archival_state_mut()
+            .write_block_as_tip(&mock_block_1a)
+            .await?;
+
+        // Pretend that the premine UTXO's membership proof belongs to a block that
+        // was never stored, e.g. because it was pruned
+        let monitored_utxos = global_state.wallet_state.wallet_db.monitored_utxos_mut();
+        let mut premine_mutxo = monitored_utxos.get(0).await;
+        premine_mutxo.blockhash_to_membership_proof[0].0 = rng.gen();
+        monitored_utxos.set(0, premine_mutxo).await;
+        assert_eq!(
+            MonitoredUtxoState::NeedsRestore,
+            global_state
+                .wallet_state
+                .list_utxos(mock_block_1a.hash())
+                .await[0]
+                .state
+        );
+
+        global_state
+            .resync_membership_proofs_from_stored_blocks(mock_block_1a.hash())
+            .await?;
+
+        let reports = global_state
+            .wallet_state
+            .list_utxos(mock_block_1a.hash())
+            .await;
+        assert_eq!(MonitoredUtxoState::Unrecoverable, reports[0].state);
+        assert_eq!(
+            Some(mock_block_1a.hash()),
+            global_state
+                .wallet_state
+                .wallet_db
+                .monitored_utxos()
+                .get(0)
+                .await
+                .unrecoverable_at
+                .map(|(digest, _, _)| digest)
+        );
+
+        // An unrecoverable MUTXO does not keep the wallet from counting as synced
+        assert!(
+            global_state
+                .wallet_state
+                .is_synced_to(mock_block_1a.hash())
+                .await
+        );
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn resync_ms_membership_proofs_fork_test() -> Result<()> {
@@ -1671,7 +2890,7 @@ mod global_state_tests {
                 ExpectedUtxo::new(
                     coinbase_utxo,
                     coinbase_output_randomness,
-                    own_spending_key.privacy_preimage,
+                    own_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::OwnMiner,
                 ),
             )
@@ -1733,6 +2952,129 @@ mod global_state_tests {
         Ok(())
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn balance_and_utxos_at_past_heights_follow_current_chain() {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 2, WalletSecret::devnet_wallet()).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+        global_state.wallet_state.historical_index = true;
+        let own_spending_key = global_state
+            .wallet_state
+            .wallet_secret
+            .nth_generation_spending_key(0);
+        let own_receiving_address = own_spending_key.to_address();
+        let other_receiving_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let genesis_block = global_state.chain.archival_state().get_tip().await;
+        let premine_balance = global_state.get_balance_at(0u64.into()).await.unwrap();
+        assert!(!premine_balance.is_zero());
+
+        // Receive the coinbase of block 1a, then lose it to a longer fork
+        let (block_1a, coinbase_utxo, coinbase_output_randomness) =
+            make_mock_block(&genesis_block, None, own_receiving_address, rng.gen());
+        global_state
+            .set_new_self_mined_tip(
+                block_1a.clone(),
+                ExpectedUtxo::new(
+                    coinbase_utxo.clone(),
+                    coinbase_output_randomness,
+                    own_spending_key.privacy_preimage.expose(),
+                    UtxoNotifier::OwnMiner,
+                ),
+            )
+            .await
+            .unwrap();
+        let coinbase_addition_record = commit(
+            Hash::hash(&coinbase_utxo),
+            coinbase_output_randomness,
+            own_receiving_address.privacy_digest,
+        );
+        let coinbase_amount = coinbase_utxo.get_native_currency_amount();
+        assert_eq!(
+            premine_balance + coinbase_amount,
+            global_state.get_balance_at(1u64.into()).await.unwrap()
+        );
+        assert_eq!(
+            premine_balance,
+            global_state.get_balance_at(0u64.into()).await.unwrap()
+        );
+        assert!(global_state
+            .was_utxo_unspent_at(coinbase_addition_record, 1u64.into())
+            .await
+            .unwrap());
+        assert!(!global_state
+            .was_utxo_unspent_at(coinbase_addition_record, 0u64.into())
+            .await
+            .unwrap());
+
+        let (block_1b, other_coinbase_utxo, other_coinbase_output_randomness) =
+            make_mock_block(&genesis_block, None, other_receiving_address, rng.gen());
+        let (block_2b, _, _) = make_mock_block(&block_1b, None, other_receiving_address, rng.gen());
+        global_state.set_new_tip(block_1b).await.unwrap();
+        global_state.set_new_tip(block_2b.clone()).await.unwrap();
+        global_state
+            .resync_membership_proofs_from_stored_blocks(block_2b.hash())
+            .await
+            .unwrap();
+        assert_eq!(
+            premine_balance,
+            global_state.get_balance_at(1u64.into()).await.unwrap()
+        );
+        assert!(!global_state
+            .was_utxo_unspent_at(coinbase_addition_record, 1u64.into())
+            .await
+            .unwrap());
+
+        // Other UTXOs are found in the AOCL, but their spending is hidden
+        let other_coinbase_addition_record = commit(
+            Hash::hash(&other_coinbase_utxo),
+            other_coinbase_output_randomness,
+            other_receiving_address.privacy_digest,
+        );
+        assert_eq!(
+            Err(HistoricalQueryError::NotOwned),
+            global_state
+                .was_utxo_unspent_at(other_coinbase_addition_record, 1u64.into())
+                .await
+        );
+        assert!(!global_state
+            .was_utxo_unspent_at(other_coinbase_addition_record, 0u64.into())
+            .await
+            .unwrap());
+        assert!(!global_state
+            .was_utxo_unspent_at(pseudorandom_addition_record(rng.gen()), 2u64.into())
+            .await
+            .unwrap());
+
+        assert_eq!(
+            Err(HistoricalQueryError::AboveTip {
+                height: 3u64.into(),
+                tip_height: 2u64.into()
+            }),
+            global_state.get_balance_at(3u64.into()).await
+        );
+
+        // Blocks processed without the index cannot be undone
+        global_state.wallet_state.historical_index = false;
+        let (block_3b, _, _) = make_mock_block(&block_2b, None, other_receiving_address, rng.gen());
+        global_state.set_new_tip(block_3b.clone()).await.unwrap();
+        assert_eq!(
+            Err(HistoricalQueryError::NotIndexed {
+                block_digest: block_3b.hash(),
+                height: 3u64.into()
+            }),
+            global_state.get_balance_at(2u64.into()).await
+        );
+        assert_eq!(
+            premine_balance,
+            global_state.get_balance_at(3u64.into()).await.unwrap()
+        );
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn resync_ms_membership_proofs_across_stale_fork() -> Result<()> {
@@ -1761,7 +3103,7 @@ mod global_state_tests {
                     ExpectedUtxo::new(
                         coinbase_utxo_1a,
                         cb_utxo_output_randomness_1a,
-                        own_spending_key.privacy_preimage,
+                        own_spending_key.privacy_preimage.expose(),
                         UtxoNotifier::OwnMiner,
                     ),
                 )
@@ -2039,7 +3381,7 @@ mod global_state_tests {
                 .add_expected_utxo(
                     rec_data.utxo.clone(),
                     rec_data.sender_randomness,
-                    alice_spending_key.privacy_preimage,
+                    alice_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::Cli,
                 )
                 .unwrap();
@@ -2054,7 +3396,7 @@ mod global_state_tests {
                 .add_expected_utxo(
                     rec_data.utxo.clone(),
                     rec_data.sender_randomness,
-                    bob_spending_key.privacy_preimage,
+                    bob_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::Cli,
                 )
                 .unwrap();
@@ -2068,7 +3410,7 @@ mod global_state_tests {
                 ExpectedUtxo::new(
                     cb_utxo,
                     cb_output_randomness,
-                    genesis_spending_key.privacy_preimage,
+                    genesis_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::OwnMiner,
                 ),
             )
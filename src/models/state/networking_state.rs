@@ -1,12 +1,29 @@
 use crate::config_models::data_directory::DataDirectory;
 use crate::database::{create_db_if_missing, NeptuneLevelDb, WriteBatchAsync};
+use crate::models::consensus::timestamp::Timestamp;
 use crate::models::database::PeerDatabases;
 use crate::models::peer::{self, PeerStanding};
+use crate::models::state::alerts::AlertStore;
+use crate::models::state::block_serving::BlockServingThrottle;
+use crate::models::state::clock_skew::ClockSkewStatus;
+use crate::models::state::connection_metrics::ConnectionMetrics;
+use crate::models::state::dandelion::StemPool;
+use crate::models::state::disk_space::DiskSpaceStatus;
+use crate::models::state::memory_status::BufferSizes;
+use crate::models::state::peer_quality::PeerHistory;
+use crate::models::state::peer_reconnect::PeerReconnector;
+use crate::models::state::slow_peers::SlowPeers;
+use crate::models::state::tip_staleness::TipStaleness;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_ext::DigestExt;
+use crate::util_types::entropy;
 use anyhow::Result;
+use bytesize::ByteSize;
 use std::net::IpAddr;
 use std::{collections::HashMap, net::SocketAddr};
 
 pub const BANNED_IPS_DB_NAME: &str = "banned_ips";
+pub const PEER_HISTORIES_DB_NAME: &str = "peer_histories";
 
 type PeerMap = HashMap<SocketAddr, peer::PeerInfo>;
 
@@ -29,19 +46,85 @@ pub struct NetworkingState {
 
     // Read-only value set during startup
     pub instance_id: u128,
+
+    // Result of the latest comparison of our clock against peers and the tip.
+    // Only the main thread may update this value.
+    pub clock_skew: ClockSkewStatus,
+
+    // Result of the latest check for a tip that stopped advancing while peers
+    // report higher tips. Only the main thread may update this value.
+    pub tip_staleness: TipStaleness,
+
+    // Competing tip of equal work that the operator pinned the node to, while
+    // it is the tip. Only the main thread may update this value.
+    pub preferred_tip: Option<Digest>,
+
+    // Result of the latest check of the free disk space. Only the main thread
+    // may update this value.
+    pub disk_space: DiskSpaceStatus,
+
+    // Transactions in the stem phase of Dandelion relay, not yet diffused.
+    // Peer threads remove transactions they see diffused.
+    pub stem_pool: StemPool,
+
+    // Verified network alerts that have not expired.
+    // Peer threads and the RPC server add alerts.
+    pub alerts: AlertStore,
+
+    // Bytes of blocks served to each peer in the current window.
+    // Peer threads record the blocks they serve.
+    pub block_serving: BlockServingThrottle,
+
+    // Handshake failures, connection durations and traffic by message type.
+    // Peer threads record their own connections.
+    pub connection_metrics: ConnectionMetrics,
+
+    // Outcomes of the handshakes with peers, by IP, to tell chronically slow
+    // peers. Peer threads record their own handshakes.
+    pub slow_peers: SlowPeers,
+
+    // When to attempt connecting to the peers given with `--peers`.
+    // Only the main thread may update this value.
+    pub peer_reconnector: PeerReconnector,
+
+    // Addresses on which incoming peer connections are accepted. An
+    // unspecified IP stands for all interfaces in its family.
+    // Read-only value set during startup
+    pub listen_addresses: Vec<SocketAddr>,
+
+    // Sizes of the block buffers the main and peer threads keep outside of
+    // the global state, for memory accounting. Each thread records its own.
+    pub buffer_sizes: BufferSizes,
 }
 
 impl NetworkingState {
-    pub fn new(peer_map: PeerMap, peer_databases: PeerDatabases, syncing: bool) -> Self {
+    pub fn new(
+        peer_map: PeerMap,
+        peer_databases: PeerDatabases,
+        syncing: bool,
+        max_stem_pool_size: ByteSize,
+    ) -> Self {
         Self {
             peer_map,
             peer_databases,
             syncing,
-            instance_id: rand::random(),
+            instance_id: entropy::random(),
+            clock_skew: ClockSkewStatus::default(),
+            tip_staleness: TipStaleness::new(Digest::ZERO, Timestamp::now()),
+            preferred_tip: None,
+            disk_space: DiskSpaceStatus::default(),
+            stem_pool: StemPool::new(max_stem_pool_size),
+            alerts: AlertStore::default(),
+            block_serving: BlockServingThrottle::default(),
+            connection_metrics: ConnectionMetrics::default(),
+            slow_peers: SlowPeers::default(),
+            peer_reconnector: PeerReconnector::default(),
+            listen_addresses: vec![],
+            buffer_sizes: BufferSizes::default(),
         }
     }
 
-    /// Create databases for peer standings
+    /// Create databases for peer standings and peer histories
     pub async fn initialize_peer_databases(data_dir: &DataDirectory) -> Result<PeerDatabases> {
         let database_dir_path = data_dir.database_dir_path();
         DataDirectory::create_dir_if_not_exists(&database_dir_path).await?;
@@ -51,8 +134,16 @@ impl NetworkingState {
             &create_db_if_missing(),
         )
         .await?;
+        let peer_histories = NeptuneLevelDb::<IpAddr, PeerHistory>::new(
+            &data_dir.peer_histories_database_dir_path(),
+            &create_db_if_missing(),
+        )
+        .await?;
 
-        Ok(PeerDatabases { peer_standings })
+        Ok(PeerDatabases {
+            peer_standings,
+            peer_histories,
+        })
     }
 
     /// Return a list of peer sanctions stored in the database.
@@ -73,6 +164,20 @@ impl NetworkingState {
         self.peer_databases.peer_standings.get(ip).await
     }
 
+    /// What is known about the peer from past connections, or the history of
+    /// a new peer
+    pub async fn get_peer_history_from_database(&self, ip: IpAddr) -> PeerHistory {
+        self.peer_databases
+            .peer_histories
+            .get(ip)
+            .await
+            .unwrap_or_default()
+    }
+
+    pub async fn write_peer_history(&mut self, ip: IpAddr, history: PeerHistory) {
+        self.peer_databases.peer_histories.put(ip, history).await
+    }
+
     pub async fn clear_ip_standing_in_database(&mut self, ip: IpAddr) {
         let old_standing = self.peer_databases.peer_standings.get(ip).await;
 
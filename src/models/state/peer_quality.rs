@@ -0,0 +1,224 @@
+use std::net::SocketAddr;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::block_propagation::PeerPropagationStats;
+
+/// Probability that a sync request goes to a random candidate instead of the
+/// best ranked one, so that peers without a track record get to build one
+pub const SYNC_PROBE_PROBABILITY: f64 = 0.1;
+
+/// Score lost per point of negative standing, i.e. per protocol violation
+/// severity point
+const STANDING_WEIGHT: f64 = 0.1;
+
+/// Score lost per second a peer's block announcements lag behind the first
+/// announcer, on average
+const ANNOUNCEMENT_DELAY_WEIGHT_PER_SEC: f64 = 0.5;
+
+/// How a peer has served block batch requests during syncing
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncPeerRecord {
+    pub answered_requests: u64,
+    pub timed_out_requests: u64,
+    pub blocks_received: u64,
+    pub response_millis_total: u64,
+}
+
+impl SyncPeerRecord {
+    pub fn record_answer(&mut self, block_count: u64, response_millis: u64) {
+        self.answered_requests += 1;
+        self.blocks_received += block_count;
+        self.response_millis_total += response_millis;
+    }
+
+    pub fn record_timeout(&mut self) {
+        self.timed_out_requests += 1;
+    }
+
+    /// Blocks received per second, over all answered requests
+    pub fn blocks_per_sec(&self) -> Option<f64> {
+        (self.response_millis_total > 0)
+            .then(|| self.blocks_received as f64 * 1000.0 / self.response_millis_total as f64)
+    }
+
+    /// Fraction of requests that were answered
+    pub fn answer_ratio(&self) -> Option<f64> {
+        let request_count = self.answered_requests + self.timed_out_requests;
+        (request_count > 0).then(|| self.answered_requests as f64 / request_count as f64)
+    }
+
+    /// The record with all counts halved, to seed the record of a new
+    /// connection with, so that past connections weigh less than recent ones
+    pub fn decayed(&self) -> Self {
+        Self {
+            answered_requests: self.answered_requests / 2,
+            timed_out_requests: self.timed_out_requests / 2,
+            blocks_received: self.blocks_received / 2,
+            response_millis_total: self.response_millis_total / 2,
+        }
+    }
+}
+
+/// What is known about a peer from past connections, persisted per IP in the
+/// peer database
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerHistory {
+    /// Number of past connections with the peer
+    pub connections: u64,
+
+    /// How the peer served block batch requests, as of its last disconnect
+    pub sync_record: SyncPeerRecord,
+
+    /// Number of protocol violations the peer was sanctioned for
+    pub violations: u64,
+}
+
+/// Rank a peer as a source of blocks. Higher is better, and a peer nothing is
+/// known about scores zero.
+///
+/// Rewards throughput in past batch requests and being first to announce new
+/// blocks. Penalizes unanswered requests, lagging announcements and protocol
+/// violations, as reflected by a negative standing.
+pub fn sync_quality_score(
+    standing: i32,
+    sync_record: Option<&SyncPeerRecord>,
+    propagation: Option<&PeerPropagationStats>,
+) -> f64 {
+    let mut score = f64::from(standing.min(0)) * STANDING_WEIGHT;
+
+    if let Some(record) = sync_record {
+        if let Some(blocks_per_sec) = record.blocks_per_sec() {
+            score += blocks_per_sec.ln_1p();
+        }
+        if let Some(answer_ratio) = record.answer_ratio() {
+            score += 2.0 * (answer_ratio - 0.5);
+        }
+    }
+
+    if let Some(stats) = propagation {
+        if stats.announcements > 0 {
+            score += stats.first_announcements as f64 / stats.announcements as f64;
+        }
+        if let Some(mean_delay_millis) = stats.mean_delay_millis() {
+            score -= mean_delay_millis as f64 / 1000.0 * ANNOUNCEMENT_DELAY_WEIGHT_PER_SEC;
+        }
+    }
+
+    score
+}
+
+/// Keep the candidates that are archival nodes, as advertised in their
+/// handshakes, since light nodes cannot serve blocks below their tip. Falls
+/// back to all candidates if none is archival.
+pub fn prefer_archival_peers(
+    candidates: Vec<SocketAddr>,
+    is_archival: impl Fn(SocketAddr) -> bool,
+) -> Vec<SocketAddr> {
+    let archival = candidates
+        .iter()
+        .copied()
+        .filter(|&peer| is_archival(peer))
+        .collect::<Vec<_>>();
+    if archival.is_empty() {
+        candidates
+    } else {
+        archival
+    }
+}
+
+/// Choose the peer to request the next batch of blocks from: usually the best
+/// scoring candidate, occasionally a random one.
+pub fn choose_sync_peer<R: Rng>(
+    scored_candidates: &[(SocketAddr, f64)],
+    rng: &mut R,
+) -> Option<SocketAddr> {
+    if rng.gen_bool(SYNC_PROBE_PROBABILITY) {
+        return scored_candidates.choose(rng).map(|(peer, _)| *peer);
+    }
+
+    scored_candidates
+        .iter()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(peer, _)| *peer)
+}
+
+#[cfg(test)]
+mod peer_quality_tests {
+    use rand::thread_rng;
+
+    use super::*;
+    use crate::tests::shared::get_dummy_socket_address;
+
+    #[test]
+    fn fast_reliable_peers_outrank_slow_and_misbehaving_ones() {
+        let mut fast = SyncPeerRecord::default();
+        fast.record_answer(100, 2_000);
+        let mut slow = SyncPeerRecord::default();
+        slow.record_answer(100, 20_000);
+        let mut unreliable = fast.clone();
+        unreliable.record_timeout();
+        unreliable.record_timeout();
+
+        let fast_score = sync_quality_score(0, Some(&fast), None);
+        assert!(fast_score > sync_quality_score(0, Some(&slow), None));
+        assert!(fast_score > sync_quality_score(0, Some(&unreliable), None));
+        assert!(fast_score > sync_quality_score(-50, Some(&fast), None));
+        assert_eq!(0.0, sync_quality_score(0, None, None));
+    }
+
+    #[test]
+    fn past_records_seed_new_connections_at_reduced_weight() {
+        let mut record = SyncPeerRecord::default();
+        record.record_answer(100, 2_000);
+        record.record_answer(100, 2_000);
+        record.record_timeout();
+
+        let seed = record.decayed();
+        assert_eq!(1, seed.answered_requests);
+        assert_eq!(0, seed.timed_out_requests);
+        assert_eq!(record.blocks_per_sec(), seed.blocks_per_sec());
+        assert_eq!(
+            SyncPeerRecord::default(),
+            SyncPeerRecord::default().decayed()
+        );
+
+        let history = PeerHistory {
+            connections: 3,
+            sync_record: record,
+            violations: 1,
+        };
+        let bytes = bincode::serialize(&history).unwrap();
+        assert_eq!(history, bincode::deserialize(&bytes).unwrap());
+    }
+
+    #[test]
+    fn best_peer_is_preferred_but_others_are_probed() {
+        let mut rng = thread_rng();
+        let peers: Vec<SocketAddr> = (1..=3).map(get_dummy_socket_address).collect();
+        let scored = vec![(peers[0], -1.0), (peers[1], 3.0), (peers[2], 0.0)];
+
+        let mut best_count = 0;
+        for _ in 0..1000 {
+            if choose_sync_peer(&scored, &mut rng) == Some(peers[1]) {
+                best_count += 1;
+            }
+        }
+        assert!((850..1000).contains(&best_count));
+        assert!(choose_sync_peer(&[], &mut rng).is_none());
+    }
+
+    #[test]
+    fn archival_peers_are_preferred_when_available() {
+        let peers: Vec<SocketAddr> = (1..=3).map(get_dummy_socket_address).collect();
+
+        let only_second_is_archival = |peer| peer == peers[1];
+        assert_eq!(
+            vec![peers[1]],
+            prefer_archival_peers(peers.clone(), only_second_is_archival)
+        );
+        assert_eq!(peers, prefer_archival_peers(peers.clone(), |_| false));
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+
+use crate::util_types::entropy;
+use serde::{Deserialize, Serialize};
+
+/// Delay before the second attempt to connect to a configured peer. Each
+/// failed attempt doubles the delay, up to [`RECONNECT_MAX_DELAY`].
+pub const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(5);
+
+/// Longest delay between attempts to connect to a configured peer
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+
+/// Random extra delay, as a fraction of the delay, so that nodes that lost
+/// the same peer do not all reconnect to it at once
+const RECONNECT_JITTER: f64 = 0.25;
+
+/// Whether this node is connected to a peer given with `--peers`, and if not,
+/// when it will try again
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReconnectState {
+    Connected,
+
+    /// Not connected despite the attempts made since startup, or since the
+    /// connection was lost
+    Waiting {
+        attempts: u32,
+        next_attempt: SystemTime,
+    },
+
+    /// The max number of attempts were made. Not retried until restart.
+    GaveUp {
+        attempts: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConfiguredPeerStatus {
+    pub address: SocketAddr,
+    pub state: ReconnectState,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Backoff {
+    /// Attempts since the peer was last connected
+    attempts: u32,
+    next_attempt: SystemTime,
+    connected: bool,
+}
+
+/// Schedules attempts to connect to the peers given with `--peers`, backing
+/// off exponentially while a peer cannot be reached.
+///
+/// Attempts are not tracked to completion. An attempt is deemed failed if
+/// the peer is not connected by the time the next one is due.
+#[derive(Clone, Debug, Default)]
+pub struct PeerReconnector {
+    peers: HashMap<SocketAddr, Backoff>,
+
+    /// Attempts after which to give up on a peer, if any
+    max_attempts: Option<u32>,
+}
+
+impl PeerReconnector {
+    /// Schedule the given peers, which are dialed at startup
+    pub fn new(peers: &[SocketAddr], max_attempts: Option<u32>, now: SystemTime) -> Self {
+        let peers = peers
+            .iter()
+            .map(|&address| {
+                let backoff = Backoff {
+                    attempts: 1,
+                    next_attempt: now + Self::delay_after(1),
+                    connected: false,
+                };
+                (address, backoff)
+            })
+            .collect();
+
+        Self {
+            peers,
+            max_attempts,
+        }
+    }
+
+    /// Delay after the `attempts`th failed attempt, without jitter
+    fn delay_after(attempts: u32) -> Duration {
+        let doublings = attempts.saturating_sub(1).min(31);
+        RECONNECT_BASE_DELAY
+            .saturating_mul(1 << doublings)
+            .min(RECONNECT_MAX_DELAY)
+    }
+
+    fn jittered(delay: Duration) -> Duration {
+        delay.mul_f64(1.0 + entropy::seeded_rng().gen_range(0.0..RECONNECT_JITTER))
+    }
+
+    /// Update the schedule with the currently connected peers, and return the
+    /// configured peers to attempt to connect to now.
+    ///
+    /// A peer whose connection was lost is due immediately.
+    pub fn due_peers(&mut self, connected: &[SocketAddr], now: SystemTime) -> Vec<SocketAddr> {
+        let mut due = vec![];
+        for (address, backoff) in self.peers.iter_mut() {
+            if connected.contains(address) {
+                backoff.connected = true;
+                continue;
+            }
+
+            if backoff.connected {
+                backoff.connected = false;
+                backoff.attempts = 0;
+                backoff.next_attempt = now;
+            }
+
+            let gave_up = self
+                .max_attempts
+                .is_some_and(|max_attempts| backoff.attempts >= max_attempts);
+            if gave_up || now < backoff.next_attempt {
+                continue;
+            }
+
+            backoff.attempts += 1;
+            backoff.next_attempt = now + Self::jittered(Self::delay_after(backoff.attempts));
+            due.push(*address);
+        }
+
+        due
+    }
+
+    pub fn status(&self) -> Vec<ConfiguredPeerStatus> {
+        let mut status = self
+            .peers
+            .iter()
+            .map(|(&address, backoff)| {
+                let state = if backoff.connected {
+                    ReconnectState::Connected
+                } else if self
+                    .max_attempts
+                    .is_some_and(|max_attempts| backoff.attempts >= max_attempts)
+                {
+                    ReconnectState::GaveUp {
+                        attempts: backoff.attempts,
+                    }
+                } else {
+                    ReconnectState::Waiting {
+                        attempts: backoff.attempts,
+                        next_attempt: backoff.next_attempt,
+                    }
+                };
+                ConfiguredPeerStatus { address, state }
+            })
+            .collect::<Vec<_>>();
+        status.sort_by_key(|peer| peer.address);
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod peer_reconnect_tests {
+    use super::*;
+
+    #[test]
+    fn unreachable_peers_are_retried_with_backoff_until_max_attempts() {
+        let peer: SocketAddr = "192.0.2.1:9798".parse().unwrap();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut reconnector = PeerReconnector::new(&[peer], Some(3), start);
+
+        // The startup attempt counts as the first
+        assert!(reconnector.due_peers(&[], start).is_empty());
+        let second_attempt = start + RECONNECT_BASE_DELAY;
+        assert_eq!(vec![peer], reconnector.due_peers(&[], second_attempt));
+        assert!(reconnector.due_peers(&[], second_attempt).is_empty());
+
+        // The delay doubles, plus jitter
+        let ReconnectState::Waiting {
+            attempts: 2,
+            next_attempt,
+        } = reconnector.status()[0].state
+        else {
+            panic!("Peer must be waiting");
+        };
+        let delay = next_attempt.duration_since(second_attempt).unwrap();
+        assert!(delay >= 2 * RECONNECT_BASE_DELAY);
+        assert!(delay <= (2 * RECONNECT_BASE_DELAY).mul_f64(1.0 + RECONNECT_JITTER));
+
+        assert_eq!(vec![peer], reconnector.due_peers(&[], next_attempt));
+        assert!(reconnector
+            .due_peers(&[], next_attempt + RECONNECT_MAX_DELAY * 2)
+            .is_empty());
+        assert_eq!(
+            ReconnectState::GaveUp { attempts: 3 },
+            reconnector.status()[0].state
+        );
+    }
+
+    #[test]
+    fn lost_connections_are_retried_immediately() {
+        let peer: SocketAddr = "[2001:db8::1]:9798".parse().unwrap();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let mut reconnector = PeerReconnector::new(&[peer], None, now);
+
+        assert!(reconnector.due_peers(&[peer], now).is_empty());
+        assert_eq!(ReconnectState::Connected, reconnector.status()[0].state);
+        assert_eq!(vec![peer], reconnector.due_peers(&[], now));
+        assert!(matches!(
+            reconnector.status()[0].state,
+            ReconnectState::Waiting { attempts: 1, .. }
+        ));
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        assert_eq!(RECONNECT_BASE_DELAY, PeerReconnector::delay_after(1));
+        assert_eq!(RECONNECT_MAX_DELAY, PeerReconnector::delay_after(u32::MAX));
+    }
+}
@@ -0,0 +1,519 @@
+//! Queue for the production of proofs, which can take minutes.
+//!
+//! Jobs wait for one of a limited number of slots, so that concurrent sends
+//! do not exhaust the CPU or RAM, and run on tokio's blocking threadpool.
+//! Queued and running jobs can be listed and cancelled over RPC.
+//!
+//! A job can be queued before its work is known, with a [`ProvingTicket`], so
+//! that e.g. a send can be cancelled, or time out, while it waits for the
+//! wallet as well as while its proofs are produced.
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{Notify, Semaphore};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::models::consensus::timestamp::Timestamp;
+
+/// RAM that one proving job is assumed to need, for the default number of
+/// concurrent jobs
+pub const PROVING_JOB_MEMORY: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Number of finished jobs kept for listing
+const FINISHED_JOBS_KEPT: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ProvingJobId(pub u64);
+
+impl Display for ProvingJobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProvingJobStatus {
+    Queued,
+    Running,
+    Finished,
+    Failed(String),
+    Cancelled,
+    TimedOut,
+}
+
+impl ProvingJobStatus {
+    pub fn is_done(&self) -> bool {
+        !matches!(self, Self::Queued | Self::Running)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProvingJobInfo {
+    pub id: ProvingJobId,
+    pub description: String,
+    pub status: ProvingJobStatus,
+    pub queued_at: Timestamp,
+    pub started_at: Option<Timestamp>,
+    pub finished_at: Option<Timestamp>,
+
+    /// Proofs produced so far, of the total the job needs
+    pub proofs_done: usize,
+    pub proofs_total: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+pub enum ProvingJobError {
+    #[error("proving job {0} was cancelled")]
+    Cancelled(ProvingJobId),
+
+    #[error("proving job {0} failed")]
+    Failed(ProvingJobId),
+
+    #[error("proving job {0} timed out")]
+    TimedOut(ProvingJobId),
+}
+
+#[derive(Debug)]
+struct ProvingJob {
+    info: ProvingJobInfo,
+    cancel: Arc<Notify>,
+}
+
+#[derive(Debug)]
+struct ProvingQueueInner {
+    jobs: Mutex<BTreeMap<ProvingJobId, ProvingJob>>,
+    slots: Arc<Semaphore>,
+    max_concurrent_jobs: usize,
+    next_id: AtomicU64,
+}
+
+/// Runs proving jobs, at most a fixed number at a time. Cloning gives a
+/// handle to the same queue.
+#[derive(Debug, Clone)]
+pub struct ProvingQueue {
+    inner: Arc<ProvingQueueInner>,
+}
+
+/// Handed to the work of a job, to report its progress
+#[derive(Debug, Clone)]
+pub struct ProvingProgress {
+    queue: ProvingQueue,
+    id: ProvingJobId,
+}
+
+impl ProvingProgress {
+    pub fn report(&self, proofs_done: usize, proofs_total: usize) {
+        self.queue.update(self.id, |info| {
+            info.proofs_done = proofs_done;
+            info.proofs_total = proofs_total;
+        });
+    }
+}
+
+/// A queued job whose work is yet to be given to [`Self::run`]. The job is
+/// listed, and can be cancelled, from when the ticket is taken. Dropping the
+/// ticket without running it cancels the job.
+#[derive(Debug)]
+pub struct ProvingTicket {
+    queue: ProvingQueue,
+    id: ProvingJobId,
+    cancel: Arc<Notify>,
+    deadline: Option<Instant>,
+}
+
+impl ProvingTicket {
+    pub fn id(&self) -> ProvingJobId {
+        self.id
+    }
+
+    /// Time the job out if it is not done after `timeout`, counted from now
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    /// Replace the description under which the job is listed
+    pub fn describe(&self, description: impl Into<String>) {
+        let description = description.into();
+        self.queue
+            .update(self.id, |info| info.description = description);
+    }
+
+    /// Await `future`, unless the job is cancelled or times out first
+    pub async fn until_cancelled<F: Future>(
+        &self,
+        future: F,
+    ) -> Result<F::Output, ProvingJobError> {
+        if let Some(err) = self.queue.ended(self.id) {
+            return Err(err);
+        }
+
+        let deadline = async {
+            match self.deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            biased;
+            _ = self.cancel.notified() => Err(ProvingJobError::Cancelled(self.id)),
+            _ = deadline => {
+                if self.queue.finish(self.id, ProvingJobStatus::TimedOut).is_some() {
+                    warn!("Proving job {} timed out", self.id);
+                }
+                Err(ProvingJobError::TimedOut(self.id))
+            }
+            output = future => Ok(output),
+        }
+    }
+
+    /// Wait for a slot, and run `work` on the blocking threadpool. Returns
+    /// early if the job is cancelled or times out.
+    pub async fn run<T, F>(self, work: F) -> Result<T, ProvingJobError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ProvingProgress) -> T + Send + 'static,
+    {
+        let id = self.id;
+        let slot = self
+            .until_cancelled(self.queue.inner.slots.clone().acquire_owned())
+            .await?
+            .expect("proving slots are never closed");
+
+        let mut started = false;
+        self.queue.update(id, |info| {
+            if info.status == ProvingJobStatus::Queued {
+                info.status = ProvingJobStatus::Running;
+                info.started_at = Some(Timestamp::now());
+                started = true;
+            }
+        });
+        if !started {
+            return Err(self
+                .queue
+                .ended(id)
+                .unwrap_or(ProvingJobError::Cancelled(id)));
+        }
+
+        let progress = ProvingProgress {
+            queue: self.queue.clone(),
+            id,
+        };
+        let worker = tokio::task::spawn_blocking(move || {
+            // The slot is taken until the work is done, even if the job is
+            // cancelled before that
+            let _slot = slot;
+            work(&progress)
+        });
+
+        match self.until_cancelled(worker).await? {
+            Ok(output) => {
+                self.queue.finish(id, ProvingJobStatus::Finished);
+                Ok(output)
+            }
+            Err(err) => {
+                warn!("Proving job {id} failed: {err}");
+                self.queue
+                    .finish(id, ProvingJobStatus::Failed(err.to_string()));
+                Err(ProvingJobError::Failed(id))
+            }
+        }
+    }
+}
+
+impl Drop for ProvingTicket {
+    fn drop(&mut self) {
+        // A no-op if the job is done
+        self.queue.finish(self.id, ProvingJobStatus::Cancelled);
+    }
+}
+
+impl ProvingQueue {
+    pub fn new(max_concurrent_jobs: usize) -> Self {
+        let max_concurrent_jobs = max_concurrent_jobs.max(1);
+        Self {
+            inner: Arc::new(ProvingQueueInner {
+                jobs: Mutex::new(BTreeMap::new()),
+                slots: Arc::new(Semaphore::new(max_concurrent_jobs)),
+                max_concurrent_jobs,
+                next_id: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// One job per core, as far as RAM allows for [`PROVING_JOB_MEMORY`] per
+    /// job
+    pub fn default_max_concurrent_jobs() -> usize {
+        let cores = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+        let jobs_in_memory = available_memory()
+            .map(|bytes| (bytes / PROVING_JOB_MEMORY) as usize)
+            .unwrap_or(cores);
+
+        cores.min(jobs_in_memory).max(1)
+    }
+
+    pub fn max_concurrent_jobs(&self) -> usize {
+        self.inner.max_concurrent_jobs
+    }
+
+    /// All queued and running jobs, and the most recently finished ones,
+    /// oldest first
+    pub fn jobs(&self) -> Vec<ProvingJobInfo> {
+        self.inner
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|job| job.info.clone())
+            .collect()
+    }
+
+    /// Cancel a queued or running job. Returns false if there is no such job,
+    /// or if it is done. A running job's thread runs to completion, but its
+    /// result is discarded.
+    pub fn cancel(&self, id: ProvingJobId) -> bool {
+        let Some(cancel) = self.finish(id, ProvingJobStatus::Cancelled) else {
+            return false;
+        };
+        cancel.notify_one();
+        info!("Cancelled proving job {id}");
+
+        true
+    }
+
+    /// The error that a job ended with, if it was cancelled or timed out
+    fn ended(&self, id: ProvingJobId) -> Option<ProvingJobError> {
+        match &self.inner.jobs.lock().unwrap().get(&id)?.info.status {
+            ProvingJobStatus::Cancelled => Some(ProvingJobError::Cancelled(id)),
+            ProvingJobStatus::TimedOut => Some(ProvingJobError::TimedOut(id)),
+            _ => None,
+        }
+    }
+
+    fn update(&self, id: ProvingJobId, update: impl FnOnce(&mut ProvingJobInfo)) {
+        if let Some(job) = self.inner.jobs.lock().unwrap().get_mut(&id) {
+            update(&mut job.info);
+        }
+    }
+
+    /// Set the outcome of a job, unless it is done already, and forget the
+    /// oldest finished jobs. Returns the job's cancellation notifier if the
+    /// outcome was set.
+    fn finish(&self, id: ProvingJobId, status: ProvingJobStatus) -> Option<Arc<Notify>> {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        let cancel = jobs
+            .get_mut(&id)
+            .filter(|job| !job.info.status.is_done())
+            .map(|job| {
+                job.info.status = status;
+                job.info.finished_at = Some(Timestamp::now());
+                job.cancel.clone()
+            });
+
+        let finished = jobs
+            .values()
+            .filter(|job| job.info.status.is_done())
+            .map(|job| job.info.id)
+            .collect::<Vec<_>>();
+        for id in finished
+            .iter()
+            .take(finished.len().saturating_sub(FINISHED_JOBS_KEPT))
+        {
+            jobs.remove(id);
+        }
+
+        cancel
+    }
+
+    /// Queue a job whose work is given later, to [`ProvingTicket::run`]
+    pub fn ticket(&self, description: impl Into<String>) -> ProvingTicket {
+        let id = ProvingJobId(self.inner.next_id.fetch_add(1, Ordering::Relaxed));
+        let cancel = Arc::new(Notify::new());
+        let info = ProvingJobInfo {
+            id,
+            description: description.into(),
+            status: ProvingJobStatus::Queued,
+            queued_at: Timestamp::now(),
+            started_at: None,
+            finished_at: None,
+            proofs_done: 0,
+            proofs_total: 0,
+        };
+        self.inner.jobs.lock().unwrap().insert(
+            id,
+            ProvingJob {
+                info,
+                cancel: cancel.clone(),
+            },
+        );
+
+        ProvingTicket {
+            queue: self.clone(),
+            id,
+            cancel,
+            deadline: None,
+        }
+    }
+
+    /// Queue `work`, and run it on the blocking threadpool once a slot is
+    /// free. Returns early if the job is cancelled.
+    pub async fn run<T, F>(
+        &self,
+        description: impl Into<String>,
+        work: F,
+    ) -> Result<T, ProvingJobError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&ProvingProgress) -> T + Send + 'static,
+    {
+        self.ticket(description).run(work).await
+    }
+}
+
+/// RAM available for new allocations, if it can be determined
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let kilobytes = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+
+    Some(kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod proving_queue_tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn jobs_report_progress_and_are_listed() {
+        let queue = ProvingQueue::new(1);
+        let output = queue
+            .run("two proofs", |progress| {
+                progress.report(1, 2);
+                progress.report(2, 2);
+                42
+            })
+            .await
+            .unwrap();
+        assert_eq!(42, output);
+
+        let jobs = queue.jobs();
+        assert_eq!(1, jobs.len());
+        assert_eq!(ProvingJobStatus::Finished, jobs[0].status);
+        assert_eq!((2, 2), (jobs[0].proofs_done, jobs[0].proofs_total));
+        assert!(jobs[0].started_at.is_some() && jobs[0].finished_at.is_some());
+        assert!(!queue.cancel(jobs[0].id));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn queued_jobs_wait_for_a_slot_and_can_be_cancelled() {
+        let queue = ProvingQueue::new(1);
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let running = tokio::spawn({
+            let queue = queue.clone();
+            async move {
+                queue
+                    .run("blocks the only slot", move |_| release_rx.recv().unwrap())
+                    .await
+            }
+        });
+        let queued = tokio::spawn({
+            let queue = queue.clone();
+            async move { queue.run("waits", |_| ()).await }
+        });
+
+        while queue.jobs().len() < 2 || queue.jobs()[0].status != ProvingJobStatus::Running {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let waiting = queue.jobs()[1].clone();
+        assert_eq!(ProvingJobStatus::Queued, waiting.status);
+
+        assert!(queue.cancel(waiting.id));
+        assert_eq!(
+            Err(ProvingJobError::Cancelled(waiting.id)),
+            queued.await.unwrap()
+        );
+
+        release_tx.send(()).unwrap();
+        running.await.unwrap().unwrap();
+        let statuses = queue
+            .jobs()
+            .into_iter()
+            .map(|job| job.status)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![ProvingJobStatus::Finished, ProvingJobStatus::Cancelled],
+            statuses
+        );
+    }
+
+    #[tokio::test]
+    async fn tickets_can_be_cancelled_or_time_out_before_the_work_is_run() {
+        let queue = ProvingQueue::new(1);
+
+        let ticket = queue.ticket("waits for the wallet");
+        assert_eq!(ProvingJobStatus::Queued, queue.jobs()[0].status);
+        let waiting = ticket.until_cancelled(std::future::pending::<()>());
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            assert!(queue.cancel(ticket.id()));
+        };
+        let (result, ()) = tokio::join!(waiting, cancel);
+        assert_eq!(Err(ProvingJobError::Cancelled(ticket.id())), result);
+        assert_eq!(
+            Err(ProvingJobError::Cancelled(ticket.id())),
+            ticket.run(|_| ()).await
+        );
+
+        let ticket = queue
+            .ticket("times out")
+            .with_timeout(Duration::from_millis(10));
+        let id = ticket.id();
+        assert_eq!(
+            Err(ProvingJobError::TimedOut(id)),
+            ticket.until_cancelled(std::future::pending::<()>()).await
+        );
+        assert_eq!(Err(ProvingJobError::TimedOut(id)), ticket.run(|_| ()).await);
+
+        // Tickets dropped before their work is run are cancelled
+        drop(queue.ticket("abandoned"));
+        let statuses = queue
+            .jobs()
+            .into_iter()
+            .map(|job| job.status)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                ProvingJobStatus::Cancelled,
+                ProvingJobStatus::TimedOut,
+                ProvingJobStatus::Cancelled
+            ],
+            statuses
+        );
+    }
+
+    #[test]
+    fn default_concurrency_is_at_least_one() {
+        assert!(ProvingQueue::default_max_concurrent_jobs() >= 1);
+        assert_eq!(1, ProvingQueue::new(0).max_concurrent_jobs());
+    }
+}
@@ -0,0 +1,165 @@
+//! Read-only access to the archival state of a node that keeps running, e.g.
+//! for analytics jobs and explorers in a separate process.
+//!
+//! A LevelDB database can only be opened by one process at a time, so a read
+//! replica opens a snapshot: a copy of the block index and mutator set
+//! databases, taken when the replica is opened. Blocks are read from the
+//! node's block files, which are only appended to. Blocks that the node
+//! stores after the snapshot was taken are seen once the replica is reopened.
+//!
+//! See [`ArchivalState::open_read_only`](super::archival_state::ArchivalState::open_read_only).
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use tracing::debug;
+
+use super::archival_state::{BLOCK_INDEX_DB_NAME, MUTATOR_SET_DIRECTORY_NAME};
+use crate::config_models::data_directory::DataDirectory;
+use crate::util_types::entropy;
+
+/// Prefix of the snapshot directories, which live in the system's temporary
+/// directory
+pub const READ_REPLICA_DIRECTORY_PREFIX: &str = "neptune-read-replica-";
+
+/// Times to try copying a database that the node compacts meanwhile
+const SNAPSHOT_ATTEMPTS: usize = 5;
+
+/// The LevelDB file that marks a database as opened by a process
+const LEVELDB_LOCK_FILE_NAME: &str = "LOCK";
+
+/// A copy of the databases of a data directory, removed when dropped
+#[derive(Debug)]
+pub struct DatabaseSnapshot {
+    root: PathBuf,
+}
+
+impl DatabaseSnapshot {
+    pub async fn take(data_dir: &DataDirectory) -> Result<Self> {
+        let snapshot = Self {
+            root: std::env::temp_dir().join(format!(
+                "{READ_REPLICA_DIRECTORY_PREFIX}{:016x}",
+                entropy::random::<u64>()
+            )),
+        };
+        copy_database(
+            &data_dir.block_index_database_dir_path(),
+            &snapshot.block_index_database_dir_path(),
+        )
+        .await?;
+        copy_database(
+            &data_dir.mutator_set_database_dir_path(),
+            &snapshot.mutator_set_database_dir_path(),
+        )
+        .await?;
+
+        Ok(snapshot)
+    }
+
+    pub fn block_index_database_dir_path(&self) -> PathBuf {
+        self.root.join(BLOCK_INDEX_DB_NAME)
+    }
+
+    pub fn mutator_set_database_dir_path(&self) -> PathBuf {
+        self.root.join(MUTATOR_SET_DIRECTORY_NAME)
+    }
+}
+
+impl Drop for DatabaseSnapshot {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.root) {
+            debug!(
+                "Could not remove database snapshot {}: {err}",
+                self.root.display()
+            );
+        }
+    }
+}
+
+/// Copy the files of a LevelDB database, but for its lock file. A compaction
+/// by the process that has the database open can delete files during the
+/// copy, upon which the copy is retried.
+async fn copy_database(source: &Path, destination: &Path) -> Result<()> {
+    if !tokio::fs::try_exists(source).await? {
+        bail!("There is no database at {}", source.display());
+    }
+
+    let mut attempt = 1;
+    loop {
+        match try_copy_database(source, destination).await {
+            Ok(()) => return Ok(()),
+            Err(err) if attempt < SNAPSHOT_ATTEMPTS => {
+                debug!("Copying database {} failed: {err:#}", source.display());
+                let _ = tokio::fs::remove_dir_all(destination).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+async fn try_copy_database(source: &Path, destination: &Path) -> Result<()> {
+    DataDirectory::create_dir_if_not_exists(destination).await?;
+
+    let mut file_names = vec![];
+    let mut entries = tokio::fs::read_dir(source).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name != LEVELDB_LOCK_FILE_NAME {
+            file_names.push(file_name);
+        }
+    }
+
+    // The manifest lists the table files of the database. Copied first, it
+    // lists no table file that is written during the copy.
+    file_names.sort_by_key(|name| !(name == "CURRENT" || name.starts_with("MANIFEST-")));
+    for file_name in file_names {
+        tokio::fs::copy(source.join(&file_name), destination.join(&file_name))
+            .await
+            .with_context(|| format!("Could not copy {file_name}"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod read_replica_tests {
+    use rand::random;
+
+    use crate::config_models::network::Network;
+    use crate::models::state::archival_state::ArchivalState;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{
+        add_block_to_archival_state, make_mock_block_with_valid_pow, mock_genesis_archival_state,
+    };
+
+    #[tokio::test]
+    async fn replica_reads_the_state_of_a_running_node() {
+        let network = Network::RegTest;
+        let (mut archival_state, _peer_db_lock, data_dir) =
+            mock_genesis_archival_state(network).await;
+        let genesis = archival_state.genesis_block().clone();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) = make_mock_block_with_valid_pow(&genesis, None, address, random());
+        add_block_to_archival_state(&mut archival_state, block_1.clone())
+            .await
+            .unwrap();
+
+        // The node keeps its databases open
+        let mut replica = ArchivalState::open_read_only(data_dir, network)
+            .await
+            .unwrap();
+        assert!(replica.is_read_only());
+        assert_eq!(block_1.hash(), replica.get_tip().await.hash());
+        assert_eq!(
+            Some(block_1.clone()),
+            replica.get_block(block_1.hash()).await.unwrap()
+        );
+
+        let (block_2, _, _) = make_mock_block_with_valid_pow(&block_1, None, address, random());
+        assert!(replica.write_block_as_tip(&block_2).await.is_err());
+        assert!(replica.update_mutator_set(&block_2).await.is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! Classification of peers whose handshakes are chronically slow.
+//!
+//! Every handshake phase is bounded by `--handshake-timeout`, and the whole
+//! connection setup by `--connection-setup-timeout`, so a stalling peer cannot
+//! hold a connection slot. The outcome of each handshake is recorded by IP: a
+//! handshake is slow if it took more than half the phase timeout. A peer whose
+//! handshakes mostly time out or are slow is chronically slow, and peer
+//! discovery only connects to it when no other candidate is left.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use rand::seq::IteratorRandom;
+
+use crate::util_types::entropy;
+
+/// Max number of peers whose handshakes are recorded
+pub const MAX_HANDSHAKE_RECORDS: usize = 1000;
+
+/// Handshakes to record before a peer can be classified as slow
+pub const SLOW_PEER_MIN_HANDSHAKES: u64 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandshakeOutcome {
+    Fast,
+    Slow,
+    TimedOut,
+}
+
+impl HandshakeOutcome {
+    /// Classify a completed handshake by its duration
+    pub fn of_completed(duration: Duration, handshake_timeout: Duration) -> Self {
+        if duration > handshake_timeout / 2 {
+            Self::Slow
+        } else {
+            Self::Fast
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HandshakeRecord {
+    pub fast: u64,
+    pub slow: u64,
+    pub timed_out: u64,
+}
+
+impl HandshakeRecord {
+    /// Whether most handshakes were slow or timed out
+    pub fn is_chronically_slow(&self) -> bool {
+        let count = self.fast + self.slow + self.timed_out;
+        count >= SLOW_PEER_MIN_HANDSHAKES && self.slow + self.timed_out > self.fast
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SlowPeers {
+    records: HashMap<IpAddr, HandshakeRecord>,
+}
+
+impl SlowPeers {
+    pub fn record(&mut self, ip: IpAddr, outcome: HandshakeOutcome) {
+        if !self.records.contains_key(&ip) && self.records.len() >= MAX_HANDSHAKE_RECORDS {
+            let mut rng = entropy::seeded_rng();
+            if let Some(evicted) = self.records.keys().copied().choose(&mut rng) {
+                self.records.remove(&evicted);
+            }
+        }
+
+        let record = self.records.entry(ip).or_default();
+        match outcome {
+            HandshakeOutcome::Fast => record.fast += 1,
+            HandshakeOutcome::Slow => record.slow += 1,
+            HandshakeOutcome::TimedOut => record.timed_out += 1,
+        }
+    }
+
+    pub fn is_slow(&self, ip: IpAddr) -> bool {
+        self.records
+            .get(&ip)
+            .is_some_and(HandshakeRecord::is_chronically_slow)
+    }
+}
+
+#[cfg(test)]
+mod slow_peers_tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn peers_are_slow_once_most_handshakes_are() {
+        let timeout = Duration::from_secs(10);
+        assert_eq!(
+            HandshakeOutcome::Fast,
+            HandshakeOutcome::of_completed(Duration::from_secs(1), timeout)
+        );
+        assert_eq!(
+            HandshakeOutcome::Slow,
+            HandshakeOutcome::of_completed(Duration::from_secs(6), timeout)
+        );
+
+        let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let mut slow_peers = SlowPeers::default();
+        slow_peers.record(ip, HandshakeOutcome::TimedOut);
+        slow_peers.record(ip, HandshakeOutcome::Slow);
+        assert!(!slow_peers.is_slow(ip), "too few handshakes to tell");
+
+        slow_peers.record(ip, HandshakeOutcome::Fast);
+        assert!(slow_peers.is_slow(ip));
+
+        slow_peers.record(ip, HandshakeOutcome::Fast);
+        slow_peers.record(ip, HandshakeOutcome::Fast);
+        assert!(!slow_peers.is_slow(ip));
+    }
+}
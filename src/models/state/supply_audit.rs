@@ -0,0 +1,67 @@
+use num_traits::CheckedSub;
+use serde::{Deserialize, Serialize};
+
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+/// The result of walking the canonical chain and the append-only commitment
+/// list (AOCL) of the mutator set, summing all emissions and checking them
+/// against the reward schedule.
+///
+/// Amounts of outputs are hidden, so the supply cannot be derived from the
+/// AOCL. Instead, every block's coinbase is checked against the reward
+/// schedule, and the AOCL is checked to contain exactly the outputs of the
+/// canonical chain, in order.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupplyAudit {
+    #[serde(with = "digest_serde")]
+    pub tip_digest: Digest,
+    pub tip_height: BlockHeight,
+
+    /// The coinbase of the genesis block
+    pub premine: NeptuneCoins,
+
+    /// Sum of the coinbases of all blocks after genesis
+    pub total_coinbase: NeptuneCoins,
+
+    /// Sum of the fees of all blocks after genesis. Fees are paid out of
+    /// existing coins and may be claimed in a coinbase, so they do not add to
+    /// the supply.
+    pub total_fees: NeptuneCoins,
+
+    /// Sum of the block rewards of all blocks after genesis
+    pub scheduled_rewards: NeptuneCoins,
+
+    /// Heights of blocks whose coinbase exceeds their reward plus fee
+    pub blocks_exceeding_schedule: Vec<BlockHeight>,
+
+    pub aocl_leaf_count: u64,
+
+    /// Number of outputs in the canonical chain, including genesis
+    pub output_count: u64,
+
+    /// Index of the first AOCL leaf that differs from the corresponding
+    /// output of the canonical chain
+    pub first_aocl_mismatch: Option<u64>,
+}
+
+impl SupplyAudit {
+    /// Total amount of coins ever created: the premine plus all coinbases,
+    /// minus the fees they reclaimed. `None` if the fees exceed the premine
+    /// and coinbases, which only an inconsistent chain can cause.
+    pub fn emitted_supply(&self) -> Option<NeptuneCoins> {
+        self.premine
+            .safe_add(self.total_coinbase)?
+            .checked_sub(&self.total_fees)
+    }
+
+    /// True if no block claimed more than the schedule allows and the AOCL
+    /// matches the outputs of the canonical chain.
+    pub fn is_consistent(&self) -> bool {
+        self.blocks_exceeding_schedule.is_empty()
+            && self.first_aocl_mismatch.is_none()
+            && self.aocl_leaf_count == self.output_count
+    }
+}
@@ -0,0 +1,290 @@
+//! Sweeping the funds of another key into the wallet, e.g. to migrate a paper
+//! wallet or an offline key.
+//!
+//! The canonical chain is scanned for UTXOs announced to the generation
+//! address of the key, their membership proofs are restored from the archival
+//! mutator set, and those that are unspent and not time-locked are spent in a
+//! single transaction to the wallet. As in a rescan, UTXOs that were only ever
+//! notified off-chain, such as premine allocations, cannot be found.
+
+use anyhow::{bail, Result};
+use num_traits::CheckedSub;
+use serde::{Deserialize, Serialize};
+use tasm_lib::twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+use thiserror::Error;
+use tracing::info;
+
+use super::wallet::address::generation_address::SpendingKey;
+use super::wallet::utxo_notification_pool::UtxoNotifier;
+use super::wallet::WalletDisabled;
+use super::{GlobalState, GlobalStateLock};
+use crate::database::storage::storage_vec::traits::*;
+use crate::locks::tokio::AtomicRwWriteGuard;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::utxo::{LockScript, Utxo};
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
+
+/// Number of blocks read from disk per acquisition of the global state lock
+const SWEEP_SCAN_BATCH_SIZE: u64 = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum SweepError {
+    #[error("invalid seed phrase")]
+    InvalidSecret,
+
+    #[error("sweeping needs the blocks of an archival node")]
+    NotArchival,
+
+    #[error("no spendable UTXOs were found for the key")]
+    NothingToSweep,
+
+    #[error("the swept amount of {0} does not cover the fee")]
+    InsufficientFunds(NeptuneCoins),
+
+    #[error("could not sweep: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    WalletDisabled(#[from] WalletDisabled),
+}
+
+/// Find the UTXOs of `spending_key` on the canonical chain that are unspent
+/// and spendable at `timestamp`, with membership proofs valid for the tip.
+///
+/// The global state lock is taken for a batch of blocks at a time while
+/// scanning, and then for writing, which the caller keeps to spend the UTXOs
+/// before the tip changes.
+pub async fn find_sweepable_utxos<'a>(
+    global_state_lock: &'a GlobalStateLock,
+    spending_key: &SpendingKey,
+    timestamp: Timestamp,
+) -> Result<
+    (
+        Vec<(Utxo, LockScript, MsMembershipProof)>,
+        AtomicRwWriteGuard<'a, GlobalState>,
+    ),
+    SweepError,
+> {
+    let mut announced = vec![];
+    let mut next_height = 0u64;
+    let scanned_tip = loop {
+        let global_state = global_state_lock.lock_guard().await;
+        if !global_state.chain.is_archival_node() {
+            return Err(SweepError::NotArchival);
+        }
+        let tip_height = u64::from(global_state.chain.light_state().kernel.header.height);
+        if next_height > tip_height {
+            break global_state.chain.light_state().hash();
+        }
+
+        let last_height = (next_height + SWEEP_SCAN_BATCH_SIZE - 1).min(tip_height);
+        let blocks = global_state
+            .chain
+            .archival_state()
+            .iter_canonical_blocks(BlockHeight::from(next_height)..=last_height.into());
+        pin_mut!(blocks);
+        while let Some(block) = blocks.next().await {
+            let block = block.map_err(|err| SweepError::Failed(err.to_string()))?;
+            announced.extend(spending_key.scan_block_for_announced_utxos(&block));
+        }
+        next_height = last_height + 1;
+    };
+
+    let global_state_mut = global_state_lock.lock_guard_mut().await;
+    if scanned_tip != global_state_mut.chain.light_state().hash() {
+        return Err(SweepError::Failed(
+            "the tip changed during the scan; try again".to_string(),
+        ));
+    }
+
+    let lock_script = spending_key.to_address().lock_script();
+    let ams = global_state_mut
+        .chain
+        .archival_state()
+        .archival_mutator_set
+        .ams();
+    let mut sweepable = vec![];
+    for announced_utxo in announced {
+        if !announced_utxo.utxo.can_spend_at(timestamp) {
+            continue;
+        }
+
+        let item = Hash::hash(&announced_utxo.utxo);
+        let membership_proof = match ams
+            .restore_membership_proof(
+                item,
+                announced_utxo.sender_randomness,
+                announced_utxo.receiver_preimage,
+                announced_utxo.aocl_index,
+            )
+            .await
+        {
+            Ok(membership_proof) => membership_proof,
+            Err(err) => return Err(SweepError::Failed(err.to_string())),
+        };
+
+        // Spent UTXOs fail verification, as their removal record is in the
+        // sliding window Bloom filter
+        if ams.verify(item, &membership_proof).await {
+            sweepable.push((announced_utxo.utxo, lock_script.clone(), membership_proof));
+        }
+    }
+
+    Ok((sweepable, global_state_mut))
+}
+
+/// Spend all funds of the key with the given spending key to the wallet, less
+/// `fee`. Returns the transaction, which the caller broadcasts.
+pub async fn sweep(
+    global_state_lock: &GlobalStateLock,
+    spending_key: SpendingKey,
+    fee: NeptuneCoins,
+) -> Result<Transaction, SweepError> {
+    let now = Timestamp::now();
+    let (sweepable, mut global_state_mut) =
+        find_sweepable_utxos(global_state_lock, &spending_key, now).await?;
+    if sweepable.is_empty() {
+        return Err(SweepError::NothingToSweep);
+    }
+
+    let total = sweepable
+        .iter()
+        .map(|(utxo, _lock_script, _mp)| utxo.get_native_currency_amount())
+        .sum::<NeptuneCoins>();
+    if fee > total {
+        return Err(SweepError::InsufficientFunds(total));
+    }
+    info!(
+        "Sweeping {total} in {} UTXOs to the wallet",
+        sweepable.len()
+    );
+
+    global_state_mut
+        .create_sweep_transaction(spending_key, sweepable, fee, now)
+        .await
+        .map_err(|err| SweepError::Failed(err.to_string()))
+}
+
+impl GlobalState {
+    /// Create a transaction that spends the given inputs, which the spending key
+    /// unlocks, and sends their total less `fee` to the wallet. The inputs must
+    /// be synced to the tip.
+    pub async fn create_sweep_transaction(
+        &mut self,
+        spending_key: SpendingKey,
+        spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+        fee: NeptuneCoins,
+        timestamp: Timestamp,
+    ) -> Result<Transaction> {
+        let total = spendable_utxos_and_mps
+            .iter()
+            .map(|(utxo, _lock_script, _mp)| utxo.get_native_currency_amount())
+            .sum::<NeptuneCoins>();
+        let Some(amount) = total.checked_sub(&fee) else {
+            bail!("Not enough funds to pay the fee.");
+        };
+
+        let receiver_data = vec![self.add_own_output(amount, UtxoNotifier::Cli).await?];
+        let (inputs, outputs, output_utxos) =
+            self.generate_utxo_data_from_inputs(&receiver_data, &spendable_utxos_and_mps);
+        let public_announcements = receiver_data
+            .into_iter()
+            .map(|receiver| receiver.public_announcement)
+            .collect();
+        let mutator_set_accumulator = self
+            .chain
+            .light_state()
+            .kernel
+            .body
+            .mutator_set_accumulator
+            .clone();
+
+        Self::draft_transaction_from_data(
+            self.cli().prover(),
+            spending_key,
+            inputs,
+            spendable_utxos_and_mps,
+            outputs,
+            output_utxos,
+            fee,
+            public_announcements,
+            timestamp,
+            mutator_set_accumulator,
+        )
+        .prove(self.proving_queue.ticket("sweep"))
+        .await
+    }
+}
+
+#[cfg(test)]
+mod sweep_tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::config_models::network::Network;
+    use crate::models::blockchain::block::Block;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{make_mock_block, mock_genesis_global_state};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn announced_unspent_utxos_of_another_key_are_found() {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let global_state_lock =
+            mock_genesis_global_state(network, 0, WalletSecret::new_random()).await;
+        let paper_spending_key = WalletSecret::new_random().nth_generation_spending_key(0);
+        let paper_address = paper_spending_key.to_address();
+
+        // A block paying the paper key, with an announcement of its UTXO
+        let genesis_block = Block::genesis_block(network);
+        let (mut block_1, utxo, sender_randomness) =
+            make_mock_block(&genesis_block, None, paper_address, rng.gen());
+        let announcement = paper_address
+            .generate_public_announcement(&utxo, sender_randomness)
+            .unwrap();
+        block_1
+            .kernel
+            .body
+            .transaction
+            .kernel
+            .public_announcements
+            .push(announcement);
+        global_state_lock
+            .lock_guard_mut()
+            .await
+            .set_new_tip(block_1.clone())
+            .await
+            .unwrap();
+
+        let timestamp = block_1.kernel.header.timestamp + Timestamp::months(12);
+        let (sweepable, global_state) =
+            find_sweepable_utxos(&global_state_lock, &paper_spending_key, timestamp)
+                .await
+                .unwrap();
+        assert_eq!(1, sweepable.len());
+        let (found_utxo, lock_script, membership_proof) = &sweepable[0];
+        assert_eq!(utxo, *found_utxo);
+        assert_eq!(paper_address.lock_script(), *lock_script);
+        assert!(global_state
+            .chain
+            .light_state()
+            .kernel
+            .body
+            .mutator_set_accumulator
+            .verify(Hash::hash(&utxo), membership_proof));
+        drop(global_state);
+
+        // Nothing is found for a key that was paid nothing
+        let other_spending_key = WalletSecret::new_random().nth_generation_spending_key(0);
+        let (sweepable, _global_state) =
+            find_sweepable_utxos(&global_state_lock, &other_spending_key, timestamp)
+                .await
+                .unwrap();
+        assert!(sweepable.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::blockchain::block::block_header::TARGET_BLOCK_INTERVAL;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+
+/// After this many target block intervals without a new tip, while peers
+/// report higher tips, the tip is stale
+pub const STALE_TIP_TARGET_INTERVALS: u64 = 3;
+
+/// An assessment of whether syncing silently stalled: the tip has not changed
+/// for a long time, although peers claim to have higher blocks.
+///
+/// The time is measured since this node last saw the tip change, not since
+/// the tip's timestamp, so that a node syncing old blocks is not stale.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TipStaleness {
+    #[serde(with = "digest_serde")]
+    pub tip_digest: Digest,
+
+    /// When this node first saw the current tip
+    pub tip_seen_at: Timestamp,
+
+    /// The highest tip claimed by a connected peer, if higher than ours
+    pub highest_peer_height: Option<BlockHeight>,
+
+    pub stale: bool,
+}
+
+impl TipStaleness {
+    pub fn new(tip_digest: Digest, now: Timestamp) -> Self {
+        Self {
+            tip_digest,
+            tip_seen_at: now,
+            highest_peer_height: None,
+            stale: false,
+        }
+    }
+
+    /// Reassess against the current tip and the heights peers claim
+    pub fn assess(
+        self,
+        tip_digest: Digest,
+        tip_height: BlockHeight,
+        peer_heights: &[BlockHeight],
+        now: Timestamp,
+    ) -> Self {
+        let tip_seen_at = if tip_digest == self.tip_digest {
+            self.tip_seen_at
+        } else {
+            now
+        };
+        let highest_peer_height = peer_heights
+            .iter()
+            .copied()
+            .filter(|&height| height > tip_height)
+            .max();
+        let stale_after = Timestamp::millis(STALE_TIP_TARGET_INTERVALS * TARGET_BLOCK_INTERVAL);
+
+        Self {
+            tip_digest,
+            tip_seen_at,
+            highest_peer_height,
+            stale: highest_peer_height.is_some() && now > tip_seen_at + stale_after,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tip_staleness_tests {
+    use rand::random;
+
+    use super::*;
+
+    #[test]
+    fn tip_is_stale_only_if_peers_are_ahead() {
+        let start = Timestamp::now();
+        let later = start + Timestamp::hours(1);
+        let tip: Digest = random();
+        let height = BlockHeight::from(10u64);
+        let higher = BlockHeight::from(12u64);
+
+        let staleness = TipStaleness::new(tip, start);
+        assert!(!staleness.assess(tip, height, &[height], later).stale);
+        assert!(!staleness.assess(tip, height, &[higher], start).stale);
+
+        let stalled = staleness.assess(tip, height, &[height, higher], later);
+        assert!(stalled.stale);
+        assert_eq!(Some(higher), stalled.highest_peer_height);
+
+        // A new tip resets the clock
+        let new_tip = stalled.assess(random(), higher, &[higher], later);
+        assert!(!new_tip.stale);
+        assert_eq!(later, new_tip.tip_seen_at);
+    }
+}
@@ -0,0 +1,265 @@
+//! Watches of transactions until they are buried under a requested number of
+//! blocks.
+//!
+//! A block holds a single transaction, merged from the transactions it
+//! includes, so a transaction is recognized in a block by its inputs and
+//! outputs rather than by its digest. A watch fires once the transaction has
+//! the requested number of confirmations, and every time a reorganization
+//! takes the block that included it out of the canonical chain.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::block::Block;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::Transaction;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::mutator_set::addition_record::AdditionRecord;
+use crate::util_types::mutator_set::shared::NUM_TRIALS;
+
+/// Max number of transactions watched at a time
+pub const MAX_WATCHED_TRANSACTIONS: usize = 1000;
+
+/// The reason a transaction cannot be watched
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum WatchError {
+    #[error("transaction {0} is not in the mempool")]
+    UnknownTransaction(Digest),
+
+    #[error("confirmations must be at least 1")]
+    ZeroConfirmations,
+
+    #[error("already watching {MAX_WATCHED_TRANSACTIONS} transactions")]
+    TooManyWatches,
+}
+
+/// What happened to a watched transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The transaction has the requested number of confirmations. The watch
+    /// ends.
+    Confirmed {
+        transaction_digest: Digest,
+        block_digest: Digest,
+        confirmations: u64,
+    },
+
+    /// The block that included the transaction left the canonical chain. The
+    /// transaction is watched for inclusion in another block.
+    ReorgedOut {
+        transaction_digest: Digest,
+        block_digest: Digest,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TransactionWatch {
+    confirmations: u64,
+    input_index_sets: Vec<[u128; NUM_TRIALS as usize]>,
+    outputs: Vec<AdditionRecord>,
+
+    /// The block that included the transaction, and its height
+    included_in: Option<(Digest, BlockHeight)>,
+}
+
+impl TransactionWatch {
+    /// A transaction without inputs and outputs cannot be recognized
+    fn is_included_in(&self, kernel: &TransactionKernel) -> bool {
+        if self.input_index_sets.is_empty() && self.outputs.is_empty() {
+            return false;
+        }
+
+        let block_inputs = kernel
+            .inputs
+            .iter()
+            .map(|input| input.absolute_indices.to_array())
+            .collect::<Vec<_>>();
+
+        self.input_index_sets
+            .iter()
+            .all(|index_set| block_inputs.contains(index_set))
+            && self
+                .outputs
+                .iter()
+                .all(|output| kernel.outputs.contains(output))
+    }
+}
+
+/// Transactions watched for confirmations, by digest
+#[derive(Debug, Default)]
+pub struct TransactionWatches(HashMap<Digest, TransactionWatch>);
+
+impl TransactionWatches {
+    /// Watch a transaction until it has `confirmations` confirmations. A
+    /// repeated watch replaces the previous one.
+    pub fn watch(
+        &mut self,
+        transaction_digest: Digest,
+        transaction: &Transaction,
+        confirmations: u64,
+    ) -> Result<(), WatchError> {
+        if confirmations == 0 {
+            return Err(WatchError::ZeroConfirmations);
+        }
+        if self.0.len() >= MAX_WATCHED_TRANSACTIONS && !self.0.contains_key(&transaction_digest) {
+            return Err(WatchError::TooManyWatches);
+        }
+
+        let kernel = &transaction.kernel;
+        self.0.insert(
+            transaction_digest,
+            TransactionWatch {
+                confirmations,
+                input_index_sets: kernel
+                    .inputs
+                    .iter()
+                    .map(|input| input.absolute_indices.to_array())
+                    .collect(),
+                outputs: kernel.outputs.clone(),
+                included_in: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The blocks that included watched transactions, and their heights
+    pub fn including_blocks(&self) -> Vec<(Digest, BlockHeight)> {
+        self.0
+            .values()
+            .filter_map(|watch| watch.included_in)
+            .collect()
+    }
+
+    /// Update the watches for a new tip. `is_canonical` tells whether a block
+    /// that included a watched transaction is still in the canonical chain.
+    pub fn update_with_tip(
+        &mut self,
+        tip: &Block,
+        is_canonical: impl Fn(Digest) -> bool,
+    ) -> Vec<WatchEvent> {
+        let tip_digest = tip.hash();
+        let tip_height = tip.kernel.header.height;
+        let mut events = vec![];
+        for (&transaction_digest, watch) in self.0.iter_mut() {
+            if let Some((block_digest, _)) = watch.included_in {
+                if !is_canonical(block_digest) {
+                    watch.included_in = None;
+                    events.push(WatchEvent::ReorgedOut {
+                        transaction_digest,
+                        block_digest,
+                    });
+                }
+            }
+
+            if watch.included_in.is_none()
+                && watch.is_included_in(&tip.kernel.body.transaction.kernel)
+            {
+                watch.included_in = Some((tip_digest, tip_height));
+            }
+        }
+
+        self.0.retain(|&transaction_digest, watch| {
+            let Some((block_digest, height)) = watch.included_in else {
+                return true;
+            };
+            let confirmations = u64::from(tip_height).saturating_sub(u64::from(height)) + 1;
+            if confirmations < watch.confirmations {
+                return true;
+            }
+
+            events.push(WatchEvent::Confirmed {
+                transaction_digest,
+                block_digest,
+                confirmations,
+            });
+            false
+        });
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod transaction_watch_tests {
+    use rand::random;
+
+    use crate::config_models::network::Network;
+    use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{
+        make_mock_block, make_mock_transaction_with_wallet, mock_genesis_wallet_state,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn watches_fire_at_depth_and_on_reorg() {
+        let network = Network::RegTest;
+        let wallet_state = mock_genesis_wallet_state(WalletSecret::devnet_wallet(), network).await;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let genesis = Block::genesis_block(network);
+
+        // A block includes the transaction if it creates its outputs
+        let (block_1, _, _) = make_mock_block(&genesis, None, address, random());
+        let output = block_1.kernel.body.transaction.kernel.outputs[0];
+        let transaction = make_mock_transaction_with_wallet(
+            vec![],
+            vec![output],
+            NeptuneCoins::new(0),
+            &wallet_state,
+            None,
+        );
+        let transaction_digest: Digest = random();
+
+        let mut watches = TransactionWatches::default();
+        assert_eq!(
+            Err(WatchError::ZeroConfirmations),
+            watches.watch(transaction_digest, &transaction, 0)
+        );
+        watches.watch(transaction_digest, &transaction, 2).unwrap();
+
+        assert!(watches.update_with_tip(&block_1, |_| true).is_empty());
+        assert_eq!(
+            vec![(block_1.hash(), block_1.kernel.header.height)],
+            watches.including_blocks()
+        );
+
+        // A competing block 2 takes block 1 out of the canonical chain
+        let (block_2, _, _) = make_mock_block(&genesis, None, address, random());
+        assert_eq!(
+            vec![WatchEvent::ReorgedOut {
+                transaction_digest,
+                block_digest: block_1.hash()
+            }],
+            watches.update_with_tip(&block_2, |digest| digest != block_1.hash())
+        );
+        assert!(watches.including_blocks().is_empty());
+
+        // Block 1 comes back, with a child
+        let (block_1_child, _, _) = make_mock_block(&block_1, None, address, random());
+        assert!(watches.update_with_tip(&block_1, |_| true).is_empty());
+        assert_eq!(
+            vec![WatchEvent::Confirmed {
+                transaction_digest,
+                block_digest: block_1.hash(),
+                confirmations: 2,
+            }],
+            watches.update_with_tip(&block_1_child, |_| true)
+        );
+        assert!(watches.is_empty());
+    }
+}
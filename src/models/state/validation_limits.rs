@@ -0,0 +1,132 @@
+//! Limits on how much validation work runs at a time, so that the node can be
+//! tuned to the machine it runs on: a small VPS should not verify proofs for
+//! every connected peer at once, a large server can.
+//!
+//! Three kinds of work are limited, each by a semaphore:
+//!
+//!  - proof verifications, of blocks and of transactions, the heaviest work;
+//!  - validations of blocks received from peers, proofs included;
+//!  - admissions of transactions received from peers to the mempool, proofs
+//!    included.
+//!
+//! Block validations and mempool admissions take a proof verification permit
+//! while verifying proofs, always after their own permit, so that no two tasks
+//! can wait for each other's permits.
+
+use std::sync::Arc;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config_models::cli_args;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationLimitsConfig {
+    pub max_proof_verifications: usize,
+    pub max_block_validations: usize,
+    pub mempool_admission_workers: usize,
+}
+
+impl ValidationLimitsConfig {
+    /// The limits set by the CLI arguments, one per core for those not set
+    pub fn from_cli_args(cli: &cli_args::Args) -> Self {
+        let cores = std::thread::available_parallelism()
+            .map(|cores| cores.get())
+            .unwrap_or(1);
+
+        Self {
+            max_proof_verifications: cli.max_proof_verifications.unwrap_or(cores).max(1),
+            max_block_validations: cli.max_block_validations.unwrap_or(cores).max(1),
+            mempool_admission_workers: cli.mempool_admission_workers.unwrap_or(cores).max(1),
+        }
+    }
+}
+
+/// Cloning gives a handle to the same semaphores
+#[derive(Debug, Clone)]
+pub struct ValidationLimits {
+    config: ValidationLimitsConfig,
+    proof_verifications: Arc<Semaphore>,
+    block_validations: Arc<Semaphore>,
+    mempool_admissions: Arc<Semaphore>,
+}
+
+impl ValidationLimits {
+    pub fn new(config: ValidationLimitsConfig) -> Self {
+        Self {
+            config,
+            proof_verifications: Arc::new(Semaphore::new(config.max_proof_verifications)),
+            block_validations: Arc::new(Semaphore::new(config.max_block_validations)),
+            mempool_admissions: Arc::new(Semaphore::new(config.mempool_admission_workers)),
+        }
+    }
+
+    pub fn config(&self) -> ValidationLimitsConfig {
+        self.config
+    }
+
+    /// Wait for a slot to verify proofs in, held until the permit is dropped
+    pub async fn proof_verification(&self) -> OwnedSemaphorePermit {
+        Self::acquire(&self.proof_verifications).await
+    }
+
+    /// Wait for a slot to validate a block in, held until the permit is
+    /// dropped
+    pub async fn block_validation(&self) -> OwnedSemaphorePermit {
+        Self::acquire(&self.block_validations).await
+    }
+
+    /// Wait for a slot to admit a transaction to the mempool in, held until
+    /// the permit is dropped
+    pub async fn mempool_admission(&self) -> OwnedSemaphorePermit {
+        Self::acquire(&self.mempool_admissions).await
+    }
+
+    async fn acquire(semaphore: &Arc<Semaphore>) -> OwnedSemaphorePermit {
+        semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("validation semaphores are never closed")
+    }
+}
+
+#[cfg(test)]
+mod validation_limits_tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn permits_are_limited_per_kind_of_work() {
+        let limits = ValidationLimits::new(ValidationLimitsConfig {
+            max_proof_verifications: 1,
+            max_block_validations: 2,
+            mempool_admission_workers: 1,
+        });
+        let timeout = Duration::from_millis(50);
+
+        let block_permits = [
+            limits.block_validation().await,
+            limits.block_validation().await,
+        ];
+        assert!(tokio::time::timeout(timeout, limits.block_validation())
+            .await
+            .is_err());
+
+        // Other kinds of work are not held up
+        let proof_permit = limits.proof_verification().await;
+        assert!(tokio::time::timeout(timeout, limits.proof_verification())
+            .await
+            .is_err());
+        let _admission_permit = limits.mempool_admission().await;
+
+        drop(proof_permit);
+        assert!(tokio::time::timeout(timeout, limits.proof_verification())
+            .await
+            .is_ok());
+        drop(block_permits);
+        assert!(tokio::time::timeout(timeout, limits.block_validation())
+            .await
+            .is_ok());
+    }
+}
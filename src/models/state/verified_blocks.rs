@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+
+use crate::prelude::twenty_first::math::digest::Digest;
+
+/// Number of verified blocks remembered. Remembering more forgets the least
+/// recently verified one.
+pub const VERIFIED_BLOCK_CACHE_SIZE: usize = 1000;
+
+/// Digests of blocks and their proofs, see
+/// [`Block::proofs_digest`](crate::models::blockchain::block::Block::proofs_digest), for
+/// blocks whose proofs verified, so that the proofs of blocks offered again,
+/// e.g. when a fork is reorganized back onto, need not be verified again.
+/// Least recently inserted entries are evicted first.
+///
+/// Blocks received without their proofs do not belong here.
+#[derive(Debug, Default)]
+pub struct VerifiedBlockCache {
+    entries: VecDeque<Digest>,
+}
+
+impl VerifiedBlockCache {
+    /// Insert a digest, or mark it as recently inserted if known
+    pub fn insert(&mut self, digest: Digest) {
+        if let Some(index) = self.entries.iter().position(|d| *d == digest) {
+            let entry = self.entries.remove(index).unwrap();
+            self.entries.push_back(entry);
+            return;
+        }
+
+        if self.entries.len() == VERIFIED_BLOCK_CACHE_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(digest);
+    }
+
+    /// Whether the proofs of the block are known to verify
+    pub fn contains(&self, digest: Digest) -> bool {
+        self.entries.contains(&digest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod verified_blocks_tests {
+    use super::*;
+
+    #[test]
+    fn least_recently_verified_block_is_forgotten() {
+        let digest = |i: u64| Digest::new([i.into(); 5]);
+        let mut cache = VerifiedBlockCache::default();
+        for i in 0..VERIFIED_BLOCK_CACHE_SIZE as u64 {
+            cache.insert(digest(i));
+        }
+
+        // verifying the oldest entry again makes it the most recent one
+        assert!(cache.contains(digest(0)));
+        cache.insert(digest(0));
+
+        cache.insert(digest(1_000_000));
+        assert_eq!(VERIFIED_BLOCK_CACHE_SIZE, cache.len());
+        assert!(!cache.contains(digest(1)));
+        assert!(cache.contains(digest(0)));
+        assert!(cache.contains(digest(1_000_000)));
+    }
+}
@@ -17,21 +17,24 @@ use serde_derive::Serialize;
 use sha3::digest::ExtendableOutput;
 use sha3::digest::Update;
 use sha3::Shake256;
+use tracing::warn;
 use triton_vm::triton_asm;
 use triton_vm::triton_instr;
 use twenty_first::math::lattice::kem::CIPHERTEXT_SIZE_IN_BFES;
 use twenty_first::math::tip5::DIGEST_LENGTH;
 use twenty_first::{
     math::{b_field_element::BFieldElement, lattice, tip5::Digest},
-    util_types::algebraic_hasher::AlgebraicHasher,
+    util_types::{algebraic_hasher::AlgebraicHasher, mmr::mmr_trait::Mmr},
 };
 
 use crate::config_models::network::Network;
+use crate::models::blockchain::block::Block;
 use crate::models::blockchain::shared::Hash;
 use crate::models::blockchain::transaction::utxo::LockScript;
 use crate::models::blockchain::transaction::utxo::Utxo;
 use crate::models::blockchain::transaction::PublicAnnouncement;
 use crate::models::blockchain::transaction::Transaction;
+use crate::models::state::wallet::secret_digest::SecretDigest;
 use crate::util_types::mutator_set::addition_record::AdditionRecord;
 
 pub const GENERATION_FLAG: BFieldElement = BFieldElement::new(79);
@@ -40,11 +43,24 @@ pub const GENERATION_FLAG: BFieldElement = BFieldElement::new(79);
 pub struct SpendingKey {
     pub receiver_identifier: BFieldElement,
     pub decryption_key: lattice::kem::SecretKey,
-    pub privacy_preimage: Digest,
-    pub unlock_key: Digest,
+    pub privacy_preimage: SecretDigest,
+    pub unlock_key: SecretDigest,
     pub seed: Digest,
 }
 
+/// A UTXO announced to a spending key in a block, with what is needed to
+/// restore its mutator set membership proof
+#[derive(Clone, Debug)]
+pub struct AnnouncedUtxo {
+    pub addition_record: AdditionRecord,
+    pub utxo: Utxo,
+    pub sender_randomness: Digest,
+    pub receiver_preimage: Digest,
+
+    /// The index of the UTXO's leaf in the append-only commitment list
+    pub aocl_index: u64,
+}
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ReceivingAddress {
     pub receiver_identifier: BFieldElement,
@@ -141,14 +157,16 @@ pub fn std_lockscript_reference_verify_unlock(
     _bind_to: Digest,
     witness_data: [BFieldElement; DIGEST_LENGTH],
 ) -> bool {
-    spending_lock == Digest::new(witness_data).hash::<Hash>()
+    // The hash of the witness is compared in constant time, as the witness is
+    // usually our own unlock key.
+    SecretDigest::new(Digest::new(witness_data).hash::<Hash>()) == spending_lock
 }
 
 impl SpendingKey {
     pub fn to_address(&self) -> ReceivingAddress {
         let randomness: [u8; 32] = shake256::<32>(&bincode::serialize(&self.seed).unwrap());
         let (_sk, pk) = lattice::kem::keygen(randomness);
-        let privacy_digest = self.privacy_preimage.hash::<Hash>();
+        let privacy_digest = self.privacy_preimage.hash();
         ReceivingAddress {
             receiver_identifier: self.receiver_identifier,
             encryption_key: pk,
@@ -195,8 +213,8 @@ impl SpendingKey {
 
             // and join those with the receiver digest to get a commitment
             // Note: the commitment is computed in the same way as in the mutator set.
-            let receiver_preimage = self.privacy_preimage;
-            let receiver_digest = receiver_preimage.hash::<Hash>();
+            let receiver_preimage = self.privacy_preimage.expose();
+            let receiver_digest = self.privacy_preimage.hash();
             let addition_record = commit(Hash::hash(&utxo), sender_randomness, receiver_digest);
 
             // push to list
@@ -211,11 +229,52 @@ impl SpendingKey {
         received_utxos_with_randomnesses
     }
 
+    /// Return the UTXOs announced to this key in `block`, with their AOCL
+    /// indices. Announcements are not committed to by the outputs, so a block
+    /// may announce a UTXO that it does not contain; such UTXOs are skipped.
+    pub fn scan_block_for_announced_utxos(&self, block: &Block) -> Vec<AnnouncedUtxo> {
+        // The AOCL indices of the outputs follow those of the outputs of the
+        // preceding blocks
+        let outputs = &block.kernel.body.transaction.kernel.outputs;
+        let first_aocl_index = block
+            .kernel
+            .body
+            .mutator_set_accumulator
+            .aocl
+            .count_leaves()
+            - outputs.len() as u64;
+
+        let mut announced_utxos = vec![];
+        for (addition_record, utxo, sender_randomness, receiver_preimage) in
+            self.scan_for_announced_utxos(&block.kernel.body.transaction)
+        {
+            let Some(position) = outputs.iter().position(|output| *output == addition_record)
+            else {
+                warn!(
+                    "Block {} announces a UTXO that is not among its outputs",
+                    block.hash()
+                );
+                continue;
+            };
+            announced_utxos.push(AnnouncedUtxo {
+                addition_record,
+                utxo,
+                sender_randomness,
+                receiver_preimage,
+                aocl_index: first_aocl_index + position as u64,
+            });
+        }
+
+        announced_utxos
+    }
+
     pub fn derive_from_seed(seed: Digest) -> Self {
-        let privacy_preimage =
-            Hash::hash_varlen(&[seed.values().to_vec(), vec![BFieldElement::new(0)]].concat());
-        let unlock_key =
-            Hash::hash_varlen(&[seed.values().to_vec(), vec![BFieldElement::new(1)]].concat());
+        let privacy_preimage = SecretDigest::new(Hash::hash_varlen(
+            &[seed.values().to_vec(), vec![BFieldElement::new(0)]].concat(),
+        ));
+        let unlock_key = SecretDigest::new(Hash::hash_varlen(
+            &[seed.values().to_vec(), vec![BFieldElement::new(1)]].concat(),
+        ));
         let randomness: [u8; 32] = shake256::<32>(&bincode::serialize(&seed).unwrap());
         let (sk, _pk) = lattice::kem::keygen(randomness);
         let receiver_identifier = derive_receiver_id(seed);
@@ -274,13 +333,13 @@ impl SpendingKey {
     }
 
     fn generate_spending_lock(&self) -> Digest {
-        self.unlock_key.hash::<Hash>()
+        self.unlock_key.hash()
     }
 
     /// Unlock the UTXO binding it to some transaction by its kernel hash.
     /// This function mocks proof generation.
     pub fn binding_unlock(&self, _bind_to: Digest) -> [BFieldElement; DIGEST_LENGTH] {
-        let witness_data = self.unlock_key;
+        let witness_data = self.unlock_key.expose();
         witness_data.values()
     }
 }
@@ -291,7 +350,7 @@ impl ReceivingAddress {
         let receiver_identifier = derive_receiver_id(seed);
         let randomness: [u8; 32] = shake256::<32>(&bincode::serialize(&seed).unwrap());
         let (_sk, pk) = lattice::kem::keygen(randomness);
-        let privacy_digest = spending_key.privacy_preimage.hash::<Hash>();
+        let privacy_digest = spending_key.privacy_preimage.hash();
         Self {
             receiver_identifier,
             encryption_key: pk,
@@ -309,7 +368,9 @@ impl ReceivingAddress {
     /// address.
     pub fn can_unlock_with(&self, witness: &[BFieldElement]) -> bool {
         match witness.try_into() {
-            Ok(witness_array) => Digest::new(witness_array).hash::<Hash>() == self.spending_lock,
+            Ok(witness_array) => {
+                SecretDigest::new(Digest::new(witness_array).hash::<Hash>()) == self.spending_lock
+            }
             Err(_) => false,
         }
     }
@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config_models::network::Network;
+
+use super::address::generation_address::ReceivingAddress;
+use super::WalletDisabled;
+
+/// A named receiving address, saved in the wallet database so that coins can
+/// be sent by name
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBookEntry {
+    pub name: String,
+    pub address: ReceivingAddress,
+    pub note: String,
+}
+
+/// The reason an address book entry was not saved
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum AddressBookError {
+    #[error("name must not be empty")]
+    EmptyName,
+
+    #[error("invalid address for {network}: {reason}")]
+    InvalidAddress { network: Network, reason: String },
+
+    #[error(transparent)]
+    WalletDisabled(#[from] WalletDisabled),
+}
+
+impl AddressBookEntry {
+    /// Create an entry from a bech32m-encoded address, whose checksum and
+    /// network are verified.
+    pub fn new(
+        name: String,
+        encoded_address: String,
+        note: String,
+        network: Network,
+    ) -> Result<Self, AddressBookError> {
+        if name.trim().is_empty() {
+            return Err(AddressBookError::EmptyName);
+        }
+
+        let address = ReceivingAddress::from_bech32m(encoded_address, network).map_err(|err| {
+            AddressBookError::InvalidAddress {
+                network,
+                reason: err.to_string(),
+            }
+        })?;
+
+        Ok(Self {
+            name,
+            address,
+            note,
+        })
+    }
+}
+
+#[cfg(test)]
+mod address_book_tests {
+    use crate::models::state::wallet::WalletSecret;
+
+    use super::*;
+
+    #[test]
+    fn entry_requires_name_and_valid_address() {
+        let network = Network::RegTest;
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let encoded = address.to_bech32m(network).unwrap();
+
+        let entry =
+            AddressBookEntry::new("alice".to_owned(), encoded.clone(), String::new(), network)
+                .unwrap();
+        assert_eq!(address, entry.address);
+
+        assert_eq!(
+            Err(AddressBookError::EmptyName),
+            AddressBookEntry::new(" ".to_owned(), encoded.clone(), String::new(), network)
+        );
+
+        // flipping a character breaks the checksum
+        let mut corrupted = encoded.clone().into_bytes();
+        let last = corrupted.len() - 1;
+        corrupted[last] = if corrupted[last] == b'q' { b'p' } else { b'q' };
+        let corrupted = String::from_utf8(corrupted).unwrap();
+        assert!(matches!(
+            AddressBookEntry::new("bob".to_owned(), corrupted, String::new(), network),
+            Err(AddressBookError::InvalidAddress { .. })
+        ));
+
+        assert!(matches!(
+            AddressBookEntry::new("carol".to_owned(), encoded, String::new(), Network::Alpha),
+            Err(AddressBookError::InvalidAddress { .. })
+        ));
+    }
+}
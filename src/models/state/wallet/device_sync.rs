@@ -0,0 +1,304 @@
+//! Incremental exports of the wallet state for companion wallets, e.g. on a
+//! phone, that hold the same seed but run no node.
+//!
+//! Every update of the monitored UTXOs, such as a block that pays the
+//! wallet or spends from it, a rescan or an abandoned UTXO, is recorded in
+//! the wallet database as a [`WalletChange`] that lists the indices of the
+//! monitored UTXOs it touched. A block that only moves the tip lists none, as
+//! the membership proofs of all unspent UTXOs are updated by every block. The
+//! number of recorded changes is the wallet's sequence number. A companion
+//! wallet asks for the delta since the sequence number it saw last, and gets
+//! the current state of every monitored UTXO that changed since, and of every
+//! unspent one if the tip moved, encrypted under a key derived from the
+//! wallet secret.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::monitored_utxo::MonitoredUtxo;
+use super::wallet_backup::WalletBackups;
+use super::wallet_state::WalletState;
+use super::WalletDisabled;
+use crate::database::storage::storage_vec::traits::*;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
+
+/// An update of the monitored UTXOs, as recorded in the wallet database
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletChange {
+    /// The block the wallet was synced to by the update
+    pub tip_digest: Digest,
+
+    /// Number of monitored UTXOs after the update
+    pub utxo_count: u64,
+
+    /// Indices of the monitored UTXOs that were added or changed
+    pub changed: Vec<u64>,
+}
+
+/// The current state of a monitored UTXO that changed since the sequence
+/// number a companion wallet asked from
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UtxoDelta {
+    /// Index of the UTXO among the monitored UTXOs, which never changes
+    pub index: u64,
+
+    /// True if the UTXO was added since the sequence number
+    pub is_new: bool,
+
+    pub utxo: Utxo,
+    pub confirmed_in_block: Option<(Digest, Timestamp, BlockHeight)>,
+    pub spent_in_block: Option<(Digest, Timestamp, BlockHeight)>,
+    pub abandoned: bool,
+
+    /// Membership proof valid for the block the wallet is synced to, if the
+    /// wallet holds one
+    pub membership_proof: Option<MsMembershipProof>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletDelta {
+    /// The sequence number the delta starts from
+    pub since: u64,
+
+    /// The sequence number of the wallet, to ask from next time
+    pub sequence: u64,
+
+    /// The block the wallet is synced to
+    pub tip_digest: Digest,
+
+    pub utxos: Vec<UtxoDelta>,
+}
+
+impl WalletDelta {
+    /// Encrypt under the wallet's device sync key, see
+    /// [`super::WalletSecret::device_sync_encryption_key`]
+    pub fn encrypt(&self, key: &[u8; 32]) -> Result<Vec<u8>> {
+        WalletBackups::encrypt(self, key)
+    }
+
+    pub fn decrypt(bytes: &[u8], key: &[u8; 32]) -> Result<Self> {
+        WalletBackups::decrypt(bytes, key)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum DeviceSyncError {
+    #[error("sequence number {since} is ahead of the wallet's sequence number {sequence}")]
+    AheadOfWallet { since: u64, sequence: u64 },
+
+    #[error("could not export wallet delta: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    WalletDisabled(#[from] WalletDisabled),
+}
+
+impl WalletState {
+    /// The current state of all monitored UTXOs that changed after the
+    /// wallet's sequence number was `since`
+    pub async fn wallet_delta(&self, since: u64) -> Result<WalletDelta, DeviceSyncError> {
+        let sequence = self.wallet_db.wallet_change_count().await;
+        if since > sequence {
+            return Err(DeviceSyncError::AheadOfWallet { since, sequence });
+        }
+
+        let (utxo_count_at_since, tip_at_since) = match since {
+            0 => (0, None),
+            _ => {
+                let change = self.wallet_db.wallet_change(since - 1).await;
+                (change.utxo_count, Some(change.tip_digest))
+            }
+        };
+        let mut changed = self
+            .wallet_db
+            .wallet_changes_since(since)
+            .await
+            .into_iter()
+            .flat_map(|change| change.changed)
+            .collect::<BTreeSet<_>>();
+
+        let tip_digest = self.wallet_db.get_sync_label().await;
+        let monitored_utxos = self.wallet_db.monitored_utxos();
+
+        // A new tip comes with new membership proofs for all unspent UTXOs
+        if since < sequence && tip_at_since != Some(tip_digest) {
+            let stream = monitored_utxos.stream().await;
+            pin_mut!(stream); // needed for iteration
+
+            while let Some((index, monitored_utxo)) = stream.next().await {
+                if monitored_utxo.spent_in_block.is_none() && monitored_utxo.abandoned_at.is_none()
+                {
+                    changed.insert(index);
+                }
+            }
+        }
+
+        let mut utxos = Vec::with_capacity(changed.len());
+        for index in changed {
+            let monitored_utxo: MonitoredUtxo = monitored_utxos.get(index).await;
+            utxos.push(UtxoDelta {
+                index,
+                is_new: index >= utxo_count_at_since,
+                membership_proof: monitored_utxo.get_membership_proof_for_block(tip_digest),
+                utxo: monitored_utxo.utxo,
+                confirmed_in_block: monitored_utxo.confirmed_in_block,
+                spent_in_block: monitored_utxo.spent_in_block,
+                abandoned: monitored_utxo.abandoned_at.is_some(),
+            });
+        }
+
+        Ok(WalletDelta {
+            since,
+            sequence,
+            tip_digest,
+            utxos,
+        })
+    }
+
+    /// [`Self::wallet_delta`], encrypted for a companion wallet
+    pub async fn encrypted_wallet_delta(&self, since: u64) -> Result<Vec<u8>, DeviceSyncError> {
+        let key = self.wallet_secret.device_sync_encryption_key();
+        self.wallet_delta(since)
+            .await?
+            .encrypt(&key)
+            .map_err(|err| DeviceSyncError::Failed(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod device_sync_tests {
+    use rand::{thread_rng, Rng};
+
+    use crate::config_models::network::Network;
+    use crate::models::blockchain::block::Block;
+    use crate::models::state::wallet::utxo_notification_pool::{ExpectedUtxo, UtxoNotifier};
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{make_mock_block, mock_genesis_global_state};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn deltas_hold_the_utxos_changed_since_the_sequence_number() {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let wallet_secret = WalletSecret::new_random();
+        let own_spending_key = wallet_secret.nth_generation_spending_key(0);
+        let global_state_lock = mock_genesis_global_state(network, 0, wallet_secret.clone()).await;
+        let mut global_state = global_state_lock.lock_guard_mut().await;
+
+        let start = global_state.wallet_state.wallet_delta(0).await.unwrap();
+        let utxo_count_at_start = global_state
+            .wallet_state
+            .wallet_db
+            .monitored_utxos()
+            .len()
+            .await;
+
+        let genesis_block = Block::genesis_block(network);
+        let (block_1, coinbase_utxo, sender_randomness) = make_mock_block(
+            &genesis_block,
+            None,
+            own_spending_key.to_address(),
+            rng.gen(),
+        );
+        global_state
+            .set_new_self_mined_tip(
+                block_1.clone(),
+                ExpectedUtxo::new(
+                    coinbase_utxo,
+                    sender_randomness,
+                    own_spending_key.privacy_preimage.expose(),
+                    UtxoNotifier::OwnMiner,
+                ),
+            )
+            .await
+            .unwrap();
+
+        let delta = global_state
+            .wallet_state
+            .wallet_delta(start.sequence)
+            .await
+            .unwrap();
+        assert_eq!(block_1.hash(), delta.tip_digest);
+        assert!(delta.sequence > start.sequence);
+        let new_utxos = delta
+            .utxos
+            .iter()
+            .filter(|utxo| utxo.is_new)
+            .collect::<Vec<_>>();
+        assert_eq!(1, new_utxos.len(), "the coinbase of block 1 is new");
+        assert_eq!(utxo_count_at_start, new_utxos[0].index);
+        assert!(delta
+            .utxos
+            .iter()
+            .all(|utxo| utxo.membership_proof.is_some()));
+
+        // Nothing changed since the latest sequence number
+        let empty = global_state
+            .wallet_state
+            .wallet_delta(delta.sequence)
+            .await
+            .unwrap();
+        assert!(empty.utxos.is_empty());
+        assert_eq!(
+            Err(DeviceSyncError::AheadOfWallet {
+                since: delta.sequence + 1,
+                sequence: delta.sequence
+            }),
+            global_state
+                .wallet_state
+                .wallet_delta(delta.sequence + 1)
+                .await
+        );
+
+        // Only the wallet's own device sync key decrypts the delta
+        let encrypted = global_state
+            .wallet_state
+            .encrypted_wallet_delta(start.sequence)
+            .await
+            .unwrap();
+        let key = wallet_secret.device_sync_encryption_key();
+        assert_eq!(delta, WalletDelta::decrypt(&encrypted, &key).unwrap());
+        assert!(WalletDelta::decrypt(&encrypted, &wallet_secret.backup_encryption_key()).is_err());
+
+        // A block that does not involve the wallet only moves the tip, which
+        // refreshes the membership proofs without listing the UTXOs
+        let other_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_2, _, _) = make_mock_block(&block_1, None, other_address, rng.gen());
+        global_state.set_new_tip(block_2.clone()).await.unwrap();
+        let sequence = global_state
+            .wallet_state
+            .wallet_db
+            .wallet_change_count()
+            .await;
+        assert_eq!(delta.sequence + 1, sequence);
+        assert!(global_state
+            .wallet_state
+            .wallet_db
+            .wallet_change(delta.sequence)
+            .await
+            .changed
+            .is_empty());
+        let moved = global_state
+            .wallet_state
+            .wallet_delta(delta.sequence)
+            .await
+            .unwrap();
+        assert_eq!(block_2.hash(), moved.tip_digest);
+        assert!(moved
+            .utxos
+            .iter()
+            .any(|utxo| utxo.index == new_utxos[0].index
+                && !utxo.is_new
+                && utxo.membership_proof.is_some()));
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::mutator_set::addition_record::AdditionRecord;
+
+use super::WalletDisabled;
+
+/// How a block changed the wallet's UTXOs, as recorded by the historical
+/// index. Undoing the deltas of the blocks from the tip back to some height
+/// gives the wallet's UTXOs at that height.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockDelta {
+    pub block_digest: Digest,
+    pub prev_block_digest: Digest,
+    pub height: BlockHeight,
+
+    /// Own UTXOs confirmed in the block, by addition record
+    pub received: Vec<(AdditionRecord, NeptuneCoins)>,
+
+    /// Own UTXOs spent in the block, by addition record
+    pub spent: Vec<(AdditionRecord, NeptuneCoins)>,
+}
+
+impl BlockDelta {
+    /// Turn the wallet's unspent UTXOs after this block into those before it
+    pub fn undo(&self, unspent_utxos: &mut HashMap<AdditionRecord, NeptuneCoins>) {
+        for (addition_record, _) in self.received.iter() {
+            unspent_utxos.remove(addition_record);
+        }
+        unspent_utxos.extend(self.spent.iter().copied());
+    }
+}
+
+/// The reason a query at a past block height failed
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum HistoricalQueryError {
+    #[error("height {height} is above the tip at height {tip_height}")]
+    AboveTip {
+        height: BlockHeight,
+        tip_height: BlockHeight,
+    },
+
+    #[error("the wallet is not synced to the tip")]
+    NotSynced,
+
+    #[error(
+        "block {block_digest} at height {height} is not in the historical index; \
+        it only covers blocks processed with --historical-index"
+    )]
+    NotIndexed {
+        block_digest: Digest,
+        height: BlockHeight,
+    },
+
+    #[error(
+        "the UTXO does not belong to the wallet, so whether it was spent cannot be told; \
+        the mutator set does not reveal which UTXO an input spends"
+    )]
+    NotOwned,
+
+    #[error("{0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    WalletDisabled(#[from] WalletDisabled),
+}
+
+#[cfg(test)]
+mod historical_index_tests {
+    use rand::random;
+
+    use super::*;
+    use crate::util_types::mutator_set::addition_record::pseudorandom_addition_record;
+
+    #[test]
+    fn undoing_a_block_restores_spent_and_drops_received_utxos() {
+        let kept = pseudorandom_addition_record(random());
+        let spent = pseudorandom_addition_record(random());
+        let received = pseudorandom_addition_record(random());
+        let delta = BlockDelta {
+            block_digest: random(),
+            prev_block_digest: random(),
+            height: 1u64.into(),
+            received: vec![(received, NeptuneCoins::new(3))],
+            spent: vec![(spent, NeptuneCoins::new(2))],
+        };
+
+        let mut unspent_utxos = HashMap::from([
+            (kept, NeptuneCoins::new(1)),
+            (received, NeptuneCoins::new(3)),
+        ]);
+        delta.undo(&mut unspent_utxos);
+        assert_eq!(
+            HashMap::from([(kept, NeptuneCoins::new(1)), (spent, NeptuneCoins::new(2))]),
+            unspent_utxos
+        );
+    }
+}
@@ -1,10 +1,19 @@
 use crate::prelude::twenty_first;
 
 pub mod address;
+pub mod address_book;
 pub mod coin_with_possible_timelock;
+pub mod device_sync;
+pub mod historical_index;
 pub mod monitored_utxo;
+pub mod prepared_send;
 pub mod rusty_wallet_database;
+pub mod secret_digest;
+pub mod sent_transactions;
+pub mod shared_control;
+pub mod spend_policy;
 pub mod utxo_notification_pool;
+pub mod wallet_backup;
 pub mod wallet_state;
 pub mod wallet_status;
 
@@ -13,10 +22,11 @@ use bip39::Mnemonic;
 use itertools::Itertools;
 use num_traits::Zero;
 use rand::rngs::StdRng;
-use rand::{thread_rng, Rng, SeedableRng};
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::fs::{self};
 use std::path::{Path, PathBuf};
+use thiserror::Error;
 use tracing::info;
 use twenty_first::math::bfield_codec::BFieldCodec;
 use twenty_first::math::digest::Digest;
@@ -28,9 +38,11 @@ use twenty_first::math::b_field_element::BFieldElement;
 
 use crate::models::blockchain::block::block_height::BlockHeight;
 
+use crate::util_types::entropy;
 use crate::Hash;
 
 use self::address::generation_address;
+use self::secret_digest::SecretDigest;
 
 pub const WALLET_DIRECTORY: &str = "wallet";
 pub const WALLET_SECRET_FILE_NAME: &str = "wallet.dat";
@@ -41,6 +53,11 @@ const STANDARD_WALLET_VERSION: u8 = 0;
 pub const WALLET_DB_NAME: &str = "wallet";
 pub const WALLET_OUTPUT_COUNT_DB_NAME: &str = "wallout_output_count_db";
 
+/// Returned by wallet operations on a node running with `--no-wallet`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+#[error("the wallet is disabled on this node")]
+pub struct WalletDisabled;
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 struct SecretKeyMaterial(XFieldElement);
 
@@ -91,9 +108,9 @@ impl WalletSecret {
     }
 
     /// Create a new `Wallet` and populate it with a new secret seed, with entropy
-    /// obtained via `thread_rng()` from the operating system.
+    /// obtained from the node's entropy source.
     pub fn new_random() -> Self {
-        Self::new_pseudorandom(thread_rng().gen())
+        Self::new_pseudorandom(entropy::random())
     }
 
     /// Create a new `Wallet` and populate it by expanding a given seed.
@@ -207,6 +224,22 @@ impl WalletSecret {
         generation_address::SpendingKey::derive_from_seed(key_seed)
     }
 
+    /// Return the unlock key with which the wallet cosigns for the `counter`th
+    /// shared-control lock. Its hash is the cosigner key to hand to the other
+    /// cosigners, see [`shared_control::SharedControlLock`].
+    pub fn nth_shared_control_unlock_key(&self, counter: u16) -> SecretDigest {
+        SecretDigest::new(Hash::hash_varlen(
+            &[
+                self.secret_seed.0.encode(),
+                vec![
+                    shared_control::SHARED_CONTROL_FLAG,
+                    BFieldElement::new(counter.into()),
+                ],
+            ]
+            .concat(),
+        ))
+    }
+
     /// Return the secret key that is used to deterministically generate commitment pseudo-randomness
     /// for the mutator set.
     pub fn generate_sender_randomness(
@@ -228,6 +261,32 @@ impl WalletSecret {
         )
     }
 
+    /// Return the key that backups of the wallet database are encrypted under
+    pub fn backup_encryption_key(&self) -> [u8; 32] {
+        const BACKUP_ENCRYPTION_KEY_FLAG: u64 = 0xbac4_0be7u64;
+        self.encryption_key(BACKUP_ENCRYPTION_KEY_FLAG)
+    }
+
+    /// Return the key that wallet deltas for companion wallets are encrypted
+    /// under
+    pub fn device_sync_encryption_key(&self) -> [u8; 32] {
+        const DEVICE_SYNC_ENCRYPTION_KEY_FLAG: u64 = 0xde71_ce5au64;
+        self.encryption_key(DEVICE_SYNC_ENCRYPTION_KEY_FLAG)
+    }
+
+    fn encryption_key(&self, flag: u64) -> [u8; 32] {
+        let key_digest = Hash::hash_varlen(
+            &[self.secret_seed.0.encode(), vec![BFieldElement::new(flag)]].concat(),
+        );
+        let key_bytes = key_digest
+            .values()
+            .iter()
+            .flat_map(|bfe| bfe.value().to_le_bytes())
+            .collect_vec();
+
+        key_bytes[..32].try_into().unwrap()
+    }
+
     /// Read Wallet from file as JSON
     pub fn read_from_file(wallet_file: &Path) -> Result<Self> {
         let wallet_file_content: String = fs::read_to_string(wallet_file).with_context(|| {
@@ -350,11 +409,12 @@ mod wallet_tests {
     use crate::database::storage::storage_vec::traits::*;
     use itertools::Itertools;
     use num_traits::CheckedSub;
-    use rand::random;
+    use rand::{random, thread_rng};
     use tracing_test::traced_test;
     use twenty_first::math::tip5::DIGEST_LENGTH;
     use twenty_first::math::x_field_element::EXTENSION_DEGREE;
 
+    use super::address_book::AddressBookEntry;
     use super::monitored_utxo::MonitoredUtxo;
     use super::wallet_state::WalletState;
     use super::*;
@@ -378,6 +438,72 @@ mod wallet_tests {
         wallet_state.wallet_db.monitored_utxos().get_all().await
     }
 
+    #[tokio::test]
+    async fn sent_transaction_digests_are_found_by_idempotency_key_test() {
+        let mut wallet_state =
+            mock_genesis_wallet_state(WalletSecret::new_random(), Network::RegTest).await;
+        let wallet_db = &mut wallet_state.wallet_db;
+        let (digest_a, digest_b, digest_c): (Digest, Digest, Digest) = random();
+
+        assert!(wallet_db.get_sent_transaction_digest("a").await.is_none());
+        wallet_db
+            .set_sent_transaction_digest("a".to_owned(), digest_a)
+            .await;
+        wallet_db
+            .set_sent_transaction_digest("b".to_owned(), digest_b)
+            .await;
+        wallet_db
+            .set_sent_transaction_digest("c".to_owned(), digest_c)
+            .await;
+        assert_eq!(
+            Some(digest_b),
+            wallet_db.get_sent_transaction_digest("b").await
+        );
+
+        assert!(wallet_db.remove_sent_transaction_digest("a").await);
+        assert!(!wallet_db.remove_sent_transaction_digest("a").await);
+        assert!(wallet_db.get_sent_transaction_digest("a").await.is_none());
+        assert_eq!(
+            Some(digest_b),
+            wallet_db.get_sent_transaction_digest("b").await
+        );
+        assert_eq!(
+            Some(digest_c),
+            wallet_db.get_sent_transaction_digest("c").await
+        );
+    }
+
+    #[tokio::test]
+    async fn address_book_entries_are_unique_by_name_test() {
+        let mut wallet_state =
+            mock_genesis_wallet_state(WalletSecret::new_random(), Network::RegTest).await;
+        let wallet_db = &mut wallet_state.wallet_db;
+        let entry = |name: &str, note: &str| AddressBookEntry {
+            name: name.to_owned(),
+            address: WalletSecret::new_random()
+                .nth_generation_spending_key(0)
+                .to_address(),
+            note: note.to_owned(),
+        };
+
+        let (alice, bob, carol) = (entry("alice", ""), entry("bob", ""), entry("carol", ""));
+        assert!(!wallet_db.set_address_book_entry(alice.clone()).await);
+        assert!(!wallet_db.set_address_book_entry(bob.clone()).await);
+        assert!(!wallet_db.set_address_book_entry(carol.clone()).await);
+
+        let new_bob = entry("bob", "new address");
+        assert!(wallet_db.set_address_book_entry(new_bob.clone()).await);
+        assert_eq!(
+            Some(new_bob.clone()),
+            wallet_db.get_address_book_entry("bob").await
+        );
+
+        assert!(wallet_db.remove_address_book_entry("alice").await);
+        assert!(!wallet_db.remove_address_book_entry("alice").await);
+        assert!(wallet_db.get_address_book_entry("alice").await.is_none());
+        assert_eq!(vec![new_bob, carol], wallet_db.address_book().await);
+    }
+
     #[tokio::test]
     async fn wallet_state_constructor_with_genesis_block_test() -> Result<()> {
         let mut rng = thread_rng();
@@ -479,7 +605,7 @@ mod wallet_tests {
             .add_expected_utxo(
                 block_1_coinbase_utxo.clone(),
                 block_1_coinbase_sender_randomness,
-                own_spending_key.privacy_preimage,
+                own_spending_key.privacy_preimage.expose(),
                 UtxoNotifier::OwnMiner,
             )
             .unwrap();
@@ -609,7 +735,7 @@ mod wallet_tests {
             .add_expected_utxo(
                 cb_utxo,
                 cb_output_randomness,
-                own_spending_key.privacy_preimage,
+                own_spending_key.privacy_preimage.expose(),
                 UtxoNotifier::OwnMiner,
             )
             .unwrap();
@@ -670,7 +796,7 @@ mod wallet_tests {
                 .add_expected_utxo(
                     cb_utxo_prime,
                     cb_output_randomness_prime,
-                    own_spending_key.privacy_preimage,
+                    own_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::OwnMiner,
                 )
                 .unwrap();
@@ -896,7 +1022,7 @@ mod wallet_tests {
                 .add_expected_utxo(
                     receive_data.utxo,
                     receive_data.sender_randomness,
-                    own_spending_key.privacy_preimage,
+                    own_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::Cli,
                 )
                 .unwrap();
@@ -956,7 +1082,7 @@ mod wallet_tests {
                 .add_expected_utxo(
                     ret.1,
                     ret.2,
-                    own_spending_key.privacy_preimage,
+                    own_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::OwnMiner,
                 )
                 .unwrap();
@@ -1149,7 +1275,7 @@ mod wallet_tests {
             .add_expected_utxo(
                 cb_utxo,
                 cb_sender_randomness,
-                own_spending_key.privacy_preimage,
+                own_spending_key.privacy_preimage.expose(),
                 UtxoNotifier::OwnMiner,
             )
             .unwrap();
@@ -1158,7 +1284,7 @@ mod wallet_tests {
             .add_expected_utxo(
                 receiver_data_six.utxo,
                 receiver_data_six.sender_randomness,
-                own_spending_key.privacy_preimage,
+                own_spending_key.privacy_preimage.expose(),
                 UtxoNotifier::Cli,
             )
             .unwrap();
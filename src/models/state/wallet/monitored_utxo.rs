@@ -1,6 +1,7 @@
 use crate::{models::consensus::timestamp::Timestamp, prelude::twenty_first};
 
 use std::collections::VecDeque;
+use std::fmt::Display;
 
 use crate::{
     models::{blockchain::block::block_height::BlockHeight, state::archival_state::ArchivalState},
@@ -10,6 +11,98 @@ use serde::{Deserialize, Serialize};
 use twenty_first::math::tip5::Digest;
 
 use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::util_types::digest_serde;
+
+/// Whether the membership proof of a monitored UTXO can be used to spend it
+/// at some block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MonitoredUtxoState {
+    /// A membership proof valid for the block is stored
+    Spendable,
+
+    /// No membership proof valid for the block is stored, but one can be
+    /// derived from a stored one when membership proofs are resynced
+    NeedsRestore,
+
+    /// No membership proof valid for the block is stored, and resyncing
+    /// failed, e.g. because blocks it needs were not available. Funds can
+    /// only be recovered by restoring the UTXO from the incoming
+    /// randomness file.
+    Unrecoverable,
+}
+
+impl Display for MonitoredUtxoState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = match self {
+            MonitoredUtxoState::Spendable => "spendable",
+            MonitoredUtxoState::NeedsRestore => "needs restore",
+            MonitoredUtxoState::Unrecoverable => "unrecoverable",
+        };
+        write!(f, "{state}")
+    }
+}
+
+/// Where a monitored UTXO came from, as far as the wallet could tell when the
+/// block confirming it was scanned
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum,
+)]
+pub enum UtxoOrigin {
+    /// A block reward mined by this node, or a premine allocation
+    Coinbase,
+
+    /// A payment from someone else. Also used when the origin is unknown, as
+    /// for UTXOs restored from the incoming randomness file.
+    #[default]
+    IncomingPayment,
+
+    /// An output of our own transaction that also paid someone else
+    Change,
+
+    /// An output of our own transaction that paid only ourselves
+    Consolidation,
+}
+
+impl Display for UtxoOrigin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let origin = match self {
+            UtxoOrigin::Coinbase => "coinbase",
+            UtxoOrigin::IncomingPayment => "incoming payment",
+            UtxoOrigin::Change => "change",
+            UtxoOrigin::Consolidation => "consolidation",
+        };
+        write!(f, "{origin}")
+    }
+}
+
+/// A monitored UTXO and the state of its membership proof. For reporting
+/// purposes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoredUtxoReport {
+    #[serde(with = "digest_serde")]
+    pub utxo_digest: Digest,
+    pub amount: NeptuneCoins,
+    pub aocl_leaf_index: Option<u64>,
+    pub confirmed_at_height: Option<BlockHeight>,
+    pub spent: bool,
+    pub abandoned: bool,
+    pub state: MonitoredUtxoState,
+}
+
+/// A change to the wallet's balance: a monitored UTXO being confirmed, or
+/// spent, in which case the amount is negative
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionHistoryEntry {
+    #[serde(with = "digest_serde")]
+    pub block_digest: Digest,
+    pub height: BlockHeight,
+    pub timestamp: Timestamp,
+    pub amount: NeptuneCoins,
+
+    /// The origin of the UTXO, also for entries spending it
+    pub origin: UtxoOrigin,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MonitoredUtxo {
@@ -29,6 +122,41 @@ pub struct MonitoredUtxo {
     /// Indicator used to mark the UTXO as belonging to an abandoned fork
     /// Indicates what was the block tip when UTXO was marked as abandoned
     pub abandoned_at: Option<(Digest, Timestamp, BlockHeight)>,
+
+    /// Indicator used to mark that the membership proof could not be resynced
+    /// Indicates what was the block tip when resyncing failed
+    pub unrecoverable_at: Option<(Digest, Timestamp, BlockHeight)>,
+
+    pub origin: UtxoOrigin,
+}
+
+/// A monitored UTXO as stored by wallet databases created before the
+/// `unrecoverable_at` and `origin` fields were added. The storage encoding
+/// lays out fields in order, so such entries cannot be read as
+/// [`MonitoredUtxo`]; they are converted when the database is opened.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacyMonitoredUtxo {
+    pub utxo: Utxo,
+    pub blockhash_to_membership_proof: VecDeque<(Digest, MsMembershipProof)>,
+    pub number_of_mps_per_utxo: usize,
+    pub spent_in_block: Option<(Digest, Timestamp, BlockHeight)>,
+    pub confirmed_in_block: Option<(Digest, Timestamp, BlockHeight)>,
+    pub abandoned_at: Option<(Digest, Timestamp, BlockHeight)>,
+}
+
+impl From<LegacyMonitoredUtxo> for MonitoredUtxo {
+    fn from(legacy: LegacyMonitoredUtxo) -> Self {
+        Self {
+            utxo: legacy.utxo,
+            blockhash_to_membership_proof: legacy.blockhash_to_membership_proof,
+            number_of_mps_per_utxo: legacy.number_of_mps_per_utxo,
+            spent_in_block: legacy.spent_in_block,
+            confirmed_in_block: legacy.confirmed_in_block,
+            abandoned_at: legacy.abandoned_at,
+            unrecoverable_at: None,
+            origin: UtxoOrigin::default(),
+        }
+    }
 }
 
 impl MonitoredUtxo {
@@ -40,9 +168,27 @@ impl MonitoredUtxo {
             spent_in_block: None,
             confirmed_in_block: None,
             abandoned_at: None,
+            unrecoverable_at: None,
+            origin: UtxoOrigin::default(),
+        }
+    }
+
+    /// Return the state of the membership proof relative to the given block
+    pub fn state(&self, block_hash: Digest) -> MonitoredUtxoState {
+        if self.is_synced_to(block_hash) {
+            MonitoredUtxoState::Spendable
+        } else if self.unrecoverable_at.is_some() {
+            MonitoredUtxoState::Unrecoverable
+        } else {
+            MonitoredUtxoState::NeedsRestore
         }
     }
 
+    /// Mark the membership proof as impossible to resync to the given tip
+    pub fn mark_unrecoverable(&mut self, tip: (Digest, Timestamp, BlockHeight)) {
+        self.unrecoverable_at = Some(tip);
+    }
+
     // determine whether the attached membership proof is synced to the given block
     pub fn is_synced_to(&self, block_hash: Digest) -> bool {
         self.get_membership_proof_for_block(block_hash).is_some()
@@ -59,6 +205,7 @@ impl MonitoredUtxo {
 
         self.blockhash_to_membership_proof
             .push_front((block_digest, updated_membership_proof));
+        self.unrecoverable_at = None;
     }
 
     pub fn get_membership_proof_for_block(
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use get_size::GetSize;
+use serde::{Deserialize, Serialize};
+
+use crate::models::blockchain::transaction::utxo::{LockScript, Utxo};
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::UtxoReceiverData;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+use crate::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
+
+use super::address::generation_address::ReceivingAddress;
+
+/// How long a prepared send can be confirmed for, in minutes
+pub const PREPARED_SEND_LIFETIME_IN_MINUTES: usize = 10;
+
+/// Max number of prepared sends awaiting confirmation. Preparing more evicts
+/// the oldest.
+pub const MAX_PREPARED_SENDS: usize = 100;
+
+/// An input selected for a prepared send
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, GetSize)]
+pub struct PreviewedInput {
+    pub aocl_leaf_index: u64,
+    pub amount: NeptuneCoins,
+}
+
+/// What a prepared send will do if confirmed, returned to the client so it
+/// can be shown to the user before funds move
+#[derive(Clone, Debug, Serialize, Deserialize, GetSize)]
+pub struct SendPreview {
+    /// Pass to `confirm_send` to create and broadcast the transaction
+    #[serde(with = "digest_serde")]
+    pub token: Digest,
+
+    /// The tip the inputs were selected at. Confirming fails if the tip changes.
+    #[serde(with = "digest_serde")]
+    pub tip_digest: Digest,
+
+    pub inputs: Vec<PreviewedInput>,
+    pub amount: NeptuneCoins,
+    pub change: NeptuneCoins,
+    pub fee: NeptuneCoins,
+
+    /// The date before which the recipient cannot spend the output, if it is
+    /// time-locked
+    pub release_date: Option<Timestamp>,
+
+    pub expires_at: Timestamp,
+}
+
+/// Everything needed to create the transaction a [`SendPreview`] describes
+#[derive(Clone, Debug, GetSize)]
+pub struct PreparedSend {
+    pub preview: SendPreview,
+    #[get_size(ignore)] // Stored inline
+    pub address: ReceivingAddress,
+    pub receiver_data: Vec<UtxoReceiverData>,
+    pub spendable_utxos_and_mps: Vec<(Utxo, LockScript, MsMembershipProof)>,
+    pub timestamp: Timestamp,
+}
+
+/// Prepared sends awaiting confirmation, by token. Kept in memory only, as
+/// they are meant to be confirmed within minutes.
+#[derive(Debug, Default, GetSize)]
+pub struct PreparedSends(HashMap<Digest, PreparedSend>);
+
+impl PreparedSends {
+    pub fn insert(&mut self, prepared_send: PreparedSend) {
+        let now = prepared_send.timestamp;
+        self.0.retain(|_, pending| pending.preview.expires_at > now);
+        while self.0.len() >= MAX_PREPARED_SENDS {
+            let oldest_token = self
+                .0
+                .iter()
+                .min_by_key(|(_, pending)| pending.timestamp)
+                .map(|(token, _)| *token)
+                .unwrap();
+            self.0.remove(&oldest_token);
+        }
+
+        self.0.insert(prepared_send.preview.token, prepared_send);
+    }
+
+    /// Remove and return the prepared send with the given token, unless it
+    /// has expired.
+    pub fn take(&mut self, token: Digest, now: Timestamp) -> Option<PreparedSend> {
+        self.0
+            .remove(&token)
+            .filter(|prepared_send| prepared_send.preview.expires_at > now)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod prepared_send_tests {
+    use rand::random;
+
+    use crate::models::state::wallet::WalletSecret;
+
+    use super::*;
+
+    fn prepared_send(timestamp: Timestamp) -> PreparedSend {
+        PreparedSend {
+            preview: SendPreview {
+                token: random(),
+                tip_digest: random(),
+                inputs: vec![],
+                amount: NeptuneCoins::new(1),
+                change: NeptuneCoins::new(0),
+                fee: NeptuneCoins::new(0),
+                release_date: None,
+                expires_at: timestamp + Timestamp::minutes(PREPARED_SEND_LIFETIME_IN_MINUTES),
+            },
+            address: WalletSecret::new_random()
+                .nth_generation_spending_key(0)
+                .to_address(),
+            receiver_data: vec![],
+            spendable_utxos_and_mps: vec![],
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn prepared_sends_expire_and_are_taken_once() {
+        let now = Timestamp::now();
+        let mut prepared_sends = PreparedSends::default();
+
+        let pending = prepared_send(now);
+        let token = pending.preview.token;
+        prepared_sends.insert(pending);
+        assert!(prepared_sends.take(token, now).is_some());
+        assert!(prepared_sends.take(token, now).is_none());
+
+        let pending = prepared_send(now);
+        let token = pending.preview.token;
+        prepared_sends.insert(pending);
+        let too_late = now + Timestamp::minutes(PREPARED_SEND_LIFETIME_IN_MINUTES);
+        assert!(prepared_sends.take(token, too_late).is_none());
+    }
+
+    #[test]
+    fn oldest_prepared_send_is_evicted() {
+        let now = Timestamp::now();
+        let mut prepared_sends = PreparedSends::default();
+
+        let oldest = prepared_send(now);
+        let oldest_token = oldest.preview.token;
+        prepared_sends.insert(oldest);
+        for i in 1..=MAX_PREPARED_SENDS {
+            prepared_sends.insert(prepared_send(now + Timestamp::millis(i as u64)));
+        }
+
+        assert_eq!(MAX_PREPARED_SENDS, prepared_sends.len());
+        assert!(prepared_sends.take(oldest_token, now).is_none());
+    }
+}
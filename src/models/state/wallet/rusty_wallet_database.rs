@@ -1,25 +1,75 @@
 use crate::prelude::twenty_first;
 
+use crate::database::storage::storage_vec::traits::*;
 use crate::database::{
     storage::storage_schema::{
         traits::*, DbtSingleton, DbtVec, RustyKey, RustyValue, SimpleRustyStorage,
     },
     NeptuneLevelDb,
 };
+use num_traits::Zero;
 use twenty_first::math::tip5::Digest;
 
-use super::monitored_utxo::MonitoredUtxo;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use tracing::info;
+
+use super::address_book::AddressBookEntry;
+use super::device_sync::WalletChange;
+use super::historical_index::BlockDelta;
+use super::monitored_utxo::{LegacyMonitoredUtxo, MonitoredUtxo};
+
+/// Everything stored in the wallet database, except the block deltas of the
+/// historical index, which are rebuilt as blocks arrive
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalletDatabaseContents {
+    pub monitored_utxos: Vec<MonitoredUtxo>,
+    pub sync_label: Digest,
+    pub counter: u64,
+    pub sent_transactions: Vec<(String, Digest)>,
+    pub send_history: Vec<(Timestamp, NeptuneCoins)>,
+    pub address_book: Vec<AddressBookEntry>,
+}
 
 pub struct RustyWalletDatabase {
     storage: SimpleRustyStorage,
 
-    monitored_utxos: DbtVec<MonitoredUtxo>,
+    // monitored UTXOs in the layout of databases created before fields were
+    // added to them. Emptied into `monitored_utxos` on connecting.
+    legacy_monitored_utxos: DbtVec<LegacyMonitoredUtxo>,
 
     // records which block the database is synced to
     sync_label: DbtSingleton<Digest>,
 
     // counts the number of output UTXOs generated by this wallet
     counter: DbtSingleton<u64>,
+
+    // idempotency keys of send requests, and the digests of the transactions
+    // they created
+    sent_transactions: DbtVec<(String, Digest)>,
+
+    // position of each idempotency key in `sent_transactions`, so that send
+    // requests need not scan all of them. Built on connecting.
+    sent_transaction_positions: HashMap<String, u64>,
+
+    // when, and how much including fees, this wallet sent through the send RPC
+    send_history: DbtVec<(Timestamp, NeptuneCoins)>,
+
+    // named receiving addresses, unique by name
+    address_book: DbtVec<AddressBookEntry>,
+
+    // how each block changed the wallet's UTXOs, when the historical index
+    // is enabled
+    block_deltas: DbtVec<BlockDelta>,
+
+    // updates of the monitored UTXOs, for exporting deltas to companion
+    // wallets
+    wallet_changes: DbtVec<WalletChange>,
+
+    monitored_utxos: DbtVec<MonitoredUtxo>,
 }
 
 impl RustyWalletDatabase {
@@ -30,19 +80,80 @@ impl RustyWalletDatabase {
             crate::LOG_LOCK_EVENT_CB,
         );
 
-        let monitored_utxos_storage = storage
+        let legacy_monitored_utxos_storage = storage
             .schema
-            .new_vec::<MonitoredUtxo>("monitored_utxos")
+            .new_vec::<LegacyMonitoredUtxo>("monitored_utxos")
             .await;
         let sync_label_storage = storage.schema.new_singleton::<Digest>("sync_label").await;
         let counter_storage = storage.schema.new_singleton::<u64>("counter").await;
+        let sent_transactions_storage = storage
+            .schema
+            .new_vec::<(String, Digest)>("sent_transactions")
+            .await;
+        let send_history_storage = storage
+            .schema
+            .new_vec::<(Timestamp, NeptuneCoins)>("send_history")
+            .await;
+        let address_book_storage = storage
+            .schema
+            .new_vec::<AddressBookEntry>("address_book")
+            .await;
+        let block_deltas_storage = storage.schema.new_vec::<BlockDelta>("block_deltas").await;
+        let wallet_changes_storage = storage
+            .schema
+            .new_vec::<WalletChange>("wallet_changes")
+            .await;
+        let monitored_utxos_storage = storage
+            .schema
+            .new_vec::<MonitoredUtxo>("monitored_utxos_v2")
+            .await;
 
-        Self {
+        let mut wallet_database = Self {
             storage,
+            legacy_monitored_utxos: legacy_monitored_utxos_storage,
             monitored_utxos: monitored_utxos_storage,
             sync_label: sync_label_storage,
             counter: counter_storage,
+            sent_transactions: sent_transactions_storage,
+            sent_transaction_positions: HashMap::new(),
+            send_history: send_history_storage,
+            address_book: address_book_storage,
+            block_deltas: block_deltas_storage,
+            wallet_changes: wallet_changes_storage,
+        };
+        wallet_database.migrate_legacy_monitored_utxos().await;
+        wallet_database.index_sent_transactions().await;
+
+        wallet_database
+    }
+
+    async fn index_sent_transactions(&mut self) {
+        let stream = self.sent_transactions.stream().await;
+        pin_mut!(stream); // needed for iteration
+
+        while let Some((position, (key, _transaction_digest))) = stream.next().await {
+            self.sent_transaction_positions.insert(key, position);
+        }
+    }
+
+    /// Convert the monitored UTXOs stored in the legacy layout, keeping their
+    /// order. The conversion is persisted along with the next change.
+    async fn migrate_legacy_monitored_utxos(&mut self) {
+        if self.legacy_monitored_utxos.is_empty().await {
+            return;
+        }
+
+        let legacy_monitored_utxos = self.legacy_monitored_utxos.get_all().await;
+        info!(
+            "Converting {} monitored UTXOs stored in the legacy layout",
+            legacy_monitored_utxos.len()
+        );
+        for legacy_monitored_utxo in legacy_monitored_utxos {
+            self.monitored_utxos
+                .push(MonitoredUtxo::from(legacy_monitored_utxo))
+                .await;
         }
+        self.legacy_monitored_utxos.clear().await;
     }
 
     /// get monitored_utxos.
@@ -71,6 +182,227 @@ impl RustyWalletDatabase {
     pub async fn set_counter(&mut self, counter: u64) {
         self.counter.set(counter).await;
     }
+
+    /// Get the digest of the transaction created by the send request with the
+    /// given idempotency key, if any.
+    pub async fn get_sent_transaction_digest(&self, idempotency_key: &str) -> Option<Digest> {
+        let position = *self.sent_transaction_positions.get(idempotency_key)?;
+        let (_key, transaction_digest) = self.sent_transactions.get(position).await;
+
+        Some(transaction_digest)
+    }
+
+    /// Record that the send request with the given idempotency key created the
+    /// transaction with the given digest.
+    pub async fn set_sent_transaction_digest(
+        &mut self,
+        idempotency_key: String,
+        transaction_digest: Digest,
+    ) {
+        if let Some(&position) = self.sent_transaction_positions.get(&idempotency_key) {
+            self.sent_transactions
+                .set(position, (idempotency_key, transaction_digest))
+                .await;
+            return;
+        }
+
+        let position = self.sent_transactions.len().await;
+        self.sent_transaction_positions
+            .insert(idempotency_key.clone(), position);
+        self.sent_transactions
+            .push((idempotency_key, transaction_digest))
+            .await;
+    }
+
+    /// Forget the idempotency key, e.g. because the transaction it refers to was
+    /// never broadcast. Returns false if the key was not recorded.
+    pub async fn remove_sent_transaction_digest(&mut self, idempotency_key: &str) -> bool {
+        let Some(position) = self.sent_transaction_positions.remove(idempotency_key) else {
+            return false;
+        };
+
+        // Order is irrelevant, so move the last entry into the vacated slot
+        let last = self.sent_transactions.pop().await.unwrap();
+        if last.0 != idempotency_key {
+            self.sent_transaction_positions
+                .insert(last.0.clone(), position);
+            self.sent_transactions.set(position, last).await;
+        }
+
+        true
+    }
+
+    /// Record that the wallet sent `amount` at `timestamp`
+    pub async fn record_send(&mut self, timestamp: Timestamp, amount: NeptuneCoins) {
+        self.send_history.push((timestamp, amount)).await;
+    }
+
+    /// Forget a send recorded with [`Self::record_send`], e.g. because its
+    /// transaction could not be created. Returns false if it was not recorded.
+    pub async fn remove_send(&mut self, timestamp: Timestamp, amount: NeptuneCoins) -> bool {
+        let num_entries = self.send_history.len().await;
+        for i in (0..num_entries).rev() {
+            if self.send_history.get(i).await != (timestamp, amount) {
+                continue;
+            }
+
+            // Order is irrelevant, so move the last entry into the vacated slot
+            let last = self.send_history.pop().await.unwrap();
+            if i != num_entries - 1 {
+                self.send_history.set(i, last).await;
+            }
+            return true;
+        }
+
+        false
+    }
+
+    /// Get the total amount sent at or after `timestamp`
+    pub async fn amount_sent_since(&self, timestamp: Timestamp) -> NeptuneCoins {
+        let stream = self.send_history.stream_values().await;
+        pin_mut!(stream); // needed for iteration
+
+        let mut amount_sent = NeptuneCoins::zero();
+        while let Some((sent_at, amount)) = stream.next().await {
+            if sent_at >= timestamp {
+                amount_sent = amount_sent + amount;
+            }
+        }
+
+        amount_sent
+    }
+
+    /// Get all address book entries, in the order they were added
+    pub async fn address_book(&self) -> Vec<AddressBookEntry> {
+        self.address_book.get_all().await
+    }
+
+    /// Get the address book entry with the given name, if any
+    pub async fn get_address_book_entry(&self, name: &str) -> Option<AddressBookEntry> {
+        let stream = self.address_book.stream_values().await;
+        pin_mut!(stream); // needed for iteration
+
+        while let Some(entry) = stream.next().await {
+            if entry.name == name {
+                return Some(entry);
+            }
+        }
+
+        None
+    }
+
+    /// Add an entry to the address book, replacing any entry with the same
+    /// name. Returns true if an entry was replaced.
+    pub async fn set_address_book_entry(&mut self, entry: AddressBookEntry) -> bool {
+        let num_entries = self.address_book.len().await;
+        for i in 0..num_entries {
+            if self.address_book.get(i).await.name == entry.name {
+                self.address_book.set(i, entry).await;
+                return true;
+            }
+        }
+
+        self.address_book.push(entry).await;
+        false
+    }
+
+    /// Remove the address book entry with the given name. Returns false if
+    /// there was no such entry.
+    pub async fn remove_address_book_entry(&mut self, name: &str) -> bool {
+        let num_entries = self.address_book.len().await;
+        for i in 0..num_entries {
+            if self.address_book.get(i).await.name != name {
+                continue;
+            }
+
+            // Keep the order of the remaining entries
+            for j in i + 1..num_entries {
+                let entry = self.address_book.get(j).await;
+                self.address_book.set(j - 1, entry).await;
+            }
+            self.address_book.pop().await;
+            return true;
+        }
+
+        false
+    }
+
+    /// Get the contents of the database, e.g. for backing it up
+    pub async fn contents(&self) -> WalletDatabaseContents {
+        WalletDatabaseContents {
+            monitored_utxos: self.monitored_utxos.get_all().await,
+            sync_label: self.sync_label.get().await,
+            counter: self.counter.get().await,
+            sent_transactions: self.sent_transactions.get_all().await,
+            send_history: self.send_history.get_all().await,
+            address_book: self.address_book.get_all().await,
+        }
+    }
+
+    /// Record how a block changed the wallet's UTXOs
+    pub async fn record_block_delta(&mut self, block_delta: BlockDelta) {
+        self.block_deltas.push(block_delta).await;
+    }
+
+    /// The number of recorded block deltas
+    pub async fn block_delta_count(&self) -> u64 {
+        self.block_deltas.len().await
+    }
+
+    /// Get the block delta recorded as the `index`th, counting from zero
+    pub async fn block_delta(&self, index: u64) -> BlockDelta {
+        self.block_deltas.get(index).await
+    }
+
+    /// Record that the monitored UTXOs with the given indices were added or
+    /// changed, leaving the database synced to `tip_digest`. Membership proofs
+    /// that were merely updated to a new tip need not be listed, as moving the
+    /// tip implies that all of them were. Does nothing if no indices are given
+    /// and the tip did not move.
+    pub async fn record_wallet_change(&mut self, tip_digest: Digest, changed: Vec<u64>) {
+        if changed.is_empty() && self.latest_wallet_change_tip().await == Some(tip_digest) {
+            return;
+        }
+
+        let utxo_count = self.monitored_utxos.len().await;
+        self.wallet_changes
+            .push(WalletChange {
+                tip_digest,
+                utxo_count,
+                changed,
+            })
+            .await;
+    }
+
+    /// The number of recorded wallet changes, which is the sequence number of
+    /// the wallet
+    pub async fn wallet_change_count(&self) -> u64 {
+        self.wallet_changes.len().await
+    }
+
+    /// The tip the latest recorded wallet change left the database synced to
+    async fn latest_wallet_change_tip(&self) -> Option<Digest> {
+        match self.wallet_changes.len().await {
+            0 => None,
+            count => Some(self.wallet_changes.get(count - 1).await.tip_digest),
+        }
+    }
+
+    /// Get the wallet change with the given sequence number
+    pub async fn wallet_change(&self, sequence: u64) -> WalletChange {
+        self.wallet_changes.get(sequence).await
+    }
+
+    /// Get the wallet changes from the given sequence number on
+    pub async fn wallet_changes_since(&self, sequence: u64) -> Vec<WalletChange> {
+        let count = self.wallet_changes.len().await;
+        let mut changes = vec![];
+        for i in sequence..count {
+            changes.push(self.wallet_changes.get(i).await);
+        }
+
+        changes
+    }
 }
 
 impl StorageWriter for RustyWalletDatabase {
@@ -78,3 +410,81 @@ impl StorageWriter for RustyWalletDatabase {
         self.storage.persist().await
     }
 }
+
+#[cfg(test)]
+mod rusty_wallet_database_tests {
+    use std::collections::VecDeque;
+
+    use rand::random;
+
+    use super::*;
+    use crate::models::blockchain::transaction::utxo::{LockScript, Utxo};
+
+    #[tokio::test]
+    async fn sent_transactions_are_found_by_key_after_reconnecting() {
+        let db = NeptuneLevelDb::open_new_test_database(true, None, None, None)
+            .await
+            .unwrap();
+        let mut wallet_db = RustyWalletDatabase::connect(db.clone()).await;
+        let digests: [Digest; 3] = random();
+        for (i, digest) in digests.iter().enumerate() {
+            wallet_db
+                .set_sent_transaction_digest(format!("key-{i}"), *digest)
+                .await;
+        }
+        assert!(wallet_db.remove_sent_transaction_digest("key-0").await);
+        assert!(!wallet_db.remove_sent_transaction_digest("key-0").await);
+        wallet_db.persist().await;
+
+        let wallet_db = RustyWalletDatabase::connect(db).await;
+        assert_eq!(None, wallet_db.get_sent_transaction_digest("key-0").await);
+        assert_eq!(
+            Some(digests[1]),
+            wallet_db.get_sent_transaction_digest("key-1").await
+        );
+        assert_eq!(
+            Some(digests[2]),
+            wallet_db.get_sent_transaction_digest("key-2").await
+        );
+    }
+
+    #[tokio::test]
+    async fn monitored_utxos_in_the_legacy_layout_are_converted() {
+        let db = NeptuneLevelDb::open_new_test_database(true, None, None, None)
+            .await
+            .unwrap();
+
+        // As written before fields were added to monitored UTXOs
+        let mut legacy_storage = SimpleRustyStorage::new(db.clone());
+        let mut legacy_monitored_utxos = legacy_storage
+            .schema
+            .new_vec::<LegacyMonitoredUtxo>("monitored_utxos")
+            .await;
+        let utxo = Utxo::new_native_coin(LockScript::anyone_can_spend(), NeptuneCoins::new(7));
+        let confirmed_in_block = Some((random(), Timestamp::now(), 1u64.into()));
+        legacy_monitored_utxos
+            .push(LegacyMonitoredUtxo {
+                utxo: utxo.clone(),
+                blockhash_to_membership_proof: VecDeque::new(),
+                number_of_mps_per_utxo: 3,
+                spent_in_block: None,
+                confirmed_in_block,
+                abandoned_at: None,
+            })
+            .await;
+        legacy_storage.persist().await;
+
+        let mut wallet_db = RustyWalletDatabase::connect(db.clone()).await;
+        assert_eq!(1, wallet_db.monitored_utxos().len().await);
+        let monitored_utxo = wallet_db.monitored_utxos().get(0).await;
+        assert_eq!(utxo, monitored_utxo.utxo);
+        assert_eq!(confirmed_in_block, monitored_utxo.confirmed_in_block);
+        assert!(monitored_utxo.unrecoverable_at.is_none());
+        wallet_db.persist().await;
+
+        // The conversion is done once
+        let wallet_db = RustyWalletDatabase::connect(db).await;
+        assert!(wallet_db.legacy_monitored_utxos.is_empty().await);
+        assert_eq!(1, wallet_db.monitored_utxos().len().await);
+    }
+}
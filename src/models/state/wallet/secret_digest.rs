@@ -0,0 +1,125 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::models::blockchain::shared::Hash;
+use crate::prelude::twenty_first;
+use twenty_first::math::digest::Digest;
+use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+/// A digest that is, or is derived from, secret key material, such as a
+/// receiver preimage or an unlock key.
+///
+/// Equality is evaluated in constant time, so comparing a secret against a
+/// candidate does not leak through timing how many leading elements match.
+/// `Ord` and `Hash` are deliberately not implemented: sorting or hashing
+/// secrets branches on their values. `Debug` output is redacted.
+///
+/// Serializes exactly like the wrapped [`Digest`].
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretDigest(Digest);
+
+impl SecretDigest {
+    pub fn new(digest: Digest) -> Self {
+        Self(digest)
+    }
+
+    /// Return the secret value. Comparisons must not be made on the result.
+    pub fn expose(&self) -> Digest {
+        self.0
+    }
+
+    /// Return the hash of the secret, which may be published.
+    pub fn hash(&self) -> Digest {
+        Hash::hash(&self.0)
+    }
+}
+
+impl ConstantTimeEq for SecretDigest {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.ct_eq(&other.0)
+    }
+}
+
+impl ConstantTimeEq<Digest> for SecretDigest {
+    fn ct_eq(&self, other: &Digest) -> Choice {
+        self.0
+            .values()
+            .iter()
+            .zip(other.values().iter())
+            .fold(Choice::from(1), |acc, (a, b)| {
+                acc & a.value().ct_eq(&b.value())
+            })
+    }
+}
+
+impl PartialEq for SecretDigest {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl PartialEq<Digest> for SecretDigest {
+    fn eq(&self, other: &Digest) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl PartialEq<SecretDigest> for Digest {
+    fn eq(&self, other: &SecretDigest) -> bool {
+        other.ct_eq(self).into()
+    }
+}
+
+impl Eq for SecretDigest {}
+
+impl fmt::Debug for SecretDigest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretDigest(<redacted>)")
+    }
+}
+
+impl From<Digest> for SecretDigest {
+    fn from(digest: Digest) -> Self {
+        Self(digest)
+    }
+}
+
+#[cfg(test)]
+mod secret_digest_tests {
+    use rand::random;
+    use twenty_first::math::b_field_element::BFieldElement;
+
+    use super::*;
+
+    #[test]
+    fn equality_matches_digest_equality() {
+        let digest: Digest = random();
+        let secret = SecretDigest::new(digest);
+        assert_eq!(secret, SecretDigest::new(digest));
+        assert!(secret == digest);
+        assert!(digest == secret);
+
+        let mut values = digest.values();
+        values[4] += BFieldElement::new(1);
+        let almost = Digest::new(values);
+        assert_ne!(secret, SecretDigest::new(almost));
+        assert!(secret != almost);
+        assert!(almost != secret);
+
+        assert_eq!(Hash::hash(&digest), secret.hash());
+    }
+
+    #[test]
+    fn debug_output_is_redacted_and_encoding_is_unchanged() {
+        let digest: Digest = random();
+        let secret = SecretDigest::new(digest);
+        assert!(!format!("{secret:?}").contains(&format!("{}", digest.values()[0])));
+        assert_eq!(
+            bincode::serialize(&digest).unwrap(),
+            bincode::serialize(&secret).unwrap()
+        );
+    }
+}
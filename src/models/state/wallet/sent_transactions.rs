@@ -0,0 +1,294 @@
+//! Transactions sent by this wallet, tracked until a block settles them.
+//!
+//! A transaction that spends an input of one of ours, without creating all of
+//! our outputs, is a double spend: either by someone who holds the same keys,
+//! or by ourselves through another node. Such transactions are detected when
+//! they reach the mempool and when they are mined, and ours is marked as
+//! conflicted.
+
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+use tasm_lib::twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+
+use crate::models::blockchain::block::Block;
+use crate::models::blockchain::shared::Hash;
+use crate::models::blockchain::transaction::Transaction;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::util_types::digest_serde;
+use crate::util_types::mutator_set::addition_record::AdditionRecord;
+use crate::util_types::mutator_set::removal_record::AbsoluteIndexSet;
+use crate::util_types::mutator_set::shared::NUM_TRIALS;
+
+/// Max number of sent transactions remembered. The oldest settled ones are
+/// forgotten first.
+pub const MAX_SENT_TRANSACTIONS: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SentTransactionStatus {
+    Unconfirmed,
+
+    /// Mined in the given block
+    Confirmed(#[serde(with = "digest_serde")] Digest),
+
+    /// An input was spent by another transaction, either in the mempool, or
+    /// in a block. Conflicted transactions that are still in the mempool can
+    /// be mined all the same, until a block settles the conflict.
+    Conflicted {
+        #[serde(with = "digest_serde")]
+        conflicting_digest: Digest,
+        in_block: bool,
+    },
+}
+
+impl SentTransactionStatus {
+    /// Whether a block has settled the fate of the transaction
+    pub fn is_settled(&self) -> bool {
+        matches!(
+            self,
+            Self::Confirmed(_) | Self::Conflicted { in_block: true, .. }
+        )
+    }
+}
+
+/// A transaction sent by this wallet
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SentTransaction {
+    #[serde(with = "digest_serde")]
+    pub digest: Digest,
+    pub fee: NeptuneCoins,
+    pub sent_at: Timestamp,
+    pub status: SentTransactionStatus,
+
+    #[serde(skip)]
+    input_index_sets: Vec<[u128; NUM_TRIALS as usize]>,
+    #[serde(skip)]
+    outputs: Vec<AdditionRecord>,
+}
+
+/// A transaction of ours that was found to be double spent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DoubleSpend {
+    pub own_digest: Digest,
+    pub conflicting_digest: Digest,
+    pub in_block: bool,
+}
+
+impl SentTransaction {
+    fn spends_input_of(&self, index_sets: &[AbsoluteIndexSet]) -> bool {
+        index_sets
+            .iter()
+            .any(|index_set| self.input_index_sets.contains(&index_set.to_array()))
+    }
+
+    /// Whether all our outputs are among the given ones, as when the other
+    /// transaction is ours, or ours merged with others
+    fn outputs_included_in(&self, outputs: &[AdditionRecord]) -> bool {
+        self.outputs.iter().all(|output| outputs.contains(output))
+    }
+}
+
+/// Sent transactions, oldest first. Kept in memory only.
+#[derive(Debug, Default)]
+pub struct SentTransactions(VecDeque<SentTransaction>);
+
+impl SentTransactions {
+    pub fn record(&mut self, transaction: &Transaction, now: Timestamp) {
+        self.0.push_back(SentTransaction {
+            digest: Hash::hash(transaction),
+            fee: transaction.kernel.fee,
+            sent_at: now,
+            status: SentTransactionStatus::Unconfirmed,
+            input_index_sets: transaction
+                .kernel
+                .inputs
+                .iter()
+                .map(|input| input.absolute_indices.to_array())
+                .collect(),
+            outputs: transaction.kernel.outputs.clone(),
+        });
+
+        while self.0.len() > MAX_SENT_TRANSACTIONS {
+            let Some(position) = self.0.iter().position(|sent| sent.status.is_settled()) else {
+                self.0.pop_front();
+                continue;
+            };
+            self.0.remove(position);
+        }
+    }
+
+    /// All sent transactions, oldest first
+    pub fn all(&self) -> Vec<SentTransaction> {
+        self.0.iter().cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Whether the remembered transaction of ours that created `output` paid
+    /// only outputs among `own_outputs`, or `None` if we remember no
+    /// transaction creating it
+    pub fn pays_only_own_outputs(
+        &self,
+        output: &AdditionRecord,
+        own_outputs: &HashSet<AdditionRecord>,
+    ) -> Option<bool> {
+        self.0
+            .iter()
+            .find(|sent| sent.outputs.contains(output))
+            .map(|sent| {
+                sent.outputs
+                    .iter()
+                    .all(|output| own_outputs.contains(output))
+            })
+    }
+
+    /// Check a transaction that reached the mempool or the stem pool against
+    /// our unconfirmed ones. Returns those it double spends.
+    pub fn check_transaction(&mut self, transaction: &Transaction) -> Vec<DoubleSpend> {
+        let index_sets = transaction
+            .kernel
+            .inputs
+            .iter()
+            .map(|input| input.absolute_indices.clone())
+            .collect::<Vec<_>>();
+
+        self.mark_conflicts(
+            Hash::hash(transaction),
+            &index_sets,
+            &transaction.kernel.outputs,
+            false,
+        )
+    }
+
+    /// Settle our transactions whose inputs the block spends, as confirmed
+    /// if it creates their outputs, as conflicted otherwise. Returns those it
+    /// double spends.
+    pub fn check_block(&mut self, block: &Block) -> Vec<DoubleSpend> {
+        let kernel = &block.kernel.body.transaction.kernel;
+        let index_sets = kernel
+            .inputs
+            .iter()
+            .map(|input| input.absolute_indices.clone())
+            .collect::<Vec<_>>();
+
+        let block_digest = block.hash();
+        for sent in self.0.iter_mut().filter(|sent| !sent.status.is_settled()) {
+            if sent.spends_input_of(&index_sets) && sent.outputs_included_in(&kernel.outputs) {
+                sent.status = SentTransactionStatus::Confirmed(block_digest);
+            }
+        }
+
+        self.mark_conflicts(block_digest, &index_sets, &kernel.outputs, true)
+    }
+
+    fn mark_conflicts(
+        &mut self,
+        conflicting_digest: Digest,
+        index_sets: &[AbsoluteIndexSet],
+        outputs: &[AdditionRecord],
+        in_block: bool,
+    ) -> Vec<DoubleSpend> {
+        let mut double_spends = vec![];
+        for sent in self.0.iter_mut() {
+            let already_known = matches!(
+                sent.status,
+                SentTransactionStatus::Conflicted { conflicting_digest: known, .. }
+                    if known == conflicting_digest
+            );
+            if sent.status.is_settled()
+                || already_known
+                || sent.digest == conflicting_digest
+                || !sent.spends_input_of(index_sets)
+                || sent.outputs_included_in(outputs)
+            {
+                continue;
+            }
+
+            sent.status = SentTransactionStatus::Conflicted {
+                conflicting_digest,
+                in_block,
+            };
+            double_spends.push(DoubleSpend {
+                own_digest: sent.digest,
+                conflicting_digest,
+                in_block,
+            });
+        }
+
+        double_spends
+    }
+}
+
+#[cfg(test)]
+mod sent_transactions_tests {
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{make_mock_transaction_with_wallet, mock_genesis_wallet_state};
+    use crate::util_types::mutator_set::removal_record::RemovalRecord;
+    use crate::util_types::test_shared::mutator_set::random_removal_record;
+
+    use super::*;
+
+    async fn transaction_spending(
+        inputs: Vec<RemovalRecord>,
+        outputs: Vec<AdditionRecord>,
+    ) -> Transaction {
+        let wallet_state =
+            mock_genesis_wallet_state(WalletSecret::devnet_wallet(), Network::RegTest).await;
+        make_mock_transaction_with_wallet(
+            inputs,
+            outputs,
+            NeptuneCoins::new(1),
+            &wallet_state,
+            None,
+        )
+    }
+
+    #[tokio::test]
+    async fn double_spends_are_detected_once() {
+        let input = random_removal_record();
+        let own_output = AdditionRecord::new(rand::random());
+        let own = transaction_spending(vec![input.clone()], vec![own_output]).await;
+        let mut sent_transactions = SentTransactions::default();
+        sent_transactions.record(&own, Timestamp::now());
+
+        // Relaying our own transaction, or one merged from it, is no conflict
+        assert!(sent_transactions.check_transaction(&own).is_empty());
+        let merged = transaction_spending(
+            vec![input.clone(), random_removal_record()],
+            vec![own_output, AdditionRecord::new(rand::random())],
+        )
+        .await;
+        assert!(sent_transactions.check_transaction(&merged).is_empty());
+
+        let double_spend = transaction_spending(
+            vec![input.clone()],
+            vec![AdditionRecord::new(rand::random())],
+        )
+        .await;
+        let detected = sent_transactions.check_transaction(&double_spend);
+        assert_eq!(
+            vec![DoubleSpend {
+                own_digest: Hash::hash(&own),
+                conflicting_digest: Hash::hash(&double_spend),
+                in_block: false,
+            }],
+            detected
+        );
+        assert!(sent_transactions
+            .check_transaction(&double_spend)
+            .is_empty());
+        assert!(!sent_transactions.all()[0].status.is_settled());
+
+        let unrelated = transaction_spending(vec![random_removal_record()], vec![]).await;
+        assert!(sent_transactions.check_transaction(&unrelated).is_empty());
+    }
+}
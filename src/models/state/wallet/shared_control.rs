@@ -0,0 +1,453 @@
+//! Shared control of funds by m of n cosigners, e.g. for a treasury.
+//!
+//! Each cosigner derives a dedicated unlock key from its wallet secret, see
+//! [`super::WalletSecret::nth_shared_control_unlock_key`], and hands its hash,
+//! the cosigner key, to a coordinator. The cosigner keys and the threshold `m`
+//! make up a [`SharedControlLock`], whose lock script halts only if it is
+//! given the unlock keys of exactly `m` distinct cosigners.
+//!
+//! Spending follows the flow of partially signed transactions: the
+//! coordinator creates a [`SharedSpend`] that fixes the inputs and outputs,
+//! passes it from cosigner to cosigner, each adding its unlock key, and
+//! creates the transaction once `m` cosigners signed. As with generation
+//! addresses, the lock script does not yet bind the unlock keys to the
+//! transaction, so the unlock keys must only be handed to a trusted
+//! coordinator, and must not be reused for another lock.
+
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use anyhow::Result;
+use bech32::FromBase32;
+use bech32::ToBase32;
+use bech32::Variant;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use triton_vm::instruction::LabelledInstruction;
+use triton_vm::triton_asm;
+use triton_vm::triton_instr;
+use twenty_first::math::b_field_element::BFieldElement;
+use twenty_first::math::bfield_codec::BFieldCodec;
+use twenty_first::math::digest::Digest;
+use twenty_first::math::tip5::DIGEST_LENGTH;
+
+use super::secret_digest::SecretDigest;
+use super::WalletDisabled;
+use crate::config_models::network::Network;
+use crate::models::blockchain::transaction::primitive_witness::PrimitiveWitness;
+use crate::models::blockchain::transaction::transaction_kernel::TransactionKernel;
+use crate::models::blockchain::transaction::utxo::{LockScript, Utxo};
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::{GlobalState, UtxoReceiverData};
+use crate::prelude::{triton_vm, twenty_first};
+use crate::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
+use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+
+pub const SHARED_CONTROL_FLAG: BFieldElement = BFieldElement::new(83);
+
+/// Max number of cosigners of a shared-control lock, which bounds the size of
+/// its lock script
+pub const MAX_COSIGNERS: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum SharedControlError {
+    #[error("threshold {threshold} is not between 1 and the number of cosigners {cosigners}")]
+    InvalidThreshold { threshold: usize, cosigners: usize },
+
+    #[error("at most {MAX_COSIGNERS} cosigners are supported")]
+    TooManyCosigners,
+
+    #[error("cosigner key {0} is given more than once")]
+    DuplicateCosignerKey(Digest),
+
+    #[error("input {0} is not locked by the shared-control lock")]
+    ForeignInput(usize),
+
+    #[error("inputs of {inputs} do not cover outputs of {outputs} and a fee of {fee}")]
+    Unbalanced {
+        inputs: NeptuneCoins,
+        outputs: NeptuneCoins,
+        fee: NeptuneCoins,
+    },
+
+    #[error("the unlock key is not that of a cosigner")]
+    NotACosigner,
+
+    #[error("the spend was created at another tip; create it again")]
+    StaleSpend,
+
+    #[error("{signed} of the {threshold} required cosigners signed")]
+    NotEnoughSignatures { signed: usize, threshold: usize },
+
+    #[error("could not create transaction: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    WalletDisabled(#[from] WalletDisabled),
+}
+
+/// A lock that `threshold` of the cosigners must unlock together
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedControlLock {
+    pub threshold: usize,
+
+    /// The cosigner keys, i.e. the hashes of the cosigners' unlock keys
+    pub cosigner_keys: Vec<Digest>,
+}
+
+impl SharedControlLock {
+    pub fn new(threshold: usize, cosigner_keys: Vec<Digest>) -> Result<Self, SharedControlError> {
+        if cosigner_keys.len() > MAX_COSIGNERS {
+            return Err(SharedControlError::TooManyCosigners);
+        }
+        if threshold == 0 || threshold > cosigner_keys.len() {
+            return Err(SharedControlError::InvalidThreshold {
+                threshold,
+                cosigners: cosigner_keys.len(),
+            });
+        }
+        if let Some(duplicate) = cosigner_keys.iter().duplicates().next() {
+            return Err(SharedControlError::DuplicateCosignerKey(*duplicate));
+        }
+
+        Ok(Self {
+            threshold,
+            cosigner_keys,
+        })
+    }
+
+    /// Generate the lock script. For every cosigner, it reads a flag telling
+    /// whether the cosigner signed, and if so, reads its unlock key and checks
+    /// it against the cosigner key. It halts if exactly `threshold` cosigners
+    /// signed. The logic contained in here should be identical to
+    /// [`Self::verify_unlock`].
+    pub fn lock_script(&self) -> LockScript {
+        let mut check_cosigners = vec![];
+        let mut subroutines = vec![];
+        for (index, cosigner_key) in self.cosigner_keys.iter().enumerate() {
+            let check_unlock_key = format!("check_unlock_key_of_cosigner_{index}");
+            let push_cosigner_key_to_stack = cosigner_key
+                .values()
+                .iter()
+                .rev()
+                .map(|elem| triton_instr!(push elem.value()))
+                .collect::<Vec<LabelledInstruction>>();
+
+            check_cosigners.extend(triton_asm!(
+                // _ signed_count
+                divine 1
+                // _ signed_count signed
+
+                dup 0 dup 0 push -1 add mul push 0 eq assert
+                // _ signed_count signed, where signed is 0 or 1
+
+                dup 0 skiz call {check_unlock_key}
+                add
+                // _ (signed_count + signed)
+            ));
+            subroutines.extend(triton_asm!(
+                {check_unlock_key}:
+                    push 0 push 0 push 0 push 0 push 0
+                    divine 5
+                    hash
+                    {&push_cosigner_key_to_stack}
+                    assert_vector
+                    pop 5
+                    return
+            ));
+        }
+
+        let threshold = self.threshold;
+        let instructions = triton_asm!(
+            push 0
+            {&check_cosigners}
+            push {threshold}
+            eq
+            assert
+            read_io 5
+            halt
+            {&subroutines}
+        );
+
+        instructions.into()
+    }
+
+    /// The lock script witness that unlocks with the given unlock keys, by
+    /// cosigner index
+    pub fn witness(&self, unlock_keys: &BTreeMap<usize, SecretDigest>) -> Vec<BFieldElement> {
+        (0..self.cosigner_keys.len())
+            .flat_map(|index| match unlock_keys.get(&index) {
+                Some(unlock_key) => [vec![BFieldElement::new(1)], unlock_key.expose().encode()],
+                None => [vec![BFieldElement::new(0)], vec![]],
+            })
+            .flatten()
+            .collect()
+    }
+
+    /// Verify the cosigners' assent to the transaction.
+    /// This is the rust reference implementation, but the version of
+    /// this logic that is proven is `lock_script`.
+    pub fn verify_unlock(&self, witness: &[BFieldElement]) -> bool {
+        let mut tokens = witness.iter();
+        let mut signed_count = 0;
+        for cosigner_key in &self.cosigner_keys {
+            match tokens.next().map(|flag| flag.value()) {
+                Some(0) => continue,
+                Some(1) => (),
+                _ => return false,
+            }
+            let unlock_key: Vec<BFieldElement> =
+                tokens.by_ref().take(DIGEST_LENGTH).copied().collect();
+            let Ok(unlock_key) = Digest::try_from(unlock_key.as_slice()) else {
+                return false;
+            };
+            if SecretDigest::new(unlock_key).hash() != *cosigner_key {
+                return false;
+            }
+            signed_count += 1;
+        }
+
+        signed_count == self.threshold
+    }
+
+    /// The index of the cosigner with the given unlock key
+    pub fn cosigner_index(&self, unlock_key: &SecretDigest) -> Option<usize> {
+        self.cosigner_keys
+            .iter()
+            .position(|cosigner_key| *cosigner_key == unlock_key.hash())
+    }
+
+    fn get_hrp(network: Network) -> String {
+        // NOLSC: Neptune lattice-based shared control
+        let mut hrp = "nolsc".to_string();
+        let network_byte: char = match network {
+            Network::Alpha | Network::Beta | Network::Main => 'm',
+            Network::Testnet => 't',
+            Network::RegTest => 'r',
+        };
+        hrp.push(network_byte);
+        hrp
+    }
+
+    pub fn to_bech32m(&self, network: Network) -> Result<String> {
+        let hrp = Self::get_hrp(network);
+        let payload = bincode::serialize(self)?;
+        match bech32::encode(&hrp, payload.to_base32(), Variant::Bech32m) {
+            Ok(enc) => Ok(enc),
+            Err(e) => bail!("Could not encode shared-control lock as bech32m because error: {e}"),
+        }
+    }
+
+    pub fn from_bech32m(encoded: &str, network: Network) -> Result<Self> {
+        let (hrp, data, variant) = bech32::decode(encoded)?;
+        if variant != Variant::Bech32m {
+            bail!("Can only decode bech32m shared-control locks.");
+        }
+        if hrp != Self::get_hrp(network) {
+            bail!("Could not decode bech32m shared-control lock because of invalid prefix");
+        }
+
+        let payload = Vec::<u8>::from_base32(&data)?;
+        let lock: Self = bincode::deserialize(&payload)?;
+
+        // Reject locks that could not have been created
+        Ok(Self::new(lock.threshold, lock.cosigner_keys)?)
+    }
+}
+
+/// A transaction spending UTXOs under a shared-control lock, passed between
+/// cosigners until enough of them signed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedSpend {
+    pub lock: SharedControlLock,
+    pub kernel: TransactionKernel,
+    pub inputs: Vec<(Utxo, LockScript, MsMembershipProof)>,
+    pub output_utxos: Vec<Utxo>,
+    pub mutator_set_accumulator: MutatorSetAccumulator,
+
+    /// The unlock keys of the cosigners that signed, by cosigner index
+    pub unlock_keys: BTreeMap<usize, SecretDigest>,
+}
+
+impl SharedSpend {
+    /// Create an unsigned spend of UTXOs under `lock`. The outputs must
+    /// include any change.
+    pub fn new(
+        lock: SharedControlLock,
+        inputs: Vec<(Utxo, MsMembershipProof)>,
+        receiver_data: Vec<UtxoReceiverData>,
+        fee: NeptuneCoins,
+        timestamp: Timestamp,
+        mutator_set_accumulator: MutatorSetAccumulator,
+    ) -> Result<Self, SharedControlError> {
+        let lock_script = lock.lock_script();
+        if let Some(foreign_input) = inputs
+            .iter()
+            .position(|(utxo, _)| utxo.lock_script_hash != lock_script.hash())
+        {
+            return Err(SharedControlError::ForeignInput(foreign_input));
+        }
+
+        let input_amount = inputs
+            .iter()
+            .map(|(utxo, _)| utxo.get_native_currency_amount())
+            .sum::<NeptuneCoins>();
+        let output_amount = receiver_data
+            .iter()
+            .map(|rd| rd.utxo.get_native_currency_amount())
+            .sum::<NeptuneCoins>();
+        if input_amount != output_amount + fee {
+            return Err(SharedControlError::Unbalanced {
+                inputs: input_amount,
+                outputs: output_amount,
+                fee,
+            });
+        }
+
+        let inputs = inputs
+            .into_iter()
+            .map(|(utxo, membership_proof)| (utxo, lock_script.clone(), membership_proof))
+            .collect_vec();
+        let kernel = TransactionKernel {
+            inputs: GlobalState::generate_removal_records(&inputs, &mutator_set_accumulator),
+            outputs: GlobalState::generate_addition_records(&receiver_data),
+            public_announcements: receiver_data
+                .iter()
+                .map(|rd| rd.public_announcement.clone())
+                .collect(),
+            fee,
+            timestamp,
+            coinbase: None,
+            mutator_set_hash: mutator_set_accumulator.hash(),
+        };
+
+        Ok(Self {
+            lock,
+            kernel,
+            inputs,
+            output_utxos: receiver_data.into_iter().map(|rd| rd.utxo).collect(),
+            mutator_set_accumulator,
+            unlock_keys: BTreeMap::new(),
+        })
+    }
+
+    /// Add a cosigner's unlock key. Returns the index of the cosigner.
+    pub fn sign(&mut self, unlock_key: SecretDigest) -> Result<usize, SharedControlError> {
+        let index = self
+            .lock
+            .cosigner_index(&unlock_key)
+            .ok_or(SharedControlError::NotACosigner)?;
+        self.unlock_keys.insert(index, unlock_key);
+
+        Ok(index)
+    }
+
+    /// True if enough cosigners signed to create the transaction
+    pub fn is_complete(&self) -> bool {
+        self.unlock_keys.len() >= self.lock.threshold
+    }
+
+    /// The primitive witness of the transaction, unlocking every input with
+    /// the unlock keys of the first `threshold` cosigners that signed
+    pub fn primitive_witness(&self) -> Result<PrimitiveWitness, SharedControlError> {
+        if !self.is_complete() {
+            return Err(SharedControlError::NotEnoughSignatures {
+                signed: self.unlock_keys.len(),
+                threshold: self.lock.threshold,
+            });
+        }
+
+        let unlock_keys = self
+            .unlock_keys
+            .iter()
+            .take(self.lock.threshold)
+            .map(|(index, unlock_key)| (*index, *unlock_key))
+            .collect();
+        let witness = self.lock.witness(&unlock_keys);
+        if !self.lock.verify_unlock(&witness) {
+            return Err(SharedControlError::Failed(
+                "unlock keys do not satisfy the lock".to_string(),
+            ));
+        }
+
+        Ok(
+            GlobalState::generate_primitive_witness_with_lock_script_witnesses(
+                vec![witness; self.inputs.len()],
+                &self.inputs,
+                &self.output_utxos,
+                &self.kernel,
+                self.mutator_set_accumulator.clone(),
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod shared_control_tests {
+    use num_traits::Zero;
+    use triton_vm::prelude::NonDeterminism;
+
+    use crate::models::state::wallet::WalletSecret;
+
+    use super::*;
+
+    #[test]
+    fn threshold_of_cosigners_unlocks() {
+        let unlock_keys = (0..3)
+            .map(|_| WalletSecret::new_random().nth_shared_control_unlock_key(0))
+            .collect_vec();
+        let cosigner_keys = unlock_keys.iter().map(SecretDigest::hash).collect_vec();
+        assert!(SharedControlLock::new(4, cosigner_keys.clone()).is_err());
+        assert!(SharedControlLock::new(2, vec![cosigner_keys[0]; 2]).is_err());
+        let lock = SharedControlLock::new(2, cosigner_keys).unwrap();
+        assert_eq!(
+            lock,
+            SharedControlLock::from_bech32m(
+                &lock.to_bech32m(Network::RegTest).unwrap(),
+                Network::RegTest
+            )
+            .unwrap()
+        );
+
+        let lock_script = lock.lock_script();
+        let halts = |signers: &[usize]| {
+            let keys = signers
+                .iter()
+                .map(|&index| (index, unlock_keys[index]))
+                .collect();
+            let witness = lock.witness(&keys);
+            let public_input = Digest::default().encode();
+            let halts = lock_script
+                .program
+                .run(public_input.into(), NonDeterminism::new(witness.clone()))
+                .is_ok();
+            assert_eq!(halts, lock.verify_unlock(&witness));
+            halts
+        };
+        assert!(halts(&[0, 2]));
+        assert!(halts(&[1, 2]));
+        assert!(!halts(&[1]));
+        assert!(!halts(&[0, 1, 2]));
+
+        let mut spend = SharedSpend::new(
+            lock,
+            vec![],
+            vec![],
+            NeptuneCoins::zero(),
+            Timestamp::now(),
+            MutatorSetAccumulator::default(),
+        )
+        .unwrap();
+        let outsider = WalletSecret::new_random().nth_shared_control_unlock_key(0);
+        assert_eq!(Err(SharedControlError::NotACosigner), spend.sign(outsider));
+        assert_eq!(Ok(2), spend.sign(unlock_keys[2]));
+        assert!(matches!(
+            spend.primitive_witness(),
+            Err(SharedControlError::NotEnoughSignatures { signed: 1, .. })
+        ));
+        assert_eq!(Ok(0), spend.sign(unlock_keys[0]));
+        assert!(spend.primitive_witness().is_ok());
+    }
+}
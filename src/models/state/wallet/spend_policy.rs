@@ -0,0 +1,154 @@
+use anyhow::{Context, Result};
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config_models::cli_args;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+
+use super::address::generation_address::ReceivingAddress;
+
+/// Limits on what the node's wallet may send, set through the CLI. Meant to
+/// protect hot wallets used by services: a compromised RPC client cannot
+/// drain more than the limits allow.
+///
+/// Amounts include the fee.
+#[derive(Clone, Debug, Default)]
+pub struct SpendPolicy {
+    pub max_amount_per_transaction: Option<NeptuneCoins>,
+    pub max_amount_per_day: Option<NeptuneCoins>,
+
+    /// If not empty, only these addresses may be sent to
+    pub allowed_addresses: Vec<ReceivingAddress>,
+}
+
+/// The reason a send was refused by the [`SpendPolicy`]
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum SpendPolicyViolation {
+    #[error("amount {amount} exceeds the limit of {limit} per transaction")]
+    TransactionLimitExceeded {
+        amount: NeptuneCoins,
+        limit: NeptuneCoins,
+    },
+
+    #[error(
+        "amount {amount} exceeds the remaining daily allowance of {remaining} (limit {limit})"
+    )]
+    DailyLimitExceeded {
+        amount: NeptuneCoins,
+        remaining: NeptuneCoins,
+        limit: NeptuneCoins,
+    },
+
+    #[error("address is not on the allowlist")]
+    AddressNotAllowed,
+}
+
+impl SpendPolicy {
+    /// The window over which `max_amount_per_day` applies
+    pub fn day() -> Timestamp {
+        Timestamp::hours(24)
+    }
+
+    pub fn from_cli_args(cli_args: &cli_args::Args) -> Result<Self> {
+        let allowed_addresses = cli_args
+            .send_allowlist
+            .iter()
+            .map(|encoded| {
+                ReceivingAddress::from_bech32m(encoded.to_owned(), cli_args.network)
+                    .with_context(|| format!("Invalid address on send allowlist: {encoded}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            max_amount_per_transaction: cli_args.max_send_amount,
+            max_amount_per_day: cli_args.max_daily_send_amount,
+            allowed_addresses,
+        })
+    }
+
+    /// Check a send of `amount` to `address`, given the amount sent during the
+    /// last [`Self::day`].
+    pub fn check(
+        &self,
+        amount: NeptuneCoins,
+        address: &ReceivingAddress,
+        sent_during_last_day: NeptuneCoins,
+    ) -> Result<(), SpendPolicyViolation> {
+        if !self.allowed_addresses.is_empty() && !self.allowed_addresses.contains(address) {
+            return Err(SpendPolicyViolation::AddressNotAllowed);
+        }
+
+        if let Some(limit) = self.max_amount_per_transaction {
+            if amount > limit {
+                return Err(SpendPolicyViolation::TransactionLimitExceeded { amount, limit });
+            }
+        }
+
+        if let Some(limit) = self.max_amount_per_day {
+            let remaining = if sent_during_last_day < limit {
+                limit - sent_during_last_day
+            } else {
+                NeptuneCoins::zero()
+            };
+            if amount > remaining {
+                return Err(SpendPolicyViolation::DailyLimitExceeded {
+                    amount,
+                    remaining,
+                    limit,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod spend_policy_tests {
+    use crate::models::state::wallet::WalletSecret;
+
+    use super::*;
+
+    #[test]
+    fn limits_and_allowlist_are_enforced() {
+        let allowed = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let other = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let coins = NeptuneCoins::new;
+
+        let unrestricted = SpendPolicy::default();
+        assert!(unrestricted.check(coins(1000), &other, coins(1000)).is_ok());
+
+        let policy = SpendPolicy {
+            max_amount_per_transaction: Some(coins(10)),
+            max_amount_per_day: Some(coins(25)),
+            allowed_addresses: vec![allowed],
+        };
+        assert!(policy.check(coins(10), &allowed, coins(15)).is_ok());
+        assert_eq!(
+            Err(SpendPolicyViolation::AddressNotAllowed),
+            policy.check(coins(1), &other, coins(0))
+        );
+        assert_eq!(
+            Err(SpendPolicyViolation::TransactionLimitExceeded {
+                amount: coins(11),
+                limit: coins(10)
+            }),
+            policy.check(coins(11), &allowed, coins(0))
+        );
+        assert_eq!(
+            Err(SpendPolicyViolation::DailyLimitExceeded {
+                amount: coins(10),
+                remaining: coins(5),
+                limit: coins(25)
+            }),
+            policy.check(coins(10), &allowed, coins(20))
+        );
+        assert!(policy.check(coins(1), &allowed, coins(30)).is_err());
+    }
+}
@@ -115,7 +115,7 @@ impl UtxoNotificationPool {
     }
 
     /// Delete an expected UTXO from this data model
-    fn drop_expected_utxo(&mut self, addition_record: AdditionRecord) {
+    pub fn drop_expected_utxo(&mut self, addition_record: AdditionRecord) {
         let maybe_removed = self.notifications.remove(&addition_record);
 
         if let Some(removed_exp_utxo) = maybe_removed {
@@ -152,6 +152,11 @@ impl UtxoNotificationPool {
         }
     }
 
+    /// The size above which the least credible notifications are evicted, in bytes
+    pub fn max_total_size(&self) -> usize {
+        self.max_total_size
+    }
+
     pub fn len(&self) -> usize {
         debug_assert_eq!(
             self.notifications.len(),
@@ -187,6 +192,14 @@ impl UtxoNotificationPool {
         received_expected_utxos
     }
 
+    /// Return who notified us of the expected UTXO with the given addition
+    /// record, if it is expected
+    pub fn notifier(&self, addition_record: &AdditionRecord) -> Option<&UtxoNotifier> {
+        self.notifications
+            .get(addition_record)
+            .map(|expected_utxo| &expected_utxo.received_from)
+    }
+
     /// Return all expected UTXOs
     pub fn get_all_expected_utxos(&self) -> Vec<ExpectedUtxo> {
         self.notifications.values().cloned().collect_vec()
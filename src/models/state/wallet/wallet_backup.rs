@@ -0,0 +1,208 @@
+//! Rotating, encrypted backups of the wallet database.
+//!
+//! After every so many changes to the wallet database, such as received UTXOs
+//! and generated keys, its contents are written to a timestamped file in the
+//! backup directory, and all but the most recent backups are deleted.
+//!
+//! Backups are encrypted with AES-256-GCM under a key derived from the wallet
+//! secret, so they can be kept on untrusted storage, and reading one requires
+//! the wallet secret or its seed phrase.
+
+use std::path::{Path, PathBuf};
+
+use aead::Aead;
+use aead::KeyInit;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::Nonce;
+use anyhow::{bail, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::rusty_wallet_database::WalletDatabaseContents;
+use crate::models::consensus::timestamp::Timestamp;
+
+pub const WALLET_BACKUP_DIRECTORY: &str = "backups";
+
+const WALLET_BACKUP_FILE_PREFIX: &str = "wallet-backup-";
+const WALLET_BACKUP_FILE_EXTENSION: &str = "dat";
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, Clone)]
+pub struct WalletBackups {
+    directory: PathBuf,
+
+    /// Number of changes to the wallet database between backups
+    interval: u64,
+
+    /// Number of most recent backups kept
+    retained: usize,
+
+    changes_since_backup: u64,
+    last_backup: Option<Timestamp>,
+}
+
+impl WalletBackups {
+    pub fn new(directory: PathBuf, interval: u64, retained: usize) -> Self {
+        let last_backup = Self::list(&directory).last().map(|(created, _)| *created);
+
+        Self {
+            directory,
+            interval,
+            retained,
+            changes_since_backup: 0,
+            last_backup,
+        }
+    }
+
+    /// When the most recent backup was written, if any was
+    pub fn last_backup(&self) -> Option<Timestamp> {
+        self.last_backup
+    }
+
+    /// Count changes to the wallet database. Returns true if a backup is due.
+    pub fn record_changes(&mut self, count: u64) -> bool {
+        self.changes_since_backup += count;
+        self.changes_since_backup >= self.interval
+    }
+
+    /// Write an encrypted backup, and delete all but the most recent backups.
+    /// Returns the path of the new backup.
+    pub async fn write(
+        &mut self,
+        contents: &WalletDatabaseContents,
+        key: &[u8; 32],
+        now: Timestamp,
+    ) -> Result<PathBuf> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let path = self.directory.join(format!(
+            "{WALLET_BACKUP_FILE_PREFIX}{:013}.{WALLET_BACKUP_FILE_EXTENSION}",
+            now.0.value()
+        ));
+        tokio::fs::write(&path, Self::encrypt(contents, key)?).await?;
+        self.changes_since_backup = 0;
+        self.last_backup = Some(now);
+
+        let backups = Self::list(&self.directory);
+        let surplus = backups.len().saturating_sub(self.retained);
+        for (_, old_path) in backups.into_iter().take(surplus) {
+            tokio::fs::remove_file(old_path).await?;
+        }
+
+        Ok(path)
+    }
+
+    /// Read the backup in the given file
+    pub async fn read(path: &Path, key: &[u8; 32]) -> Result<WalletDatabaseContents> {
+        Self::decrypt(&tokio::fs::read(path).await?, key)
+    }
+
+    /// The backups in the directory and when they were written, oldest first
+    fn list(directory: &Path) -> Vec<(Timestamp, PathBuf)> {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return vec![];
+        };
+
+        let mut backups = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let millis = path
+                    .file_name()?
+                    .to_str()?
+                    .strip_prefix(WALLET_BACKUP_FILE_PREFIX)?
+                    .strip_suffix(&format!(".{WALLET_BACKUP_FILE_EXTENSION}"))?
+                    .parse::<u64>()
+                    .ok()?;
+                Some((Timestamp::millis(millis), path))
+            })
+            .collect::<Vec<_>>();
+        backups.sort_by_key(|(created, _)| created.0.value());
+
+        backups
+    }
+
+    /// Encrypt the encoding of `value` with AES-256-GCM under `key`, with the
+    /// random nonce prepended
+    pub(super) fn encrypt<T: Serialize>(value: &T, key: &[u8; 32]) -> Result<Vec<u8>> {
+        let plaintext = bincode::serialize(value)?;
+        let nonce_bytes: [u8; NONCE_LENGTH] = rand::random();
+        let cipher = Aes256Gcm::new(&(*key).into());
+        let ciphertext = match cipher.encrypt(Nonce::from_slice(&nonce_bytes), plaintext.as_ref()) {
+            Ok(ciphertext) => ciphertext,
+            Err(_) => bail!("Could not encrypt wallet data."),
+        };
+
+        Ok([nonce_bytes.to_vec(), ciphertext].concat())
+    }
+
+    pub(super) fn decrypt<T: DeserializeOwned>(bytes: &[u8], key: &[u8; 32]) -> Result<T> {
+        if bytes.len() <= NONCE_LENGTH {
+            bail!("Encrypted wallet data is truncated.");
+        }
+        let (nonce_bytes, ciphertext) = bytes.split_at(NONCE_LENGTH);
+        let cipher = Aes256Gcm::new(&(*key).into());
+        let plaintext = match cipher.decrypt(Nonce::from_slice(nonce_bytes), ciphertext) {
+            Ok(plaintext) => plaintext,
+            Err(_) => bail!("Could not decrypt wallet data. Was it made by another wallet?"),
+        };
+
+        Ok(bincode::deserialize(&plaintext)?)
+    }
+}
+
+#[cfg(test)]
+mod wallet_backup_tests {
+    use crate::config_models::network::Network;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::{mock_genesis_wallet_state, unit_test_data_directory};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn backups_are_encrypted_and_rotated() {
+        let network = Network::RegTest;
+        let wallet_secret = WalletSecret::new_random();
+        let wallet_state = mock_genesis_wallet_state(wallet_secret.clone(), network).await;
+        let contents = wallet_state.wallet_db.contents().await;
+        let key = wallet_secret.backup_encryption_key();
+
+        let directory = unit_test_data_directory(network)
+            .unwrap()
+            .wallet_backup_dir_path();
+        let mut backups = WalletBackups::new(directory.clone(), 3, 2);
+        assert!(backups.last_backup().is_none());
+        assert!(!backups.record_changes(2));
+        assert!(backups.record_changes(1));
+
+        let mut paths = vec![];
+        for minutes in 1..=3 {
+            let now = Timestamp::minutes(minutes);
+            paths.push(backups.write(&contents, &key, now).await.unwrap());
+            assert_eq!(Some(now), backups.last_backup());
+        }
+        assert!(!backups.record_changes(1), "Writing resets the count");
+
+        // Only the two most recent backups are kept
+        assert!(!paths[0].exists());
+        assert_eq!(
+            paths[1..].to_vec(),
+            WalletBackups::list(&directory)
+                .into_iter()
+                .map(|(_, path)| path)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(Timestamp::minutes(3)),
+            WalletBackups::new(directory, 3, 2).last_backup()
+        );
+
+        let restored = WalletBackups::read(&paths[2], &key).await.unwrap();
+        assert_eq!(contents.sync_label, restored.sync_label);
+        assert_eq!(
+            contents.monitored_utxos.len(),
+            restored.monitored_utxos.len()
+        );
+
+        let other_key = WalletSecret::new_random().backup_encryption_key();
+        assert!(WalletBackups::read(&paths[2], &other_key).await.is_err());
+    }
+}
@@ -3,15 +3,17 @@ use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
 use crate::models::consensus::tasm::program::ConsensusProgram;
 use crate::models::consensus::timestamp::Timestamp;
 use crate::prelude::twenty_first;
+use crate::util_types::digest_ext::DigestExt;
 
 use crate::database::storage::storage_schema::traits::*;
+use crate::database::storage::storage_schema::{RustyKey, RustyValue};
 use crate::database::storage::storage_vec::traits::*;
 use crate::database::NeptuneLevelDb;
 use anyhow::{bail, Result};
 use itertools::Itertools;
 use num_traits::Zero;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Debug;
 use std::path::PathBuf;
@@ -23,8 +25,10 @@ use twenty_first::math::digest::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
 use super::coin_with_possible_timelock::CoinWithPossibleTimeLock;
+use super::historical_index::BlockDelta;
 use super::rusty_wallet_database::RustyWalletDatabase;
 use super::utxo_notification_pool::{UtxoNotificationPool, UtxoNotifier};
+use super::wallet_backup::WalletBackups;
 use super::wallet_status::{WalletStatus, WalletStatusElement};
 use super::{WalletSecret, WALLET_INCOMING_SECRETS_FILE_NAME};
 use crate::config_models::cli_args::Args;
@@ -32,8 +36,13 @@ use crate::config_models::data_directory::DataDirectory;
 use crate::models::blockchain::block::Block;
 use crate::models::blockchain::transaction::utxo::{LockScript, Utxo};
 use crate::models::blockchain::transaction::Transaction;
-use crate::models::state::wallet::monitored_utxo::MonitoredUtxo;
+use crate::models::state::wallet::monitored_utxo::{
+    MonitoredUtxo, MonitoredUtxoReport, UtxoOrigin,
+};
+use crate::models::state::wallet::prepared_send::PreparedSends;
+use crate::models::state::wallet::sent_transactions::SentTransactions;
 use crate::util_types::mutator_set::addition_record::AdditionRecord;
+use crate::util_types::mutator_set::commit;
 use crate::util_types::mutator_set::ms_membership_proof::MsMembershipProof;
 use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
 use crate::util_types::mutator_set::removal_record::{AbsoluteIndexSet, RemovalRecord};
@@ -47,6 +56,18 @@ pub struct WalletState {
     // Any thread may read from expected_utxos, only main thread may write
     pub expected_utxos: UtxoNotificationPool,
 
+    /// Sends prepared through the `prepare_send` RPC, awaiting confirmation
+    pub prepared_sends: PreparedSends,
+
+    /// Transactions sent by this wallet, watched for double spends
+    pub sent_transactions: SentTransactions,
+
+    /// Whether to record how each block changes the wallet's UTXOs
+    pub historical_index: bool,
+
+    /// Rotating backups of the wallet database, unless disabled
+    pub backups: Option<WalletBackups>,
+
     /// Path to directory containing wallet files
     wallet_directory_path: PathBuf,
 }
@@ -82,6 +103,7 @@ impl Debug for WalletState {
             .field("wallet_secret", &self.wallet_secret)
             .field("number_of_mps_per_utxo", &self.number_of_mps_per_utxo)
             .field("expected_utxos", &self.expected_utxos)
+            .field("historical_index", &self.historical_index)
             .field("wallet_directory_path", &self.wallet_directory_path)
             .finish()
     }
@@ -97,7 +119,7 @@ impl WalletState {
     /// the wallet database is deleted.
     ///
     /// Uses non-blocking I/O via tokio.
-    async fn store_utxo_ms_recovery_data(
+    pub(crate) async fn store_utxo_ms_recovery_data(
         &self,
         utxo_ms_recovery_data: IncomingUtxoRecoveryData,
     ) -> Result<()> {
@@ -154,6 +176,31 @@ impl WalletState {
         Ok(ret)
     }
 
+    /// Create a wallet state with a random secret, for nodes running without a
+    /// wallet. Nothing is read from or written to the wallet directory: the
+    /// wallet database lives in a temporary directory that is removed when the
+    /// wallet state is dropped.
+    pub async fn new_ephemeral(cli_args: &Args) -> Self {
+        let wallet_db = NeptuneLevelDb::open_new_test_database(
+            true,
+            Some(crate::database::create_db_if_missing()),
+            None,
+            None,
+        )
+        .await
+        .expect("temporary wallet database must open");
+        let wallet_directory_path = wallet_db.path().clone();
+
+        Self::from_database(
+            wallet_db,
+            WalletSecret::new_random(),
+            wallet_directory_path,
+            None,
+            cli_args,
+        )
+        .await
+    }
+
     pub async fn new_from_wallet_secret(
         data_dir: &DataDirectory,
         wallet_secret: WalletSecret,
@@ -177,6 +224,31 @@ impl WalletState {
             }
         };
 
+        let backup_dir = (cli_args.wallet_backup_interval > 0).then(|| {
+            cli_args
+                .wallet_backup_dir
+                .clone()
+                .unwrap_or_else(|| data_dir.wallet_backup_dir_path())
+        });
+        Self::from_database(
+            wallet_db,
+            wallet_secret,
+            data_dir.wallet_directory_path(),
+            backup_dir,
+            cli_args,
+        )
+        .await
+    }
+
+    /// Create a wallet state on an opened wallet database. Backups are written
+    /// to `backup_dir`, if given.
+    async fn from_database(
+        wallet_db: NeptuneLevelDb<RustyKey, RustyValue>,
+        wallet_secret: WalletSecret,
+        wallet_directory_path: PathBuf,
+        backup_dir: Option<PathBuf>,
+        cli_args: &Args,
+    ) -> Self {
         let rusty_wallet_database = RustyWalletDatabase::connect(wallet_db).await;
         let sync_label = rusty_wallet_database.get_sync_label().await;
 
@@ -188,7 +260,17 @@ impl WalletState {
                 cli_args.max_utxo_notification_size,
                 cli_args.max_unconfirmed_utxo_notification_count_per_peer,
             ),
-            wallet_directory_path: data_dir.wallet_directory_path(),
+            prepared_sends: PreparedSends::default(),
+            sent_transactions: SentTransactions::default(),
+            historical_index: cli_args.historical_index,
+            backups: backup_dir.map(|backup_dir| {
+                WalletBackups::new(
+                    backup_dir,
+                    cli_args.wallet_backup_interval,
+                    cli_args.wallet_backup_count,
+                )
+            }),
+            wallet_directory_path,
         };
 
         // Wallet state has to be initialized with the genesis block, otherwise the outputs
@@ -196,7 +278,7 @@ impl WalletState {
         // This also ensures that any premine outputs are added to the file containing the
         // incoming randomness such that a wallet-DB recovery will include genesis block
         // outputs.
-        if sync_label == Digest::default() {
+        if sync_label.is_zero() {
             // Check if we are premine recipients
             let own_spending_key = wallet_state.wallet_secret.nth_generation_spending_key(0);
             let own_receiving_address = own_spending_key.to_address();
@@ -206,8 +288,8 @@ impl WalletState {
                         .expected_utxos
                         .add_expected_utxo(
                             utxo,
-                            Digest::default(),
-                            own_spending_key.privacy_preimage,
+                            Digest::ZERO,
+                            own_spending_key.privacy_preimage.expose(),
                             UtxoNotifier::Premine,
                         )
                         .unwrap();
@@ -286,6 +368,74 @@ impl WalletState {
             .collect_vec()
     }
 
+    /// How `new_block` changes the wallet's UTXOs, given the own UTXOs it
+    /// spends and those it confirms
+    async fn block_delta(
+        &self,
+        new_block: &Block,
+        spent_inputs: &[(Utxo, AbsoluteIndexSet, u64)],
+        received_outputs: &HashMap<AdditionRecord, (Utxo, Digest, Digest)>,
+    ) -> Result<BlockDelta> {
+        let monitored_utxos = self.wallet_db.monitored_utxos();
+        let mut spent = vec![];
+        for (utxo, _abs_i, mutxo_list_index) in spent_inputs.iter() {
+            // Spent inputs are found through their latest membership proof
+            let Some((_, ms_mp)) = monitored_utxos
+                .get(*mutxo_list_index)
+                .await
+                .get_latest_membership_proof_entry()
+            else {
+                bail!("Spent monitored UTXO {mutxo_list_index} has no membership proof");
+            };
+            let addition_record = commit(
+                Hash::hash(utxo),
+                ms_mp.sender_randomness,
+                ms_mp.receiver_preimage.hash::<Hash>(),
+            );
+            spent.push((addition_record, utxo.get_native_currency_amount()));
+        }
+
+        let received = received_outputs
+            .iter()
+            .map(|(addition_record, (utxo, _, _))| {
+                (*addition_record, utxo.get_native_currency_amount())
+            })
+            .collect_vec();
+
+        Ok(BlockDelta {
+            block_digest: new_block.hash(),
+            prev_block_digest: new_block.kernel.header.prev_block_digest,
+            height: new_block.kernel.header.height,
+            received,
+            spent,
+        })
+    }
+
+    /// Tell where a UTXO received in a block came from, given all the block's
+    /// outputs that belong to us. Outputs of our own transactions are change,
+    /// unless the transaction paid only ourselves. Other outputs are told apart
+    /// by who notified us of them, and are incoming payments if nobody did.
+    fn classify_received_utxo(
+        &self,
+        addition_record: &AdditionRecord,
+        own_outputs: &HashSet<AdditionRecord>,
+    ) -> UtxoOrigin {
+        match self
+            .sent_transactions
+            .pays_only_own_outputs(addition_record, own_outputs)
+        {
+            Some(true) => return UtxoOrigin::Consolidation,
+            Some(false) => return UtxoOrigin::Change,
+            None => (),
+        }
+
+        match self.expected_utxos.notifier(addition_record) {
+            Some(UtxoNotifier::OwnMiner | UtxoNotifier::Premine) => UtxoOrigin::Coinbase,
+            Some(UtxoNotifier::Myself) => UtxoOrigin::Change,
+            _ => UtxoOrigin::IncomingPayment,
+        }
+    }
+
     /// Update wallet state with new block. Assume the given block
     /// is valid and that the wallet state is not up to date yet.
     pub async fn update_wallet_state_with_new_block(
@@ -316,12 +466,32 @@ impl WalletState {
                 .map(|(ar, utxo, send_rand, rec_premi)| (ar, (utxo, send_rand, rec_premi)))
                 .collect();
 
+        let own_outputs: HashSet<AdditionRecord> =
+            addition_record_to_utxo_info.keys().copied().collect();
+        let origins: HashMap<AdditionRecord, UtxoOrigin> = own_outputs
+            .iter()
+            .map(|addition_record| {
+                (
+                    *addition_record,
+                    self.classify_received_utxo(addition_record, &own_outputs),
+                )
+            })
+            .collect();
+
+        if self.historical_index {
+            let block_delta = self
+                .block_delta(new_block, &spent_inputs, &addition_record_to_utxo_info)
+                .await?;
+            self.wallet_db.record_block_delta(block_delta).await;
+        }
+
         // Derive the membership proofs for received UTXOs, and in
         // the process update existing membership proofs with
         // updates from this block
 
         let monitored_utxos = self.wallet_db.monitored_utxos_mut();
         let mut incoming_utxo_recovery_data_list = vec![];
+        let mut added_mutxo_indices = vec![];
 
         // return early if there are no monitored utxos and this
         // block does not affect our balance
@@ -329,6 +499,8 @@ impl WalletState {
             && addition_record_to_utxo_info.is_empty()
             && monitored_utxos.is_empty().await
         {
+            // persist the block delta, if one was recorded
+            self.wallet_db.persist().await;
             return Ok(());
         }
 
@@ -366,6 +538,8 @@ impl WalletState {
                         // TODO: If MUTXO was spent, maybe we also don't want to maintain it?
                         if monitored_utxo.abandoned_at.is_some() {
                             debug!("Monitored UTXO with digest {utxo_digest} was marked as abandoned. Skipping.");
+                        } else if monitored_utxo.unrecoverable_at.is_some() {
+                            debug!("Monitored UTXO with digest {utxo_digest} was marked as unrecoverable. Skipping.");
                         } else {
                             let confirmed_in_block_info = match monitored_utxo.confirmed_in_block {
                                 Some(mutxo_received_in_block) => format!(
@@ -472,7 +646,9 @@ impl WalletState {
                     new_block.kernel.header.timestamp,
                     new_block.kernel.header.height,
                 ));
+                mutxo.origin = origins[addition_record];
                 monitored_utxos.push(mutxo).await;
+                added_mutxo_indices.push(mutxos_len);
             }
 
             // Update mutator set to bring it to the correct state for the next call to batch-update
@@ -508,6 +684,7 @@ impl WalletState {
             new_block.kernel.body.transaction.kernel.inputs.len()
         );
         let mut block_tx_input_count: usize = 0;
+        let mut spent_mutxo_indices = vec![];
         while let Some(removal_record) = removal_records.pop() {
             let res = MsMembershipProof::batch_update_from_remove(
                 &mut valid_membership_proofs_and_own_utxo_count
@@ -539,6 +716,7 @@ impl WalletState {
                         block_tx_input_count
                     );
 
+                    spent_mutxo_indices.push(*mutxo_list_index);
                     let mut spent_mutxo = monitored_utxos.get(*mutxo_list_index).await;
                     spent_mutxo.spent_in_block = Some((
                         new_block.hash(),
@@ -604,8 +782,21 @@ impl WalletState {
             self.store_utxo_ms_recovery_data(item).await?;
         }
 
+        // The updated membership proofs are implied by the new tip
+        let changed_mutxo_indices = added_mutxo_indices
+            .into_iter()
+            .chain(spent_mutxo_indices)
+            .sorted()
+            .dedup()
+            .collect_vec();
+        self.wallet_db
+            .record_wallet_change(new_block.hash(), changed_mutxo_indices)
+            .await;
+
         self.wallet_db.set_sync_label(new_block.hash()).await;
         self.wallet_db.persist().await;
+        self.record_wallet_changes((spent_inputs.len() + origins.len()) as u64)
+            .await;
 
         // Mark all expected UTXOs that were received in this block as received
         expected_utxos_in_this_block
@@ -619,6 +810,25 @@ impl WalletState {
         Ok(())
     }
 
+    /// Count changes to the wallet database, and back it up if enough of them
+    /// accumulated since the last backup. Failure to back up is logged rather
+    /// than returned, as it must not stop the wallet from working.
+    pub async fn record_wallet_changes(&mut self, count: u64) {
+        let Some(backups) = self.backups.as_mut() else {
+            return;
+        };
+        if !backups.record_changes(count) {
+            return;
+        }
+
+        let contents = self.wallet_db.contents().await;
+        let key = self.wallet_secret.backup_encryption_key();
+        match backups.write(&contents, &key, Timestamp::now()).await {
+            Ok(path) => info!("Wrote wallet backup {}", path.display()),
+            Err(err) => warn!("Could not write wallet backup: {err}"),
+        }
+    }
+
     pub async fn is_synced_to(&self, tip_hash: Digest) -> bool {
         let db_sync_digest = self.wallet_db.get_sync_label().await;
         if db_sync_digest != tip_hash {
@@ -632,8 +842,15 @@ impl WalletState {
         let stream = monitored_utxos.stream_values().await;
         pin_mut!(stream); // needed for iteration
 
+        // MUTXOs whose membership proof is unrecoverable are not waited for,
+        // as no amount of resyncing will fix them.
         stream
-            .all(|m| futures::future::ready(m.get_membership_proof_for_block(tip_hash).is_some()))
+            .all(|m| {
+                futures::future::ready(
+                    m.unrecoverable_at.is_some()
+                        || m.get_membership_proof_for_block(tip_hash).is_some(),
+                )
+            })
             .await
     }
 
@@ -715,9 +932,16 @@ impl WalletState {
             .nth_generation_spending_key(0)
             .to_address()
             .lock_script();
+        // Time-locked UTXOs cannot be spent yet
+        let mut spendable = wallet_status
+            .synced_unspent
+            .iter()
+            .filter(|(wse, _msmp)| wse.utxo.can_spend_at(timestamp));
         while allocated_amount < requested_amount {
-            let (wallet_status_element, membership_proof) =
-                wallet_status.synced_unspent[ret.len()].clone();
+            let (wallet_status_element, membership_proof) = spendable
+                .next()
+                .cloned()
+                .expect("available amount covers the requested amount");
             allocated_amount =
                 allocated_amount + wallet_status_element.utxo.get_native_currency_amount();
             ret.push((
@@ -766,6 +990,85 @@ impl WalletState {
         }
         own_coins
     }
+
+    /// Return all monitored UTXOs with the state of their membership proofs
+    /// relative to the given block.
+    pub async fn list_utxos(&self, tip_digest: Digest) -> Vec<MonitoredUtxoReport> {
+        let monitored_utxos = self.wallet_db.monitored_utxos();
+        let mut reports = vec![];
+
+        let stream = monitored_utxos.stream_values().await;
+        pin_mut!(stream); // needed for iteration
+
+        while let Some(mutxo) = stream.next().await {
+            reports.push(MonitoredUtxoReport {
+                utxo_digest: Hash::hash(&mutxo.utxo),
+                amount: mutxo.utxo.get_native_currency_amount(),
+                aocl_leaf_index: mutxo
+                    .get_latest_membership_proof_entry()
+                    .map(|(_, mp)| mp.auth_path_aocl.leaf_index),
+                confirmed_at_height: mutxo.confirmed_in_block.map(|(_, _, height)| height),
+                spent: mutxo.spent_in_block.is_some(),
+                abandoned: mutxo.abandoned_at.is_some(),
+                state: mutxo.state(tip_digest),
+            });
+        }
+        reports
+    }
+
+    /// Return true if the wallet monitors the UTXO with the given addition
+    /// record, whether spent or not.
+    pub async fn monitors_addition_record(&self, addition_record: &AdditionRecord) -> bool {
+        let stream = self.wallet_db.monitored_utxos().stream_values().await;
+        pin_mut!(stream); // needed for iteration
+
+        while let Some(mutxo) = stream.next().await {
+            let Some((_, ms_mp)) = mutxo.get_latest_membership_proof_entry() else {
+                continue;
+            };
+            let own_addition_record = commit(
+                Hash::hash(&mutxo.utxo),
+                ms_mp.sender_randomness,
+                ms_mp.receiver_preimage.hash::<Hash>(),
+            );
+            if own_addition_record == *addition_record {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Return the monitored UTXOs that were confirmed in the block with the
+    /// given digest, which must be the tip the wallet is synced to. Only the
+    /// monitored UTXOs that the wallet changes recorded for that block list
+    /// are read, so the cost does not grow with the size of the wallet.
+    pub async fn get_utxos_confirmed_in_block(&self, block_digest: Digest) -> Vec<Utxo> {
+        let mut changed = BTreeSet::new();
+        let mut sequence = self.wallet_db.wallet_change_count().await;
+        while sequence > 0 {
+            sequence -= 1;
+            let wallet_change = self.wallet_db.wallet_change(sequence).await;
+            if wallet_change.tip_digest != block_digest {
+                break;
+            }
+            changed.extend(wallet_change.changed);
+        }
+
+        let monitored_utxos = self.wallet_db.monitored_utxos();
+        let mut utxos = vec![];
+        for index in changed {
+            let mutxo = monitored_utxos.get(index).await;
+            if mutxo
+                .confirmed_in_block
+                .is_some_and(|(digest, _, _)| digest == block_digest)
+            {
+                utxos.push(mutxo.utxo);
+            }
+        }
+
+        utxos
+    }
 }
 
 #[cfg(test)]
@@ -879,7 +1182,7 @@ mod tests {
                 ExpectedUtxo::new(
                     block_3a_coinbase_utxo,
                     block_3a_coinbase_sender_randomness,
-                    own_spending_key.privacy_preimage,
+                    own_spending_key.privacy_preimage.expose(),
                     UtxoNotifier::OwnMiner,
                 ),
             )
@@ -1062,4 +1365,67 @@ mod tests {
                 .verify(Hash::hash(&utxo), &ms_membership_proof));
         }
     }
+
+    #[tokio::test]
+    async fn received_utxos_are_classified_by_origin() {
+        let mut rng = thread_rng();
+        let network = Network::RegTest;
+        let own_wallet_secret = WalletSecret::new_random();
+        let own_spending_key = own_wallet_secret.nth_generation_spending_key(0);
+        let own_address = own_spending_key.to_address();
+        let own_global_state_lock = mock_genesis_global_state(network, 0, own_wallet_secret).await;
+        let mut own_global_state = own_global_state_lock.lock_guard_mut().await;
+        let genesis_block = Block::genesis_block(network);
+
+        // A block mined by us
+        let (block_1, coinbase_utxo_1, sender_randomness_1) =
+            make_mock_block(&genesis_block, None, own_address, rng.gen());
+        own_global_state
+            .set_new_self_mined_tip(
+                block_1.clone(),
+                ExpectedUtxo::new(
+                    coinbase_utxo_1,
+                    sender_randomness_1,
+                    own_spending_key.privacy_preimage.expose(),
+                    UtxoNotifier::OwnMiner,
+                ),
+            )
+            .await
+            .unwrap();
+
+        // A block paying us, as notified through the CLI
+        let (block_2, coinbase_utxo_2, sender_randomness_2) =
+            make_mock_block(&block_1, None, own_address, rng.gen());
+        own_global_state
+            .wallet_state
+            .expected_utxos
+            .add_expected_utxo(
+                coinbase_utxo_2,
+                sender_randomness_2,
+                own_spending_key.privacy_preimage.expose(),
+                UtxoNotifier::Cli,
+            )
+            .unwrap();
+        own_global_state.set_new_tip(block_2.clone()).await.unwrap();
+
+        let history = own_global_state.get_transaction_history(None).await;
+        assert_eq!(2, history.len());
+
+        let coinbase_history = own_global_state
+            .get_transaction_history(Some(UtxoOrigin::Coinbase))
+            .await;
+        assert_eq!(1, coinbase_history.len());
+        assert_eq!(block_1.hash(), coinbase_history[0].block_digest);
+
+        let payment_history = own_global_state
+            .get_transaction_history(Some(UtxoOrigin::IncomingPayment))
+            .await;
+        assert_eq!(1, payment_history.len());
+        assert_eq!(block_2.hash(), payment_history[0].block_digest);
+
+        assert!(own_global_state
+            .get_transaction_history(Some(UtxoOrigin::Change))
+            .await
+            .is_empty());
+    }
 }
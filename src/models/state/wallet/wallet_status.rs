@@ -30,6 +30,18 @@ impl Display for WalletStatusElement {
     }
 }
 
+/// The spendable balance of the wallet as of the tip, and as it will be once
+/// the transactions in the mempool are mined
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WalletBalance {
+    /// Sum of the synced, unspent UTXOs that can be spent now
+    pub confirmed: NeptuneCoins,
+
+    /// The confirmed balance, minus the UTXOs that mempool transactions
+    /// spend, plus the expected UTXOs that they create
+    pub unconfirmed: NeptuneCoins,
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct WalletStatus {
     pub synced_unspent: Vec<(WalletStatusElement, MsMembershipProof)>,
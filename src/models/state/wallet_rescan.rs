@@ -0,0 +1,450 @@
+//! Rescans of the chain for UTXOs announced to the wallet, e.g. after it was
+//! restored from its seed phrase.
+//!
+//! A rescan is a pipeline of three stages:
+//!  1. one task reads the canonical blocks from disk, a batch at a time, so
+//!     that the global state lock is not held for the whole rescan;
+//!  2. a pool of workers scans the transactions of the blocks for UTXOs
+//!     announced to the wallet, which takes a decryption attempt per
+//!     announcement;
+//!  3. a final stage takes the blocks in order of height, restores the
+//!     membership proofs of the UTXOs found from the archival mutator set,
+//!     and marks them as spent when a later block spends them.
+//!
+//! UTXOs already monitored are skipped, so a rescan can be repeated. UTXOs
+//! that were only ever notified off-chain cannot be found by a rescan.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Result};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tasm_lib::twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use super::wallet::address::generation_address::{AnnouncedUtxo, SpendingKey};
+use super::wallet::monitored_utxo::MonitoredUtxo;
+use super::wallet::wallet_state::IncomingUtxoRecoveryData;
+use super::wallet::WalletDisabled;
+use super::GlobalStateLock;
+use crate::database::storage::storage_vec::traits::*;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::block::Block;
+use crate::models::blockchain::shared::Hash;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::util_types::mutator_set::shared::NUM_TRIALS;
+
+/// Number of blocks read from disk per acquisition of the global state lock
+const RESCAN_BATCH_SIZE: u64 = 100;
+
+/// Number of blocks read ahead of the scanning workers
+const RESCAN_READ_AHEAD: usize = 2 * RESCAN_BATCH_SIZE as usize;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RescanStatus {
+    Running,
+    Finished,
+    Cancelled,
+    Failed(String),
+}
+
+/// The progress of the running, or the last, rescan
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RescanProgress {
+    pub from_height: BlockHeight,
+
+    /// The tip when the rescan last read blocks. The rescan follows the tip
+    /// until it catches up.
+    pub tip_height: BlockHeight,
+
+    /// The last block that went through all stages
+    pub scanned_height: Option<BlockHeight>,
+    pub found_utxos: usize,
+    pub started_at: Timestamp,
+    pub status: RescanStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error, Serialize, Deserialize)]
+pub enum RescanError {
+    #[error("a rescan is already running")]
+    AlreadyRunning,
+
+    #[error("no rescan is running")]
+    NotRunning,
+
+    #[error("rescans need the blocks of an archival node")]
+    NotArchival,
+
+    #[error(transparent)]
+    WalletDisabled(#[from] WalletDisabled),
+}
+
+/// The running, or the last, rescan. Cloning gives a handle to the same
+/// rescan, so that its task can report progress without locking the global
+/// state.
+#[derive(Debug, Clone, Default)]
+pub struct WalletRescan {
+    progress: Arc<Mutex<Option<RescanProgress>>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl WalletRescan {
+    pub fn progress(&self) -> Option<RescanProgress> {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(
+            self.progress(),
+            Some(RescanProgress {
+                status: RescanStatus::Running,
+                ..
+            })
+        )
+    }
+
+    /// Ask the running rescan to stop. It stops before the next block, and
+    /// keeps the UTXOs found so far.
+    pub fn cancel(&self) -> Result<(), RescanError> {
+        if !self.is_running() {
+            return Err(RescanError::NotRunning);
+        }
+        self.cancelled.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn start(&self, from_height: BlockHeight, tip_height: BlockHeight) -> Result<(), RescanError> {
+        let mut progress = self.progress.lock().unwrap();
+        if matches!(
+            *progress,
+            Some(RescanProgress {
+                status: RescanStatus::Running,
+                ..
+            })
+        ) {
+            return Err(RescanError::AlreadyRunning);
+        }
+
+        self.cancelled.store(false, Ordering::Relaxed);
+        *progress = Some(RescanProgress {
+            from_height,
+            tip_height,
+            scanned_height: None,
+            found_utxos: 0,
+            started_at: Timestamp::now(),
+            status: RescanStatus::Running,
+        });
+
+        Ok(())
+    }
+
+    fn update(&self, update: impl FnOnce(&mut RescanProgress)) {
+        if let Some(progress) = self.progress.lock().unwrap().as_mut() {
+            update(progress);
+        }
+    }
+
+    fn finish(&self, result: Result<()>) {
+        let status = match result {
+            Ok(()) if self.is_cancelled() => RescanStatus::Cancelled,
+            Ok(()) => RescanStatus::Finished,
+            Err(err) => RescanStatus::Failed(format!("{err:#}")),
+        };
+        self.update(|progress| progress.status = status);
+    }
+}
+
+/// Start a rescan of the canonical blocks from `from_height` to the tip, in
+/// a task of its own. Progress is reported through
+/// [`GlobalState::wallet_rescan`](super::GlobalState::wallet_rescan).
+pub async fn start_rescan(
+    global_state_lock: GlobalStateLock,
+    from_height: BlockHeight,
+) -> Result<(), RescanError> {
+    let (rescan, spending_key, tip_height) = {
+        let global_state = global_state_lock.lock_guard().await;
+        if !global_state.chain.is_archival_node() {
+            return Err(RescanError::NotArchival);
+        }
+        (
+            global_state.wallet_rescan.clone(),
+            global_state
+                .wallet_state
+                .wallet_secret
+                .nth_generation_spending_key(0),
+            global_state.chain.light_state().kernel.header.height,
+        )
+    };
+
+    rescan.start(from_height, tip_height)?;
+    info!("Rescanning blocks from height {from_height} for wallet UTXOs");
+    tokio::spawn(async move {
+        let result =
+            rescan_blocks(global_state_lock, rescan.clone(), from_height, spending_key).await;
+        match &result {
+            Ok(()) => info!("Rescan stopped: {:?}", rescan.progress()),
+            Err(err) => warn!("Rescan failed: {err:#}"),
+        }
+        rescan.finish(result);
+    });
+
+    Ok(())
+}
+
+async fn rescan_blocks(
+    global_state_lock: GlobalStateLock,
+    rescan: WalletRescan,
+    from_height: BlockHeight,
+    spending_key: SpendingKey,
+) -> Result<()> {
+    let (block_tx, block_rx) = mpsc::channel(RESCAN_READ_AHEAD);
+    let reader = tokio::spawn(read_blocks(
+        global_state_lock.clone(),
+        rescan.clone(),
+        from_height,
+        block_tx,
+    ));
+
+    let workers = std::thread::available_parallelism()
+        .map(|cores| cores.get())
+        .unwrap_or(1);
+    let scanned_blocks = stream::unfold(block_rx, |mut block_rx| async move {
+        block_rx.recv().await.map(|block| (block, block_rx))
+    })
+    .map(move |block: Block| {
+        tokio::task::spawn_blocking(move || {
+            let announced_utxos = spending_key.scan_block_for_announced_utxos(&block);
+            (block, announced_utxos)
+        })
+    })
+    // `buffered` keeps the order of the blocks
+    .buffered(workers);
+    pin_mut!(scanned_blocks);
+
+    // Index sets of the UTXOs found, by monitored UTXO, to find their spends
+    let mut found_index_sets: HashMap<[u128; NUM_TRIALS as usize], u64> = HashMap::new();
+    let mut previous_digest = None;
+    while let Some(scanned_block) = scanned_blocks.next().await {
+        if rescan.is_cancelled() {
+            break;
+        }
+
+        let (block, announced_utxos) = scanned_block?;
+        let header = &block.kernel.header;
+        if previous_digest.is_some_and(|digest| digest != header.prev_block_digest) {
+            bail!("The chain was reorganized during the rescan. Start it again.");
+        }
+        previous_digest = Some(block.hash());
+
+        let block_info = (block.hash(), header.timestamp, header.height);
+        let spent = block
+            .kernel
+            .body
+            .transaction
+            .kernel
+            .inputs
+            .iter()
+            .filter_map(|input| found_index_sets.remove(&input.absolute_indices.to_array()))
+            .collect::<Vec<_>>();
+        if spent.is_empty() && announced_utxos.is_empty() {
+            rescan.update(|progress| progress.scanned_height = Some(header.height));
+            continue;
+        }
+
+        let mut global_state_mut = global_state_lock.lock_guard_mut().await;
+        let tip_digest = global_state_mut.chain.light_state().hash();
+        let mut restored = vec![];
+        for AnnouncedUtxo {
+            utxo,
+            sender_randomness,
+            receiver_preimage,
+            aocl_index,
+            ..
+        } in announced_utxos
+        {
+            let item = Hash::hash(&utxo);
+            let ams = global_state_mut
+                .chain
+                .archival_state()
+                .archival_mutator_set
+                .ams();
+            let membership_proof = match ams
+                .restore_membership_proof(item, sender_randomness, receiver_preimage, aocl_index)
+                .await
+            {
+                Ok(membership_proof) => membership_proof,
+                Err(err) => bail!("Could not restore membership proof of UTXO: {err}"),
+            };
+            restored.push((
+                utxo,
+                sender_randomness,
+                receiver_preimage,
+                aocl_index,
+                item,
+                membership_proof,
+            ));
+        }
+
+        let wallet_state = &mut global_state_mut.wallet_state;
+        let monitored_utxos = wallet_state.wallet_db.monitored_utxos_mut();
+        let mut changed = vec![];
+        for index in spent {
+            let mut monitored_utxo = monitored_utxos.get(index).await;
+            monitored_utxo.spent_in_block = Some(block_info);
+            monitored_utxos.set(index, monitored_utxo).await;
+            changed.push(index);
+        }
+
+        let mut recovery_data = vec![];
+        for (utxo, sender_randomness, receiver_preimage, aocl_index, item, membership_proof) in
+            restored
+        {
+            let already_monitored = {
+                let stream = monitored_utxos.stream_values().await;
+                pin_mut!(stream);
+                stream
+                    .any(|monitored_utxo| {
+                        futures::future::ready(
+                            monitored_utxo.utxo == utxo
+                                && monitored_utxo
+                                    .blockhash_to_membership_proof
+                                    .iter()
+                                    .any(|(_, mp)| mp.auth_path_aocl.leaf_index == aocl_index),
+                        )
+                    })
+                    .await
+            };
+            if already_monitored {
+                continue;
+            }
+
+            let index = monitored_utxos.len().await;
+            found_index_sets.insert(membership_proof.compute_indices(item).to_array(), index);
+            let mut monitored_utxo =
+                MonitoredUtxo::new(utxo.clone(), wallet_state.number_of_mps_per_utxo);
+            monitored_utxo.confirmed_in_block = Some(block_info);
+            monitored_utxo.add_membership_proof_for_tip(tip_digest, membership_proof);
+            monitored_utxos.push(monitored_utxo).await;
+            changed.push(index);
+
+            recovery_data.push(IncomingUtxoRecoveryData {
+                utxo,
+                sender_randomness,
+                receiver_preimage,
+                aocl_index,
+            });
+        }
+
+        let found_utxos = recovery_data.len();
+        for item in recovery_data {
+            wallet_state.store_utxo_ms_recovery_data(item).await?;
+        }
+        wallet_state
+            .wallet_db
+            .record_wallet_change(tip_digest, changed)
+            .await;
+        wallet_state.wallet_db.persist().await;
+        drop(global_state_mut);
+
+        if found_utxos > 0 {
+            info!(
+                "Rescan found {found_utxos} UTXOs in block {} at height {}",
+                block_info.0, block_info.2
+            );
+        }
+        rescan.update(|progress| {
+            progress.scanned_height = Some(block_info.2);
+            progress.found_utxos += found_utxos;
+        });
+    }
+
+    reader.abort();
+    match reader.await {
+        Ok(result) => result,
+        Err(err) if err.is_cancelled() => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Read the canonical blocks from `from_height` on, until the tip, and send
+/// them to the workers
+async fn read_blocks(
+    global_state_lock: GlobalStateLock,
+    rescan: WalletRescan,
+    from_height: BlockHeight,
+    block_tx: mpsc::Sender<Block>,
+) -> Result<()> {
+    let mut next_height = u64::from(from_height);
+    loop {
+        let global_state = global_state_lock.lock_guard().await;
+        let tip_height = global_state.chain.light_state().kernel.header.height;
+        rescan.update(|progress| progress.tip_height = tip_height);
+        if next_height > u64::from(tip_height) || rescan.is_cancelled() {
+            return Ok(());
+        }
+
+        let last_height = (next_height + RESCAN_BATCH_SIZE - 1).min(u64::from(tip_height));
+        let mut batch = vec![];
+        {
+            let blocks = global_state
+                .chain
+                .archival_state()
+                .iter_canonical_blocks(next_height.into()..=last_height.into());
+            pin_mut!(blocks);
+            while let Some(block) = blocks.next().await {
+                batch.push(block?);
+            }
+        }
+        drop(global_state);
+        next_height = last_height + 1;
+
+        for block in batch {
+            if block_tx.send(block).await.is_err() {
+                // The rescan stopped
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod wallet_rescan_tests {
+    use super::*;
+
+    #[test]
+    fn one_rescan_at_a_time() {
+        let rescan = WalletRescan::default();
+        assert_eq!(None, rescan.progress());
+        assert_eq!(Err(RescanError::NotRunning), rescan.cancel());
+
+        rescan.start(5u64.into(), 10u64.into()).unwrap();
+        assert!(rescan.is_running());
+        assert_eq!(
+            Err(RescanError::AlreadyRunning),
+            rescan.start(0u64.into(), 10u64.into())
+        );
+
+        rescan.cancel().unwrap();
+        rescan.finish(Ok(()));
+        assert_eq!(RescanStatus::Cancelled, rescan.progress().unwrap().status);
+
+        // A new rescan is not cancelled by the previous cancellation
+        rescan.start(0u64.into(), 10u64.into()).unwrap();
+        rescan.finish(Err(anyhow::anyhow!("disk on fire")));
+        let progress = rescan.progress().unwrap();
+        assert_eq!(BlockHeight::from(0u64), progress.from_height);
+        assert_eq!(
+            RescanStatus::Failed("disk on fire".to_string()),
+            progress.status
+        );
+    }
+}
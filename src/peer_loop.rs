@@ -1,26 +1,38 @@
 use crate::models::consensus::timestamp::Timestamp;
 use crate::prelude::twenty_first;
 
-use crate::connect_to_peers::close_peer_connected_callback;
+use crate::connect_to_peers::{
+    close_peer_connected_callback, find_duplicate_connection, DuplicateConnection,
+};
 use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::block::header_chain_proof::MAX_HEADER_CHAIN_PROOF_SAMPLES;
+use crate::models::blockchain::block::header_validation::check_proof_of_work;
 use crate::models::blockchain::block::transfer_block::TransferBlock;
 use crate::models::blockchain::block::Block;
+use crate::models::blockchain::transaction::Transaction;
 use crate::models::channel::{MainToPeerThread, PeerThreadToMain, PeerThreadToMainTransaction};
 use crate::models::peer::{
-    HandshakeData, MutablePeerState, PeerInfo, PeerMessage, PeerSanctionReason, PeerStanding,
-};
-use crate::models::state::mempool::{
-    MEMPOOL_IGNORE_TRANSACTIONS_THIS_MANY_SECS_AHEAD, MEMPOOL_TX_THRESHOLD_AGE_IN_SECS,
+    supports_message_extensions, HandshakeData, MutablePeerState, PeerInfo, PeerMessage,
+    PeerSanctionReason, PeerStanding, TransactionNotification, MAX_ADVERTISED_LISTEN_ADDRESSES,
 };
+use crate::models::state::alerts::SignedAlert;
+use crate::models::state::block_timeline::STAGE_VALIDATE;
+use crate::models::state::connection_metrics::{ConnectionDirection, MeteredPeer};
+use crate::models::state::event_journal::JournalEvent;
+use crate::models::state::future_blocks::FutureBlocks;
+use crate::models::state::invalid_blocks::InvalidBlockReason;
 use crate::models::state::GlobalStateLock;
+use crate::time_fn_call;
 use anyhow::{bail, Result};
 use futures::sink::{Sink, SinkExt};
 use futures::stream::{TryStream, TryStreamExt};
+use futures::{pin_mut, StreamExt};
+use get_size::GetSize;
 use itertools::Itertools;
 use std::cmp;
 use std::marker::Unpin;
 use std::net::SocketAddr;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::select;
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
@@ -30,6 +42,17 @@ const STANDARD_BLOCK_BATCH_SIZE: usize = 50;
 const MAX_PEER_LIST_LENGTH: usize = 10;
 const MINIMUM_BLOCK_BATCH_SIZE: usize = 2;
 
+/// A peer's mempool requests are answered at most this often, unless the peer
+/// is whitelisted
+pub const MEMPOOL_REQUEST_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// A peer's header chain proof requests are answered at most this often,
+/// unless the peer is whitelisted
+pub const HEADER_CHAIN_PROOF_REQUEST_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Max number of transactions in a mempool response
+pub const MAX_MEMPOOL_INVENTORY_SIZE: usize = 1000;
+
 const KEEP_CONNECTION_ALIVE: bool = false;
 const _DISCONNECT_CONNECTION: bool = true;
 
@@ -83,25 +106,128 @@ impl PeerLoopHandler {
             .map(|p| p.standing.sanction(reason))
             .unwrap_or(0);
 
-        if new_standing < -(global_state_mut.cli().peer_tolerance as PeerStandingNumber) {
+        let mut history = global_state_mut
+            .net
+            .get_peer_history_from_database(self.peer_address.ip())
+            .await;
+        history.violations += 1;
+        global_state_mut
+            .net
+            .write_peer_history(self.peer_address.ip(), history)
+            .await;
+
+        if global_state_mut
+            .cli()
+            .is_banned_by_standing(self.peer_address.ip(), new_standing)
+        {
             warn!("Banning peer");
+            global_state_mut
+                .journal
+                .record(JournalEvent::PeerBanned {
+                    ip: self.peer_address.ip(),
+                    standing: new_standing,
+                })
+                .await;
             bail!("Banning peer");
         }
 
         Ok(())
     }
 
+    /// Whether the peer may be served blocks now, or has used its share of
+    /// `--block-serving-limit`
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn may_serve_blocks(&self) -> bool {
+        let cli = self.global_state_lock.cli();
+        if cli.is_whitelisted(self.peer_address.ip()) {
+            return true;
+        }
+
+        let allowed = self
+            .global_state_lock
+            .lock_guard_mut()
+            .await
+            .net
+            .block_serving
+            .allows(
+                self.peer_address,
+                Instant::now(),
+                cli.block_serving_limit.as_u64(),
+            );
+        if !allowed {
+            debug!(
+                "Peer {} used its share of the block serving limit. Not serving blocks.",
+                self.peer_address
+            );
+        }
+
+        allowed
+    }
+
+    /// Count blocks of `bytes` towards the peer's share of
+    /// `--block-serving-limit`
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn record_blocks_served(&self, bytes: usize) {
+        self.global_state_lock
+            .lock_guard_mut()
+            .await
+            .net
+            .block_serving
+            .record(self.peer_address, bytes as u64, Instant::now());
+    }
+
+    /// Record the size of the orphan blocks held for fork reconciliation, for
+    /// the node's memory status
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn record_fork_reconciliation_blocks(&self, peer_state: &MutablePeerState) {
+        self.global_state_lock
+            .lock_guard_mut()
+            .await
+            .net
+            .buffer_sizes
+            .record_orphan_blocks(self.peer_address, &peer_state.fork_reconciliation_blocks);
+    }
+
+    /// Whether the peer understands the messages of
+    /// [`PEER_MESSAGE_EXTENSIONS_MIN_VERSION`](crate::models::peer::PEER_MESSAGE_EXTENSIONS_MIN_VERSION),
+    /// which must only be sent if it does
+    fn peer_supports_message_extensions(&self) -> bool {
+        supports_message_extensions(&self.peer_handshake_data.version)
+    }
+
+    /// Prepare a block for sending to this peer, leaving out its proofs if the
+    /// peer asked for that
+    fn to_transfer_block(block: Block, peer_state_info: &MutablePeerState) -> TransferBlock {
+        let transfer_block = TransferBlock::from(block);
+        if peer_state_info.omit_block_proofs {
+            transfer_block.without_proofs()
+        } else {
+            transfer_block
+        }
+    }
+
     /// Handle validation and send all blocks to the main thread if they're all
     /// valid. Use with a list of blocks or a single block. When the
     /// `received_blocks` is a list, the parent of the `i+1`th block in the
     /// list is the `i`th block. The parent of element zero in this list is
     /// `parent_of_first_block`.
     ///
+    /// Blocks that are only invalid because their timestamps are slightly too
+    /// far in the future are sent to the main thread to be held until they
+    /// become valid.
+    ///
     /// Locking:
     ///   * acquires `global_state_lock` for write via Self::punish()
+    ///   * acquires `global_state_lock` for write to record validation timings
     async fn handle_blocks(
         &self,
-        received_blocks: Vec<Block>,
+        mut received_blocks: Vec<Block>,
         parent_of_first_block: Block,
     ) -> Result<BlockHeight> {
         debug!(
@@ -115,25 +241,131 @@ impl PeerLoopHandler {
         );
         let now = Timestamp::now();
         let mut previous_block = &parent_of_first_block;
-        for new_block in received_blocks.iter() {
-            if !new_block.has_proof_of_work(previous_block) {
+        let mut validation_durations = Vec::with_capacity(received_blocks.len());
+        let mut first_future_block_index = None;
+        for (i, new_block) in received_blocks.iter().enumerate() {
+            // Validate a block that is slightly ahead of our clock as of when it
+            // becomes valid, so that it can be held until then.
+            let is_future_block = FutureBlocks::can_hold(new_block, now);
+            let validation_time = if is_future_block {
+                first_future_block_index.get_or_insert(i);
+                new_block.earliest_valid_time()
+            } else {
+                now
+            };
+
+            // Reject blocks already known to be invalid without validating them
+            // again. Blocks are known by their proofs digest, as the block's
+            // digest does not cover its proofs: a peer that relays an honest
+            // block with omitted or tampered proofs must not get the honest
+            // block rejected.
+            let proofs_digest = new_block.proofs_digest();
+            let known_invalid = self
+                .global_state_lock
+                .lock_guard_mut()
+                .await
+                .invalid_blocks
+                .get(proofs_digest);
+            if let Some(reason) = known_invalid {
                 warn!(
-                    "Received invalid proof-of-work for block of height {} from peer with IP {}",
-                    new_block.kernel.header.height, self.peer_address
+                    "Peer with IP {} offered block of height {} that is known to be invalid: {reason}",
+                    self.peer_address, new_block.kernel.header.height
                 );
-                warn!("Difficulty is {}.", previous_block.kernel.header.difficulty);
+                self.punish(PeerSanctionReason::KnownInvalidBlock((
+                    new_block.kernel.header.height,
+                    new_block.hash(),
+                )))
+                .await?;
+                bail!("Received known invalid block");
+            }
+
+            // Skip the heavy proof verification of blocks validated before, e.g.
+            // on a fork that is reorganized back onto, or before a restart. The
+            // block's digest does not cover its proofs, so the exact proofs must
+            // have been seen before.
+            let proofs_verified = self
+                .global_state_lock
+                .lock_guard()
+                .await
+                .block_proofs_verified(proofs_digest)
+                .await;
+
+            // Blocks without proofs are only accepted by nodes that asked for
+            // them, and that trust the proof-of-work instead
+            let verify_proofs = !proofs_verified
+                && !(new_block.proofs_omitted()
+                    && self.global_state_lock.cli().accepts_blocks_without_proofs());
+
+            let validation_limits = self.global_state_lock.validation_limits();
+            let block_validation_permit = validation_limits.block_validation().await;
+            let proof_verification_permit = if verify_proofs {
+                Some(validation_limits.proof_verification().await)
+            } else {
+                None
+            };
+
+            let block_span = tracing::info_span!(
+                "validate_block",
+                block_digest = %new_block.hash(),
+                height = %new_block.kernel.header.height,
+            );
+            let ((has_proof_of_work, is_valid), validation_duration) = block_span.in_scope(|| {
+                time_fn_call(|| {
+                    let has_proof_of_work = new_block.has_proof_of_work(previous_block);
+                    let is_valid = has_proof_of_work
+                        && new_block.is_valid_with(previous_block, validation_time, verify_proofs);
+                    (has_proof_of_work, is_valid)
+                })
+            });
+            drop(proof_verification_permit);
+            drop(block_validation_permit);
+            if is_valid && verify_proofs {
+                self.global_state_lock
+                    .lock_guard_mut()
+                    .await
+                    .mark_block_proofs_verified(proofs_digest)
+                    .await;
+            }
+            validation_durations.push(validation_duration);
+
+            // Remember the failure only if it is inherent to the block: it was
+            // validated against its actual parent, and not rejected for being too
+            // far in the future, which it may not be later.
+            let failure_is_permanent = new_block.kernel.header.prev_block_digest
+                == previous_block.hash()
+                && new_block.earliest_valid_time() <= validation_time;
+            let failure_reason = if !has_proof_of_work {
+                Some(InvalidBlockReason::InsufficientProofOfWork)
+            } else if !is_valid {
+                Some(InvalidBlockReason::InvalidBlock)
+            } else {
+                None
+            };
+            if let Some(reason) = failure_reason.filter(|_| failure_is_permanent) {
+                self.global_state_lock
+                    .lock_guard_mut()
+                    .await
+                    .invalid_blocks
+                    .insert(proofs_digest, reason);
+            }
+
+            if !has_proof_of_work {
                 warn!(
-                    "Proof of work should be {} (or more) but was [{}].",
-                    Block::difficulty_to_digest_threshold(previous_block.kernel.header.difficulty),
-                    new_block.hash().values().iter().join(", ")
+                    "Received invalid proof-of-work for block of height {} from peer with IP {}",
+                    new_block.kernel.header.height, self.peer_address
                 );
+                if let Err(err) =
+                    check_proof_of_work(&previous_block.kernel.header, new_block.hash())
+                {
+                    warn!("{err}");
+                }
                 self.punish(PeerSanctionReason::InvalidBlock((
                     new_block.kernel.header.height,
                     new_block.hash(),
                 )))
                 .await?;
                 bail!("Failed to validate block due to insufficient PoW");
-            } else if !new_block.is_valid(previous_block, now) {
+            } else if !is_valid {
                 warn!(
                     "Received invalid block of height {} from peer with IP {}",
                     new_block.kernel.header.height, self.peer_address
@@ -155,12 +387,44 @@ impl PeerLoopHandler {
             previous_block = new_block;
         }
 
+        // Start the processing timelines of the new blocks with their validation time
+        {
+            let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+            let validated_at = Timestamp::now();
+            for (new_block, validation_duration) in received_blocks.iter().zip(validation_durations)
+            {
+                global_state_mut
+                    .block_timelines
+                    .entry(new_block.hash(), new_block.kernel.header.height)
+                    .add_stage(STAGE_VALIDATE, validation_duration);
+                global_state_mut
+                    .block_propagation
+                    .record_validation(new_block.hash(), validated_at);
+            }
+        }
+
         // Send the new blocks to the main thread which handles the state update
         // and storage to the database.
         let new_block_height = received_blocks.last().unwrap().kernel.header.height;
-        self.to_main_tx
-            .send(PeerThreadToMain::NewBlocks(received_blocks))
-            .await?;
+        let future_blocks = match first_future_block_index {
+            Some(index) => received_blocks.split_off(index),
+            None => vec![],
+        };
+        if !received_blocks.is_empty() {
+            self.to_main_tx
+                .send(PeerThreadToMain::NewBlocks(received_blocks))
+                .await?;
+        }
+        if !future_blocks.is_empty() {
+            info!(
+                "Holding {} block(s) from peer {} until their timestamps are no longer too far in the future",
+                future_blocks.len(),
+                self.peer_address
+            );
+            self.to_main_tx
+                .send(PeerThreadToMain::FutureBlocks(future_blocks))
+                .await?;
+        }
         info!(
             "Updated block info by block from peer. block height {}",
             new_block_height
@@ -169,6 +433,46 @@ impl PeerLoopHandler {
         Ok(new_block_height)
     }
 
+    /// Validate a block with as much accumulated proof-of-work as the tip, and
+    /// send it to the main thread to be stored as a competing tip, see
+    /// [`crate::models::state::fork_choice`]. Only archival nodes store
+    /// competing tips, and only children of stored blocks.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write via Self::handle_blocks()
+    async fn receive_competing_tip(&self, received_block: Box<Block>) -> Result<()> {
+        let parent_block = {
+            let global_state = self.global_state_lock.lock_guard().await;
+            if !global_state.chain.is_archival_node() {
+                return Ok(());
+            }
+            let archival_state = global_state.chain.archival_state();
+            if archival_state
+                .get_block_header(received_block.hash())
+                .await
+                .is_some()
+            {
+                return Ok(());
+            }
+            archival_state
+                .get_block(received_block.kernel.header.prev_block_digest)
+                .await?
+        };
+        let Some(parent_block) = parent_block else {
+            debug!("Parent of competing tip is not stored. Ignoring it.");
+            return Ok(());
+        };
+
+        info!(
+            "Got competing tip of height {} with as much proof-of-work as the tip",
+            received_block.kernel.header.height
+        );
+        self.handle_blocks(vec![*received_block], parent_block)
+            .await?;
+
+        Ok(())
+    }
+
     /// Function for handling the receiving of single new block from a peer
     ///
     /// Locking:
@@ -230,6 +534,7 @@ impl PeerLoopHandler {
                             .max_number_of_blocks_before_syncing
             {
                 peer_state.fork_reconciliation_blocks.push(*received_block);
+                self.record_fork_reconciliation_blocks(peer_state).await;
             } else {
                 // Blocks received out of order. Or more than allowed received without
                 // going into sync mode. Give up on block resolution attempt.
@@ -244,6 +549,7 @@ impl PeerLoopHandler {
                     peer_state.fork_reconciliation_blocks.len() + 1
                 );
                 peer_state.fork_reconciliation_blocks = vec![];
+                self.record_fork_reconciliation_blocks(peer_state).await;
                 return Ok(());
             }
 
@@ -267,6 +573,9 @@ impl PeerLoopHandler {
         // Reset the fork resolution state since we got all the way back to find a block that we have
         let fork_reconciliation_event = !peer_state.fork_reconciliation_blocks.is_empty();
         peer_state.fork_reconciliation_blocks = vec![];
+        if fork_reconciliation_event {
+            self.record_fork_reconciliation_blocks(peer_state).await;
+        }
 
         // Sanity check, that the blocks are correctly sorted (they should be)
         // TODO: This has failed: Investigate!
@@ -345,15 +654,13 @@ impl PeerLoopHandler {
                     .net
                     .peer_map
                     .values()
-                    .filter(|peer_info| peer_info.listen_address().is_some())
-                    .take(MAX_PEER_LIST_LENGTH) // limit length of response
-                    .map(|peer_info| {
-                        (
-                            // unwrap is safe bc of above `filter`
-                            peer_info.listen_address().unwrap(),
-                            peer_info.instance_id,
-                        )
+                    .flat_map(|peer_info| {
+                        peer_info
+                            .known_listen_addresses()
+                            .into_iter()
+                            .map(|address| (address, peer_info.instance_id))
                     })
+                    .take(MAX_PEER_LIST_LENGTH) // limit length of response
                     .collect();
 
                 // We sort the returned list, so this function is easier to test
@@ -385,38 +692,74 @@ impl PeerLoopHandler {
                     t_block.header.height,
                     t_block.header.timestamp.standard_format()
                 );
+                if t_block.proofs_omitted()
+                    && !self.global_state_lock.cli().accepts_blocks_without_proofs()
+                {
+                    // Older peers cannot decode the request
+                    if self.peer_supports_message_extensions() {
+                        debug!("Block came without proofs, requesting it again with proofs");
+                        let block_digest = Block::from(*t_block).hash();
+                        peer.send(PeerMessage::BlockRequestWithProofs(block_digest))
+                            .await?;
+                    }
+                    return Ok(false);
+                }
                 let new_block_height = t_block.header.height;
 
                 let block: Box<Block> = Box::new((*t_block).into());
 
+                let checkpoints = self.global_state_lock.cli().checkpoints();
+                if checkpoints.contradicts(new_block_height, block.hash()) {
+                    warn!("Got block of height {new_block_height} contradicting a checkpoint");
+                    self.punish(PeerSanctionReason::ContradictsCheckpoint(new_block_height))
+                        .await?;
+                    return Ok(false);
+                }
+
                 // Update the value for the highest known height that peer possesses iff
                 // we are not in a fork reconciliation state.
                 if peer_state_info.fork_reconciliation_blocks.is_empty() {
                     peer_state_info.highest_shared_block_height = new_block_height;
                 }
 
-                let incoming_block_is_heavier = self
-                    .global_state_lock
-                    .lock_guard()
-                    .await
-                    .chain
-                    .light_state()
-                    .kernel
-                    .header
-                    .proof_of_work_family
-                    < block.kernel.header.proof_of_work_family;
+                let (tip_digest, tip_proof_of_work_family) = {
+                    let global_state = self.global_state_lock.lock_guard().await;
+                    let tip = global_state.chain.light_state();
+                    (tip.hash(), tip.kernel.header.proof_of_work_family)
+                };
+                let incoming_block_is_heavier =
+                    tip_proof_of_work_family < block.kernel.header.proof_of_work_family;
+                let incoming_block_ties_with_tip = tip_proof_of_work_family
+                    == block.kernel.header.proof_of_work_family
+                    && block.hash() != tip_digest;
                 let reconciliation_ongoing = match peer_state_info.fork_reconciliation_blocks.last()
                 {
                     Some(last_block) => last_block.kernel.header.prev_block_digest == block.hash(),
                     None => false,
                 };
 
+                // Peers that send a block this node already has are timed too
+                {
+                    let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+                    let propagation = &mut global_state_mut.block_propagation;
+                    if incoming_block_is_heavier || propagation.is_tracked(block.hash()) {
+                        propagation.record_announcement(
+                            block.hash(),
+                            new_block_height,
+                            self.peer_address,
+                            Timestamp::now(),
+                        );
+                    }
+                }
+
                 // Determine whether
                 //  a) the incoming block's POW family is larger than what we have; or
                 //  b) we are populating a fork reconciliation blocks list.
                 if incoming_block_is_heavier || reconciliation_ongoing {
                     debug!("block is new");
                     self.receive_new_block(block, peer, peer_state_info).await?;
+                } else if incoming_block_ties_with_tip {
+                    self.receive_competing_tip(block).await?;
                 } else {
                     info!(
                         "Got non-canonical block from peer, height: {}, PoW family: {:?}",
@@ -429,6 +772,22 @@ impl PeerLoopHandler {
                 peers_suggested_starting_points,
                 requested_batch_size,
             ) => {
+                // Peers prefer archival nodes for batches, but ask light nodes
+                // when no archival node is connected
+                if !self
+                    .global_state_lock
+                    .lock_guard()
+                    .await
+                    .chain
+                    .is_archival_node()
+                {
+                    debug!("Cannot serve block batches without archival state");
+                    return Ok(false);
+                }
+                if !self.may_serve_blocks().await {
+                    return Ok(false);
+                }
+
                 // Find the block that the peer is requesting to start from
                 let mut peers_latest_canonical_block: Option<Block> = None;
 
@@ -496,11 +855,10 @@ impl PeerLoopHandler {
                 let tip_digest = global_state.chain.light_state().hash();
 
                 let responded_batch_size = cmp::max(responded_batch_size, MINIMUM_BLOCK_BATCH_SIZE);
-                let mut returned_blocks: Vec<TransferBlock> =
-                    Vec::with_capacity(responded_batch_size);
+                let mut returned_digests: Vec<Digest> = Vec::with_capacity(responded_batch_size);
 
                 let mut current_digest = peers_latest_canonical_block.hash();
-                while returned_blocks.len() < responded_batch_size {
+                while returned_digests.len() < responded_batch_size {
                     let children = global_state
                         .chain
                         .archival_state()
@@ -528,18 +886,30 @@ impl PeerLoopHandler {
                         canonical
                     };
 
-                    // get block and append to list
-                    let canonical_child: Block = global_state
+                    // append to list and prepare for next iteration
+                    returned_digests.push(canonical_child_digest);
+                    current_digest = canonical_child_digest;
+                }
+
+                // Decode the blocks in parallel
+                let mut returned_blocks: Vec<TransferBlock> =
+                    Vec::with_capacity(returned_digests.len());
+                let mut returned_bytes = 0;
+                {
+                    let canonical_children = global_state
                         .chain
                         .archival_state()
-                        .get_block(canonical_child_digest)
-                        .await?
-                        .unwrap();
-                    returned_blocks.push(canonical_child.into());
-
-                    // prepare for next iteration
-                    current_digest = canonical_child_digest;
+                        .get_blocks(returned_digests);
+                    pin_mut!(canonical_children);
+                    while let Some(canonical_child) = canonical_children.next().await {
+                        let canonical_child: Block = canonical_child?.unwrap();
+                        returned_bytes += canonical_child.get_size();
+                        returned_blocks
+                            .push(Self::to_transfer_block(canonical_child, peer_state_info));
+                    }
                 }
+                drop(global_state);
+                self.record_blocks_served(returned_bytes).await;
 
                 debug!(
                     "Returning {} blocks in batch response",
@@ -595,6 +965,15 @@ impl PeerLoopHandler {
                     }
                 };
 
+                // Full verification of a whole batch cannot be asked for in one
+                // request, so leave it to a sync with another peer
+                if t_blocks.iter().any(|t_block| t_block.proofs_omitted())
+                    && !self.global_state_lock.cli().accepts_blocks_without_proofs()
+                {
+                    warn!("Got batch of blocks without proofs, which were not asked for");
+                    return Ok(false);
+                }
+
                 // Convert all blocks to Block objects
                 debug!(
                     "Found own block of height {} to match received batch",
@@ -602,6 +981,18 @@ impl PeerLoopHandler {
                 );
                 let received_blocks: Vec<Block> = t_blocks.into_iter().map(|x| x.into()).collect();
 
+                let checkpoints = self.global_state_lock.cli().checkpoints();
+                if let Some(height) = checkpoints.first_contradiction(
+                    received_blocks
+                        .iter()
+                        .map(|block| (block.kernel.header.height, block.hash())),
+                ) {
+                    warn!("Got batch of blocks contradicting the checkpoint at height {height}");
+                    self.punish(PeerSanctionReason::ContradictsCheckpoint(height))
+                        .await?;
+                    return Ok(false);
+                }
+
                 // Get the latest block that we know of and handle all received blocks
                 self.handle_blocks(received_blocks, most_canonical_own_block_match)
                     .await?;
@@ -631,6 +1022,20 @@ impl PeerLoopHandler {
                     "Got BlockNotification of height {}",
                     block_notification.height
                 );
+
+                // Do not download blocks of a chain that contradicts a checkpoint
+                let checkpoints = self.global_state_lock.cli().checkpoints();
+                if checkpoints.contradicts(block_notification.height, block_notification.hash) {
+                    warn!(
+                        "Got block notification of height {} contradicting a checkpoint",
+                        block_notification.height
+                    );
+                    self.punish(PeerSanctionReason::ContradictsCheckpoint(
+                        block_notification.height,
+                    ))
+                    .await?;
+                    return Ok(false);
+                }
                 peer_state_info.highest_shared_block_height = block_notification.height;
                 {
                     let block_is_new = self
@@ -646,6 +1051,21 @@ impl PeerLoopHandler {
 
                     debug!("block_is_new: {}", block_is_new);
 
+                    // Peers that announce a block this node already has are
+                    // timed too
+                    {
+                        let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+                        let propagation = &mut global_state_mut.block_propagation;
+                        if block_is_new || propagation.is_tracked(block_notification.hash) {
+                            propagation.record_announcement(
+                                block_notification.hash,
+                                block_notification.height,
+                                self.peer_address,
+                                Timestamp::now(),
+                            );
+                        }
+                    }
+
                     // Only request block if it is new, and if we are not currently reconciling
                     // a fork. If we are reconciling, that is handled later, and the information
                     // about that is stored in `highest_shared_block_height`. If we are syncing
@@ -685,6 +1105,9 @@ impl PeerLoopHandler {
                 Ok(false)
             }
             PeerMessage::BlockRequestByHash(block_digest) => {
+                if !self.may_serve_blocks().await {
+                    return Ok(false);
+                }
                 match self
                     .global_state_lock
                     .lock_guard()
@@ -700,13 +1123,52 @@ impl PeerLoopHandler {
                         Ok(false)
                     }
                     Some(b) => {
-                        peer.send(PeerMessage::Block(Box::new(b.into()))).await?;
+                        self.record_blocks_served(b.get_size()).await;
+                        peer.send(PeerMessage::Block(Box::new(Self::to_transfer_block(
+                            b,
+                            peer_state_info,
+                        ))))
+                        .await?;
                         Ok(false)
                     }
                 }
             }
+            PeerMessage::BlockRequestWithProofs(block_digest) => {
+                if !self.may_serve_blocks().await {
+                    return Ok(false);
+                }
+                let block = self
+                    .global_state_lock
+                    .lock_guard()
+                    .await
+                    .chain
+                    .archival_state()
+                    .get_block(block_digest)
+                    .await?;
+                match block {
+                    None => {
+                        warn!("Peer requested unkown block with hash {}", block_digest);
+                    }
+                    Some(block) if block.proofs_omitted() => {
+                        // Answering with the block as stored would only prompt the
+                        // same request again
+                        debug!(
+                            "Cannot serve proofs of block {block_digest}, as they were not stored"
+                        );
+                    }
+                    Some(block) => {
+                        self.record_blocks_served(block.get_size()).await;
+                        peer.send(PeerMessage::Block(Box::new(block.into())))
+                            .await?;
+                    }
+                }
+                Ok(false)
+            }
             PeerMessage::BlockRequestByHeight(block_height) => {
                 debug!("Got BlockRequestByHeight of height {}", block_height);
+                if !self.may_serve_blocks().await {
+                    return Ok(false);
+                }
 
                 let block_digests = self
                     .global_state_lock
@@ -751,8 +1213,11 @@ impl PeerLoopHandler {
                     .get_block(canonical_chain_block_digest)
                     .await?
                     .unwrap();
-                let block_response: PeerMessage =
-                    PeerMessage::Block(Box::new(canonical_chain_block.into()));
+                self.record_blocks_served(canonical_chain_block.get_size())
+                    .await;
+                let block_response: PeerMessage = PeerMessage::Block(Box::new(
+                    Self::to_transfer_block(canonical_chain_block, peer_state_info),
+                ));
 
                 debug!("Sending block");
                 peer.send(block_response).await?;
@@ -768,6 +1233,10 @@ impl PeerLoopHandler {
                 Ok(false)
             }
             PeerMessage::Transaction(transaction) => {
+                if self.global_state_lock.cli().blocks_only {
+                    debug!("Ignoring transaction from peer in blocks-only mode");
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
                 debug!(
                     "`peer_loop` received following transaction from peer. {} inputs, {} outputs. Synced to mutator set hash: {}",
                     transaction.kernel.inputs.len(),
@@ -775,86 +1244,117 @@ impl PeerLoopHandler {
                     transaction.kernel.mutator_set_hash
                 );
 
-                // If transaction is invalid, punish
-                if !transaction.is_valid() {
-                    warn!("Received invalid tx");
-                    self.punish(PeerSanctionReason::InvalidTransaction).await?;
-                    return Ok(KEEP_CONNECTION_ALIVE);
-                }
-
-                // If transaction has coinbase, punish.
-                // Transactions received from peers have not been mined yet.
-                // Only the miner is allowed to produce transactions with non-empty coinbase fields.
-                if transaction.kernel.coinbase.is_some() {
-                    warn!("Received non-mined transaction with coinbase.");
-                    self.punish(PeerSanctionReason::NonMinedTransactionHasCoinbase)
-                        .await?;
+                self.handle_received_transaction(*transaction, None).await
+            }
+            PeerMessage::StemTransaction(transaction) => {
+                if self.global_state_lock.cli().blocks_only {
+                    debug!("Ignoring stem-phase transaction from peer in blocks-only mode");
                     return Ok(KEEP_CONNECTION_ALIVE);
                 }
-
-                // if transaction is not confirmable, punish
-                let confirmable = transaction.is_confirmable_relative_to(
-                    &self
-                        .global_state_lock
-                        .lock_guard()
-                        .await
-                        .chain
-                        .light_state()
-                        .kernel
-                        .body
-                        .mutator_set_accumulator,
+                debug!(
+                    "`peer_loop` received stem-phase transaction from peer. {} inputs, {} outputs.",
+                    transaction.kernel.inputs.len(),
+                    transaction.kernel.outputs.len(),
                 );
-                if !confirmable {
-                    warn!("Received unconfirmable tx");
-                    self.punish(PeerSanctionReason::UnconfirmableTransaction)
-                        .await?;
-                    return Ok(KEEP_CONNECTION_ALIVE);
-                }
 
-                // Get transaction timestamp
-                let tx_timestamp = transaction.kernel.timestamp;
-
-                // 2. Ignore if transaction is too old
-                let now = Timestamp::now();
-                if tx_timestamp < now - Timestamp::seconds(MEMPOOL_TX_THRESHOLD_AGE_IN_SECS) {
-                    // TODO: Consider punishing here
-                    warn!("Received too old tx");
-                    return Ok(KEEP_CONNECTION_ALIVE);
+                self.handle_received_transaction(*transaction, Some(self.peer_address))
+                    .await
+            }
+            PeerMessage::Alert(alert) => self.handle_received_alert(*alert, peer_state_info).await,
+            PeerMessage::HeaderChainProofRequest(sample_heights) => {
+                debug!(
+                    "Got header chain proof request for {} heights",
+                    sample_heights.len()
+                );
+                if sample_heights.len() > MAX_HEADER_CHAIN_PROOF_SAMPLES {
+                    self.punish(PeerSanctionReason::InvalidMessage).await?;
+                    return Ok(false);
                 }
 
-                // 3. Ignore if transaction is too far into the future
-                if tx_timestamp
-                    > now + Timestamp::seconds(MEMPOOL_IGNORE_TRANSACTIONS_THIS_MANY_SECS_AHEAD)
-                {
-                    // TODO: Consider punishing here
-                    warn!("Received tx too far into the future. Got timestamp: {tx_timestamp:?}");
+                let now = Instant::now();
+                let answered_recently =
+                    peer_state_info
+                        .last_header_chain_proof_served
+                        .is_some_and(|served| {
+                            now.duration_since(served) < HEADER_CHAIN_PROOF_REQUEST_INTERVAL
+                        });
+                let whitelisted = self
+                    .global_state_lock
+                    .cli()
+                    .is_whitelisted(self.peer_address.ip());
+                if answered_recently && !whitelisted {
+                    warn!("Ignoring header chain proof request from peer that asked too recently");
+                    self.punish(PeerSanctionReason::HeaderChainProofRequestFlood)
+                        .await?;
                     return Ok(KEEP_CONNECTION_ALIVE);
                 }
 
-                // Otherwise relay to main
-                let pt2m_transaction = PeerThreadToMainTransaction {
-                    transaction: *transaction.to_owned(),
-                    confirmable_for_block: self
-                        .global_state_lock
-                        .lock_guard()
-                        .await
+                let outline = {
+                    let global_state = self.global_state_lock.lock_guard().await;
+                    if !global_state.chain.is_archival_node() {
+                        debug!("Cannot serve header chain proofs without archival state");
+                        return Ok(false);
+                    }
+                    global_state
                         .chain
-                        .light_state()
-                        .hash(),
+                        .archival_state()
+                        .header_chain_proof_outline(&sample_heights)
+                        .await?
                 };
-                self.to_main_tx
-                    .send(PeerThreadToMain::Transaction(Box::new(pt2m_transaction)))
+                peer_state_info.last_header_chain_proof_served = Some(now);
+
+                // Each header is read under its own lock, so that blocks can
+                // be applied in between
+                let proof = outline
+                    .complete(|digest| async move {
+                        self.global_state_lock
+                            .lock_guard()
+                            .await
+                            .chain
+                            .archival_state()
+                            .proven_header(digest)
+                            .await
+                    })
                     .await?;
-
-                Ok(KEEP_CONNECTION_ALIVE)
+                peer.send(PeerMessage::HeaderChainProofResponse(Box::new(proof)))
+                    .await?;
+                Ok(false)
+            }
+            PeerMessage::HeaderChainProofResponse(_) => {
+                // Only light clients request these proofs
+                debug!("Ignoring unrequested header chain proof");
+                Ok(false)
             }
             PeerMessage::TransactionNotification(transaction_notification) => {
-                // 1. Ignore if we already know this transaction.
-                let transaction_is_known = self
+                // A stem-phase transaction that is announced has been diffused, so
+                // this node need not diffuse it when its embargo ends.
+                let transaction_digest = transaction_notification.transaction_digest;
+                let in_stem_pool = self
                     .global_state_lock
                     .lock_guard()
                     .await
-                    .mempool
+                    .net
+                    .stem_pool
+                    .contains(transaction_digest);
+                if in_stem_pool {
+                    self.global_state_lock
+                        .lock_guard_mut()
+                        .await
+                        .net
+                        .stem_pool
+                        .remove(transaction_digest);
+                }
+
+                // 1. Ignore if we take no transactions from peers, or already
+                // know this transaction.
+                if self.global_state_lock.cli().blocks_only {
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
+                let transaction_is_known = self
+                    .global_state_lock
+                    .lock_guard()
+                    .await
+                    .mempool
                     .contains(transaction_notification.transaction_digest);
                 if transaction_is_known {
                     debug!("transaction was already known");
@@ -872,6 +1372,128 @@ impl PeerLoopHandler {
 
                 Ok(KEEP_CONNECTION_ALIVE)
             }
+            PeerMessage::MempoolRequest => {
+                let now = Instant::now();
+                let answered_recently = peer_state_info
+                    .last_mempool_request_served
+                    .is_some_and(|served| now.duration_since(served) < MEMPOOL_REQUEST_INTERVAL);
+                let whitelisted = self
+                    .global_state_lock
+                    .cli()
+                    .is_whitelisted(self.peer_address.ip());
+                if answered_recently && !whitelisted {
+                    warn!("Ignoring mempool request from peer that asked too recently");
+                    self.punish(PeerSanctionReason::MempoolRequestFlood).await?;
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
+                peer_state_info.last_mempool_request_served = Some(now);
+
+                let inventory = self
+                    .global_state_lock
+                    .lock_guard()
+                    .await
+                    .mempool
+                    .get_sorted_iter()
+                    .take(MAX_MEMPOOL_INVENTORY_SIZE)
+                    .map(|(transaction_digest, _)| TransactionNotification { transaction_digest })
+                    .collect_vec();
+                debug!(
+                    "Answering mempool request with {} transactions",
+                    inventory.len()
+                );
+                peer.send(PeerMessage::MempoolResponse(inventory)).await?;
+
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            PeerMessage::ClockRequest => {
+                peer.send(PeerMessage::Clock(Timestamp::now())).await?;
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            PeerMessage::Clock(peer_clock) => {
+                let Some(requested_at) = peer_state_info.clock_requested_at.take() else {
+                    debug!("Ignoring unrequested clock");
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                };
+                let now = Timestamp::now();
+                if let Some(peer_info) = self
+                    .global_state_lock
+                    .lock_guard_mut()
+                    .await
+                    .net
+                    .peer_map
+                    .get_mut(&self.peer_address)
+                {
+                    peer_info.record_clock(peer_clock, requested_at, now);
+                }
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            PeerMessage::OmitBlockProofsRequest => {
+                peer_state_info.omit_block_proofs = true;
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            PeerMessage::BlocksOnly => {
+                // Main needs to know too, so as not to pass it stem-phase
+                // transactions
+                peer_state_info.blocks_only = true;
+                if let Some(peer_info) = self
+                    .global_state_lock
+                    .lock_guard_mut()
+                    .await
+                    .net
+                    .peer_map
+                    .get_mut(&self.peer_address)
+                {
+                    peer_info.blocks_only = true;
+                }
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            PeerMessage::ListenAddresses(addresses) => {
+                if addresses.len() > MAX_ADVERTISED_LISTEN_ADDRESSES {
+                    self.punish(PeerSanctionReason::InvalidMessage).await?;
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
+                if let Some(peer_info) = self
+                    .global_state_lock
+                    .lock_guard_mut()
+                    .await
+                    .net
+                    .peer_map
+                    .get_mut(&self.peer_address)
+                {
+                    peer_info.advertised_listen_addresses = addresses;
+                }
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            PeerMessage::MempoolResponse(inventory) => {
+                if !std::mem::take(&mut peer_state_info.mempool_requested) {
+                    debug!("Ignoring unrequested mempool response");
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
+                if inventory.len() > MAX_MEMPOOL_INVENTORY_SIZE {
+                    self.punish(PeerSanctionReason::InvalidMessage).await?;
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
+
+                // Fetch the transactions we lack, as if they had been announced
+                let unknown_transactions = {
+                    let global_state = self.global_state_lock.lock_guard().await;
+                    inventory
+                        .into_iter()
+                        .map(|notification| notification.transaction_digest)
+                        .filter(|digest| !global_state.mempool.contains(*digest))
+                        .collect_vec()
+                };
+                debug!(
+                    "Requesting {} transactions from peer's mempool",
+                    unknown_transactions.len()
+                );
+                for transaction_digest in unknown_transactions {
+                    peer.send(PeerMessage::TransactionRequest(transaction_digest))
+                        .await?;
+                }
+
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
             PeerMessage::TransactionRequest(transaction_identifier) => {
                 if let Some(transaction) = self
                     .global_state_lock
@@ -889,6 +1511,153 @@ impl PeerLoopHandler {
         }
     }
 
+    /// Validate a transaction received from the peer, punishing the peer if it
+    /// is invalid, and pass it on to main. `stem_from` is set if the transaction
+    /// is in the stem phase of Dandelion relay.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for read
+    ///   * acquires `global_state_lock` for write via Self::punish()
+    async fn handle_received_transaction(
+        &self,
+        transaction: Transaction,
+        stem_from: Option<SocketAddr>,
+    ) -> Result<bool> {
+        let validation_limits = self.global_state_lock.validation_limits();
+        let _admission_permit = validation_limits.mempool_admission().await;
+
+        // If transaction is invalid, punish
+        let proof_verification_permit = validation_limits.proof_verification().await;
+        let is_valid = transaction.is_valid();
+        drop(proof_verification_permit);
+        if !is_valid {
+            warn!("Received invalid tx");
+            self.punish(PeerSanctionReason::InvalidTransaction).await?;
+            return Ok(KEEP_CONNECTION_ALIVE);
+        }
+
+        // If transaction has coinbase, punish.
+        // Transactions received from peers have not been mined yet.
+        // Only the miner is allowed to produce transactions with non-empty coinbase fields.
+        if transaction.kernel.coinbase.is_some() {
+            warn!("Received non-mined transaction with coinbase.");
+            self.punish(PeerSanctionReason::NonMinedTransactionHasCoinbase)
+                .await?;
+            return Ok(KEEP_CONNECTION_ALIVE);
+        }
+
+        // if transaction is not confirmable, punish
+        let confirmable = transaction.is_confirmable_relative_to(
+            &self
+                .global_state_lock
+                .lock_guard()
+                .await
+                .chain
+                .light_state()
+                .kernel
+                .body
+                .mutator_set_accumulator,
+        );
+        if !confirmable {
+            warn!("Received unconfirmable tx");
+            self.punish(PeerSanctionReason::UnconfirmableTransaction)
+                .await?;
+            return Ok(KEEP_CONNECTION_ALIVE);
+        }
+
+        // Ignore if transaction violates the mempool policy, e.g. if it is too
+        // old or too far into the future. Policies differ between nodes, so
+        // this is no reason to punish.
+        let policy_check = self
+            .global_state_lock
+            .lock_guard()
+            .await
+            .mempool
+            .policy()
+            .check(&transaction, Timestamp::now());
+        if let Err(violation) = policy_check {
+            if !self
+                .global_state_lock
+                .cli()
+                .is_whitelisted(self.peer_address.ip())
+            {
+                warn!("Received tx that violates the mempool policy: {violation}");
+                return Ok(KEEP_CONNECTION_ALIVE);
+            }
+            debug!("Accepting tx from whitelisted peer despite mempool policy: {violation}");
+        }
+
+        // Otherwise relay to main
+        let pt2m_transaction = PeerThreadToMainTransaction {
+            transaction,
+            confirmable_for_block: self
+                .global_state_lock
+                .lock_guard()
+                .await
+                .chain
+                .light_state()
+                .hash(),
+            stem_from,
+        };
+        self.to_main_tx
+            .send(PeerThreadToMain::Transaction(Box::new(pt2m_transaction)))
+            .await?;
+
+        Ok(KEEP_CONNECTION_ALIVE)
+    }
+
+    /// Verify a network alert not seen before, and pass it on to main for relay
+    /// if it is valid. Punish the peer if the alert is invalid in a way that
+    /// does not depend on this node's trusted keys or clock, or if the peer
+    /// sends too many new alerts.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn handle_received_alert(
+        &self,
+        alert: SignedAlert,
+        peer_state_info: &mut MutablePeerState,
+    ) -> Result<bool> {
+        // Relayed alerts reach this node from several peers
+        let is_known = self
+            .global_state_lock
+            .lock_guard()
+            .await
+            .net
+            .alerts
+            .contains(&alert);
+        if is_known {
+            return Ok(KEEP_CONNECTION_ALIVE);
+        }
+
+        if !peer_state_info.alert_rate_limiter.allow(Instant::now()) {
+            warn!("Dropping alert from peer that sent too many alerts");
+            self.punish(PeerSanctionReason::AlertFlood).await?;
+            return Ok(KEEP_CONNECTION_ALIVE);
+        }
+
+        let accepted = self
+            .global_state_lock
+            .lock_guard_mut()
+            .await
+            .accept_alert(alert.clone(), Timestamp::now());
+        match accepted {
+            Ok(true) => {
+                self.to_main_tx
+                    .send(PeerThreadToMain::Alert(Box::new(alert)))
+                    .await?;
+            }
+            Ok(false) => (),
+            Err(err) if err.is_local() => debug!("Ignoring alert from peer: {err}"),
+            Err(err) => {
+                warn!("Received invalid alert: {err}");
+                self.punish(PeerSanctionReason::InvalidAlert).await?;
+            }
+        }
+
+        Ok(KEEP_CONNECTION_ALIVE)
+    }
+
     /// Handle message from main thread. The boolean return value indicates if
     /// the connection should be closed.
     ///
@@ -970,7 +1739,48 @@ impl PeerLoopHandler {
                 }
                 Ok(false)
             }
+            MainToPeerThread::StemTransaction(transaction, target_socket_addr) => {
+                // Older peers cannot decode it. Main does not pick them, and the
+                // stem pool diffuses the transaction if it is not passed on.
+                if target_socket_addr == self.peer_address
+                    && self.peer_supports_message_extensions()
+                {
+                    debug!("Sending PeerMessage::StemTransaction");
+                    peer.send(PeerMessage::StemTransaction(transaction)).await?;
+                }
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            MainToPeerThread::MempoolRequest(target_socket_addr) => {
+                if target_socket_addr == self.peer_address
+                    && self.peer_supports_message_extensions()
+                {
+                    debug!("Sending PeerMessage::MempoolRequest");
+                    peer_state_info.mempool_requested = true;
+                    peer.send(PeerMessage::MempoolRequest).await?;
+                }
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            MainToPeerThread::ClockRequest => {
+                if self.peer_supports_message_extensions() {
+                    debug!("Sending PeerMessage::ClockRequest");
+                    peer_state_info.clock_requested_at = Some(Timestamp::now());
+                    peer.send(PeerMessage::ClockRequest).await?;
+                }
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
+            MainToPeerThread::Alert(alert) => {
+                // Older peers cannot decode alerts
+                if !self.peer_supports_message_extensions() {
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
+                debug!("Sending PeerMessage::Alert");
+                peer.send(PeerMessage::Alert(alert)).await?;
+                Ok(KEEP_CONNECTION_ALIVE)
+            }
             MainToPeerThread::TransactionNotification(transaction_notification) => {
+                if peer_state_info.blocks_only {
+                    return Ok(KEEP_CONNECTION_ALIVE);
+                }
                 debug!("Sending PeerMessage::TransactionNotification");
                 peer.send(PeerMessage::TransactionNotification(
                     transaction_notification,
@@ -1074,7 +1884,7 @@ impl PeerLoopHandler {
     ///   * acquires `global_state_lock` for write
     pub async fn run_wrapper<S>(
         &self,
-        mut peer: S,
+        peer: S,
         from_main_rx: broadcast::Receiver<MainToPeerThread>,
     ) -> Result<()>
     where
@@ -1102,35 +1912,84 @@ impl PeerLoopHandler {
             standing,
             version: self.peer_handshake_data.version.clone(),
             is_archival_node: self.peer_handshake_data.is_archival_node,
+            clock_offset_millis: None,
+            advertised_listen_addresses: vec![],
+            blocks_only: false,
         };
 
+        drop(global_state);
+
         // There is potential for a race-condition in the peer_map here, as we've previously
         // counted the number of entries and checked if instance ID was already connected. But
-        // this check could have been invalidated by other threads so we perform it again
+        // this check could have been invalidated by other threads so we perform it again, under
+        // the same lock as the insertion. Of simultaneous connections in both directions, the
+        // one that was checked second sees the other one here.
+        let mut global_state_mut = self.global_state_lock.lock_guard_mut().await;
+        let duplicate = find_duplicate_connection(
+            global_state_mut.net.peer_map.values(),
+            global_state_mut.net.instance_id,
+            self.peer_handshake_data.instance_id,
+            self.peer_address,
+            self.inbound_connection,
+        );
+        let superseded = match duplicate {
+            DuplicateConnection::Refuse => {
+                bail!("Attempted to connect to already connected peer. Aborting connection.");
+            }
+            DuplicateConnection::Supersede(superseded) => Some(superseded),
+            DuplicateConnection::None => None,
+        };
 
-        if global_state
-            .net
-            .peer_map
-            .values()
-            .any(|pi| pi.instance_id == self.peer_handshake_data.instance_id)
+        if superseded.is_none()
+            && global_state_mut.net.peer_map.len() >= global_state_mut.cli().max_peers as usize
         {
-            bail!("Attempted to connect to already connected peer. Aborting connection.");
-        }
-
-        if global_state.net.peer_map.len() >= global_state.cli().max_peers as usize {
             bail!("Attempted to connect to more peers than allowed. Aborting connection.");
         }
 
-        if global_state.net.peer_map.contains_key(&self.peer_address) {
-            // This shouldn't be possible, unless the peer reports a different instance ID than
-            // for the other connection. Only a malignant client would do that.
-            bail!("Already connected to peer. Aborting connection");
+        global_state_mut
+            .net
+            .peer_map
+            .insert(self.peer_address, new_peer);
+        let traffic = global_state_mut.net.connection_metrics.connection_opened(
+            self.peer_address,
+            ConnectionDirection::new(self.inbound_connection),
+            SystemTime::now(),
+        );
+        drop(global_state_mut);
+        let mut peer = MeteredPeer::new(peer, traffic);
+
+        if let Some(superseded) = superseded {
+            info!(
+                "Connected to {} in both directions. Closing the connection through {superseded}.",
+                self.peer_address
+            );
+            self.to_main_tx
+                .send(PeerThreadToMain::SupersededConnection(superseded))
+                .await?;
         }
-        drop(global_state);
 
-        self.global_state_lock
-            .lock_mut(|s| s.net.peer_map.insert(self.peer_address, new_peer))
-            .await;
+        // Tell the peer where else it can reach us, which does not fit in the
+        // handshake that older versions decode
+        let listen_addresses = self
+            .global_state_lock
+            .lock_guard()
+            .await
+            .advertised_listen_addresses()
+            .into_iter()
+            .take(MAX_ADVERTISED_LISTEN_ADDRESSES)
+            .collect_vec();
+        if !listen_addresses.is_empty() && self.peer_supports_message_extensions() {
+            peer.send(PeerMessage::ListenAddresses(listen_addresses))
+                .await?;
+        }
+        if self.global_state_lock.cli().accepts_blocks_without_proofs()
+            && self.peer_supports_message_extensions()
+        {
+            peer.send(PeerMessage::OmitBlockProofsRequest).await?;
+        }
+        if self.global_state_lock.cli().blocks_only && self.peer_supports_message_extensions() {
+            peer.send(PeerMessage::BlocksOnly).await?;
+        }
 
         // This message is used to determine if we are to enter synchronization mode.
         self.to_main_tx
@@ -1179,13 +2038,18 @@ impl PeerLoopHandler {
 
 #[cfg(test)]
 mod peer_loop_tests {
-    use rand::{thread_rng, Rng};
+    use rand::{random, thread_rng, Rng};
     use tokio::sync::mpsc::error::TryRecvError;
     use tracing_test::traced_test;
 
     use crate::{
-        config_models::network::Network,
-        models::{peer::TransactionNotification, state::wallet::WalletSecret},
+        config_models::{cli_args, network::Network},
+        models::{
+            blockchain::block::FUTUREDATING_LIMIT_IN_HOURS,
+            peer::TransactionNotification,
+            state::alerts::{Alert, AlertSecretKey, AlertSeverity},
+            state::wallet::WalletSecret,
+        },
         tests::shared::{
             get_dummy_peer_connection_data_genesis, get_dummy_socket_address,
             get_test_genesis_setup, make_mock_block_with_invalid_pow,
@@ -1195,6 +2059,47 @@ mod peer_loop_tests {
 
     use super::*;
 
+    #[traced_test]
+    #[tokio::test]
+    async fn inbound_connection_supersedes_outbound_connection_to_same_peer() -> Result<()> {
+        let (_peer_broadcast_tx, from_main_rx, to_main_tx, mut to_main_rx, state_lock, mut hsd) =
+            get_test_genesis_setup(Network::Alpha, 1).await?;
+
+        // The peer has a lower instance ID, so its connection to us is kept
+        // over ours to it
+        let (outbound_address, own_instance_id) = {
+            let mut state = state_lock.lock_guard_mut().await;
+            let own_instance_id = state.net.instance_id;
+            let outbound = state.net.peer_map.values_mut().next().unwrap();
+            assert!(!outbound.inbound);
+            outbound.instance_id = own_instance_id.saturating_sub(1);
+            (outbound.connected_address, own_instance_id)
+        };
+        hsd.instance_id = own_instance_id.saturating_sub(1);
+
+        let mock = Mock::new(vec![Action::Read(PeerMessage::Bye)]);
+        let inbound_address = get_dummy_socket_address(7);
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            inbound_address,
+            hsd,
+            true,
+            1,
+        );
+        peer_loop_handler.run_wrapper(mock, from_main_rx).await?;
+
+        let mut superseded = vec![];
+        while let Ok(message) = to_main_rx.try_recv() {
+            if let PeerThreadToMain::SupersededConnection(address) = message {
+                superseded.push(address);
+            }
+        }
+        assert_eq!(vec![outbound_address], superseded);
+
+        Ok(())
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn test_peer_loop_bye() -> Result<()> {
@@ -1448,38 +2353,325 @@ mod peer_loop_tests {
 
     #[traced_test]
     #[tokio::test]
-    async fn test_peer_loop_block_with_block_in_db() -> Result<()> {
+    async fn known_invalid_block_is_rejected_without_validation_test() -> Result<()> {
+        // A block is rejected for insufficient PoW and remembered. When another peer
+        // offers it again, it is rejected by digest and sanctioned more heavily.
         let mut rng = thread_rng();
-        // The scenario tested here is that a client receives a block that is already
-        // known and stored. The expected behavior is to ignore the block and not send
-        // a message to the main thread.
         let network = Network::Alpha;
-        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, mut to_main_rx1, state_lock, hsd) =
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, hsd) =
             get_test_genesis_setup(network, 0).await?;
-        let mut global_state_mut = state_lock.lock_guard_mut().await;
-        let peer_address = get_dummy_socket_address(0);
-        let genesis_block: Block = global_state_mut.chain.archival_state().get_tip().await;
+        let genesis_block: Block = state_lock
+            .lock_guard()
+            .await
+            .chain
+            .archival_state()
+            .get_tip()
+            .await;
+        let a_recipient_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_without_valid_pow, _, _) =
+            make_mock_block_with_invalid_pow(&genesis_block, None, a_recipient_address, rng.gen());
+        let bad_digest = block_without_valid_pow.hash();
+        let bad_proofs_digest = block_without_valid_pow.proofs_digest();
+
+        for (i, expected_sanction) in [
+            PeerSanctionReason::InvalidBlock((1u64.into(), bad_digest)),
+            PeerSanctionReason::KnownInvalidBlock((1u64.into(), bad_digest)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let peer_address = get_dummy_socket_address(i as u8);
+            let mock = Mock::new(vec![
+                Action::Read(PeerMessage::Block(Box::new(
+                    block_without_valid_pow.clone().into(),
+                ))),
+                Action::Read(PeerMessage::Bye),
+            ]);
+            let peer_loop_handler = PeerLoopHandler::new(
+                to_main_tx.clone(),
+                state_lock.clone(),
+                peer_address,
+                hsd.clone(),
+                true,
+                1,
+            );
+            assert!(peer_loop_handler
+                .run_wrapper(mock, peer_broadcast_tx.subscribe())
+                .await
+                .is_err());
 
-        let a_wallet_secret = WalletSecret::new_random();
-        let a_recipient_address = a_wallet_secret.nth_generation_spending_key(0).to_address();
-        let (block_1, _, _) =
-            make_mock_block_with_valid_pow(&genesis_block, None, a_recipient_address, rng.gen());
-        global_state_mut.set_new_tip(block_1.clone()).await?;
-        drop(global_state_mut);
+            let standing = state_lock
+                .lock_guard()
+                .await
+                .net
+                .get_peer_standing_from_database(peer_address.ip())
+                .await
+                .unwrap();
+            assert_eq!(Some(expected_sanction), standing.latest_sanction);
+            assert_eq!(-(expected_sanction.to_severity() as i32), standing.standing);
+        }
 
-        let mock_peer_messages = Mock::new(vec![
-            Action::Read(PeerMessage::Block(Box::new(block_1.into()))),
-            Action::Read(PeerMessage::Bye),
-        ]);
+        assert_eq!(
+            Some(InvalidBlockReason::InsufficientProofOfWork),
+            state_lock
+                .lock_guard_mut()
+                .await
+                .invalid_blocks
+                .get(bad_proofs_digest)
+        );
 
-        let from_main_rx_clone = peer_broadcast_tx.subscribe();
+        Ok(())
+    }
 
-        let peer_loop_handler = PeerLoopHandler::new(
-            to_main_tx.clone(),
-            state_lock.clone(),
-            peer_address,
-            hsd,
-            false,
+    #[traced_test]
+    #[tokio::test]
+    async fn alerts_are_verified_and_relayed_test() -> Result<()> {
+        // An alert signed by a trusted key is stored and passed on to main for
+        // relay, once. An alert with a forged signature is sanctioned.
+        let network = Network::Alpha;
+        let (
+            peer_broadcast_tx,
+            _from_main_rx_clone,
+            to_main_tx,
+            mut to_main_rx1,
+            mut state_lock,
+            _hsd,
+        ) = get_test_genesis_setup(network, 0).await?;
+        let secret_key = AlertSecretKey::generate();
+        let cli = cli_args::Args {
+            alert_key: vec![secret_key.public_key()],
+            ..state_lock.cli().clone()
+        };
+        state_lock.set_cli(cli).await;
+
+        let now = Timestamp::now();
+        let alert = Alert {
+            id: 1,
+            network,
+            severity: AlertSeverity::Critical,
+            message: "critical bug, upgrade to v0.0.6".to_string(),
+            created: now,
+            expires: now + Timestamp::days(1),
+        }
+        .sign(&secret_key);
+        let mut forged_alert = alert.clone();
+        forged_alert.alert.id = 2;
+
+        let (hsd, peer_address) = get_dummy_peer_connection_data_genesis(network, 0).await;
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::Alert(Box::new(alert.clone()))),
+            Action::Read(PeerMessage::Alert(Box::new(alert.clone()))),
+            Action::Read(PeerMessage::Alert(Box::new(forged_alert))),
+            Action::Read(PeerMessage::Bye),
+        ]);
+        let peer_loop_handler =
+            PeerLoopHandler::new(to_main_tx, state_lock.clone(), peer_address, hsd, true, 1);
+        peer_loop_handler
+            .run_wrapper(mock, peer_broadcast_tx.subscribe())
+            .await?;
+
+        let mut relayed_alerts = vec![];
+        while let Ok(message) = to_main_rx1.try_recv() {
+            if let PeerThreadToMain::Alert(relayed_alert) = message {
+                relayed_alerts.push(*relayed_alert);
+            }
+        }
+        assert_eq!(vec![alert.clone()], relayed_alerts);
+        assert_eq!(
+            vec![alert],
+            state_lock.lock_guard().await.net.alerts.active(now)
+        );
+
+        let standing = state_lock
+            .lock_guard()
+            .await
+            .net
+            .get_peer_standing_from_database(peer_address.ip())
+            .await
+            .unwrap();
+        assert_eq!(
+            Some(PeerSanctionReason::InvalidAlert),
+            standing.latest_sanction
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn header_chain_proof_request_is_answered() -> Result<()> {
+        let network = Network::Alpha;
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let peer_address = get_dummy_socket_address(0);
+        let heights: Vec<BlockHeight> = vec![1u64.into()];
+        let expected_proof = state_lock
+            .lock_guard()
+            .await
+            .chain
+            .archival_state()
+            .header_chain_proof(&heights)
+            .await?;
+        expected_proof.verify(&heights)?;
+
+        let too_many_heights = vec![BlockHeight::from(1u64); MAX_HEADER_CHAIN_PROOF_SAMPLES + 1];
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::HeaderChainProofRequest(heights)),
+            Action::Write(PeerMessage::HeaderChainProofResponse(Box::new(
+                expected_proof,
+            ))),
+            Action::Read(PeerMessage::HeaderChainProofRequest(too_many_heights)),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let peer_loop_handler =
+            PeerLoopHandler::new(to_main_tx, state_lock.clone(), peer_address, hsd, true, 1);
+        peer_loop_handler
+            .run_wrapper(mock, peer_broadcast_tx.subscribe())
+            .await?;
+
+        let standing = state_lock
+            .lock_guard()
+            .await
+            .net
+            .get_peer_standing_from_database(peer_address.ip())
+            .await;
+        assert_eq!(
+            Some(PeerSanctionReason::InvalidMessage),
+            standing.unwrap().latest_sanction
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn header_chain_proof_requests_are_rate_limited() -> Result<()> {
+        let network = Network::Alpha;
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let peer_address = get_dummy_socket_address(0);
+        let heights: Vec<BlockHeight> = vec![1u64.into()];
+        let expected_proof = state_lock
+            .lock_guard()
+            .await
+            .chain
+            .archival_state()
+            .header_chain_proof(&heights)
+            .await?;
+
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::HeaderChainProofRequest(heights.clone())),
+            Action::Write(PeerMessage::HeaderChainProofResponse(Box::new(
+                expected_proof,
+            ))),
+            Action::Read(PeerMessage::HeaderChainProofRequest(heights)),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let peer_loop_handler =
+            PeerLoopHandler::new(to_main_tx, state_lock.clone(), peer_address, hsd, true, 1);
+        peer_loop_handler
+            .run_wrapper(mock, peer_broadcast_tx.subscribe())
+            .await?;
+
+        let standing = state_lock
+            .lock_guard()
+            .await
+            .net
+            .get_peer_standing_from_database(peer_address.ip())
+            .await;
+        assert_eq!(
+            Some(PeerSanctionReason::HeaderChainProofRequestFlood),
+            standing.unwrap().latest_sanction
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn proofs_are_omitted_for_peers_that_ask() -> Result<()> {
+        let mut rng = thread_rng();
+        let network = Network::Alpha;
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let peer_address = get_dummy_socket_address(0);
+        let genesis_block = Block::genesis_block(network);
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let (block_1, _, _) =
+            make_mock_block_with_valid_pow(&genesis_block, None, address, rng.gen());
+        state_lock
+            .lock_guard_mut()
+            .await
+            .set_new_tip(block_1.clone())
+            .await?;
+
+        let full_block = TransferBlock::from(block_1.clone());
+        let stripped_block = full_block.clone().without_proofs();
+        assert!(stripped_block.proofs_omitted());
+        assert_eq!(block_1.hash(), Block::from(stripped_block.clone()).hash());
+
+        // This node did not ask for blocks without proofs, so it asks again for
+        // the proofs of a block that lacks them
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::OmitBlockProofsRequest),
+            Action::Read(PeerMessage::BlockRequestByHash(block_1.hash())),
+            Action::Write(PeerMessage::Block(Box::new(stripped_block.clone()))),
+            Action::Read(PeerMessage::BlockRequestWithProofs(block_1.hash())),
+            Action::Write(PeerMessage::Block(Box::new(full_block))),
+            Action::Read(PeerMessage::Block(Box::new(stripped_block))),
+            Action::Write(PeerMessage::BlockRequestWithProofs(block_1.hash())),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let peer_loop_handler =
+            PeerLoopHandler::new(to_main_tx, state_lock.clone(), peer_address, hsd, true, 1);
+        peer_loop_handler
+            .run_wrapper(mock, peer_broadcast_tx.subscribe())
+            .await?;
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn test_peer_loop_block_with_block_in_db() -> Result<()> {
+        let mut rng = thread_rng();
+        // The scenario tested here is that a client receives a block that is already
+        // known and stored. The expected behavior is to ignore the block and not send
+        // a message to the main thread.
+        let network = Network::Alpha;
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, mut to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let mut global_state_mut = state_lock.lock_guard_mut().await;
+        let peer_address = get_dummy_socket_address(0);
+        let genesis_block: Block = global_state_mut.chain.archival_state().get_tip().await;
+
+        let a_wallet_secret = WalletSecret::new_random();
+        let a_recipient_address = a_wallet_secret.nth_generation_spending_key(0).to_address();
+        let (block_1, _, _) =
+            make_mock_block_with_valid_pow(&genesis_block, None, a_recipient_address, rng.gen());
+        global_state_mut.set_new_tip(block_1.clone()).await?;
+        drop(global_state_mut);
+
+        let mock_peer_messages = Mock::new(vec![
+            Action::Read(PeerMessage::Block(Box::new(block_1.into()))),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let from_main_rx_clone = peer_broadcast_tx.subscribe();
+
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx.clone(),
+            state_lock.clone(),
+            peer_address,
+            hsd,
+            false,
             1,
         );
         peer_loop_handler
@@ -1744,6 +2936,7 @@ mod peer_loop_tests {
 
         let (mock_block_1, _, _) =
             make_mock_block_with_valid_pow(&genesis_block, None, a_recipient_address, rng.gen());
+        let block_1_digest = mock_block_1.hash();
         let mock = Mock::new(vec![
             Action::Read(PeerMessage::Block(Box::new(mock_block_1.into()))),
             Action::Read(PeerMessage::Bye),
@@ -1782,6 +2975,133 @@ mod peer_loop_tests {
             bail!("peer map must be empty after closing connection gracefully");
         }
 
+        // Verify that the peer is recorded as first announcer, and the validation time
+        let propagation = state_lock.lock_guard().await.block_propagation.report(1);
+        assert_eq!(block_1_digest, propagation.blocks[0].digest);
+        assert_eq!(peer_address, propagation.blocks[0].first_announcer);
+        assert!(propagation.blocks[0].validated_after_millis.is_some());
+        assert_eq!(1, propagation.peers[0].first_announcements);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn late_announcements_of_known_blocks_are_timed_test() -> Result<()> {
+        let network = Network::Alpha;
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let tip = state_lock.lock_guard().await.chain.light_state().clone();
+        let first_announcer = get_dummy_socket_address(1);
+        let late_announcer = get_dummy_socket_address(0);
+        state_lock
+            .lock_guard_mut()
+            .await
+            .block_propagation
+            .record_announcement(
+                tip.hash(),
+                tip.kernel.header.height,
+                first_announcer,
+                Timestamp::now(),
+            );
+
+        // The tip is no longer new, yet its announcement is recorded
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::BlockNotification((&tip).into())),
+            Action::Read(PeerMessage::Bye),
+        ]);
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            late_announcer,
+            hsd,
+            false,
+            1,
+        );
+        peer_loop_handler
+            .run_wrapper(mock, from_main_rx_clone)
+            .await?;
+
+        let propagation = state_lock.lock_guard().await.block_propagation.report(1);
+        assert_eq!(
+            vec![first_announcer, late_announcer],
+            propagation.blocks[0].announcers
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn block_slightly_in_the_future_is_held_test() -> Result<()> {
+        // Scenario: client only knows genesis block. Then receives block 1, whose
+        // timestamp is a minute beyond the future limit. The block must be passed
+        // to main for holding instead of being rejected.
+        let network = Network::RegTest;
+        let mut rng = thread_rng();
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, mut to_main_rx1, state_lock, hsd) =
+            get_test_genesis_setup(network, 0).await?;
+        let a_recipient_address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let peer_address = get_dummy_socket_address(0);
+        let genesis_block: Block = state_lock
+            .lock_guard()
+            .await
+            .chain
+            .archival_state()
+            .get_tip()
+            .await;
+
+        let block_timestamp = Timestamp::now()
+            + Timestamp::hours(FUTUREDATING_LIMIT_IN_HOURS)
+            + Timestamp::minutes(1);
+        let (block_1, _, _) = make_mock_block_with_valid_pow(
+            &genesis_block,
+            Some(block_timestamp),
+            a_recipient_address,
+            rng.gen(),
+        );
+        let block_1_digest = block_1.hash();
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::Block(Box::new(block_1.into()))),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx.clone(),
+            state_lock.clone(),
+            peer_address,
+            hsd,
+            false,
+            1,
+        );
+        peer_loop_handler
+            .run_wrapper(mock, from_main_rx_clone)
+            .await?;
+
+        match to_main_rx1.recv().await {
+            Some(PeerThreadToMain::AddPeerMaxBlockHeight(_)) => (),
+            _ => bail!("Must receive add of peer block max height"),
+        }
+        match to_main_rx1.recv().await {
+            Some(PeerThreadToMain::FutureBlocks(blocks)) => {
+                assert_eq!(
+                    vec![block_1_digest],
+                    blocks.iter().map(Block::hash).collect_vec()
+                )
+            }
+            _ => bail!("Future block must be sent to main for holding"),
+        };
+
+        let standing = state_lock
+            .lock_guard()
+            .await
+            .net
+            .get_peer_standing_from_database(peer_address.ip())
+            .await;
+        assert!(standing.map_or(true, |standing| standing.standing == 0));
+
         Ok(())
     }
 
@@ -2439,4 +3759,428 @@ mod peer_loop_tests {
 
         Ok(())
     }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn blocks_only_node_ignores_peer_transactions_test() -> Result<()> {
+        // A node in blocks-only mode neither requests announced transactions
+        // nor accepts transactions sent to it anyway.
+        let (
+            _peer_broadcast_tx,
+            from_main_rx_clone,
+            to_main_tx,
+            mut to_main_rx1,
+            mut state_lock,
+            _hsd,
+        ) = get_test_genesis_setup(Network::Alpha, 1).await?;
+        let cli = cli_args::Args {
+            blocks_only: true,
+            ..state_lock.cli().clone()
+        };
+        state_lock.set_cli(cli).await;
+
+        let transaction_1 = make_mock_transaction(vec![], vec![]);
+        let tx_notification: TransactionNotification = transaction_1.clone().into();
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::TransactionNotification(tx_notification)),
+            Action::Read(PeerMessage::Transaction(Box::new(transaction_1.clone()))),
+            Action::Read(PeerMessage::StemTransaction(Box::new(transaction_1))),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let (hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            get_dummy_socket_address(0),
+            hsd_1.clone(),
+            true,
+            1,
+        );
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        peer_loop_handler
+            .run(mock, from_main_rx_clone, &mut peer_state)
+            .await?;
+
+        match to_main_rx1.try_recv() {
+            Err(TryRecvError::Empty) => (),
+            Err(TryRecvError::Disconnected) => bail!("to_main channel must still be open"),
+            Ok(_) => bail!("to_main channel must be empty"),
+        }
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn blocks_only_peer_is_not_sent_transactions_test() -> Result<()> {
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 1).await?;
+        let peer_address = *state_lock
+            .lock_guard()
+            .await
+            .net
+            .peer_map
+            .keys()
+            .next()
+            .unwrap();
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::BlocksOnly),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let (hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            peer_address,
+            hsd_1.clone(),
+            true,
+            1,
+        );
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        peer_loop_handler
+            .run(mock, from_main_rx_clone, &mut peer_state)
+            .await?;
+        assert!(peer_state.blocks_only);
+        assert!(state_lock.lock_guard().await.net.peer_map[&peer_address].blocks_only);
+
+        // Sending the notification would fail, as the mock expects no message
+        let transaction_notification = make_mock_transaction(vec![], vec![]).into();
+        assert!(
+            !peer_loop_handler
+                .handle_main_thread_message(
+                    MainToPeerThread::TransactionNotification(transaction_notification),
+                    &mut Mock::<PeerMessage>::new(vec![]),
+                    &mut peer_state,
+                )
+                .await?
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn mempool_requests_are_answered_and_rate_limited_test() -> Result<()> {
+        // A mempool request is answered with the mempool's inventory, a second
+        // one right after is sanctioned. Unrequested responses are ignored.
+        let network = Network::Alpha;
+        let (peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(network, 0).await?;
+
+        let transaction_1 = make_mock_transaction(vec![], vec![]);
+        state_lock
+            .lock_guard_mut()
+            .await
+            .mempool
+            .insert(&transaction_1);
+        let tx_notification: TransactionNotification = transaction_1.into();
+        let unknown = TransactionNotification {
+            transaction_digest: random(),
+        };
+
+        let (hsd, peer_address) = get_dummy_peer_connection_data_genesis(network, 0).await;
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::MempoolRequest),
+            Action::Write(PeerMessage::MempoolResponse(vec![tx_notification])),
+            Action::Read(PeerMessage::MempoolRequest),
+            Action::Read(PeerMessage::MempoolResponse(vec![unknown])),
+            Action::Read(PeerMessage::Bye),
+        ]);
+        let peer_loop_handler =
+            PeerLoopHandler::new(to_main_tx, state_lock.clone(), peer_address, hsd, true, 1);
+        peer_loop_handler
+            .run_wrapper(mock, peer_broadcast_tx.subscribe())
+            .await?;
+
+        let standing = state_lock
+            .lock_guard()
+            .await
+            .net
+            .get_peer_standing_from_database(peer_address.ip())
+            .await
+            .unwrap();
+        assert_eq!(
+            Some(PeerSanctionReason::MempoolRequestFlood),
+            standing.latest_sanction
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn missing_transactions_of_requested_mempool_are_fetched_test() -> Result<()> {
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 1).await?;
+
+        let known_transaction = make_mock_transaction(vec![], vec![]);
+        state_lock
+            .lock_guard_mut()
+            .await
+            .mempool
+            .insert(&known_transaction);
+        let known: TransactionNotification = known_transaction.into();
+        let unknown = TransactionNotification {
+            transaction_digest: random(),
+        };
+
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::MempoolResponse(vec![known, unknown])),
+            Action::Write(PeerMessage::TransactionRequest(unknown.transaction_digest)),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let (hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            get_dummy_socket_address(0),
+            hsd_1.clone(),
+            true,
+            1,
+        );
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        peer_state.mempool_requested = true;
+        peer_loop_handler
+            .run(mock, from_main_rx_clone, &mut peer_state)
+            .await?;
+        assert!(!peer_state.mempool_requested);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn requested_clock_of_peer_is_recorded_test() -> Result<()> {
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 1).await?;
+        let peer_address = *state_lock
+            .lock_guard()
+            .await
+            .net
+            .peer_map
+            .keys()
+            .next()
+            .unwrap();
+        assert_eq!(
+            None,
+            state_lock.lock_guard().await.net.peer_map[&peer_address].clock_offset_millis
+        );
+
+        let requested_at = Timestamp::now();
+        let peer_clock = requested_at + Timestamp::hours(1);
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::Clock(peer_clock)),
+            Action::Read(PeerMessage::Clock(peer_clock + Timestamp::hours(1))),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let (hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            peer_address,
+            hsd_1.clone(),
+            true,
+            1,
+        );
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        peer_state.clock_requested_at = Some(requested_at);
+        peer_loop_handler
+            .run(mock, from_main_rx_clone, &mut peer_state)
+            .await?;
+
+        // Only the requested clock counts, which is about an hour ahead
+        let offset = state_lock.lock_guard().await.net.peer_map[&peer_address]
+            .clock_offset_millis
+            .unwrap();
+        assert!((3_500_000..=3_600_000).contains(&offset));
+        assert!(peer_state.clock_requested_at.is_none());
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn advertised_listen_addresses_are_recorded_test() -> Result<()> {
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 1).await?;
+        let peer_address = *state_lock
+            .lock_guard()
+            .await
+            .net
+            .peer_map
+            .keys()
+            .next()
+            .unwrap();
+        let listen_addresses: Vec<SocketAddr> =
+            vec!["0.0.0.0:9798".parse()?, "[2001:db8::1]:9798".parse()?];
+        let too_many_listen_addresses =
+            vec![get_dummy_socket_address(1); MAX_ADVERTISED_LISTEN_ADDRESSES + 1];
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::ListenAddresses(listen_addresses.clone())),
+            Action::Read(PeerMessage::ListenAddresses(too_many_listen_addresses)),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let (hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            peer_address,
+            hsd_1.clone(),
+            true,
+            1,
+        );
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        peer_loop_handler
+            .run(mock, from_main_rx_clone, &mut peer_state)
+            .await?;
+
+        let global_state = state_lock.lock_guard().await;
+        let peer_info = &global_state.net.peer_map[&peer_address];
+        assert_eq!(listen_addresses, peer_info.advertised_listen_addresses);
+        assert!(peer_info
+            .known_listen_addresses()
+            .contains(&SocketAddr::new(peer_address.ip(), 9798)));
+        assert_eq!(
+            Some(PeerSanctionReason::InvalidMessage),
+            peer_info.standing.latest_sanction
+        );
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn message_extensions_are_not_sent_to_old_peers_test() -> Result<()> {
+        let (_peer_broadcast_tx, _from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 0).await?;
+        let (mut hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        "0.0.5".clone_into(&mut hsd_1.version);
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            get_dummy_socket_address(0),
+            hsd_1.clone(),
+            true,
+            1,
+        );
+
+        // Sending any message would fail, as the mock expects none
+        let mut mock = Mock::<PeerMessage>::new(vec![]);
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        let transaction = make_mock_transaction(vec![], vec![]);
+        let now = Timestamp::now();
+        let alert = Alert {
+            id: 1,
+            network: Network::Alpha,
+            severity: AlertSeverity::Warning,
+            message: "upgrade".to_string(),
+            created: now,
+            expires: now + Timestamp::days(1),
+        }
+        .sign(&AlertSecretKey::generate());
+        for message in [
+            MainToPeerThread::ClockRequest,
+            MainToPeerThread::StemTransaction(Box::new(transaction), get_dummy_socket_address(0)),
+            MainToPeerThread::Alert(Box::new(alert)),
+            MainToPeerThread::MempoolRequest(get_dummy_socket_address(0)),
+        ] {
+            assert!(
+                !peer_loop_handler
+                    .handle_main_thread_message(message, &mut mock, &mut peer_state)
+                    .await?
+            );
+        }
+        assert!(peer_state.clock_requested_at.is_none());
+        assert!(!peer_state.mempool_requested);
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn stem_transaction_is_passed_to_main_with_sender_test() -> Result<()> {
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, mut to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 1).await?;
+
+        let transaction_1 = make_mock_transaction(vec![], vec![]);
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::StemTransaction(Box::new(
+                transaction_1.clone(),
+            ))),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let (hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        let peer_address = get_dummy_socket_address(0);
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            peer_address,
+            hsd_1.clone(),
+            true,
+            1,
+        );
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        peer_loop_handler
+            .run(mock, from_main_rx_clone, &mut peer_state)
+            .await?;
+
+        match to_main_rx1.recv().await {
+            Some(PeerThreadToMain::Transaction(pt2m_transaction)) => {
+                assert_eq!(transaction_1, pt2m_transaction.transaction);
+                assert_eq!(Some(peer_address), pt2m_transaction.stem_from);
+            }
+            _ => bail!("Must receive stem-phase transaction"),
+        }
+
+        Ok(())
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn announced_stem_transaction_leaves_stem_pool_test() -> Result<()> {
+        // A transaction this node holds in the stem phase is announced by a peer, so
+        // it has been diffused and this node must fetch it like any other.
+        let (_peer_broadcast_tx, from_main_rx_clone, to_main_tx, _to_main_rx1, state_lock, _hsd) =
+            get_test_genesis_setup(Network::Alpha, 1).await?;
+
+        let transaction_1 = make_mock_transaction(vec![], vec![]);
+        state_lock.lock_guard_mut().await.net.stem_pool.insert(
+            transaction_1.clone(),
+            Timestamp::now(),
+            &mut rand::thread_rng(),
+        );
+
+        let tx_notification: TransactionNotification = transaction_1.into();
+        let mock = Mock::new(vec![
+            Action::Read(PeerMessage::TransactionNotification(tx_notification)),
+            Action::Write(PeerMessage::TransactionRequest(
+                tx_notification.transaction_digest,
+            )),
+            Action::Read(PeerMessage::Bye),
+        ]);
+
+        let (hsd_1, _sa_1) = get_dummy_peer_connection_data_genesis(Network::Alpha, 1).await;
+        let peer_loop_handler = PeerLoopHandler::new(
+            to_main_tx,
+            state_lock.clone(),
+            get_dummy_socket_address(0),
+            hsd_1.clone(),
+            true,
+            1,
+        );
+        let mut peer_state = MutablePeerState::new(hsd_1.tip_header.height);
+        peer_loop_handler
+            .run(mock, from_main_rx_clone, &mut peer_state)
+            .await?;
+
+        assert!(state_lock.lock_guard().await.net.stem_pool.is_empty());
+
+        Ok(())
+    }
 }
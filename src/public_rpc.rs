@@ -0,0 +1,493 @@
+//! A public, read-only RPC endpoint, for block-explorer-style APIs served
+//! from the same node as the full RPC.
+//!
+//! The [`PublicRPC`] service is a subset of [`RPC`]: it only reads chain and
+//! mempool data, and has no access to the wallet, to peers, or to anything
+//! that changes state. It listens on `--public-rpc-port`, separately from the
+//! full RPC, and answers each IP at most `--public-rpc-requests-per-minute`
+//! times per minute. IPv6 addresses are limited by their /64 prefix, which is
+//! what a single host is typically assigned. Requests beyond that fail with
+//! [`PublicRpcError::RateLimited`]. Simultaneous connections are limited by
+//! the same address, and closed when idle for [`PUBLIC_RPC_IDLE_TIMEOUT`].
+//!
+//! Responses must fit in a frame of [`MAX_PUBLIC_RPC_FRAME_LENGTH`] bytes,
+//! which also bounds what a client can send. Blocks and transactions that
+//! do not fit fail with [`PublicRpcError::ResponseTooLarge`]; they can be
+//! had from the full RPC.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use futures::{future, StreamExt};
+use serde::{Deserialize, Serialize};
+use tarpc::context;
+use tarpc::server::incoming::Incoming;
+use tarpc::server::{self, Channel};
+use tarpc::tokio_serde::formats::Json;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::config_models::network::Network;
+use crate::models::blockchain::block::block_detail::BlockDetail;
+use crate::models::blockchain::block::block_header::BlockHeader;
+use crate::models::blockchain::block::block_height::BlockHeight;
+use crate::models::blockchain::block::block_info::BlockInfo;
+use crate::models::blockchain::block::block_selector::BlockSelector;
+use crate::models::channel::RPCServerToMain;
+use crate::models::state::finality::FinalizedBlock;
+use crate::models::state::GlobalStateLock;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::rpc_server::{NeptuneRPCServer, RPC};
+
+/// Max number of digests returned by `latest_tip_digests`
+pub const MAX_PUBLIC_TIP_DIGESTS: usize = 100;
+
+/// Max number of simultaneous connections from one IP, or one /64 prefix for
+/// IPv6
+const MAX_PUBLIC_CHANNELS_PER_IP: u32 = 5;
+
+/// Connections that send no request for this long are closed
+pub const PUBLIC_RPC_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Max number of simultaneous connections
+const MAX_PUBLIC_CHANNELS: usize = 100;
+
+/// Max number of IPs whose requests are tracked
+const MAX_TRACKED_IPS: usize = 10_000;
+
+/// Max size of a request or response frame. Requests are small; the largest
+/// responses are blocks.
+pub const MAX_PUBLIC_RPC_FRAME_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Max size of a response, leaving room in its frame for the envelope that
+/// identifies the request
+const MAX_PUBLIC_RPC_RESPONSE_LENGTH: usize = MAX_PUBLIC_RPC_FRAME_LENGTH - 64 * 1024;
+
+#[derive(Clone, Copy, Debug, Error, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PublicRpcError {
+    #[error("too many requests, try again later")]
+    RateLimited,
+
+    #[error("response does not fit in a frame of the public RPC")]
+    ResponseTooLarge,
+}
+
+/// Fail with [`PublicRpcError::ResponseTooLarge`] if `response` does not fit
+/// in a frame, which the transport would otherwise fail to send
+fn fit_in_frame<T: Serialize>(response: T) -> Result<T, PublicRpcError> {
+    match serde_json::to_vec(&response) {
+        Ok(encoded) if encoded.len() <= MAX_PUBLIC_RPC_RESPONSE_LENGTH => Ok(response),
+        _ => Err(PublicRpcError::ResponseTooLarge),
+    }
+}
+
+#[tarpc::service]
+pub trait PublicRPC {
+    /// Return which network the node runs on
+    async fn network() -> Result<Network, PublicRpcError>;
+
+    /// Return the height of the tip
+    async fn block_height() -> Result<BlockHeight, PublicRpcError>;
+
+    /// Return the digests of the tip and its ancestors, at most
+    /// [`MAX_PUBLIC_TIP_DIGESTS`]
+    async fn latest_tip_digests(n: usize) -> Result<Vec<Digest>, PublicRpcError>;
+
+    /// Return information about the specified block, if found
+    async fn block_info(block_selector: BlockSelector)
+        -> Result<Option<BlockInfo>, PublicRpcError>;
+
+    /// Return the digest of the specified block, if found
+    async fn block_digest(block_selector: BlockSelector) -> Result<Option<Digest>, PublicRpcError>;
+
+    /// Return the header of the specified block, if found
+    async fn header(block_selector: BlockSelector) -> Result<Option<BlockHeader>, PublicRpcError>;
+
+    /// Return the canonical encoding of the stored block with the given
+    /// digest, as hex, if found. Fails for blocks that do not fit in a frame.
+    async fn get_block_hex(digest: Digest) -> Result<Option<String>, PublicRpcError>;
+
+    /// Return the block at the given height on the chain of the tip, with its
+    /// transaction kernel, if found
+    async fn get_block_by_height(
+        height: BlockHeight,
+    ) -> Result<Option<BlockDetail>, PublicRpcError>;
+
+    /// Return the stored block with the given digest, with its transaction
+    /// kernel, if found
+    async fn get_block_by_digest(digest: Digest) -> Result<Option<BlockDetail>, PublicRpcError>;
+
+    /// Return the canonical encoding of the mempool transaction with the given
+    /// digest, as hex, if found
+    async fn get_transaction_hex(
+        transaction_digest: Digest,
+    ) -> Result<Option<String>, PublicRpcError>;
+
+    /// Return the digest of the AOCL leaf with the given index, if found
+    async fn utxo_digest(leaf_index: u64) -> Result<Option<Digest>, PublicRpcError>;
+
+    /// Return the number of transactions in the mempool
+    async fn mempool_tx_count() -> Result<usize, PublicRpcError>;
+
+    /// Return the size of the mempool in bytes
+    async fn mempool_size() -> Result<usize, PublicRpcError>;
+
+    /// Return the last block marked final, if any
+    async fn finalized_block() -> Result<Option<FinalizedBlock>, PublicRpcError>;
+}
+
+/// Limits the number of requests per IP in a sliding window of a minute
+#[derive(Debug)]
+pub struct PublicRpcRateLimiter {
+    requests_per_minute: usize,
+    requests: HashMap<IpAddr, VecDeque<Instant>>,
+}
+
+impl PublicRpcRateLimiter {
+    pub fn new(requests_per_minute: usize) -> Self {
+        Self {
+            requests_per_minute,
+            requests: HashMap::new(),
+        }
+    }
+
+    /// Record a request from `ip` at time `now`. Returns false if the IP has
+    /// made the max number of requests in the past minute, in which case the
+    /// request must be rejected.
+    pub fn allow(&mut self, ip: IpAddr, now: Instant) -> bool {
+        let minute = Duration::from_secs(60);
+        let ip = rate_limited_prefix(ip);
+        if !self.requests.contains_key(&ip) && self.requests.len() >= MAX_TRACKED_IPS {
+            self.requests.retain(|_, requests| {
+                requests
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) <= minute)
+            });
+
+            // All tracked IPs made requests in the past minute, so forget the
+            // one that has been quiet the longest
+            if self.requests.len() >= MAX_TRACKED_IPS {
+                let quietest = self
+                    .requests
+                    .iter()
+                    .min_by_key(|(_, requests)| requests.back().copied())
+                    .map(|(ip, _)| *ip);
+                if let Some(quietest) = quietest {
+                    self.requests.remove(&quietest);
+                }
+            }
+        }
+
+        let requests = self.requests.entry(ip).or_default();
+        while requests
+            .front()
+            .is_some_and(|request| now.duration_since(*request) > minute)
+        {
+            requests.pop_front();
+        }
+
+        if requests.len() >= self.requests_per_minute {
+            return false;
+        }
+        requests.push_back(now);
+        true
+    }
+}
+
+/// The address that requests are limited by: the IP itself for IPv4, the /64
+/// prefix for IPv6
+fn rate_limited_prefix(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(ipv6) => {
+            let prefix = u128::from(ipv6) & (u128::MAX << 64);
+            IpAddr::V6(Ipv6Addr::from(prefix))
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NeptunePublicRPCServer {
+    rpc: NeptuneRPCServer,
+    rate_limiter: Arc<Mutex<PublicRpcRateLimiter>>,
+}
+
+impl NeptunePublicRPCServer {
+    fn check_rate_limit(&self) -> Result<(), PublicRpcError> {
+        let ip = self.rpc.socket_address.ip().to_canonical();
+        let allowed = self
+            .rate_limiter
+            .lock()
+            .expect("rate limiter lock")
+            .allow(ip, Instant::now());
+        if allowed {
+            Ok(())
+        } else {
+            Err(PublicRpcError::RateLimited)
+        }
+    }
+}
+
+impl PublicRPC for NeptunePublicRPCServer {
+    async fn network(self, ctx: context::Context) -> Result<Network, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::network(self.rpc, ctx).await)
+    }
+
+    async fn block_height(self, ctx: context::Context) -> Result<BlockHeight, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::block_height(self.rpc, ctx).await)
+    }
+
+    async fn latest_tip_digests(
+        self,
+        ctx: context::Context,
+        n: usize,
+    ) -> Result<Vec<Digest>, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::latest_tip_digests(self.rpc, ctx, n.min(MAX_PUBLIC_TIP_DIGESTS)).await)
+    }
+
+    async fn block_info(
+        self,
+        ctx: context::Context,
+        block_selector: BlockSelector,
+    ) -> Result<Option<BlockInfo>, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::block_info(self.rpc, ctx, block_selector).await)
+    }
+
+    async fn block_digest(
+        self,
+        ctx: context::Context,
+        block_selector: BlockSelector,
+    ) -> Result<Option<Digest>, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::block_digest(self.rpc, ctx, block_selector).await)
+    }
+
+    async fn header(
+        self,
+        ctx: context::Context,
+        block_selector: BlockSelector,
+    ) -> Result<Option<BlockHeader>, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::header(self.rpc, ctx, block_selector).await)
+    }
+
+    async fn get_block_hex(
+        self,
+        ctx: context::Context,
+        digest: Digest,
+    ) -> Result<Option<String>, PublicRpcError> {
+        self.check_rate_limit()?;
+        fit_in_frame(RPC::get_block_hex(self.rpc, ctx, digest).await)
+    }
+
+    async fn get_block_by_height(
+        self,
+        ctx: context::Context,
+        height: BlockHeight,
+    ) -> Result<Option<BlockDetail>, PublicRpcError> {
+        self.check_rate_limit()?;
+        fit_in_frame(RPC::get_block_by_height(self.rpc, ctx, height).await)
+    }
+
+    async fn get_block_by_digest(
+        self,
+        ctx: context::Context,
+        digest: Digest,
+    ) -> Result<Option<BlockDetail>, PublicRpcError> {
+        self.check_rate_limit()?;
+        fit_in_frame(RPC::get_block_by_digest(self.rpc, ctx, digest).await)
+    }
+
+    async fn get_transaction_hex(
+        self,
+        ctx: context::Context,
+        transaction_digest: Digest,
+    ) -> Result<Option<String>, PublicRpcError> {
+        self.check_rate_limit()?;
+        fit_in_frame(RPC::get_transaction_hex(self.rpc, ctx, transaction_digest).await)
+    }
+
+    async fn utxo_digest(
+        self,
+        ctx: context::Context,
+        leaf_index: u64,
+    ) -> Result<Option<Digest>, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::utxo_digest(self.rpc, ctx, leaf_index).await)
+    }
+
+    async fn mempool_tx_count(self, ctx: context::Context) -> Result<usize, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::mempool_tx_count(self.rpc, ctx).await)
+    }
+
+    async fn mempool_size(self, ctx: context::Context) -> Result<usize, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::mempool_size(self.rpc, ctx).await)
+    }
+
+    async fn finalized_block(
+        self,
+        ctx: context::Context,
+    ) -> Result<Option<FinalizedBlock>, PublicRpcError> {
+        self.check_rate_limit()?;
+        Ok(RPC::finalized_block(self.rpc, ctx).await)
+    }
+}
+
+/// Listen for public RPC connections on `--public-rpc-listen-addr` and
+/// `--public-rpc-port`, and serve them from a new task.
+pub async fn serve(
+    public_rpc_port: u16,
+    state: GlobalStateLock,
+    rpc_server_to_main_tx: tokio::sync::mpsc::Sender<RPCServerToMain>,
+) -> Result<JoinHandle<()>> {
+    let address = SocketAddr::new(state.cli().public_rpc_listen_addr, public_rpc_port);
+    let mut listener = tarpc::serde_transport::tcp::listen(address, Json::default)
+        .await
+        .with_context(|| format!("Failed to bind public RPC server to {address}"))?;
+    listener
+        .config_mut()
+        .max_frame_length(MAX_PUBLIC_RPC_FRAME_LENGTH);
+
+    let rate_limiter = Arc::new(Mutex::new(PublicRpcRateLimiter::new(
+        state.cli().public_rpc_requests_per_minute,
+    )));
+
+    let join_handle = tokio::spawn(async move {
+        listener
+            // Ignore accept errors.
+            .filter_map(|r| future::ready(r.ok()))
+            .map(server::BaseChannel::with_defaults)
+            .max_channels_per_key(MAX_PUBLIC_CHANNELS_PER_IP, |t| {
+                rate_limited_prefix(t.transport().peer_addr().unwrap().ip().to_canonical())
+            })
+            .map(move |channel| {
+                let peer_address = channel.transport().peer_addr().unwrap();
+                let server = NeptunePublicRPCServer {
+                    rpc: NeptuneRPCServer {
+                        socket_address: peer_address,
+                        state: state.clone(),
+                        rpc_server_to_main_tx: rpc_server_to_main_tx.clone(),
+                    },
+                    rate_limiter: rate_limiter.clone(),
+                };
+
+                // Dropping the channel closes the connection
+                let mut requests = Box::pin(channel.execute(server.serve()));
+                async move {
+                    loop {
+                        match tokio::time::timeout(PUBLIC_RPC_IDLE_TIMEOUT, requests.next()).await {
+                            Ok(Some(request)) => {
+                                tokio::spawn(request);
+                            }
+                            Ok(None) => break,
+                            Err(_) => {
+                                debug!("Closing idle public RPC connection from {peer_address}");
+                                break;
+                            }
+                        }
+                    }
+                }
+            })
+            .buffer_unordered(MAX_PUBLIC_CHANNELS)
+            .for_each(|_| async {})
+            .await;
+    });
+
+    Ok(join_handle)
+}
+
+#[cfg(test)]
+mod public_rpc_tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::models::state::wallet::WalletSecret;
+    use crate::tests::shared::mock_genesis_global_state;
+
+    #[tokio::test]
+    async fn requests_are_rate_limited_per_ip() {
+        let network = Network::RegTest;
+        let state = mock_genesis_global_state(network, 0, WalletSecret::new_random()).await;
+        let (rpc_server_to_main_tx, _rx) = tokio::sync::mpsc::channel(1);
+        let rate_limiter = Arc::new(Mutex::new(PublicRpcRateLimiter::new(2)));
+        let server = |ip: Ipv4Addr| NeptunePublicRPCServer {
+            rpc: NeptuneRPCServer {
+                socket_address: SocketAddr::new(IpAddr::V4(ip), 8080),
+                state: state.clone(),
+                rpc_server_to_main_tx: rpc_server_to_main_tx.clone(),
+            },
+            rate_limiter: rate_limiter.clone(),
+        };
+
+        let ip = Ipv4Addr::new(192, 0, 2, 1);
+        let ctx = context::current();
+        assert_eq!(Ok(network), server(ip).network(ctx).await);
+        assert_eq!(
+            Ok(BlockHeight::genesis()),
+            server(ip).block_height(ctx).await
+        );
+        assert_eq!(
+            Err(PublicRpcError::RateLimited),
+            server(ip).network(ctx).await
+        );
+
+        // Other IPs have their own limit
+        let other_ip = Ipv4Addr::new(192, 0, 2, 2);
+        assert_eq!(Ok(network), server(other_ip).network(ctx).await);
+
+        // The limit is per minute
+        let mut rate_limiter = rate_limiter.lock().unwrap();
+        let later = Instant::now() + Duration::from_secs(61);
+        assert!(rate_limiter.allow(IpAddr::V4(ip), later));
+    }
+
+    #[test]
+    fn responses_that_do_not_fit_in_a_frame_are_refused() {
+        let hex = "00".repeat(MAX_PUBLIC_RPC_RESPONSE_LENGTH / 4);
+        assert_eq!(Ok(Some(hex.clone())), fit_in_frame(Some(hex)));
+
+        let hex = "00".repeat(MAX_PUBLIC_RPC_RESPONSE_LENGTH / 2);
+        assert_eq!(
+            Err(PublicRpcError::ResponseTooLarge),
+            fit_in_frame(Some(hex))
+        );
+    }
+
+    #[test]
+    fn ipv6_hosts_are_limited_by_prefix() {
+        let mut rate_limiter = PublicRpcRateLimiter::new(1);
+        let now = Instant::now();
+        let ip: IpAddr = "2001:db8:0:1::1".parse().unwrap();
+        let same_prefix: IpAddr = "2001:db8:0:1:ffff::2".parse().unwrap();
+        let other_prefix: IpAddr = "2001:db8:0:2::1".parse().unwrap();
+
+        assert!(rate_limiter.allow(ip, now));
+        assert!(!rate_limiter.allow(same_prefix, now));
+        assert!(rate_limiter.allow(other_prefix, now));
+    }
+
+    #[test]
+    fn tracked_ips_are_bounded_when_all_are_recent() {
+        let mut rate_limiter = PublicRpcRateLimiter::new(1);
+        let now = Instant::now();
+        for i in 0..=MAX_TRACKED_IPS as u32 {
+            let ip = IpAddr::V4(Ipv4Addr::from(i));
+            assert!(rate_limiter.allow(ip, now + Duration::from_millis(i.into())));
+        }
+
+        assert_eq!(MAX_TRACKED_IPS, rate_limiter.requests.len());
+        assert!(!rate_limiter
+            .requests
+            .contains_key(&IpAddr::V4(Ipv4Addr::from(0))));
+    }
+}
@@ -3,37 +3,92 @@ use crate::models::consensus::timestamp::Timestamp;
 use crate::models::state::wallet::coin_with_possible_timelock::CoinWithPossibleTimeLock;
 use crate::prelude::twenty_first;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use futures::future;
+use futures::Future;
+use futures::StreamExt;
 use get_size::GetSize;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::IpAddr;
 use std::net::SocketAddr;
 use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 use systemstat::{Platform, System};
 use tarpc::context;
+use tarpc::server;
+use tarpc::server::incoming::Incoming;
+use tarpc::server::Channel;
+use tarpc::tokio_serde::formats::Json;
+use thiserror::Error;
 use tokio::sync::mpsc::error::SendError;
+use tokio::task::JoinHandle;
 use tracing::{error, info};
 use twenty_first::math::digest::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 
+use crate::config_models::data_directory::DataDirectory;
 use crate::config_models::network::Network;
+use crate::health_server::HealthReport;
+use crate::models::blockchain::block::block_detail::BlockDetail;
 use crate::models::blockchain::block::block_header::BlockHeader;
 use crate::models::blockchain::block::block_height::BlockHeight;
 use crate::models::blockchain::block::block_info::BlockInfo;
 use crate::models::blockchain::block::block_selector::BlockSelector;
+use crate::models::blockchain::block::Block;
+use crate::models::blockchain::consensus_encoding::{decode_hex, encode_hex, DecodeError};
 use crate::models::blockchain::shared::Hash;
-use crate::models::blockchain::transaction::utxo::Utxo;
+use crate::models::blockchain::transaction::data_carrier::DataCarrier;
+use crate::models::blockchain::transaction::Transaction;
 use crate::models::channel::RPCServerToMain;
 use crate::models::peer::InstanceId;
 use crate::models::peer::PeerInfo;
 use crate::models::peer::PeerStanding;
+use crate::models::state::alerts::{AlertError, SignedAlert};
+use crate::models::state::block_propagation::BlockPropagationReport;
+use crate::models::state::block_timeline::BlockProcessingTimeline;
+use crate::models::state::block_tree::BlockTree;
+use crate::models::state::connection_metrics::ConnectionMetricsReport;
+use crate::models::state::dashboard_snapshot::DashboardSnapshot;
+use crate::models::state::disk_usage::{DiskUsage, JournalDiskUsage};
+use crate::models::state::event_journal::{JournalEntry, MAX_JOURNAL_REPLAY_COUNT};
+use crate::models::state::finality::{FinalityError, FinalizedBlock};
+use crate::models::state::fork_choice::{check_preferable_tip, fork_tips, ForkTip, PreferTipError};
+use crate::models::state::memory_status::MemoryStatus;
+use crate::models::state::mempool::MempoolEntry;
+use crate::models::state::metrics_history::{MetricsHistory, MetricsSnapshot};
+use crate::models::state::peer_reconnect::ConfiguredPeerStatus;
+use crate::models::state::proving_queue::{ProvingJobId, ProvingJobInfo, ProvingTicket};
+use crate::models::state::supply_audit::SupplyAudit;
+use crate::models::state::sweep::{self, SweepError};
+use crate::models::state::transaction_watch::WatchError;
 use crate::models::state::wallet::address::generation_address;
-use crate::models::state::wallet::wallet_status::WalletStatus;
-use crate::models::state::{GlobalStateLock, UtxoReceiverData};
+use crate::models::state::wallet::address_book::{AddressBookEntry, AddressBookError};
+use crate::models::state::wallet::device_sync::DeviceSyncError;
+use crate::models::state::wallet::historical_index::HistoricalQueryError;
+use crate::models::state::wallet::monitored_utxo::{
+    MonitoredUtxoReport, TransactionHistoryEntry, UtxoOrigin,
+};
+use crate::models::state::wallet::prepared_send::{PreparedSend, SendPreview};
+use crate::models::state::wallet::sent_transactions::SentTransaction;
+use crate::models::state::wallet::shared_control::{
+    SharedControlError, SharedControlLock, SharedSpend,
+};
+use crate::models::state::wallet::spend_policy::{SpendPolicy, SpendPolicyViolation};
+use crate::models::state::wallet::wallet_status::{WalletBalance, WalletStatus};
+use crate::models::state::wallet::{WalletDisabled, WalletSecret};
+use crate::models::state::wallet_rescan::{self, RescanError, RescanProgress};
+use crate::models::state::{GlobalState, GlobalStateLock, TransactionDraft};
+use crate::util_types::digest_serde;
+use crate::util_types::mutator_set::active_window::ActiveWindowStats;
+use crate::util_types::mutator_set::addition_record::AdditionRecord;
+use crate::util_types::mutator_set::ms_membership_evidence::MsMembershipEvidence;
+use crate::util_types::mutator_set::mutator_set_accumulator::MutatorSetAccumulator;
+use crate::util_types::mutator_set::removal_record::AbsoluteIndexSet;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DashBoardOverviewDataFromClient {
+    #[serde(with = "digest_serde")]
     pub tip_digest: Digest,
     pub tip_header: BlockHeader,
     pub syncing: bool,
@@ -54,6 +109,13 @@ pub struct DashBoardOverviewDataFromClient {
 
     /// CPU temperature in degrees Celcius
     pub cpu_temp: Option<f32>,
+
+    /// The running, or the last, rescan of the chain for wallet UTXOs
+    pub wallet_rescan: Option<RescanProgress>,
+
+    /// When the wallet database was last backed up. `None` if it never was,
+    /// or if backups are disabled.
+    pub last_wallet_backup: Option<Timestamp>,
 }
 
 #[tarpc::service]
@@ -63,6 +125,9 @@ pub trait RPC {
     // Return which network the client is running
     async fn network() -> Network;
 
+    /// Return the address on which peers can connect to this node, as
+    /// advertised to them: the first `--announce-address`, or else the first
+    /// address it listens on
     async fn own_listen_address_for_peers() -> Option<SocketAddr>;
 
     /// Return the node's instance-ID which is a globally unique random generated number
@@ -83,6 +148,10 @@ pub trait RPC {
     /// Returns info about the peers we are connected to
     async fn peer_info() -> Vec<PeerInfo>;
 
+    /// Return whether the peers given with `--peers` are connected, and if
+    /// not, when the next attempt to connect to them is made
+    async fn configured_peers() -> Vec<ConfiguredPeerStatus>;
+
     /// Return info about all peers that have been sanctioned
     async fn all_sanctioned_peers() -> HashMap<IpAddr, PeerStanding>;
 
@@ -92,26 +161,85 @@ pub trait RPC {
     /// Returns information about the specified block if found
     async fn block_info(block_selector: BlockSelector) -> Option<BlockInfo>;
 
+    /// Return the canonical encoding of the stored block with the given
+    /// digest, as hex, if found
+    async fn get_block_hex(digest: Digest) -> Option<String>;
+
+    /// Return the block at the given height on the chain of the tip, with its
+    /// transaction kernel, if found. Only available on archival nodes.
+    async fn get_block_by_height(height: BlockHeight) -> Option<BlockDetail>;
+
+    /// Return the stored block with the given digest, with its transaction
+    /// kernel, if found. The block may be on a fork. Only available on archival
+    /// nodes.
+    async fn get_block_by_digest(digest: Digest) -> Option<BlockDetail>;
+
+    /// Return the canonical encoding of the mempool transaction with the given
+    /// digest, as hex, if found
+    async fn get_transaction_hex(transaction_digest: Digest) -> Option<String>;
+
+    /// Decode a block from its canonical encoding as hex, without validating it
+    async fn decode_block(hex: String) -> Result<Block, DecodeError>;
+
+    /// Decode a transaction from its canonical encoding as hex, without
+    /// validating it
+    async fn decode_transaction(hex: String) -> Result<Transaction, DecodeError>;
+
     /// Return the digest for the specified block if found
     async fn block_digest(block_selector: BlockSelector) -> Option<Digest>;
 
+    /// Return the digests of the known blocks with a timestamp between `from`
+    /// and `to`, both inclusive, ordered by timestamp. Only available on
+    /// archival nodes.
+    async fn blocks_in_time_range(from: Timestamp, to: Timestamp) -> Vec<Digest>;
+
     /// Return the digest for the specified UTXO leaf index if found
     async fn utxo_digest(leaf_index: u64) -> Option<Digest>;
 
+    /// Return evidence, verifiable against the tip's mutator set, that the AOCL
+    /// leaf at `leaf_index` exists and of whether the given Bloom filter indices
+    /// are set. Only available on archival nodes.
+    async fn mutator_set_evidence(
+        leaf_index: u64,
+        absolute_indices: Option<AbsoluteIndexSet>,
+    ) -> Option<MutatorSetEvidenceAtTip>;
+
+    /// Sum all coinbases of the chain and check them against the reward schedule
+    /// and the append-only commitment list. Returns `None` on light nodes.
+    async fn audit_supply() -> Option<SupplyAudit>;
+
     /// Return the block header for the specified block
     async fn header(block_selector: BlockSelector) -> Option<BlockHeader>;
 
     /// Get sum of unspent UTXOs.
-    async fn synced_balance() -> NeptuneCoins;
+    async fn synced_balance() -> Result<NeptuneCoins, WalletDisabled>;
+
+    /// Get sum of unspent UTXOs that are time-locked, and cannot be spent yet
+    async fn timelocked_balance() -> Result<NeptuneCoins, WalletDisabled>;
+
+    /// Get the wallet's confirmed balance, and its balance with the mempool's
+    /// transactions applied
+    async fn balance() -> Result<WalletBalance, WalletDisabled>;
 
     /// Get the client's wallet transaction history
-    async fn history() -> Vec<(Digest, BlockHeight, Timestamp, NeptuneCoins)>;
+    async fn history() -> Result<Vec<(Digest, BlockHeight, Timestamp, NeptuneCoins)>, WalletDisabled>;
+
+    /// Get the client's wallet transaction history, sorted by height, with the
+    /// origin of each UTXO. If `kind` is given, only the entries for UTXOs of
+    /// that origin are returned, e.g. to tell mining rewards from revenue.
+    async fn get_transaction_history(
+        kind: Option<UtxoOrigin>,
+    ) -> Result<Vec<TransactionHistoryEntry>, WalletDisabled>;
+
+    /// Return the transactions sent by this wallet since startup, and whether
+    /// they were confirmed or double spent
+    async fn sent_transactions() -> Result<Vec<SentTransaction>, WalletDisabled>;
 
     /// Return information about funds in the wallet
-    async fn wallet_status() -> WalletStatus;
+    async fn wallet_status() -> Result<WalletStatus, WalletDisabled>;
 
     /// Return an address that this client can receive funds on
-    async fn own_receiving_address() -> generation_address::ReceivingAddress;
+    async fn own_receiving_address() -> Result<generation_address::ReceivingAddress, WalletDisabled>;
 
     /// Return the number of transactions in the mempool
     async fn mempool_tx_count() -> usize;
@@ -119,9 +247,25 @@ pub trait RPC {
     // TODO: Change to return current size and max size
     async fn mempool_size() -> usize;
 
+    /// Return the fee, size and time in pool of a transaction in the mempool
+    async fn get_mempool_entry(transaction_digest: Digest) -> Option<MempoolEntry>;
+
+    /// Watch a transaction in the mempool until it has the given number of
+    /// confirmations. The node publishes a chain event, and runs the
+    /// `--on-watched-transaction` hook, when it does, and whenever the block
+    /// that included it is reorganized out.
+    async fn watch_transaction(
+        transaction_digest: Digest,
+        confirmations: u64,
+    ) -> Result<(), WatchError>;
+
     /// Return the information used on the dashboard's overview tab
     async fn dashboard_overview_data() -> DashBoardOverviewDataFromClient;
 
+    /// Return the tip, sync progress, peers, mempool, recent blocks and wallet
+    /// balance in a single snapshot, for terminal dashboards
+    async fn dashboard_snapshot() -> DashboardSnapshot;
+
     /// Determine whether the user-supplied string is a valid address
     async fn validate_address(
         address: String,
@@ -132,10 +276,98 @@ pub trait RPC {
     async fn validate_amount(amount: String) -> Option<NeptuneCoins>;
 
     /// Determine whether the given amount is less than (or equal to) the balance
-    async fn amount_leq_synced_balance(amount: NeptuneCoins) -> bool;
+    async fn amount_leq_synced_balance(amount: NeptuneCoins) -> Result<bool, WalletDisabled>;
 
     /// Generate a report of all owned and unspent coins, whether time-locked or not.
-    async fn list_own_coins() -> Vec<CoinWithPossibleTimeLock>;
+    async fn list_own_coins() -> Result<Vec<CoinWithPossibleTimeLock>, WalletDisabled>;
+
+    /// List all monitored UTXOs, with whether their membership proofs are synced to the
+    /// tip, need to be restored, or cannot be restored.
+    async fn list_utxos() -> Result<Vec<MonitoredUtxoReport>, WalletDisabled>;
+
+    /// Return the wallet's balance, including time-locked funds, as of the
+    /// block at the given height. Requires `--historical-index`.
+    async fn balance_at(height: BlockHeight) -> Result<NeptuneCoins, HistoricalQueryError>;
+
+    /// Determine whether the UTXO with the given addition record was unspent
+    /// as of the block at the given height. Any UTXO is found on archival
+    /// nodes, but whether it was spent can only be told for the wallet's own
+    /// UTXOs, which requires `--historical-index`.
+    async fn utxo_unspent_at(
+        addition_record: AdditionRecord,
+        height: BlockHeight,
+    ) -> Result<bool, HistoricalQueryError>;
+
+    /// Return the wallet's monitored UTXOs that changed after its sequence number
+    /// was `since`, encrypted under its device sync key, for a companion wallet
+    /// to stay in sync. The decrypted delta holds the sequence number to ask from
+    /// next time.
+    async fn wallet_delta(since: u64) -> Result<Vec<u8>, DeviceSyncError>;
+
+    /// Return all address book entries
+    async fn address_book() -> Vec<AddressBookEntry>;
+
+    /// Return the address book entry with the given name
+    async fn address_book_entry(name: String) -> Option<AddressBookEntry>;
+
+    /// Return journaled events with a sequence number of at least `sequence`, oldest first.
+    /// At most `MAX_JOURNAL_REPLAY_COUNT` events are returned per call.
+    ///
+    /// On a reorg, archival nodes journal every disconnected and connected block. Light
+    /// nodes only journal the new tip as connected, as they do not store the other blocks.
+    async fn journal_events_since(sequence: u64) -> Vec<JournalEntry>;
+
+    /// Return the per-stage processing times of the `n` most recently processed blocks,
+    /// newest first.
+    async fn block_processing_timelines(n: usize) -> Vec<BlockProcessingTimeline>;
+
+    /// Return when the `n` most recently announced blocks were first announced, by
+    /// which peer, and how long until they were validated, along with per-peer
+    /// announcement statistics
+    async fn block_propagation(n: usize) -> BlockPropagationReport;
+
+    /// Return the stored blocks from `depth` heights below the tip and up,
+    /// forks included, with their parents, accumulated proof-of-work and
+    /// whether they are canonical. `None` on light nodes.
+    async fn export_block_tree(depth: u64) -> Option<BlockTree>;
+
+    /// Return the tips of the stored forks from up to 100 heights below the
+    /// tip, the heaviest first, marking those with as much accumulated
+    /// proof-of-work as the tip. Empty on light nodes.
+    async fn list_forks() -> Vec<ForkTip>;
+
+    /// Report whether databases are open, the tip is recent and peers are connected
+    async fn health() -> HealthReport;
+
+    /// Return handshake failures by reason and connection durations per
+    /// direction, and the traffic by message type of every connected peer
+    async fn connection_metrics() -> ConnectionMetricsReport;
+
+    /// Return occupancy statistics of the active window of the tip's mutator set
+    async fn active_window_stats() -> ActiveWindowStats;
+
+    /// Return the RAM used by in-memory pools and caches, and the sizes above
+    /// which they evict entries
+    async fn memory_status() -> MemoryStatus;
+
+    /// Return the disk used by the data directory, and the event journal's
+    /// retention policy
+    async fn get_disk_usage() -> Option<DiskUsage>;
+
+    /// Return the metrics snapshots of the last `hours` hours, oldest first.
+    /// Snapshots are taken every 10 minutes and kept for 30 days.
+    async fn get_metrics_history(hours: u64) -> Vec<MetricsSnapshot>;
+
+    /// Return the network alerts that have not expired, most severe first
+    async fn alerts() -> Vec<SignedAlert>;
+
+    /// Return the queued and running proving jobs, and those finished
+    /// recently, with their progress
+    async fn list_proving_jobs() -> Vec<ProvingJobInfo>;
+
+    /// Return the last block marked final, if any. Blocks at or below it cannot
+    /// be reorganized away.
+    async fn finalized_block() -> Option<FinalizedBlock>;
 
     /******** CHANGE THINGS ********/
     // Place all things that change state here
@@ -146,21 +378,123 @@ pub trait RPC {
     /// Clears standing for ip, whether connected or not
     async fn clear_standing_by_ip(ip: IpAddr);
 
-    /// Send coins
+    /// Mark a block of the canonical chain, and thereby its ancestors, final, so
+    /// that no reorg can roll it back. For external finality providers.
+    async fn mark_block_final(block_digest: Digest) -> Result<FinalizedBlock, FinalityError>;
+
+    /// Send coins. If an idempotency key is given and a previous request with the same
+    /// key created a transaction, the digest of that transaction is returned and no new
+    /// transaction is created, so a request can be safely retried.
+    ///
+    /// If a release date is given, the recipient cannot spend the coins in a
+    /// transaction dated before it.
+    ///
+    /// Fails with [`SendFailure::PolicyViolation`] if the send would break the wallet's
+    /// spend policy.
     async fn send(
         amount: NeptuneCoins,
         address: generation_address::ReceivingAddress,
         fee: NeptuneCoins,
-    ) -> Option<Digest>;
+        release_date: Option<Timestamp>,
+        idempotency_key: Option<String>,
+    ) -> Result<Digest, SendFailure>;
+
+    /// Select inputs for a send without creating the transaction, and return a
+    /// preview of it with a token to pass to `confirm_send`. The inputs are valid
+    /// for the current tip only, and the token expires after a few minutes.
+    async fn prepare_send(
+        amount: NeptuneCoins,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+        release_date: Option<Timestamp>,
+    ) -> Result<SendPreview, SendFailure>;
+
+    /// Create and broadcast the transaction of a prepared send. Each token can be
+    /// confirmed once. The spend policy and idempotency key are checked as for
+    /// `send`.
+    async fn confirm_send(
+        token: Digest,
+        idempotency_key: Option<String>,
+    ) -> Result<Digest, SendFailure>;
+
+    /// Save a named address in the address book, replacing any entry with the same
+    /// name. The address is bech32m-encoded for the node's network. Returns true if
+    /// an entry was replaced.
+    async fn save_address_book_entry(
+        name: String,
+        address: String,
+        note: String,
+    ) -> Result<bool, AddressBookError>;
+
+    /// Remove the named address from the address book. Returns false if there was no
+    /// such entry.
+    async fn remove_address_book_entry(name: String) -> bool;
+
+    /// Verify a signed network alert, store it, and relay it to all peers. The
+    /// alert is relayed again if it is already known.
+    async fn broadcast_alert(alert: SignedAlert) -> Result<(), AlertError>;
+
+    /// Send all funds of another wallet, e.g. a paper wallet, to this wallet,
+    /// less the fee. The chain is scanned for UTXOs announced to the generation
+    /// address of the wallet with the given seed phrase, which requires an
+    /// archival node. Returns the digest of the transaction.
+    async fn sweep_key(seed_phrase: Vec<String>, fee: NeptuneCoins) -> Result<Digest, SweepError>;
+
+    /// Broadcast a transaction that embeds `data` in a data carrier and pays only
+    /// the fee, with change back to the wallet. The data must fit in a data carrier
+    /// and within the node's mempool policy. Returns the digest of the transaction.
+    async fn send_data(data: Vec<u8>, fee: NeptuneCoins) -> Result<Digest, SendFailure>;
+
+    /// Return the cosigner key with which the wallet takes part in its `index`th
+    /// shared-control lock, to hand to the coordinator of the lock
+    async fn shared_control_key(index: u16) -> Result<Digest, WalletDisabled>;
+
+    /// Return the lock that `threshold` of the cosigners with the given keys
+    /// must unlock together, bech32m-encoded for the node's network
+    async fn shared_control_lock(
+        threshold: usize,
+        cosigner_keys: Vec<Digest>,
+    ) -> Result<String, SharedControlError>;
+
+    /// Sign a shared spend with the wallet's `index`th shared-control unlock key,
+    /// and return it for the next cosigner
+    async fn sign_shared_spend(
+        shared_spend: SharedSpend,
+        index: u16,
+    ) -> Result<SharedSpend, SharedControlError>;
+
+    /// Create and broadcast the transaction of a shared spend that enough
+    /// cosigners signed. Returns the digest of the transaction.
+    async fn broadcast_shared_spend(
+        shared_spend: SharedSpend,
+    ) -> Result<Digest, SharedControlError>;
+
+    /// Cancel a queued or running proving job. A send waiting for it fails.
+    /// Returns false if there is no such job, or if it is done.
+    async fn cancel_proving_job(id: ProvingJobId) -> bool;
 
     /// Stop miner if running
     async fn pause_miner();
 
+    /// Pin the node to the stored block `digest`, which must have as much
+    /// accumulated proof-of-work as the tip, until a block on either branch
+    /// makes their work differ. Recorded in the event journal.
+    async fn prefer_tip(digest: Digest) -> Result<(), PreferTipError>;
+
     /// Start miner if not running
     async fn restart_miner();
 
     /// mark MUTXOs as abandoned
-    async fn prune_abandoned_monitored_utxos() -> usize;
+    async fn prune_abandoned_monitored_utxos() -> Result<usize, WalletDisabled>;
+
+    /// Rescan the canonical blocks from the given height for UTXOs announced
+    /// to the wallet, e.g. after restoring it from its seed phrase. Returns
+    /// once the rescan started; its progress is part of
+    /// `dashboard_overview_data`.
+    async fn rescan_wallet(from_height: BlockHeight) -> Result<(), RescanError>;
+
+    /// Stop the running rescan, keeping the UTXOs found so far
+    async fn cancel_rescan() -> Result<(), RescanError>;
 
     /// Gracious shutdown.
     async fn shutdown() -> bool;
@@ -169,6 +503,54 @@ pub trait RPC {
     async fn cpu_temp() -> Option<f32>;
 }
 
+/// Evidence about an AOCL leaf, together with the mutator set it can be
+/// verified against. The mutator set is that of the tip, whose digest
+/// commits to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MutatorSetEvidenceAtTip {
+    #[serde(with = "digest_serde")]
+    pub tip_digest: Digest,
+    pub mutator_set_accumulator: MutatorSetAccumulator,
+    pub evidence: MsMembershipEvidence,
+}
+
+/// The reason the `send` or `confirm_send` RPC did not send
+#[derive(Clone, Debug, Error, Serialize, Deserialize)]
+pub enum SendFailure {
+    #[error("spend policy violated: {0}")]
+    PolicyViolation(SpendPolicyViolation),
+
+    #[error("could not send: {0}")]
+    Failed(String),
+
+    #[error(transparent)]
+    WalletDisabled(#[from] WalletDisabled),
+}
+
+/// What `send_internal` is asked to send
+enum SendRequest {
+    New {
+        amount: NeptuneCoins,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+        release_date: Option<Timestamp>,
+    },
+    Prepared(Box<PreparedSend>),
+}
+
+/// How drafting the transaction of a send ended
+enum SendOutcome {
+    /// A transaction spending the given total was drafted
+    Created(Box<TransactionDraft>, NeptuneCoins),
+
+    /// The idempotency key was already used by the transaction with this
+    /// digest
+    AlreadySent(Digest),
+
+    /// No transaction was drafted
+    Failed(SendFailure),
+}
+
 #[derive(Clone)]
 pub struct NeptuneRPCServer {
     pub socket_address: SocketAddr,
@@ -176,7 +558,295 @@ pub struct NeptuneRPCServer {
     pub rpc_server_to_main_tx: tokio::sync::mpsc::Sender<RPCServerToMain>,
 }
 
+/// Listen for RPC connections on the IP and port given by `--rpc-listen-addr`
+/// and `--rpc-port`, and serve them from a new task.
+pub async fn serve(
+    state: GlobalStateLock,
+    rpc_server_to_main_tx: tokio::sync::mpsc::Sender<RPCServerToMain>,
+) -> Result<JoinHandle<()>> {
+    let rpc_address = SocketAddr::new(state.cli().rpc_listen_addr, state.cli().rpc_port);
+    let mut rpc_listener = tarpc::serde_transport::tcp::listen(rpc_address, Json::default)
+        .await
+        .with_context(|| format!("Failed to bind RPC server to {rpc_address}"))?;
+    rpc_listener.config_mut().max_frame_length(usize::MAX);
+
+    async fn spawn(fut: impl Future<Output = ()> + Send + 'static) {
+        tokio::spawn(fut);
+    }
+
+    let rpc_join_handle = tokio::spawn(async move {
+        rpc_listener
+            // Ignore accept errors.
+            .filter_map(|r| future::ready(r.ok()))
+            .map(server::BaseChannel::with_defaults)
+            // Limit channels to 5 per IP. 1 for dashboard and a few more for CLI interactions
+            .max_channels_per_key(5, |t| {
+                t.transport().peer_addr().unwrap().ip().to_canonical()
+            })
+            // serve is generated by the service attribute. It takes as input any type implementing
+            // the generated RPC trait.
+            .map(move |channel| {
+                let server = NeptuneRPCServer {
+                    socket_address: channel.transport().peer_addr().unwrap(),
+                    state: state.clone(),
+                    rpc_server_to_main_tx: rpc_server_to_main_tx.clone(),
+                };
+
+                channel.execute(server.serve()).for_each(spawn)
+            })
+            // Max 10 channels.
+            .buffer_unordered(10)
+            .for_each(|_| async {})
+            .await;
+    });
+
+    Ok(rpc_join_handle)
+}
+
 impl NeptuneRPCServer {
+    /// Fail if the node runs with `--no-wallet`.
+    fn wallet_enabled(&self) -> Result<(), WalletDisabled> {
+        if self.state.cli().no_wallet {
+            Err(WalletDisabled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Queue the proving job of a send, which is listed, and can be
+    /// cancelled, while the send waits for the wallet. The send times out
+    /// after `--send-timeout`.
+    fn send_ticket(&self) -> ProvingTicket {
+        self.state
+            .proving_queue()
+            .ticket("send")
+            .with_timeout(Duration::from_secs(self.state.cli().send_timeout))
+    }
+
+    /// Restart the miner after a send, if it was mining before the send
+    /// paused it
+    async fn resume_mining(&self, was_mining: bool) {
+        if was_mining {
+            let _ = self
+                .rpc_server_to_main_tx
+                .send(RPCServerToMain::RestartMiner)
+                .await;
+        }
+    }
+
+    /// Draft the transaction for the request, and record it in the spend
+    /// history and under the idempotency key, if any.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn draft_send(
+        &self,
+        request: SendRequest,
+        idempotency_key: Option<&str>,
+        spend_policy: &SpendPolicy,
+        now: Timestamp,
+        ticket: &ProvingTicket,
+    ) -> SendOutcome {
+        let mut state = match ticket.until_cancelled(self.state.lock_guard_mut()).await {
+            Ok(state) => state,
+            Err(err) => return SendOutcome::Failed(SendFailure::Failed(err.to_string())),
+        };
+        if let Some(key) = idempotency_key {
+            let sent_digest = state
+                .wallet_state
+                .wallet_db
+                .get_sent_transaction_digest(key)
+                .await;
+            if let Some(sent_digest) = sent_digest {
+                return SendOutcome::AlreadySent(sent_digest);
+            }
+        }
+
+        let prepared_send = match request {
+            SendRequest::New {
+                amount,
+                address,
+                fee,
+                release_date,
+            } => match state
+                .prepare_send(amount, address, fee, release_date, now)
+                .await
+            {
+                Ok(prepared_send) => prepared_send,
+                Err(err) => return SendOutcome::Failed(SendFailure::Failed(err.to_string())),
+            },
+            SendRequest::Prepared(prepared_send) => {
+                if prepared_send.preview.tip_digest != state.chain.light_state().hash() {
+                    return SendOutcome::Failed(SendFailure::Failed(
+                        "tip changed since the send was prepared; prepare it again".to_string(),
+                    ));
+                }
+                *prepared_send
+            }
+        };
+
+        let PreparedSend {
+            preview,
+            address,
+            receiver_data,
+            spendable_utxos_and_mps,
+            timestamp,
+        } = prepared_send;
+        let total_spend = preview.amount + preview.fee;
+        let sent_during_last_day = state
+            .wallet_state
+            .wallet_db
+            .amount_sent_since(now - SpendPolicy::day())
+            .await;
+        if let Err(violation) = spend_policy.check(total_spend, &address, sent_during_last_day) {
+            return SendOutcome::Failed(SendFailure::PolicyViolation(violation));
+        }
+
+        let draft = match state
+            .draft_transaction_with_inputs(
+                receiver_data,
+                preview.fee,
+                timestamp,
+                spendable_utxos_and_mps,
+            )
+            .await
+        {
+            Ok(draft) => draft,
+            Err(err) => {
+                tracing::error!("Could not create transaction: {}", err);
+                return SendOutcome::Failed(SendFailure::Failed(err.to_string()));
+            }
+        };
+
+        let wallet_db = &mut state.wallet_state.wallet_db;
+        wallet_db.record_send(now, total_spend).await;
+        if let Some(key) = idempotency_key {
+            wallet_db
+                .set_sent_transaction_digest(key.to_owned(), draft.transaction_digest())
+                .await;
+        }
+
+        SendOutcome::Created(Box::new(draft), total_spend)
+    }
+
+    /// Create a transaction for the request and hand it to the main loop.
+    /// Shared by `send` and `confirm_send`.
+    ///
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn send_internal(
+        &self,
+        request: SendRequest,
+        idempotency_key: Option<String>,
+    ) -> Result<Digest, SendFailure> {
+        if let Some(key) = &idempotency_key {
+            let state = self.state.lock_guard().await;
+            let sent_digest = state
+                .wallet_state
+                .wallet_db
+                .get_sent_transaction_digest(key)
+                .await;
+            if let Some(sent_digest) = sent_digest {
+                info!("Send request with idempotency key {key} was already processed");
+                return Ok(sent_digest);
+            }
+        }
+
+        let spend_policy = SpendPolicy::from_cli_args(self.state.cli())
+            .map_err(|err| SendFailure::Failed(err.to_string()))?;
+        let now = Timestamp::now();
+        let ticket = self.send_ticket();
+
+        // Pause miner if we are mining
+        let was_mining = self.state.mining().await;
+        if was_mining {
+            let _ = self
+                .rpc_server_to_main_tx
+                .send(RPCServerToMain::PauseMiner)
+                .await;
+        }
+
+        // All cryptographic data must be in relation to a single block
+        // and a write-lock must therefore be held over GlobalState while the
+        // transaction is drafted. The same lock covers the idempotency key and
+        // the spend history, so that concurrent requests cannot both pass their
+        // checks: both are recorded before the lock is released for proving,
+        // and forgotten again if proving fails.
+        let outcome = self
+            .draft_send(
+                request,
+                idempotency_key.as_deref(),
+                &spend_policy,
+                now,
+                &ticket,
+            )
+            .await;
+
+        // Prove without holding the lock, which can take minutes
+        let proven = match outcome {
+            SendOutcome::Created(draft, total_spend) => {
+                let draft = *draft;
+                let change_addition_record = draft.change_addition_record;
+                let proven = draft.prove(ticket).await;
+                if let Err(err) = &proven {
+                    let mut state = self.state.lock_guard_mut().await;
+                    if let Some(change_addition_record) = change_addition_record {
+                        state.forget_change_of_failed_transaction(change_addition_record, err);
+                    }
+                    let wallet_db = &mut state.wallet_state.wallet_db;
+                    wallet_db.remove_send(now, total_spend).await;
+                    if let Some(key) = &idempotency_key {
+                        wallet_db.remove_sent_transaction_digest(key).await;
+                    }
+                }
+                proven.map_err(|err| SendFailure::Failed(err.to_string()))
+            }
+            SendOutcome::AlreadySent(sent_digest) => {
+                self.resume_mining(was_mining).await;
+                return Ok(sent_digest);
+            }
+            SendOutcome::Failed(failure) => Err(failure),
+        };
+        let transaction = match proven {
+            Ok(transaction) => transaction,
+            Err(failure) => {
+                self.resume_mining(was_mining).await;
+                return Err(failure);
+            }
+        };
+
+        // Send transaction message to main
+        let response: Result<(), SendError<RPCServerToMain>> = self
+            .rpc_server_to_main_tx
+            .send(RPCServerToMain::Send(Box::new(transaction.clone())))
+            .await;
+
+        self.resume_mining(was_mining).await;
+
+        // A transaction that never reached the main loop was not sent, so a retry
+        // must be allowed to create a new one.
+        if response.is_err() {
+            if let Some(key) = &idempotency_key {
+                self.state
+                    .lock_guard_mut()
+                    .await
+                    .wallet_state
+                    .wallet_db
+                    .remove_sent_transaction_digest(key)
+                    .await;
+            }
+        }
+
+        self.state.flush_databases().await.expect("flushed DBs");
+
+        match response {
+            Ok(()) => Ok(Hash::hash(&transaction)),
+            Err(_) => Err(SendFailure::Failed(
+                "could not hand transaction to main loop".to_string(),
+            )),
+        }
+    }
+
     async fn confirmations_internal(&self) -> Option<BlockHeight> {
         let state = self.state.lock_guard().await;
 
@@ -197,6 +867,25 @@ impl NeptuneRPCServer {
         }
     }
 
+    /// The detail of the stored block with the given digest, if any
+    async fn block_detail(state: &GlobalState, digest: Digest) -> Option<BlockDetail> {
+        if !state.chain.is_archival_node() {
+            return None;
+        }
+
+        let archival_state = state.chain.archival_state();
+        let block = archival_state.get_block(digest).await.unwrap()?;
+        let is_canonical = archival_state
+            .block_height_to_canonical_block_digest(
+                block.header().height,
+                state.chain.light_state().hash(),
+            )
+            .await
+            == Some(digest);
+
+        Some(BlockDetail::from_block(&block, is_canonical))
+    }
+
     /// Return temperature of CPU, if available.
     fn cpu_temp_inner() -> Option<f32> {
         let current_system = System::new();
@@ -213,10 +902,12 @@ impl RPC for NeptuneRPCServer {
     }
 
     async fn own_listen_address_for_peers(self, _context: context::Context) -> Option<SocketAddr> {
-        let listen_for_peers_ip = self.state.cli().listen_addr;
-        let listen_for_peers_socket = self.state.cli().peer_port;
-        let socket_address = SocketAddr::new(listen_for_peers_ip, listen_for_peers_socket);
-        Some(socket_address)
+        self.state
+            .lock_guard()
+            .await
+            .advertised_listen_addresses()
+            .first()
+            .copied()
     }
 
     async fn own_instance_id(self, _context: context::Context) -> InstanceId {
@@ -238,6 +929,24 @@ impl RPC for NeptuneRPCServer {
         self.confirmations_internal().await
     }
 
+    async fn blocks_in_time_range(
+        self,
+        _: context::Context,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Vec<Digest> {
+        let state = self.state.lock_guard().await;
+        if !state.chain.is_archival_node() {
+            return vec![];
+        }
+
+        state
+            .chain
+            .archival_state()
+            .get_blocks_in_time_range(from, to)
+            .await
+    }
+
     async fn utxo_digest(self, _: context::Context, leaf_index: u64) -> Option<Digest> {
         let state = self.state.lock_guard().await;
         let aocl = &state.chain.archival_state().archival_mutator_set.ams().aocl;
@@ -248,6 +957,55 @@ impl RPC for NeptuneRPCServer {
         }
     }
 
+    async fn mutator_set_evidence(
+        self,
+        _: context::Context,
+        leaf_index: u64,
+        absolute_indices: Option<AbsoluteIndexSet>,
+    ) -> Option<MutatorSetEvidenceAtTip> {
+        let state = self.state.lock_guard().await;
+        if !state.chain.is_archival_node() {
+            return None;
+        }
+
+        let evidence = state
+            .chain
+            .archival_state()
+            .archival_mutator_set
+            .ams()
+            .membership_evidence(leaf_index, absolute_indices)
+            .await
+            .ok()?;
+        let tip = state.chain.light_state();
+
+        Some(MutatorSetEvidenceAtTip {
+            tip_digest: tip.hash(),
+            mutator_set_accumulator: tip.body().mutator_set_accumulator.clone(),
+            evidence,
+        })
+    }
+
+    async fn audit_supply(self, _: context::Context) -> Option<SupplyAudit> {
+        // The lock is only held to take snapshots of the databases. The audit
+        // reads through them, so the node keeps updating its state meanwhile,
+        // and the state does not change under the audit.
+        let snapshot = {
+            let state = self.state.lock_guard().await;
+            if !state.chain.is_archival_node() {
+                return None;
+            }
+            state.chain.archival_state().read_only_snapshot().await
+        };
+
+        match snapshot.audit_supply().await {
+            Ok(audit) => Some(audit),
+            Err(err) => {
+                error!("Supply audit failed: {err}");
+                None
+            }
+        }
+    }
+
     async fn block_digest(
         self,
         _: context::Context,
@@ -273,11 +1031,75 @@ impl RPC for NeptuneRPCServer {
         let archival_state = state.chain.archival_state();
 
         let block = archival_state.get_block(digest).await.unwrap()?;
-        Some(BlockInfo::from_block_and_digests(
+        let tip_digest = state.chain.light_state().hash();
+        let mut block_info = BlockInfo::from_block_and_digests(
             &block,
             archival_state.genesis_block().hash(),
-            state.chain.light_state().hash(),
-        ))
+            tip_digest,
+        );
+        block_info.is_final = archival_state.is_block_final(digest, tip_digest).await;
+
+        Some(block_info)
+    }
+
+    async fn get_block_hex(self, _: context::Context, digest: Digest) -> Option<String> {
+        let state = self.state.lock_guard().await;
+        let block = state
+            .chain
+            .archival_state()
+            .get_block(digest)
+            .await
+            .unwrap()?;
+
+        Some(encode_hex(&block))
+    }
+
+    async fn get_block_by_height(
+        self,
+        _: context::Context,
+        height: BlockHeight,
+    ) -> Option<BlockDetail> {
+        let state = self.state.lock_guard().await;
+        if !state.chain.is_archival_node() {
+            return None;
+        }
+
+        let digest = state
+            .chain
+            .archival_state()
+            .block_height_to_canonical_block_digest(height, state.chain.light_state().hash())
+            .await?;
+        Self::block_detail(&state, digest).await
+    }
+
+    async fn get_block_by_digest(self, _: context::Context, digest: Digest) -> Option<BlockDetail> {
+        let state = self.state.lock_guard().await;
+        Self::block_detail(&state, digest).await
+    }
+
+    async fn get_transaction_hex(
+        self,
+        _: context::Context,
+        transaction_digest: Digest,
+    ) -> Option<String> {
+        self.state
+            .lock_guard()
+            .await
+            .mempool
+            .get(transaction_digest)
+            .map(encode_hex)
+    }
+
+    async fn decode_block(self, _: context::Context, hex: String) -> Result<Block, DecodeError> {
+        decode_hex(&hex)
+    }
+
+    async fn decode_transaction(
+        self,
+        _: context::Context,
+        hex: String,
+    ) -> Result<Transaction, DecodeError> {
+        decode_hex(&hex)
     }
 
     async fn latest_tip_digests(self, _context: tarpc::context::Context, n: usize) -> Vec<Digest> {
@@ -303,6 +1125,10 @@ impl RPC for NeptuneRPCServer {
             .collect()
     }
 
+    async fn configured_peers(self, _: context::Context) -> Vec<ConfiguredPeerStatus> {
+        self.state.lock_guard().await.net.peer_reconnector.status()
+    }
+
     #[doc = r" Return info about all peers that have been sanctioned"]
     async fn all_sanctioned_peers(
         self,
@@ -367,7 +1193,12 @@ impl RPC for NeptuneRPCServer {
         Some(amount)
     }
 
-    async fn amount_leq_synced_balance(self, _ctx: context::Context, amount: NeptuneCoins) -> bool {
+    async fn amount_leq_synced_balance(
+        self,
+        _ctx: context::Context,
+        amount: NeptuneCoins,
+    ) -> Result<bool, WalletDisabled> {
+        self.wallet_enabled()?;
         let now = Timestamp::now();
         // test inequality
         let wallet_status = self
@@ -376,10 +1207,14 @@ impl RPC for NeptuneRPCServer {
             .await
             .get_wallet_status_for_tip()
             .await;
-        amount <= wallet_status.synced_unspent_available_amount(now)
+        Ok(amount <= wallet_status.synced_unspent_available_amount(now))
     }
 
-    async fn synced_balance(self, _context: tarpc::context::Context) -> NeptuneCoins {
+    async fn synced_balance(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Result<NeptuneCoins, WalletDisabled> {
+        self.wallet_enabled()?;
         let now = Timestamp::now();
         let wallet_status = self
             .state
@@ -387,23 +1222,52 @@ impl RPC for NeptuneRPCServer {
             .await
             .get_wallet_status_for_tip()
             .await;
-        wallet_status.synced_unspent_available_amount(now)
+        Ok(wallet_status.synced_unspent_available_amount(now))
     }
 
-    async fn wallet_status(self, _context: tarpc::context::Context) -> WalletStatus {
-        self.state
+    async fn timelocked_balance(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Result<NeptuneCoins, WalletDisabled> {
+        self.wallet_enabled()?;
+        let now = Timestamp::now();
+        let wallet_status = self
+            .state
             .lock_guard()
             .await
             .get_wallet_status_for_tip()
-            .await
+            .await;
+        Ok(wallet_status.synced_unspent_timelocked_amount(now))
     }
 
-    async fn header(
+    async fn balance(
         self,
         _context: tarpc::context::Context,
-        block_selector: BlockSelector,
-    ) -> Option<BlockHeader> {
-        let state = self.state.lock_guard().await;
+    ) -> Result<WalletBalance, WalletDisabled> {
+        self.wallet_enabled()?;
+        let now = Timestamp::now();
+        Ok(self.state.lock_guard().await.get_wallet_balance(now).await)
+    }
+
+    async fn wallet_status(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Result<WalletStatus, WalletDisabled> {
+        self.wallet_enabled()?;
+        Ok(self
+            .state
+            .lock_guard()
+            .await
+            .get_wallet_status_for_tip()
+            .await)
+    }
+
+    async fn header(
+        self,
+        _context: tarpc::context::Context,
+        block_selector: BlockSelector,
+    ) -> Option<BlockHeader> {
+        let state = self.state.lock_guard().await;
         let block_digest = block_selector.as_digest(&state).await?;
         state
             .chain
@@ -415,14 +1279,16 @@ impl RPC for NeptuneRPCServer {
     async fn own_receiving_address(
         self,
         _context: tarpc::context::Context,
-    ) -> generation_address::ReceivingAddress {
-        self.state
+    ) -> Result<generation_address::ReceivingAddress, WalletDisabled> {
+        self.wallet_enabled()?;
+        Ok(self
+            .state
             .lock_guard()
             .await
             .wallet_state
             .wallet_secret
             .nth_generation_spending_key(0)
-            .to_address()
+            .to_address())
     }
 
     async fn mempool_tx_count(self, _context: tarpc::context::Context) -> usize {
@@ -433,10 +1299,40 @@ impl RPC for NeptuneRPCServer {
         self.state.lock_guard().await.mempool.get_size()
     }
 
+    async fn get_mempool_entry(
+        self,
+        _context: tarpc::context::Context,
+        transaction_digest: Digest,
+    ) -> Option<MempoolEntry> {
+        self.state
+            .lock_guard()
+            .await
+            .mempool
+            .entry(transaction_digest)
+    }
+
+    async fn watch_transaction(
+        self,
+        _context: tarpc::context::Context,
+        transaction_digest: Digest,
+        confirmations: u64,
+    ) -> Result<(), WatchError> {
+        let mut state = self.state.lock_guard_mut().await;
+        let transaction = state
+            .mempool
+            .get(transaction_digest)
+            .cloned()
+            .ok_or(WatchError::UnknownTransaction(transaction_digest))?;
+        state
+            .transaction_watches
+            .watch(transaction_digest, &transaction, confirmations)
+    }
+
     async fn history(
         self,
         _context: tarpc::context::Context,
-    ) -> Vec<(Digest, BlockHeight, Timestamp, NeptuneCoins)> {
+    ) -> Result<Vec<(Digest, BlockHeight, Timestamp, NeptuneCoins)>, WalletDisabled> {
+        self.wallet_enabled()?;
         let history = self.state.lock_guard().await.get_balance_history().await;
 
         // sort
@@ -447,7 +1343,38 @@ impl RPC for NeptuneRPCServer {
         display_history.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
 
         // return
-        display_history
+        Ok(display_history)
+    }
+
+    async fn get_transaction_history(
+        self,
+        _context: tarpc::context::Context,
+        kind: Option<UtxoOrigin>,
+    ) -> Result<Vec<TransactionHistoryEntry>, WalletDisabled> {
+        self.wallet_enabled()?;
+        let mut history = self
+            .state
+            .lock_guard()
+            .await
+            .get_transaction_history(kind)
+            .await;
+        history.sort_by_key(|entry| entry.height);
+
+        Ok(history)
+    }
+
+    async fn sent_transactions(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Result<Vec<SentTransaction>, WalletDisabled> {
+        self.wallet_enabled()?;
+        Ok(self
+            .state
+            .lock_guard()
+            .await
+            .wallet_state
+            .sent_transactions
+            .all())
     }
 
     async fn dashboard_overview_data(
@@ -467,6 +1394,12 @@ impl RPC for NeptuneRPCServer {
         let peer_count = Some(state.net.peer_map.len());
 
         let is_mining = Some(state.mining);
+        let wallet_rescan = state.wallet_rescan.progress();
+        let last_wallet_backup = state
+            .wallet_state
+            .backups
+            .as_ref()
+            .and_then(|backups| backups.last_backup());
         drop(state);
 
         let confirmations = self.confirmations_internal().await;
@@ -483,9 +1416,16 @@ impl RPC for NeptuneRPCServer {
             is_mining,
             confirmations,
             cpu_temp,
+            wallet_rescan,
+            last_wallet_backup,
         }
     }
 
+    async fn dashboard_snapshot(self, _context: tarpc::context::Context) -> DashboardSnapshot {
+        let state = self.state.lock_guard().await;
+        DashboardSnapshot::from_state(&state, Timestamp::now()).await
+    }
+
     /******** CHANGE THINGS ********/
     /// Locking:
     ///   * acquires `global_state_lock` for write
@@ -531,6 +1471,33 @@ impl RPC for NeptuneRPCServer {
             .expect("flushed DBs");
     }
 
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn mark_block_final(
+        self,
+        _context: tarpc::context::Context,
+        block_digest: Digest,
+    ) -> Result<FinalizedBlock, FinalityError> {
+        let mut global_state_mut = self.state.lock_guard_mut().await;
+        if !global_state_mut.chain.is_archival_node() {
+            return Err(FinalityError::NotArchival);
+        }
+
+        let tip_digest = global_state_mut.chain.light_state().hash();
+        let finalized = global_state_mut
+            .chain
+            .archival_state_mut()
+            .mark_block_final(block_digest, tip_digest)
+            .await?;
+
+        global_state_mut
+            .flush_databases()
+            .await
+            .expect("flushed DBs");
+
+        Ok(finalized)
+    }
+
     /// Locking:
     ///   * acquires `global_state_lock` for write
     async fn send(
@@ -539,97 +1506,281 @@ impl RPC for NeptuneRPCServer {
         amount: NeptuneCoins,
         address: generation_address::ReceivingAddress,
         fee: NeptuneCoins,
-    ) -> Option<Digest> {
+        release_date: Option<Timestamp>,
+        idempotency_key: Option<String>,
+    ) -> Result<Digest, SendFailure> {
+        self.wallet_enabled()?;
         let span = tracing::debug_span!("Constructing transaction objects");
         let _enter = span.enter();
 
-        let coins = amount.to_native_coins();
-        let utxo = Utxo::new(address.lock_script(), coins);
+        self.send_internal(
+            SendRequest::New {
+                amount,
+                address,
+                fee,
+                release_date,
+            },
+            idempotency_key,
+        )
+        .await
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn prepare_send(
+        self,
+        _ctx: context::Context,
+        amount: NeptuneCoins,
+        address: generation_address::ReceivingAddress,
+        fee: NeptuneCoins,
+        release_date: Option<Timestamp>,
+    ) -> Result<SendPreview, SendFailure> {
+        self.wallet_enabled()?;
+        let spend_policy = SpendPolicy::from_cli_args(self.state.cli())
+            .map_err(|err| SendFailure::Failed(err.to_string()))?;
         let now = Timestamp::now();
 
-        // note: for future changes:
-        // No consensus data should be read within this read-lock.
-        // Else a write lock must be used instead and held until
-        // create_transaction() completes, so entire op is atomic.
-        // See: https://github.com/Neptune-Crypto/neptune-core/issues/134
-        let state = self.state.lock_guard().await;
-        let block_height = state.chain.light_state().header().height;
-        let receiver_privacy_digest = address.privacy_digest;
-        let sender_randomness = state
+        let mut state = self.state.lock_guard_mut().await;
+        let sent_during_last_day = state
             .wallet_state
-            .wallet_secret
-            .generate_sender_randomness(block_height, receiver_privacy_digest);
-        drop(state);
+            .wallet_db
+            .amount_sent_since(now - SpendPolicy::day())
+            .await;
+        spend_policy
+            .check(amount + fee, &address, sent_during_last_day)
+            .map_err(SendFailure::PolicyViolation)?;
 
-        // 1. Build transaction object
-        // TODO: Allow user to set fee here. Don't set it automatically as we want the user
-        // to be in control of this. But we could add an endpoint to get recommended fee
-        // density.
-        let public_announcement =
-            match address.generate_public_announcement(&utxo, sender_randomness) {
-                Ok(pa) => pa,
-                Err(_) => {
-                    tracing::error!(
-                        "Failed to generate transaction because could not encrypt to address."
-                    );
-                    return None;
-                }
-            };
-        let receiver_data = [(UtxoReceiverData {
-            utxo,
-            sender_randomness,
-            receiver_privacy_digest,
-            public_announcement,
-        })]
-        .to_vec();
+        let prepared_send = state
+            .prepare_send(amount, address, fee, release_date, now)
+            .await
+            .map_err(|err| SendFailure::Failed(err.to_string()))?;
+        let preview = prepared_send.preview.clone();
+        state.wallet_state.prepared_sends.insert(prepared_send);
 
-        // Pause miner if we are mining
-        let was_mining = self.state.mining().await;
-        if was_mining {
-            let _ = self
-                .rpc_server_to_main_tx
-                .send(RPCServerToMain::PauseMiner)
-                .await;
-        }
+        Ok(preview)
+    }
 
-        // All cryptographic data must be in relation to a single block
-        // and a write-lock must therefore be held over GlobalState to ensure this.
-        let transaction_result = self
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn confirm_send(
+        self,
+        _ctx: context::Context,
+        token: Digest,
+        idempotency_key: Option<String>,
+    ) -> Result<Digest, SendFailure> {
+        self.wallet_enabled()?;
+        let prepared_send = self
             .state
             .lock_guard_mut()
             .await
-            .create_transaction(receiver_data, fee, now)
+            .wallet_state
+            .prepared_sends
+            .take(token, Timestamp::now())
+            .ok_or_else(|| SendFailure::Failed("unknown or expired token".to_string()))?;
+
+        self.send_internal(
+            SendRequest::Prepared(Box::new(prepared_send)),
+            idempotency_key,
+        )
+        .await
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn save_address_book_entry(
+        self,
+        _ctx: context::Context,
+        name: String,
+        address: String,
+        note: String,
+    ) -> Result<bool, AddressBookError> {
+        self.wallet_enabled()?;
+        let entry = AddressBookEntry::new(name, address, note, self.state.cli().network)?;
+
+        let mut global_state_mut = self.state.lock_guard_mut().await;
+        let replaced = global_state_mut
+            .wallet_state
+            .wallet_db
+            .set_address_book_entry(entry)
             .await;
+        global_state_mut.wallet_state.record_wallet_changes(1).await;
 
-        let transaction = match transaction_result {
-            Ok(tx) => tx,
-            Err(err) => {
-                tracing::error!("Could not create transaction: {}", err);
-                return None;
+        global_state_mut
+            .flush_databases()
+            .await
+            .expect("flushed DBs");
+
+        Ok(replaced)
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for read, a batch of blocks at a time,
+    ///     then for write while creating the transaction
+    async fn sweep_key(
+        self,
+        _ctx: context::Context,
+        seed_phrase: Vec<String>,
+        fee: NeptuneCoins,
+    ) -> Result<Digest, SweepError> {
+        self.wallet_enabled()?;
+        let wallet_secret =
+            WalletSecret::from_phrase(&seed_phrase).map_err(|_| SweepError::InvalidSecret)?;
+        let transaction = sweep::sweep(
+            &self.state,
+            wallet_secret.nth_generation_spending_key(0),
+            fee,
+        )
+        .await?;
+        let transaction_digest = Hash::hash(&transaction);
+
+        self.rpc_server_to_main_tx
+            .send(RPCServerToMain::Send(Box::new(transaction)))
+            .await
+            .map_err(|err| SweepError::Failed(err.to_string()))?;
+
+        Ok(transaction_digest)
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn send_data(
+        self,
+        _ctx: context::Context,
+        data: Vec<u8>,
+        fee: NeptuneCoins,
+    ) -> Result<Digest, SendFailure> {
+        self.wallet_enabled()?;
+        let data_carrier =
+            DataCarrier::new(data).map_err(|err| SendFailure::Failed(err.to_string()))?;
+
+        let ticket = self.send_ticket();
+        let transaction = {
+            let mut global_state_mut = ticket
+                .until_cancelled(self.state.lock_guard_mut())
+                .await
+                .map_err(|err| SendFailure::Failed(err.to_string()))?;
+            let max_data_carrier_size = global_state_mut.mempool.policy().max_data_carrier_size;
+            if data_carrier.data.len() > max_data_carrier_size {
+                return Err(SendFailure::Failed(format!(
+                    "the mempool policy allows at most {max_data_carrier_size} bytes of data"
+                )));
             }
+            global_state_mut
+                .create_data_carrier_transaction(data_carrier, fee, Timestamp::now(), ticket)
+                .await
+                .map_err(|err| SendFailure::Failed(err.to_string()))?
         };
+        let transaction_digest = Hash::hash(&transaction);
 
-        // 2. Send transaction message to main
-        let response: Result<(), SendError<RPCServerToMain>> = self
-            .rpc_server_to_main_tx
-            .send(RPCServerToMain::Send(Box::new(transaction.clone())))
-            .await;
+        self.rpc_server_to_main_tx
+            .send(RPCServerToMain::Send(Box::new(transaction)))
+            .await
+            .map_err(|err| SendFailure::Failed(err.to_string()))?;
 
-        // Restart mining if it was paused
-        if was_mining {
-            let _ = self
-                .rpc_server_to_main_tx
-                .send(RPCServerToMain::RestartMiner)
-                .await;
-        }
+        Ok(transaction_digest)
+    }
 
-        self.state.flush_databases().await.expect("flushed DBs");
+    async fn shared_control_key(
+        self,
+        _ctx: context::Context,
+        index: u16,
+    ) -> Result<Digest, WalletDisabled> {
+        self.wallet_enabled()?;
+        let state = self.state.lock_guard().await;
 
-        if response.is_ok() {
-            Some(Hash::hash(&transaction))
-        } else {
-            None
+        Ok(state
+            .wallet_state
+            .wallet_secret
+            .nth_shared_control_unlock_key(index)
+            .hash())
+    }
+
+    async fn shared_control_lock(
+        self,
+        _ctx: context::Context,
+        threshold: usize,
+        cosigner_keys: Vec<Digest>,
+    ) -> Result<String, SharedControlError> {
+        SharedControlLock::new(threshold, cosigner_keys)?
+            .to_bech32m(self.state.cli().network)
+            .map_err(|err| SharedControlError::Failed(err.to_string()))
+    }
+
+    async fn sign_shared_spend(
+        self,
+        _ctx: context::Context,
+        mut shared_spend: SharedSpend,
+        index: u16,
+    ) -> Result<SharedSpend, SharedControlError> {
+        self.wallet_enabled()?;
+        let unlock_key = self
+            .state
+            .lock_guard()
+            .await
+            .wallet_state
+            .wallet_secret
+            .nth_shared_control_unlock_key(index);
+        shared_spend.sign(unlock_key)?;
+
+        Ok(shared_spend)
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for read
+    async fn broadcast_shared_spend(
+        self,
+        _ctx: context::Context,
+        shared_spend: SharedSpend,
+    ) -> Result<Digest, SharedControlError> {
+        let transaction = {
+            let state = self.state.lock_guard().await;
+            let tip_mutator_set_hash = state
+                .chain
+                .light_state()
+                .kernel
+                .body
+                .mutator_set_accumulator
+                .hash();
+            if shared_spend.kernel.mutator_set_hash != tip_mutator_set_hash {
+                return Err(SharedControlError::StaleSpend);
+            }
+
+            // Report missing signatures as such, not as a failed proving job
+            shared_spend.primitive_witness()?;
+            state
+                .create_shared_spend_transaction(&shared_spend)
+                .await
+                .map_err(|err| SharedControlError::Failed(err.to_string()))?
+        };
+        let transaction_digest = Hash::hash(&transaction);
+
+        self.rpc_server_to_main_tx
+            .send(RPCServerToMain::Send(Box::new(transaction)))
+            .await
+            .map_err(|err| SharedControlError::Failed(err.to_string()))?;
+
+        Ok(transaction_digest)
+    }
+
+    /// Locking:
+    ///   * acquires `global_state_lock` for write
+    async fn remove_address_book_entry(self, _ctx: context::Context, name: String) -> bool {
+        let mut global_state_mut = self.state.lock_guard_mut().await;
+        let removed = global_state_mut
+            .wallet_state
+            .wallet_db
+            .remove_address_book_entry(&name)
+            .await;
+        if removed {
+            global_state_mut.wallet_state.record_wallet_changes(1).await;
         }
+
+        global_state_mut
+            .flush_databases()
+            .await
+            .expect("flushed DBs");
+
+        removed
     }
 
     async fn shutdown(self, _: context::Context) -> bool {
@@ -643,6 +1794,50 @@ impl RPC for NeptuneRPCServer {
         response.is_ok()
     }
 
+    async fn broadcast_alert(
+        self,
+        _context: tarpc::context::Context,
+        alert: SignedAlert,
+    ) -> Result<(), AlertError> {
+        self.state
+            .lock_guard_mut()
+            .await
+            .accept_alert(alert.clone(), Timestamp::now())?;
+
+        let _ = self
+            .rpc_server_to_main_tx
+            .send(RPCServerToMain::BroadcastAlert(Box::new(alert)))
+            .await;
+
+        Ok(())
+    }
+
+    async fn cancel_proving_job(self, _context: tarpc::context::Context, id: ProvingJobId) -> bool {
+        self.state.proving_queue().cancel(id)
+    }
+
+    async fn prefer_tip(
+        self,
+        _context: tarpc::context::Context,
+        digest: Digest,
+    ) -> Result<(), PreferTipError> {
+        {
+            let state = self.state.lock_guard().await;
+            if !state.chain.is_archival_node() {
+                return Err(PreferTipError::NotArchival);
+            }
+            let tip_digest = state.chain.light_state().hash();
+            check_preferable_tip(state.chain.archival_state(), tip_digest, digest).await?;
+        }
+
+        let _ = self
+            .rpc_server_to_main_tx
+            .send(RPCServerToMain::PreferTip(digest))
+            .await;
+
+        Ok(())
+    }
+
     async fn pause_miner(self, _context: tarpc::context::Context) {
         if self.state.cli().mine {
             let _ = self
@@ -665,7 +1860,25 @@ impl RPC for NeptuneRPCServer {
         }
     }
 
-    async fn prune_abandoned_monitored_utxos(self, _context: tarpc::context::Context) -> usize {
+    async fn rescan_wallet(
+        self,
+        _context: tarpc::context::Context,
+        from_height: BlockHeight,
+    ) -> Result<(), RescanError> {
+        self.wallet_enabled()?;
+        wallet_rescan::start_rescan(self.state.clone(), from_height).await
+    }
+
+    async fn cancel_rescan(self, _context: tarpc::context::Context) -> Result<(), RescanError> {
+        self.wallet_enabled()?;
+        self.state.lock_guard().await.wallet_rescan.cancel()
+    }
+
+    async fn prune_abandoned_monitored_utxos(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Result<usize, WalletDisabled> {
+        self.wallet_enabled()?;
         let mut global_state_mut = self.state.lock_guard_mut().await;
         const DEFAULT_MUTXO_PRUNE_DEPTH: usize = 200;
 
@@ -673,34 +1886,269 @@ impl RPC for NeptuneRPCServer {
             .prune_abandoned_monitored_utxos(DEFAULT_MUTXO_PRUNE_DEPTH)
             .await;
 
-        global_state_mut
-            .flush_databases()
-            .await
-            .expect("flushed DBs");
+        global_state_mut
+            .flush_databases()
+            .await
+            .expect("flushed DBs");
+
+        match prune_count_res {
+            Ok(prune_count) => {
+                info!("Marked {prune_count} monitored UTXOs as abandoned");
+                Ok(prune_count)
+            }
+            Err(err) => {
+                error!("Pruning monitored UTXOs failed with error: {err}");
+                Ok(0)
+            }
+        }
+    }
+
+    #[doc = r" Generate a report of all owned and unspent coins, whether time-locked or not."]
+    async fn list_own_coins(
+        self,
+        _context: ::tarpc::context::Context,
+    ) -> Result<Vec<CoinWithPossibleTimeLock>, WalletDisabled> {
+        self.wallet_enabled()?;
+        Ok(self
+            .state
+            .lock_guard()
+            .await
+            .wallet_state
+            .get_all_own_coins_with_possible_timelocks()
+            .await)
+    }
+
+    async fn list_utxos(
+        self,
+        _context: tarpc::context::Context,
+    ) -> Result<Vec<MonitoredUtxoReport>, WalletDisabled> {
+        self.wallet_enabled()?;
+        let state = self.state.lock_guard().await;
+        let tip_digest = state.chain.light_state().hash();
+        Ok(state.wallet_state.list_utxos(tip_digest).await)
+    }
+
+    async fn balance_at(
+        self,
+        _context: tarpc::context::Context,
+        height: BlockHeight,
+    ) -> Result<NeptuneCoins, HistoricalQueryError> {
+        self.wallet_enabled()?;
+        self.state.lock_guard().await.get_balance_at(height).await
+    }
+
+    async fn utxo_unspent_at(
+        self,
+        _context: tarpc::context::Context,
+        addition_record: AdditionRecord,
+        height: BlockHeight,
+    ) -> Result<bool, HistoricalQueryError> {
+        self.wallet_enabled()?;
+        self.state
+            .lock_guard()
+            .await
+            .was_utxo_unspent_at(addition_record, height)
+            .await
+    }
+
+    async fn wallet_delta(
+        self,
+        _context: tarpc::context::Context,
+        since: u64,
+    ) -> Result<Vec<u8>, DeviceSyncError> {
+        self.wallet_enabled()?;
+        self.state
+            .lock_guard()
+            .await
+            .wallet_state
+            .encrypted_wallet_delta(since)
+            .await
+    }
+
+    async fn address_book(self, _context: tarpc::context::Context) -> Vec<AddressBookEntry> {
+        let state = self.state.lock_guard().await;
+        state.wallet_state.wallet_db.address_book().await
+    }
+
+    async fn address_book_entry(
+        self,
+        _context: tarpc::context::Context,
+        name: String,
+    ) -> Option<AddressBookEntry> {
+        let state = self.state.lock_guard().await;
+        state
+            .wallet_state
+            .wallet_db
+            .get_address_book_entry(&name)
+            .await
+    }
+
+    async fn journal_events_since(
+        self,
+        _context: tarpc::context::Context,
+        sequence: u64,
+    ) -> Vec<JournalEntry> {
+        let state = self.state.lock_guard().await;
+        match state
+            .journal
+            .entries_since(sequence, MAX_JOURNAL_REPLAY_COUNT)
+            .await
+        {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("Reading the event journal failed with error: {err}");
+                vec![]
+            }
+        }
+    }
+
+    async fn block_processing_timelines(
+        self,
+        _context: tarpc::context::Context,
+        n: usize,
+    ) -> Vec<BlockProcessingTimeline> {
+        self.state.lock_guard().await.block_timelines.latest(n)
+    }
+
+    async fn block_propagation(
+        self,
+        _context: tarpc::context::Context,
+        n: usize,
+    ) -> BlockPropagationReport {
+        self.state.lock_guard().await.block_propagation.report(n)
+    }
+
+    async fn export_block_tree(
+        self,
+        _context: tarpc::context::Context,
+        depth: u64,
+    ) -> Option<BlockTree> {
+        let state = self.state.lock_guard().await;
+        if !state.chain.is_archival_node() {
+            return None;
+        }
+
+        let tip_digest = state.chain.light_state().hash();
+        Some(BlockTree::collect(state.chain.archival_state(), tip_digest, depth).await)
+    }
+
+    async fn list_forks(self, _context: tarpc::context::Context) -> Vec<ForkTip> {
+        let state = self.state.lock_guard().await;
+        if !state.chain.is_archival_node() {
+            return vec![];
+        }
+
+        let tip_digest = state.chain.light_state().hash();
+        fork_tips(
+            state.chain.archival_state(),
+            tip_digest,
+            state.net.preferred_tip,
+        )
+        .await
+    }
+
+    async fn health(self, _context: tarpc::context::Context) -> HealthReport {
+        HealthReport::from_state(&*self.state.lock_guard().await, Timestamp::now())
+    }
+
+    async fn connection_metrics(
+        self,
+        _context: tarpc::context::Context,
+    ) -> ConnectionMetricsReport {
+        self.state
+            .lock_guard()
+            .await
+            .net
+            .connection_metrics
+            .report(SystemTime::now())
+    }
+
+    async fn active_window_stats(self, _context: tarpc::context::Context) -> ActiveWindowStats {
+        self.state
+            .lock_guard()
+            .await
+            .chain
+            .light_state()
+            .body()
+            .mutator_set_accumulator
+            .swbf_active
+            .stats()
+    }
+
+    async fn memory_status(self, _context: tarpc::context::Context) -> MemoryStatus {
+        MemoryStatus::from_state(&*self.state.lock_guard().await)
+    }
+
+    async fn get_disk_usage(self, _context: tarpc::context::Context) -> Option<DiskUsage> {
+        let journal_usage = {
+            let state = self.state.lock_guard().await;
+            JournalDiskUsage::from_journal(&state.journal).await
+        };
+        let cli = self.state.cli();
+        let disk_usage = match (
+            journal_usage,
+            DataDirectory::get(cli.data_dir.clone(), cli.network),
+        ) {
+            (Ok(journal_usage), Ok(data_dir)) => DiskUsage::measure(&data_dir, journal_usage).await,
+            (Err(err), _) | (_, Err(err)) => Err(err),
+        };
 
-        match prune_count_res {
-            Ok(prune_count) => {
-                info!("Marked {prune_count} monitored UTXOs as abandoned");
-                prune_count
-            }
+        match disk_usage {
+            Ok(disk_usage) => Some(disk_usage),
             Err(err) => {
-                error!("Pruning monitored UTXOs failed with error: {err}");
-                0
+                error!("Measuring disk usage failed: {err:#}");
+                None
             }
         }
     }
 
-    #[doc = r" Generate a report of all owned and unspent coins, whether time-locked or not."]
-    async fn list_own_coins(
+    async fn get_metrics_history(
         self,
-        _context: ::tarpc::context::Context,
-    ) -> Vec<CoinWithPossibleTimeLock> {
+        _context: tarpc::context::Context,
+        hours: u64,
+    ) -> Vec<MetricsSnapshot> {
+        let cli = self.state.cli();
+        let since = Timestamp::millis(
+            Timestamp::now()
+                .0
+                .value()
+                .saturating_sub(hours.saturating_mul(60 * 60 * 1000)),
+        );
+        let history = match DataDirectory::get(cli.data_dir.clone(), cli.network) {
+            Ok(data_dir) => {
+                MetricsHistory::new(&data_dir.metrics_history_file_path())
+                    .read_since(since)
+                    .await
+            }
+            Err(err) => Err(err),
+        };
+
+        history.unwrap_or_else(|err| {
+            error!("Reading the metrics history failed: {err:#}");
+            vec![]
+        })
+    }
+
+    async fn alerts(self, _context: tarpc::context::Context) -> Vec<SignedAlert> {
         self.state
             .lock_guard()
             .await
-            .wallet_state
-            .get_all_own_coins_with_possible_timelocks()
-            .await
+            .net
+            .alerts
+            .active(Timestamp::now())
+    }
+
+    async fn list_proving_jobs(self, _context: tarpc::context::Context) -> Vec<ProvingJobInfo> {
+        self.state.proving_queue().jobs()
+    }
+
+    async fn finalized_block(self, _context: tarpc::context::Context) -> Option<FinalizedBlock> {
+        let state = self.state.lock_guard().await;
+        if !state.chain.is_archival_node() {
+            return None;
+        }
+
+        state.chain.archival_state().finalized_block().await
     }
 
     #[doc = r" Return the temperature of the CPU in degrees Celcius."]
@@ -712,16 +2160,18 @@ impl RPC for NeptuneRPCServer {
 #[cfg(test)]
 mod rpc_server_tests {
     use super::*;
+    use crate::models::state::alerts::{Alert, AlertSecretKey, AlertSeverity};
     use crate::Block;
     use crate::{
         config_models::network::Network,
         models::{peer::PeerSanctionReason, state::wallet::WalletSecret},
         rpc_server::NeptuneRPCServer,
-        tests::shared::mock_genesis_global_state,
+        tests::shared::{make_mock_block, mock_genesis_global_state},
         RPC_CHANNEL_CAPACITY,
     };
     use anyhow::Result;
     use num_traits::{One, Zero};
+    use rand::random;
     use std::net::{IpAddr, Ipv4Addr, SocketAddr};
     use strum::IntoEnumIterator;
     use tracing_test::traced_test;
@@ -768,6 +2218,7 @@ mod rpc_server_tests {
         let _ = rpc_server.clone().own_instance_id(ctx).await;
         let _ = rpc_server.clone().block_height(ctx).await;
         let _ = rpc_server.clone().peer_info(ctx).await;
+        let _ = rpc_server.clone().configured_peers(ctx).await;
         let _ = rpc_server.clone().all_sanctioned_peers(ctx).await;
         let _ = rpc_server.clone().latest_tip_digests(ctx, 2).await;
         let _ = rpc_server
@@ -778,18 +2229,114 @@ mod rpc_server_tests {
             .clone()
             .block_info(ctx, BlockSelector::Digest(Digest::default()))
             .await;
+        let _ = rpc_server
+            .clone()
+            .get_block_hex(ctx, Digest::default())
+            .await;
+        let _ = rpc_server
+            .clone()
+            .get_block_by_height(ctx, BlockHeight::from(u64::MAX))
+            .await;
+        let _ = rpc_server
+            .clone()
+            .get_block_by_digest(ctx, Digest::default())
+            .await;
+        let _ = rpc_server
+            .clone()
+            .get_transaction_hex(ctx, Digest::default())
+            .await;
+        let _ = rpc_server.clone().decode_block(ctx, "00".to_string()).await;
+        let _ = rpc_server
+            .clone()
+            .decode_transaction(ctx, "00".to_string())
+            .await;
         let _ = rpc_server
             .clone()
             .block_digest(ctx, BlockSelector::Digest(Digest::default()))
             .await;
+        let _ = rpc_server
+            .clone()
+            .blocks_in_time_range(ctx, Timestamp::zero(), Timestamp::now())
+            .await;
         let _ = rpc_server.clone().utxo_digest(ctx, 0).await;
         let _ = rpc_server.clone().synced_balance(ctx).await;
+        let _ = rpc_server.clone().timelocked_balance(ctx).await;
+        let _ = rpc_server.clone().balance(ctx).await;
         let _ = rpc_server.clone().history(ctx).await;
+        let _ = rpc_server
+            .clone()
+            .get_transaction_history(ctx, Some(UtxoOrigin::Coinbase))
+            .await;
+        let _ = rpc_server.clone().sent_transactions(ctx).await;
         let _ = rpc_server.clone().wallet_status(ctx).await;
-        let own_receiving_address = rpc_server.clone().own_receiving_address(ctx).await;
+        let own_receiving_address = rpc_server.clone().own_receiving_address(ctx).await.unwrap();
         let _ = rpc_server.clone().mempool_tx_count(ctx).await;
         let _ = rpc_server.clone().mempool_size(ctx).await;
+        let _ = rpc_server
+            .clone()
+            .get_mempool_entry(ctx, Digest::default())
+            .await;
+        let _ = rpc_server
+            .clone()
+            .watch_transaction(ctx, Digest::default(), 1)
+            .await;
         let _ = rpc_server.clone().dashboard_overview_data(ctx).await;
+        let _ = rpc_server.clone().journal_events_since(ctx, 0).await;
+        let _ = rpc_server.clone().block_processing_timelines(ctx, 10).await;
+        let _ = rpc_server.clone().block_propagation(ctx, 10).await;
+        let _ = rpc_server.clone().export_block_tree(ctx, 10).await;
+        let _ = rpc_server.clone().list_forks(ctx).await;
+        let _ = rpc_server.clone().health(ctx).await;
+        let _ = rpc_server.clone().connection_metrics(ctx).await;
+        let _ = rpc_server.clone().memory_status(ctx).await;
+        let _ = rpc_server.clone().dashboard_snapshot(ctx).await;
+        let _ = rpc_server.clone().get_disk_usage(ctx).await;
+        let _ = rpc_server.clone().get_metrics_history(ctx, 24).await;
+        let _ = rpc_server.clone().alerts(ctx).await;
+        let _ = rpc_server.clone().list_proving_jobs(ctx).await;
+        let _ = rpc_server.clone().finalized_block(ctx).await;
+        let _ = rpc_server
+            .clone()
+            .mark_block_final(ctx, Digest::default())
+            .await;
+        let _ = rpc_server
+            .clone()
+            .cancel_proving_job(ctx, ProvingJobId(0))
+            .await;
+        let _ = rpc_server
+            .clone()
+            .sweep_key(
+                ctx,
+                WalletSecret::new_random().to_phrase(),
+                NeptuneCoins::new(1),
+            )
+            .await;
+        let _ = rpc_server
+            .clone()
+            .send_data(ctx, b"hello".to_vec(), NeptuneCoins::new(1))
+            .await;
+        let _ = rpc_server.clone().shared_control_key(ctx, 0).await;
+        let _ = rpc_server
+            .clone()
+            .shared_control_lock(ctx, 1, vec![Digest::default()])
+            .await;
+        let shared_spend = SharedSpend::new(
+            SharedControlLock::new(1, vec![Digest::default()]).unwrap(),
+            vec![],
+            vec![],
+            NeptuneCoins::zero(),
+            Timestamp::now(),
+            MutatorSetAccumulator::default(),
+        )
+        .unwrap();
+        let _ = rpc_server
+            .clone()
+            .sign_shared_spend(ctx, shared_spend.clone(), 0)
+            .await;
+        let _ = rpc_server
+            .clone()
+            .broadcast_shared_spend(ctx, shared_spend)
+            .await;
         let _ = rpc_server
             .clone()
             .validate_address(ctx, "Not a valid address".to_owned(), Network::Testnet)
@@ -806,14 +2353,67 @@ mod rpc_server_tests {
                 NeptuneCoins::one(),
                 own_receiving_address,
                 NeptuneCoins::one(),
+                None,
+                None,
+            )
+            .await;
+        let _ = rpc_server
+            .clone()
+            .prepare_send(
+                ctx,
+                NeptuneCoins::one(),
+                own_receiving_address,
+                NeptuneCoins::one(),
+                Some(Timestamp::now() + Timestamp::days(1)),
             )
             .await;
+        let _ = rpc_server
+            .clone()
+            .confirm_send(ctx, Digest::default(), None)
+            .await;
+        let _ = rpc_server
+            .clone()
+            .save_address_book_entry(ctx, "name".to_owned(), "address".to_owned(), String::new())
+            .await;
+        let _ = rpc_server.clone().address_book(ctx).await;
+        let _ = rpc_server.clone().balance_at(ctx, 0u64.into()).await;
+        let _ = rpc_server
+            .clone()
+            .utxo_unspent_at(ctx, AdditionRecord::new(Digest::default()), 0u64.into())
+            .await;
+        let _ = rpc_server.clone().wallet_delta(ctx, 0).await;
+        let _ = rpc_server
+            .clone()
+            .address_book_entry(ctx, "name".to_owned())
+            .await;
+        let _ = rpc_server
+            .clone()
+            .remove_address_book_entry(ctx, "name".to_owned())
+            .await;
+        let alert = Alert {
+            id: 0,
+            network,
+            severity: AlertSeverity::Info,
+            message: "message".to_owned(),
+            created: Timestamp::now(),
+            expires: Timestamp::now() + Timestamp::hours(1),
+        };
+        let _ = rpc_server
+            .clone()
+            .broadcast_alert(ctx, alert.sign(&AlertSecretKey::generate()))
+            .await;
+        let _ = rpc_server.clone().prefer_tip(ctx, Digest::default()).await;
         let _ = rpc_server.clone().pause_miner(ctx).await;
         let _ = rpc_server.clone().restart_miner(ctx).await;
         let _ = rpc_server
             .clone()
             .prune_abandoned_monitored_utxos(ctx)
             .await;
+        let _ = rpc_server.clone().cancel_rescan(ctx).await;
+        let _ = rpc_server
+            .clone()
+            .rescan_wallet(ctx, BlockHeight::genesis())
+            .await;
         let _ = rpc_server.shutdown(ctx).await;
 
         Ok(())
@@ -824,12 +2424,47 @@ mod rpc_server_tests {
     async fn balance_is_zero_at_init() -> Result<()> {
         // Verify that a wallet not receiving a premine is empty at startup
         let (rpc_server, _) = test_rpc_server(Network::Alpha, WalletSecret::new_random(), 2).await;
-        let balance = rpc_server.synced_balance(context::current()).await;
+        let balance = rpc_server.synced_balance(context::current()).await?;
         assert!(balance.is_zero());
 
         Ok(())
     }
 
+    #[tokio::test]
+    async fn wallet_rpcs_fail_without_wallet() {
+        let (mut rpc_server, _) =
+            test_rpc_server(Network::RegTest, WalletSecret::new_random(), 2).await;
+        // pretend --no-wallet
+        let mut cli = rpc_server.state.cli().clone();
+        cli.no_wallet = true;
+        rpc_server.state.set_cli(cli).await;
+        let ctx = context::current();
+
+        assert_eq!(
+            Err(WalletDisabled),
+            rpc_server.clone().synced_balance(ctx).await
+        );
+        assert_eq!(
+            Err(WalletDisabled),
+            rpc_server.clone().own_receiving_address(ctx).await
+        );
+        assert!(rpc_server.clone().list_utxos(ctx).await.is_err());
+        let sent = rpc_server
+            .clone()
+            .prepare_send(
+                ctx,
+                NeptuneCoins::new(1),
+                generation_address::ReceivingAddress::derive_from_seed(random()),
+                NeptuneCoins::new(1),
+                None,
+            )
+            .await;
+        assert!(matches!(sent, Err(SendFailure::WalletDisabled(_))));
+
+        // node-level RPCs are unaffected
+        assert_eq!(Network::RegTest, rpc_server.network(ctx).await);
+    }
+
     #[allow(clippy::shadow_unrelated)]
     #[traced_test]
     #[tokio::test]
@@ -1115,6 +2750,192 @@ mod rpc_server_tests {
             .is_none());
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn mutator_set_evidence_test() {
+        let (rpc_server, _state_lock) =
+            test_rpc_server(Network::Alpha, WalletSecret::new_random(), 2).await;
+
+        let report = rpc_server
+            .clone()
+            .mutator_set_evidence(context::current(), 0, None)
+            .await
+            .unwrap();
+        assert!(report.evidence.verify(&report.mutator_set_accumulator));
+        assert_eq!(0, report.evidence.auth_path_aocl.leaf_index);
+        assert_eq!(
+            None,
+            report.evidence.is_spent(&report.mutator_set_accumulator)
+        );
+
+        assert!(rpc_server
+            .mutator_set_evidence(context::current(), u64::MAX, None)
+            .await
+            .is_none());
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn audit_supply_test() {
+        let (rpc_server, state_lock) =
+            test_rpc_server(Network::Alpha, WalletSecret::new_random(), 2).await;
+
+        let audit = rpc_server.audit_supply(context::current()).await.unwrap();
+        assert!(audit.is_consistent());
+        assert_eq!(
+            state_lock.lock_guard().await.chain.light_state().hash(),
+            audit.tip_digest
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn prepare_and_confirm_send_without_funds_test() {
+        let (rpc_server, state_lock) =
+            test_rpc_server(Network::Alpha, WalletSecret::new_random(), 2).await;
+        let ctx = context::current();
+        let address = rpc_server.clone().own_receiving_address(ctx).await.unwrap();
+
+        let preview = rpc_server
+            .clone()
+            .prepare_send(
+                ctx,
+                NeptuneCoins::new(1),
+                address,
+                NeptuneCoins::new(1),
+                None,
+            )
+            .await;
+        assert!(matches!(preview, Err(SendFailure::Failed(_))));
+        assert!(state_lock
+            .lock_guard()
+            .await
+            .wallet_state
+            .prepared_sends
+            .is_empty());
+
+        let confirmed = rpc_server.confirm_send(ctx, random(), None).await;
+        assert!(matches!(confirmed, Err(SendFailure::Failed(_))));
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn prepare_and_confirm_send_test() {
+        // The premine of the devnet wallet is spendable on Alpha
+        let network = Network::Alpha;
+        let state_lock = mock_genesis_global_state(network, 2, WalletSecret::devnet_wallet()).await;
+        let (to_main_tx, mut to_main_rx) =
+            tokio::sync::mpsc::channel::<RPCServerToMain>(RPC_CHANNEL_CAPACITY);
+        let rpc_server = NeptuneRPCServer {
+            socket_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080),
+            state: state_lock.clone(),
+            rpc_server_to_main_tx: to_main_tx,
+        };
+        let ctx = context::current();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+        let amount = NeptuneCoins::new(1);
+        let fee = NeptuneCoins::new(1);
+        let balance = state_lock
+            .lock_guard()
+            .await
+            .get_wallet_balance(Timestamp::now())
+            .await;
+
+        let preview = rpc_server
+            .clone()
+            .prepare_send(ctx, amount, address, fee, None)
+            .await
+            .unwrap();
+        assert_eq!(amount, preview.amount);
+        assert_eq!(fee, preview.fee);
+
+        let idempotency_key = "payment".to_string();
+        let transaction_digest = rpc_server
+            .clone()
+            .confirm_send(ctx, preview.token, Some(idempotency_key.clone()))
+            .await
+            .unwrap();
+
+        // The transaction is handed to the main loop, which relays it
+        let Some(RPCServerToMain::Send(transaction)) = to_main_rx.recv().await else {
+            panic!("main loop must receive the transaction");
+        };
+        assert_eq!(transaction_digest, Hash::hash(transaction.as_ref()));
+        assert!(transaction.is_valid());
+
+        // The wallet recorded the send, and expects the change
+        let mut state = state_lock.lock_guard_mut().await;
+        assert!(state.wallet_state.prepared_sends.is_empty());
+        assert_eq!(
+            Some(transaction_digest),
+            state
+                .wallet_state
+                .wallet_db
+                .get_sent_transaction_digest(&idempotency_key)
+                .await
+        );
+        assert_eq!(
+            amount + fee,
+            state
+                .wallet_state
+                .wallet_db
+                .amount_sent_since(Timestamp::now() - Timestamp::hours(1))
+                .await
+        );
+
+        // Once in the mempool, the transaction counts against the balance
+        state.mempool.insert(&transaction);
+        assert!(state.mempool.contains(transaction_digest));
+        let balance_with_mempool = state.get_wallet_balance(Timestamp::now()).await;
+        assert_eq!(balance.confirmed, balance_with_mempool.confirmed);
+        assert_eq!(
+            balance.unconfirmed,
+            balance_with_mempool.unconfirmed + amount + fee
+        );
+    }
+
+    #[traced_test]
+    #[tokio::test]
+    async fn address_book_test() {
+        let network = Network::RegTest;
+        let (rpc_server, _) = test_rpc_server(network, WalletSecret::new_random(), 2).await;
+        let ctx = context::current();
+        let address = rpc_server.clone().own_receiving_address(ctx).await.unwrap();
+        let encoded = address.to_bech32m(network).unwrap();
+
+        let saved = rpc_server
+            .clone()
+            .save_address_book_entry(ctx, "me".to_owned(), encoded, "own address".to_owned())
+            .await;
+        assert_eq!(Ok(false), saved);
+        let invalid = rpc_server
+            .clone()
+            .save_address_book_entry(ctx, "other".to_owned(), "nolgar1".to_owned(), String::new())
+            .await;
+        assert!(matches!(
+            invalid,
+            Err(AddressBookError::InvalidAddress { .. })
+        ));
+
+        let entry = rpc_server
+            .clone()
+            .address_book_entry(ctx, "me".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(address, entry.address);
+        assert_eq!(vec![entry], rpc_server.clone().address_book(ctx).await);
+
+        assert!(
+            rpc_server
+                .clone()
+                .remove_address_book_entry(ctx, "me".to_owned())
+                .await
+        );
+        assert!(rpc_server.address_book(ctx).await.is_empty());
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn block_info_test() {
@@ -1194,6 +3015,72 @@ mod rpc_server_tests {
             .is_none());
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn block_detail_is_found_by_canonical_height_and_by_digest() {
+        let network = Network::RegTest;
+        let (rpc_server, state_lock) =
+            test_rpc_server(network, WalletSecret::new_random(), 2).await;
+        let ctx = context::current();
+        let address = WalletSecret::new_random()
+            .nth_generation_spending_key(0)
+            .to_address();
+
+        // Block 1a is reorganized away by blocks 1b and 2b
+        let genesis = Block::genesis_block(network);
+        let (block_1a, _, _) = make_mock_block(&genesis, None, address, random());
+        let (block_1b, _, _) = make_mock_block(&genesis, None, address, random());
+        let (block_2b, _, _) = make_mock_block(&block_1b, None, address, random());
+        {
+            let mut state = state_lock.lock_guard_mut().await;
+            for block in [&block_1a, &block_1b, &block_2b] {
+                state.set_new_tip(block.clone()).await.unwrap();
+            }
+        }
+
+        let detail_1b = rpc_server
+            .clone()
+            .get_block_by_height(ctx, BlockHeight::from(1u64))
+            .await
+            .unwrap();
+        assert_eq!(BlockDetail::from_block(&block_1b, true), detail_1b);
+        assert_eq!(
+            Some(detail_1b),
+            rpc_server
+                .clone()
+                .get_block_by_digest(ctx, block_1b.hash())
+                .await
+        );
+        assert_eq!(
+            block_2b.body().transaction.kernel.outputs.len(),
+            rpc_server
+                .clone()
+                .get_block_by_height(ctx, BlockHeight::from(2u64))
+                .await
+                .unwrap()
+                .num_addition_records
+        );
+
+        let detail_1a = rpc_server
+            .clone()
+            .get_block_by_digest(ctx, block_1a.hash())
+            .await
+            .unwrap();
+        assert!(!detail_1a.is_canonical);
+        assert_eq!(block_1a.hash(), detail_1a.digest);
+
+        assert!(rpc_server
+            .clone()
+            .get_block_by_height(ctx, BlockHeight::from(3u64))
+            .await
+            .is_none());
+        assert!(rpc_server
+            .clone()
+            .get_block_by_digest(ctx, Digest::default())
+            .await
+            .is_none());
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn block_digest_test() {
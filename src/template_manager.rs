@@ -0,0 +1,138 @@
+//! Decides when the miner should rebuild its block template.
+//!
+//! The miner builds a template when it starts mining and whenever the tip
+//! changes. Transactions that arrive in the mempool afterwards only make it
+//! into a block if the template is rebuilt, so the template manager watches
+//! the mempool and tells the miner to rebuild once the mempool offers
+//! significantly better fees than the template being mined. Bursts of
+//! incoming transactions are debounced: a template is never rebuilt for fees
+//! sooner than [`MIN_TEMPLATE_AGE`] after the previous one was built.
+
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::select;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::*;
+
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::shared::SIZE_20MB_IN_BYTES;
+use crate::models::state::GlobalStateLock;
+
+/// Templates are not rebuilt for better fees more often than this
+pub const MIN_TEMPLATE_AGE: Duration = Duration::from_secs(5);
+
+/// By how much the fees on offer in the mempool must exceed those of the
+/// template being mined, for the template to be rebuilt
+pub const MIN_FEE_INCREASE_PERCENT: u32 = 10;
+
+pub struct TemplateManager {
+    global_state_lock: GlobalStateLock,
+
+    /// Changes whenever a transaction enters the mempool
+    mempool_changed: watch::Receiver<()>,
+
+    /// The fees of the template being mined, set by the miner whenever it
+    /// builds one. `None` if the miner is idle.
+    template_fees: watch::Receiver<Option<NeptuneCoins>>,
+
+    /// Tells the miner to rebuild its template
+    refresh_tx: watch::Sender<()>,
+}
+
+impl TemplateManager {
+    pub fn new(
+        global_state_lock: GlobalStateLock,
+        mempool_changed: watch::Receiver<()>,
+        template_fees: watch::Receiver<Option<NeptuneCoins>>,
+        refresh_tx: watch::Sender<()>,
+    ) -> Self {
+        Self {
+            global_state_lock,
+            mempool_changed,
+            template_fees,
+            refresh_tx,
+        }
+    }
+
+    /// Run until the main loop or the miner goes away
+    pub async fn run(mut self) -> Result<()> {
+        let mut template_built_at = Instant::now();
+        let mut mempool_changed_since = false;
+        loop {
+            let debounce_deadline = template_built_at + MIN_TEMPLATE_AGE;
+            select! {
+                changed = self.mempool_changed.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    mempool_changed_since = true;
+                }
+                changed = self.template_fees.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+
+                    // The new template was built from the mempool as it is
+                    self.template_fees.borrow_and_update();
+                    template_built_at = Instant::now();
+                    mempool_changed_since = false;
+                }
+                _ = tokio::time::sleep_until(debounce_deadline), if mempool_changed_since => {}
+            }
+
+            if !mempool_changed_since || Instant::now() < template_built_at + MIN_TEMPLATE_AGE {
+                continue;
+            }
+            mempool_changed_since = false;
+
+            let Some(template_fees) = *self.template_fees.borrow() else {
+                continue;
+            };
+            let mempool_fees = self.best_mempool_fees().await;
+            if is_significantly_better(mempool_fees, template_fees) {
+                info!("Mempool offers fees of {mempool_fees}, up from {template_fees}. Refreshing block template.");
+                self.refresh_tx.send_replace(());
+            }
+        }
+
+        debug!("Template manager shut down.");
+        Ok(())
+    }
+
+    /// The fees of the transactions a new template would include
+    async fn best_mempool_fees(&self) -> NeptuneCoins {
+        self.global_state_lock
+            .lock_guard()
+            .await
+            .mempool
+            .get_transactions_for_block(SIZE_20MB_IN_BYTES, Timestamp::now())
+            .iter()
+            .map(|transaction| transaction.kernel.fee)
+            .sum()
+    }
+}
+
+/// Whether `mempool_fees` beat `template_fees` by at least
+/// [`MIN_FEE_INCREASE_PERCENT`]
+fn is_significantly_better(mempool_fees: NeptuneCoins, template_fees: NeptuneCoins) -> bool {
+    mempool_fees > template_fees
+        && mempool_fees.scalar_mul(100) >= template_fees.scalar_mul(100 + MIN_FEE_INCREASE_PERCENT)
+}
+
+#[cfg(test)]
+mod template_manager_tests {
+    use super::*;
+
+    #[test]
+    fn only_significantly_better_fees_refresh_the_template() {
+        let coins = NeptuneCoins::new;
+        assert!(is_significantly_better(coins(1), coins(0)));
+        assert!(!is_significantly_better(coins(0), coins(0)));
+        assert!(!is_significantly_better(coins(105), coins(100)));
+        assert!(is_significantly_better(coins(110), coins(100)));
+        assert!(!is_significantly_better(coins(90), coins(100)));
+    }
+}
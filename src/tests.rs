@@ -1 +1,2 @@
+pub mod consensus_tests;
 pub mod shared;
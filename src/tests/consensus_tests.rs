@@ -0,0 +1,238 @@
+//! Golden vectors for block validation.
+//!
+//! Each vector is a serialized block, its parent, the local time at which it
+//! is validated, and whether it must be found valid. The vectors live in
+//! [`VECTORS_DIR`] and are loaded and run through validation, so that a change
+//! of the validation code that changes its verdict on any of them fails the
+//! test, even if the change compiles and the other tests pass.
+//!
+//! The vectors are committed. A vector that is missing, e.g. one that was just
+//! added, is written on first run, after which it must be committed along with
+//! the change. Recorded vectors are only overwritten when the tests run with
+//! the environment variable [`REGENERATE_ENV_VAR`] set, e.g. after a
+//! deliberate consensus change.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config_models::network::Network;
+use crate::models::blockchain::block::block_body::BlockBody;
+use crate::models::blockchain::block::block_header::BlockHeader;
+use crate::models::blockchain::block::Block;
+use crate::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+use crate::models::consensus::timestamp::Timestamp;
+use crate::models::state::wallet::WalletSecret;
+use crate::prelude::twenty_first::amount::u32s::U32s;
+use crate::prelude::twenty_first::math::digest::Digest;
+use crate::tests::shared::make_mock_block;
+
+/// Directory of the golden vectors, relative to the crate root
+pub const VECTORS_DIR: &str = "test_data/consensus";
+
+/// Set to overwrite the golden vectors with freshly generated ones
+pub const REGENERATE_ENV_VAR: &str = "NEPTUNE_REGENERATE_CONSENSUS_VECTORS";
+
+const VECTOR_EXTENSION: &str = "bin";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsensusVector {
+    /// The validation rule this vector exercises
+    pub rule: String,
+    pub previous_block: Block,
+    pub block: Block,
+    pub now: Timestamp,
+    pub valid: bool,
+}
+
+impl ConsensusVector {
+    fn new(rule: &str, previous_block: &Block, block: Block, now: Timestamp, valid: bool) -> Self {
+        Self {
+            rule: rule.to_owned(),
+            previous_block: previous_block.clone(),
+            block,
+            now,
+            valid,
+        }
+    }
+}
+
+fn vectors_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(VECTORS_DIR)
+}
+
+fn with_header(block: &Block, mutate: impl FnOnce(&mut BlockHeader)) -> Block {
+    let mut header = block.header().clone();
+    mutate(&mut header);
+    Block::new(header, block.body().clone(), block.block_type.clone())
+}
+
+fn with_body(block: &Block, mutate: impl FnOnce(&mut BlockBody)) -> Block {
+    let mut body = block.body().clone();
+    mutate(&mut body);
+    Block::new(block.header().clone(), body, block.block_type.clone())
+}
+
+/// Build one valid block on top of the RegTest genesis block, and variations
+/// of it that each break one validation rule. Named by file stem.
+pub fn generate_vectors() -> Vec<(String, ConsensusVector)> {
+    let genesis = Block::genesis_block(Network::RegTest);
+    let beneficiary = WalletSecret::devnet_wallet()
+        .nth_generation_spending_key(0)
+        .to_address();
+    let timestamp = genesis.header().timestamp + Timestamp::minutes(10);
+    let (block, _, _) = make_mock_block(&genesis, Some(timestamp), beneficiary, [1u8; 32]);
+    let now = timestamp;
+
+    let vectors = vec![
+        (
+            "valid_coinbase_block",
+            ConsensusVector::new("all", &genesis, block.clone(), now, true),
+        ),
+        (
+            "height_not_parent_plus_one",
+            ConsensusVector::new(
+                "block height is parent height plus one",
+                &genesis,
+                with_header(&block, |header| header.height = header.height.next()),
+                now,
+                false,
+            ),
+        ),
+        (
+            "wrong_previous_digest",
+            ConsensusVector::new(
+                "block points to parent",
+                &genesis,
+                with_header(&block, |header| {
+                    header.prev_block_digest = Digest::default()
+                }),
+                now,
+                false,
+            ),
+        ),
+        (
+            "block_mmr_not_updated",
+            ConsensusVector::new(
+                "parent is appended to block MMR",
+                &genesis,
+                with_body(&block, |body| {
+                    body.block_mmr_accumulator = genesis.body().block_mmr_accumulator.clone()
+                }),
+                now,
+                false,
+            ),
+        ),
+        (
+            "timestamp_before_parent",
+            ConsensusVector::new(
+                "timestamp is not before parent timestamp",
+                &genesis,
+                with_header(&block, |header| {
+                    header.timestamp = genesis.header().timestamp - Timestamp::seconds(1)
+                }),
+                now,
+                false,
+            ),
+        ),
+        (
+            "wrong_difficulty",
+            ConsensusVector::new(
+                "difficulty follows difficulty control",
+                &genesis,
+                with_header(&block, |header| {
+                    header.difficulty = header.difficulty + U32s::new([1, 0, 0, 0, 0])
+                }),
+                now,
+                false,
+            ),
+        ),
+        (
+            "timestamp_too_far_in_future",
+            ConsensusVector::new(
+                "timestamp is not too far in the future",
+                &genesis,
+                block.clone(),
+                block.earliest_valid_time() - Timestamp::millis(1),
+                false,
+            ),
+        ),
+        (
+            "mutator_set_not_updated",
+            ConsensusVector::new(
+                "mutator set accumulator is updated with the transaction",
+                &genesis,
+                with_body(&block, |body| {
+                    body.mutator_set_accumulator = genesis.body().mutator_set_accumulator.clone()
+                }),
+                now,
+                false,
+            ),
+        ),
+        (
+            "coinbase_exceeds_reward",
+            ConsensusVector::new(
+                "coinbase does not exceed mining reward plus fees",
+                &genesis,
+                with_body(&block, |body| {
+                    let reward = Block::get_mining_reward(block.header().height);
+                    body.transaction.kernel.coinbase = Some(reward + NeptuneCoins::new(1));
+                }),
+                now,
+                false,
+            ),
+        ),
+    ];
+
+    vectors
+        .into_iter()
+        .map(|(name, vector)| (name.to_owned(), vector))
+        .collect()
+}
+
+fn write_vector(dir: &Path, name: &str, vector: &ConsensusVector) {
+    fs::create_dir_all(dir).unwrap();
+    let path = dir.join(name).with_extension(VECTOR_EXTENSION);
+    fs::write(path, bincode::serialize(vector).unwrap()).unwrap();
+}
+
+fn read_vectors(dir: &Path) -> Vec<(String, ConsensusVector)> {
+    let mut vectors = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("Failed to read consensus vectors in {VECTORS_DIR}: {err}"))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == VECTOR_EXTENSION))
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let vector = bincode::deserialize(&fs::read(&path).unwrap())
+                .unwrap_or_else(|err| panic!("Failed to decode consensus vector {name}: {err}"));
+            (name, vector)
+        })
+        .collect::<Vec<_>>();
+    vectors.sort_by(|(a, _), (b, _)| a.cmp(b));
+    vectors
+}
+
+#[test]
+fn golden_blocks_validate_as_recorded() {
+    let dir = vectors_dir();
+    let regenerate = std::env::var_os(REGENERATE_ENV_VAR).is_some();
+    for (name, vector) in generate_vectors() {
+        let path = dir.join(&name).with_extension(VECTOR_EXTENSION);
+        if regenerate || !path.exists() {
+            println!("Recording consensus vector {name} in {VECTORS_DIR}; commit it.");
+            write_vector(&dir, &name, &vector);
+        }
+    }
+
+    let vectors = read_vectors(&dir);
+    for (name, vector) in vectors {
+        assert_eq!(
+            vector.valid,
+            vector.block.is_valid(&vector.previous_block, vector.now),
+            "Consensus vector {name} (rule: {}) must be {}",
+            vector.rule,
+            if vector.valid { "valid" } else { "invalid" }
+        );
+    }
+}
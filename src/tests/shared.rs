@@ -65,8 +65,10 @@ use crate::models::database::PeerDatabases;
 use crate::models::peer::{HandshakeData, PeerInfo, PeerMessage, PeerStanding};
 use crate::models::state::archival_state::ArchivalState;
 use crate::models::state::blockchain_state::{BlockchainArchivalState, BlockchainState};
+use crate::models::state::event_journal::EventJournal;
 use crate::models::state::light_state::LightState;
 use crate::models::state::mempool::Mempool;
+use crate::models::state::mempool_policy::MempoolPolicy;
 use crate::models::state::networking_state::NetworkingState;
 use crate::models::state::wallet::address::generation_address;
 use crate::models::state::wallet::wallet_state::WalletState;
@@ -125,6 +127,9 @@ pub fn get_dummy_peer(address: SocketAddr) -> PeerInfo {
         version: get_dummy_version(),
         port_for_incoming_connections: Some(8080),
         is_archival_node: true,
+        clock_offset_millis: None,
+        advertised_listen_addresses: vec![],
+        blocks_only: false,
     }
 }
 
@@ -170,7 +175,7 @@ pub async fn mock_genesis_global_state(
     peer_count: u8,
     wallet: WalletSecret,
 ) -> GlobalStateLock {
-    let (archival_state, peer_db, _data_dir) = mock_genesis_archival_state(network).await;
+    let (archival_state, peer_db, data_dir) = mock_genesis_archival_state(network).await;
 
     let syncing = false;
     let mut peer_map: HashMap<SocketAddr, PeerInfo> = get_peer_map();
@@ -179,7 +184,7 @@ pub async fn mock_genesis_global_state(
             std::net::SocketAddr::from_str(&format!("123.123.123.{}:8080", i)).unwrap();
         peer_map.insert(peer_address, get_dummy_peer(peer_address));
     }
-    let networking_state = NetworkingState::new(peer_map, peer_db, syncing);
+    let networking_state = NetworkingState::new(peer_map, peer_db, syncing, ByteSize::mb(50));
     let genesis_block = archival_state.get_tip().await;
 
     // Sanity check
@@ -194,13 +199,19 @@ pub async fn mock_genesis_global_state(
         light_state,
         archival_state,
     });
-    let mempool = Mempool::new(ByteSize::gb(1));
+    let mempool = Mempool::new(MempoolPolicy::for_network(network));
     let cli_args = cli_args::Args {
         network,
         ..Default::default()
     };
 
     let wallet_state = mock_genesis_wallet_state(wallet, network).await;
+    let journal = EventJournal::open(
+        data_dir.event_journal_dir_path(),
+        cli_args.journal_retention(),
+    )
+    .await
+    .unwrap();
 
     GlobalStateLock::new(
         wallet_state,
@@ -208,6 +219,7 @@ pub async fn mock_genesis_global_state(
         networking_state,
         cli_args.clone(),
         mempool,
+        journal,
         cli_args.mine,
     )
 }
@@ -739,7 +751,7 @@ pub async fn make_mock_transaction_with_generation_key(
         .collect_vec();
     let spending_key_unlock_keys = input_utxos_mps_keys
         .iter()
-        .map(|(_utxo, _mp, sk)| sk.unlock_key.encode())
+        .map(|(_utxo, _mp, sk)| sk.unlock_key.expose().encode())
         .collect_vec();
     let input_lock_scripts = input_utxos_mps_keys
         .iter()
@@ -0,0 +1,43 @@
+//! The all-zero digest, which no hash function outputs in practice, and which
+//! therefore serves as the sentinel for "no digest", e.g. the parent digest of
+//! the genesis block or the sync label of a wallet that never synced. It also
+//! pads Merkle trees and hash pairs, and stands in for the sender randomness of
+//! premine UTXOs, which have no sender.
+//!
+//! `Digest::default()` is the same value, but does not say that it is meant as
+//! a sentinel. Use [`DigestExt::ZERO`] and [`DigestExt::is_zero`] instead,
+//! except in tests and in the programs under `validity/tasm`, which mirror VM
+//! code.
+
+use crate::prelude::twenty_first::math::b_field_element::BFieldElement;
+use crate::prelude::twenty_first::math::digest::Digest;
+use tasm_lib::DIGEST_LENGTH;
+
+pub trait DigestExt {
+    /// The digest whose elements are all zero
+    const ZERO: Self;
+
+    fn is_zero(&self) -> bool;
+}
+
+impl DigestExt for Digest {
+    const ZERO: Self = Digest::new([BFieldElement::ZERO; DIGEST_LENGTH]);
+
+    fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+}
+
+#[cfg(test)]
+mod digest_ext_tests {
+    use rand::random;
+
+    use super::*;
+
+    #[test]
+    fn only_the_default_digest_is_zero() {
+        assert_eq!(Digest::default(), Digest::ZERO);
+        assert!(Digest::ZERO.is_zero());
+        assert!(!random::<Digest>().is_zero());
+    }
+}
@@ -0,0 +1,180 @@
+//! Compact serde representation for [`Digest`], for use with
+//! `#[serde(with = "crate::util_types::digest_serde")]`.
+//!
+//! The derived representation of a digest is a list of five field elements,
+//! which is verbose in JSON. This module instead produces:
+//!
+//!  - in human-readable formats such as JSON: a `0x`-prefixed hex string, as
+//!    given by [`Digest::to_hex`];
+//!  - in binary formats such as bincode: a fixed-size array of
+//!    [`DIGEST_SIZE_IN_BYTES`] bytes, the canonical value of each element in
+//!    little-endian order.
+//!
+//! Deserialization of human-readable formats also accepts hex without prefix
+//! and the derived representation, so data written before fields switched to
+//! this module can still be read.
+
+use std::fmt;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::prelude::twenty_first::math::b_field_element::BFieldElement;
+use crate::prelude::twenty_first::math::digest::Digest;
+
+const ELEMENT_SIZE_IN_BYTES: usize = 8;
+const DIGEST_ELEMENT_COUNT: usize = 5;
+pub const DIGEST_SIZE_IN_BYTES: usize = DIGEST_ELEMENT_COUNT * ELEMENT_SIZE_IN_BYTES;
+
+const HEX_PREFIX: &str = "0x";
+
+pub fn to_bytes(digest: &Digest) -> [u8; DIGEST_SIZE_IN_BYTES] {
+    let mut bytes = [0u8; DIGEST_SIZE_IN_BYTES];
+    for (chunk, element) in bytes
+        .chunks_exact_mut(ELEMENT_SIZE_IN_BYTES)
+        .zip(digest.values())
+    {
+        chunk.copy_from_slice(&element.value().to_le_bytes());
+    }
+
+    bytes
+}
+
+/// Decode the byte representation of a digest. Fails if any element is not
+/// canonical, i.e. not smaller than the field modulus, so that every digest
+/// has exactly one encoding.
+pub fn from_bytes(bytes: &[u8; DIGEST_SIZE_IN_BYTES]) -> Result<Digest, String> {
+    let mut elements = [BFieldElement::new(0); DIGEST_ELEMENT_COUNT];
+    for (element, chunk) in elements
+        .iter_mut()
+        .zip(bytes.chunks_exact(ELEMENT_SIZE_IN_BYTES))
+    {
+        let value = u64::from_le_bytes(chunk.try_into().unwrap());
+        if value >= BFieldElement::P {
+            return Err(format!("non-canonical field element in digest: {value}"));
+        }
+        *element = BFieldElement::new(value);
+    }
+
+    Ok(Digest::new(elements))
+}
+
+pub fn serialize<S: Serializer>(digest: &Digest, serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&format!("{HEX_PREFIX}{}", digest.to_hex()))
+    } else {
+        // A tuple rather than a byte slice, so no length prefix is written
+        let mut tuple = serializer.serialize_tuple(DIGEST_SIZE_IN_BYTES)?;
+        for byte in to_bytes(digest) {
+            tuple.serialize_element(&byte)?;
+        }
+        tuple.end()
+    }
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Digest, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(HumanReadableDigestVisitor)
+    } else {
+        deserializer.deserialize_tuple(DIGEST_SIZE_IN_BYTES, ByteArrayDigestVisitor)
+    }
+}
+
+struct HumanReadableDigestVisitor;
+
+impl<'de> Visitor<'de> for HumanReadableDigestVisitor {
+    type Value = Digest;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a hex-encoded digest or a list of five field elements")
+    }
+
+    fn visit_str<E: de::Error>(self, hex: &str) -> Result<Digest, E> {
+        let hex = hex.strip_prefix(HEX_PREFIX).unwrap_or(hex);
+        Digest::try_from_hex(hex).map_err(E::custom)
+    }
+
+    // The derived representation of `Digest`
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Digest, A::Error> {
+        Digest::deserialize(de::value::SeqAccessDeserializer::new(seq))
+    }
+}
+
+struct ByteArrayDigestVisitor;
+
+impl<'de> Visitor<'de> for ByteArrayDigestVisitor {
+    type Value = Digest;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an array of {DIGEST_SIZE_IN_BYTES} bytes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Digest, A::Error> {
+        let mut bytes = [0u8; DIGEST_SIZE_IN_BYTES];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+        }
+        from_bytes(&bytes).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod digest_serde_tests {
+    use rand::random;
+    use serde::Serialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Wrapper(#[serde(with = "super")] Digest);
+
+    #[test]
+    fn json_is_prefixed_hex_and_round_trips() {
+        let digest: Digest = random();
+        let json = serde_json::to_string(&Wrapper(digest)).unwrap();
+        assert_eq!(format!("\"0x{}\"", digest.to_hex()), json);
+        assert_eq!(
+            Wrapper(digest),
+            serde_json::from_str::<Wrapper>(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn bincode_is_fixed_size_and_round_trips() {
+        let digest: Digest = random();
+        let encoded = bincode::serialize(&Wrapper(digest)).unwrap();
+        assert_eq!(DIGEST_SIZE_IN_BYTES, encoded.len());
+        assert_eq!(to_bytes(&digest).to_vec(), encoded);
+        assert_eq!(
+            Wrapper(digest),
+            bincode::deserialize::<Wrapper>(&encoded).unwrap()
+        );
+    }
+
+    #[test]
+    fn legacy_and_unprefixed_json_is_accepted() {
+        let digest: Digest = random();
+
+        let legacy_json = serde_json::to_string(&digest).unwrap();
+        assert_eq!(
+            Wrapper(digest),
+            serde_json::from_str::<Wrapper>(&legacy_json).unwrap()
+        );
+
+        let unprefixed_json = format!("\"{}\"", digest.to_hex());
+        assert_eq!(
+            Wrapper(digest),
+            serde_json::from_str::<Wrapper>(&unprefixed_json).unwrap()
+        );
+    }
+
+    #[test]
+    fn non_canonical_bytes_are_rejected() {
+        let bytes = [0xff; DIGEST_SIZE_IN_BYTES];
+        assert!(from_bytes(&bytes).is_err());
+        assert!(bincode::deserialize::<Wrapper>(&bytes).is_err());
+    }
+}
@@ -1,3 +1,6 @@
+pub mod digest_ext;
+pub mod digest_serde;
+pub mod entropy;
 pub mod mutator_set;
 
 #[cfg(test)]
@@ -24,6 +24,7 @@ pub mod boxed_big_array;
 pub mod chunk;
 pub mod chunk_dictionary;
 pub mod mmra_and_membership_proofs;
+pub mod ms_membership_evidence;
 pub mod ms_membership_proof;
 pub mod msa_and_records;
 pub mod mutator_set_accumulator;
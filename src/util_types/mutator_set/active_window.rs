@@ -4,15 +4,38 @@ use arbitrary::Arbitrary;
 use get_size::GetSize;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::ops::Range;
 use twenty_first::math::bfield_codec::BFieldCodec;
 
 use super::chunk::Chunk;
 use super::shared::{CHUNK_SIZE, WINDOW_SIZE};
 
+/// Number of segments the active window is split into, one per chunk.
+pub const NUM_SEGMENTS: u32 = WINDOW_SIZE / CHUNK_SIZE;
+
+/// Occupancy statistics of the active window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActiveWindowStats {
+    /// Number of distinct indices that are set, i.e. the popcount of the
+    /// window read as a plain Bloom filter
+    pub set_bits: u32,
+
+    /// Sum of all counters. An index that was set twice counts twice.
+    pub total_count: u64,
+
+    /// The highest counter of any index
+    pub max_count: u32,
+
+    /// Number of segments, out of [`NUM_SEGMENTS`], with at least one set index
+    pub occupied_segments: u32,
+}
+
 #[derive(Clone, Debug, Eq, Serialize, Deserialize, GetSize, BFieldCodec, Arbitrary)]
 pub struct ActiveWindow {
     // It's OK to store this in memory, since it's on the size of kilobytes, not gigabytes.
+    // Serialized as a flat list, as part of blocks. The archival mutator set
+    // persists it in segments, see `RustyArchivalMutatorSet`.
     pub sbf: Vec<u32>,
 }
 
@@ -161,6 +184,62 @@ impl ActiveWindow {
         false
     }
 
+    /// Split the window into segments of `CHUNK_SIZE` indices, keyed by their
+    /// position in the window. Indices are relative to the segment's start,
+    /// and segments without set indices are omitted.
+    pub fn segments(&self) -> BTreeMap<u32, Vec<u32>> {
+        let mut segments: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+        for index in self.sbf.iter() {
+            segments
+                .entry(index / CHUNK_SIZE)
+                .or_default()
+                .push(index % CHUNK_SIZE);
+        }
+        segments
+    }
+
+    /// Inverse of [`Self::segments`].
+    pub fn from_segments(segments: impl IntoIterator<Item = (u32, Vec<u32>)>) -> Self {
+        let mut sbf = segments
+            .into_iter()
+            .flat_map(|(position, indices)| {
+                assert!(position < NUM_SEGMENTS, "segment outside of window");
+                indices
+                    .into_iter()
+                    .map(move |index| position * CHUNK_SIZE + index)
+            })
+            .collect_vec();
+        sbf.sort();
+
+        Self { sbf }
+    }
+
+    pub fn stats(&self) -> ActiveWindowStats {
+        let mut words = vec![0u64; (WINDOW_SIZE / u64::BITS) as usize];
+        for index in self.sbf.iter() {
+            words[(index / u64::BITS) as usize] |= 1 << (index % u64::BITS);
+        }
+        let set_bits = words.iter().map(|word| word.count_ones()).sum();
+        let occupied_segments = words
+            .chunks((CHUNK_SIZE / u64::BITS) as usize)
+            .filter(|segment| segment.iter().any(|word| *word != 0))
+            .count() as u32;
+        let max_count = self
+            .sbf
+            .iter()
+            .counts()
+            .into_values()
+            .max()
+            .unwrap_or_default() as u32;
+
+        ActiveWindowStats {
+            set_bits,
+            total_count: self.sbf.len() as u64,
+            max_count,
+            occupied_segments,
+        }
+    }
+
     pub fn to_vec_u32(&self) -> Vec<u32> {
         self.sbf.clone()
     }
@@ -315,6 +394,46 @@ mod active_window_tests {
         assert_eq!(aw0.sbf, aw0_back.sbf);
     }
 
+    #[test]
+    fn segments_round_trip_and_stats_count_distinct_indices() {
+        let mut aw = ActiveWindow::new();
+        assert_eq!(ActiveWindowStats::default(), aw.stats());
+
+        for index in [5, 5, CHUNK_SIZE + 1, WINDOW_SIZE - 1] {
+            aw.insert(index);
+        }
+
+        let segments = aw.segments();
+        assert_eq!(vec![5, 5], segments[&0]);
+        assert_eq!(vec![1], segments[&1]);
+        assert_eq!(vec![CHUNK_SIZE - 1], segments[&(NUM_SEGMENTS - 1)]);
+        assert_eq!(aw, ActiveWindow::from_segments(segments));
+
+        let stats = aw.stats();
+        assert_eq!(3, stats.set_bits);
+        assert_eq!(4, stats.total_count);
+        assert_eq!(2, stats.max_count);
+        assert_eq!(3, stats.occupied_segments);
+    }
+
+    #[test]
+    fn test_full_active_window_serialization() {
+        let mut rng = thread_rng();
+
+        let mut aw = ActiveWindow::new();
+        for _ in 0..(3 * CHUNK_SIZE) {
+            aw.insert(rng.next_u32() % WINDOW_SIZE);
+        }
+
+        // The encoding is part of the block format, and must not change
+        let encoded = bincode::serialize(&aw).unwrap();
+        assert_eq!(bincode::serialize(&aw.sbf).unwrap(), encoded);
+        assert_eq!(aw, bincode::deserialize::<ActiveWindow>(&encoded).unwrap());
+
+        let json = serde_json::to_string(&aw).unwrap();
+        assert_eq!(aw, serde_json::from_str::<ActiveWindow>(&json).unwrap());
+    }
+
     #[test]
     fn test_active_window_decode() {
         let mut rng = thread_rng();
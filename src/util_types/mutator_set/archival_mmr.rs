@@ -1,5 +1,6 @@
 use crate::database::storage::storage_vec::traits::*;
 use crate::prelude::twenty_first;
+use crate::util_types::digest_ext::DigestExt;
 
 use tasm_lib::twenty_first::util_types::mmr::shared_advanced::get_authentication_path_node_indices;
 use tasm_lib::twenty_first::util_types::mmr::shared_advanced::get_peak_heights_and_peak_node_indices;
@@ -10,6 +11,8 @@ use twenty_first::math::digest::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 use twenty_first::util_types::shared::bag_peaks;
 
+use futures::stream::Stream;
+use std::collections::BTreeSet;
 use std::marker::PhantomData;
 
 use itertools::Itertools;
@@ -85,6 +88,19 @@ where
         }
     }
 
+    /// Return a stream over all leafs and their indices. Leafs appended after
+    /// the call are not included. The stream borrows the MMR, so the MMR
+    /// cannot change while the stream is alive.
+    pub async fn stream_leafs(&self) -> impl Stream<Item = (u64, Digest)> + '_ {
+        let num_leafs = self.count_leaves().await;
+        let node_indices = (0..num_leafs).map(shared_advanced::leaf_index_to_node_index);
+        self.digests
+            .stream_many_values(node_indices)
+            .await
+            .enumerate()
+            .map(|(leaf_index, leaf)| (leaf_index as u64, leaf))
+    }
+
     /// Mutate an existing leaf.
     pub async fn mutate_leaf(&mut self, leaf_index: u64, new_leaf: Digest) {
         // 1. change the leaf value
@@ -121,6 +137,40 @@ where
         }
     }
 
+    /// Mutate several existing leafs. Unlike repeated calls to `mutate_leaf`,
+    /// every ancestor of the mutated leafs is recomputed only once.
+    pub async fn batch_mutate_leaf(&mut self, mutation_data: Vec<(u64, Digest)>) {
+        let num_nodes = self.digests.len().await;
+        let mut dirty_parents = BTreeSet::new();
+        let leaf_writes = mutation_data.into_iter().map(|(leaf_index, new_leaf)| {
+            let node_index = shared_advanced::leaf_index_to_node_index(leaf_index);
+            let parent_index = shared_advanced::parent(node_index);
+            if parent_index < num_nodes {
+                dirty_parents.insert(parent_index);
+            }
+            (node_index, new_leaf)
+        });
+        let leaf_writes = leaf_writes.collect_vec();
+        self.digests.set_many(leaf_writes).await;
+
+        // Children have smaller node indices than their parents, so visiting
+        // nodes in ascending order recomputes children before their parents.
+        while let Some(node_index) = dirty_parents.pop_first() {
+            let (_, height) = shared_advanced::right_lineage_length_and_own_height(node_index);
+            let left_child = node_index - (1 << height);
+            let right_child = node_index - 1;
+            let children = self.digests.get_many(&[left_child, right_child]).await;
+            self.digests
+                .set(node_index, H::hash_pair(children[0], children[1]))
+                .await;
+
+            let parent_index = shared_advanced::parent(node_index);
+            if parent_index < num_nodes {
+                dirty_parents.insert(parent_index);
+            }
+        }
+    }
+
     /// Modify a bunch of leafs and keep a set of membership proofs in sync. Notice that this
     /// function is not just the application of `mutate_leaf` multiple times, as it also preserves
     /// a list of membership proofs.
@@ -134,9 +184,7 @@ where
             "Duplicated leaves are not allowed in membership proof updater"
         );
 
-        for (leaf_index, digest) in mutation_data.iter() {
-            self.mutate_leaf(*leaf_index, *digest).await;
-        }
+        self.batch_mutate_leaf(mutation_data).await;
 
         let mut modified_mps: Vec<usize> = vec![];
         for (i, mp) in membership_proofs.iter_mut().enumerate() {
@@ -184,7 +232,7 @@ impl<H: AlgebraicHasher, Storage: StorageVec<Digest>> ArchivalMmr<H, Storage> {
     /// is the empty vector. This method fixes that.
     pub async fn fix_dummy_async(&mut self) {
         if self.digests.len().await == 0 {
-            self.digests.push(Digest::default()).await;
+            self.digests.push(Digest::ZERO).await;
         }
     }
 
@@ -301,7 +349,7 @@ pub(crate) mod mmr_test {
 
     impl<H: AlgebraicHasher, Storage: StorageVec<Digest>> ArchivalMmr<H, Storage> {
         /// Return the number of nodes in all the trees in the MMR
-        async fn count_nodes(&mut self) -> u64 {
+        async fn count_nodes(&self) -> u64 {
             self.digests.len().await - 1
         }
     }
@@ -452,6 +500,23 @@ pub(crate) mod mmr_test {
         ));
     }
 
+    #[tokio::test]
+    async fn stream_leafs_test() {
+        type H = Tip5;
+
+        let leaf_hashes: Vec<Digest> = random_elements(13);
+        let archival_mmr = mock::get_ammr_from_digests::<H>(leaf_hashes.clone()).await;
+
+        let stream = archival_mmr.stream_leafs().await;
+        pin_mut!(stream);
+        let mut streamed_leafs = vec![];
+        while let Some((leaf_index, leaf)) = stream.next().await {
+            assert_eq!(streamed_leafs.len() as u64, leaf_index);
+            streamed_leafs.push(leaf);
+        }
+        assert_eq!(leaf_hashes, streamed_leafs);
+    }
+
     #[tokio::test]
     async fn mutate_leaf_archival_test() {
         type H = Tip5;
@@ -596,6 +661,14 @@ pub(crate) mod mmr_test {
                     archival_batch_mut.get_peaks().await,
                     archival_individual_mut.get_peaks().await
                 );
+                for leaf_index in 0..size as u64 {
+                    assert_eq!(
+                        archival_batch_mut.prove_membership_async(leaf_index).await,
+                        archival_individual_mut
+                            .prove_membership_async(leaf_index)
+                            .await
+                    );
+                }
             }
         }
     }
@@ -5,7 +5,9 @@ use crate::util_types::mutator_set::{get_swbf_indices, MutatorSetError};
 
 use std::collections::{BTreeSet, HashMap};
 use std::error::Error;
+use std::sync::Arc;
 
+use futures::stream::Stream;
 use itertools::Itertools;
 use twenty_first::math::tip5::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
@@ -17,9 +19,10 @@ use super::addition_record::AdditionRecord;
 use super::archival_mmr::ArchivalMmr;
 use super::chunk::Chunk;
 use super::chunk_dictionary::ChunkDictionary;
+use super::ms_membership_evidence::MsMembershipEvidence;
 use super::ms_membership_proof::MsMembershipProof;
 use super::mutator_set_accumulator::MutatorSetAccumulator;
-use super::removal_record::RemovalRecord;
+use super::removal_record::{AbsoluteIndexSet, RemovalRecord};
 use super::shared::{BATCH_SIZE, CHUNK_SIZE};
 
 pub struct ArchivalMutatorSet<MmrStorage, ChunkStorage>
@@ -159,6 +162,14 @@ where
         }
     }
 
+    /// Return a stream over all addition records in the append-only commitment
+    /// list (AOCL), as `(leaf index, canonical commitment)` pairs. The stream
+    /// borrows the mutator set, so it sees a consistent snapshot: records
+    /// added after the call are not included.
+    pub async fn stream_aocl_leafs(&self) -> impl Stream<Item = (u64, Digest)> + '_ {
+        self.aocl.stream_leafs().await
+    }
+
     /// Returns an authentication path for an element in the append-only commitment list
     pub async fn get_aocl_authentication_path(
         &self,
@@ -199,6 +210,40 @@ where
         Ok((chunk_auth_path, chunk))
     }
 
+    /// Produce evidence that the AOCL leaf with the given index is in the set,
+    /// and of whether the given Bloom filter indices are set, which can be
+    /// verified against the accumulator of this mutator set.
+    pub async fn membership_evidence(
+        &self,
+        aocl_leaf_index: u64,
+        absolute_indices: Option<AbsoluteIndexSet>,
+    ) -> Result<MsMembershipEvidence, Box<dyn Error>> {
+        let auth_path_aocl = self.get_aocl_authentication_path(aocl_leaf_index).await?;
+        let aocl_leaf = self.aocl.get_leaf_async(aocl_leaf_index).await;
+
+        let batch_index = self.get_batch_index_async().await as u64;
+        let chunk_indices: BTreeSet<u64> = absolute_indices
+            .iter()
+            .flat_map(|indices| indices.to_vec())
+            .map(|index| (index / CHUNK_SIZE as u128) as u64)
+            .filter(|chunk_index| *chunk_index < batch_index)
+            .collect();
+        let mut target_chunks = ChunkDictionary::default();
+        for chunk_index in chunk_indices {
+            let (chunk_membership_proof, chunk) = self.get_chunk_and_auth_path(chunk_index).await?;
+            target_chunks
+                .dictionary
+                .insert(chunk_index, (chunk_membership_proof, Arc::new(chunk)));
+        }
+
+        Ok(MsMembershipEvidence {
+            aocl_leaf,
+            auth_path_aocl,
+            absolute_indices,
+            target_chunks,
+        })
+    }
+
     /// Restore membership_proof. If called on someone else's UTXO, this leaks privacy. In this case,
     /// caller is better off using `get_aocl_authentication_path` and `get_chunk_and_auth_path` for the
     /// relevant indices.
@@ -238,7 +283,7 @@ where
                 self.swbf_inactive.prove_membership_async(chunk_index).await;
             target_chunks
                 .dictionary
-                .insert(chunk_index, (chunk_membership_proof, chunk.to_owned()));
+                .insert(chunk_index, (chunk_membership_proof, Arc::new(chunk)));
         }
 
         Ok(MsMembershipProof {
@@ -253,16 +298,26 @@ where
     /// were inserted by it. These live in either the active window, or
     /// in a relevant chunk.
     pub async fn revert_remove(&mut self, removal_record: &RemovalRecord) {
-        let removal_record_indices: Vec<u128> = removal_record.absolute_indices.to_vec();
+        self.batch_revert_remove(std::slice::from_ref(removal_record))
+            .await;
+    }
+
+    /// Revert a number of `RemovalRecord`s, e.g. all those of a block, in one
+    /// pass: every affected chunk is read and written once, and the inactive
+    /// part of the Bloom filter is updated with one batch mutation.
+    pub async fn batch_revert_remove(&mut self, removal_records: &[RemovalRecord]) {
         let batch_index = self.get_batch_index_async().await;
         let active_window_start = batch_index * CHUNK_SIZE as u128;
         let mut chunkidx_to_difference_dict: HashMap<u64, Chunk> = HashMap::new();
 
         // Populate the dictionary by iterating over all the removal
-        // record's indices and inserting them into the correct
+        // records' indices and inserting them into the correct
         // chunk in the dictionary, if the index is in the inactive
         // part. Otherwise, remove the index from the active window.
-        for rr_index in removal_record_indices {
+        for rr_index in removal_records
+            .iter()
+            .flat_map(|removal_record| removal_record.absolute_indices.to_vec())
+        {
             if rr_index >= active_window_start {
                 let relative_index = (rr_index - active_window_start) as u32;
                 self.swbf_active.remove(relative_index);
@@ -276,24 +331,34 @@ where
             }
         }
 
-        for (chunk_index, revert_chunk) in chunkidx_to_difference_dict {
-            // For each chunk, subtract the difference from the chunk.
-            let previous_chunk = self.chunks.get(chunk_index).await;
-            let mut new_chunk = previous_chunk;
-            new_chunk.subtract(revert_chunk.clone());
+        // For each chunk, subtract the difference from the chunk.
+        let chunk_indices = chunkidx_to_difference_dict
+            .keys()
+            .copied()
+            .sorted()
+            .collect_vec();
+        let mut new_chunks = self.chunks.get_many(&chunk_indices).await;
+        for (chunk_index, new_chunk) in chunk_indices.iter().zip(new_chunks.iter_mut()) {
+            let revert_chunk = chunkidx_to_difference_dict.remove(chunk_index).unwrap();
+            new_chunk.subtract(revert_chunk);
+        }
 
-            // update archival mmr
-            self.swbf_inactive
-                .mutate_leaf(chunk_index, Hash::hash(&new_chunk))
-                .await;
+        // update archival mmr
+        let mutation_data = chunk_indices
+            .iter()
+            .copied()
+            .zip(new_chunks.iter().map(Hash::hash))
+            .collect_vec();
+        self.swbf_inactive.batch_mutate_leaf(mutation_data).await;
 
-            self.chunks.set(chunk_index, new_chunk).await;
-        }
+        self.chunks
+            .set_many(chunk_indices.into_iter().zip(new_chunks))
+            .await;
     }
 
     /// Determine whether the given `AdditionRecord` can be reversed.
     /// Equivalently, determine if it was added last.
-    pub async fn add_is_reversible(&mut self, addition_record: &AdditionRecord) -> bool {
+    pub async fn add_is_reversible(&self, addition_record: &AdditionRecord) -> bool {
         let leaf_index = self.aocl.count_leaves().await - 1;
         let digest = self.aocl.get_leaf_async(leaf_index).await;
         addition_record.canonical_commitment == digest
@@ -306,30 +371,52 @@ where
     ///   from the inactive window, and slide window back by putting the
     ///   last inactive chunk in the active window.
     pub async fn revert_add(&mut self, addition_record: &AdditionRecord) {
-        let removed_add_index = self.aocl.count_leaves().await - 1;
+        self.batch_revert_add(std::slice::from_ref(addition_record))
+            .await;
+    }
 
-        // 1. Remove last leaf from AOCL
-        let digest = self.aocl.remove_last_leaf_async().await.unwrap();
-        assert_eq!(addition_record.canonical_commitment, digest);
+    /// Revert the last added `AdditionRecord`s, e.g. all those of a block.
+    /// The records must be given in the order they were added, and are
+    /// reverted last to first, as if by repeated calls to `revert_add`.
+    pub async fn batch_revert_add(&mut self, addition_records: &[AdditionRecord]) {
+        let num_leaves = self.aocl.count_leaves().await;
+        let num_reverted = addition_records.len() as u64;
+        assert!(
+            num_reverted <= num_leaves,
+            "Cannot revert {num_reverted} additions from an AOCL with {num_leaves} leaves."
+        );
+        let removed_add_indices = num_leaves - num_reverted..num_leaves;
+
+        // 1. Remove last leafs from AOCL
+        for addition_record in addition_records.iter().rev() {
+            let digest = self.aocl.remove_last_leaf_async().await.unwrap();
+            assert_eq!(
+                addition_record.canonical_commitment, digest,
+                "Addition records must be the last ones added."
+            );
+        }
 
-        // 2. Possibly shrink bloom filter by moving a chunk back into active window
+        // 2. Possibly shrink bloom filter by moving chunks back into active window
         //
         // This happens when the batch index changes (i.e. every `BATCH_SIZE` addition).
-        if !MutatorSetAccumulator::window_slides_back(removed_add_index) {
-            return;
+        let num_slides = removed_add_indices
+            .filter(|removed_add_index| {
+                MutatorSetAccumulator::window_slides_back(*removed_add_index)
+            })
+            .count();
+        for _ in 0..num_slides {
+            // 2.a. Remove a chunk from inactive window
+            let _digest = self.swbf_inactive.remove_last_leaf_async().await;
+            let last_inactive_chunk = self.chunks.pop().await.unwrap();
+
+            // 2.b. Slide active window back by putting `last_inactive_chunk` back
+            self.swbf_active.slide_window_back(&last_inactive_chunk);
         }
-
-        // 2.a. Remove a chunk from inactive window
-        let _digest = self.swbf_inactive.remove_last_leaf_async().await;
-        let last_inactive_chunk = self.chunks.pop().await.unwrap();
-
-        // 2.b. Slide active window back by putting `last_inactive_chunk` back
-        self.swbf_active.slide_window_back(&last_inactive_chunk);
     }
 
     /// Determine whether the index `index` is set in the Bloom
     /// filter, whether in the active window, or in some chunk.
-    pub async fn bloom_filter_contains(&mut self, index: u128) -> bool {
+    pub async fn bloom_filter_contains(&self, index: u128) -> bool {
         let batch_index = self.get_batch_index_async().await;
         let active_window_start = batch_index * CHUNK_SIZE as u128;
 
@@ -447,9 +534,10 @@ where
                         removal_record
                     )
                 });
+            let relevant_chunk = Arc::make_mut(&mut relevant_chunk.1);
             for index in indices {
                 let relative_index = (index % CHUNK_SIZE as u128) as u32;
-                relevant_chunk.1.insert(relative_index);
+                relevant_chunk.insert(relative_index);
             }
         }
 
@@ -459,7 +547,7 @@ where
         let all_leafs = new_target_chunks
             .dictionary
             .values()
-            .map(|(_p, chunk)| Hash::hash(chunk));
+            .map(|(_p, chunk)| Hash::hash(&*chunk));
         let mutation_data = target_chunk_indices.into_iter().zip(all_leafs).collect();
 
         // If we want to update the membership proof with this removal, we
@@ -471,7 +559,7 @@ where
         new_target_chunks
             .dictionary
             .into_iter()
-            .map(|(chunk_index, (_mp, chunk))| (chunk_index, chunk))
+            .map(|(chunk_index, (_mp, chunk))| (chunk_index, Arc::unwrap_or_clone(chunk)))
             .collect()
     }
 }
@@ -483,7 +571,6 @@ mod archival_mutator_set_tests {
     use rand::{Rng, SeedableRng};
 
     use crate::util_types::mutator_set::commit;
-    use crate::util_types::mutator_set::removal_record::AbsoluteIndexSet;
     use crate::util_types::mutator_set::shared::{BATCH_SIZE, NUM_TRIALS};
     use crate::util_types::test_shared::mutator_set::{
         empty_rusty_mutator_set, make_item_and_randomnesses,
@@ -603,6 +690,129 @@ mod archival_mutator_set_tests {
         }
     }
 
+    #[tokio::test]
+    async fn batch_revert_matches_sequential_revert() {
+        let mut rms_batch = empty_rusty_mutator_set().await;
+        let mut rms_sequential = empty_rusty_mutator_set().await;
+        let ams_batch = rms_batch.ams_mut();
+        let ams_sequential = rms_sequential.ams_mut();
+
+        // Build a mutator set with a slid window and some removals
+        let mut records = vec![];
+        for _ in 0..5 * BATCH_SIZE {
+            let record = prepare_random_addition(ams_batch).await;
+            ams_batch.add(&record.1).await;
+            ams_sequential.add(&record.1).await;
+            records.push(record);
+        }
+        let commitment_before_block = ams_batch.hash().await;
+
+        // Apply a "block" that crosses window slides and removes old items
+        let mut removal_records = vec![];
+        for (leaf_index, (item, _, membership_proof)) in records.iter().enumerate().step_by(3) {
+            let membership_proof = ams_batch
+                .restore_membership_proof(
+                    *item,
+                    membership_proof.sender_randomness,
+                    membership_proof.receiver_preimage,
+                    leaf_index as u64,
+                )
+                .await
+                .unwrap();
+            removal_records.push(ams_batch.drop(*item, &membership_proof).await);
+        }
+        let mut block_additions = vec![];
+        for _ in 0..3 * BATCH_SIZE {
+            block_additions.push(prepare_random_addition(ams_batch).await.1);
+        }
+        for ams in [&mut *ams_batch, &mut *ams_sequential] {
+            ams.batch_remove(removal_records.clone(), &mut []).await;
+            for addition_record in block_additions.iter() {
+                ams.add(addition_record).await;
+            }
+        }
+
+        // Revert the block
+        ams_batch.batch_revert_add(&block_additions).await;
+        ams_batch.batch_revert_remove(&removal_records).await;
+        for addition_record in block_additions.iter().rev() {
+            ams_sequential.revert_add(addition_record).await;
+        }
+        for removal_record in removal_records.iter() {
+            ams_sequential.revert_remove(removal_record).await;
+        }
+
+        assert_eq!(commitment_before_block, ams_batch.hash().await);
+        assert_eq!(commitment_before_block, ams_sequential.hash().await);
+        assert_eq!(ams_sequential.swbf_active, ams_batch.swbf_active);
+        assert_eq!(
+            ams_sequential.chunks.get_all().await,
+            ams_batch.chunks.get_all().await
+        );
+    }
+
+    #[tokio::test]
+    async fn membership_evidence_reveals_spent_status() {
+        let mut rms = empty_rusty_mutator_set().await;
+        let archival_mutator_set = rms.ams_mut();
+
+        let mut records = vec![];
+        for _ in 0..3 * BATCH_SIZE {
+            let record = prepare_random_addition(archival_mutator_set).await;
+            archival_mutator_set.add(&record.1).await;
+            records.push(record);
+        }
+
+        // Spend the first item, whose indices are now partly in inactive chunks
+        let (item, _, membership_proof) = &records[0];
+        let membership_proof = archival_mutator_set
+            .restore_membership_proof(
+                *item,
+                membership_proof.sender_randomness,
+                membership_proof.receiver_preimage,
+                0,
+            )
+            .await
+            .unwrap();
+        let removal_record = archival_mutator_set.drop(*item, &membership_proof).await;
+        archival_mutator_set.remove(&removal_record).await;
+
+        let accumulator = archival_mutator_set.accumulator().await;
+        let spent_evidence = archival_mutator_set
+            .membership_evidence(0, Some(removal_record.absolute_indices.clone()))
+            .await
+            .unwrap();
+        assert!(spent_evidence.verify(&accumulator));
+        assert_eq!(Some(true), spent_evidence.is_spent(&accumulator));
+
+        let (item, _, membership_proof) = &records[1];
+        let unspent_indices = AbsoluteIndexSet::new(&get_swbf_indices(
+            *item,
+            membership_proof.sender_randomness,
+            membership_proof.receiver_preimage,
+            1,
+        ));
+        let unspent_evidence = archival_mutator_set
+            .membership_evidence(1, Some(unspent_indices))
+            .await
+            .unwrap();
+        assert!(unspent_evidence.verify(&accumulator));
+        assert_eq!(Some(false), unspent_evidence.is_spent(&accumulator));
+
+        let leaf_only_evidence = archival_mutator_set
+            .membership_evidence(2, None)
+            .await
+            .unwrap();
+        assert!(leaf_only_evidence.verify(&accumulator));
+        assert_eq!(None, leaf_only_evidence.is_spent(&accumulator));
+        assert!(!leaf_only_evidence.verify(&MutatorSetAccumulator::default()));
+
+        assert!(archival_mutator_set
+            .membership_evidence(3 * BATCH_SIZE as u64, None)
+            .await
+            .is_err());
+    }
+
     #[tokio::test]
     async fn bloom_filter_is_reversible() {
         // With the `3086841408u32` seed a collission is generated at i = 1 and i = 38, on index 510714
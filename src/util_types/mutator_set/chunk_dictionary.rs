@@ -7,8 +7,12 @@ use get_size::GetSize;
 use itertools::Itertools;
 use rand::rngs::StdRng;
 use rand::{Rng, RngCore, SeedableRng};
-use serde::{Deserialize, Serialize};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 use triton_vm::prelude::Digest;
 use twenty_first::math::bfield_codec::BFieldCodec;
 
@@ -16,15 +20,96 @@ use super::chunk::Chunk;
 use twenty_first::math::b_field_element::BFieldElement;
 use twenty_first::util_types::mmr::mmr_membership_proof::MmrMembershipProof;
 
-#[derive(Clone, Debug, Serialize, Deserialize, GetSize, PartialEq, Eq, Default, Arbitrary)]
+/// The chunks of the inactive part of the sliding-window Bloom filter that a
+/// membership proof or removal record refers to.
+///
+/// Chunks are reference counted, so the many membership proofs and removal
+/// records referring to the same chunk share a single copy of it. Mutating a
+/// shared chunk through [`Arc::make_mut`] copies it first.
+///
+/// Serializes as a flat sequence of `(chunk index, membership proof, chunk)`
+/// entries, sorted by chunk index, which is deserialized one entry at a time.
+#[derive(Clone, Debug, GetSize, PartialEq, Eq, Default, Arbitrary)]
 pub struct ChunkDictionary {
     // {chunk index => (MMR membership proof for the whole chunk to which index belongs, chunk value)}
-    pub dictionary: HashMap<u64, (MmrMembershipProof<Hash>, Chunk)>,
+    pub dictionary: HashMap<u64, (MmrMembershipProof<Hash>, Arc<Chunk>)>,
 }
 
 impl ChunkDictionary {
     pub fn new(dictionary: HashMap<u64, (MmrMembershipProof<Hash>, Chunk)>) -> Self {
-        Self { dictionary }
+        Self {
+            dictionary: dictionary
+                .into_iter()
+                .map(|(chunk_index, (mmr_mp, chunk))| (chunk_index, (mmr_mp, Arc::new(chunk))))
+                .collect(),
+        }
+    }
+}
+
+/// Upper bound on the number of entries reserved for before they are read, so
+/// a bogus length prefix cannot cause a large allocation.
+const MAX_PREALLOCATED_ENTRIES: usize = 64;
+
+impl Serialize for ChunkDictionary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.dictionary.len()))?;
+        for chunk_index in self.dictionary.keys().sorted() {
+            let (membership_proof, chunk) = &self.dictionary[chunk_index];
+            seq.serialize_element(&(chunk_index, membership_proof, &**chunk))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkDictionary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(ChunkDictionaryVisitor)
+        } else {
+            deserializer.deserialize_seq(ChunkDictionaryVisitor)
+        }
+    }
+}
+
+/// The representation derived before chunk dictionaries were serialized as a
+/// sequence of entries.
+#[derive(Deserialize)]
+struct LegacyChunkDictionary {
+    dictionary: HashMap<u64, (MmrMembershipProof<Hash>, Chunk)>,
+}
+
+struct ChunkDictionaryVisitor;
+
+impl<'de> Visitor<'de> for ChunkDictionaryVisitor {
+    type Value = ChunkDictionary;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence of chunk dictionary entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ChunkDictionary, A::Error> {
+        let capacity = seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATED_ENTRIES);
+        let mut dictionary = HashMap::with_capacity(capacity);
+        while let Some((chunk_index, membership_proof, chunk)) =
+            seq.next_element::<(u64, MmrMembershipProof<Hash>, Chunk)>()?
+        {
+            if dictionary
+                .insert(chunk_index, (membership_proof, Arc::new(chunk)))
+                .is_some()
+            {
+                return Err(de::Error::custom(format!(
+                    "duplicate chunk index in chunk dictionary: {chunk_index}"
+                )));
+            }
+        }
+
+        Ok(ChunkDictionary { dictionary })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<ChunkDictionary, A::Error> {
+        let legacy =
+            LegacyChunkDictionary::deserialize(de::value::MapAccessDeserializer::new(map))?;
+        Ok(ChunkDictionary::new(legacy.dictionary))
     }
 }
 
@@ -80,7 +165,7 @@ impl BFieldCodec for ChunkDictionary {
             let chunk = *Chunk::decode(&sequence[read_index..read_index + chunk_length])?;
             read_index += chunk_length;
 
-            dictionary.insert(key, (membership_proof, chunk));
+            dictionary.insert(key, (membership_proof, Arc::new(chunk)));
         }
 
         Ok(Box::new(ChunkDictionary { dictionary }))
@@ -226,7 +311,69 @@ mod chunk_dict_tests {
         let s_back_non_empty = serde_json::from_str::<ChunkDictionary>(&json_non_empty).unwrap();
         assert!(!s_back_non_empty.dictionary.is_empty());
         assert!(s_back_non_empty.dictionary.contains_key(&key));
-        assert_eq!((mp, chunk), s_back_non_empty.dictionary[&key]);
+        assert_eq!((mp, Arc::new(chunk)), s_back_non_empty.dictionary[&key]);
+    }
+
+    #[test]
+    fn cloned_dictionaries_share_chunks_until_mutated() {
+        let chunk_dictionary = random_chunk_dictionary();
+        let mut clone = chunk_dictionary.clone();
+        let chunk_index = *chunk_dictionary.dictionary.keys().next().unwrap();
+
+        assert!(Arc::ptr_eq(
+            &chunk_dictionary.dictionary[&chunk_index].1,
+            &clone.dictionary[&chunk_index].1
+        ));
+
+        let (_, chunk) = clone.dictionary.get_mut(&chunk_index).unwrap();
+        Arc::make_mut(chunk).insert(0);
+        assert!(!Arc::ptr_eq(
+            &chunk_dictionary.dictionary[&chunk_index].1,
+            &clone.dictionary[&chunk_index].1
+        ));
+        assert_ne!(chunk_dictionary, clone);
+    }
+
+    #[test]
+    fn serialization_is_deterministic_and_round_trips() {
+        let chunk_dictionary = random_chunk_dictionary();
+
+        let encoded = bincode::serialize(&chunk_dictionary).unwrap();
+        assert_eq!(
+            encoded,
+            bincode::serialize(&chunk_dictionary.clone()).unwrap()
+        );
+        assert_eq!(
+            chunk_dictionary,
+            bincode::deserialize::<ChunkDictionary>(&encoded).unwrap()
+        );
+
+        let json = serde_json::to_string(&chunk_dictionary).unwrap();
+        assert_eq!(
+            chunk_dictionary,
+            serde_json::from_str::<ChunkDictionary>(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn legacy_json_is_accepted_and_duplicates_are_rejected() {
+        let chunk_dictionary = random_chunk_dictionary();
+        let legacy_json = serde_json::json!({
+            "dictionary": chunk_dictionary
+                .dictionary
+                .iter()
+                .map(|(chunk_index, (mp, chunk))| (chunk_index.to_string(), (mp, &**chunk)))
+                .collect::<HashMap<_, _>>()
+        });
+        assert_eq!(
+            chunk_dictionary,
+            serde_json::from_value::<ChunkDictionary>(legacy_json).unwrap()
+        );
+
+        let (chunk_index, (mp, chunk)) = chunk_dictionary.dictionary.iter().next().unwrap();
+        let entry = (chunk_index, mp, &**chunk);
+        let duplicated = bincode::serialize(&vec![entry, entry]).unwrap();
+        assert!(bincode::deserialize::<ChunkDictionary>(&duplicated).is_err());
     }
 
     #[test]
@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use tasm_lib::twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
+use tasm_lib::twenty_first::util_types::mmr::mmr_membership_proof::MmrMembershipProof;
+use tasm_lib::twenty_first::util_types::mmr::mmr_trait::Mmr;
+use tasm_lib::Digest;
+
+use crate::Hash;
+
+use super::chunk_dictionary::ChunkDictionary;
+use super::mutator_set_accumulator::MutatorSetAccumulator;
+use super::removal_record::AbsoluteIndexSet;
+use super::shared::{indices_to_hash_map, CHUNK_SIZE};
+
+/// Evidence that an addition record is in the append-only commitment list
+/// (AOCL), and of which of a set of Bloom filter indices are set, that can be
+/// checked against a [`MutatorSetAccumulator`] alone.
+///
+/// This lets someone who does not run an archival node, e.g. an auditor,
+/// verify claims about the supply and about whether a UTXO was spent.
+/// Unlike [`MsMembershipProof`](super::ms_membership_proof::MsMembershipProof)
+/// it does not contain the secrets from which the indices are derived, so the
+/// indices must be supplied by whoever knows them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MsMembershipEvidence {
+    /// The canonical commitment of the addition record
+    pub aocl_leaf: Digest,
+    pub auth_path_aocl: MmrMembershipProof<Hash>,
+
+    /// The absolute Bloom filter indices the evidence covers, if any
+    pub absolute_indices: Option<AbsoluteIndexSet>,
+
+    /// The inactive chunks that contain any of the indices
+    pub target_chunks: ChunkDictionary,
+}
+
+impl MsMembershipEvidence {
+    /// Verify that the AOCL leaf and all chunks the indices require are
+    /// committed to by the mutator set accumulator.
+    pub fn verify(&self, mutator_set_accumulator: &MutatorSetAccumulator) -> bool {
+        let aocl = &mutator_set_accumulator.aocl;
+        if !self
+            .auth_path_aocl
+            .verify(&aocl.get_peaks(), self.aocl_leaf, aocl.count_leaves())
+        {
+            return false;
+        }
+
+        let swbf_inactive = &mutator_set_accumulator.swbf_inactive;
+        let all_chunks_are_valid =
+            self.target_chunks
+                .dictionary
+                .values()
+                .all(|(membership_proof, chunk)| {
+                    membership_proof.verify(
+                        &swbf_inactive.get_peaks(),
+                        Hash::hash(&**chunk),
+                        swbf_inactive.count_leaves(),
+                    )
+                });
+        if !all_chunks_are_valid {
+            return false;
+        }
+
+        let Some(absolute_indices) = &self.absolute_indices else {
+            return true;
+        };
+        let batch_index = mutator_set_accumulator.get_batch_index();
+        indices_to_hash_map(&absolute_indices.to_array())
+            .keys()
+            .all(|chunk_index| {
+                *chunk_index >= batch_index
+                    || self.target_chunks.dictionary.contains_key(chunk_index)
+            })
+    }
+
+    /// Return true if all indices are set, i.e. if the UTXO they belong to
+    /// was spent, or `None` if the evidence covers no indices. Meaningful
+    /// only if [`Self::verify`] succeeds for the same accumulator.
+    pub fn is_spent(&self, mutator_set_accumulator: &MutatorSetAccumulator) -> Option<bool> {
+        let absolute_indices = self.absolute_indices.as_ref()?;
+        let window_start = mutator_set_accumulator.get_batch_index() as u128 * CHUNK_SIZE as u128;
+
+        let all_indices_are_set = absolute_indices.to_vec().into_iter().all(|index| {
+            if index >= window_start {
+                mutator_set_accumulator
+                    .swbf_active
+                    .contains((index - window_start) as u32)
+            } else {
+                let chunk_index = (index / CHUNK_SIZE as u128) as u64;
+                self.target_chunks
+                    .dictionary
+                    .get(&chunk_index)
+                    .is_some_and(|(_, chunk)| chunk.contains((index % CHUNK_SIZE as u128) as u32))
+            }
+        });
+
+        Some(all_indices_are_set)
+    }
+}
@@ -12,6 +12,7 @@ use std::error::Error;
 use std::fmt;
 use std::marker::PhantomData;
 use std::ops::IndexMut;
+use std::sync::Arc;
 use tasm_lib::structure::tasm_object::TasmObject;
 use twenty_first::math::bfield_codec::BFieldCodec;
 use twenty_first::math::tip5::Digest;
@@ -111,8 +112,8 @@ impl MsMembershipProof {
         // window does slide
         let batch_index = new_item_index / BATCH_SIZE as u64;
         let old_window_start_batch_index = batch_index - 1;
-        let new_chunk = mutator_set.swbf_active.slid_chunk();
-        let new_chunk_digest: Digest = Hash::hash(&new_chunk);
+        let new_chunk = Arc::new(mutator_set.swbf_active.slid_chunk());
+        let new_chunk_digest: Digest = Hash::hash(&*new_chunk);
 
         // Insert the new chunk digest into the accumulator-version of the
         // SWBF MMR to get its authentication path. It's important to convert the MMR
@@ -170,7 +171,7 @@ impl MsMembershipProof {
         // Perform the updates
 
         // First insert the new entry into the chunk dictionary for the membership
-        // proofs that need it.
+        // proofs that need it. They all share the same copy of the new chunk.
         for i in mps_for_new_chunk_dictionary_entry.iter() {
             membership_proofs
                 .index_mut(*i)
@@ -262,8 +263,8 @@ impl MsMembershipProof {
         }
 
         // window does slide
-        let new_chunk = mutator_set.swbf_active.slid_chunk();
-        let new_chunk_digest: Digest = Hash::hash(&new_chunk);
+        let new_chunk = Arc::new(mutator_set.swbf_active.slid_chunk());
+        let new_chunk_digest: Digest = Hash::hash(&*new_chunk);
 
         // Get indices by recalculating them. (We do not cache indices any more.)
         let all_indices = get_swbf_indices(
@@ -330,7 +331,7 @@ impl MsMembershipProof {
                 // add dictionary entry
                 self.target_chunks
                     .dictionary
-                    .insert(chunk_index, (new_auth_path.clone(), new_chunk.clone()));
+                    .insert(chunk_index, (new_auth_path.clone(), Arc::clone(&new_chunk)));
                 swbf_chunk_dictionary_updated = true;
 
                 continue 'outer;
@@ -620,7 +621,7 @@ mod ms_proof_tests {
         mp_mutated
             .target_chunks
             .dictionary
-            .insert(0, (mmr_mp, zero_chunk));
+            .insert(0, (mmr_mp, Arc::new(zero_chunk)));
         assert_ne!(mp_mutated, base_mp);
     }
 
@@ -1,15 +1,14 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::models::blockchain::shared::Hash;
 use crate::prelude::twenty_first;
+use crate::util_types::digest_ext::DigestExt;
 
 use get_size::GetSize;
 use itertools::Itertools;
-use num_traits::Zero;
 use serde::{Deserialize, Serialize};
-use tasm_lib::twenty_first::math::b_field_element::BFieldElement;
 use tasm_lib::twenty_first::util_types::mmr::mmr_membership_proof::MmrMembershipProof;
-use tasm_lib::DIGEST_LENGTH;
 use twenty_first::math::bfield_codec::BFieldCodec;
 use twenty_first::math::tip5::Digest;
 use twenty_first::util_types::mmr::mmr_trait::Mmr;
@@ -139,9 +138,10 @@ impl MutatorSetAccumulator {
                         removal_record
                     )
                 });
+            let relevant_chunk = Arc::make_mut(&mut relevant_chunk.1);
             for index in indices {
                 let relative_index = (index % CHUNK_SIZE as u128) as u32;
-                relevant_chunk.1.insert(relative_index);
+                relevant_chunk.insert(relative_index);
             }
         }
 
@@ -154,7 +154,7 @@ impl MutatorSetAccumulator {
         let all_leafs = new_target_chunks
             .dictionary
             .values()
-            .map(|(_p, chunk)| Hash::hash(chunk));
+            .map(|(_p, chunk)| Hash::hash(&*chunk));
         let mutation_data: Vec<(MmrMembershipProof<Hash>, Digest)> =
             all_mmr_membership_proofs.zip(all_leafs).collect();
 
@@ -166,7 +166,7 @@ impl MutatorSetAccumulator {
         new_target_chunks
             .dictionary
             .into_iter()
-            .map(|(chunk_index, (_mp, chunk))| (chunk_index, chunk))
+            .map(|(chunk_index, (_mp, chunk))| (chunk_index, Arc::unwrap_or_clone(chunk)))
             .collect()
     }
 
@@ -245,10 +245,7 @@ impl MutatorSetAccumulator {
         // verify that a commitment to the item lives in the aocl mmr
         let leaf = Hash::hash_pair(
             Hash::hash_pair(item, membership_proof.sender_randomness),
-            Hash::hash_pair(
-                membership_proof.receiver_preimage,
-                Digest::new([BFieldElement::zero(); DIGEST_LENGTH]),
-            ),
+            Hash::hash_pair(membership_proof.receiver_preimage, Digest::ZERO),
         );
         let is_aocl_member = membership_proof.auth_path_aocl.verify(
             &self.aocl.get_peaks(),
@@ -289,14 +286,14 @@ impl MutatorSetAccumulator {
                     break 'outer;
                 }
 
-                let mp_and_chunk: &(MmrMembershipProof<Hash>, Chunk) = membership_proof
+                let mp_and_chunk: &(MmrMembershipProof<Hash>, Arc<Chunk>) = membership_proof
                     .target_chunks
                     .dictionary
                     .get(&chunk_index)
                     .unwrap();
                 let valid_auth_path = mp_and_chunk.0.verify(
                     &self.swbf_inactive.get_peaks(),
-                    Hash::hash(&mp_and_chunk.1),
+                    Hash::hash(&*mp_and_chunk.1),
                     self.swbf_inactive.count_leaves(),
                 );
 
@@ -352,11 +349,10 @@ impl MutatorSetAccumulator {
         let aocl_mmr_bagged = self.aocl.bag_peaks();
         let inactive_swbf_bagged = self.swbf_inactive.bag_peaks();
         let active_swbf_bagged = Hash::hash(&self.swbf_active);
-        let default = Digest::default();
 
         Hash::hash_pair(
             Hash::hash_pair(aocl_mmr_bagged, inactive_swbf_bagged),
-            Hash::hash_pair(active_swbf_bagged, default),
+            Hash::hash_pair(active_swbf_bagged, Digest::ZERO),
         )
     }
 
@@ -396,34 +392,36 @@ impl MutatorSetAccumulator {
 
             // Collect all affected chunks as they look before these removal records are applied
             // These chunks are part of the removal records, so we fetch them there.
-            let mut mutation_data_preimage: HashMap<u64, (&mut Chunk, MmrMembershipProof<Hash>)> =
-                HashMap::new();
+            let mut mutation_data_preimage: HashMap<
+                u64,
+                (&mut Arc<Chunk>, MmrMembershipProof<Hash>),
+            > = HashMap::new();
             for removal_record in removal_records.iter_mut() {
                 for (chunk_index, (mmr_mp, chunk)) in
                     removal_record.target_chunks.dictionary.iter_mut()
                 {
-                    let chunk_hash = Hash::hash(chunk);
+                    let chunk_hash = Hash::hash(&**chunk);
                     let prev_val =
                         mutation_data_preimage.insert(*chunk_index, (chunk, mmr_mp.to_owned()));
 
                     // Sanity check that all removal records agree on both chunks and MMR membership
                     // proofs.
                     if let Some((chnk, mm)) = prev_val {
-                        assert!(mm == *mmr_mp && chunk_hash == Hash::hash(chnk))
+                        assert!(mm == *mmr_mp && chunk_hash == Hash::hash(&**chnk))
                     }
                 }
             }
 
             // Apply the removal records: the new chunk is obtained by adding the chunk difference
             for (chunk_index, (chunk, _)) in mutation_data_preimage.iter_mut() {
-                **chunk = chunk
-                    .clone()
-                    .combine(chunkidx_to_chunk_difference_dict[chunk_index].clone())
-                    .clone();
+                **chunk = Arc::new(
+                    Chunk::clone(chunk)
+                        .combine(chunkidx_to_chunk_difference_dict[chunk_index].clone()),
+                );
             }
 
             // Set the chunk values in the membership proofs that we want to preserve to the
-            // newly calculated chunk values.
+            // newly calculated chunk values, which they then share.
             // This is done by looping over all membership proofs and checking if they contain
             // any of the chunks that are affected by the removal records.
             for mp in preserved_membership_proofs.iter_mut() {
@@ -440,7 +438,7 @@ impl MutatorSetAccumulator {
             let swbf_inactive_mutation_data: Vec<(MmrMembershipProof<Hash>, Digest)> =
                 mutation_data_preimage
                     .into_values()
-                    .map(|x| (x.1, Hash::hash(x.0)))
+                    .map(|x| (x.1, Hash::hash(&**x.0)))
                     .collect();
 
             // Create a vector of pointers to the MMR-membership part of the mutator set membership
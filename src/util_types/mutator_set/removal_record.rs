@@ -13,6 +13,7 @@ use serde_derive::Serialize;
 use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::ops::IndexMut;
+use std::sync::Arc;
 use tasm_lib::structure::tasm_object::TasmObject;
 use twenty_first::math::bfield_codec::BFieldCodec;
 use twenty_first::math::tip5::Digest;
@@ -151,8 +152,8 @@ impl RemovalRecord {
         }
 
         // window does slide
-        let new_chunk = mutator_set.swbf_active.slid_chunk();
-        let new_chunk_digest: Digest = Hash::hash(&new_chunk);
+        let new_chunk = Arc::new(mutator_set.swbf_active.slid_chunk());
+        let new_chunk_digest: Digest = Hash::hash(&*new_chunk);
 
         // Insert the new chunk digest into the accumulator-version of the
         // SWBF MMR to get its authentication path. It's important to convert the MMR
@@ -204,7 +205,7 @@ impl RemovalRecord {
         // Perform the updates
 
         // First insert the new entry into the chunk dictionary for the removal
-        // record that need it.
+        // record that need it. They all share the same copy of the new chunk.
         for i in rrs_for_new_chunk_dictionary_entry.iter() {
             removal_records
                 .index_mut(*i)
@@ -212,7 +213,7 @@ impl RemovalRecord {
                 .dictionary
                 .insert(
                     old_window_start_batch_index,
-                    (new_swbf_auth_path.clone(), new_chunk.clone()),
+                    (new_swbf_auth_path.clone(), Arc::clone(&new_chunk)),
                 );
         }
 
@@ -284,7 +285,7 @@ impl RemovalRecord {
             .dictionary
             .iter()
             .all(|(_i, (proof, chunk))| {
-                let leaf_digest = Hash::hash(chunk);
+                let leaf_digest = Hash::hash(&*chunk);
                 let leaf_count = mutator_set.swbf_inactive.count_leaves();
                 proof.verify(&peaks, leaf_digest, leaf_count)
             })
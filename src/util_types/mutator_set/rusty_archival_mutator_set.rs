@@ -1,3 +1,5 @@
+use itertools::Itertools;
+
 use crate::database::storage::storage_schema::{
     traits::*, DbtSingleton, DbtVec, RustyKey, RustyValue, SimpleRustyStorage,
 };
@@ -5,11 +7,14 @@ use crate::database::NeptuneLevelDb;
 use crate::prelude::twenty_first;
 use crate::Hash;
 
+use std::collections::HashMap;
 use twenty_first::math::tip5::Digest;
 
 use super::{
-    active_window::ActiveWindow, archival_mmr::ArchivalMmr,
-    archival_mutator_set::ArchivalMutatorSet, chunk::Chunk,
+    active_window::{ActiveWindow, NUM_SEGMENTS},
+    archival_mmr::ArchivalMmr,
+    archival_mutator_set::ArchivalMutatorSet,
+    chunk::Chunk,
 };
 
 type AmsMmrStorage = DbtVec<Digest>;
@@ -17,7 +22,21 @@ type AmsChunkStorage = DbtVec<Chunk>;
 pub struct RustyArchivalMutatorSet {
     ams: ArchivalMutatorSet<AmsMmrStorage, AmsChunkStorage>,
     storage: SimpleRustyStorage,
+
+    /// The whole active window as one value, as stored by earlier versions.
+    /// Only read to migrate to `active_window_segments`.
     active_window_storage: DbtSingleton<Vec<u32>>,
+
+    /// The active window, split into one segment per chunk. The segment of
+    /// a chunk is stored in slot `chunk_index % NUM_SEGMENTS`, so that sliding
+    /// the window changes no other segment, and the chunk entering the window
+    /// reuses the slot of the chunk that slid out of it.
+    active_window_segments: DbtVec<Vec<u32>>,
+
+    /// The non-empty segments as last persisted, by absolute chunk index, to
+    /// find the segments that changed since.
+    persisted_segments: HashMap<u64, Vec<u32>>,
+
     sync_label: DbtSingleton<Digest>,
 }
 
@@ -36,6 +55,10 @@ impl RustyArchivalMutatorSet {
             .schema
             .new_singleton::<Vec<u32>>("active_window")
             .await;
+        let active_window_segments = storage
+            .schema
+            .new_vec::<Vec<u32>>("active_window_segments")
+            .await;
         let sync_label = storage.schema.new_singleton::<Digest>("sync_label").await;
 
         let ams = ArchivalMutatorSet::<AmsMmrStorage, AmsChunkStorage> {
@@ -50,9 +73,19 @@ impl RustyArchivalMutatorSet {
             storage,
             sync_label,
             active_window_storage: active_window,
+            active_window_segments,
+            persisted_segments: HashMap::new(),
         }
     }
 
+    /// A read-only view of the mutator set as last persisted, which later
+    /// changes do not affect. See [`NeptuneLevelDb::snapshot`].
+    pub async fn snapshot(&self) -> Self {
+        let mut snapshot = Self::connect(self.storage.db().snapshot()).await;
+        snapshot.restore_or_new().await;
+        snapshot
+    }
+
     #[inline]
     pub fn ams(&self) -> &ArchivalMutatorSet<AmsMmrStorage, AmsChunkStorage> {
         &self.ams
@@ -80,15 +113,115 @@ impl RustyArchivalMutatorSet {
         self.ams_mut().swbf_inactive.fix_dummy_async().await;
 
         // populate active window
-        self.ams_mut().swbf_active.sbf = self.active_window_storage.get().await;
+        let legacy_active_window = self.active_window_storage.get().await;
+        if !legacy_active_window.is_empty() {
+            // Segments are written on the next persist, which also clears
+            // the legacy value.
+            self.ams_mut().swbf_active.sbf = legacy_active_window;
+            self.persisted_segments.clear();
+            return;
+        }
+
+        let window_start = self.window_start_chunk_index().await;
+        let num_slots = self.active_window_segments.len().await;
+        let chunk_indices = (window_start..window_start + NUM_SEGMENTS as u64)
+            .filter(|chunk_index| segment_slot(*chunk_index) < num_slots)
+            .collect_vec();
+        let slots = chunk_indices
+            .iter()
+            .copied()
+            .map(segment_slot)
+            .collect_vec();
+        let segments = self.active_window_segments.get_many(&slots).await;
+        self.persisted_segments = chunk_indices
+            .into_iter()
+            .zip(segments)
+            .filter(|(_, segment)| !segment.is_empty())
+            .collect();
+        self.ams_mut().swbf_active =
+            ActiveWindow::from_segments(self.persisted_segments.iter().map(
+                |(chunk_index, segment)| ((chunk_index - window_start) as u32, segment.clone()),
+            ));
+    }
+
+    /// Write the given segments, by absolute chunk index, to their slots.
+    /// Emptied segments are written first, so that a chunk that entered the
+    /// window, whether sliding forward or back, overwrites the emptied
+    /// segment of the chunk that left it.
+    async fn write_segments(&mut self, mut segments: Vec<(u64, Vec<u32>)>) {
+        segments.sort_by_key(|(_, segment)| !segment.is_empty());
+        if let Some(last_slot) = segments
+            .iter()
+            .map(|(chunk_index, _)| segment_slot(*chunk_index))
+            .max()
+        {
+            while self.active_window_segments.len().await <= last_slot {
+                self.active_window_segments.push(vec![]).await;
+            }
+        }
+        for (chunk_index, segment) in segments {
+            if segment.is_empty() {
+                self.persisted_segments.remove(&chunk_index);
+            } else {
+                self.persisted_segments.insert(chunk_index, segment.clone());
+            }
+            self.active_window_segments
+                .set(segment_slot(chunk_index), segment)
+                .await;
+        }
+    }
+
+    /// The absolute index of the chunk at the start of the active window.
+    async fn window_start_chunk_index(&self) -> u64 {
+        self.ams().get_batch_index_async().await as u64
+    }
+
+    /// Return the segments of the active window that changed since it was
+    /// last persisted, by absolute chunk index. Segments that became empty
+    /// are returned as such.
+    async fn changed_segments(&self) -> Vec<(u64, Vec<u32>)> {
+        let window_start = self.window_start_chunk_index().await;
+        let segments: HashMap<u64, Vec<u32>> = self
+            .ams()
+            .swbf_active
+            .segments()
+            .into_iter()
+            .map(|(position, segment)| (window_start + position as u64, segment))
+            .collect();
+
+        let changed = segments
+            .iter()
+            .filter(|(chunk_index, segment)| {
+                self.persisted_segments.get(chunk_index) != Some(segment)
+            })
+            .map(|(chunk_index, segment)| (*chunk_index, segment.clone()));
+        let emptied = self
+            .persisted_segments
+            .keys()
+            .filter(|chunk_index| !segments.contains_key(chunk_index))
+            .map(|chunk_index| (*chunk_index, vec![]));
+
+        changed
+            .chain(emptied)
+            .sorted_by_key(|(chunk_index, _)| *chunk_index)
+            .collect()
     }
 }
 
+/// The slot of `active_window_segments` that holds the segment of the chunk
+/// with the given absolute index.
+fn segment_slot(chunk_index: u64) -> u64 {
+    chunk_index % NUM_SEGMENTS as u64
+}
+
 impl StorageWriter for RustyArchivalMutatorSet {
     async fn persist(&mut self) {
-        self.active_window_storage
-            .set(self.ams().swbf_active.sbf.clone())
-            .await;
+        let changed_segments = self.changed_segments().await;
+        self.write_segments(changed_segments).await;
+
+        if !self.active_window_storage.get().await.is_empty() {
+            self.active_window_storage.set(vec![]).await;
+        }
 
         self.storage.persist().await;
     }
@@ -96,13 +229,13 @@ impl StorageWriter for RustyArchivalMutatorSet {
 
 #[cfg(test)]
 mod tests {
-    use itertools::Itertools;
     use rand::{random, thread_rng, RngCore};
     use twenty_first::math::tip5::Tip5;
 
     use crate::util_types::mutator_set::commit;
     use crate::util_types::mutator_set::{
-        ms_membership_proof::MsMembershipProof, shared::BATCH_SIZE,
+        ms_membership_proof::MsMembershipProof,
+        shared::{BATCH_SIZE, CHUNK_SIZE, WINDOW_SIZE},
     };
     use crate::util_types::test_shared::mutator_set::*;
 
@@ -172,10 +305,7 @@ mod tests {
             let index = rng.next_u64() as usize % items.len();
             let item = items[index];
             let membership_proof = mps[index].clone();
-            let removal_record = rusty_mutator_set
-                .ams_mut()
-                .drop(item, &membership_proof)
-                .await;
+            let removal_record = rusty_mutator_set.ams().drop(item, &membership_proof).await;
             MsMembershipProof::batch_update_from_remove(
                 &mut mps.iter_mut().collect_vec(),
                 &removal_record,
@@ -241,4 +371,144 @@ mod tests {
 
         assert_eq!(active_window_before, active_window_after);
     }
+
+    #[tokio::test]
+    async fn only_changed_segments_are_persisted() {
+        let db = NeptuneLevelDb::open_new_test_database(false, None, None, None)
+            .await
+            .unwrap();
+        let mut rusty_mutator_set = RustyArchivalMutatorSet::connect(db).await;
+        rusty_mutator_set.restore_or_new().await;
+        assert!(rusty_mutator_set.changed_segments().await.is_empty());
+
+        let active_window = &mut rusty_mutator_set.ams_mut().swbf_active;
+        active_window.insert(3);
+        active_window.insert(CHUNK_SIZE + 3);
+        assert_eq!(2, rusty_mutator_set.changed_segments().await.len());
+
+        rusty_mutator_set.persist().await;
+        assert!(rusty_mutator_set.changed_segments().await.is_empty());
+
+        rusty_mutator_set.ams_mut().swbf_active.remove(3);
+        assert_eq!(
+            vec![(0, vec![])],
+            rusty_mutator_set.changed_segments().await
+        );
+    }
+
+    #[tokio::test]
+    async fn segments_that_slid_out_of_the_window_are_overwritten() {
+        let db = NeptuneLevelDb::open_new_test_database(false, None, None, None)
+            .await
+            .unwrap();
+        let mut rusty_mutator_set = RustyArchivalMutatorSet::connect(db).await;
+        rusty_mutator_set.restore_or_new().await;
+
+        let last_chunk_index = NUM_SEGMENTS as u64 - 1;
+        rusty_mutator_set
+            .write_segments(vec![(0, vec![1]), (last_chunk_index, vec![2])])
+            .await;
+        assert_eq!(
+            NUM_SEGMENTS as u64,
+            rusty_mutator_set.active_window_segments.len().await
+        );
+
+        // The window slid by two chunks: chunk 0 left it, chunks
+        // `NUM_SEGMENTS` and `NUM_SEGMENTS + 1` entered it.
+        let entered = NUM_SEGMENTS as u64;
+        rusty_mutator_set
+            .write_segments(vec![
+                (0, vec![]),
+                (entered, vec![3]),
+                (entered + 1, vec![4]),
+            ])
+            .await;
+        assert_eq!(
+            NUM_SEGMENTS as u64,
+            rusty_mutator_set.active_window_segments.len().await
+        );
+        assert_eq!(
+            vec![3],
+            rusty_mutator_set.active_window_segments.get(0).await
+        );
+        assert_eq!(
+            vec![4],
+            rusty_mutator_set.active_window_segments.get(1).await
+        );
+        assert_eq!(
+            vec![2],
+            rusty_mutator_set
+                .active_window_segments
+                .get(last_chunk_index)
+                .await
+        );
+        assert!(!rusty_mutator_set.persisted_segments.contains_key(&0));
+    }
+
+    #[tokio::test]
+    async fn active_window_survives_reverting_a_window_slide() {
+        type H = Tip5;
+
+        let db = NeptuneLevelDb::open_new_test_database(false, None, None, None)
+            .await
+            .unwrap();
+        let db_path = db.path().clone();
+        let mut rusty_mutator_set = RustyArchivalMutatorSet::connect(db).await;
+        rusty_mutator_set.restore_or_new().await;
+
+        let mut addition_records = vec![];
+        for _ in 0..=BATCH_SIZE {
+            let (item, sender_randomness, receiver_preimage) = make_item_and_randomnesses();
+            addition_records.push(commit(
+                item,
+                sender_randomness,
+                receiver_preimage.hash::<H>(),
+            ));
+        }
+
+        // Set an index in the first chunk before the window slides past it
+        for addition_record in &addition_records[..BATCH_SIZE as usize] {
+            rusty_mutator_set.ams_mut().add(addition_record).await;
+        }
+        rusty_mutator_set.ams_mut().swbf_active.insert(3);
+        rusty_mutator_set.persist().await;
+        let active_window_before_slide = rusty_mutator_set.ams().swbf_active.clone();
+
+        // Slide, then set an index in the chunk that entered the window. It
+        // shares its slot with the first chunk.
+        let last_addition_record = addition_records.last().unwrap();
+        rusty_mutator_set.ams_mut().add(last_addition_record).await;
+        let entered_index = WINDOW_SIZE - CHUNK_SIZE + 5;
+        rusty_mutator_set
+            .ams_mut()
+            .swbf_active
+            .insert(entered_index);
+        rusty_mutator_set.persist().await;
+
+        // Revert both, as a reorg does, which slides the first chunk back
+        rusty_mutator_set
+            .ams_mut()
+            .swbf_active
+            .remove(entered_index);
+        rusty_mutator_set
+            .ams_mut()
+            .revert_add(last_addition_record)
+            .await;
+        assert_eq!(
+            active_window_before_slide,
+            rusty_mutator_set.ams().swbf_active
+        );
+        rusty_mutator_set.persist().await;
+        drop(rusty_mutator_set);
+
+        let db = NeptuneLevelDb::open_test_database(&db_path, true, None, None, None)
+            .await
+            .unwrap();
+        let mut reloaded_mutator_set = RustyArchivalMutatorSet::connect(db).await;
+        reloaded_mutator_set.restore_or_new().await;
+        assert_eq!(
+            active_window_before_slide,
+            reloaded_mutator_set.ams().swbf_active
+        );
+    }
 }
@@ -2,11 +2,13 @@ use crate::models::blockchain::shared::Hash;
 use crate::prelude::twenty_first;
 
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use tasm_lib::Digest;
 use twenty_first::util_types::algebraic_hasher::AlgebraicHasher;
 use twenty_first::util_types::mmr::mmr_membership_proof::MmrMembershipProof;
 
+use super::chunk::Chunk;
 use super::chunk_dictionary::ChunkDictionary;
 use super::removal_record::RemovalRecord;
 
@@ -25,6 +27,32 @@ pub fn indices_to_hash_map(all_indices: &[u128; NUM_TRIALS as usize]) -> HashMap
         })
 }
 
+/// Tracks how one chunk was modified in a batch update, so that chunk
+/// dictionaries holding the same chunk end up sharing the modified chunk
+/// instead of each holding its own modified copy.
+#[derive(Default)]
+struct UpdatedChunks {
+    // (chunk before modification, chunk after modification)
+    modifications: Vec<(Arc<Chunk>, Arc<Chunk>)>,
+}
+
+impl UpdatedChunks {
+    fn update(&mut self, chunk: &mut Arc<Chunk>, modify: impl Fn(&mut Chunk)) {
+        if let Some((_, modified)) = self
+            .modifications
+            .iter()
+            .find(|(original, _)| *original == *chunk)
+        {
+            *chunk = modified.clone();
+            return;
+        }
+
+        let original = chunk.clone();
+        modify(Arc::make_mut(chunk));
+        self.modifications.push((original, chunk.clone()));
+    }
+}
+
 /// Prepare a batch-modification with necessary authentication data
 /// to update the chunk dictionaries of mutator set membership proofs
 /// under application of a removal record.
@@ -61,15 +89,17 @@ pub fn get_batch_mutation_argument_for_removal_record(
     // contain modified chunks.
     let mut mutated_chunk_dictionaries: HashSet<usize> = HashSet::new();
     for (chunk_index, indices) in removal_record.get_chunkidx_to_indices_dict().iter() {
+        let mut updated_chunks = UpdatedChunks::default();
         for (i, chunk_dictionary) in chunk_dictionaries.iter_mut().enumerate() {
             match chunk_dictionary.dictionary.get_mut(chunk_index) {
                 // Leaf and its MMR-membership proof exists in own MS-membership proof (in `chunk_dictionaries`)
                 Some((mmr_mp, chunk)) => {
-                    for index in indices.iter() {
-                        let relative_index = (index % CHUNK_SIZE as u128) as u32;
-                        mutated_chunk_dictionaries.insert(i);
-                        chunk.insert(relative_index);
-                    }
+                    mutated_chunk_dictionaries.insert(i);
+                    updated_chunks.update(chunk, |chunk| {
+                        for index in indices.iter() {
+                            chunk.insert((index % CHUNK_SIZE as u128) as u32);
+                        }
+                    });
 
                     // If this leaf/membership proof pair has not already been collected,
                     // then store it as a mutation argument. This assumes that all membership
@@ -80,7 +110,7 @@ pub fn get_batch_mutation_argument_for_removal_record(
                     // *old* (non-updated) MMR membership proof.
                     if !batch_modification_hash_map.contains_key(chunk_index) {
                         batch_modification_hash_map
-                            .insert(*chunk_index, (mmr_mp.to_owned(), Hash::hash(chunk)));
+                            .insert(*chunk_index, (mmr_mp.to_owned(), Hash::hash(&**chunk)));
                     }
                 }
 
@@ -98,7 +128,7 @@ pub fn get_batch_mutation_argument_for_removal_record(
                             // mutation argument (2nd element of returned tuple), so we only need to
                             // calculate it once.
                             if !batch_modification_hash_map.contains_key(chunk_index) {
-                                let mut target_chunk = chunk.to_owned();
+                                let mut target_chunk = Chunk::clone(chunk);
                                 for index in indices.iter() {
                                     target_chunk.insert((index % CHUNK_SIZE as u128) as u32);
                                 }
@@ -161,21 +191,23 @@ pub fn prepare_authenticated_batch_modification_for_removal_record_reversion(
     let mut mutated_chunk_dictionaries: HashSet<usize> = HashSet::new();
 
     for (chunk_index, indices) in removal_record.get_chunkidx_to_indices_dict().iter() {
+        let mut updated_chunks = UpdatedChunks::default();
         for (i, chunk_dictionary) in chunk_dictionaries.iter_mut().enumerate() {
             match chunk_dictionary.dictionary.get_mut(chunk_index) {
                 // Leaf and its MMR-membership proof exists in own MS-membership proof (via `chunk_dictionaries`)
                 Some((mmr_mp, chunk)) => {
-                    for index in indices.iter() {
-                        let relative_index = (index % CHUNK_SIZE as u128) as u32;
-                        mutated_chunk_dictionaries.insert(i);
-                        chunk.remove_once(relative_index);
-                    }
+                    mutated_chunk_dictionaries.insert(i);
+                    updated_chunks.update(chunk, |chunk| {
+                        for index in indices.iter() {
+                            chunk.remove_once((index % CHUNK_SIZE as u128) as u32);
+                        }
+                    });
 
                     // Insert into the mutation_argument_hash_map the updated chunk and its
                     // *old* (before reversion) MMR membership proof.
                     if !batch_modification_hash_map.contains_key(chunk_index) {
                         batch_modification_hash_map
-                            .insert(*chunk_index, (mmr_mp.to_owned(), Hash::hash(chunk)));
+                            .insert(*chunk_index, (mmr_mp.to_owned(), Hash::hash(&**chunk)));
                     }
                 }
 
@@ -196,7 +228,7 @@ pub fn prepare_authenticated_batch_modification_for_removal_record_reversion(
                             // mutation argument (2nd element of returned tuple), so we only need to
                             // calculate it once.
                             if !batch_modification_hash_map.contains_key(chunk_index) {
-                                let target_chunk = chunk.to_owned();
+                                let target_chunk = Chunk::clone(chunk);
                                 // for index in indices.iter() {
                                 //     let relative_index = (index % CHUNK_SIZE as u128) as u32;
                                 //     target_chunk.insert(relative_index);
@@ -1,2 +1,187 @@
 #[test]
 fn it_returns_ok() {}
+
+mod two_nodes {
+    use std::future::Future;
+    use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+    use std::path::PathBuf;
+    use std::process::{Child, Command, Stdio};
+    use std::time::Duration;
+
+    use anyhow::{bail, Result};
+    use neptune_core::models::blockchain::type_scripts::neptune_coins::NeptuneCoins;
+    use neptune_core::rpc_server::RPCClient;
+    use num_traits::Zero;
+    use tarpc::tokio_serde::formats::Json;
+    use tarpc::{client, context};
+
+    /// Max time to wait for a node to reach a state, e.g. a block height
+    const WAIT_TIMEOUT: Duration = Duration::from_secs(600);
+
+    /// A `neptune-core` process on RegTest, killed when dropped
+    struct Node {
+        process: Child,
+        data_dir: PathBuf,
+        peer_port: u16,
+        rpc: RPCClient,
+    }
+
+    impl Node {
+        async fn launch(name: &str, extra_args: &[String]) -> Result<Self> {
+            let data_dir = std::env::temp_dir().join(format!(
+                "neptune-integration-{name}-{:016x}",
+                rand::random::<u64>()
+            ));
+            let peer_port = free_port()?;
+            let rpc_port = free_port()?;
+            let process = Command::new(env!("CARGO_BIN_EXE_neptune-core"))
+                .arg("--network=regtest")
+                .arg(format!("--data-dir={}", data_dir.display()))
+                .arg(format!("--peer-port={peer_port}"))
+                .arg(format!("--rpc-port={rpc_port}"))
+                .args(extra_args)
+                .args(deterministic_seed_args(name))
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?;
+
+            // The RPC server is up once the node has loaded its state
+            let rpc_address = SocketAddr::from((Ipv4Addr::LOCALHOST, rpc_port));
+            let transport = wait_for(|| async {
+                Ok(
+                    tarpc::serde_transport::tcp::connect(rpc_address, Json::default)
+                        .await
+                        .ok(),
+                )
+            })
+            .await?;
+            let rpc = RPCClient::new(client::Config::default(), transport).spawn();
+
+            Ok(Self {
+                process,
+                data_dir,
+                peer_port,
+                rpc,
+            })
+        }
+
+        fn peer_address(&self) -> SocketAddr {
+            SocketAddr::from((Ipv4Addr::LOCALHOST, self.peer_port))
+        }
+
+        async fn height(&self) -> Result<u64> {
+            Ok(self.rpc.block_height(context::current()).await?.into())
+        }
+
+        async fn balance(&self) -> Result<NeptuneCoins> {
+            Ok(self.rpc.synced_balance(context::current()).await??)
+        }
+    }
+
+    /// Seed each node's randomness from `NEPTUNE_TEST_SEED` and its name, if
+    /// set, so that a failing run can be replayed
+    #[cfg(feature = "test-determinism")]
+    fn deterministic_seed_args(name: &str) -> Vec<String> {
+        let Ok(seed) = std::env::var("NEPTUNE_TEST_SEED") else {
+            return vec![];
+        };
+        let seed: u64 = seed.parse().expect("NEPTUNE_TEST_SEED must be a u64");
+        let name_hash = name.bytes().fold(0u64, |hash, byte| {
+            hash.wrapping_mul(31).wrapping_add(byte.into())
+        });
+        vec![format!("--deterministic-seed={}", seed ^ name_hash)]
+    }
+
+    #[cfg(not(feature = "test-determinism"))]
+    fn deterministic_seed_args(_name: &str) -> Vec<String> {
+        vec![]
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+            let _ = std::fs::remove_dir_all(&self.data_dir);
+        }
+    }
+
+    fn free_port() -> Result<u16> {
+        Ok(TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?
+            .local_addr()?
+            .port())
+    }
+
+    /// Poll `f` until it returns a value, or fail after [`WAIT_TIMEOUT`]
+    async fn wait_for<T, F, Fut>(mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Option<T>>>,
+    {
+        let started = tokio::time::Instant::now();
+        loop {
+            if let Some(value) = f().await? {
+                return Ok(value);
+            }
+            if started.elapsed() > WAIT_TIMEOUT {
+                bail!("timed out after {WAIT_TIMEOUT:?}");
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+
+    /// Launch a mining node and a node connecting to it, mine on the first,
+    /// sync the second, and send coins from the first wallet to the second.
+    ///
+    /// Slow, as it mines and proves for real, so run explicitly with
+    /// `cargo test --test integration_test -- --ignored`.
+    #[ignore]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn two_nodes_sync_and_transact() -> Result<()> {
+        let miner = Node::launch("miner", &["--mine".to_owned()]).await?;
+        let follower =
+            Node::launch("follower", &[format!("--peers={}", miner.peer_address())]).await?;
+
+        // The miner earns coinbase rewards, and the follower syncs the blocks
+        wait_for(|| async { Ok((miner.height().await? >= 2).then_some(())) }).await?;
+        wait_for(|| async { Ok((!miner.balance().await?.is_zero()).then_some(())) }).await?;
+        wait_for(|| async { Ok((follower.height().await? >= 2).then_some(())) }).await?;
+        assert!(!miner.rpc.peer_info(context::current()).await?.is_empty());
+
+        // The miner pays the follower, and mines the transaction
+        let amount = NeptuneCoins::new(1);
+        let follower_address = follower
+            .rpc
+            .own_receiving_address(context::current())
+            .await??;
+        miner
+            .rpc
+            .send(
+                context::current(),
+                amount,
+                follower_address,
+                NeptuneCoins::new(1),
+                None,
+            )
+            .await??;
+        let follower_balance = wait_for(|| async {
+            let balance = follower.balance().await?;
+            Ok((!balance.is_zero()).then_some(balance))
+        })
+        .await?;
+        assert_eq!(amount, follower_balance);
+
+        // Once mining stops, both nodes agree on the tip
+        miner.rpc.pause_miner(context::current()).await?;
+        wait_for(|| async {
+            let miner_tip = miner.rpc.latest_tip_digests(context::current(), 1).await?;
+            let follower_tip = follower
+                .rpc
+                .latest_tip_digests(context::current(), 1)
+                .await?;
+            Ok((miner_tip == follower_tip).then_some(()))
+        })
+        .await?;
+
+        Ok(())
+    }
+}